@@ -29,22 +29,35 @@ export class Viewport extends React.Component {
     }
 
     renderLocally() {
-
-        // Need to import _bg to get a hold on the wasm memory buffer
-        import ("rendering_wasm/rendering_wasm_bg.wasm").then(module => {
-            if (!this.renderers.has(this.state.sceneId)) {
-                const rend = this.state.module.create_renderer(this.state.sceneId)
-                this.renderers.set(this.state.sceneId, rend)
+        // Render progressively: every animation frame adds one sample per
+        // pixel and redraws the canvas.
+        wasm_promise.then(module => {
+            const sceneId = this.state.sceneId
+            if (this.progressive && this.progressiveSceneId === sceneId) {
+                return
             }
 
-            //const frame = this.state.module.render()
-            const frame = this.renderers.get(this.state.sceneId).render()
-
-            const imageRaw = new Uint8ClampedArray(module.memory.buffer,
-                frame.data(), frame.len())
-            this.state.image = new ImageData(imageRaw, frame.width(), frame.height());
-            const context = this.refs.local_canvas.getContext('2d');
-            context.putImageData(this.state.image, 0, 0);
+            const renderer = module.create_progressive_renderer(sceneId, 64)
+            this.progressive = renderer
+            this.progressiveSceneId = sceneId
+
+            const canvas = this.refs.local_canvas
+            canvas.width = renderer.width()
+            canvas.height = renderer.height()
+            const context = canvas.getContext('2d')
+
+            const step = () => {
+                // A newer renderer (scene change) takes over the canvas.
+                if (this.progressive !== renderer) {
+                    return
+                }
+                const more = renderer.step()
+                renderer.draw(context)
+                if (more) {
+                    requestAnimationFrame(step)
+                }
+            }
+            requestAnimationFrame(step)
         });
 
         return ( <canvas className="viewport" ref="local_canvas"/> );
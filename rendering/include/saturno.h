@@ -0,0 +1,149 @@
+#ifndef SATURNO_H
+#define SATURNO_H
+
+/* Generated with cbindgen, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct Image Image;
+typedef struct Renderer Renderer;
+
+typedef enum SaturnoStatus {
+  SATURNO_STATUS_OK = 0,
+  SATURNO_STATUS_NULL_POINTER = 1,
+  SATURNO_STATUS_INVALID_ARGUMENT = 2,
+  SATURNO_STATUS_BUFFER_TOO_SMALL = 3,
+  SATURNO_STATUS_RENDER_FAILED = 4,
+} SaturnoStatus;
+
+typedef enum SaturnoMaterialKind {
+  SATURNO_MATERIAL_KIND_LAMBERTIAN = 0,
+  SATURNO_MATERIAL_KIND_METAL = 1,
+  SATURNO_MATERIAL_KIND_DIELECTRIC = 2,
+  SATURNO_MATERIAL_KIND_DIFFUSE_LIGHT = 3,
+} SaturnoMaterialKind;
+
+/*
+ * Opaque scene handle of the C API.
+ */
+typedef struct SaturnoScene SaturnoScene;
+
+typedef Image Frame;
+
+typedef struct SaturnoVec3 {
+  double x;
+  double y;
+  double z;
+} SaturnoVec3;
+
+/*
+ * `color` is the albedo (or the emitted color of lights), in [0, 1].
+ * `parameter` is the fuzz of metals, the refraction index of dielectrics
+ * and the intensity of lights; lambertians ignore it.
+ */
+typedef struct SaturnoMaterial {
+  enum SaturnoMaterialKind kind;
+  struct SaturnoVec3 color;
+  double parameter;
+} SaturnoMaterial;
+
+/*
+ * Camera placement, the resolution is given when rendering.
+ */
+typedef struct SaturnoCamera {
+  struct SaturnoVec3 look_from;
+  struct SaturnoVec3 look_at;
+  struct SaturnoVec3 up;
+  double vertical_fov;
+  double aperture;
+} SaturnoCamera;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+Renderer *get_renderer(uint32_t scene_id);
+
+/*
+ * Returns a null pointer if the scene can not be rendered.
+ *
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Renderer>` handed out by this
+ * module.
+ */
+Frame *render_scene(Renderer *ptr);
+
+/*
+ * This function recreates the entire renderer on every
+ * frame. Returns a null pointer if the scene can not be rendered.
+ */
+Frame *get_frame(void);
+
+/*
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
+uint32_t get_width(Frame *ptr);
+
+/*
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
+uint32_t get_height(Frame *ptr);
+
+/*
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
+uint8_t get_value(Frame *ptr, uint32_t x, uint32_t y, uint32_t c);
+
+/*
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
+const uint8_t *get_data(Frame *ptr);
+
+/*
+ * Create an empty scene, with the camera at the origin looking down -z.
+ * Release it with `saturno_scene_free`.
+ */
+struct SaturnoScene *saturno_scene_new(void);
+
+void saturno_scene_free(struct SaturnoScene *scene);
+
+enum SaturnoStatus saturno_scene_add_sphere(struct SaturnoScene *scene,
+                                            struct SaturnoVec3 center,
+                                            double radius,
+                                            struct SaturnoMaterial material);
+
+enum SaturnoStatus saturno_scene_set_camera(struct SaturnoScene *scene,
+                                            struct SaturnoCamera camera);
+
+/*
+ * Render the scene into `buffer`: `width * height` RGBA pixels of 8 bits
+ * per channel, in scanlines from the top.
+ *
+ * # Safety
+ *
+ * `buffer` must point to `buffer_len` writable bytes.
+ */
+enum SaturnoStatus saturno_scene_render(struct SaturnoScene *scene,
+                                        uint32_t width,
+                                        uint32_t height,
+                                        uint32_t samples,
+                                        uint8_t *buffer,
+                                        uintptr_t buffer_len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SATURNO_H */
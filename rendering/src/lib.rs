@@ -1,20 +1,40 @@
 pub mod raytracer;
 
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push)]
 mod tests {
     use crate::raytracer::actor::RayTraceable;
     use crate::raytracer::actor::Sphere;
+    use crate::raytracer::aov;
+    use crate::raytracer::aov::IdKind;
     use crate::raytracer::camera::Camera;
+    use crate::raytracer::canvas::develop;
     use crate::raytracer::canvas::Canvas;
+    use crate::raytracer::common::consts;
+    use crate::raytracer::common::Float;
     use crate::raytracer::common::Ray;
     use crate::raytracer::common_testing::init_image_testing;
+    use crate::raytracer::error::SaturnoError;
     use crate::raytracer::material::Lambertian;
     use crate::raytracer::material::Primary;
     use crate::raytracer::material::Metal;
     use crate::raytracer::material::Dielectric;
+    use crate::raytracer::material::DiffuseLight;
     use crate::raytracer::material::Shading;
+    use crate::raytracer::material::ShadowCatcher;
+    use crate::raytracer::output;
+    use crate::raytracer::progressive::Progressive;
     use crate::raytracer::scenes;
+    use crate::raytracer::settings::Crop;
+    use crate::raytracer::settings::Filter;
+    use crate::raytracer::settings::RenderSettings;
+    use crate::raytracer::settings::ThreadPriority;
+    use crate::raytracer::settings::Tonemapper;
+    use crate::raytracer::HdrImage;
     use ndarray::arr1;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::sync::Arc;
 
     extern crate image;
 
@@ -23,6 +43,8 @@ mod tests {
         let ray = Ray {
             origin: arr1(&[0.5, 0.6, 0.7, 1.0]),
             direction: arr1(&[1.0, 1.0, 1.0, 0.0]),
+            differentials: None,
+            time: 0.0,
         };
 
         assert_eq!(ray.origin[2], 0.7);
@@ -49,9 +71,14 @@ mod tests {
             arr1(&[0.0, 1.0, 0.0, 0.0]),
             0.0,
         );
-        let canvas = Canvas::new(dims[0], dims[1], vec![], 1, camera);
+        let canvas = Canvas::new(vec![], camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(1)
+            .build()
+            .unwrap();
 
-        let image = canvas.render_scene();
+        let image = canvas.render_scene(&settings).unwrap();
         //image.print();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
@@ -68,7 +95,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Primary::new(
+            material: Arc::new(Primary::new(
                 arr1(&[1.0, 0.0, 0.0, 1.0]),
                 Shading::COLOR,
             )),
@@ -84,8 +111,13 @@ mod tests {
             arr1(&[0.0, 1.0, 0.0, 0.0]),
             0.0,
         );
-        let canvas = Canvas::new(dims[0], dims[1], actors, 1, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(1)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -101,7 +133,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Primary::new(
+            material: Arc::new(Primary::new(
                 arr1(&[1.0, 0.0, 0.0, 1.0]),
                 Shading::NORMALS,
             )),
@@ -117,8 +149,13 @@ mod tests {
             arr1(&[0.0, 1.0, 0.0, 0.0]),
             0.0,
         );
-        let canvas = Canvas::new(dims[0], dims[1], actors, 1, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(1)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -134,7 +171,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Primary::new(
+            material: Arc::new(Primary::new(
                 arr1(&[1.0, 0.0, 0.0, 1.0]),
                 Shading::NORMALS,
             )),
@@ -143,7 +180,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, -100.5, -1.0, 1.0]),
             radius: 100.0,
-            material: Box::new(Primary::new(
+            material: Arc::new(Primary::new(
                 arr1(&[0.5, 0.5, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -159,8 +196,13 @@ mod tests {
             arr1(&[0.0, 1.0, 0.0, 0.0]),
             0.0,
         );
-        let canvas = Canvas::new(dims[0], dims[1], actors, 50, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(50)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -176,7 +218,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[1.0, 0.0, 0.0, 1.0]),
                 Shading::NORMALS,
             )),
@@ -185,7 +227,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, -100.5, -1.0, 1.0]),
             radius: 100.0,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[1.0, 0.0, 0.0, 1.0]),
                 Shading::COLOR,
             )),
@@ -201,8 +243,13 @@ mod tests {
             arr1(&[0.0, 1.0, 0.0, 0.0]),
             0.0,
         );
-        let canvas = Canvas::new(dims[0], dims[1], actors, 100, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(100)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -219,7 +266,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, -100.5, -1.0, 1.0]),
             radius: 100.0,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.6, 0.6, 0.6, 1.0]),
                 Shading::COLOR,
             )),
@@ -228,7 +275,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -237,7 +284,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[1.0, 0.4, 0.4, 1.0]),
                 Shading::COLOR,
             )),
@@ -246,7 +293,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.8, 0.2, 1.0]),
                 Shading::COLOR,
             )),
@@ -263,8 +310,13 @@ mod tests {
             0.0,
         );
 
-        let canvas = Canvas::new(dims[0], dims[1], actors, 50, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(50)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -276,13 +328,13 @@ mod tests {
         let mut output_path = init_image_testing();
         output_path.push("camera_fov.png");
 
-        let rad = std::f64::consts::PI / 4.0;
+        let rad = consts::PI / 4.0;
 
         let mut actors = vec![];
         actors.push(Box::new(Sphere {
             center: arr1(&[-rad, 0.0, -1.0, 1.0]),
             radius: rad,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.0, 0.0, 1.0, 1.0]),
                 Shading::COLOR,
             )),
@@ -291,7 +343,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[rad, 0.0, -1.0, 1.0]),
             radius: rad,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[1.0, 0.0, 0.0, 1.0]),
                 Shading::COLOR,
             )),
@@ -307,14 +359,58 @@ mod tests {
             arr1(&[0.0, 1.0, 0.0, 0.0]),
             0.0,
         );
-        let canvas = Canvas::new(dims[0], dims[1], actors, 50, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(50)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
         assert_eq!(1.0, 1.0);
     }
 
+    #[test]
+    fn camera_navigation() {
+        use saturno::scene::description::CameraDescription;
+
+        let close = |a: [Float; 3], b: [Float; 3]| {
+            a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-4)
+        };
+        let mut camera = CameraDescription {
+            look_from: [0.0, 0.0, 2.0],
+            look_at: [0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0],
+            vertical_fov: 90.0,
+            aperture: 0.0,
+            shutter: [0.0, 0.0],
+        };
+
+        // A quarter turn around the vertical axis keeps the distance.
+        camera.orbit(consts::FRAC_PI_2, 0.0);
+        assert!(close(camera.look_from, [2.0, 0.0, 0.0]));
+        assert!((camera.distance() - 2.0).abs() < 1e-4);
+
+        // The pitch stops short of the pole.
+        camera.orbit(0.0, consts::PI);
+        assert!(camera.look_from[1] < 2.0);
+        assert!(camera.look_from[0] > 0.0);
+        camera.orbit(0.0, -consts::PI);
+        assert!(camera.look_from[1] > -2.0);
+
+        // Panning moves the camera and its focus together.
+        camera.look_from = [2.0, 0.0, 0.0];
+        camera.pan(1.0, 0.5);
+        assert!(close(camera.look_at, [0.0, 0.5, -1.0]));
+        assert!(close(camera.look_from, [2.0, 0.5, -1.0]));
+
+        camera.dolly(0.5);
+        assert!((camera.distance() - 1.0).abs() < 1e-4);
+        assert!(close(camera.look_at, [0.0, 0.5, -1.0]));
+    }
+
     #[test]
     fn render_metal() {
         let mut output_path = init_image_testing();
@@ -325,7 +421,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, -100.5, -1.0, 1.0]),
             radius: 100.0,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.6, 0.6, 0.6, 1.0]),
                 Shading::COLOR,
             )),
@@ -334,7 +430,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -343,7 +439,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Metal::new(
+            material: Arc::new(Metal::new(
                 arr1(&[1.0, 0.4, 0.4, 1.0]),
                 Shading::COLOR,
                 0.8,
@@ -353,7 +449,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Metal::new(
+            material: Arc::new(Metal::new(
                 arr1(&[0.8, 0.8, 0.8, 1.0]),
                 Shading::COLOR,
                 0.0,
@@ -371,8 +467,13 @@ mod tests {
             0.0,
         );
 
-        let canvas = Canvas::new(dims[0], dims[1], actors, 50, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(50)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -389,7 +490,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, -100.5, -1.0, 1.0]),
             radius: 100.0,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.8, 0.8, 0.0, 1.0]),
                 Shading::COLOR,
             )),
@@ -398,7 +499,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -407,7 +508,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -416,7 +517,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Metal::new(
+            material: Arc::new(Metal::new(
                 arr1(&[0.8, 0.6, 0.2, 1.0]),
                 Shading::COLOR,
                 0.0,
@@ -426,7 +527,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Dielectric::new(
+            material: Arc::new(Dielectric::new(
                 arr1(&[1.0, 1.0, 1.0, 0.0]),
                 Shading::COLOR,
                 1.5,
@@ -444,8 +545,13 @@ mod tests {
             0.0,
         );
 
-        let canvas = Canvas::new(dims[0], dims[1], actors, 100, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(100)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -462,7 +568,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, -100.5, -1.0, 1.0]),
             radius: 100.0,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.8, 0.8, 0.0, 1.0]),
                 Shading::COLOR,
             )),
@@ -471,7 +577,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -480,7 +586,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -489,7 +595,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Metal::new(
+            material: Arc::new(Metal::new(
                 arr1(&[0.8, 0.6, 0.2, 1.0]),
                 Shading::COLOR,
                 0.0,
@@ -499,7 +605,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Dielectric::new(
+            material: Arc::new(Dielectric::new(
                 arr1(&[1.0, 1.0, 1.0, 0.0]),
                 Shading::COLOR,
                 1.5,
@@ -509,7 +615,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
             radius: -0.45,
-            material: Box::new(Dielectric::new(
+            material: Arc::new(Dielectric::new(
                 arr1(&[1.0, 1.0, 1.0, 0.0]),
                 Shading::COLOR,
                 1.5,
@@ -527,8 +633,13 @@ mod tests {
             0.0,
         );
 
-        let canvas = Canvas::new(dims[0], dims[1], actors, 100, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(100)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -545,7 +656,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, -100.5, -1.0, 1.0]),
             radius: 100.0,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.8, 0.8, 0.0, 1.0]),
                 Shading::COLOR,
             )),
@@ -554,7 +665,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -563,7 +674,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[0.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Lambertian::new(
+            material: Arc::new(Lambertian::new(
                 arr1(&[0.1, 0.2, 0.5, 1.0]),
                 Shading::COLOR,
             )),
@@ -572,7 +683,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Metal::new(
+            material: Arc::new(Metal::new(
                 arr1(&[0.8, 0.6, 0.2, 1.0]),
                 Shading::COLOR,
                 0.0,
@@ -582,7 +693,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
             radius: 0.5,
-            material: Box::new(Dielectric::new(
+            material: Arc::new(Dielectric::new(
                 arr1(&[1.0, 1.0, 1.0, 0.0]),
                 Shading::COLOR,
                 1.5,
@@ -592,7 +703,7 @@ mod tests {
         actors.push(Box::new(Sphere {
             center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
             radius: -0.45,
-            material: Box::new(Dielectric::new(
+            material: Arc::new(Dielectric::new(
                 arr1(&[1.0, 1.0, 1.0, 0.0]),
                 Shading::COLOR,
                 1.5,
@@ -610,8 +721,13 @@ mod tests {
             2.0,
         );
 
-        let canvas = Canvas::new(dims[0], dims[1], actors, 100, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(100)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
@@ -637,11 +753,7734 @@ mod tests {
             0.2,
         );
 
-        let canvas = Canvas::new(dims[0], dims[1], actors, 10, camera);
-        let image = canvas.render_scene();
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(10)
+            .build()
+            .unwrap();
+        let image = canvas.render_scene(&settings).unwrap();
         let image_png =
             image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
         let _result = image_png.save(output_path);
         assert_eq!(1.0, 1.0);
     }
+
+    #[test]
+    fn rgbe_round_trip() {
+        let color = [0.25, 1.5, 1000.0];
+        let decoded = output::decode_rgbe(output::encode_rgbe(color));
+
+        for c in 0..3 {
+            assert!((decoded[c] - color[c]).abs() <= color[2] / 128.0);
+        }
+        assert_eq!(output::encode_rgbe([0.0, 0.0, 0.0]), [0, 0, 0, 0]);
+
+        let infinite = output::encode_rgbe([Float::INFINITY, 1.0, 0.0]);
+        assert_eq!((infinite[0], infinite[3]), (255, 255));
+        assert!(output::decode_rgbe(infinite)[0].is_finite());
+    }
+
+    #[test]
+    fn render_hdr_output() {
+        let mut output_path = init_image_testing();
+        output_path.push("render_hdr_output.hdr");
+
+        let dims: [u32; 2] = [40, 20];
+        let camera = Camera::new(
+            90.0,
+            dims[0],
+            dims[1],
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(vec![], camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(1)
+            .build()
+            .unwrap();
+
+        let hdr = canvas.render_hdr(&settings).unwrap();
+        output::write_hdr(&output_path, &hdr).unwrap();
+
+        let reader = BufReader::new(File::open(&output_path).unwrap());
+        let decoder = image::hdr::HDRDecoder::new(reader).unwrap();
+        let metadata = decoder.metadata();
+        assert_eq!(metadata.width, dims[0]);
+        assert_eq!(metadata.height, dims[1]);
+
+        let pixels = decoder.read_image_hdr().unwrap();
+        for (i, pixel) in pixels.iter().enumerate() {
+            let expected = hdr.get_pixel(i);
+            for c in 0..3 {
+                let value = pixel[c] as Float;
+                assert!((value - expected[c]).abs() < 0.01);
+            }
+        }
+    }
+
+    #[test]
+    fn render_transparent_background() {
+        let mut output_path = init_image_testing();
+        output_path.push("render_transparent_background.png");
+
+        let actors = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.1, 0.2, 0.5, 1.0]),
+                Shading::COLOR,
+            )),
+        }) as Box<dyn RayTraceable>];
+
+        let dims: [u32; 2] = [200, 100];
+        let camera = Camera::new(
+            90.0,
+            dims[0],
+            dims[1],
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(4)
+            .transparent_background(true)
+            .build()
+            .unwrap();
+
+        let image = canvas.render_scene(&settings).unwrap();
+        assert_eq!(image.get_value(0, 0, 3), 0);
+        assert_eq!(image.get_value(100, 50, 3), 255);
+
+        let image_png =
+            image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
+        let _result = image_png.save(output_path);
+    }
+
+    #[test]
+    fn render_shadow_catcher() {
+        let mut output_path = init_image_testing();
+        output_path.push("render_shadow_catcher.png");
+
+        let actors = vec![
+            Box::new(Sphere {
+                center: arr1(&[0.0, -100.5, -1.0, 1.0]),
+                radius: 100.0,
+                material: Arc::new(ShadowCatcher::new(1.0)),
+            }) as Box<dyn RayTraceable>,
+            Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.1, 0.2, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>,
+        ];
+
+        let dims: [u32; 2] = [200, 100];
+        let camera = Camera::new(
+            90.0,
+            dims[0],
+            dims[1],
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(20)
+            .transparent_background(true)
+            .build()
+            .unwrap();
+
+        let image = canvas.render_scene(&settings).unwrap();
+
+        // Sky and unshadowed ground far away are see-through, the ground
+        // under the sphere is not.
+        assert_eq!(image.get_value(0, 0, 3), 0);
+        assert!(image.get_value(100, 99, 3) > 0);
+        assert_eq!(image.get_value(100, 99, 0), 0);
+
+        let image_png =
+            image::RgbaImage::from_raw(dims[0], dims[1], image.data).unwrap();
+        let _result = image_png.save(output_path);
+    }
+
+    #[test]
+    fn murmur3_hash() {
+        // Reference values of MurmurHash3_x86_32 (seed 0).
+        assert_eq!(aov::murmur3_32(b"", 0), 0);
+        assert_eq!(aov::murmur3_32(b"hello", 0), 0x248b_fa47);
+        assert_eq!(aov::murmur3_32(b"hello, world", 0), 0x149b_bb7f);
+    }
+
+    #[test]
+    fn render_id_passes() {
+        let mut output_path = init_image_testing();
+        output_path.push("render_id_passes.exr");
+
+        let actors = vec![
+            Box::new(Sphere {
+                center: arr1(&[-0.5, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.1, 0.2, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>,
+            Box::new(Sphere {
+                center: arr1(&[0.5, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.1, 0.2, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>,
+        ];
+
+        let dims: [u32; 2] = [40, 20];
+        let camera = Camera::new(
+            90.0,
+            dims[0],
+            dims[1],
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(8)
+            .build()
+            .unwrap();
+
+        // Both spheres share the material, but not the object ID.
+        let (objects, materials) = canvas.render_ids(&settings).unwrap();
+        assert_eq!(objects.get_value(0, 0), 0);
+        assert_eq!(objects.get_value(10, 10), 1);
+        assert_eq!(objects.get_value(30, 10), 2);
+        assert_eq!(materials.get_value(10, 10), materials.get_value(30, 10));
+        assert_ne!(materials.get_value(10, 10), 0);
+
+        let cryptomatte = canvas
+            .render_cryptomatte(&settings, IdKind::OBJECT, 6)
+            .unwrap();
+        assert_eq!(cryptomatte.manifest.len(), 2);
+        output::write_cryptomatte(&output_path, &cryptomatte).unwrap();
+
+        use exr::prelude::*;
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .all_layers()
+            .all_attributes()
+            .from_file(&output_path)
+            .unwrap();
+
+        let layer = &image.layer_data[0];
+        assert_eq!(layer.channel_data.list.len(), 12);
+        let manifest = format!("cryptomatte/{}/manifest", cryptomatte.key());
+        assert!(layer
+            .attributes
+            .other
+            .contains_key(&Text::from(manifest.as_str())));
+
+        // Coverage of the top ranked ID.
+        let coverage = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|c| c.name.eq("CryptoObject00.G"))
+            .unwrap();
+        let values: Vec<f32> = coverage.sample_data.values_as_f32().collect();
+        assert_eq!(values[0], 0.0);
+        assert_eq!(values[(10 * dims[0] + 10) as usize], 1.0);
+    }
+
+    #[test]
+    fn cryptomatte_manifest() {
+        let mut cryptomatte = aov::Cryptomatte::new(IdKind::OBJECT, 1, 1, 6);
+        cryptomatte.manifest.insert("ball".to_string(), 1);
+        cryptomatte
+            .manifest
+            .insert("say \"cheese\"\\\n".to_string(), 0xabcdef);
+
+        // The names come back as they were, quotes and all.
+        let manifest: std::collections::BTreeMap<String, String> =
+            serde_json::from_str(&cryptomatte.manifest_json()).unwrap();
+        assert_eq!(manifest["ball"], "00000001");
+        assert_eq!(manifest["say \"cheese\"\\\n"], "00abcdef");
+    }
+
+    #[test]
+    fn render_light_groups() {
+        let mut key = DiffuseLight::new(arr1(&[1.0, 0.9, 0.8, 1.0]), 4.0);
+        key.light_group = Some("key".to_string());
+        let mut fill = DiffuseLight::new(arr1(&[0.2, 0.3, 1.0, 1.0]), 2.0);
+        fill.light_group = Some("fill".to_string());
+
+        let actors = vec![
+            Box::new(Sphere {
+                center: arr1(&[0.0, -100.5, -1.0, 1.0]),
+                radius: 100.0,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.8, 0.8, 0.8, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>,
+            Box::new(Sphere {
+                center: arr1(&[-1.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(key),
+            }) as Box<dyn RayTraceable>,
+            Box::new(Sphere {
+                center: arr1(&[1.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(fill),
+            }) as Box<dyn RayTraceable>,
+        ];
+
+        let dims: [u32; 2] = [40, 20];
+        let camera = Camera::new(
+            90.0,
+            dims[0],
+            dims[1],
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(actors, camera);
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(8)
+            .build()
+            .unwrap();
+
+        let (beauty, groups) = canvas.render_light_groups(&settings).unwrap();
+        let names: Vec<&String> = groups.keys().collect();
+        assert_eq!(names, vec!["background", "fill", "key"]);
+
+        // The light group AOVs add up to the beauty pass.
+        for i in 0..beauty.size() {
+            let expected = beauty.get_pixel(i);
+            for (c, value) in expected.iter().take(3).enumerate() {
+                let sum: Float =
+                    groups.values().map(|group| group.get_pixel(i)[c]).sum();
+                assert!((sum - value).abs() < 1e-6 * value.max(1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn render_deterministic() {
+        let render = |seed: u64, threads: usize, crop: Option<Crop>| {
+            let actors = vec![
+                Box::new(Sphere {
+                    center: arr1(&[0.0, -100.5, -1.0, 1.0]),
+                    radius: 100.0,
+                    material: Arc::new(Lambertian::new(
+                        arr1(&[0.8, 0.8, 0.0, 1.0]),
+                        Shading::COLOR,
+                    )),
+                }) as Box<dyn RayTraceable>,
+                Box::new(Sphere {
+                    center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                    radius: 0.5,
+                    material: Arc::new(Dielectric::new(
+                        arr1(&[1.0, 1.0, 1.0, 0.0]),
+                        Shading::COLOR,
+                        1.5,
+                    )),
+                }) as Box<dyn RayTraceable>,
+            ];
+
+            let dims: [u32; 2] = [40, 20];
+            let camera = Camera::new(
+                40.0,
+                dims[0],
+                dims[1],
+                arr1(&[0.0, 0.5, 1.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 1.0]),
+                arr1(&[0.0, 1.0, 0.0, 0.0]),
+                0.1,
+            );
+            let canvas = Canvas::new(actors, camera);
+            let mut settings = RenderSettings::builder()
+                .size(dims[0], dims[1])
+                .samples(8)
+                .threads(threads)
+                .seed(seed)
+                .build()
+                .unwrap();
+            settings.crop = crop;
+            canvas.render_hdr(&settings).unwrap()
+        };
+
+        let image = render(7, 1, None);
+        assert_eq!(image.data, render(7, 1, None).data);
+        assert_eq!(image.data, render(7, 3, None).data);
+        assert_ne!(image.data, render(8, 1, None).data);
+
+        // A crop renders exactly like the same region of the full frame.
+        let crop = Crop {
+            x: 10,
+            y: 5,
+            width: 8,
+            height: 4,
+        };
+        let cropped = render(7, 2, Some(crop));
+        assert_eq!(cropped.width, 8);
+        assert_eq!(cropped.height, 4);
+        for i in 0..cropped.size() {
+            let (x, y) = cropped.get_pixel_coordinate(i);
+            let j = ((y + crop.y) * image.width + x + crop.x) as usize;
+            assert_eq!(cropped.get_pixel(i), image.get_pixel(j));
+        }
+    }
+
+    #[test]
+    fn render_settings() {
+        let settings = RenderSettings::builder()
+            .size(640, 480)
+            .samples(16)
+            .filter(Filter::Gaussian { sigma: 0.5 })
+            .tonemapper(Tonemapper::Aces)
+            .seed(42)
+            .build()
+            .unwrap();
+
+        let json = serde_json::to_string(&settings).unwrap();
+        let parsed: RenderSettings = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, settings);
+
+        // Missing fields take their default value.
+        let parsed: RenderSettings =
+            serde_json::from_str(r#"{"width": 320, "samples": 4}"#).unwrap();
+        assert_eq!(parsed.width, 320);
+        assert_eq!(parsed.height, RenderSettings::default().height);
+
+        let crop = Crop {
+            x: 600,
+            y: 0,
+            width: 100,
+            height: 100,
+        };
+        match RenderSettings::builder().size(640, 480).crop(crop).build() {
+            Err(SaturnoError::InvalidSettings(_)) => {}
+            _ => panic!("crop windows must lie within the image"),
+        }
+
+        // One stop of exposure doubles the radiance before tone mapping.
+        let mut hdr = HdrImage::new(1, 1, 4);
+        hdr.set_pixel(0, [0.1, 0.2, 0.3, 1.0]);
+        let mut doubled = HdrImage::new(1, 1, 4);
+        doubled.set_pixel(0, [0.2, 0.4, 0.6, 1.0]);
+        let exposed = RenderSettings::builder().exposure(1.0).build().unwrap();
+        assert_eq!(
+            develop(&hdr, &exposed).data,
+            develop(&doubled, &RenderSettings::default()).data
+        );
+        assert!(RenderSettings::builder()
+            .exposure(Float::NAN)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn render_threads() {
+        let cores = std::thread::available_parallelism().unwrap().get();
+        let settings = RenderSettings::default();
+        assert_eq!(settings.thread_count(), (cores - 1).max(1));
+
+        let all = RenderSettings {
+            reserved_cores: 0,
+            ..RenderSettings::default()
+        };
+        assert_eq!(all.thread_count(), cores);
+        let none = RenderSettings {
+            reserved_cores: cores + 1,
+            ..RenderSettings::default()
+        };
+        assert_eq!(none.thread_count(), 1);
+        let explicit = RenderSettings {
+            threads: 3,
+            reserved_cores: cores,
+            ..RenderSettings::default()
+        };
+        assert_eq!(explicit.thread_count(), 3);
+
+        let parsed: RenderSettings =
+            serde_json::from_str(r#"{"priority": "low"}"#).unwrap();
+        assert_eq!(parsed.priority, ThreadPriority::Low);
+
+        // Low priority renders on worker threads, even a single one, and
+        // gives the same image.
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(20, 10),
+        );
+        let render = |threads: usize, priority: ThreadPriority| {
+            let settings = RenderSettings::builder()
+                .size(20, 10)
+                .seed(9)
+                .threads(threads)
+                .priority(priority)
+                .build()
+                .unwrap();
+            canvas.render_scene(&settings).unwrap().data
+        };
+        let expected = render(1, ThreadPriority::Normal);
+        assert_eq!(render(1, ThreadPriority::Low), expected);
+        assert_eq!(render(2, ThreadPriority::Low), expected);
+    }
+
+    #[test]
+    fn render_errors() {
+        let camera = || {
+            Camera::new(
+                90.0,
+                20,
+                10,
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 1.0]),
+                arr1(&[0.0, 1.0, 0.0, 0.0]),
+                0.0,
+            )
+        };
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(1)
+            .build()
+            .unwrap();
+
+        let canvas = Canvas::new(vec![], camera());
+        let no_samples = RenderSettings {
+            samples: 0,
+            ..settings.clone()
+        };
+        match canvas.render_scene(&no_samples) {
+            Err(SaturnoError::InvalidSettings(_)) => {}
+            _ => panic!("zero samples per pixel should not render"),
+        }
+
+        let wrong_size = RenderSettings {
+            width: 40,
+            ..settings.clone()
+        };
+        match canvas.render_scene(&wrong_size) {
+            Err(SaturnoError::InvalidSettings(_)) => {}
+            _ => panic!("the image size must match the camera"),
+        }
+
+        let actors = vec![
+            Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.1, 0.2, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>,
+            Box::new(Sphere {
+                center: arr1(&[Float::NAN, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.1, 0.2, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>,
+        ];
+        let canvas = Canvas::new(actors, camera());
+        match canvas.render_hdr(&settings) {
+            Err(SaturnoError::InvalidActor { actor, .. }) => {
+                assert_eq!(actor, "actor1")
+            }
+            _ => panic!("a sphere with a NaN center should not render"),
+        }
+
+        let mut output_path = init_image_testing();
+        output_path.push("missing_directory/render_errors.hdr");
+        let hdr = Canvas::new(vec![], camera()).render_hdr(&settings).unwrap();
+        let error = output::write_hdr(&output_path, &hdr).unwrap_err();
+        assert!(error.to_string().contains("render_errors.hdr"));
+    }
+
+    #[test]
+    fn render_pixel_trace() {
+        use saturno::render::debug::BounceEvent;
+
+        let canvas = Canvas::new(
+            scenes::random_book_cover(),
+            scenes::book_cover_camera(20, 10),
+        );
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(4)
+            .seed(21)
+            .build()
+            .unwrap();
+        let rect = Crop {
+            x: 8,
+            y: 6,
+            width: 2,
+            height: 1,
+        };
+
+        // With a seed, the traced samples are those of the render.
+        let traces = canvas.trace_pixels(&settings, rect).unwrap();
+        let hdr = canvas.render_hdr(&settings).unwrap();
+        assert_eq!(traces.len(), 2);
+        for trace in &traces {
+            let index = (trace.y * 20 + trace.x) as usize;
+            assert_eq!(trace.color, hdr.get_pixel(index));
+            assert_eq!(trace.samples.len(), 4);
+
+            for sample in &trace.samples {
+                let (last, scattered) = sample.bounces.split_last().unwrap();
+                assert_eq!(sample.bounces[0].depth, 1);
+                assert!(last.radiance.is_some());
+                assert!(scattered
+                    .iter()
+                    .all(|bounce| bounce.event == BounceEvent::Scattered));
+
+                // The throughput is the product of the attenuations.
+                for pair in sample.bounces.windows(2) {
+                    let attenuation = pair[0].attenuation.unwrap();
+                    let throughputs = pair[0]
+                        .throughput
+                        .iter()
+                        .zip(attenuation.iter().zip(pair[1].throughput.iter()));
+                    for (before, (attenuation, after)) in throughputs {
+                        assert!((after - before * attenuation).abs() < 1e-6);
+                    }
+                }
+            }
+        }
+
+        let outside = Crop { x: 19, ..rect };
+        assert!(canvas.trace_pixels(&settings, outside).is_err());
+    }
+
+    #[test]
+    fn render_path_export() {
+        use saturno::io::paths;
+
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(20, 10),
+        );
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(3)
+            .seed(4)
+            .build()
+            .unwrap();
+        let rect = Crop {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        let traces = canvas.trace_pixels(&settings, rect).unwrap();
+
+        // The corner pixel sees the sky: camera, then 2 units out.
+        let lines = paths::path_lines(&traces, 2.0);
+        assert_eq!(lines.len(), 3);
+        let line = &lines[0];
+        assert_eq!(line.name, "pixel_0_0_sample_0");
+        assert_eq!(line.points.len(), line.events.len() + 1);
+        let (from, to) = (line.points[0], line.points[1]);
+        let length = (0..3).map(|c| (to[c] - from[c]).powi(2)).sum::<Float>();
+        assert!((length.sqrt() - 2.0).abs() < 1e-4);
+
+        let mut obj = vec![];
+        paths::encode_paths_obj(&mut obj, &lines).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!(obj.lines().filter(|l| l.starts_with("l ")).count(), 3);
+        assert!(obj.contains("o pixel_0_0_sample_2"));
+
+        let mut ply = vec![];
+        paths::encode_paths_ply(&mut ply, &lines).unwrap();
+        let ply = String::from_utf8(ply).unwrap();
+        let vertices: usize = lines.iter().map(|line| line.points.len()).sum();
+        assert!(ply.contains(&format!("element vertex {}\n", vertices)));
+        assert!(ply.contains(&format!("element edge {}\n", vertices - 3)));
+        assert_eq!(ply.lines().count(), 13 + vertices + vertices - 3);
+    }
+
+    #[test]
+    fn render_cost_heatmap() {
+        use saturno::render::aov::CostKind;
+
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(20, 10),
+        );
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(2)
+            .seed(8)
+            .build()
+            .unwrap();
+        let cost = canvas.render_cost(&settings).unwrap();
+
+        // Without an accelerator every ray tests both spheres.
+        for (rays, tests) in cost.rays.iter().zip(cost.tests.iter()) {
+            assert!(*rays >= 2);
+            assert_eq!(*tests, 2 * rays);
+        }
+        // Sky pixels trace one ray per sample, the spheres bounce.
+        assert_eq!(cost.rays[0], 2);
+        assert!(cost.rays[5 * 20 + 10] > 2);
+
+        let heatmap = cost.heatmap(CostKind::Rays);
+        assert_eq!((heatmap.width, heatmap.height), (20, 10));
+        let max = (0..cost.rays.len()).max_by_key(|i| cost.rays[*i]).unwrap();
+        assert_eq!(&heatmap.data[max * 4..max * 4 + 4], &[255, 0, 0, 255]);
+
+        // A mesh costs the nodes and triangles its rays go through, not a
+        // test as an actor.
+        use saturno::scene::mesh::Mesh;
+        let cells = 16;
+        let mut positions = vec![];
+        for j in 0..=cells {
+            for i in 0..=cells {
+                let (u, v) = (i as Float / 16.0, j as Float / 16.0);
+                positions.push([0.5 * u - 0.25, 0.5 * v - 0.25, -1.0]);
+            }
+        }
+        let mut triangles = vec![];
+        for j in 0..cells {
+            for i in 0..cells {
+                let corner = j * (cells + 1) + i;
+                let next = corner + cells + 1;
+                triangles.push([corner, corner + 1, next]);
+                triangles.push([corner + 1, next + 1, next]);
+            }
+        }
+        let uvs = vec![[0.0, 0.0]; positions.len()];
+        let black = Arc::new(Lambertian::new(
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            Shading::COLOR,
+        ));
+        let mesh = Mesh::new(positions, uvs, triangles, black).unwrap();
+        let canvas = Canvas::new(
+            vec![Box::new(mesh) as Box<dyn RayTraceable>],
+            scenes::two_spheres_camera(20, 10),
+        );
+        let cost = canvas.render_cost(&settings).unwrap();
+        let (sky, mesh) = (0, 5 * 20 + 10);
+        assert_eq!((cost.nodes[sky], cost.tests[sky]), (2, 2));
+        assert!(cost.nodes[mesh] > cost.nodes[sky]);
+        assert!(cost.tests[mesh] > 2 * cost.tests[sky]);
+        // The hottest pixel is on the mesh.
+        let max = (0..cost.tests.len())
+            .max_by_key(|i| cost.tests[*i])
+            .unwrap();
+        assert!(
+            (7..=12).contains(&(max % 20)) && (2..=7).contains(&(max / 20))
+        );
+    }
+
+    #[test]
+    fn render_quarantine() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::material::Scattering;
+
+        // An emitter that went wrong.
+        #[derive(Clone)]
+        struct NanEmitter;
+
+        impl Scattering for NanEmitter {
+            fn scatter(
+                &self,
+                _incident: &Ray,
+                _hit: &Hit,
+                _attenuation: &mut ndarray::Array1<Float>,
+                _scattered: &mut Ray,
+                _depth: u32,
+            ) -> bool {
+                false
+            }
+
+            fn color(&self, _hit: &Hit) -> ndarray::Array1<Float> {
+                arr1(&[Float::NAN, 0.0, 0.0, 1.0])
+            }
+
+            fn clone_box(&self) -> Box<dyn Scattering> {
+                Box::new(self.clone())
+            }
+
+            fn name(&self) -> String {
+                "nan_emitter".to_string()
+            }
+
+            fn color_noscatter(&self, hit: &Hit) -> ndarray::Array1<Float> {
+                self.color(hit)
+            }
+        }
+
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(NanEmitter),
+        })];
+        let canvas = Canvas::new(actors, scenes::two_spheres_camera(20, 10));
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(2)
+            .seed(3)
+            .build()
+            .unwrap();
+
+        assert!(matches!(
+            canvas.render_hdr(&settings),
+            Err(SaturnoError::NonFinitePixel { .. })
+        ));
+
+        let quarantine = canvas.render_quarantine(&settings).unwrap();
+        assert!(quarantine.image.data.iter().all(|v| v.is_finite()));
+        assert_eq!(quarantine.culprits.len(), 1);
+        let (culprit, samples) = &quarantine.culprits[0];
+        assert_eq!(culprit.actor, "actor0");
+        assert_eq!(culprit.material, "nan_emitter");
+        assert_eq!(culprit.quantity, "radiance");
+
+        // The sphere is magenta in the mask, the sky is left alone.
+        let center = quarantine.mask.get_pixel(5 * 20 + 10);
+        assert_eq!(center, [1.0, 0.0, 1.0, 1.0]);
+        assert_eq!(quarantine.mask.get_pixel(0), [0.0, 0.0, 0.0, 0.0]);
+        let discarded: Float = (0..quarantine.mask.size())
+            .map(|i| quarantine.mask.get_pixel(i)[3])
+            .sum();
+        assert_eq!(discarded * 2.0, *samples as Float);
+
+        // Same as the setting, with the sky rendered as usual.
+        let quarantined = RenderSettings {
+            quarantine: true,
+            ..settings
+        };
+        let hdr = canvas.render_hdr(&quarantined).unwrap();
+        assert_eq!(hdr.data, quarantine.image.data);
+        assert!(quarantine.image.get_pixel(0)[2] > 0.9);
+    }
+
+    #[test]
+    fn render_bounds_overlay() {
+        use saturno::render::overlay::actor_color;
+        use saturno::render::Image;
+
+        let camera = scenes::two_spheres_camera(40, 20);
+        for (x, y) in [(0.0, 0.0), (12.5, 3.0), (39.0, 19.5)] {
+            let ray = camera.get_ray(x, y);
+            let point = ray.point_at_parameter(2.0);
+            let (px, py) =
+                camera.project(&[point[0], point[1], point[2]]).unwrap();
+            assert!((px - x).abs() < 1e-6 && (py - y).abs() < 1e-6);
+        }
+        assert!(camera.project(&[0.0, 0.0, 1.0]).is_none());
+
+        let canvas = Canvas::new(scenes::two_spheres_normals(), camera);
+        let settings = RenderSettings::builder().size(40, 20).build().unwrap();
+        let mut image = Image::new(40, 20, 4);
+        canvas.draw_bounds(&settings, &mut image);
+
+        // The front face of the small sphere's box spans x = 10 to 30,
+        // its back face x = 16.7 to 23.3 (y = 6.7 to 13.3); the middle
+        // stays empty.
+        let color = actor_color(0);
+        let pixel = |x: u32, y: u32| -> [u8; 4] {
+            let j = ((y * 40 + x) * 4) as usize;
+            [
+                image.data[j],
+                image.data[j + 1],
+                image.data[j + 2],
+                image.data[j + 3],
+            ]
+        };
+        assert!(pixel(9, 5) == color || pixel(10, 5) == color);
+        assert_eq!(pixel(16, 8), color);
+        assert_eq!(pixel(20, 5)[3], 0);
+
+        // A crop draws the same pixels.
+        let crop = RenderSettings::builder()
+            .size(40, 20)
+            .crop(Crop {
+                x: 8,
+                y: 2,
+                width: 20,
+                height: 10,
+            })
+            .build()
+            .unwrap();
+        let mut cropped = Image::new(20, 10, 4);
+        canvas.draw_bounds(&crop, &mut cropped);
+        for y in 0..10 {
+            for x in 0..20 {
+                assert_eq!(cropped.get_value(x, y, 3), pixel(x + 8, y + 2)[3]);
+            }
+        }
+    }
+
+    #[test]
+    fn render_spectral() {
+        use saturno::render::spectral;
+
+        // Uplifted colors integrate back to themselves, white is flat.
+        let integrate = |rgb: [Float; 3]| {
+            let mut sum = [0.0; 3];
+            for lambda in 380..780 {
+                let lambda = lambda as Float + 0.5;
+                let value = spectral::uplift(rgb, lambda);
+                let weights = spectral::rgb_weights(lambda);
+                for c in 0..3 {
+                    sum[c] += value * weights[c];
+                }
+            }
+            sum
+        };
+        for rgb in [[1.0, 1.0, 1.0], [0.2, 0.5, 0.8], [0.7, 0.6, 0.2]] {
+            let back = integrate(rgb);
+            for c in 0..3 {
+                assert!((back[c] - rgb[c]).abs() < 1e-3, "{:?}", back);
+            }
+        }
+        assert!((spectral::uplift([1.0, 1.0, 1.0], 450.0) - 1.0).abs() < 1e-6);
+
+        let hero = spectral::Wavelengths::new(700.0);
+        assert_eq!(hero.hero(), 700.0);
+        for lambda in hero.lambda {
+            assert!((380.0..780.0).contains(&lambda));
+        }
+
+        // The spectral render converges to the RGB one.
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(20, 10),
+        );
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(16)
+            .seed(5)
+            .build()
+            .unwrap();
+        let spectral_settings = RenderSettings {
+            spectral: true,
+            ..settings.clone()
+        };
+        let rgb = canvas.render_hdr(&settings).unwrap();
+        let spectral = canvas.render_hdr(&spectral_settings).unwrap();
+        for c in 0..4 {
+            let mean = |image: &HdrImage| -> Float {
+                (0..image.size())
+                    .map(|i| image.get_pixel(i)[c])
+                    .sum::<Float>()
+                    / image.size() as Float
+            };
+            assert!((mean(&rgb) - mean(&spectral)).abs() < 0.03);
+        }
+    }
+
+    #[test]
+    fn render_dispersion() {
+        use saturno::render::spectral::Wavelengths;
+        use saturno::scene::actor::Hit;
+        use saturno::scene::material::Dispersion;
+        use saturno::scene::material::Scattering;
+
+        // N-BK7 catalog values at the F, d and C lines.
+        let bk7 = Dispersion::bk7();
+        assert!((bk7.refraction_idx(486.1) - 1.5224).abs() < 1e-4);
+        assert!((bk7.refraction_idx(587.6) - 1.5168).abs() < 1e-4);
+        assert!((bk7.refraction_idx(656.3) - 1.5143).abs() < 1e-4);
+        assert!(
+            (Dispersion::diamond().refraction_idx(589.0) - 2.417).abs() < 1e-3
+        );
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.01 };
+        assert!((cauchy.refraction_idx(500.0) - 1.54).abs() < 1e-9);
+
+        let white = arr1(&[1.0, 1.0, 1.0, 0.0]);
+        let prism = Dielectric::dispersive(white.clone(), Shading::COLOR, bk7);
+        assert!(prism.dispersive());
+        assert!(
+            !Dielectric::new(white.clone(), Shading::COLOR, 1.5).dispersive()
+        );
+        let mut hit = Hit::new();
+        assert_eq!(prism.refraction_idx_at(&hit), prism.refraction_idx);
+        hit.wavelength = Some(400.0);
+        let blue = prism.refraction_idx_at(&hit);
+        hit.wavelength = Some(700.0);
+        assert!(blue > prism.refraction_idx_at(&hit));
+
+        // The hero carries the whole path after a dispersive bounce.
+        let wavelengths = Wavelengths::new(500.0);
+        let mut weights = arr1(&[0.5, 0.5, 0.5, 1.0]);
+        let hero = wavelengths.terminate_secondary(&mut weights);
+        assert!(hero.hero_only);
+        assert_eq!(weights, arr1(&[1.5, 0.0, 0.0, 1.0]));
+        let mut again = arr1(&[0.5, 0.5, 0.5, 1.0]);
+        hero.terminate_secondary(&mut again);
+        assert_eq!(again, arr1(&[0.5, 0.5, 0.5, 1.0]));
+
+        // In RGB dispersive glass renders like glass of its d-line index,
+        // spectral renders stay finite.
+        let glass = |material: Dielectric| {
+            let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(material),
+            })];
+            Canvas::new(actors, scenes::two_spheres_camera(20, 10))
+        };
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(4)
+            .seed(2)
+            .build()
+            .unwrap();
+        let dispersive = glass(prism.clone()).render_hdr(&settings).unwrap();
+        let plain =
+            glass(Dielectric::new(white, Shading::COLOR, prism.refraction_idx))
+                .render_hdr(&settings)
+                .unwrap();
+        assert_eq!(dispersive.data, plain.data);
+
+        let spectral = RenderSettings {
+            spectral: true,
+            ..settings
+        };
+        let image = glass(prism).render_hdr(&spectral).unwrap();
+        assert!(image.data.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn render_thin_film() {
+        use saturno::scene::material::ThinFilm;
+
+        let white = arr1(&[1.0, 1.0, 1.0, 1.0]);
+        let film = |thickness, refraction_idx, substrate_idx| {
+            ThinFilm::new(
+                Arc::new(Metal::new(white.clone(), Shading::COLOR, 0.0)),
+                thickness,
+                refraction_idx,
+                substrate_idx,
+            )
+        };
+
+        // Without a film (or one matching the substrate) the reflectance
+        // is the Fresnel one, 4% for glass at normal incidence.
+        let bare = film(0.0, 1.5, 1.5);
+        assert!((bare.reflectance(500.0, 1.0) - 0.04).abs() < 1e-9);
+        assert!(
+            (film(300.0, 1.5, 1.5).reflectance(420.0, 1.0) - 0.04).abs() < 1e-9
+        );
+
+        // A quarter wave coating of index sqrt(1.5) cancels the
+        // reflection at its design wavelength, but not at the others.
+        let n = Float::sqrt(1.5);
+        let coating = film(550.0 / (4.0 * n), n, 1.5);
+        assert!(coating.reflectance(550.0, 1.0) < 1e-9);
+        assert!(coating.reflectance(420.0, 1.0) > 0.005);
+
+        // A soap film reflects colors that shift with the angle.
+        let soap = film(400.0, 1.33, 1.0);
+        let colors = |cos: Float| {
+            [650.0, 532.0, 465.0].map(|lambda| soap.reflectance(lambda, cos))
+        };
+        let (normal, grazing) = (colors(1.0), colors(0.5));
+        assert!((normal[0] - normal[2]).abs() > 0.01);
+        assert!(normal != grazing);
+        for r in normal.iter().chain(grazing.iter()) {
+            assert!((0.0..=1.0).contains(r));
+        }
+
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(soap.clone()),
+        })];
+        let canvas = Canvas::new(actors, scenes::two_spheres_camera(20, 10));
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(4)
+            .seed(4)
+            .build()
+            .unwrap();
+        for spectral in [false, true] {
+            let settings = RenderSettings {
+                spectral,
+                ..settings.clone()
+            };
+            let image = canvas.render_hdr(&settings).unwrap();
+            assert!(image.data.iter().all(|v| v.is_finite()));
+        }
+    }
+
+    #[test]
+    fn render_subsurface() {
+        use saturno::render::debug::BounceEvent;
+        use saturno::scene::material::Subsurface;
+
+        let canvas = |albedo: [Float; 3]| {
+            let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Subsurface::new(
+                    arr1(&[albedo[0], albedo[1], albedo[2], 1.0]),
+                    0.05,
+                    1.4,
+                )),
+            })];
+            Canvas::new(actors, scenes::two_spheres_camera(20, 10))
+        };
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(8)
+            .max_depth(500)
+            .seed(6)
+            .build()
+            .unwrap();
+
+        // Paths walk inside the sphere before leaving it.
+        let traces = canvas([0.9, 0.9, 0.9])
+            .trace_pixels(
+                &settings,
+                Crop {
+                    x: 10,
+                    y: 5,
+                    width: 1,
+                    height: 1,
+                },
+            )
+            .unwrap();
+        let events: Vec<_> = traces[0]
+            .samples
+            .iter()
+            .flat_map(|sample| sample.bounces.iter())
+            .filter(|bounce| bounce.event == BounceEvent::Volume)
+            .collect();
+        assert!(!events.is_empty());
+        for bounce in events {
+            let p = bounce.hit.as_ref().unwrap().point;
+            let r2 = p[0] * p[0] + p[1] * p[1] + (p[2] + 1.0) * (p[2] + 1.0);
+            assert!(r2 < 0.25 + 1e-9);
+        }
+
+        // Every channel keeps its albedo at each event: red light leaves
+        // a red medium, and a brighter medium is brighter.
+        let sphere_mean = |albedo| {
+            let image = canvas(albedo).render_hdr(&settings).unwrap();
+            let mut sum = [0.0; 3];
+            for y in 3..7 {
+                for x in 8..12 {
+                    let pixel = image.get_pixel(y * 20 + x);
+                    for c in 0..3 {
+                        sum[c] += pixel[c];
+                    }
+                }
+            }
+            sum
+        };
+        let red = sphere_mean([0.95, 0.3, 0.3]);
+        assert!(red[0] > 2.0 * red[1] && red[0] > 2.0 * red[2]);
+        let white = sphere_mean([0.95, 0.95, 0.95]);
+        let gray = sphere_mean([0.5, 0.5, 0.5]);
+        assert!(white[1] > gray[1]);
+    }
+
+    #[test]
+    fn render_anisotropic_metal() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::material::AnisotropicMetal;
+        use saturno::scene::material::Mask;
+        use saturno::scene::material::Scattering;
+        use saturno::scene::texture::Channel;
+        use saturno::scene::texture::LevelSelection;
+        use saturno::scene::texture::Texture;
+        use saturno::scene::texture::TextureFilter;
+
+        // Sphere hits carry a unit tangent perpendicular to the normal.
+        let sphere = Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.5, 0.5, 0.5, 1.0]),
+                Shading::COLOR,
+            )),
+        };
+        let mut hit = Hit::new();
+        let ray =
+            Ray::new(arr1(&[0.1, 0.2, 0.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        assert!(sphere.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+        assert!(hit.tangent.dot(&hit.normal).abs() < 1e-9);
+        assert!((hit.tangent.dot(&hit.tangent) - 1.0).abs() < 1e-9);
+
+        // A flat patch facing +z, tangent along x.
+        let mut hit = Hit::new();
+        hit.point = arr1(&[0.0, 0.0, 0.0, 1.0]);
+        hit.normal = arr1(&[0.0, 0.0, 1.0, 0.0]);
+        hit.tangent = arr1(&[1.0, 0.0, 0.0, 0.0]);
+        let incident =
+            Ray::new(arr1(&[0.0, 0.0, 1.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        let white = arr1(&[1.0, 1.0, 1.0, 1.0]);
+
+        // Spread of the reflections along the tangent and across it.
+        let spread = |metal: &AnisotropicMetal, hit: &Hit| {
+            let mut sum = [0.0, 0.0];
+            for _ in 0..2000 {
+                let mut attenuation = arr1(&[0.0, 0.0, 0.0, 0.0]);
+                let mut scattered =
+                    Ray::new(hit.point.clone(), hit.normal.clone());
+                if metal.scatter(
+                    &incident,
+                    hit,
+                    &mut attenuation,
+                    &mut scattered,
+                    1,
+                ) {
+                    assert!(attenuation
+                        .iter()
+                        .take(3)
+                        .all(|a| *a <= 1.0 + 1e-9));
+                    let d = &scattered.direction;
+                    let d = d / d.dot(d).sqrt();
+                    sum[0] += d[0] * d[0];
+                    sum[1] += d[1] * d[1];
+                }
+            }
+            sum
+        };
+
+        let still = Mask::Uniform { value: 0.0 };
+        let mirror =
+            AnisotropicMetal::new(white.clone(), 1e-6, 1e-6, still.clone());
+        let [x, y] = spread(&mirror, &hit);
+        assert!(x < 1e-6 && y < 1e-6);
+
+        let brushed = AnisotropicMetal::new(white.clone(), 0.5, 0.05, still);
+        let [x, y] = spread(&brushed, &hit);
+        assert!(x > 10.0 * y);
+
+        let across = Mask::Uniform { value: 0.5 };
+        let turned = AnisotropicMetal::new(white.clone(), 0.5, 0.05, across);
+        let [x, y] = spread(&turned, &hit);
+        assert!(y > 10.0 * x);
+
+        // Turned by a texture at the texture coordinates of the hit, as
+        // much as the red of its right half.
+        let mut strokes = Texture::new(
+            2,
+            1,
+            vec![[0.0, 0.0, 0.0, 1.0], [0.5, 0.0, 0.0, 1.0]],
+        )
+        .unwrap();
+        strokes.filter = TextureFilter::Nearest;
+        strokes.lod = LevelSelection::Fixed { level: 0.0 };
+        let rotation = Mask::texture(Arc::new(strokes), Channel::Red);
+        let painted = AnisotropicMetal::new(white, 0.5, 0.05, rotation);
+        hit.uv = [0.25, 0.5];
+        let [x, y] = spread(&painted, &hit);
+        assert!(x > 10.0 * y);
+        hit.uv = [0.75, 0.5];
+        let [x, y] = spread(&painted, &hit);
+        assert!(y > 10.0 * x);
+
+        // Scene files give a number or a mask.
+        use saturno::scene::description::MaterialDescription;
+        let rotation = |json: &str| match serde_json::from_str(json).unwrap() {
+            MaterialDescription::AnisotropicMetal { rotation, .. } => rotation,
+            _ => unreachable!(),
+        };
+        let metal = r#"{"type": "anisotropic_metal", "color": [1, 1, 1],
+            "alpha_x": 0.5, "alpha_y": 0.05"#;
+        assert_eq!(
+            rotation(&format!("{}}}", metal)),
+            Mask::Uniform { value: 0.0 }
+        );
+        assert_eq!(
+            rotation(&format!(r#"{}, "rotation": 0.5}}"#, metal)),
+            Mask::Uniform { value: 0.5 }
+        );
+        assert!(matches!(
+            rotation(&format!(
+                r#"{}, "rotation": {{"pattern": "texture", "path": "strokes.png"}}}}"#,
+                metal
+            )),
+            Mask::Texture { .. }
+        ));
+
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+            material: Arc::new(brushed),
+            ..sphere
+        })];
+        let canvas = Canvas::new(actors, scenes::two_spheres_camera(20, 10));
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(4)
+            .seed(9)
+            .build()
+            .unwrap();
+        let image = canvas.render_hdr(&settings).unwrap();
+        assert!(image.data.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn render_clearcoat() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::material::Clearcoat;
+        use saturno::scene::material::Scattering;
+
+        let coat = |albedo: Float| {
+            Clearcoat::new(
+                Arc::new(Lambertian::new(
+                    arr1(&[albedo, albedo, albedo, 1.0]),
+                    Shading::COLOR,
+                )),
+                1.5,
+                0.0,
+            )
+        };
+        assert!((coat(1.0).reflectance(1.0) - 0.04).abs() < 1e-9);
+        assert!(coat(1.0).reflectance(0.1) > 0.5);
+
+        let mut hit = Hit::new();
+        hit.point = arr1(&[0.0, 0.0, 0.0, 1.0]);
+        hit.normal = arr1(&[0.0, 0.0, 1.0, 0.0]);
+        let mirror = arr1(&[0.0, 0.0, 1.0, 0.0]);
+        let reflected = |material: &Clearcoat, cos: Float| {
+            let sin = (1.0 - cos * cos).sqrt();
+            let incident = Ray::new(
+                arr1(&[-sin, 0.0, cos, 1.0]),
+                arr1(&[sin, 0.0, -cos, 0.0]),
+            );
+            let mut specular = 0;
+            for _ in 0..4000 {
+                let mut attenuation = arr1(&[0.0, 0.0, 0.0, 0.0]);
+                let mut scattered = Ray::new(hit.point.clone(), mirror.clone());
+                material.scatter(
+                    &incident,
+                    &hit,
+                    &mut attenuation,
+                    &mut scattered,
+                    1,
+                );
+                // A white base under a clear coat keeps all the energy.
+                assert_eq!(attenuation[0], 1.0);
+                let d = &scattered.direction;
+                if (d[0] - sin).abs() < 1e-9 && (d[2] - cos).abs() < 1e-9 {
+                    specular += 1;
+                }
+            }
+            specular as Float / 4000.0
+        };
+        assert!((reflected(&coat(1.0), 1.0) - 0.04).abs() < 0.015);
+        assert!(reflected(&coat(1.0), 0.1) > 0.45);
+
+        // The tint filters the base, on the way in and out.
+        let mut tinted = coat(1.0);
+        tinted.tint = arr1(&[1.0, 0.5, 0.5, 1.0]);
+        let incident =
+            Ray::new(arr1(&[0.0, 0.0, 1.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        let mut attenuation = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        let mut scattered = Ray::new(hit.point.clone(), mirror);
+        for _ in 0..100 {
+            tinted.scatter(
+                &incident,
+                &hit,
+                &mut attenuation,
+                &mut scattered,
+                1,
+            );
+            if attenuation[1] < 1.0 {
+                break;
+            }
+        }
+        assert_eq!(attenuation, arr1(&[1.0, 0.25, 0.25, 1.0]));
+    }
+
+    #[test]
+    fn render_cutout() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::material::Cutout;
+        use saturno::scene::material::Mask;
+
+        let checker = Mask::Checker {
+            size: 0.5,
+            values: [1.0, 0.0],
+        };
+        let at = |x: Float| {
+            let mut hit = Hit::new();
+            hit.point = arr1(&[x, 0.1, 0.1, 1.0]);
+            hit
+        };
+        assert_eq!(checker.value(&at(0.1)), 1.0);
+        assert_eq!(checker.value(&at(0.6)), 0.0);
+        assert_eq!(checker.value(&at(-0.1)), 0.0);
+
+        let ground = || -> Box<dyn RayTraceable> {
+            Box::new(Sphere {
+                center: arr1(&[0.0, -100.5, -1.0, 1.0]),
+                radius: 100.0,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.5, 0.5, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            })
+        };
+        let card = |opacity: Float| -> Box<dyn RayTraceable> {
+            Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Cutout::new(
+                    Arc::new(Lambertian::new(
+                        arr1(&[0.8, 0.1, 0.1, 1.0]),
+                        Shading::COLOR,
+                    )),
+                    Mask::Uniform { value: opacity },
+                )),
+            })
+        };
+
+        // Rays go through the front, then the back, in proportion to the
+        // transparency.
+        let canvas =
+            Canvas::new(vec![card(0.5)], scenes::two_spheres_camera(20, 10));
+        let ray =
+            Ray::new(arr1(&[0.0, 0.0, 0.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        let (mut front, mut back, mut missed) = (0, 0, 0);
+        for _ in 0..4000 {
+            let mut hit = Hit::new();
+            if !canvas.world.is_hit(&ray, 0.0001, Float::MAX, &mut hit) {
+                missed += 1;
+            } else if hit.t < 1.0 {
+                front += 1;
+            } else {
+                back += 1;
+            }
+        }
+        assert!((front as Float / 4000.0 - 0.5).abs() < 0.04);
+        assert!((back as Float / 4000.0 - 0.25).abs() < 0.04);
+        assert!((missed as Float / 4000.0 - 0.25).abs() < 0.04);
+
+        // A fully transparent actor neither shows nor casts shadows.
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(4)
+            .seed(9)
+            .build()
+            .unwrap();
+        let render = |actors| {
+            Canvas::new(actors, scenes::two_spheres_camera(20, 10))
+                .render_hdr(&settings)
+                .unwrap()
+                .data
+        };
+        assert_eq!(render(vec![ground(), card(0.0)]), render(vec![ground()]));
+        assert_ne!(render(vec![ground(), card(1.0)]), render(vec![ground()]));
+
+        // The alpha of a texture at the texture coordinates of the hit:
+        // a card opaque on the left half of its texture only.
+        use saturno::scene::description::MeshDescription;
+        use saturno::scene::description::SceneDescription;
+        use saturno::scene::description::SceneFiles;
+        use saturno::scene::material::Scattering;
+        use saturno::scene::mesh::Mesh;
+        use saturno::scene::texture::Channel;
+        use saturno::scene::texture::LevelSelection;
+        use saturno::scene::texture::Texture;
+        use saturno::scene::texture::TextureFilter;
+        use saturno::scene::texture::TextureSampler;
+
+        let mut leaf = Texture::new(
+            2,
+            1,
+            vec![[0.2, 0.6, 0.1, 1.0], [0.2, 0.6, 0.1, 0.0]],
+        )
+        .unwrap();
+        leaf.filter = TextureFilter::Nearest;
+        leaf.lod = LevelSelection::Fixed { level: 0.0 };
+        let leaf: Arc<dyn TextureSampler> = Arc::new(leaf);
+        let alpha = Mask::texture(leaf.clone(), Channel::Alpha);
+        let mut hit = Hit::new();
+        hit.uv = [0.25, 0.5];
+        assert_eq!(alpha.value(&hit), 1.0);
+        hit.uv = [0.75, 0.5];
+        assert_eq!(alpha.value(&hit), 0.0);
+        assert_eq!(
+            Mask::texture(leaf.clone(), Channel::Green).value(&hit),
+            0.6
+        );
+
+        // In a scene, the images are read with its files.
+        struct Leaf(Arc<dyn TextureSampler>);
+        impl SceneFiles for Leaf {
+            fn mesh(
+                &mut self,
+                mesh: &MeshDescription,
+                _material: Arc<dyn Scattering>,
+            ) -> Result<Mesh, String> {
+                Err(format!("no {}", mesh.path))
+            }
+
+            fn texture(
+                &mut self,
+                path: &str,
+            ) -> Result<Arc<dyn TextureSampler>, String> {
+                match path {
+                    "leaf.png" => Ok(self.0.clone()),
+                    _ => Err(format!("no {}", path)),
+                }
+            }
+        }
+        let mut scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+                "spheres": [{
+                    "center": [0, -100.5, -1],
+                    "radius": 100,
+                    "material": {"type": "lambertian", "albedo": [0.5, 0.5, 0.5]}
+                }, {
+                    "center": [0, 0, -1],
+                    "radius": 0.5,
+                    "material": {"type": "cutout",
+                        "mask": {"pattern": "texture", "path": "leaf.png"},
+                        "base": {"type": "lambertian", "albedo": [0.8, 0.1, 0.1]}}
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert!(scene.actors().is_err());
+        scene.read_textures(&mut Leaf(leaf)).unwrap();
+        let leaves = render(scene.actors().unwrap());
+        // Pixels of the card see the ground through the holes.
+        let bare = render(vec![ground()]);
+        let opaque = render(vec![ground(), card(1.0)]);
+        let pixel =
+            |image: &[Float], i: usize| image[i * 4..i * 4 + 4].to_vec();
+        let card_pixels: Vec<usize> = (0..20 * 10)
+            .filter(|&i| pixel(&opaque, i) != pixel(&bare, i))
+            .collect();
+        let holes = card_pixels
+            .iter()
+            .filter(|&&i| pixel(&leaves, i) == pixel(&bare, i))
+            .count();
+        let solid = card_pixels
+            .iter()
+            .filter(|&&i| pixel(&leaves, i) == pixel(&opaque, i))
+            .count();
+        assert!(holes > 0 && solid > 0, "{} {}", holes, solid);
+    }
+
+    #[test]
+    fn render_mix() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::material::Mask;
+        use saturno::scene::material::Mix;
+        use saturno::scene::material::Scattering;
+
+        let red = Arc::new(Lambertian::new(
+            arr1(&[0.9, 0.1, 0.1, 1.0]),
+            Shading::COLOR,
+        ));
+        let blue = Arc::new(Lambertian::new(
+            arr1(&[0.1, 0.1, 0.9, 1.0]),
+            Shading::COLOR,
+        ));
+        let mix = |value: Float| {
+            Mix::new(red.clone(), blue.clone(), Mask::Uniform { value })
+        };
+
+        let mut hit = Hit::new();
+        hit.point = arr1(&[0.0, 0.0, -0.5, 1.0]);
+        hit.normal = arr1(&[0.0, 0.0, 1.0, 0.0]);
+        let color = mix(0.25).color(&hit);
+        for (c, expected) in [0.7, 0.1, 0.3, 1.0].iter().enumerate() {
+            assert!((color[c] - expected).abs() < 1e-12);
+        }
+
+        // Each bounce scatters off one of them, picked by weight.
+        let incident =
+            Ray::new(arr1(&[0.0, 0.0, 0.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        let mut blues = 0;
+        for _ in 0..4000 {
+            let mut attenuation = arr1(&[0.0, 0.0, 0.0, 0.0]);
+            let mut scattered = Ray::new(hit.point.clone(), hit.normal.clone());
+            assert!(mix(0.25).scatter(
+                &incident,
+                &hit,
+                &mut attenuation,
+                &mut scattered,
+                1,
+            ));
+            if attenuation[2] == 0.9 {
+                blues += 1;
+            }
+        }
+        assert!((blues as Float / 4000.0 - 0.25).abs() < 0.03);
+
+        let checker = Mix::new(
+            red.clone(),
+            blue.clone(),
+            Mask::Checker {
+                size: 1.0,
+                values: [0.0, 1.0],
+            },
+        );
+        assert_eq!(checker.weight(&hit), 1.0);
+        hit.point = arr1(&[0.5, 0.0, 0.5, 1.0]);
+        assert_eq!(checker.weight(&hit), 0.0);
+
+        // Pure blends render exactly like their material.
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(2)
+            .seed(4)
+            .build()
+            .unwrap();
+        let render = |material: Arc<dyn Scattering>| {
+            let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material,
+            })];
+            Canvas::new(actors, scenes::two_spheres_camera(20, 10))
+                .render_hdr(&settings)
+                .unwrap()
+                .data
+        };
+        assert_eq!(render(Arc::new(mix(0.0))), render(red.clone()));
+        assert_eq!(render(Arc::new(mix(1.0))), render(blue.clone()));
+
+        // Blended by a texture, at the texture coordinates of the hit: the
+        // red of a decal, all `second` on its left half.
+        use saturno::scene::description::MaterialDescription;
+        use saturno::scene::material::MaskTexture;
+        use saturno::scene::texture::Channel;
+        use saturno::scene::texture::LevelSelection;
+        use saturno::scene::texture::Texture;
+        use saturno::scene::texture::TextureFilter;
+
+        let mut decal = Texture::new(
+            2,
+            1,
+            vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 0.0, 1.0]],
+        )
+        .unwrap();
+        decal.filter = TextureFilter::Nearest;
+        decal.lod = LevelSelection::Fixed { level: 0.0 };
+        let decal = Arc::new(decal);
+        let textured = Mix::new(
+            red.clone(),
+            blue.clone(),
+            Mask::texture(decal.clone(), Channel::Red),
+        );
+        hit.uv = [0.25, 0.5];
+        assert_eq!(textured.weight(&hit), 1.0);
+        hit.uv = [0.75, 0.5];
+        assert_eq!(textured.weight(&hit), 0.0);
+        let mut textures = vec![];
+        textured.textures(&mut textures);
+        assert_eq!(textures.len(), 1);
+
+        // The same from a description, once its image is read.
+        let mut description: MaterialDescription = serde_json::from_str(
+            r#"{"type": "mix",
+                "factor": {"pattern": "texture", "path": "decal.png", "channel": "red"},
+                "first": {"type": "lambertian", "albedo": [0.9, 0.1, 0.1]},
+                "second": {"type": "lambertian", "albedo": [0.1, 0.1, 0.9]}}"#,
+        )
+        .unwrap();
+        let library = Default::default();
+        assert!(description.build(&library).is_err());
+        if let MaterialDescription::Mix {
+            factor: Mask::Texture { texture, .. },
+            ..
+        } = &mut description
+        {
+            *texture = Some(MaskTexture(decal));
+        }
+        let built = description.build(&library).unwrap();
+        let halves = render(built);
+        assert_ne!(halves, render(red.clone()));
+        assert_ne!(halves, render(blue.clone()));
+    }
+
+    #[test]
+    fn sample_texture() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::texture::{LevelSelection, Texture};
+        use saturno::scene::texture::{TextureFilter, Wrap};
+
+        // 4x4 checker of black and white texels.
+        let texels = (0..16)
+            .map(|i| {
+                let value = ((i % 4 + i / 4) % 2) as Float;
+                [value, value, value, 1.0]
+            })
+            .collect();
+        let mut texture = Texture::new(4, 4, texels).unwrap();
+        assert_eq!(texture.levels(), 3);
+        assert!(Texture::new(2, 2, vec![[0.0; 4]; 3]).is_err());
+
+        // Wrap modes, texel (4, 0) is past the right edge.
+        assert_eq!(texture.texel(0, 4, 0)[0], 0.0);
+        texture.wrap = Wrap::Clamp;
+        assert_eq!(texture.texel(0, 4, 0)[0], 1.0);
+        texture.wrap = Wrap::Mirror;
+        assert_eq!(texture.texel(0, 4, 0)[0], 1.0);
+        assert_eq!(texture.texel(0, -1, 0)[0], 0.0);
+        texture.wrap = Wrap::Repeat;
+
+        // Texel centers read back exactly, in between they blend.
+        texture.filter = TextureFilter::Bilinear;
+        let center = [0.125, 1.0 - 0.125];
+        assert_eq!(texture.sample(center, 0.0)[0], 0.0);
+        assert_eq!(texture.sample([0.375, 1.0 - 0.125], 0.0)[0], 1.0);
+        assert!(
+            (texture.sample([0.25, 1.0 - 0.125], 0.0)[0] - 0.5).abs() < 1e-12
+        );
+        texture.filter = TextureFilter::Nearest;
+        assert_eq!(texture.sample([0.26, 1.0 - 0.125], 0.0)[0], 1.0);
+
+        // Coarser levels average the checker to grey, the footprint picks
+        // the level whose texels are as wide.
+        texture.filter = TextureFilter::Trilinear;
+        assert!((texture.sample(center, 1.0)[0] - 0.5).abs() < 1e-12);
+        assert_eq!(texture.level_of_detail(0.5), 1.0);
+        assert_eq!(texture.level_of_detail(0.0), 0.0);
+        assert_eq!(texture.level_of_detail(100.0), 2.0);
+        texture.lod = LevelSelection::Fixed { level: 0.5 };
+        assert!((texture.sample(center, 0.0)[0] - 0.25).abs() < 1e-12);
+
+        // Spheres map longitude and latitude, the texture multiplies the
+        // albedo of lambertians.
+        let texture = Arc::new(
+            Texture::new(
+                2,
+                1,
+                vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]],
+            )
+            .unwrap(),
+        );
+        let sphere = Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(Lambertian::textured(
+                arr1(&[0.5, 0.5, 0.5, 1.0]),
+                texture,
+            )),
+        };
+        let look = |origin: [Float; 3], direction: [Float; 3]| {
+            let ray = Ray::new(
+                arr1(&[origin[0], origin[1], origin[2], 1.0]),
+                arr1(&[direction[0], direction[1], direction[2], 0.0]),
+            );
+            let mut hit = Hit::new();
+            assert!(sphere.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+            hit
+        };
+        let front = look([0.0, 0.0, 0.0], [0.0, 0.0, -1.0]);
+        assert!((front.uv[0] - 0.5).abs() < 1e-12);
+        assert!((front.uv[1] - 0.5).abs() < 1e-12);
+        let top = look([0.0, 1.0, -1.0], [0.0, -1.0, 0.0]);
+        assert!((top.uv[1] - 1.0).abs() < 1e-12);
+
+        let left = look([-1.0, 0.0, -1.0], [1.0, 0.0, 0.0]);
+        assert!((left.uv[0] - 0.25).abs() < 1e-12);
+        let color = left.material.color(&left);
+        assert_eq!([color[0], color[2]], [0.5, 0.0]);
+    }
+
+    #[test]
+    fn texture_cache() {
+        use saturno::io::tiled;
+        use saturno::scene::texture::Texture;
+        use saturno::scene::texture_cache::TextureCache;
+        use saturno::scene::texture_cache::TILE_SIZE;
+
+        let mut path = init_image_testing();
+        path.push("texture_cache.stx");
+
+        // Not a multiple of the tiles, every texel different.
+        let (width, height) = (150, 70);
+        let texels = (0..width * height)
+            .map(|i| {
+                let (x, y) = ((i % width) as Float, (i / width) as Float);
+                [x / 256.0, y / 256.0, 0.5, 1.0]
+            })
+            .collect();
+        let texture = Texture::new(width, height, texels).unwrap();
+        tiled::write_tiled_texture(&path, &texture).unwrap();
+
+        // Opening reads nothing, lookups read the tiles they need.
+        let tile_bytes =
+            TILE_SIZE * TILE_SIZE * 4 * std::mem::size_of::<Float>();
+        let cache = Arc::new(TextureCache::new(2 * tile_bytes));
+        let cached = tiled::read_tiled_texture(&path, cache.clone()).unwrap();
+        assert_eq!(cache.stats().misses, 0);
+        assert_eq!(
+            (cached.width(), cached.height(), cached.levels()),
+            (width, height, texture.levels())
+        );
+        assert_eq!(cached.texel(0, 3, 2), texture.texel(0, 3, 2));
+        assert_eq!(cached.texel(0, 5, 60), texture.texel(0, 5, 60));
+        assert_eq!(cache.stats().misses, 1);
+        assert_eq!(cache.stats().hits, 1);
+
+        // Every texel of every level, through a cache holding 2 tiles.
+        for level in 0..texture.levels() {
+            let (width, height) = texture.level_size(level);
+            for y in 0..height as i64 {
+                for x in 0..width as i64 {
+                    assert_eq!(
+                        cached.texel(level, x, y),
+                        texture.texel(level, x, y)
+                    );
+                }
+            }
+        }
+        let stats = cache.stats();
+        assert!(stats.evictions > 0);
+        assert!(stats.resident_bytes <= 2 * tile_bytes);
+        assert_eq!(stats.failures, 0);
+        assert_eq!(
+            cached.sample([0.3, 0.6], 0.02),
+            texture.sample([0.3, 0.6], 0.02)
+        );
+
+        // Truncated files are refused when opened.
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 16]).unwrap();
+        assert!(tiled::read_tiled_texture(&path, cache).is_err());
+    }
+
+    #[test]
+    fn udim_texture() {
+        use saturno::io::tiled;
+        use saturno::io::udim;
+        use saturno::scene::material::Scattering;
+        use saturno::scene::texture::{Texture, TextureSampler, Udim};
+        use saturno::scene::texture_cache::TextureCache;
+
+        assert_eq!(Udim::number([0.5, 0.5]), Some(1001));
+        assert_eq!(Udim::number([9.5, 0.5]), Some(1010));
+        assert_eq!(Udim::number([1.5, 1.5]), Some(1012));
+        assert_eq!(Udim::number([10.5, 0.5]), None);
+        assert_eq!(Udim::number([-0.5, 0.5]), None);
+
+        let mut directory = init_image_testing();
+        directory.push("udim");
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+
+        // Tile 1001 black on the left and white on the right, tile 1012
+        // red. Other files are not tiles of the set.
+        let tiles = [
+            (1001, vec![[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]]),
+            (1012, vec![[1.0, 0.0, 0.0, 1.0], [1.0, 0.0, 0.0, 1.0]]),
+        ];
+        for (number, texels) in tiles.iter() {
+            let texture = Texture::new(2, 1, texels.clone()).unwrap();
+            let path = directory.join(format!("wood.{}.stx", number));
+            tiled::write_tiled_texture(&path, &texture).unwrap();
+        }
+        std::fs::write(directory.join("wood.12.stx"), b"").unwrap();
+        std::fs::write(directory.join("wood.1001.png"), b"").unwrap();
+
+        let pattern = directory.join("wood.<UDIM>.stx");
+        let numbers: Vec<u32> = udim::udim_tiles(&pattern)
+            .unwrap()
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect();
+        assert_eq!(numbers, vec![1001, 1012]);
+        assert!(udim::udim_tiles(&directory.join("oak.<UDIM>.stx")).is_err());
+        assert!(udim::udim_tiles(&directory.join("wood.stx")).is_err());
+
+        let cache = Arc::new(TextureCache::new(1 << 20));
+        let set = Arc::new(
+            udim::read_udim(&pattern, |path| {
+                tiled::read_tiled_texture(path, cache.clone())
+            })
+            .unwrap(),
+        );
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.name(), "udim(1001,1012)");
+
+        // Each unit square reads its own tile, clamped at its edges.
+        assert_eq!(set.sample([0.999, 0.5], 0.0), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(set.sample([0.001, 0.5], 0.0), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(set.sample([1.5, 1.5], 0.0), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(set.sample([0.5, 1.5], 0.0), [0.0; 4]);
+
+        let material = Lambertian::textured(arr1(&[1.0, 1.0, 1.0, 1.0]), set);
+        assert_eq!(
+            material.name(),
+            "lambertian(1.000,1.000,1.000;udim(1001,1012))"
+        );
+    }
+
+    #[test]
+    fn displace_mesh() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::mesh::{Displacement, Mesh};
+        use saturno::scene::texture::{LevelSelection, Texture};
+        use saturno::scene::texture::{TextureFilter, Wrap};
+
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let grid =
+            Mesh::grid([0.0, 0.0, 0.0], 2.0, 4, material.clone()).unwrap();
+        assert_eq!((grid.vertices(), grid.triangles()), (25, 32));
+        assert!(Mesh::new(
+            vec![[0.0; 3]],
+            vec![[0.0; 2]],
+            vec![[0, 0, 1]],
+            material
+        )
+        .is_err());
+
+        let down = |mesh: &Mesh, x: Float, z: Float| {
+            let ray =
+                Ray::new(arr1(&[x, 5.0, z, 1.0]), arr1(&[0.0, -1.0, 0.0, 0.0]));
+            let mut hit = Hit::new();
+            if mesh.is_hit(&ray, 0.0001, Float::MAX, &mut hit) {
+                Some(hit)
+            } else {
+                None
+            }
+        };
+        let hit = down(&grid, 0.3, -0.2).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!((hit.normal[1] - 1.0).abs() < 1e-9);
+        assert!((hit.uv[0] - 0.65).abs() < 1e-9);
+        assert!((hit.uv[1] - 0.6).abs() < 1e-9);
+        assert!(down(&grid, 1.1, 0.0).is_none());
+
+        // Subdividing shares the midpoints of the edges: 9x9 vertices.
+        let fine = grid.subdivide();
+        assert_eq!((fine.vertices(), fine.triangles()), (81, 128));
+
+        // Black on the left half, white on the right: a step of 0.5.
+        let mut step = Texture::new(
+            2,
+            1,
+            vec![[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+        )
+        .unwrap();
+        step.wrap = Wrap::Clamp;
+        step.filter = TextureFilter::Nearest;
+        step.lod = LevelSelection::Fixed { level: 0.0 };
+        let terrain = grid.displace(&Displacement {
+            texture: Arc::new(step),
+            scale: 0.5,
+            subdivisions: 2,
+        });
+        assert_eq!(terrain.triangles(), 32 * 16);
+        for vertex in 0..terrain.vertices() {
+            let [u, _] = terrain.uv(vertex);
+            let height = terrain.position(vertex)[1];
+            assert_eq!(height, if u < 0.5 { 0.0 } else { 0.5 });
+        }
+        let low = down(&terrain, -0.5, 0.3).unwrap();
+        let high = down(&terrain, 0.5, 0.3).unwrap();
+        assert!((low.t - 5.0).abs() < 1e-9);
+        assert!((high.t - 4.5).abs() < 1e-9);
+        let bounds = terrain.bounding_box().unwrap();
+        assert_eq!((bounds.min[1], bounds.max[1]), (0.0, 0.5));
+
+        // Camera rays get a footprint on meshes too.
+        let camera = Camera::new(
+            90.0,
+            100,
+            100,
+            arr1(&[0.0, 1.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 0.0]),
+            0.0,
+        );
+        let mut hit = Hit::new();
+        assert!(grid.is_hit(
+            &camera.get_ray(50.0, 50.0),
+            0.0001,
+            Float::MAX,
+            &mut hit
+        ));
+        // A pixel is 0.02 wide on the grid, 2 wide in texture coordinates.
+        assert!((hit.footprint - 0.01).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sun_light() {
+        use saturno::scene::description::SceneDescription;
+        use saturno::scene::light::Light;
+        use saturno::scene::light::Sun;
+
+        // Straight overhead, a sun of illuminance pi lights a white
+        // Lambertian surface to a radiance of 1.
+        let mut sun = Sun::new([0.0, 1.0, 0.0], consts::PI);
+        let point = arr1(&[0.0, 0.0, 0.0, 1.0]);
+        let radius = (sun.angular_diameter / 2.0).to_radians();
+        for _ in 0..100 {
+            let sample = sun.sample(&point).unwrap();
+            assert!(sample.direction[1] >= radius.cos() - 1e-9);
+            assert!(sample.distance.is_infinite());
+            assert!((sample.radiance[0] - consts::PI).abs() < 1e-9);
+        }
+        let disk = sun.emitted(&point, &arr1(&[0.0, 2.0, 0.0, 0.0])).unwrap();
+        assert!((disk[1] * sun.solid_angle() - consts::PI).abs() < 1e-9);
+        assert!(sun.emitted(&point, &arr1(&[1.0, 1.0, 0.0, 0.0])).is_none());
+        assert!(sun.validate().is_ok());
+        sun.angular_diameter = 180.0;
+        assert!(sun.validate().is_err());
+
+        let render = |angular_diameter: Float| {
+            let actors = vec![
+                Box::new(Sphere {
+                    center: arr1(&[0.0, -1000.0, 0.0, 1.0]),
+                    radius: 1000.0,
+                    material: Arc::new(Lambertian::new(
+                        arr1(&[0.5, 0.5, 0.5, 1.0]),
+                        Shading::COLOR,
+                    )),
+                }) as Box<dyn RayTraceable>,
+                Box::new(Sphere {
+                    center: arr1(&[0.0, 1.0, 0.0, 1.0]),
+                    radius: 0.25,
+                    material: Arc::new(Lambertian::new(
+                        arr1(&[0.5, 0.5, 0.5, 1.0]),
+                        Shading::COLOR,
+                    )),
+                }) as Box<dyn RayTraceable>,
+            ];
+            let camera = Camera::new(
+                60.0,
+                40,
+                40,
+                arr1(&[0.0, 3.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 0.0]),
+                0.0,
+            );
+            let mut sun = Sun::new([1.0, 1.0, 0.0], consts::PI);
+            sun.angular_diameter = angular_diameter;
+            sun.light_group = Some("sun".to_string());
+            let mut canvas = Canvas::new(actors, camera);
+            canvas.add_light(Arc::new(sun));
+            let settings = RenderSettings::builder()
+                .size(40, 40)
+                .samples(16)
+                .seed(3)
+                .build()
+                .unwrap();
+            canvas.render_light_groups(&settings).unwrap()
+        };
+
+        // The occluder casts its shadow at x = -1 on the ground, x = 1 is
+        // lit at 45 degrees.
+        let (shadow, lit) = (20 * 40 + 8, 20 * 40 + 31);
+        let (beauty, groups) = render(0.0);
+        let names: Vec<&String> = groups.keys().collect();
+        assert_eq!(names, vec!["background", "sun"]);
+        let direct = 0.5 * (0.5 as Float).sqrt();
+        assert!((groups["sun"].get_pixel(lit)[0] - direct).abs() < 0.05);
+        assert!(groups["sun"].get_pixel(shadow)[0] < 0.01);
+        assert!(beauty.get_pixel(lit)[0] > beauty.get_pixel(shadow)[0] + 0.3);
+
+        // A wide disk is mostly visible around the small occluder: the
+        // umbra turns into a penumbra.
+        let (_, soft) = render(60.0);
+        assert!(soft["sun"].get_pixel(shadow)[0] > 0.2);
+        assert!((soft["sun"].get_pixel(lit)[0] - direct).abs() < 0.05);
+
+        let json = r#"{
+            "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+            "spheres": [],
+            "lights": [{"type": "sun", "direction": [0, 1, 1],
+                        "illuminance": 100000, "light_group": "sun"}]
+        }"#;
+        let scene: SceneDescription = serde_json::from_str(json).unwrap();
+        let lights = scene.lights().unwrap();
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].name(), "sun(100000lx;0.53deg)");
+        assert_eq!(lights[0].light_group(), Some("sun".to_string()));
+    }
+
+    #[test]
+    fn spot_light() {
+        use saturno::scene::description::LightDescription;
+        use saturno::scene::light::Light;
+        use saturno::scene::light::Spot;
+        use saturno::scene::texture::{Texture, TextureFilter, Wrap};
+
+        // 2 units above, an intensity of 4 pi gives an illuminance of pi.
+        let mut spot =
+            Spot::new([0.0, 2.0, 0.0], [0.0, -1.0, 0.0], 4.0 * consts::PI);
+        let sample = spot.sample(&arr1(&[0.0, 0.0, 0.0, 1.0])).unwrap();
+        assert_eq!(sample.direction, arr1(&[0.0, 1.0, 0.0, 0.0]));
+        assert_eq!(sample.distance, 2.0);
+        assert!((sample.radiance[0] - consts::PI).abs() < 1e-9);
+
+        // Fading between the cones at 15 and 22.5 degrees off the axis.
+        let off_axis = |degrees: Float| {
+            let x = 2.0 * degrees.to_radians().tan();
+            spot.sample(&arr1(&[x, 0.0, 0.0, 1.0]))
+                .map(|sample| sample.radiance[0] * (4.0 + x * x) / 4.0)
+        };
+        assert!((off_axis(10.0).unwrap() - consts::PI).abs() < 1e-9);
+        let fading = off_axis(20.0).unwrap();
+        assert!(fading > 0.0 && fading < consts::PI);
+        assert!(off_axis(25.0).is_none());
+
+        assert!(spot.validate().is_ok());
+        spot.inner_angle = 60.0;
+        assert!(spot.validate().is_err());
+        spot.inner_angle = 30.0;
+
+        // A gobo black on its left half, the spot pointing down has the
+        // right of the gobo towards +x.
+        let mut gobo = Texture::new(
+            2,
+            1,
+            vec![[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+        )
+        .unwrap();
+        gobo.filter = TextureFilter::Nearest;
+        gobo.wrap = Wrap::Clamp;
+        spot.gobo = Some(Arc::new(gobo));
+        spot.light_group = Some("spot".to_string());
+
+        let actors = vec![Box::new(Sphere {
+            center: arr1(&[0.0, -1000.0, 0.0, 1.0]),
+            radius: 1000.0,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.5, 0.5, 0.5, 1.0]),
+                Shading::COLOR,
+            )),
+        }) as Box<dyn RayTraceable>];
+        let camera = Camera::new(
+            60.0,
+            40,
+            40,
+            arr1(&[0.0, 3.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(actors, camera);
+        canvas.add_light(Arc::new(spot));
+        let settings = RenderSettings::builder()
+            .size(40, 40)
+            .samples(4)
+            .seed(3)
+            .build()
+            .unwrap();
+        let (_, groups) = canvas.render_light_groups(&settings).unwrap();
+        let lit = groups["spot"].get_pixel(20 * 40 + 24)[0];
+        assert!(lit > 0.4 && lit < 0.5);
+        assert!(groups["spot"].get_pixel(20 * 40 + 15)[0] < 1e-6);
+        assert!(groups["spot"].get_pixel(20 * 40 + 38)[0] < 1e-6);
+
+        let json = r#"{"type": "spot", "position": [0, 2, 0],
+                       "direction": [0, -1, 0], "intensity": 500}"#;
+        let light: LightDescription = serde_json::from_str(json).unwrap();
+        assert_eq!(light.build().unwrap()[0].name(), "spot(500cd;45deg)");
+    }
+
+    #[test]
+    fn environment_portals() {
+        use saturno::scene::environment::{Environment, Portal};
+        use saturno::scene::light::Light;
+        use saturno::scene::texture::{Texture, TextureFilter};
+
+        // A map black towards -x and white towards +x.
+        let mut map = Texture::new(
+            2,
+            1,
+            vec![[0.0, 0.0, 0.0, 1.0], [1.0, 1.0, 1.0, 1.0]],
+        )
+        .unwrap();
+        map.filter = TextureFilter::Nearest;
+        let environment = Environment::new(map);
+        assert_eq!(environment.radiance(&[1.0, 0.0, 0.0])[0], 1.0);
+        assert_eq!(environment.radiance(&[-1.0, 0.0, 0.0])[0], 0.0);
+
+        // A 2x2 window 1 above the point spans a solid angle of 2 pi / 3:
+        // the samples through it average to that under a uniform sky.
+        let mut sky = Environment::uniform([1.0, 1.0, 1.0]);
+        sky.portals.push(Portal {
+            corner: [-1.0, 1.0, -1.0],
+            edge_u: [2.0, 0.0, 0.0],
+            edge_v: [0.0, 0.0, 2.0],
+        });
+        let point = arr1(&[0.0, 0.0, 0.0, 1.0]);
+        let n = 20000;
+        let mean = (0..n)
+            .map(|_| sky.sample(&point).unwrap().radiance[0])
+            .sum::<Float>()
+            / n as Float;
+        assert!((mean - 2.0 * consts::PI / 3.0).abs() < 0.02);
+        let up = arr1(&[0.1, 1.0, 0.0, 0.0]);
+        assert!(sky.can_sample(&point, &up));
+        assert!(!sky.can_sample(&point, &arr1(&[1.0, 0.2, 0.0, 0.0])));
+        assert!(sky.validate().is_ok());
+        let mut skewed = sky.clone();
+        skewed.portals[0].edge_v = [1.0, 0.0, 1.0];
+        assert!(skewed.validate().is_err());
+
+        // Under a uniform sky of radiance 1 a Lambertian ground reflects
+        // its albedo, whether the window samples part of the sky or not.
+        let render = |environment: Environment| {
+            let actors = vec![Box::new(Sphere {
+                center: arr1(&[0.0, -1000.0, 0.0, 1.0]),
+                radius: 1000.0,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.5, 0.5, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>];
+            let camera = Camera::new(
+                60.0,
+                20,
+                20,
+                arr1(&[0.0, 3.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 0.0]),
+                0.0,
+            );
+            let mut canvas = Canvas::new(actors, camera);
+            canvas.add_light(Arc::new(environment));
+            let settings = RenderSettings::builder()
+                .size(20, 20)
+                .samples(16)
+                .seed(11)
+                .build()
+                .unwrap();
+            let hdr = canvas.render_hdr(&settings).unwrap();
+            (0..hdr.size()).map(|i| hdr.get_pixel(i)[0]).sum::<Float>()
+                / hdr.size() as Float
+        };
+        assert!(
+            (render(Environment::uniform([1.0, 1.0, 1.0])) - 0.5).abs() < 0.02
+        );
+        assert!((render(sky) - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn environment_importance() {
+        use saturno::scene::environment::Environment;
+        use saturno::scene::light::Light;
+        use saturno::scene::texture::{Texture, TextureFilter};
+
+        // A dim sky with a sun 10000 times brighter in one texel.
+        let (width, height, sun) = (16, 8, 2 * 16 + 5);
+        let texels = (0..width * height)
+            .map(|i| {
+                let value = if i == sun { 1000.0 } else { 0.1 };
+                [value, value, value, 1.0]
+            })
+            .collect();
+        let mut map = Texture::new(width, height, texels).unwrap();
+        map.filter = TextureFilter::Nearest;
+        let environment = Environment::new(map);
+
+        // The light of every texel, over the solid angle of its row.
+        let expected: Float = (0..width * height)
+            .map(|i| {
+                let y = (i / width) as Float;
+                let top = (0.5 - y / height as Float) * consts::PI;
+                let bottom = (0.5 - (y + 1.0) / height as Float) * consts::PI;
+                let solid_angle = 2.0 * consts::PI / width as Float
+                    * (top.sin() - bottom.sin());
+                let value = if i == sun { 1000.0 } else { 0.1 };
+                value * solid_angle
+            })
+            .sum();
+
+        let point = arr1(&[0.0, 0.0, 0.0, 1.0]);
+        let n = 40000;
+        let mut sum = 0.0;
+        let mut towards_sun = 0;
+        for _ in 0..n {
+            let sample = environment.sample(&point).unwrap();
+            sum += sample.radiance[0];
+            let d = &sample.direction;
+            if environment.radiance(&[d[0], d[1], d[2]])[0] > 1.0 {
+                towards_sun += 1;
+            }
+        }
+        assert!((sum / n as Float - expected).abs() < 0.05 * expected);
+        // Sampled uniformly, 1.2% of the directions would find the sun.
+        assert!(towards_sun as Float > 0.05 * n as Float);
+    }
+
+    #[test]
+    fn sun_and_sky() {
+        use saturno::scene::description::LightDescription;
+        use saturno::scene::sky::SunAndSky;
+
+        // Greenwich at noon UTC of the June solstice: the sun culminates
+        // 90 - 51.48 + 23.44 degrees high, in the south (+z).
+        let mut rig = SunAndSky::new(51.48, 0.0);
+        let (elevation, azimuth) = rig.sun_position();
+        assert!((elevation - 61.96).abs() < 0.3);
+        assert!((azimuth - 180.0).abs() < 2.0);
+        let direction = rig.sun_direction();
+        assert!(direction[2] > 0.0 && direction[0].abs() < 0.05);
+        assert!(rig.sun().illuminance > 80000.0);
+
+        // Turning the compass turns the sun and the sky with it.
+        rig.north = 90.0;
+        let turned = rig.sun_direction();
+        assert!(turned[0] < 0.0 && turned[2].abs() < 0.05);
+        rig.north = 0.0;
+
+        // Sunrise in the east at the equator, on the March equinox.
+        let mut equator = SunAndSky::new(0.0, 0.0);
+        (equator.month, equator.day, equator.hour) = (3, 20, 6.1);
+        let (elevation, azimuth) = equator.sun_position();
+        assert!(elevation.abs() < 2.0);
+        assert!((azimuth - 90.0).abs() < 2.0);
+
+        // New York at local noon of the December solstice (UTC-5).
+        let mut new_york = SunAndSky::new(40.71, -74.01);
+        (new_york.month, new_york.day, new_york.utc_offset) = (12, 21, -5.0);
+        assert!((new_york.sun_position().0 - 25.85).abs() < 0.5);
+
+        // A blue sky, brighter around the sun, and dark at night.
+        let zenith = rig.sky_radiance(&[0.0, 1.0, 0.0]);
+        assert!(zenith[2] > zenith[0] && zenith[1] > 1000.0);
+        let around_sun = rig.sky_radiance(&[0.0, 0.8, 0.6]);
+        let opposite = rig.sky_radiance(&[0.0, 0.8, -0.6]);
+        assert!(around_sun[1] > opposite[1]);
+        rig.hour = 0.0;
+        assert_eq!(rig.sun().illuminance, 0.0);
+        assert_eq!(rig.sky_radiance(&[0.0, 1.0, 0.0]), [0.0, 0.0, 0.0]);
+        (rig.month, rig.day) = (2, 30);
+        assert!(rig.validate().is_err());
+
+        let json = r#"{"type": "sun_and_sky", "latitude": 45.46,
+                       "longitude": 9.19, "year": 2024, "month": 9,
+                       "day": 1, "hour": 15.5, "utc_offset": 2,
+                       "resolution": 32}"#;
+        let light: LightDescription = serde_json::from_str(json).unwrap();
+        let lights = light.build().unwrap();
+        assert_eq!(lights.len(), 2);
+        assert!(lights[0].name().starts_with("sun("));
+        assert!(lights[1].environment());
+        assert!(lights.iter().all(|light| light.validate().is_ok()));
+    }
+
+    #[test]
+    fn render_fog() {
+        use saturno::scene::fog::Fog;
+        use saturno::scene::light::Spot;
+
+        let mut fog = Fog::new(0.5);
+        let ray =
+            Ray::new(arr1(&[0.0, 0.0, 0.0, 1.0]), arr1(&[0.0, 1.0, 0.0, 0.0]));
+        let transmittance = fog.transmittance(&ray, 2.0);
+        assert!((transmittance - (-1.0 as Float).exp()).abs() < 1e-12);
+        // A layer 1 high only dims the start of the way up.
+        fog.top = Some(1.0);
+        let transmittance = fog.transmittance(&ray, Float::INFINITY);
+        assert!((transmittance - (-0.5 as Float).exp()).abs() < 1e-12);
+        let interaction = fog.sample_interaction(&ray, Float::INFINITY);
+        assert!(interaction.is_none_or(|t| t < 1.0));
+
+        // The phase function is normalized, and scatters forward on
+        // average by its anisotropy.
+        fog.anisotropy = 0.6;
+        let n = 10000;
+        let integral: Float = (0..n)
+            .map(|i| {
+                let cosine = -1.0 + 2.0 * (i as Float + 0.5) / n as Float;
+                fog.phase(cosine) * 2.0 * consts::PI * 2.0 / n as Float
+            })
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-3);
+        let forward = arr1(&[0.0, 0.0, -1.0, 0.0]);
+        let mean: Float = (0..n)
+            .map(|_| -fog.sample_direction(&forward)[2])
+            .sum::<Float>()
+            / n as Float;
+        assert!((mean - 0.6).abs() < 0.02);
+        fog.anisotropy = 1.0;
+        assert!(fog.validate().is_err());
+
+        let settings = RenderSettings::builder()
+            .size(9, 9)
+            .samples(64)
+            .seed(2)
+            .build()
+            .unwrap();
+        let camera = |vertical_fov| {
+            Camera::new(
+                vertical_fov,
+                9,
+                9,
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, -3.0, 1.0]),
+                arr1(&[0.0, 1.0, 0.0, 0.0]),
+                0.0,
+            )
+        };
+
+        // An absorbing fog dims an emitter 2 away by exp(-0.5 * 2).
+        let emitter = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -3.0, 1.0]),
+            radius: 1.0,
+            material: Arc::new(DiffuseLight::new(
+                arr1(&[1.0, 1.0, 1.0, 1.0]),
+                1.0,
+            )),
+        }) as Box<dyn RayTraceable>];
+        let mut canvas = Canvas::new(emitter, camera(5.0));
+        let mut absorbing = Fog::new(0.5);
+        absorbing.albedo = [0.0, 0.0, 0.0];
+        canvas.fog = Some(absorbing);
+        let hdr = canvas.render_hdr(&settings).unwrap();
+        let mean = (0..hdr.size()).map(|i| hdr.get_pixel(i)[0]).sum::<Float>()
+            / hdr.size() as Float;
+        assert!((mean - (-1.0 as Float).exp()).abs() < 0.02);
+
+        // A spot shining down through the fog draws a visible beam.
+        let mut spot = Spot::new([0.0, 2.0, -3.0], [0.0, -1.0, 0.0], 100.0);
+        (spot.inner_angle, spot.outer_angle) = (5.0, 10.0);
+        spot.light_group = Some("spot".to_string());
+        let mut canvas = Canvas::new(vec![], camera(60.0));
+        canvas.add_light(Arc::new(spot));
+        canvas.fog = Some(Fog::new(0.2));
+        let (_, groups) = canvas.render_light_groups(&settings).unwrap();
+        let column = |x: usize| {
+            (0..9)
+                .map(|y| groups["spot"].get_pixel(y * 9 + x)[0])
+                .sum::<Float>()
+        };
+        assert!(column(4) > 0.0);
+        assert!(column(4) > 5.0 * column(0));
+    }
+
+    #[test]
+    fn atmosphere_scattering() {
+        use saturno::scene::atmosphere::Atmosphere;
+        use saturno::scene::light::Light;
+        use saturno::scene::light::Sun;
+
+        let atmosphere = Atmosphere::earth([0.0, 0.0, 0.0], 1.0);
+        let ray = |origin: [Float; 3], direction: [Float; 3]| {
+            Ray::new(
+                arr1(&[origin[0], origin[1], origin[2], 1.0]),
+                arr1(&[direction[0], direction[1], direction[2], 0.0]),
+            )
+        };
+        let ground = [0.0, 1.0 + 1e-7, 0.0];
+
+        // Straight up, the air takes about a quarter of the blue and less
+        // of the red. Along the horizon it takes far more, the sunset.
+        let [r, _, b] = atmosphere
+            .transmittance(&ray(ground, [0.0, 1.0, 0.0]), Float::INFINITY);
+        assert!((b - 0.76).abs() < 0.02);
+        assert!(r > 0.93);
+        let [r_horizon, _, b_horizon] = atmosphere
+            .transmittance(&ray(ground, [1.0, 0.0, 0.0]), Float::INFINITY);
+        assert!(b_horizon < 0.01 && r_horizon / b_horizon > 50.0);
+
+        // The sky is blue at noon, and brighter towards the sun (aerosols
+        // scatter forward).
+        let sun: Arc<dyn Light> = Arc::new(Sun::new([1.0, 1.0, 0.0], 1.0));
+        let lights = [sun];
+        let sky = |direction| {
+            atmosphere
+                .segment(&ray(ground, direction), Float::INFINITY, &lights)
+                .unwrap()
+                .in_scattering[0]
+        };
+        let zenith = sky([0.0, 1.0, 0.0]);
+        assert!(zenith[2] > 2.0 * zenith[0]);
+        assert!(sky([1.0, 1.0, 0.0])[1] > zenith[1]);
+
+        // From space, the limb glows on the day side and not in the shadow
+        // of the planet. Rays above the air see nothing.
+        let limb = atmosphere
+            .segment(&ray([0.0, 1.005, 5.0], [0.0, 0.0, -1.0]), 10.0, &lights)
+            .unwrap();
+        assert!(limb.in_scattering[0][2] > 0.0);
+        let night = atmosphere
+            .segment(
+                &ray([-0.715, -0.715, 5.0], [0.0, 0.0, -1.0]),
+                10.0,
+                &lights,
+            )
+            .unwrap();
+        assert_eq!(night.in_scattering[0], [0.0, 0.0, 0.0]);
+        assert!(atmosphere
+            .segment(&ray([0.0, 2.0, 5.0], [0.0, 0.0, -1.0]), 10.0, &lights)
+            .is_none());
+
+        // Rendered from the ground, the sky is blue rather than the
+        // background gradient, and the ground reddened.
+        let planet = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, 0.0, 1.0]),
+            radius: 1.0,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.5, 0.5, 0.5, 1.0]),
+                Shading::NORMALS,
+            )),
+        }) as Box<dyn RayTraceable>];
+        let camera = Camera::new(
+            20.0,
+            9,
+            9,
+            arr1(&[0.0, 1.00001, 0.0, 1.0]),
+            arr1(&[0.0, 2.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, 1.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(planet, camera);
+        canvas.lights = lights.to_vec();
+        canvas.atmosphere = Some(atmosphere.clone());
+        let settings = RenderSettings::builder()
+            .size(9, 9)
+            .samples(4)
+            .seed(3)
+            .build()
+            .unwrap();
+        let hdr = canvas.render_hdr(&settings).unwrap();
+        let pixel = hdr.get_pixel(40);
+        assert!(pixel[2] > 2.0 * pixel[0] && pixel[2] < 0.1);
+
+        let mut invalid = atmosphere;
+        invalid.atmosphere_radius = 0.5;
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn planetary_rings() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::actor::HittableList;
+        use saturno::scene::description::SceneDescription;
+        use saturno::scene::light::Sun;
+        use saturno::scene::material::RingParticles;
+        use saturno::scene::ring::Ring;
+
+        let particles =
+            Arc::new(RingParticles::new(arr1(&[0.8, 0.8, 0.8, 1.0])));
+        let ring = || {
+            Box::new(Ring::new(
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                1.2,
+                2.0,
+                particles.clone(),
+            )) as Box<dyn RayTraceable>
+        };
+        let planet = || {
+            Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, 0.0, 1.0]),
+                radius: 1.0,
+                material: Arc::new(Lambertian::new(
+                    arr1(&[0.5, 0.5, 0.5, 1.0]),
+                    Shading::COLOR,
+                )),
+            }) as Box<dyn RayTraceable>
+        };
+
+        // Rays cross the ring with the probability exp(-depth / mu), more
+        // of them stop at a slant, none in the gap inside it.
+        let ring_alone = ring();
+        let hits = |origin: [Float; 3], direction: [Float; 3]| {
+            let ray = Ray::new(
+                arr1(&[origin[0], origin[1], origin[2], 1.0]),
+                arr1(&[direction[0], direction[1], direction[2], 0.0]),
+            );
+            let n = 20000;
+            (0..n)
+                .filter(|_| {
+                    ring_alone.is_hit(&ray, 0.0001, Float::MAX, &mut Hit::new())
+                })
+                .count() as Float
+                / n as Float
+        };
+        let opacity = |mu: Float| 1.0 - (-1.0 / mu).exp();
+        assert!(
+            (hits([1.5, 1.0, 0.0], [0.0, -1.0, 0.0]) - opacity(1.0)).abs()
+                < 0.02
+        );
+        let slant =
+            hits([1.5, 1.0, -Float::sqrt(3.0)], [0.0, -1.0, Float::sqrt(3.0)]);
+        assert!((slant - opacity(0.5)).abs() < 0.02);
+        assert_eq!(hits([1.0, 1.0, 0.0], [0.0, -1.0, 0.0]), 0.0);
+
+        // The phase function of the particles is normalized.
+        let n = 10000;
+        let integral: Float = (0..n)
+            .map(|i| {
+                let cosine = -1.0 + 2.0 * (i as Float + 0.5) / n as Float;
+                particles.phase(cosine) * 2.0 * consts::PI * 2.0 / n as Float
+            })
+            .sum();
+        assert!((integral - 1.0).abs() < 1e-3);
+
+        // The ring shades the southern hemisphere under a northern sun.
+        let world = HittableList::new(vec![planet(), ring()]);
+        let (x, y) = (
+            (20.0 as Float).to_radians().cos(),
+            -(20.0 as Float).to_radians().sin(),
+        );
+        let shadow = Ray::new(
+            arr1(&[x * 1.0001, y * 1.0001, 0.0, 1.0]),
+            arr1(&[1.0, 1.0, 0.0, 0.0]),
+        );
+        let n = 20000;
+        let shaded = (0..n)
+            .filter(|_| {
+                world.is_hit(&shadow, 0.0001, Float::MAX, &mut Hit::new())
+            })
+            .count() as Float
+            / n as Float;
+        assert!((shaded - opacity(Float::sqrt(0.5))).abs() < 0.02);
+
+        // Seen from above with a low sun, the planet shadows the ring.
+        let mut sun = Sun::new([1.0, 0.2, 0.0], 10.0);
+        sun.light_group = Some("sun".to_string());
+        let camera = Camera::new(
+            40.0,
+            21,
+            21,
+            arr1(&[0.0, 6.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(vec![planet(), ring()], camera);
+        canvas.add_light(Arc::new(sun));
+        let settings = RenderSettings::builder()
+            .size(21, 21)
+            .samples(64)
+            .seed(4)
+            .build()
+            .unwrap();
+        let (_, groups) = canvas.render_light_groups(&settings).unwrap();
+        let pixel = |x: usize| groups["sun"].get_pixel(10 * 21 + x)[0];
+        let (shadowed, lit) = (pixel(3), pixel(17));
+        assert!(lit > 0.05);
+        assert!(shadowed < 0.2 * lit);
+
+        let json = r#"{
+            "camera": {"look_from": [0, 0, 5], "look_at": [0, 0, 0]},
+            "rings": [{"center": [0, 0, 0], "normal": [0, 1, 0],
+                       "inner_radius": 1.2, "outer_radius": 2,
+                       "material": {"type": "ring_particles",
+                                    "albedo": [0.8, 0.7, 0.6]}}]
+        }"#;
+        let scene: SceneDescription = serde_json::from_str(json).unwrap();
+        let actors = scene.actors().unwrap();
+        assert_eq!(actors.len(), 1);
+        assert!(actors[0].validate().is_ok());
+        let mut thin = Ring::new([0.0; 3], [0.0; 3], 1.2, 2.0, particles);
+        assert!(thin.validate().is_err());
+        thin.normal = [0.0, 1.0, 0.0];
+        thin.outer_radius = 1.0;
+        assert!(thin.validate().is_err());
+    }
+
+    #[test]
+    fn solar_system() {
+        use saturno::scene::description::LightDescription;
+        use saturno::scene::ephemeris;
+        use saturno::scene::ephemeris::Planet;
+        use saturno::scene::ephemeris::Satellite;
+        use saturno::scene::scenes::SolarSystem;
+
+        assert_eq!(ephemeris::julian_date(2000, 1, 1, 12.0), 2451545.0);
+        assert_eq!(ephemeris::julian_date(1987, 6, 19, 12.0), 2446966.0);
+
+        // Early January the Earth is near its perihelion, in July near its
+        // aphelion, half an orbit away.
+        let norm =
+            |p: [Float; 3]| p.iter().map(|c| c * c).sum::<Float>().sqrt();
+        let january = SolarSystem::new(2024, 1, 3, 0.0);
+        let july = SolarSystem::new(2024, 7, 5, 0.0);
+        let (near, far) = (
+            january.position(Planet::Earth),
+            july.position(Planet::Earth),
+        );
+        assert!((norm(near) - 0.9833).abs() < 1e-3);
+        assert!((norm(far) - 1.0167).abs() < 1e-3);
+        assert!(near[1].abs() < 1e-3);
+        let cosine = (0..3).map(|i| near[i] * far[i]).sum::<Float>()
+            / (norm(near) * norm(far));
+        assert!(cosine < -0.99);
+        for planet in Planet::ALL {
+            let pole = planet.pole();
+            assert!((norm(pole) - 1.0).abs() < 1e-9);
+        }
+        assert!(Planet::Uranus.pole()[1].abs() < 0.2);
+
+        // The moons orbit at their distances, scaled apart.
+        let mut options = SolarSystem::new(2024, 1, 3, 0.0);
+        options.moon_scale = 10.0;
+        let io = options.satellite_position(Satellite::Io);
+        let jupiter = options.position(Planet::Jupiter);
+        let offset = [0, 1, 2].map(|i| io[i] - jupiter[i]);
+        assert!((norm(offset) * ephemeris::AU - 4217000.0).abs() < 1.0);
+        let moon = Satellite::Moon.position(january.julian_date());
+        let distance = norm(moon) * ephemeris::AU;
+        assert!((356000.0..407000.0).contains(&distance));
+
+        // Every body is a sphere, and Saturn has its rings.
+        let actors = scenes::solar_system(&options);
+        assert_eq!(actors.len(), Planet::ALL.len() + Satellite::ALL.len() + 1);
+        assert!(actors.iter().all(|actor| actor.validate().is_ok()));
+        options.moons = false;
+        options.rings = false;
+        assert_eq!(scenes::solar_system(&options).len(), Planet::ALL.len());
+
+        // The Sun lights the Earth with the solar illuminance, and is a
+        // disk of its luminance seen from it.
+        options.scale = 10.0;
+        options.sun_radius_scale = 10.0;
+        let sun = scenes::solar_system_sun(&options);
+        assert!(sun.validate().is_ok());
+        assert_eq!(sun.light_group(), Some("sun".to_string()));
+        let earth = options.position(Planet::Earth);
+        let point = arr1(&[earth[0], earth[1], earth[2], 1.0]);
+        let sample = sun.sample(&point).unwrap();
+        let illuminance = sample.radiance[0] * (norm(earth) / 10.0).powi(2);
+        assert!((illuminance - 128000.0).abs() < 1e-6);
+        let towards = arr1(&[-earth[0], -earth[1], -earth[2], 0.0]);
+        assert!(sun.emitted(&point, &towards).is_some());
+        assert!(sun.emitted(&point, &(-towards)).is_none());
+
+        let json = r#"{"type": "omni", "position": [1, 2, 3],
+                       "intensity": 100, "radius": 0.5}"#;
+        let light: LightDescription = serde_json::from_str(json).unwrap();
+        let omni = &light.build().unwrap()[0];
+        assert_eq!(omni.name(), "omni(100cd;0.5)");
+        let lit = omni.sample(&arr1(&[1.0, 2.0, 5.0, 1.0])).unwrap();
+        assert!((lit.radiance[0] - 25.0).abs() < 1e-9);
+        assert!((lit.distance - 1.5).abs() < 1e-9);
+        assert!(omni.sample(&arr1(&[1.0, 2.0, 3.2, 1.0])).is_none());
+    }
+
+    #[test]
+    fn black_hole_lensing() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::HittableList;
+        use saturno::scene::black_hole::AccretionDisk;
+        use saturno::scene::black_hole::BlackHole;
+        use saturno::scene::black_hole::Geodesic;
+        use saturno::scene::description::SceneDescription;
+        use saturno::scene::material::AccretionGlow;
+
+        let black_hole = BlackHole::new([0.0, 0.0, 0.0], 1.0);
+        let empty = HittableList::new(vec![]);
+        let bend = |black_hole: &BlackHole, b: Float| {
+            let ray = Ray::new(
+                arr1(&[b, 0.0, 40.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 0.0]),
+            );
+            match black_hole.trace(&ray, &empty, 0.0001, &mut Hit::new()) {
+                Geodesic::Escaped(ray) => Some(ray),
+                Geodesic::Hit(_) => panic!("nothing to hit"),
+                Geodesic::Captured => None,
+            }
+        };
+
+        // Out of its influence rays go straight, far within it they bend
+        // by 2 rs / b towards the hole, and within the critical impact
+        // parameter (3 sqrt(3) / 2 rs) they fall in.
+        let straight = bend(&black_hole, 60.0).unwrap();
+        assert_eq!(straight.direction, arr1(&[0.0, 0.0, -1.0, 0.0]));
+        let mut wide = black_hole.clone();
+        wide.influence = 2000.0;
+        let far = bend(&wide, 20.0).unwrap();
+        let deflection = (-far.direction[0]).atan2(-far.direction[2]);
+        assert!((deflection - 0.1).abs() < 0.01, "{}", deflection);
+        assert!(bend(&black_hole, 0.0).is_none());
+        assert!(bend(&black_hole, 2.5).is_none());
+        assert!(bend(&black_hole, 2.7).is_some());
+
+        // Seen edge on, the far side of the disk shows above the hole.
+        let disk = || {
+            Box::new(AccretionDisk::new(
+                [0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                3.0,
+                10.0,
+                Arc::new(AccretionGlow::new(arr1(&[1.0, 0.5, 0.2, 1.0]), 20.0)),
+            )) as Box<dyn RayTraceable>
+        };
+        let world = HittableList::new(vec![disk()]);
+        let above = Ray::new(
+            arr1(&[0.0, 4.0, 40.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 0.0]),
+        );
+        let mut hit = Hit::new();
+        assert!(matches!(
+            black_hole.trace(&above, &world, 0.0001, &mut hit),
+            Geodesic::Hit(_)
+        ));
+        assert!(hit.point[2] < 0.0);
+        assert!((AccretionGlow::profile(36.0 / 49.0) - 1.0).abs() < 1e-9);
+        assert_eq!(AccretionGlow::profile(1.0), 0.0);
+
+        let camera = Camera::new(
+            30.0,
+            21,
+            21,
+            arr1(&[0.0, 0.0, 40.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(vec![disk()], camera);
+        canvas.black_hole = Some(black_hole);
+        let settings = RenderSettings::builder()
+            .size(21, 21)
+            .samples(1)
+            .seed(5)
+            .build()
+            .unwrap();
+        let image = canvas.render_hdr(&settings).unwrap();
+        assert_eq!(image.get_pixel(10 * 21 + 10), [0.0, 0.0, 0.0, 1.0]);
+        let glowing = (0..10 * 21)
+            .filter(|&i| image.get_pixel(i)[0] > 1.0)
+            .count();
+        assert!(glowing > 0);
+
+        let json = r#"{
+            "camera": {"look_from": [0, 0, 40], "look_at": [0, 0, 0]},
+            "black_hole": {"center": [0, 0, 0], "radius": 1},
+            "accretion_disks": [{"center": [0, 0, 0], "normal": [0, 1, 0],
+                                 "inner_radius": 3, "outer_radius": 10,
+                                 "material": {"type": "accretion_glow",
+                                              "color": [1, 0.5, 0.2],
+                                              "luminance": 20}}]
+        }"#;
+        let scene: SceneDescription = serde_json::from_str(json).unwrap();
+        let black_hole = scene.black_hole.clone().unwrap();
+        assert_eq!(black_hole.influence, 50.0);
+        assert!(black_hole.validate().is_ok());
+        let actors = scene.actors().unwrap();
+        assert_eq!(actors.len(), 1);
+        assert!(actors[0].validate().is_ok());
+        let mut invalid = BlackHole::new([0.0, 0.0, 0.0], 0.0);
+        assert!(invalid.validate().is_err());
+        invalid.radius = 1.0;
+        invalid.step = 1.5;
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn star_field() {
+        use saturno::scene::description::LightDescription;
+        use saturno::scene::ephemeris::equatorial_direction;
+        use saturno::scene::stars::Star;
+        use saturno::scene::stars::StarField;
+
+        // Five magnitudes are a factor 100 in illuminance; hot stars are
+        // blue, cool ones red, all of luminance 1.
+        let mut star = Star {
+            right_ascension: 101.287,
+            declination: -16.716,
+            magnitude: 0.0,
+            color_index: 0.65,
+        };
+        assert!((star.illuminance() - 2.54e-6).abs() < 1e-12);
+        assert!((star.temperature() - 5800.0).abs() < 100.0);
+        let sunlike = star.color();
+        assert!(sunlike[0] > sunlike[2]);
+        star.magnitude = 5.0;
+        assert!((star.illuminance() * 100.0 - 2.54e-6).abs() < 1e-12);
+        star.color_index = -0.3;
+        let hot = star.color();
+        assert!(hot[2] > hot[1] && hot[1] > hot[0]);
+        let luminance = 0.2126 * hot[0] + 0.7152 * hot[1] + 0.0722 * hot[2];
+        assert!((luminance - 1.0).abs() < 1e-9);
+
+        // The catalog, in hours of right ascension, without the Sun.
+        let catalog = "id,proper,ra,dec,mag,ci\n\
+                       0,Sol,0.0,0.0,-26.7,0.656\n\
+                       32263,Sirius,6.752481,-16.716116,-1.44,0.009\n\
+                       91262,Vega,18.615649,38.783692,0.03,\n";
+        let field = StarField::parse_catalog(catalog).unwrap();
+        assert_eq!(field.stars.len(), 2);
+        assert!((field.stars[0].right_ascension - 101.287).abs() < 1e-3);
+        assert_eq!(field.stars[1].color_index, 0.65);
+        assert!(StarField::parse_catalog("ra,dec\n1,2\n").is_err());
+        assert!(StarField::parse_catalog("ra,dec,mag\n1,x,2\n").is_err());
+
+        // Each star keeps its illuminance, baked into a single texel.
+        let sirius = StarField::new(vec![field.stars[0]]);
+        let environment = sirius.environment(512);
+        let direction = field.stars[0].direction();
+        let radiance = environment.radiance(&direction);
+        let latitude = direction[1].asin();
+        let row = ((0.5 - latitude / consts::PI) * 256.0).floor();
+        let center = (0.5 - (row + 0.5) / 256.0) * consts::PI;
+        let solid_angle =
+            2.0 * consts::PI.powi(2) / (512.0 * 256.0) * center.cos();
+        let color = field.stars[0].color();
+        let illuminance = field.stars[0].illuminance();
+        for c in 0..3 {
+            let expected = color[c] * illuminance;
+            assert!((radiance[c] * solid_angle - expected).abs() < 1e-9);
+        }
+        let aside = equatorial_direction(110.0, -16.7);
+        assert_eq!(environment.radiance(&aside)[1], 0.0);
+
+        // Made up stars are drawn again from the same seed, the faint ones
+        // outnumbering the bright ones.
+        let procedural = StarField::procedural(2000, 6.5, 7);
+        assert_eq!(procedural, StarField::procedural(2000, 6.5, 7));
+        assert_ne!(procedural, StarField::procedural(2000, 6.5, 8));
+        assert!(procedural.validate().is_ok());
+        let faint = procedural.stars.iter().filter(|s| s.magnitude > 5.5);
+        let bright = procedural.stars.iter().filter(|s| s.magnitude < 3.5);
+        assert!(faint.count() > 10 * bright.count());
+        assert!(procedural
+            .stars
+            .iter()
+            .all(|s| (-1.5..=6.5).contains(&s.magnitude)));
+
+        // The Milky Way glows brightest towards the galactic center.
+        let mut sky = StarField::new(vec![]);
+        sky.milky_way = 0.001;
+        let core = sky.milky_way_radiance(&equatorial_direction(266.4, -28.9));
+        let pole = sky.milky_way_radiance(&equatorial_direction(192.9, 27.1));
+        let plane = sky.milky_way_radiance(&equatorial_direction(86.4, 28.9));
+        assert!(core[1] > plane[1] && plane[1] > 100.0 * pole[1]);
+
+        let json = r#"{"type": "stars", "count": 100, "seed": 3,
+                       "milky_way": 0.001, "resolution": 64}"#;
+        let light: LightDescription = serde_json::from_str(json).unwrap();
+        let lights = light.build().unwrap();
+        assert!(lights[0].environment());
+        assert!(lights[0].validate().is_ok());
+    }
+
+    #[test]
+    fn photosphere() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::description::MaterialDescription;
+        use saturno::scene::ephemeris;
+        use saturno::scene::ephemeris::Planet;
+        use saturno::scene::material::Photosphere;
+        use saturno::scene::material::Scattering;
+        use saturno::scene::material::SOLAR_LUMINANCE;
+        use saturno::scene::scenes::SolarSystem;
+
+        // The Sun is darker towards its limb, on average as bright as its
+        // luminance over the disk.
+        let mut sun = Photosphere::sun();
+        assert!((sun.limb(0.0) / sun.limb(1.0) - 0.3).abs() < 1e-9);
+        let steps = 10000;
+        let mean = (0..steps)
+            .map(|i| {
+                let mu = (i as Float + 0.5) / steps as Float;
+                2.0 * mu * sun.limb(mu) / steps as Float
+            })
+            .sum::<Float>();
+        assert!((mean - 1.0).abs() < 1e-6);
+
+        let mut hit = Hit::new();
+        hit.normal = arr1(&[0.0, 0.0, 1.0, 0.0]);
+        hit.incident = arr1(&[0.0, 0.0, -1.0, 0.0]);
+        let center = sun.color(&hit);
+        hit.incident = arr1(&[0.8, 0.0, -0.6, 0.0]);
+        let limb = sun.color(&hit);
+        assert!(center[1] > limb[1] && limb[1] > 0.0);
+        assert!(
+            (center[1] / (SOLAR_LUMINANCE * sun.color[1]) - sun.limb(1.0))
+                .abs()
+                < 1e-9
+        );
+
+        // Granules brighten and darken the surface, about the same on
+        // average.
+        sun.limb_darkening = [0.0, 0.0];
+        sun.granulation = 0.5;
+        hit.incident = arr1(&[0.0, 0.0, -1.0, 0.0]);
+        let samples = (0..2000)
+            .map(|i| {
+                hit.point =
+                    arr1(&[i as Float * 0.137, i as Float * 0.071, 0.0, 1.0]);
+                sun.color(&hit)[1] / (SOLAR_LUMINANCE * sun.color[1])
+            })
+            .collect::<Vec<Float>>();
+        let average = samples.iter().sum::<Float>() / samples.len() as Float;
+        assert!((average - 1.0).abs() < 0.1);
+        assert!(samples.iter().any(|s| *s > 1.2));
+        assert!(samples.iter().any(|s| *s < 0.8));
+
+        // Its light shines as bright as the disk seen from afar, and the
+        // surface then defers to it.
+        let mut star = Photosphere::blackbody(5772.0, 1000.0);
+        assert!(!star.sampled_light());
+        let omni = star.omni([0.0, 0.0, 0.0], 2.0);
+        assert!(star.sampled_light());
+        assert!((omni.intensity - 4000.0 * consts::PI).abs() < 1e-9);
+        let hot = Photosphere::blackbody(10000.0, 1.0).color;
+        assert!(hot[2] > hot[0] && star.color[0] > star.color[2]);
+
+        // The Sun of the solar system, visible and lighting the planets.
+        let mut options = SolarSystem::new(2024, 1, 3, 0.0);
+        let actors = scenes::solar_system(&options).len();
+        options.sun = true;
+        options.sun_radius_scale = 20.0;
+        let with_sun = scenes::solar_system(&options);
+        assert_eq!(with_sun.len(), actors + 1);
+        let surface = with_sun.last().unwrap();
+        assert!(surface.validate().is_ok());
+        let earth = options.position(Planet::Earth);
+        let point = arr1(&[earth[0], earth[1], earth[2], 1.0]);
+        let light = scenes::solar_system_sun(&options);
+        let lit = light.sample(&point).unwrap().radiance[1];
+        let distance = earth.iter().map(|c| c * c).sum::<Float>().sqrt();
+        let solid_angle =
+            consts::PI * (20.0 * 695700.0 / ephemeris::AU / distance).powi(2);
+        let disk = SOLAR_LUMINANCE / 400.0 * solid_angle;
+        assert!((lit / disk - 1.0).abs() < 1e-6);
+
+        let json = r#"{"type": "photosphere", "luminance": 1000,
+                       "sampled": true}"#;
+        let material: MaterialDescription = serde_json::from_str(json).unwrap();
+        let built = material.build(&Default::default()).unwrap();
+        assert!(built.sampled_light());
+        assert!(built.name().starts_with("photosphere("));
+        assert!(built.name().ends_with("0.47,0.23)"));
+    }
+
+    #[test]
+    fn caustics() {
+        use saturno::render::caustics;
+        use saturno::render::caustics::Target;
+        use saturno::render::settings::Caustics;
+        use saturno::scene::actor::HittableList;
+        use saturno::scene::light::Omni;
+        use saturno::scene::mesh::Mesh;
+
+        let glass = || {
+            Arc::new(Dielectric::new(
+                arr1(&[1.0, 1.0, 1.0, 1.0]),
+                Shading::COLOR,
+                1.5,
+            ))
+        };
+        // A slab of glass from y = 1 to 1.5, its faces outwards.
+        let (low, high) = (1.0, 1.5);
+        let slab = Mesh::new(
+            vec![
+                [-5.0, high, -5.0],
+                [5.0, high, -5.0],
+                [5.0, high, 5.0],
+                [-5.0, high, 5.0],
+                [-5.0, low, -5.0],
+                [5.0, low, -5.0],
+                [5.0, low, 5.0],
+                [-5.0, low, 5.0],
+            ],
+            vec![[0.0, 0.0]; 8],
+            vec![[0, 2, 1], [0, 3, 2], [4, 5, 6], [4, 6, 7]],
+            glass(),
+        )
+        .unwrap();
+        let world = HittableList::new(vec![Box::new(slab)]);
+        let settings = Caustics::default();
+
+        // Under a point light, the slab brings the light closer by
+        // t (1 - 1/n), and lets through (1 - 0.04)^2 of it.
+        let light = Target::Point([0.0, 4.0, 0.0]);
+        let connection = caustics::connect(
+            &world,
+            &[0.0, 0.0, 0.0],
+            &light,
+            &settings,
+            None,
+        )
+        .unwrap();
+        assert_eq!(connection.vertices, 2);
+        assert!((connection.direction[1] - 1.0).abs() < 1e-6);
+        assert!((connection.transmittance[0] - 0.9216).abs() < 1e-4);
+        let closer: Float = 4.0 - 0.5 * (1.0 - 1.0 / 1.5);
+        assert!((connection.spread / closer.powi(2) - 1.0).abs() < 1e-3);
+
+        // Off axis the path is bent towards the normal in the glass, and
+        // ends on the light.
+        let aside = caustics::connect(
+            &world,
+            &[2.0, 0.0, 1.0],
+            &light,
+            &settings,
+            None,
+        )
+        .unwrap();
+        let l = aside.last;
+        let to_light = [0.0 - l[0], 4.0 - l[1], 0.0 - l[2]];
+        let distance = to_light.iter().map(|c| c * c).sum::<Float>().sqrt();
+        for (c, towards) in to_light.iter().zip(aside.towards) {
+            assert!((c / distance - towards).abs() < 1e-4);
+        }
+        assert!(aside.direction[1] < 4.0 / (21.0 as Float).sqrt());
+
+        // A distant light keeps its solid angle through the slab.
+        let up = Target::Direction([0.0, 1.0, 0.0]);
+        let sunlit =
+            caustics::connect(&world, &[0.0, 0.0, 0.0], &up, &settings, None)
+                .unwrap();
+        assert!((sunlit.spread - 1.0).abs() < 1e-3);
+        let empty = HittableList::new(vec![]);
+        assert!(caustics::connect(
+            &empty,
+            &[0.0, 0.0, 0.0],
+            &up,
+            &settings,
+            None
+        )
+        .is_none());
+
+        // A ball of glass focuses sunlight 1.5 radii from its center: a
+        // radius further, the light is concentrated (1.5 / 1)^2 times.
+        let ball = HittableList::new(vec![Box::new(Sphere {
+            center: arr1(&[0.0, 2.5, 0.0, 1.0]),
+            radius: 1.0,
+            material: glass(),
+        })]);
+        let focused =
+            caustics::connect(&ball, &[0.0, 0.0, 0.0], &up, &settings, None)
+                .unwrap();
+        assert_eq!(focused.vertices, 2);
+        assert!((0.3..0.6).contains(&focused.spread));
+
+        // Rendered, the floor under the ball is lit through it only with
+        // the caustics.
+        let floor = Mesh::grid(
+            [0.0, 0.0, 0.0],
+            20.0,
+            1,
+            Arc::new(Lambertian::new(
+                arr1(&[0.5, 0.5, 0.5, 1.0]),
+                Shading::COLOR,
+            )),
+        )
+        .unwrap();
+        let actors: Vec<Box<dyn RayTraceable>> = vec![
+            Box::new(floor),
+            Box::new(Sphere {
+                center: arr1(&[0.0, 2.5, 0.0, 1.0]),
+                radius: 1.0,
+                material: glass(),
+            }),
+        ];
+        let camera = Camera::new(
+            5.0,
+            3,
+            3,
+            arr1(&[0.0, 0.5, 4.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(actors, camera);
+        canvas.lights = vec![Arc::new(Omni::new([0.0, 10.0, 0.0], 10000.0))];
+        let mut settings = RenderSettings::builder()
+            .size(3, 3)
+            .samples(4)
+            .max_depth(4)
+            .seed(5)
+            .build()
+            .unwrap();
+        let dark = canvas.render_hdr(&settings).unwrap().get_pixel(4)[1];
+        settings.caustics = Some(Caustics::default());
+        let lit = canvas.render_hdr(&settings).unwrap().get_pixel(4)[1];
+        assert!(lit > 10.0 * dark && lit > 5.0);
+
+        settings.caustics = Some(Caustics {
+            tolerance: 0.0,
+            ..Caustics::default()
+        });
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn irradiance_cache() {
+        use saturno::render::irradiance::IrradianceCache;
+        use saturno::render::irradiance::Record;
+        use saturno::render::settings::IrradianceCaching;
+        use saturno::scene::mesh::Mesh;
+
+        // Light from the half of the sky towards +x: turning the normal
+        // that way by a, the irradiance over pi goes from 1/2 to
+        // (1 + sin a) / 2.
+        let up = [0.0, 1.0, 0.0];
+        let half_sky = |d: &[Float; 3]| {
+            let l = if d[0] > 0.0 { 1.0 } else { 0.0 };
+            ([l, l, l], Float::INFINITY)
+        };
+        let record = Record::sample([0.0; 3], up, 1024, 0.1, 2.0, half_sky);
+        assert!((record.radiance[0] - 0.5).abs() < 0.01);
+        assert_eq!(record.radius, 2.0);
+
+        let cache = IrradianceCache::new();
+        cache.clear(None);
+        cache.insert(record, 0.2);
+        assert_eq!(cache.len(), 1);
+        let a: Float = 0.1;
+        let tilted = [a.sin(), a.cos(), 0.0];
+        let turned = cache.lookup(&[0.0; 3], &tilted, 0.2).unwrap();
+        assert!((turned[0] - (1.0 + a.sin()) / 2.0).abs() < 0.01);
+        // Out of reach: too far, too turned, or behind.
+        assert!(cache.lookup(&[0.5, 0.0, 0.0], &up, 0.2).is_none());
+        assert!(cache.lookup(&[0.0; 3], &[1.0, 0.0, 0.0], 0.2).is_none());
+        assert!(cache.lookup(&[0.0, -0.2, 0.0], &up, 0.2).is_none());
+
+        // A bright ball above and aside: the translation gradient follows
+        // the irradiance as the point moves towards it.
+        let ball = |origin: [Float; 3]| {
+            move |d: &[Float; 3]| {
+                let center = [1.0 - origin[0], 1.0, 0.0];
+                let b = d[0] * center[0] + d[1] * center[1];
+                let c = center[0] * center[0] + center[1] * center[1] - 0.25;
+                let discriminant = b * b - c;
+                if discriminant > 0.0 {
+                    ([1.0; 3], b - discriminant.sqrt())
+                } else {
+                    ([0.0; 3], Float::INFINITY)
+                }
+            }
+        };
+        let there =
+            Record::sample([0.0; 3], up, 4096, 0.01, 10.0, ball([0.0; 3]));
+        let moved = Record::sample(
+            [0.1, 0.0, 0.0],
+            up,
+            4096,
+            0.01,
+            10.0,
+            ball([0.1, 0.0, 0.0]),
+        );
+        let cache = IrradianceCache::new();
+        cache.clear(None);
+        cache.insert(there.clone(), 1.0);
+        let extrapolated = cache.lookup(&[0.1, 0.0, 0.0], &up, 1.0).unwrap()[0];
+        let change = moved.radiance[0] - there.radiance[0];
+        assert!(change > 0.0);
+        assert!((extrapolated - moved.radiance[0]).abs() < 0.3 * change);
+
+        // Rendered, the cache agrees with brute force on a floor under
+        // the sky, a ball shadowing it, from much fewer hemispheres.
+        let grey = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let floor = Mesh::grid([0.0, 0.0, 0.0], 20.0, 1, grey.clone()).unwrap();
+        let actors: Vec<Box<dyn RayTraceable>> = vec![
+            Box::new(floor),
+            Box::new(Sphere {
+                center: arr1(&[0.0, 1.0, 0.0, 1.0]),
+                radius: 1.0,
+                material: grey,
+            }),
+        ];
+        let camera = Camera::new(
+            60.0,
+            8,
+            8,
+            arr1(&[0.0, 3.0, 5.0, 1.0]),
+            arr1(&[0.0, 0.5, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(actors, camera);
+        let mut settings = RenderSettings::builder()
+            .size(8, 8)
+            .samples(16)
+            .max_depth(4)
+            .seed(3)
+            .build()
+            .unwrap();
+        let mean = |image: &HdrImage| {
+            image.data.chunks(4).map(|p| p[1]).sum::<Float>() / 64.0
+        };
+        let brute = mean(&canvas.render_hdr(&settings).unwrap());
+        settings.irradiance_cache = Some(IrradianceCaching {
+            rays: 64,
+            ..IrradianceCaching::default()
+        });
+        let cached = mean(&canvas.render_hdr(&settings).unwrap());
+        assert!((cached / brute - 1.0).abs() < 0.1);
+
+        settings.irradiance_cache = Some(IrradianceCaching {
+            min_radius: 2.0,
+            max_radius: 1.0,
+            ..IrradianceCaching::default()
+        });
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn photon_mapping() {
+        use saturno::render::settings::PhotonMapping;
+        use saturno::scene::environment::Environment;
+        use saturno::scene::light::Omni;
+        use saturno::scene::mesh::Mesh;
+
+        // A grey floor and ball under a light, in the dark: photon
+        // mapping converges to the path traced image.
+        let grey = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let floor =
+            || Mesh::grid([0.0, 0.0, 0.0], 20.0, 1, grey.clone()).unwrap();
+        let camera = Camera::new(
+            60.0,
+            8,
+            8,
+            arr1(&[0.0, 3.0, 5.0, 1.0]),
+            arr1(&[0.0, 0.5, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let actors: Vec<Box<dyn RayTraceable>> = vec![
+            Box::new(floor()),
+            Box::new(Sphere {
+                center: arr1(&[0.0, 1.0, 0.0, 1.0]),
+                radius: 1.0,
+                material: grey.clone(),
+            }),
+        ];
+        let mut canvas = Canvas::new(actors, camera);
+        canvas.lights = vec![
+            Arc::new(Omni::new([2.0, 4.0, 1.0], 100.0)),
+            Arc::new(Environment::uniform([0.0; 3])),
+        ];
+        let mut settings = RenderSettings::builder()
+            .size(8, 8)
+            .samples(64)
+            .max_depth(6)
+            .transparent_background(true)
+            .seed(9)
+            .build()
+            .unwrap();
+        let mean = |image: &HdrImage| {
+            image.data.chunks(4).map(|p| p[1]).sum::<Float>() / 64.0
+        };
+        let traced = mean(&canvas.render_hdr(&settings).unwrap());
+        settings.samples = 8;
+        settings.photon_mapping = Some(PhotonMapping {
+            photons: 20000,
+            initial_radius: 0.3,
+            ..PhotonMapping::default()
+        });
+        let mapped = canvas.render_hdr(&settings).unwrap();
+        assert!((mean(&mapped) / traced - 1.0).abs() < 0.1);
+
+        // Progressive passes average to the estimate of as many samples.
+        let mut progressive = Progressive::new(settings.clone());
+        while progressive.step(&canvas).unwrap() {}
+        let averaged = mean(&progressive.hdr());
+        assert!((averaged / mean(&mapped) - 1.0).abs() < 0.1);
+
+        // Under a ball of glass, the floor is lit by the caustic the path
+        // tracer misses from a point light.
+        let actors: Vec<Box<dyn RayTraceable>> = vec![
+            Box::new(floor()),
+            Box::new(Sphere {
+                center: arr1(&[0.0, 2.5, 0.0, 1.0]),
+                radius: 1.0,
+                material: Arc::new(Dielectric::new(
+                    arr1(&[1.0, 1.0, 1.0, 1.0]),
+                    Shading::COLOR,
+                    1.5,
+                )),
+            }),
+        ];
+        let camera = Camera::new(
+            5.0,
+            3,
+            3,
+            arr1(&[0.0, 0.5, 4.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(actors, camera);
+        canvas.lights = vec![Arc::new(Omni::new([0.0, 10.0, 0.0], 10000.0))];
+        let mut settings = RenderSettings::builder()
+            .size(3, 3)
+            .samples(4)
+            .max_depth(6)
+            .seed(5)
+            .build()
+            .unwrap();
+        let dark = canvas.render_hdr(&settings).unwrap().get_pixel(4)[1];
+        settings.photon_mapping = Some(PhotonMapping {
+            photons: 20000,
+            initial_radius: 0.05,
+            ..PhotonMapping::default()
+        });
+        let lit = canvas.render_hdr(&settings).unwrap().get_pixel(4)[1];
+        assert!(lit > 10.0 * dark && lit > 5.0);
+
+        settings.photon_mapping = Some(PhotonMapping {
+            alpha: 1.0,
+            ..PhotonMapping::default()
+        });
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn metropolis() {
+        use saturno::math::random;
+        use saturno::math::random::PrimarySamples;
+        use saturno::render::settings::Metropolis;
+        use saturno::scene::environment::Environment;
+        use saturno::scene::light::Omni;
+        use saturno::scene::mesh::Mesh;
+
+        // Small steps stay close to the samples they mutate, also once a
+        // mutation is rejected.
+        let mut samples = PrimarySamples::new(1, 0, 0.01, 0.0);
+        let draw = |samples: &mut PrimarySamples| {
+            random::with_primary_samples(samples, || {
+                [random::gen_range(0.0, 1.0), random::gen_range(0.0, 1.0)]
+            })
+        };
+        let first = draw(&mut samples);
+        let near = |a: [Float; 2], b: [Float; 2]| {
+            (0..2).all(|i| {
+                let d = (a[i] - b[i]).abs();
+                d.min(1.0 - d) < 0.1
+            })
+        };
+        samples.start_iteration();
+        let moved = draw(&mut samples);
+        assert!(moved != first && near(moved, first));
+        samples.reject();
+        samples.start_iteration();
+        assert!(near(draw(&mut samples), first));
+
+        // A grey floor and ball under a light, in the dark: the chains
+        // converge to the path traced image.
+        let grey = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let actors: Vec<Box<dyn RayTraceable>> = vec![
+            Box::new(
+                Mesh::grid([0.0, 0.0, 0.0], 20.0, 1, grey.clone()).unwrap(),
+            ),
+            Box::new(Sphere {
+                center: arr1(&[0.0, 1.0, 0.0, 1.0]),
+                radius: 1.0,
+                material: grey,
+            }),
+        ];
+        let camera = Camera::new(
+            60.0,
+            8,
+            8,
+            arr1(&[0.0, 3.0, 5.0, 1.0]),
+            arr1(&[0.0, 0.5, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(actors, camera);
+        canvas.lights = vec![
+            Arc::new(Omni::new([2.0, 4.0, 1.0], 100.0)),
+            Arc::new(Environment::uniform([0.0; 3])),
+        ];
+        let mut settings = RenderSettings::builder()
+            .size(8, 8)
+            .samples(64)
+            .max_depth(6)
+            .seed(9)
+            .build()
+            .unwrap();
+        let mean = |image: &HdrImage| {
+            image.data.chunks(4).map(|p| p[1]).sum::<Float>() / 64.0
+        };
+        let traced = mean(&canvas.render_hdr(&settings).unwrap());
+        settings.metropolis = Some(Metropolis {
+            chains: 16,
+            bootstrap: 4096,
+            ..Metropolis::default()
+        });
+        let explored = canvas.render_hdr(&settings).unwrap();
+        assert!((mean(&explored) / traced - 1.0).abs() < 0.1);
+        assert_eq!(explored.get_pixel(0)[3], 1.0);
+        settings.threads = 1;
+        let again = canvas.render_hdr(&settings).unwrap();
+        assert!((mean(&again) / mean(&explored) - 1.0).abs() < 1e-9);
+
+        settings.metropolis = Some(Metropolis {
+            chains: 100,
+            bootstrap: 10,
+            ..Metropolis::default()
+        });
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn gradient_domain() {
+        use saturno::render::gradient;
+        use saturno::render::settings::GradientDomain;
+
+        // A ramp, noisy pixels and exact gradients: the rebuilt image is
+        // much closer to the ramp, with the mean of the pixels.
+        let (width, height) = (16, 12);
+        let ramp: Vec<[Float; 3]> = (0..width * height)
+            .map(|i| {
+                let v = (i % width) as Float / 8.0 + (i / width) as Float / 4.0;
+                [v, 2.0 * v, 1.0]
+            })
+            .collect();
+        let pixels: Vec<[Float; 3]> = ramp
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let noise = if (i * 7919) % 13 < 6 { 0.3 } else { -0.3 };
+                p.map(|v| v + noise)
+            })
+            .collect();
+        let shift = |offset: usize| -> Vec<[Float; 3]> {
+            (0..width * height)
+                .map(|i| {
+                    let j = (i + offset).min(width * height - 1);
+                    [0, 1, 2].map(|c| ramp[j][c] - ramp[i][c])
+                })
+                .collect()
+        };
+        let rebuilt = gradient::reconstruct(
+            &pixels,
+            &shift(1),
+            &shift(width),
+            width,
+            height,
+            0.2,
+            200,
+        );
+        let error = |image: &[[Float; 3]]| -> Float {
+            image
+                .iter()
+                .zip(&ramp)
+                .map(|(a, b)| {
+                    (0..3).map(|c| (a[c] - b[c]).powi(2)).sum::<Float>()
+                })
+                .sum()
+        };
+        assert!(error(&rebuilt) < 0.1 * error(&pixels));
+        let mean = |image: &[[Float; 3]]| -> Float {
+            image.iter().map(|p| p[1]).sum::<Float>() / image.len() as Float
+        };
+        assert!((mean(&rebuilt) - mean(&pixels)).abs() < 1e-6);
+
+        // Rendered, the image keeps the brightness of the path tracer.
+        let grey = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: grey,
+        })];
+        let camera = Camera::new(
+            60.0,
+            16,
+            12,
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(actors, camera);
+        let mut settings = RenderSettings::builder()
+            .size(16, 12)
+            .samples(16)
+            .max_depth(4)
+            .seed(3)
+            .build()
+            .unwrap();
+        let mean = |image: &HdrImage| {
+            image.data.chunks(4).map(|p| p[1]).sum::<Float>()
+                / image.size() as Float
+        };
+        let traced = mean(&canvas.render_hdr(&settings).unwrap());
+        settings.gradient_domain = Some(GradientDomain::default());
+        let rebuilt = canvas.render_hdr(&settings).unwrap();
+        assert!((mean(&rebuilt) / traced - 1.0).abs() < 0.05);
+        assert_eq!(rebuilt.get_pixel(0)[3], 1.0);
+
+        settings.metropolis = Some(Default::default());
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn path_guiding() {
+        use saturno::render::guiding::Guide;
+        use saturno::render::settings::PathGuiding;
+
+        // Light recorded from above: over a few passes the guide learns
+        // to draw the directions up, and space gets split where many
+        // paths went.
+        let guide = Guide::new();
+        guide.clear(None);
+        assert!(!guide.guides(&[0.0; 3]));
+        let uniform = 1.0 / (4.0 * consts::PI);
+        for _ in 0..3 {
+            for i in 0..5000 {
+                let x = (i % 100) as Float / 100.0;
+                let up = [0.05 * x, 0.0, (1.0 - 0.0025 * x * x).sqrt()];
+                let down = [0.0, 0.0, -1.0];
+                guide.record(&[x, 0.0, 0.0], &up, 1.0, uniform);
+                guide.record(&[x, 0.0, 0.0], &down, 0.0, uniform);
+            }
+            guide.refine(4000, 0.01);
+        }
+        assert!(guide.len() > 1);
+        assert!(guide.guides(&[0.5, 0.0, 0.0]));
+        for _ in 0..100 {
+            let (direction, density) = guide.sample(&[0.5, 0.0, 0.0]).unwrap();
+            assert!(direction[2] > 0.9);
+            assert!(density > 10.0 * uniform);
+        }
+        assert_eq!(guide.density(&[0.5, 0.0, 0.0], &[0.0, 0.0, -1.0]), 0.0);
+
+        // Guided, the bounces are weighed so that the image keeps the
+        // brightness of the path tracer.
+        let grey = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let actors: Vec<Box<dyn RayTraceable>> = vec![
+            Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: grey.clone(),
+            }),
+            Box::new(Sphere {
+                center: arr1(&[0.0, -100.5, -1.0, 1.0]),
+                radius: 100.0,
+                material: grey,
+            }),
+        ];
+        let camera = Camera::new(
+            60.0,
+            16,
+            12,
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let canvas = Canvas::new(actors, camera);
+        let mut settings = RenderSettings::builder()
+            .size(16, 12)
+            .samples(32)
+            .max_depth(4)
+            .seed(5)
+            .build()
+            .unwrap();
+        let mean = |image: &HdrImage| {
+            image.data.chunks(4).map(|p| p[1]).sum::<Float>()
+                / image.size() as Float
+        };
+        let traced = mean(&canvas.render_hdr(&settings).unwrap());
+        settings.guiding = Some(PathGuiding {
+            training_passes: 3,
+            spatial_threshold: 200,
+            ..PathGuiding::default()
+        });
+        let guided = mean(&canvas.render_hdr(&settings).unwrap());
+        assert!((guided / traced - 1.0).abs() < 0.05);
+
+        settings.guiding = Some(PathGuiding {
+            bsdf_fraction: 0.0,
+            ..PathGuiding::default()
+        });
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn reservoir_sampling() {
+        use saturno::render::settings::ReservoirSampling;
+        use saturno::scene::environment::Environment;
+        use saturno::scene::light::Light;
+        use saturno::scene::light::Omni;
+        use saturno::scene::mesh::Mesh;
+
+        // A floor under a hundred lights, in the dark: resampling them
+        // with one shadow ray gives about the light of them all.
+        let grey = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(
+            Mesh::grid([0.0, 0.0, 0.0], 20.0, 1, grey).unwrap(),
+        )];
+        let camera = Camera::new(
+            60.0,
+            12,
+            8,
+            arr1(&[0.0, 3.0, 5.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(actors, camera);
+        canvas.lights = (0..100)
+            .map(|i| {
+                let (x, z) = ((i % 10) as Float - 4.5, (i / 10) as Float - 4.5);
+                Arc::new(Omni::new([x, 1.0 + (i % 3) as Float, z], 1.0))
+                    as Arc<dyn Light>
+            })
+            .collect();
+        canvas.lights.push(Arc::new(Environment::uniform([0.0; 3])));
+        let mut settings = RenderSettings::builder()
+            .size(12, 8)
+            .samples(16)
+            .max_depth(1)
+            .seed(4)
+            .build()
+            .unwrap();
+        let mean = |image: &HdrImage| {
+            image.data.chunks(4).map(|p| p[1]).sum::<Float>()
+                / image.size() as Float
+        };
+        let all = mean(&canvas.render_hdr(&settings).unwrap());
+        settings.reservoirs = Some(ReservoirSampling::default());
+        let resampled = mean(&canvas.render_hdr(&settings).unwrap());
+        assert!(all > 0.0);
+        assert!((resampled / all - 1.0).abs() < 0.1);
+
+        settings.reservoirs = Some(ReservoirSampling {
+            candidates: 0,
+            ..ReservoirSampling::default()
+        });
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn light_hierarchy() {
+        use saturno::render::light_tree::LightTree;
+        use saturno::render::settings::LightHierarchy;
+        use saturno::scene::environment::Environment;
+        use saturno::scene::light::Light;
+        use saturno::scene::light::Omni;
+        use saturno::scene::light::Spot;
+        use saturno::scene::mesh::Mesh;
+
+        // A row of lights: those close to the point are picked more
+        // often, and weighing each by its probability estimates the
+        // light of them all.
+        let row: Vec<Arc<dyn Light>> = (0..1000)
+            .map(|i| {
+                Arc::new(Omni::new([i as Float * 0.1, 1.0, 0.0], 1.0))
+                    as Arc<dyn Light>
+            })
+            .collect();
+        let tree = LightTree::new(&row);
+        assert_eq!(tree.len(), 1000);
+        let point = [0.0, 0.0, 0.0];
+        let light_at = |light: &Arc<dyn Light>| {
+            let sample = light.sample(&arr1(&[0.0, 0.0, 0.0, 1.0])).unwrap();
+            sample.radiance[0]
+        };
+        let all: Float = row.iter().map(light_at).sum();
+        let (mut estimate, mut close) = (0.0, 0);
+        for _ in 0..4000 {
+            let (light, probability) = tree.sample(&point).unwrap();
+            estimate += light_at(light) / probability / 4000.0;
+            if light.bounds().unwrap().position[0] < 5.0 {
+                close += 1;
+            }
+        }
+        assert!((estimate / all - 1.0).abs() < 0.05);
+        assert!(close > 2000);
+
+        // A spot facing away is never picked, the sun always sampled.
+        let lights: Vec<Arc<dyn Light>> = vec![
+            Arc::new(Spot::new([0.0, 1.0, 0.0], [0.0, 1.0, 0.0], 10.0)),
+            Arc::new(Omni::new([5.0, 1.0, 0.0], 1.0)),
+            Arc::new(Environment::uniform([0.0; 3])),
+        ];
+        let tree = LightTree::new(&lights);
+        assert_eq!((tree.len(), tree.distant().len()), (2, 1));
+        for _ in 0..100 {
+            let (light, probability) = tree.sample(&point).unwrap();
+            assert_eq!(light.bounds().unwrap().position[0], 5.0);
+            assert_eq!(probability, 1.0);
+        }
+
+        // Rendered, picking one light gives about the light of them all.
+        let grey = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(
+            Mesh::grid([0.0, 0.0, 0.0], 20.0, 1, grey).unwrap(),
+        )];
+        let camera = Camera::new(
+            60.0,
+            12,
+            8,
+            arr1(&[0.0, 3.0, 5.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(actors, camera);
+        canvas.lights = (0..200)
+            .map(|i| {
+                let (x, z) = ((i % 20) as Float - 9.5, (i / 20) as Float - 4.5);
+                Arc::new(Omni::new([x, 0.5 + (i % 3) as Float, z], 1.0))
+                    as Arc<dyn Light>
+            })
+            .collect();
+        canvas.lights.push(Arc::new(Environment::uniform([0.0; 3])));
+        let mut settings = RenderSettings::builder()
+            .size(12, 8)
+            .samples(32)
+            .max_depth(1)
+            .seed(6)
+            .build()
+            .unwrap();
+        let mean = |image: &HdrImage| {
+            image.data.chunks(4).map(|p| p[1]).sum::<Float>()
+                / image.size() as Float
+        };
+        let sampled = mean(&canvas.render_hdr(&settings).unwrap());
+        settings.light_hierarchy = Some(LightHierarchy::default());
+        let picked = mean(&canvas.render_hdr(&settings).unwrap());
+        assert!((picked / sampled - 1.0).abs() < 0.1);
+
+        settings.light_hierarchy = Some(LightHierarchy { samples: 0 });
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn volumetric_emission() {
+        use saturno::scene::volume::Emission;
+        use saturno::scene::volume::EmissiveVolume;
+
+        // A uniform glow of 1 per unit of length, 2 across.
+        let glow = EmissiveVolume::new(
+            [-1.0, -1.0, -1.0],
+            [1.0, 1.0, 1.0],
+            [2, 2, 2],
+            Emission::Rgb(vec![[1.0, 0.5, 0.0]; 8]),
+        );
+        assert!(glow.validate().is_ok());
+        let ray =
+            Ray::new(arr1(&[0.0, 0.0, 5.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        let [r, g, b] = glow.radiance(&ray, Float::INFINITY);
+        assert!((r - 2.0).abs() < 1e-9 && (g - 1.0).abs() < 1e-9 && b == 0.0);
+        // Up to an actor halfway through, then missing the box.
+        let [r, _, _] = glow.radiance(&ray, 5.0);
+        assert!((r - 1.0).abs() < 1e-9);
+        let aside =
+            Ray::new(arr1(&[3.0, 0.0, 5.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        assert_eq!(glow.radiance(&aside, Float::INFINITY), [0.0; 3]);
+
+        // Hotter voxels glow brighter and bluer, interpolated between.
+        let fire = EmissiveVolume::new(
+            [0.0, 0.0, 0.0],
+            [2.0, 1.0, 1.0],
+            [2, 1, 1],
+            Emission::Temperature(vec![1000.0, 3000.0]),
+        );
+        let cool = fire.emission(&[0.5, 0.5, 0.5]);
+        let hot = fire.emission(&[1.5, 0.5, 0.5]);
+        let middle = fire.emission(&[1.0, 0.5, 0.5]);
+        assert!(hot[1] > 10.0 * cool[1]);
+        assert!(hot[2] / hot[0] > cool[2] / cool[0]);
+        assert!((middle[0] - (hot[0] + cool[0]) / 2.0).abs() < 1e-9);
+        let mut broken = fire.clone();
+        broken.resolution = [3, 1, 1];
+        assert!(broken.validate().is_err());
+
+        // Rendered, the volume glows where it is in front of the camera.
+        let settings = RenderSettings::builder()
+            .size(9, 9)
+            .samples(4)
+            .seed(1)
+            .build()
+            .unwrap();
+        let camera = Camera::new(
+            60.0,
+            9,
+            9,
+            arr1(&[0.0, 0.0, 8.0, 1.0]),
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        let mut canvas = Canvas::new(vec![], camera);
+        let background = canvas.render_hdr(&settings).unwrap();
+        canvas.volumes.push(glow);
+        let hdr = canvas.render_hdr(&settings).unwrap();
+        let added = |i| hdr.get_pixel(i)[0] - background.get_pixel(i)[0];
+        assert!((added(4 * 9 + 4) - 2.0).abs() < 0.1);
+        assert_eq!(added(0), 0.0);
+        canvas.volumes[0].intensity = -1.0;
+        assert!(canvas.render_hdr(&settings).is_err());
+    }
+
+    #[test]
+    fn color_temperature() {
+        use saturno::scene::color::{
+            blackbody_color, blackbody_spectrum, cie_xyz, parse_temperature,
+        };
+        use saturno::scene::description::LightDescription;
+
+        // Daylight is white, a candle orange and a blue star blue, all of
+        // luminance 1.
+        let daylight = blackbody_color(6504.0);
+        assert!(daylight.iter().all(|c| (c - 1.0).abs() < 1e-9));
+        let candle = blackbody_color(1800.0);
+        assert!(candle[0] > candle[1] && candle[1] > candle[2]);
+        assert!(candle[2] < 0.05);
+        let star = blackbody_color(15000.0);
+        assert!(star[2] > star[1] && star[1] > star[0]);
+        for rgb in [candle, star] {
+            let luminance = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            assert!((luminance - 1.0).abs() < 1e-9);
+        }
+
+        // The spectrum is as bright as a constant one of 1, and warmer
+        // ones weigh the long wavelengths more.
+        let luminance = |temperature| {
+            (360..=830)
+                .step_by(5)
+                .map(|w| {
+                    let w = w as Float;
+                    blackbody_spectrum(temperature, w) * cie_xyz(w)[1]
+                })
+                .sum::<Float>()
+        };
+        let white = (360..=830)
+            .step_by(5)
+            .map(|w| cie_xyz(w as Float)[1])
+            .sum::<Float>();
+        assert!((luminance(3000.0) / white - 1.0).abs() < 1e-9);
+        let slope =
+            |t| blackbody_spectrum(t, 650.0) / blackbody_spectrum(t, 450.0);
+        assert!(slope(2700.0) > 1.0 && slope(10000.0) < 1.0);
+
+        assert_eq!(parse_temperature("6500K"), Some(6500.0));
+        assert_eq!(parse_temperature(" 1800 k"), Some(1800.0));
+        assert_eq!(parse_temperature("6500"), None);
+        assert_eq!(parse_temperature("-5K"), None);
+
+        // The lights of a description take either.
+        let json = r#"{"type": "omni", "position": [0, 1, 0],
+                       "intensity": 10, "color": "1800K"}"#;
+        let light: LightDescription = serde_json::from_str(json).unwrap();
+        let LightDescription::Omni { color, .. } = light else {
+            panic!("not an omni");
+        };
+        assert_eq!(color, candle);
+        let json = r#"{"type": "omni", "position": [0, 1, 0],
+                       "intensity": 10, "color": "warm"}"#;
+        assert!(serde_json::from_str::<LightDescription>(json).is_err());
+    }
+
+    #[test]
+    fn physical_light_units() {
+        use saturno::prelude::CameraExposure;
+        use saturno::scene::description::LightDescription;
+        use saturno::scene::light::{Light, Omni, Spot, MAX_EFFICACY};
+
+        // A bulb of 800 lumens shines 800 / 4 pi candela all around, a
+        // spot puts them into its cone.
+        let mut omni = Omni::new([0.0, 0.0, 0.0], 1.0);
+        omni.set_lumens(800.0);
+        assert!((omni.intensity - 800.0 / (4.0 * consts::PI)).abs() < 1e-9);
+        assert!((omni.lumens() - 800.0).abs() < 1e-9);
+        let mut spot = Spot::new([0.0, 0.0, 0.0], [0.0, -1.0, 0.0], 1.0);
+        spot.set_lumens(800.0);
+        assert!((spot.lumens() - 800.0).abs() < 1e-9);
+        assert!(spot.intensity > 4.0 * omni.intensity);
+        // The flux is the intensity integrated over the falloff.
+        let n = 100000;
+        let flux: Float = (0..n)
+            .map(|i| {
+                let angle = (i as Float + 0.5) / n as Float * consts::PI;
+                let out = [angle.sin(), -angle.cos(), 0.0];
+                let radiance = spot
+                    .sample(&arr1(&[-out[0], -out[1], 0.0, 1.0]))
+                    .map_or(0.0, |sample| sample.radiance[0]);
+                radiance * 2.0 * consts::PI * angle.sin() * consts::PI
+                    / n as Float
+            })
+            .sum();
+        assert!((flux - 800.0).abs() < 0.1);
+
+        // The descriptions take lumens or watts too.
+        let json = r#"{"type": "omni", "position": [0, 0, 0],
+                       "intensity": 800, "unit": "lumens"}"#;
+        let light: LightDescription = serde_json::from_str(json).unwrap();
+        assert_eq!(light.build().unwrap()[0].name(), omni.name());
+        let json = r#"{"type": "spot", "position": [0, 0, 0],
+                       "direction": [0, -1, 0], "intensity": 60,
+                       "unit": "watts", "efficacy": 15}"#;
+        let light: LightDescription = serde_json::from_str(json).unwrap();
+        spot.set_lumens(900.0);
+        assert_eq!(light.build().unwrap()[0].name(), spot.name());
+        assert_eq!(MAX_EFFICACY, 683.0);
+
+        // Sunny 16: f/16 at 1/100 s and ISO 100 is EV 14.6, exposing a
+        // white surface under 100000 lux (100000 / pi nits) to about 1.
+        let camera = CameraExposure::default();
+        assert!((camera.ev100() - 14.644).abs() < 1e-3);
+        let white = 100000.0 / consts::PI * camera.scale();
+        assert!((white - 1.0).abs() < 0.1);
+        let settings = RenderSettings::builder()
+            .exposure(1.0)
+            .camera_exposure(CameraExposure {
+                ev100: Some(10.0),
+                ..camera
+            })
+            .build()
+            .unwrap();
+        let scale = settings.exposure_scale();
+        assert!((scale - 2.0 / (1.2 * 1024.0)).abs() < 1e-12);
+        let broken = CameraExposure {
+            shutter: 0.0,
+            ..camera
+        };
+        let built = RenderSettings::builder().camera_exposure(broken).build();
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn bloom_and_glare() {
+        use saturno::prelude::Bloom;
+        use saturno::render::bloom::bloom;
+
+        // A dim image with a single bright pixel in the middle.
+        let mut hdr = HdrImage::new(41, 41, 4);
+        for i in 0..hdr.size() {
+            hdr.set_pixel(i, [0.1, 0.1, 0.1, 1.0]);
+        }
+        let center = 20 * 41 + 20;
+        hdr.set_pixel(center, [100.0, 100.0, 100.0, 1.0]);
+        let at = |image: &HdrImage, x: usize, y: usize| {
+            image.get_pixel(y * 41 + x)[0] - 0.1
+        };
+
+        // The bloom spreads a share of the light above the threshold
+        // around it, and leaves the rest of the image alone.
+        let settings = Bloom {
+            radius: 0.03,
+            ..Bloom::default()
+        };
+        let bloomed = bloom(&hdr, &settings, 1.0);
+        assert!(at(&bloomed, 21, 20) > at(&bloomed, 23, 20));
+        assert!(at(&bloomed, 23, 20) > 0.0);
+        assert_eq!(at(&bloomed, 0, 0), 0.0);
+        let added: Float = (0..hdr.size())
+            .map(|i| bloomed.get_pixel(i)[1] - hdr.get_pixel(i)[1])
+            .sum();
+        assert!((added - 0.1 * 99.0).abs() < 1e-6);
+        // Exposed 100 times less, nothing is above the threshold.
+        let dim = bloom(&hdr, &settings, 0.01);
+        assert_eq!(dim.data, hdr.data);
+
+        // Glare streaks along the 6 directions of a 6 blade aperture (the
+        // first at 45 degrees, opposite the fourth), and not between them.
+        let settings = Bloom {
+            intensity: 0.0,
+            glare: 0.5,
+            glare_length: 0.2,
+            ..Bloom::default()
+        };
+        let glared = bloom(&hdr, &settings, 1.0);
+        assert!(at(&glared, 25, 25) > 0.0);
+        assert!((at(&glared, 15, 15) - at(&glared, 25, 25)).abs() < 1e-12);
+        assert!(at(&glared, 25, 20) == 0.0 && at(&glared, 20, 25) == 0.0);
+        assert!(at(&glared, 22, 22) > at(&glared, 25, 25));
+
+        // The developed image glows around the pixel.
+        let plain = RenderSettings::default();
+        let glowing = RenderSettings::builder()
+            .bloom(Bloom {
+                radius: 0.03,
+                ..Bloom::default()
+            })
+            .build()
+            .unwrap();
+        let near = 20 * 41 + 22;
+        let before = develop(&hdr, &plain).data[near * 4];
+        assert!(develop(&hdr, &glowing).data[near * 4] > before);
+        let broken = Bloom {
+            glare: -1.0,
+            ..Bloom::default()
+        };
+        assert!(RenderSettings::builder().bloom(broken).build().is_err());
+    }
+
+    #[test]
+    fn lens_flare() {
+        use saturno::prelude::LensFlare;
+        use saturno::render::flare::lens_flare;
+
+        // A black image with a bright light right of the center.
+        let mut hdr = HdrImage::new(61, 61, 4);
+        for i in 0..hdr.size() {
+            hdr.set_pixel(i, [0.0, 0.0, 0.0, 1.0]);
+        }
+        hdr.set_pixel(30 * 61 + 45, [1000.0, 1000.0, 1000.0, 1.0]);
+        let at = |image: &HdrImage, x: usize, y: usize| {
+            let pixel = image.get_pixel(y * 61 + x);
+            [pixel[0], pixel[1], pixel[2]]
+        };
+
+        // Ghosts at half its distance from the center on either side, and
+        // mirrored as far, the colors apart.
+        let settings = LensFlare {
+            starburst: 0.0,
+            halo_intensity: 0.0,
+            ..LensFlare::default()
+        };
+        let flared = lens_flare(&hdr, &settings, 1.0);
+        for x in [38, 23, 15] {
+            assert!(at(&flared, x, 30)[1] > 0.0);
+        }
+        let mirrored = at(&flared, 15, 30);
+        assert!(mirrored[0] != mirrored[2]);
+        assert!(at(&flared, 30, 5)[1].abs() < 1e-9);
+        assert!(at(&flared, 5, 5)[1].abs() < 1e-9);
+        // Nothing flares below the threshold.
+        let dim = lens_flare(&hdr, &settings, 1e-4);
+        assert_eq!(dim.data, hdr.data);
+
+        // The starburst shines rays out of the light, the halo rings the
+        // center for a light near it.
+        let settings = LensFlare {
+            ghosts: 0,
+            halo_intensity: 0.0,
+            ..LensFlare::default()
+        };
+        let burst = lens_flare(&hdr, &settings, 1.0);
+        assert!(at(&burst, 50, 30)[0] > 0.0 && at(&burst, 45, 25)[0] > 0.0);
+        assert_eq!(at(&burst, 55, 33), [0.0; 3]);
+        let mut centered = HdrImage::new(61, 61, 4);
+        centered.set_pixel(30 * 61 + 30, [1000.0, 1000.0, 1000.0, 1.0]);
+        let settings = LensFlare {
+            ghosts: 0,
+            starburst: 0.0,
+            halo_width: 0.5,
+            ..LensFlare::default()
+        };
+        let halo = lens_flare(&centered, &settings, 1.0);
+        // The ring is a quarter of the diagonal (21.6 pixels) around.
+        let ring = at(&halo, 30, 30 + 21)[0];
+        assert!(ring > 0.0 && ring > at(&halo, 30, 30 + 10)[0]);
+        assert!(at(&halo, 30, 30 + 28)[0] < 1e-9);
+
+        // Developed, the ghosts show.
+        let plain = RenderSettings::default();
+        let flaring = RenderSettings::builder()
+            .lens_flare(LensFlare::default())
+            .build()
+            .unwrap();
+        let ghost = 30 * 61 + 15;
+        let before = develop(&hdr, &plain).data[ghost * 4 + 1];
+        assert!(develop(&hdr, &flaring).data[ghost * 4 + 1] > before);
+        let broken = LensFlare {
+            ghost_spacing: 0.0,
+            ..LensFlare::default()
+        };
+        let built = RenderSettings::builder().lens_flare(broken).build();
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn camera_imperfections() {
+        use saturno::prelude::Imperfections;
+        use saturno::render::imperfections::camera_imperfections;
+
+        let gray = vec![[0.5; 3]; 41 * 31];
+        let at = |image: &[[Float; 3]], x: usize, y: usize| image[y * 41 + x];
+
+        // The vignette darkens the corners, not the center.
+        let settings = Imperfections {
+            chromatic_aberration: 0.0,
+            grain: 0.0,
+            ..Imperfections::default()
+        };
+        let vignetted = camera_imperfections(&gray, 41, 31, &settings);
+        assert!((at(&vignetted, 20, 15)[0] - 0.5).abs() < 1e-3);
+        assert!(at(&vignetted, 0, 0)[0] < 0.4);
+        assert!(at(&vignetted, 0, 0)[0] < at(&vignetted, 10, 8)[0]);
+
+        // Chromatic aberration splits an edge away from the center, not
+        // at it.
+        let mut edge = vec![[0.0; 3]; 41 * 31];
+        for (i, pixel) in edge.iter_mut().enumerate() {
+            if i % 41 >= 35 || (i % 41 >= 20 && i / 41 == 15) {
+                *pixel = [1.0; 3];
+            }
+        }
+        let settings = Imperfections {
+            vignette: 0.0,
+            grain: 0.0,
+            chromatic_aberration: 0.05,
+            ..Imperfections::default()
+        };
+        let split = camera_imperfections(&edge, 41, 31, &settings);
+        // The red image is larger, the blue one smaller: the edge moves
+        // out in red and in in blue.
+        let (outside, inside) = (at(&split, 34, 5), at(&split, 35, 5));
+        assert!(outside[2] > 0.0 && outside[1] == 0.0);
+        assert!(inside[0] < inside[1] && inside[1] == 1.0);
+        assert_eq!(at(&split, 20, 15), [1.0; 3]);
+
+        // Grain is the same for a seed, other for another, and about as
+        // strong as asked.
+        let settings = Imperfections {
+            vignette: 0.0,
+            chromatic_aberration: 0.0,
+            grain: 0.05,
+            ..Imperfections::default()
+        };
+        let grainy = camera_imperfections(&gray, 41, 31, &settings);
+        assert_eq!(grainy, camera_imperfections(&gray, 41, 31, &settings));
+        let reseeded = Imperfections {
+            seed: 7,
+            ..settings
+        };
+        assert_ne!(grainy, camera_imperfections(&gray, 41, 31, &reseeded));
+        let deviation =
+            (grainy.iter().map(|p| (p[0] - 0.5).powi(2)).sum::<Float>()
+                / grainy.len() as Float)
+                .sqrt();
+        assert!(deviation > 0.03 && deviation < 0.07, "{}", deviation);
+        assert!(grainy.iter().all(|p| p[0] == p[1] && p[1] == p[2]));
+
+        // Developed, after the tone mapping.
+        let mut hdr = HdrImage::new(41, 31, 4);
+        for i in 0..hdr.size() {
+            hdr.set_pixel(i, [0.25, 0.25, 0.25, 1.0]);
+        }
+        let plain = develop(&hdr, &RenderSettings::default());
+        let settings = RenderSettings::builder()
+            .imperfections(Imperfections {
+                grain: 0.0,
+                ..Imperfections::default()
+            })
+            .build()
+            .unwrap();
+        let imperfect = develop(&hdr, &settings);
+        assert!(imperfect.data[0] < plain.data[0]);
+        let center = (15 * 41 + 20) * 4;
+        assert_eq!(imperfect.data[center], plain.data[center]);
+        let broken = Imperfections {
+            vignette: 2.0,
+            ..Imperfections::default()
+        };
+        let built = RenderSettings::builder().imperfections(broken).build();
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn color_management() {
+        use saturno::prelude::ColorSpace;
+        use saturno::prelude::Lut3d;
+        use saturno::prelude::OutputTransform;
+        use saturno::render::display::aces;
+
+        let close = |a: [Float; 3], b: [Float; 3]| {
+            (0..3).all(|c| (a[c] - b[c]).abs() < 1e-3)
+        };
+        // White stays white, the sRGB red is inside the ACEScg gamut and
+        // the conversions round trip.
+        let white =
+            ColorSpace::LinearSrgb.convert([1.0; 3], ColorSpace::AcesCg);
+        assert!(close(white, [1.0; 3]));
+        let red =
+            ColorSpace::LinearSrgb.convert([1.0, 0.0, 0.0], ColorSpace::AcesCg);
+        assert!(close(red, [0.613, 0.070, 0.021]));
+        let color = [0.2, 0.5, 0.8];
+        for space in
+            [ColorSpace::Srgb, ColorSpace::AcesCg, ColorSpace::Aces2065]
+        {
+            let there = ColorSpace::LinearSrgb.convert(color, space);
+            let back = space.convert(there, ColorSpace::LinearSrgb);
+            assert!(close(back, color), "{:?}", space);
+        }
+        let half = ColorSpace::Srgb.convert([0.5; 3], ColorSpace::LinearSrgb);
+        assert!(close(half, [0.214; 3]));
+
+        // The ACES transform rolls off the highlights.
+        assert!(aces([0.18; 3])[0] > 0.1 && aces([0.18; 3])[0] < 0.2);
+        assert!(aces([100.0; 3])[0] > 0.99 && aces([100.0; 3])[0] <= 1.0);
+        assert_eq!(aces([0.0; 3]), aces([-1.0; 3]));
+
+        // A .cube LUT, here inverting the colors, over a domain to 2.
+        let mut cube = String::from("TITLE \"invert\"\nLUT_3D_SIZE 2\n");
+        cube.push_str("DOMAIN_MAX 2.0 2.0 2.0\n");
+        for i in 0..8 {
+            let bits = [i & 1, (i >> 1) & 1, (i >> 2) & 1];
+            let line = bits.map(|b| (1 - b).to_string()).join(" ");
+            cube.push_str(&format!("{}\n", line));
+        }
+        let lut = Lut3d::parse_cube(&cube).unwrap();
+        assert!(close(lut.apply([1.0, 0.0, 2.0]), [0.5, 1.0, 0.0]));
+        assert!(close(lut.apply([5.0, -1.0, 0.5]), [0.0, 1.0, 0.75]));
+        assert!(Lut3d::parse_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+
+        // Developed: through the LUT, as display values, and in ACEScg
+        // the same light as in linear sRGB.
+        let mut hdr = HdrImage::new(2, 1, 4);
+        hdr.set_pixel(0, [0.5, 0.5, 0.5, 1.0]);
+        hdr.set_pixel(1, [0.0, 1.0, 2.0, 1.0]);
+        let settings = RenderSettings::builder()
+            .output_transform(OutputTransform::Lut(lut))
+            .build()
+            .unwrap();
+        let developed = develop(&hdr, &settings);
+        assert_eq!(&developed.data[0..3], &[191, 191, 191]);
+        assert_eq!(&developed.data[4..7], &[255, 127, 0]);
+        hdr.set_pixel(1, [0.1, 0.4, 0.8, 1.0]);
+        let srgb = develop(&hdr, &RenderSettings::default());
+        let mut acescg = HdrImage::new(2, 1, 4);
+        for i in 0..acescg.size() {
+            let p = hdr.get_pixel(i);
+            let [r, g, b] = ColorSpace::LinearSrgb
+                .convert([p[0], p[1], p[2]], ColorSpace::AcesCg);
+            acescg.set_pixel(i, [r, g, b, p[3]]);
+        }
+        let settings = RenderSettings::builder()
+            .working_space(ColorSpace::AcesCg)
+            .build()
+            .unwrap();
+        let converted = develop(&acescg, &settings);
+        for (a, b) in converted.data.iter().zip(&srgb.data) {
+            assert!((*a as i32 - *b as i32).abs() <= 1);
+        }
+        let json = r#"{"working_space": "acescg", "output_transform": "aces"}"#;
+        let parsed: RenderSettings = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.working_space, ColorSpace::AcesCg);
+        assert_eq!(parsed.output_transform, OutputTransform::Aces);
+    }
+
+    #[test]
+    fn white_balance() {
+        use saturno::prelude::WhiteBalance;
+        use saturno::scene::color::blackbody_color;
+        use saturno::scene::color::multiply;
+
+        // The white point changes nothing, the light of a lamp balanced
+        // at its temperature turns neutral, keeping its luminance.
+        let neutral = WhiteBalance::default().matrix();
+        let color = [0.2, 0.5, 0.8];
+        let kept = multiply(&neutral, color);
+        assert!((0..3).all(|c| (kept[c] - color[c]).abs() < 1e-3));
+        let lamp = blackbody_color(3200.0);
+        let balance = WhiteBalance {
+            temperature: 3200.0,
+            tint: 0.0,
+        };
+        let balanced = multiply(&balance.matrix(), lamp);
+        assert!((0..3).all(|c| (balanced[c] - 1.0).abs() < 0.01));
+        // Balanced for a warm light, a white one turns blue.
+        let cool = multiply(&balance.matrix(), [1.0; 3]);
+        assert!(cool[2] > cool[1] && cool[1] > cool[0]);
+        // A green tint is taken out of the green.
+        let tinted = WhiteBalance {
+            tint: 0.2,
+            ..WhiteBalance::default()
+        };
+        let magenta = multiply(&tinted.matrix(), [1.0; 3]);
+        assert!(magenta[1] < magenta[0] && magenta[1] < magenta[2]);
+
+        // Developed, a gray lit by the lamp comes out gray.
+        let mut hdr = HdrImage::new(1, 1, 4);
+        let [r, g, b] = lamp.map(|c| 0.25 * c);
+        hdr.set_pixel(0, [r, g, b, 1.0]);
+        let settings = RenderSettings::builder()
+            .white_balance(balance)
+            .build()
+            .unwrap();
+        let developed = develop(&hdr, &settings);
+        assert!(developed.data[0].abs_diff(developed.data[2]) <= 2);
+        let warm = develop(&hdr, &RenderSettings::default());
+        assert!(warm.data[0] > warm.data[2] + 20);
+        let broken = WhiteBalance {
+            temperature: 0.0,
+            tint: 0.0,
+        };
+        let built = RenderSettings::builder().white_balance(broken).build();
+        assert!(built.is_err());
+    }
+
+    #[test]
+    fn exposure_analysis() {
+        use saturno::render::analysis::false_color;
+        use saturno::render::analysis::waveform;
+        use saturno::render::analysis::Histogram;
+
+        // Black, middle gray, a stop over, near and over the clipping.
+        let levels = [0.0, 0.18, 0.36, 0.9, 4.0];
+        let mut hdr = HdrImage::new(5, 1, 4);
+        for (i, level) in levels.iter().enumerate() {
+            hdr.set_pixel(i, [*level, *level, *level, 1.0]);
+        }
+
+        let histogram = Histogram::new(&hdr, 1.0);
+        assert_eq!(histogram.bins.iter().sum::<u64>(), 5);
+        assert_eq!((histogram.black, histogram.clipped), (1, 1));
+        assert!((histogram.clipped_fraction() - 0.2).abs() < 1e-12);
+        assert_eq!(histogram.bins[0], 1);
+        assert_eq!(histogram.bins[histogram.bin(0.0)], 1);
+        assert_eq!(histogram.bin(1.0) - histogram.bin(0.0), 3);
+        // Exposed down, nothing clips.
+        assert_eq!(Histogram::new(&hdr, 0.2).clipped, 0);
+        let bars = histogram.image(96, 40);
+        assert_eq!((bars.width, bars.height), (96, 40));
+
+        let colors = false_color(&hdr, 1.0);
+        let at = |i: usize| &colors.data[i * 4..i * 4 + 3];
+        assert_eq!(at(0), &[128, 0, 160]);
+        assert_eq!(at(1), &[0, 190, 0]);
+        assert_eq!(at(2), &[255, 130, 190]);
+        assert_eq!(at(3), &[255, 230, 0]);
+        assert_eq!(at(4), &[255, 0, 0]);
+
+        // A pixel per column, higher for the brighter ones.
+        let wave = waveform(&hdr, 1.0, 32, -10.0, 6.0);
+        let row = |x: u32| {
+            (0..32)
+                .find(|y| wave.data[((y * 5 + x) * 4 + 1) as usize] > 0)
+                .unwrap()
+        };
+        assert!(row(1) > row(2) && row(2) > row(3) && row(3) > row(4));
+        assert_eq!(row(0), 31);
+    }
+
+    #[test]
+    fn focus_peaking() {
+        // Focused on the front of the small sphere, through a wide lens.
+        let camera = Camera::new(
+            90.0,
+            40,
+            20,
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -0.5, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.2,
+        );
+        assert!((camera.focus_distance() - 0.5).abs() < 1e-9);
+        assert_eq!(camera.blur_radius(0.5), 0.0);
+        // A tenth of the lens radius over a pixel (0.05 wide) per unit of
+        // depth away, relative to the depth.
+        assert!((camera.blur_radius(1.0) - 0.1 * 0.5 / 0.05).abs() < 1e-9);
+        assert!(camera.blur_radius(2.0) > camera.blur_radius(1.0));
+
+        let canvas = Canvas::new(scenes::two_spheres_normals(), camera);
+        let settings = RenderSettings::builder().size(40, 20).build().unwrap();
+        let blur = canvas.focus_blur(&settings).unwrap();
+        let center = 10 * 40 + 20;
+        assert!(blur[center] < 0.1);
+        assert!(blur[0].is_infinite());
+
+        let image = canvas.render_focus_peaking(&settings).unwrap();
+        let pixel = |i: usize| &image.data[i * 4..i * 4 + 3];
+        assert!(pixel(center)[1] > pixel(center)[0] + 50);
+        assert_eq!(pixel(0), &[0, 0, 0]);
+        let blurred =
+            blur.iter().position(|b| b.is_finite() && *b > 1.0).unwrap();
+        let gray = pixel(blurred);
+        assert!(gray[0] == gray[1] && gray[1] == gray[2]);
+
+        // Without a lens everything is in focus.
+        let pinhole = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(40, 20),
+        );
+        let sharp = pinhole.focus_blur(&settings).unwrap();
+        assert!(sharp.iter().all(|b| *b == 0.0 || b.is_infinite()));
+    }
+
+    #[test]
+    fn render_metadata() {
+        use saturno::render::metadata::scene_hash;
+        use saturno::render::metadata::RenderMetadata;
+        use std::time::Duration;
+
+        let settings = RenderSettings::builder()
+            .samples(16)
+            .seed(7)
+            .spectral(true)
+            .build()
+            .unwrap();
+        let metadata = RenderMetadata::new(&settings)
+            .scene("two_spheres", b"two_spheres")
+            .render_time(Duration::from_millis(1500));
+        assert_eq!(metadata.get("saturno:seed"), Some("7"));
+        assert_eq!(metadata.get("saturno:samples"), Some("16"));
+        assert_eq!(
+            metadata.get("saturno:integrator"),
+            Some("spectral path tracing")
+        );
+        assert_eq!(metadata.get("saturno:render_time"), Some("1.500s"));
+        let hash = format!("{:016x}", scene_hash(b"two_spheres"));
+        assert_eq!(metadata.get("saturno:scene_hash"), Some(hash.as_str()));
+        assert_ne!(scene_hash(b"a"), scene_hash(b"b"));
+        assert!(metadata.get("saturno:settings").unwrap().contains("seed"));
+
+        // In the Radiance header, a line per entry.
+        let mut hdr = Vec::new();
+        let image = HdrImage::new(2, 1, 4);
+        output::encode_hdr_metadata(&mut hdr, &image, &metadata).unwrap();
+        let header = String::from_utf8_lossy(&hdr);
+        assert!(header.contains("\nsaturno:samples=16\n"));
+        assert!(header.contains("\nsaturno:scene=two_spheres\n"));
+
+        // In text chunks after the header of a PNG, which read back.
+        let mut png = vec![137, 80, 78, 71, 13, 10, 26, 10];
+        for (kind, data) in [(*b"IHDR", vec![0; 13]), (*b"IEND", vec![])] {
+            png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            png.extend_from_slice(&kind);
+            png.extend_from_slice(&data);
+            png.extend_from_slice(&[0; 4]);
+        }
+        let mut unicode = metadata.clone();
+        unicode.insert("saturno:scene", "\u{571f}\u{661f}");
+        let tagged = output::add_png_text(&png, &unicode).unwrap();
+        assert_eq!(&tagged[12..16], b"IHDR");
+        assert_eq!(&tagged[tagged.len() - 8..tagged.len() - 4], b"IEND");
+        let text = output::png_text(&tagged).unwrap();
+        assert_eq!(text, unicode.entries);
+        // The chunks carry their CRC: that of IEND is well known.
+        let plain =
+            output::add_png_text(&png, &RenderMetadata::default()).unwrap();
+        assert_eq!(&plain[41..45], &[0xae, 0x42, 0x60, 0x82]);
+        assert!(output::png_text(b"not a png").is_err());
+    }
+
+    #[test]
+    fn multi_camera_batch() {
+        use saturno::render::contact_sheet::contact_sheet;
+        use saturno::render::contact_sheet::sheet_columns;
+        use saturno::render::contact_sheet::SHEET_BACKGROUND;
+        use saturno::scene::description::SceneDescription;
+
+        let scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+                "cameras": {
+                    "top": {"look_from": [0, 3, 1], "look_at": [0, 0, -1]},
+                    "away": {"look_from": [0, 0, 0], "look_at": [0, 0, 1]}
+                },
+                "spheres": [{
+                    "center": [0, 0, -1],
+                    "radius": 0.5,
+                    "material": {"type": "lambertian", "albedo": [0.5, 0.5, 0.5]}
+                }]
+            }"#,
+        )
+        .unwrap();
+        let views = scene.views();
+        let names: Vec<&str> = views.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["camera", "away", "top"]);
+
+        let (width, height) = (16, 8);
+        let cameras = views
+            .iter()
+            .map(|(name, c)| (name.to_string(), c.build(width, height)))
+            .collect();
+        let mut canvas = Canvas::new(
+            scene.actors().unwrap(),
+            scene.camera.build(width, height),
+        );
+        let settings = RenderSettings::builder()
+            .size(width, height)
+            .samples(1)
+            .seed(1)
+            .build()
+            .unwrap();
+        let before = canvas.render_scene(&settings).unwrap();
+        let images = canvas.render_cameras(&settings, cameras).unwrap();
+        assert_eq!(images.len(), 3);
+        assert_eq!(images[0].0, "camera");
+        // The main camera renders as before, the others see otherwise.
+        assert_eq!(images[0].1.data, before.data);
+        assert_ne!(images[1].1.data, before.data);
+        assert_ne!(images[2].1.data, before.data);
+        // The camera of the canvas is restored.
+        let after = canvas.render_scene(&settings).unwrap();
+        assert_eq!(after.data, before.data);
+
+        assert_eq!(sheet_columns(1), 1);
+        assert_eq!(sheet_columns(3), 2);
+        assert_eq!(sheet_columns(9), 3);
+        let images: Vec<&saturno::render::Image> =
+            images.iter().map(|(_, i)| i).collect();
+        let sheet = contact_sheet(&images, 0, 2);
+        // Two columns, two rows, 2 pixels apart and around.
+        assert_eq!((sheet.width, sheet.height), (2 + 2 * 18, 2 + 2 * 10));
+        let pixel =
+            |x: u32, y: u32| [0, 1, 2, 3].map(|c| sheet.get_value(x, y, c));
+        assert_eq!(pixel(0, 0), SHEET_BACKGROUND);
+        assert_eq!(pixel(19, 5), SHEET_BACKGROUND);
+        assert_eq!(pixel(30, 15), SHEET_BACKGROUND);
+        for (n, image) in images.iter().enumerate() {
+            let (left, top) =
+                (2 + 18 * (n as u32 % 2), 2 + 10 * (n as u32 / 2));
+            for (x, y) in [(0, 0), (7, 3), (15, 7)] {
+                let expected = [0, 1, 2, 3].map(|c| image.get_value(x, y, c));
+                assert_eq!(pixel(left + x, top + y), expected);
+            }
+        }
+        assert_eq!(contact_sheet(&images, 3, 0).width, 3 * 16);
+    }
+
+    #[test]
+    fn scene_stats() {
+        use saturno::prelude::Environment;
+        use saturno::prelude::Mask;
+        use saturno::prelude::Mesh;
+        use saturno::prelude::Mix;
+        use saturno::prelude::Scattering;
+        use saturno::prelude::Spot;
+        use saturno::prelude::Sun;
+        use saturno::prelude::Texture;
+        use saturno::prelude::TextureSampler;
+
+        let texels = vec![[0.5, 0.5, 0.5, 1.0]; 16];
+        let texture: Arc<dyn TextureSampler> =
+            Arc::new(Texture::new(4, 4, texels).unwrap());
+        let textured: Arc<dyn Scattering> = Arc::new(Lambertian::textured(
+            arr1(&[1.0, 1.0, 1.0, 1.0]),
+            texture.clone(),
+        ));
+        let sphere = |x: Float, material: Arc<dyn Scattering>| {
+            Box::new(Sphere {
+                center: arr1(&[x, 0.0, -2.0, 1.0]),
+                radius: 0.5,
+                material,
+            }) as Box<dyn RayTraceable>
+        };
+        let mesh = Mesh::new(
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 1.0, 3.0],
+            ],
+            vec![[0.0, 0.0]; 4],
+            vec![[0, 1, 2], [1, 3, 2]],
+            textured.clone(),
+        )
+        .unwrap();
+        let mix = Arc::new(Mix::new(
+            textured.clone(),
+            Arc::new(Metal::new(
+                arr1(&[0.8, 0.8, 0.8, 1.0]),
+                Shading::COLOR,
+                0.0,
+            )),
+            Mask::Uniform { value: 0.5 },
+        ));
+        let mut canvas = Canvas::new(
+            vec![
+                sphere(-1.0, textured.clone()),
+                sphere(1.0, mix),
+                Box::new(mesh),
+            ],
+            scenes::two_spheres_camera(20, 10),
+        );
+        let mut spot = Spot::new([0.0, 2.0, 0.0], [0.0, -1.0, 0.0], 10.0);
+        spot.gobo = Some(texture);
+        let map = Texture::new(2, 1, vec![[1.0; 4]; 2]).unwrap();
+        canvas.lights = vec![
+            Arc::new(spot),
+            Arc::new(Sun::new([0.0, 1.0, 0.0], 1.0)),
+            Arc::new(Environment::new(map)),
+        ];
+
+        let stats = canvas.stats();
+        assert_eq!(stats.actor_count(), 3);
+        assert_eq!(stats.actors["sphere"], 2);
+        assert_eq!(stats.actors["mesh"], 1);
+        assert_eq!(stats.triangles, 2);
+        // The texture shared by the materials and the gobo counts once:
+        // 4x4, 2x2 and 1x1 texels, and the 2x1 and 1x1 of the map.
+        assert_eq!(stats.textures, 2);
+        let texel = std::mem::size_of::<[Float; 4]>();
+        assert_eq!(stats.texture_memory, (21 + 3) * texel);
+        let bounds = stats.bounds.unwrap();
+        assert_eq!(bounds.min, [-1.5, -0.5, -2.5]);
+        assert_eq!(bounds.max, [1.5, 1.0, 3.0]);
+        assert_eq!(stats.unbounded, 0);
+        assert_eq!(stats.light_count(), 3);
+        assert_eq!(stats.lights["spot"], 1);
+        assert_eq!(stats.lights["sun"], 1);
+        assert_eq!(stats.lights["environment"], 1);
+
+        let report = stats.to_string();
+        assert!(report.contains("actors: 3 (1 mesh, 2 sphere)"));
+        assert!(report.contains("lights: 3 (1 environment, 1 spot, 1 sun)"));
+
+        let empty = Canvas::new(vec![], scenes::two_spheres_camera(20, 10));
+        assert_eq!(empty.stats().bounds, None);
+        assert!(empty.stats().to_string().contains("bounds: none"));
+    }
+
+    #[test]
+    fn scene_units() {
+        use saturno::io::mesh::parse_obj;
+        use saturno::io::mesh::parse_stl;
+        use saturno::io::mesh::read_mesh;
+        use saturno::prelude::Units;
+        use saturno::scene::description::SceneDescription;
+
+        assert_eq!(Units::Millimeters.factor(Units::Meters), 0.001);
+        assert!((Units::Feet.factor(Units::Inches) - 12.0).abs() < 1e-12);
+        assert_eq!(Units::parse("cm"), Ok(Units::Centimeters));
+        assert!(Units::parse("parsecs").is_err());
+
+        // A quad (split in two triangles) sharing its corners, the last
+        // one referred to from the end.
+        let obj = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                   vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                   f 1/1 2/2 3/3 -1/-1\n";
+        let quad = parse_obj(obj).unwrap();
+        assert_eq!(quad.positions.len(), 4);
+        assert_eq!(quad.triangles, [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(quad.uvs[2], [1.0, 1.0]);
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+
+        let ascii = "solid t\nfacet normal 0 0 1\nouter loop\n\
+                     vertex 0 0 0\nvertex 10 0 0\nvertex 0 20 0\n\
+                     endloop\nendfacet\nendsolid t\n";
+        let facet = parse_stl(ascii.as_bytes()).unwrap();
+        assert_eq!(facet.positions[2], [0.0, 20.0, 0.0]);
+        let mut binary = vec![0u8; 80];
+        binary.extend(1u32.to_le_bytes());
+        for value in [0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 10.0, 0.0, 0.0] {
+            binary.extend(value.to_le_bytes());
+        }
+        for value in [0.0f32, 20.0, 0.0] {
+            binary.extend(value.to_le_bytes());
+        }
+        binary.extend([0, 0]);
+        assert_eq!(parse_stl(&binary).unwrap(), facet);
+
+        // STL files are in millimeters unless told otherwise.
+        let mut path = init_image_testing();
+        path.push("scene_units.stl");
+        std::fs::write(&path, &binary).unwrap();
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let mesh =
+            read_mesh(&path, None, Units::Meters, material.clone()).unwrap();
+        let bounds = mesh.bounding_box().unwrap();
+        assert!((bounds.max[0] - 0.01).abs() < 1e-9);
+        assert!((bounds.max[1] - 0.02).abs() < 1e-9);
+        let mesh = read_mesh(
+            &path,
+            Some(Units::Centimeters),
+            Units::Millimeters,
+            material,
+        )
+        .unwrap();
+        assert!((mesh.bounding_box().unwrap().max[1] - 200.0).abs() < 1e-9);
+
+        // An omni light of 1 candela lights a point 1 meter away with 1
+        // lux, in a scene in meters or in centimeters.
+        let scene = |units: &str| -> SceneDescription {
+            serde_json::from_str(&format!(
+                r#"{{"units": "{}",
+                    "camera": {{"look_from": [0, 0, 0], "look_at": [0, 0, -1]}},
+                    "lights": [{{"type": "omni", "position": [0, 0, 0],
+                                 "intensity": 1}}]}}"#,
+                units
+            ))
+            .unwrap()
+        };
+        let radiance = |scene: SceneDescription, distance: Float| {
+            let lights = scene.lights().unwrap();
+            let point = arr1(&[distance, 0.0, 0.0, 1.0]);
+            lights[0].sample(&point).unwrap().radiance[0]
+        };
+        let meters = radiance(scene("meters"), 1.0);
+        let centimeters = radiance(scene("centimeters"), 100.0);
+        assert!((meters - 1.0).abs() < 1e-9);
+        assert!((centimeters - meters).abs() < 1e-9);
+        let default: SceneDescription = serde_json::from_str(
+            r#"{"camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]}}"#,
+        )
+        .unwrap();
+        assert_eq!(default.units, Units::Meters);
+    }
+
+    #[test]
+    fn watertight_triangles() {
+        use saturno::prelude::Hittable;
+        use saturno::prelude::Mesh;
+        use saturno::scene::actor::Aabb;
+        use saturno::scene::actor::Hit;
+
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        // A fan of thin triangles around the origin, in the plane z = 0.
+        let sides = 64;
+        let mut positions = vec![[0.0, 0.0, 0.0]];
+        for i in 0..sides {
+            let angle = i as Float / sides as Float * 2.0 * consts::PI;
+            positions.push([angle.cos(), angle.sin(), 0.0]);
+        }
+        let triangles = (0..sides)
+            .map(|i| [0, 1 + i, 1 + (i + 1) % sides])
+            .collect();
+        let uvs = vec![[0.0, 0.0]; positions.len()];
+        let fan =
+            Mesh::new(positions.clone(), uvs, triangles, material.clone())
+                .unwrap();
+
+        // Rays from all around through the shared vertex and along the
+        // shared edges never slip between the triangles.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut random = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as Float / (1u64 << 53) as Float
+        };
+        for i in 0..2000 {
+            let edge = positions[1 + i % sides];
+            let along = if i % 3 == 0 { 0.0 } else { random() * 0.9 };
+            let target = edge.map(|c| c * along);
+            let origin = [
+                random() * 4.0 - 2.0,
+                random() * 4.0 - 2.0,
+                if i % 2 == 0 { 3.0 } else { -3.0 },
+            ];
+            let direction = [0, 1, 2].map(|c| target[c] - origin[c]);
+            let ray = Ray::new(
+                arr1(&[origin[0], origin[1], origin[2], 1.0]),
+                arr1(&[direction[0], direction[1], direction[2], 0.0]),
+            );
+            let mut hit = Hit::new();
+            assert!(
+                fan.is_hit(&ray, 1e-6, Float::INFINITY, &mut hit),
+                "ray {} from {:?} to {:?} leaked",
+                i,
+                origin,
+                target
+            );
+            assert!(hit.point[2].abs() < 1e-9);
+        }
+
+        // Seen edge-on, in its plane, the fan is not hit.
+        let ray = Ray::new(
+            arr1(&[-2.0, 0.01, 0.0, 1.0]),
+            arr1(&[1.0, 0.0, 0.0, 0.0]),
+        );
+        let mut hit = Hit::new();
+        assert!(!fan.is_hit(&ray, 1e-6, Float::INFINITY, &mut hit));
+
+        // The boxes of flat geometry are crossed, grazing rays included.
+        let flat = Aabb::new([-1.0, -1.0, 0.0], [1.0, 1.0, 0.0]);
+        assert!(flat.is_hit(
+            &[0.3, 0.2, 1.0],
+            &[Float::INFINITY, Float::INFINITY, -1.0],
+            0.0,
+            10.0
+        ));
+        let unit = Aabb::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
+        let direction: [Float; 3] = [1.0, 1.0, 0.0];
+        let inverse = direction.map(|d| 1.0 / d);
+        // Rays grazing an edge of a box hit it, those passing by do not.
+        assert!(unit.is_hit(&[-0.5, 0.5, 0.5], &inverse, 0.0, 10.0));
+        assert!(unit.is_hit(&[-1.0, 0.0, 0.5], &inverse, 0.0, 10.0));
+        assert!(!unit.is_hit(
+            &[-1.0, 0.1, 0.5],
+            &[1.0, -1.0, Float::INFINITY],
+            0.0,
+            10.0
+        ));
+    }
+
+    #[test]
+    fn camera_relative() {
+        let scene = |at: Float| {
+            let actors = vec![Box::new(Sphere {
+                center: arr1(&[at, 0.0, -1.0, 1.0]),
+                radius: 0.5,
+                material: Arc::new(Primary::new(
+                    arr1(&[1.0, 0.0, 0.0, 1.0]),
+                    Shading::NORMALS,
+                )),
+            }) as Box<dyn RayTraceable>];
+            let camera = Camera::new(
+                90.0,
+                40,
+                20,
+                arr1(&[at, 0.0, 0.0, 1.0]),
+                arr1(&[at, 0.0, -1.0, 1.0]),
+                arr1(&[0.0, 1.0, 0.0, 0.0]),
+                0.0,
+            );
+            Canvas::new(actors, camera)
+        };
+        let settings = RenderSettings::builder()
+            .size(40, 20)
+            .samples(1)
+            .build()
+            .unwrap();
+        let near = scene(0.0).render_scene(&settings).unwrap();
+
+        // So far from the origin, the hit points round to the spacing of
+        // the doubles there (2 units) and the normals of the sphere are
+        // lost.
+        let far = 1e16;
+        let mut canvas = scene(far);
+        let lost = canvas.render_scene(&settings).unwrap();
+        assert_ne!(lost.data, near.data);
+
+        // Moved around the camera, it renders as it does at the origin.
+        canvas.recenter();
+        assert_eq!(canvas.world_origin(), [1e16, 0.0, 0.0]);
+        let image = canvas.render_scene(&settings).unwrap();
+        assert_eq!(image.data, near.data);
+
+        // Recentering again changes nothing.
+        canvas.recenter();
+        assert_eq!(canvas.world_origin(), [1e16, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn fractals() {
+        use saturno::prelude::Fractal;
+        use saturno::prelude::FractalKind;
+        use saturno::prelude::Hittable;
+        use saturno::prelude::Palette;
+        use saturno::prelude::TextureSampler;
+        use saturno::scene::actor::Hit;
+
+        // The estimates are lower bounds of the distances: the cube of
+        // the sponge, the bulb within the sphere of radius 1.2.
+        let sponge = FractalKind::MengerSponge { iterations: 4 };
+        assert!((sponge.distance([3.0, 0.0, 0.0]).0 - 2.0).abs() < 1e-9);
+        assert!(sponge.distance([1.0, 1.0, 0.5]).0.abs() < 1e-9);
+        // The hole through the middle of every face.
+        assert!(sponge.distance([0.0, 0.0, 0.9]).0 > 0.0);
+        let bulb = FractalKind::Mandelbulb {
+            power: 8.0,
+            iterations: 12,
+        };
+        assert!(bulb.distance([0.0, 0.0, 3.0]).0 < 3.0 - 1.2 + 1e-9);
+        assert!(bulb.distance([0.0, 0.0, 0.0]).0 <= 0.0);
+
+        // Rays marched along the axes stop on the faces of the sponge.
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let fractal = Fractal::new(sponge, [0.0, 2.0, 0.0], 0.5, material);
+        let ray = Ray::new(
+            arr1(&[0.25, 2.1, 5.0, 1.0]),
+            arr1(&[0.0, 0.0, -2.0, 0.0]),
+        );
+        let mut hit = Hit::new();
+        assert!(fractal.is_hit(&ray, 1e-6, Float::INFINITY, &mut hit));
+        assert!((hit.point[2] - 0.5).abs() < 1e-3);
+        assert!((hit.normal[2] - 1.0).abs() < 1e-3);
+        assert!((0.0..=1.0).contains(&hit.uv[0]));
+        // Through the hole in the middle it goes through.
+        let ray =
+            Ray::new(arr1(&[0.0, 2.0, 5.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        assert!(!fractal.is_hit(&ray, 1e-6, Float::INFINITY, &mut hit));
+
+        // The palette blends its colors along u.
+        let palette =
+            Palette::new(vec![[1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]).unwrap();
+        assert_eq!(palette.sample([0.5, 0.5], 0.0), [0.5, 0.0, 0.5, 1.0]);
+        assert_eq!(palette.sample([2.0, 0.5], 0.0), [0.0, 0.0, 1.0, 1.0]);
+        assert!(Palette::new(vec![]).is_err());
+
+        // Both render, colored by their orbit traps.
+        let settings = RenderSettings::builder()
+            .size(24, 16)
+            .samples(1)
+            .build()
+            .unwrap();
+        for kind in [sponge, bulb] {
+            let canvas = Canvas::new(
+                scenes::fractal(kind),
+                scenes::fractal_camera(24, 16),
+            );
+            let image = canvas.render_scene(&settings).unwrap();
+            let center = (8 * 24 + 12) * 4;
+            let pixel = &image.data[center..center + 3];
+            assert_ne!(pixel[0], pixel[2], "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn metaballs() {
+        use saturno::prelude::Charge;
+        use saturno::prelude::Hittable;
+        use saturno::prelude::Metaballs;
+        use saturno::scene::actor::Hit;
+
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let charge = |x: Float| Charge {
+            center: [x, 0.0, 0.0],
+            radius: 1.0,
+            strength: 1.0,
+        };
+        let ray = |x: Float| {
+            Ray::new(arr1(&[x, 0.0, 5.0, 1.0]), arr1(&[0.0, 0.0, -2.0, 0.0]))
+        };
+
+        // A single charge is a sphere, where (1 - d²)³ = 0.5.
+        let single = Metaballs::new(vec![charge(0.0)], 0.5, material.clone());
+        let radius = (1.0 - Float::powf(0.5, 1.0 / 3.0)).sqrt();
+        let mut hit = Hit::new();
+        assert!(single.is_hit(&ray(0.0), 1e-6, Float::INFINITY, &mut hit));
+        assert!((hit.point[2] - radius).abs() < 1e-9);
+        assert!((hit.normal[2] - 1.0).abs() < 1e-9);
+        assert!(!single.is_hit(&ray(0.6), 1e-6, Float::INFINITY, &mut hit));
+
+        // Two charges further apart than twice that blend into one blob,
+        // bridged in the middle with a smooth normal.
+        let pair = Metaballs::new(
+            vec![charge(-0.5), charge(0.5)],
+            0.5,
+            material.clone(),
+        );
+        assert!(pair.is_hit(&ray(0.0), 1e-6, Float::INFINITY, &mut hit));
+        assert!(hit.point[2] > 0.0);
+        assert!((hit.normal[2] - 1.0).abs() < 1e-9);
+        assert!(pair.is_hit(&ray(0.2), 1e-6, Float::INFINITY, &mut hit));
+        let (value, _) =
+            pair.field(&[hit.point[0], hit.point[1], hit.point[2]]);
+        assert!(value.abs() < 1e-9);
+        // Rising from the waist towards the charge.
+        assert!(hit.normal[0] < 0.0 && hit.normal[2] > 0.0);
+
+        // A negative charge digs a hole through the middle.
+        let mut holed = Metaballs::new(
+            vec![
+                charge(-0.5),
+                charge(0.5),
+                Charge {
+                    center: [0.0, 0.0, 0.0],
+                    radius: 0.5,
+                    strength: -2.0,
+                },
+            ],
+            0.5,
+            material,
+        );
+        assert!(!holed.is_hit(&ray(0.0), 1e-6, Float::INFINITY, &mut hit));
+        assert!(holed.validate().is_ok());
+        let bounds = holed.bounding_box().unwrap();
+        assert_eq!(
+            (bounds.min, bounds.max),
+            ([-1.5, -1.0, -1.0], [1.5, 1.0, 1.0])
+        );
+        holed.threshold = 0.0;
+        assert!(holed.validate().is_err());
+    }
+
+    #[test]
+    fn clipping_planes() {
+        use saturno::prelude::Cap;
+        use saturno::prelude::ClipPlane;
+        use saturno::prelude::Clipped;
+        use saturno::prelude::Hittable;
+        use saturno::prelude::Scattering;
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::HittableList;
+        use saturno::scene::description::SceneDescription;
+
+        let gray = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let red = Arc::new(Lambertian::new(
+            arr1(&[1.0, 0.0, 0.0, 1.0]),
+            Shading::COLOR,
+        ));
+        let sphere = || {
+            Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, 0.0, 1.0]),
+                radius: 1.0,
+                material: gray.clone(),
+            }) as Box<dyn RayTraceable>
+        };
+        // The half of the sphere facing the rays cut away.
+        let plane = ClipPlane {
+            point: [0.0, 0.0, 0.0],
+            normal: [0.0, 0.0, 2.0],
+        };
+        assert!(plane.clips(&[0.0, 0.0, 0.5]));
+        let ray = |x: Float| {
+            Ray::new(arr1(&[x, 0.0, 5.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]))
+        };
+
+        // Open, the ray goes through the cut to the inside of the back.
+        let mut open = Clipped::new(sphere(), vec![plane]);
+        let mut hit = Hit::new();
+        assert!(open.is_hit(&ray(0.5), 1e-6, Float::INFINITY, &mut hit));
+        assert!((hit.point[2] + Float::sqrt(0.75)).abs() < 1e-9);
+        assert!(hit.normal[2] < 0.0);
+        assert!(!open.is_hit(&ray(1.5), 1e-6, Float::INFINITY, &mut hit));
+
+        // Capped, it stops on the cut, facing it.
+        open.cap = Some(Cap::Material(red.clone()));
+        assert!(open.is_hit(&ray(0.5), 1e-6, Float::INFINITY, &mut hit));
+        assert!(hit.point[2].abs() < 1e-5);
+        assert_eq!(hit.normal[2], 1.0);
+        assert_eq!(hit.material.name(), red.name());
+        // Going out through the cut, it leaves without hitting it.
+        let out =
+            Ray::new(arr1(&[0.0, 0.0, -0.5, 1.0]), arr1(&[0.0, 0.0, 1.0, 0.0]));
+        assert!(!open.is_hit(&out, 1e-6, Float::INFINITY, &mut hit));
+        // Rays missing the kept half miss whatever is in the other.
+        let grazing =
+            Ray::new(arr1(&[-5.0, 0.0, 0.5, 1.0]), arr1(&[1.0, 0.0, 0.0, 0.0]));
+        assert!(!open.is_hit(&grazing, 1e-6, Float::INFINITY, &mut hit));
+
+        // Nested and capped by their own materials, the inner actor shows
+        // in the cut of the outer one.
+        let inner = Clipped {
+            actor: Box::new(Sphere {
+                center: arr1(&[0.0, 0.0, 0.0, 1.0]),
+                radius: 0.75,
+                material: red.clone(),
+            }),
+            planes: vec![plane],
+            cap: Some(Cap::Actor),
+        };
+        open.cap = Some(Cap::Actor);
+        let world = HittableList::new(vec![Box::new(open), Box::new(inner)]);
+        assert!(world.is_hit(&ray(0.5), 1e-6, Float::INFINITY, &mut hit));
+        assert!(hit.point[2].abs() < 1e-5);
+        assert_eq!(hit.material.name(), red.name());
+        assert!(world.is_hit(&ray(0.9), 1e-6, Float::INFINITY, &mut hit));
+        assert_eq!(hit.material.name(), gray.name());
+
+        // The planes of a scene cut every actor.
+        let scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 5], "look_at": [0, 0, 0]},
+                "spheres": [{
+                    "center": [0, 0, 0],
+                    "radius": 1,
+                    "material": {"type": "lambertian", "albedo": [0.5, 0.5, 0.5]}
+                }],
+                "clipping": {
+                    "planes": [{"point": [0, 0, 0], "normal": [0, 0, 1]}],
+                    "cap": {"type": "lambertian", "albedo": [1, 0, 0]}
+                }
+            }"#,
+        )
+        .unwrap();
+        let actors = scene.actors().unwrap();
+        assert!(actors[0].is_hit(&ray(0.5), 1e-6, Float::INFINITY, &mut hit));
+        assert!(hit.point[2].abs() < 1e-5);
+        assert_eq!(hit.material.name(), red.name());
+    }
+
+    #[test]
+    fn visibility_flags() {
+        use saturno::prelude::Hittable;
+        use saturno::prelude::RayKind;
+        use saturno::prelude::Sun;
+        use saturno::prelude::Visibility;
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::HittableList;
+        use saturno::scene::description::SceneDescription;
+
+        // A red ball over the ground, lit from the side: its shadow falls
+        // at x = -1, left of the ball seen from above.
+        let render = |visibility: Visibility| {
+            let actors = vec![
+                Box::new(Sphere {
+                    center: arr1(&[0.0, -1000.0, 0.0, 1.0]),
+                    radius: 1000.0,
+                    material: Arc::new(Lambertian::new(
+                        arr1(&[0.5, 0.5, 0.5, 1.0]),
+                        Shading::COLOR,
+                    )),
+                }) as Box<dyn RayTraceable>,
+                visibility.apply(Box::new(Sphere {
+                    center: arr1(&[0.0, 1.0, 0.0, 1.0]),
+                    radius: 0.25,
+                    material: Arc::new(Lambertian::new(
+                        arr1(&[0.9, 0.1, 0.1, 1.0]),
+                        Shading::COLOR,
+                    )),
+                })),
+            ];
+            let camera = Camera::new(
+                60.0,
+                40,
+                40,
+                arr1(&[0.0, 3.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 0.0]),
+                0.0,
+            );
+            let mut canvas = Canvas::new(actors, camera);
+            canvas.add_light(Arc::new(Sun::new([1.0, 1.0, 0.0], consts::PI)));
+            let settings = RenderSettings::builder()
+                .size(40, 40)
+                .samples(4)
+                .seed(3)
+                .build()
+                .unwrap();
+            canvas.render_hdr(&settings).unwrap()
+        };
+        let (ball, shadow, lit) = (20 * 40 + 20, 20 * 40 + 8, 20 * 40 + 31);
+        let red = |pixel: [Float; 4]| pixel[0] > 2.0 * pixel[1];
+        let shaded = |image: &HdrImage| {
+            image.get_pixel(shadow)[1] < image.get_pixel(lit)[1] - 0.2
+        };
+
+        let all = render(Visibility::default());
+        assert!(red(all.get_pixel(ball)));
+        assert!(shaded(&all));
+
+        // Hidden from the camera, it still casts its shadow.
+        let unseen = render(Visibility {
+            camera: false,
+            ..Visibility::default()
+        });
+        assert!(!red(unseen.get_pixel(ball)));
+        assert!(shaded(&unseen));
+
+        // Without shadows, it is seen but lets the sun through.
+        let shadowless = render(Visibility {
+            shadow: false,
+            ..Visibility::default()
+        });
+        assert!(red(shadowless.get_pixel(ball)));
+        assert!(!shaded(&shadowless));
+
+        // Each kind of ray sees the actors visible to it only.
+        let hidden = Visibility {
+            indirect: false,
+            ..Visibility::default()
+        };
+        let world = HittableList::new(vec![hidden.apply(Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -2.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.5, 0.5, 0.5, 1.0]),
+                Shading::COLOR,
+            )),
+        }))]);
+        let ray =
+            Ray::new(arr1(&[0.0, 0.0, 0.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+        let mut hit = Hit::new();
+        for (kind, seen) in [
+            (RayKind::Camera, true),
+            (RayKind::Shadow, true),
+            (RayKind::Indirect, false),
+        ] {
+            assert_eq!(
+                world.is_hit_by(kind, &ray, 0.0001, Float::MAX, &mut hit),
+                seen
+            );
+        }
+        assert!(!world.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+
+        // The flags of a scene, all set unless given.
+        let scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+                "spheres": [{
+                    "center": [0, 0, -2],
+                    "radius": 0.5,
+                    "material": {"type": "lambertian", "albedo": [1, 1, 1]},
+                    "visibility": {"camera": false}
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.spheres[0].visibility,
+            Visibility {
+                camera: false,
+                shadow: true,
+                indirect: true,
+                holdout: false,
+            }
+        );
+        let actors = scene.actors().unwrap();
+        assert!(!actors[0].visibility().camera);
+    }
+
+    #[test]
+    fn holdout() {
+        use saturno::prelude::Visibility;
+        use saturno::scene::description::SceneDescription;
+
+        // A holdout ball in front of a lit one, on a transparent
+        // background.
+        let render = |visibility: Visibility| {
+            let actors = vec![
+                Box::new(Sphere {
+                    center: arr1(&[0.0, 0.0, -3.0, 1.0]),
+                    radius: 1.0,
+                    material: Arc::new(Lambertian::new(
+                        arr1(&[0.5, 0.5, 0.5, 1.0]),
+                        Shading::COLOR,
+                    )),
+                }) as Box<dyn RayTraceable>,
+                visibility.apply(Box::new(Sphere {
+                    center: arr1(&[0.0, 0.0, -1.5, 1.0]),
+                    radius: 0.25,
+                    material: Arc::new(Lambertian::new(
+                        arr1(&[0.9, 0.1, 0.1, 1.0]),
+                        Shading::COLOR,
+                    )),
+                })),
+            ];
+            let camera = Camera::new(
+                60.0,
+                20,
+                20,
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 1.0]),
+                arr1(&[0.0, 1.0, 0.0, 0.0]),
+                0.0,
+            );
+            let settings = RenderSettings::builder()
+                .size(20, 20)
+                .samples(4)
+                .seed(5)
+                .transparent_background(true)
+                .build()
+                .unwrap();
+            Canvas::new(actors, camera).render_hdr(&settings).unwrap()
+        };
+        let (center, side) = (10 * 20 + 10, 10 * 20 + 4);
+
+        let plain = render(Visibility::default());
+        assert!(plain.get_pixel(center)[3] > 0.99);
+        assert!(plain.get_pixel(center)[0] > 2.0 * plain.get_pixel(center)[1]);
+
+        // Black and transparent where it is, hiding the ball behind; the
+        // rest is as without it.
+        let held = render(Visibility {
+            holdout: true,
+            ..Visibility::default()
+        });
+        assert_eq!(held.get_pixel(center), [0.0; 4]);
+        assert!(held.get_pixel(side)[3] > 0.99);
+        assert!(held.get_pixel(side)[1] > 0.0);
+
+        // Set in a scene.
+        let scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+                "spheres": [{
+                    "center": [0, 0, -2],
+                    "radius": 0.5,
+                    "material": {"type": "lambertian", "albedo": [1, 1, 1]},
+                    "visibility": {"holdout": true}
+                }]
+            }"#,
+        )
+        .unwrap();
+        let actors = scene.actors().unwrap();
+        assert!(actors[0].visibility().holdout);
+        assert!(actors[0].visibility().camera);
+    }
+
+    #[test]
+    fn instancing() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::description::NoFiles;
+        use saturno::scene::description::SceneDescription;
+
+        // A ball in three places: as is, small and red, and stretched
+        // along y (then turned), red too.
+        let mut scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+                "materials": {"paint": {"type": "lambertian", "albedo": [1, 1, 1]}},
+                "prototypes": {"ball": {"spheres": [{
+                    "center": [0, 0, 0],
+                    "radius": 1,
+                    "material": {"type": "named", "name": "paint"}
+                }]}},
+                "instances": [
+                    {"prototype": "ball", "translation": [0, 0, -5]},
+                    {
+                        "prototype": "ball",
+                        "translation": [3, 0, -5],
+                        "scale": [0.5, 0.5, 0.5],
+                        "materials": {"paint": {"type": "metal", "color": [1, 0, 0]}}
+                    },
+                    {
+                        "prototype": "ball",
+                        "translation": [-3, 0, -5],
+                        "rotation": [0, 90, 0],
+                        "scale": [1, 2, 1],
+                        "materials": {"paint": {"type": "metal", "color": [1, 0, 0]}}
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let actors = scene.instances(&mut NoFiles).unwrap();
+        assert_eq!(actors.len(), 1);
+        let bounds = actors[0].bounding_box().unwrap();
+        assert!((bounds.min[0] + 4.0).abs() < 1e-9);
+        assert!((bounds.max[0] - 3.5).abs() < 1e-9);
+        assert!((bounds.max[1] - 2.0).abs() < 1e-9);
+
+        let hit = |x: Float, y: Float| {
+            let ray =
+                Ray::new(arr1(&[x, y, 0.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+            let mut hit = Hit::new();
+            actors[0]
+                .is_hit(&ray, 0.0001, Float::MAX, &mut hit)
+                .then_some(hit)
+        };
+        let plain = hit(0.0, 0.0).unwrap();
+        assert!((plain.t - 4.0).abs() < 1e-9);
+        assert!((plain.normal[2] - 1.0).abs() < 1e-9);
+        assert!(plain.material.name().starts_with("lambertian"));
+
+        let small = hit(3.0, 0.0).unwrap();
+        assert!((small.t - 4.5).abs() < 1e-9);
+        assert!((small.point[0] - 3.0).abs() < 1e-9);
+        assert!(small.material.name().starts_with("metal"));
+        assert!(hit(3.0, 0.6).is_none());
+
+        // Stretched, it reaches higher, its normals still of unit length
+        // and facing the ray; its red is that of the other red one.
+        let stretched = hit(-3.0, 1.5).unwrap();
+        let n = &stretched.normal;
+        assert!(((n[0] * n[0] + n[1] * n[1] + n[2] * n[2]) - 1.0).abs() < 1e-9);
+        assert!(n[2] > 0.0 && n[1] > 0.0);
+        assert!(Arc::ptr_eq(&stretched.material, &small.material));
+        assert!(hit(0.0, 1.5).is_none());
+
+        scene.instances[0].prototype = "car".to_string();
+        assert!(scene.instances(&mut NoFiles).is_err());
+    }
+
+    #[test]
+    fn scattering() {
+        use saturno::prelude::Mesh;
+        use saturno::prelude::ScatterRegion;
+        use saturno::prelude::Scatterer;
+        use saturno::prelude::Scattering;
+        use saturno::prelude::Texture;
+        use saturno::prelude::TextureSampler;
+        use saturno::scene::description::MeshDescription;
+        use saturno::scene::description::SceneDescription;
+        use saturno::scene::description::SceneFiles;
+
+        let length =
+            |v: [Float; 3]| (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+
+        // Upright over a rectangle, the same for the same seed.
+        let rectangle = ScatterRegion::Rectangle {
+            center: [1.0, 2.0, -3.0],
+            size: [4.0, 2.0],
+        };
+        let scatterer = Scatterer::new(rectangle, 200, 7);
+        let placed = scatterer.scatter(None).unwrap();
+        assert_eq!(placed.len(), 200);
+        assert_eq!(placed, scatterer.scatter(None).unwrap());
+        for scattered in &placed {
+            let origin = scattered.transform.point(&[0.0; 3]);
+            assert!((-1.0..=3.0).contains(&origin[0]));
+            assert!((origin[1] - 2.0).abs() < 1e-9);
+            assert!((-4.0..=-2.0).contains(&origin[2]));
+            let up = scattered.transform.vector(&[0.0, 1.0, 0.0]);
+            assert!((up[1] - 1.0).abs() < 1e-9);
+        }
+        let mut reseeded = scatterer.clone();
+        reseeded.seed = 8;
+        assert_ne!(placed, reseeded.scatter(None).unwrap());
+
+        // A density clearing the left of the rectangle keeps some of the
+        // same instances, on the right only.
+        let mut texels = vec![[0.0, 0.0, 0.0, 1.0]; 8];
+        for texel in &mut texels[4..7] {
+            *texel = [1.0; 4];
+        }
+        let mut thinned = scatterer.clone();
+        thinned.density = Some(Arc::new(Texture::new(8, 1, texels).unwrap()));
+        let kept = thinned.scatter(None).unwrap();
+        assert!(kept.len() > 20 && kept.len() < 150);
+        for scattered in &kept {
+            assert!(placed.contains(scattered));
+            assert!(scattered.transform.point(&[0.0; 3])[0] > 0.7);
+        }
+
+        // Along the normals of a sphere, scaled within the range.
+        let sphere = ScatterRegion::Sphere {
+            center: [0.0, 0.0, 0.0],
+            radius: 2.0,
+        };
+        let mut globe = Scatterer::new(sphere, 50, 1);
+        globe.align = true;
+        globe.scale = [0.5, 1.0];
+        for scattered in globe.scatter(None).unwrap() {
+            let origin = scattered.transform.point(&[0.0; 3]);
+            assert!((length(origin) - 2.0).abs() < 1e-9);
+            let up = scattered.transform.vector(&[0.0, 1.0, 0.0]);
+            assert!((0.5..=1.0).contains(&length(up)));
+            let along = (0..3).map(|i| up[i] * origin[i]).sum::<Float>();
+            assert!((along / (2.0 * length(up)) - 1.0).abs() < 1e-9);
+        }
+
+        // Within a box.
+        let volume = ScatterRegion::Box {
+            min: [0.0, 0.0, 0.0],
+            max: [1.0, 2.0, 3.0],
+        };
+        for scattered in Scatterer::new(volume, 50, 2).scatter(None).unwrap() {
+            let origin = scattered.transform.point(&[0.0; 3]);
+            assert!(
+                (0..3).all(|i| (0.0..=(i + 1) as Float).contains(&origin[i]))
+            );
+        }
+
+        // Two prototypes over the surface of a mesh, read by the files of
+        // the scene.
+        struct Ground;
+        impl SceneFiles for Ground {
+            fn mesh(
+                &mut self,
+                _mesh: &MeshDescription,
+                material: Arc<dyn Scattering>,
+            ) -> Result<Mesh, String> {
+                Mesh::grid([0.0, -1.0, 0.0], 10.0, 4, material)
+            }
+
+            fn texture(
+                &mut self,
+                path: &str,
+            ) -> Result<Arc<dyn TextureSampler>, String> {
+                Err(format!("no {}", path))
+            }
+        }
+        let mut scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 5, 5], "look_at": [0, 0, 0]},
+                "prototypes": {
+                    "rock": {"spheres": [{
+                        "center": [0, 0, 0],
+                        "radius": 0.1,
+                        "material": {"type": "lambertian", "albedo": [0.5, 0.5, 0.5]}
+                    }]},
+                    "bush": {"spheres": [{
+                        "center": [0, 0.2, 0],
+                        "radius": 0.2,
+                        "material": {"type": "lambertian", "albedo": [0.1, 0.5, 0.1]}
+                    }]}
+                },
+                "scatters": [{
+                    "prototypes": ["rock", "bush"],
+                    "region": {"type": "mesh", "path": "ground.obj"},
+                    "count": 30,
+                    "align": true,
+                    "seed": 3
+                }]
+            }"#,
+        )
+        .unwrap();
+        let actors = scene.instances(&mut Ground).unwrap();
+        assert_eq!(actors.len(), 2);
+        for actor in &actors {
+            let bounds = actor.bounding_box().unwrap();
+            assert!(bounds.min[1] >= -1.2 && bounds.max[1] <= -0.4);
+            assert!(bounds.min[0] >= -5.5 && bounds.max[0] <= 5.5);
+        }
+        scene.scatters[0].density = Some("clearing.png".to_string());
+        assert!(scene.instances(&mut Ground).is_err());
+    }
+
+    #[test]
+    fn levels_of_detail() {
+        use saturno::prelude::Hittable;
+        use saturno::scene::actor::Hit;
+        use saturno::scene::description::NoFiles;
+        use saturno::scene::description::SceneDescription;
+
+        // A ball, metal beyond 10 from the camera, then smaller once
+        // fewer than 10 pixels across (of 0.02 at the distance of 1).
+        let scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+                "prototypes": {"ball": {
+                    "spheres": [{
+                        "center": [0, 0, 0],
+                        "radius": 1,
+                        "material": {"type": "lambertian", "albedo": [1, 1, 1]}
+                    }],
+                    "lods": [
+                        {"distance": 10, "spheres": [{
+                            "center": [0, 0, 0],
+                            "radius": 1,
+                            "material": {"type": "metal", "color": [1, 1, 1]}
+                        }]},
+                        {"pixels": 10, "spheres": [{
+                            "center": [0, 0, 0],
+                            "radius": 0.5,
+                            "material": {"type": "metal", "color": [1, 1, 1]}
+                        }]}
+                    ]
+                }},
+                "instances": [
+                    {"prototype": "ball", "translation": [0, 0, -5]},
+                    {"prototype": "ball", "translation": [3, 0, -12]},
+                    {"prototype": "ball", "translation": [-6, 0, -20]}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let camera = |z: Float| {
+            Camera::new(
+                90.0,
+                200,
+                100,
+                arr1(&[0.0, 0.0, z, 1.0]),
+                arr1(&[0.0, 0.0, z - 1.0, 1.0]),
+                arr1(&[0.0, 1.0, 0.0, 0.0]),
+                0.0,
+            )
+        };
+        let mut canvas =
+            Canvas::new(scene.instances(&mut NoFiles).unwrap(), camera(0.0));
+        let hit = |canvas: &Canvas, x: Float, y: Float| {
+            let ray =
+                Ray::new(arr1(&[x, y, 0.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]));
+            let mut hit = Hit::new();
+            canvas
+                .world
+                .is_hit(&ray, 0.0001, Float::MAX, &mut hit)
+                .then_some(hit)
+        };
+        let near = hit(&canvas, 0.0, 0.0).unwrap();
+        assert!(near.material.name().starts_with("lambertian"));
+        let middle = hit(&canvas, 3.0, 0.0).unwrap();
+        assert!(middle.material.name().starts_with("metal"));
+        assert!(hit(&canvas, 3.0, 0.7).is_some());
+        let far = hit(&canvas, -6.0, 0.0).unwrap();
+        assert!((far.t - 19.5).abs() < 1e-9);
+        assert!(hit(&canvas, -6.0, 0.7).is_none());
+
+        // Up close, the far one is back to the finest.
+        canvas.set_camera(camera(-15.0));
+        let far = hit(&canvas, -6.0, 0.7).unwrap();
+        assert!(far.material.name().starts_with("lambertian"));
+        let near = hit(&canvas, 0.0, 0.0).unwrap();
+        assert!(near.material.name().starts_with("lambertian"));
+
+        let mut scene = scene;
+        scene.prototypes.get_mut("ball").unwrap().lods[0].pixels = Some(1.0);
+        assert!(scene.instances(&mut NoFiles).is_err());
+    }
+
+    #[test]
+    fn motion_blur() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::description::NoFiles;
+        use saturno::scene::description::SceneDescription;
+
+        // A ball on an arm of 3, a half turn about z over the shutter in
+        // two segments.
+        let mut scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {
+                    "look_from": [0, 0, 5],
+                    "look_at": [0, 0, 0],
+                    "shutter": [0, 1]
+                },
+                "prototypes": {"ball": {"spheres": [{
+                    "center": [3, 0, 0],
+                    "radius": 0.5,
+                    "material": {"type": "lambertian", "albedo": [1, 1, 1]}
+                }]}},
+                "instances": [{"prototype": "ball", "motion": [
+                    {"rotation": [0, 0, 0]},
+                    {"rotation": [0, 0, 90]},
+                    {"rotation": [0, 0, 180]}
+                ]}]
+            }"#,
+        )
+        .unwrap();
+        let actors = scene.instances(&mut NoFiles).unwrap();
+        assert_eq!(actors.len(), 1);
+        let bounds = actors[0].bounding_box().unwrap();
+        assert!(bounds.min[0] <= -3.5 && bounds.max[0] >= 3.5);
+        assert!(bounds.max[1] >= 3.5);
+
+        let hit = |x: Float, y: Float, time: Float| {
+            let ray =
+                Ray::new(arr1(&[x, y, 5.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]))
+                    .at_time(time);
+            let mut hit = Hit::new();
+            actors[0]
+                .is_hit(&ray, 0.0001, Float::MAX, &mut hit)
+                .then_some(hit)
+        };
+        assert!(hit(3.0, 0.0, 0.0).is_some());
+        assert!(hit(3.0, 0.0, 0.5).is_none());
+        assert!(hit(0.0, 3.0, 0.5).is_some());
+        assert!(hit(-3.0, 0.0, 1.0).is_some());
+
+        // Between the keys it turns along the arc, not the chord.
+        let arc = 3.0 / Float::sqrt(2.0);
+        let turning = hit(arc, arc, 0.25).unwrap();
+        assert!((turning.t - 4.5).abs() < 1e-9);
+        assert!(hit(1.5, 1.5, 0.25).is_none());
+
+        // The camera casts its rays over the shutter, at 0 if closed.
+        let camera = scene.camera.build(20, 10);
+        let times: Vec<Float> =
+            (0..1000).map(|_| camera.get_ray(5.0, 5.0).time).collect();
+        assert!(times.iter().all(|t| (0.0..1.0).contains(t)));
+        assert!(
+            times.iter().any(|t| *t < 0.1) && times.iter().any(|t| *t > 0.9)
+        );
+        scene.camera.shutter = [0.0, 0.0];
+        assert_eq!(scene.camera.build(20, 10).get_ray(5.0, 5.0).time, 0.0);
+
+        scene.instances[0].motion[1].scale = [-1.0, 1.0, 1.0];
+        assert!(scene.instances(&mut NoFiles).is_err());
+    }
+
+    #[test]
+    fn deformation_blur() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::mesh::Mesh;
+
+        // A unit square facing +z, sliding 2 along x by the middle of the
+        // shutter, then folding its top edge back by the time it closes.
+        let square = vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let moved: Vec<[Float; 3]> =
+            square.iter().map(|p| [p[0] + 2.0, p[1], p[2]]).collect();
+        let mut folded = moved.clone();
+        folded[2][2] = -1.0;
+        folded[3][2] = -1.0;
+        let material = Arc::new(Lambertian::new(
+            arr1(&[1.0, 1.0, 1.0, 1.0]),
+            Shading::COLOR,
+        ));
+        let mesh = Mesh::new(
+            square.clone(),
+            vec![[0.0, 0.0]; 4],
+            vec![[0, 1, 2], [0, 2, 3]],
+            material.clone(),
+        )
+        .unwrap();
+        assert!(mesh
+            .clone()
+            .with_deformation(vec![square[..3].to_vec()])
+            .is_err());
+        let mesh = mesh.with_deformation(vec![moved, folded]).unwrap();
+        assert_eq!(mesh.deformation_samples(), 3);
+        let bounds = mesh.bounding_box().unwrap();
+        assert!(bounds.min[0] <= 0.0 && bounds.max[0] >= 3.0);
+        assert!(bounds.min[2] <= -1.0);
+
+        let hit = |x: Float, y: Float, time: Float| {
+            let ray =
+                Ray::new(arr1(&[x, y, 5.0, 1.0]), arr1(&[0.0, 0.0, -1.0, 0.0]))
+                    .at_time(time);
+            let mut hit = Hit::new();
+            mesh.is_hit(&ray, 0.0001, Float::MAX, &mut hit)
+                .then_some(hit)
+        };
+        assert!(hit(0.5, 0.5, 0.0).is_some());
+        assert!(hit(2.5, 0.5, 0.0).is_none());
+        assert!(hit(1.5, 0.5, 0.25).is_some());
+        assert!(hit(0.5, 0.5, 0.5).is_none());
+        let middle = hit(2.5, 0.5, 0.5).unwrap();
+        assert!((middle.t - 5.0).abs() < 1e-9);
+        assert!(middle.normal[2] > 0.999);
+
+        // Folded back when the shutter closes, and tilted towards +y.
+        let closed = hit(2.5, 0.5, 1.0).unwrap();
+        assert!((closed.t - 5.5).abs() < 1e-9);
+        assert!(closed.normal[1] > 0.5 && closed.normal[2] > 0.5);
+    }
+
+    #[test]
+    fn alembic_caches() {
+        use saturno::io::alembic::read_caches;
+        use saturno::io::alembic::Archive;
+        use saturno::scene::description::SceneDescription;
+        use std::path::Path;
+
+        // An Ogawa tree of groups and blocks of data, written children
+        // first.
+        enum Tree {
+            Group(Vec<Tree>),
+            Data(Vec<u8>),
+        }
+        fn write(tree: &Tree, file: &mut Vec<u8>) -> u64 {
+            match tree {
+                Tree::Data(bytes) if bytes.is_empty() => 1 << 63,
+                Tree::Data(bytes) => {
+                    let at = file.len() as u64;
+                    file.extend((bytes.len() as u64).to_le_bytes());
+                    file.extend(bytes);
+                    at | 1 << 63
+                }
+                Tree::Group(children) => {
+                    let offsets: Vec<u64> =
+                        children.iter().map(|c| write(c, file)).collect();
+                    let at = file.len() as u64;
+                    file.extend((offsets.len() as u64).to_le_bytes());
+                    for offset in offsets {
+                        file.extend(offset.to_le_bytes());
+                    }
+                    at
+                }
+            }
+        }
+        // A property header: compound (0), scalar (1) or array (2) of
+        // `pod` values, with one byte sizes.
+        fn header(
+            kind: u32,
+            pod: u32,
+            extent: u32,
+            samples: u8,
+            sampling: u8,
+            name: &str,
+        ) -> Vec<u8> {
+            let tsidx = if sampling != 0 { 0x100 } else { 0 };
+            let info = kind | pod << 4 | tsidx | extent << 12;
+            let mut bytes = info.to_le_bytes().to_vec();
+            if kind != 0 {
+                bytes.push(samples);
+                if sampling != 0 {
+                    bytes.push(sampling);
+                }
+            }
+            bytes.push(name.len() as u8);
+            bytes.extend(name.as_bytes());
+            bytes
+        }
+        fn compound(properties: Vec<(Vec<u8>, Tree)>) -> Tree {
+            let mut headers = vec![];
+            let mut children = vec![];
+            for (bytes, property) in properties {
+                headers.extend(bytes);
+                children.push(property);
+            }
+            children.push(Tree::Data(headers));
+            Tree::Group(children)
+        }
+        fn object(properties: Tree, children: Vec<(&str, &str, Tree)>) -> Tree {
+            let mut headers = vec![];
+            let mut nodes = vec![properties];
+            for (name, schema, child) in children {
+                headers.extend((name.len() as u32).to_le_bytes());
+                headers.extend(name.as_bytes());
+                headers.push(0xff);
+                let metadata = format!("schema={}", schema);
+                headers.extend((metadata.len() as u32).to_le_bytes());
+                headers.extend(metadata.as_bytes());
+                nodes.push(child);
+            }
+            headers.extend([0; 32].iter());
+            nodes.push(Tree::Data(headers));
+            Tree::Group(nodes)
+        }
+        // A sample after its hash, an array's with its dimensions.
+        let sample = |values: Vec<u8>| {
+            let mut bytes = vec![0; 16];
+            bytes.extend(values);
+            Tree::Data(bytes)
+        };
+        let array = |values: Vec<u8>| {
+            Tree::Group(vec![sample(values), Tree::Data(vec![])])
+        };
+        let f32s = |values: &[f32]| -> Vec<u8> {
+            values.iter().flat_map(|v| v.to_le_bytes()).collect()
+        };
+        let i32s = |values: &[i32]| -> Vec<u8> {
+            values.iter().flat_map(|v| v.to_le_bytes()).collect()
+        };
+        let f64s = |values: &[f64]| -> Vec<u8> {
+            values.iter().flat_map(|v| v.to_le_bytes()).collect()
+        };
+
+        // A unit quad facing +z, its corners clockwise, with texture
+        // coordinates by corner, under a transform moving it from 0 at
+        // frame 1 to 2 along x at frame 2 (at 24 frames per second).
+        let geometry = compound(vec![
+            (
+                header(2, 10, 3, 1, 0, "P"),
+                array(f32s(&[0., 0., 0., 1., 0., 0., 1., 1., 0., 0., 1., 0.])),
+            ),
+            (
+                header(2, 6, 1, 1, 0, ".faceIndices"),
+                array(i32s(&[0, 3, 2, 1])),
+            ),
+            (header(2, 6, 1, 1, 0, ".faceCounts"), array(i32s(&[4]))),
+            (
+                header(2, 10, 2, 1, 0, "uv"),
+                array(f32s(&[0., 0., 0., 1., 1., 1., 1., 0.])),
+            ),
+        ]);
+        let mesh = object(
+            compound(vec![(header(0, 0, 0, 0, 0, ".geom"), geometry)]),
+            vec![],
+        );
+        let xform = compound(vec![
+            (
+                header(1, 1, 1, 1, 0, ".ops"),
+                Tree::Group(vec![sample(vec![0x10])]),
+            ),
+            (
+                header(1, 11, 3, 2, 1, ".vals"),
+                Tree::Group(vec![
+                    sample(f64s(&[0.0, 0.0, 0.0])),
+                    sample(f64s(&[2.0, 0.0, 0.0])),
+                ]),
+            ),
+        ]);
+        let moving = object(
+            compound(vec![(header(0, 0, 0, 0, 0, ".xform"), xform)]),
+            vec![("mesh", "AbcGeom_PolyMesh_v1", mesh)],
+        );
+        let top = object(
+            compound(vec![]),
+            vec![("moving", "AbcGeom_Xform_v3", moving)],
+        );
+        let mut samplings = vec![];
+        for (count, per_cycle, start) in
+            [(1u32, 1.0, 0.0), (2, 1.0 / 24.0, 1.0 / 24.0)].iter()
+        {
+            samplings.extend(count.to_le_bytes());
+            samplings.extend(f64s(&[*per_cycle]));
+            samplings.extend(1u32.to_le_bytes());
+            samplings.extend(f64s(&[*start]));
+        }
+        let root = Tree::Group(vec![
+            Tree::Data(i32s(&[0])),
+            Tree::Data(i32s(&[10703])),
+            top,
+            Tree::Data(vec![]),
+            Tree::Data(samplings),
+            Tree::Data(vec![]),
+        ]);
+        let mut file = b"Ogawa\xff\x00\x01".to_vec();
+        file.extend([0; 8].iter());
+        let offset = write(&root, &mut file);
+        file[8..16].copy_from_slice(&offset.to_le_bytes());
+
+        let archive = Archive::parse(file.clone()).unwrap();
+        let meshes = archive.meshes(1.5 / 24.0).unwrap();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].name, "/moving/mesh");
+        let quad = &meshes[0].data;
+        assert_eq!((quad.positions.len(), quad.triangles.len()), (4, 2));
+        let corner = quad.positions.iter().position(|p| *p == [2.0, 1.0, 0.0]);
+        assert_eq!(quad.uvs[corner.unwrap()], [1.0, 1.0]);
+        // Turned counterclockwise, facing +z.
+        let [a, b, c] = quad.triangles[0].map(|i| quad.positions[i]);
+        let (u, v) = ([b[0] - a[0], b[1] - a[1]], [c[0] - a[0], c[1] - a[1]]);
+        assert!(u[0] * v[1] - u[1] * v[0] > 0.0);
+        // Clamped to the first and last samples.
+        let x =
+            |time: Float| archive.meshes(time).unwrap()[0].data.positions[0][0];
+        assert_eq!(x(0.0), x(1.0 / 24.0));
+        assert_eq!(x(1.0) - x(0.0), 2.0);
+
+        assert!(Archive::parse(file[..file.len() - 8].to_vec()).is_err());
+        assert!(Archive::parse(b"Ogawa".to_vec()).is_err());
+
+        // Over a shutter of a frame from frame 1, the quad deforms from
+        // its place at frame 1 to that at frame 2.
+        let mut path = init_image_testing();
+        path.push("alembic_caches.abc");
+        std::fs::write(&path, &file).unwrap();
+        let scene: SceneDescription = serde_json::from_str(&format!(
+            r#"{{"camera": {{"look_from": [0, 0, 5], "look_at": [0, 0, 0],
+                            "shutter": [0, 1]}},
+                "alembic": [{{"path": "{}", "material":
+                    {{"type": "lambertian", "albedo": [1, 1, 1]}}}}],
+                "frame": 1, "shutter": 1}}"#,
+            path.display()
+        ))
+        .unwrap();
+        assert!((scene.time(0.5) - 1.5 / 24.0).abs() < 1e-12);
+        let actors = read_caches(&scene, Path::new(".")).unwrap();
+        assert_eq!(actors.len(), 1);
+        let bounds = actors[0].bounding_box().unwrap();
+        assert!(bounds.min[0] <= 0.0 && bounds.max[0] >= 3.0);
+        let hit = |x: Float, time: Float| {
+            let ray = Ray::new(
+                arr1(&[x, 0.5, 5.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 0.0]),
+            )
+            .at_time(time);
+            let mut hit = saturno::scene::actor::Hit::new();
+            actors[0].is_hit(&ray, 0.0001, Float::MAX, &mut hit)
+        };
+        assert!(hit(0.5, 0.0) && !hit(2.5, 0.0));
+        assert!(hit(2.5, 1.0) && !hit(0.5, 1.0));
+    }
+
+    #[test]
+    fn usd_stages() {
+        use saturno::io::usd::parse_usda;
+        use saturno::io::usd::read_stage;
+        use saturno::scene::description::LightDescription;
+        use saturno::scene::description::NoFiles;
+        use saturno::scene::units::Units;
+
+        let stage = r#"#usda 1.0
+(
+    doc = """A quad sliding along x, a ball, a crate and
+    the lights and camera to see them"""
+    metersPerUnit = 1
+    upAxis = "Z"
+)
+
+def Xform "World"
+{
+    def Mesh "Quad" (
+        prepend apiSchemas = ["MaterialBindingAPI"]
+    )
+    {
+        int[] faceVertexCounts = [4]
+        int[] faceVertexIndices = [0, 1, 2, 3]
+        point3f[] points = [(0, 0, 0), (1, 0, 0), (1, 1, 0), (0, 1, 0)]
+        texCoord2f[] primvars:st = [(0, 0), (1, 0), (1, 1), (0, 1)] (
+            interpolation = "faceVarying"
+        )
+        rel material:binding = </World/Looks/Red>
+        double3 xformOp:translate.timeSamples = {
+            0: (0, 0, 0),
+            10: (10, 0, 0),
+        }
+        uniform token[] xformOpOrder = ["xformOp:translate"]
+    }
+
+    def Sphere "Ball"
+    {
+        double radius = 0.5
+        color3f[] primvars:displayColor = [(0, 0, 1)]
+        double3 xformOp:translate = (0, 0, 3)
+        float xformOp:scale = 2
+        uniform token[] xformOpOrder = ["xformOp:translate", "xformOp:scale"]
+    }
+
+    def Cube "Crate"
+    {
+        matrix4d xformOp:transform = ( (1, 0, 0, 0), (0, 1, 0, 0), (0, 0, 1, 0), (5, 0, 0, 1) )
+        uniform token[] xformOpOrder = ["xformOp:transform"]
+    }
+
+    def Cube "Hidden"
+    {
+        token visibility = "invisible"
+    }
+
+    def Cube "Guide"
+    {
+        uniform token purpose = "guide"
+    }
+
+    class Cube "Template"
+    {
+    }
+
+    def Scope "Looks"
+    {
+        def Material "Red"
+        {
+            token outputs:surface.connect = </World/Looks/Red/Surface.outputs:surface>
+
+            def Shader "Surface"
+            {
+                uniform token info:id = "UsdPreviewSurface"
+                color3f inputs:diffuseColor.connect = </World/Looks/Red/Texture.outputs:rgb>
+                float inputs:metallic = 0.5
+                token outputs:surface
+            }
+
+            def Shader "Texture"
+            {
+                uniform token info:id = "UsdUVTexture"
+                float4 inputs:fallback = (1, 0, 0, 1)
+                float3 outputs:rgb
+            }
+        }
+    }
+
+    def Camera "Cam"
+    {
+        float focalLength = 50
+        float verticalAperture = 24
+        double3 xformOp:translate = (0, -10, 1)
+        float xformOp:rotateX = 90
+        uniform token[] xformOpOrder = ["xformOp:translate", "xformOp:rotateX"]
+    }
+
+    def SphereLight "Bulb"
+    {
+        float inputs:radius = 0.1
+        float inputs:intensity = 100
+        float inputs:exposure = 1
+    }
+
+    def DistantLight "Sun"
+    {
+        float inputs:intensity = 1000
+        float inputs:angle = 1
+    }
+
+    def RectLight "Panel"
+    {
+        float inputs:width = 2
+        float inputs:height = 1
+        double3 xformOp:translate = (0, 0, 4)
+        uniform token[] xformOpOrder = ["xformOp:translate"]
+    }
+}
+"#;
+        // Z up turned Y up: the quad lies on the ground, moved 5 meters
+        // along x at time code 5, at its first sample without a time.
+        let scene =
+            parse_usda(stage, Units::Meters, Some(5.0), &mut NoFiles).unwrap();
+        assert_eq!(scene.actors.len(), 4);
+        let bounds: Vec<_> = scene
+            .actors
+            .iter()
+            .map(|actor| actor.bounding_box().unwrap())
+            .collect();
+        let close = |a: [Float; 3], b: [Float; 3]| {
+            (0..3).all(|i| (a[i] - b[i]).abs() < 1e-6)
+        };
+        assert!(close(bounds[0].min, [5.0, 0.0, -1.0]));
+        assert!(close(bounds[0].max, [6.0, 0.0, 0.0]));
+        // The ball scaled twice over, the crate 2 units wide.
+        assert!(close(bounds[1].min, [-1.0, 2.0, -1.0]));
+        assert!(close(bounds[1].max, [1.0, 4.0, 1.0]));
+        assert!(close(bounds[2].min, [4.0, -1.0, -1.0]));
+        assert!(close(bounds[2].max, [6.0, 1.0, 1.0]));
+        assert!(close(bounds[3].min, [-1.0, 4.0, -0.5]));
+        let ray = Ray::new(
+            arr1(&[5.5, 1.0, -0.5, 1.0]),
+            arr1(&[0.0, -1.0, 0.0, 0.0]),
+        );
+        let mut hit = saturno::scene::actor::Hit::new();
+        assert!(scene.actors[0].is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+        assert!(hit.normal[1] > 0.99);
+        let first =
+            parse_usda(stage, Units::Meters, None, &mut NoFiles).unwrap();
+        assert!(close(
+            first.actors[0].bounding_box().unwrap().min,
+            [0.0, 0.0, -1.0]
+        ));
+
+        // The camera 10 meters back and 1 up, looking down -z.
+        assert_eq!(scene.cameras.len(), 1);
+        let (name, camera) = &scene.cameras[0];
+        assert_eq!(name, "Cam");
+        assert!(close(camera.look_from, [0.0, 1.0, 10.0]));
+        assert!(close(camera.look_at, [0.0, 1.0, 9.0]));
+        assert!(close(camera.up, [0.0, 1.0, 0.0]));
+        assert!(
+            (camera.vertical_fov
+                - 2.0 * (12.0 as Float / 50.0).atan().to_degrees())
+            .abs()
+                < 1e-9
+        );
+
+        // A sphere of 200 nits 0.1 meters around, and the sun overhead.
+        assert_eq!(scene.lights.len(), 2);
+        match &scene.lights[0] {
+            LightDescription::Omni {
+                intensity, radius, ..
+            } => {
+                let expected = 200.0 * std::f64::consts::PI as Float * 0.01;
+                assert!((intensity - expected).abs() < 1e-9);
+                assert!((radius - 0.1).abs() < 1e-9);
+            }
+            other => panic!("{:?}", other),
+        }
+        match &scene.lights[1] {
+            LightDescription::Sun {
+                direction,
+                illuminance,
+                angular_diameter,
+                ..
+            } => {
+                assert!(close(*direction, [0.0, 1.0, 0.0]));
+                assert_eq!((*illuminance, *angular_diameter), (1000.0, 1.0));
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // In centimeters by default, scaled to the scene.
+        let centimeters = stage.replace("metersPerUnit = 1", "");
+        let scene =
+            parse_usda(&centimeters, Units::Meters, Some(5.0), &mut NoFiles)
+                .unwrap();
+        assert!(close(
+            scene.actors[2].bounding_box().unwrap().max,
+            [0.06, 0.01, 0.01]
+        ));
+        // Textures are read from the files of the stage.
+        let textured = stage.replace(
+            "float4 inputs:fallback",
+            "asset inputs:file = @missing.png@\n float4 inputs:fallback",
+        );
+        let error = parse_usda(&textured, Units::Meters, None, &mut NoFiles);
+        assert!(error.err().unwrap().contains("missing.png"));
+        assert!(parse_usda(
+            "#usda 1.0\ndef Mesh \"Open\" {",
+            Units::Meters,
+            None,
+            &mut NoFiles
+        )
+        .is_err());
+        assert!(
+            parse_usda("#sdf 1.4.32\n", Units::Meters, None, &mut NoFiles)
+                .is_err()
+        );
+
+        // A usdz package of stored files, the root layer first.
+        let zip = |files: &[(&str, &[u8])]| {
+            let mut zip = vec![];
+            for (name, data) in files {
+                zip.extend(0x0403_4b50u32.to_le_bytes());
+                zip.extend(
+                    [20u16, 0, 0, 0, 0].iter().flat_map(|v| v.to_le_bytes()),
+                );
+                zip.extend(0u32.to_le_bytes());
+                zip.extend((data.len() as u32).to_le_bytes());
+                zip.extend((data.len() as u32).to_le_bytes());
+                zip.extend((name.len() as u16).to_le_bytes());
+                zip.extend(0u16.to_le_bytes());
+                zip.extend(name.as_bytes());
+                zip.extend(*data);
+            }
+            // The central directory, not read.
+            zip.extend(0x0201_4b50u32.to_le_bytes());
+            zip
+        };
+        let mut path = init_image_testing();
+        path.push("usd_stages.usdz");
+        std::fs::write(
+            &path,
+            zip(&[("scene.usda", stage.as_bytes()), ("notes.txt", b"x")]),
+        )
+        .unwrap();
+        let scene = read_stage(&path, Units::Meters, Some(5.0)).unwrap();
+        assert_eq!((scene.actors.len(), scene.lights.len()), (4, 2));
+        std::fs::write(&path, zip(&[("scene.usdc", b"PXR-USDC")])).unwrap();
+        let error = read_stage(&path, Units::Meters, None).err().unwrap();
+        assert!(error.to_string().contains("usdcat"));
+    }
+
+    #[test]
+    fn render_time_budget() {
+        use std::time::Duration;
+        use std::time::Instant;
+
+        let dims: [u32; 2] = [20, 10];
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(dims[0], dims[1]),
+        );
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .seed(7)
+            .time_budget(0.2)
+            .build()
+            .unwrap();
+
+        // Passes until the budget is spent, whatever the samples.
+        let start = Instant::now();
+        let budget = Duration::from_millis(200);
+        let progressive = canvas.render_budget(&settings, budget).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        let passes = progressive.passes();
+        assert!(passes > 1);
+        assert_eq!(progressive.settings.samples, passes);
+        assert_eq!(progressive.settings.time_budget, None);
+
+        // The same image as rendering that many samples progressively.
+        let mut fixed = Progressive::new(RenderSettings {
+            samples: passes,
+            time_budget: None,
+            ..settings.clone()
+        });
+        while fixed.step(&canvas).unwrap() {}
+        assert_eq!(progressive.hdr().data, fixed.hdr().data);
+
+        // At least a pass, however short the budget.
+        let short = canvas.render_budget(&settings, Duration::ZERO).unwrap();
+        assert_eq!(short.passes(), 1);
+        let hdr = canvas.render_hdr(&settings).unwrap();
+        assert_eq!((hdr.width, hdr.height), (dims[0], dims[1]));
+
+        for budget in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+            let invalid = RenderSettings {
+                time_budget: Some(budget),
+                ..settings.clone()
+            };
+            assert!(invalid.validate().is_err(), "{}", budget);
+        }
+    }
+
+    #[test]
+    fn render_estimate() {
+        use saturno::render::estimate::estimate;
+        use saturno::render::estimate::Probe;
+        use saturno::render::settings::Crop;
+
+        let dims: [u32; 2] = [40, 20];
+        let mut canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(dims[0], dims[1]),
+        );
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(64)
+            .seed(7)
+            .build()
+            .unwrap();
+
+        // The probe renders a quarter of the size, at most 4 samples.
+        let probe = Probe::default();
+        let result = estimate(&mut canvas, &settings, &probe).unwrap();
+        assert_eq!((result.probe.width, result.probe.height), (10, 5));
+        assert_eq!(result.probe.samples, 4);
+        assert_eq!((result.cost.width, result.cost.height), (10, 5));
+        assert!(result.rays_per_sample() >= 1.0);
+        // Both spheres are tested by every ray, without a hierarchy.
+        let tests = result.tests_per_sample();
+        assert!((tests - 2.0 * result.rays_per_sample()).abs() < 1e-9);
+        assert_eq!(result.samples, 64);
+        assert!(result.seconds_per_pass > 0.0);
+        let seconds = result.seconds_per_pass * 64.0;
+        assert!((result.seconds - seconds).abs() < 1e-9 * seconds);
+        // The camera of the render is back.
+        assert_eq!(canvas.camera().resolution_x, dims[0]);
+        canvas.render_hdr(&settings).unwrap();
+
+        // Under a budget, the samples it affords.
+        let budget = RenderSettings {
+            time_budget: Some(result.seconds_per_pass * 1000.0),
+            ..settings.clone()
+        };
+        let result = estimate(&mut canvas, &budget, &probe).unwrap();
+        let seconds = budget.time_budget.unwrap();
+        let pass = result.seconds_per_pass;
+        assert_eq!(result.samples, ((seconds / pass) as u32).max(1));
+        assert!((result.seconds - seconds).abs() <= pass);
+
+        // A crop scales with the probe, and costs its pixels only.
+        let cropped = RenderSettings {
+            crop: Some(Crop {
+                x: 20,
+                y: 0,
+                width: 20,
+                height: 20,
+            }),
+            samples: 2,
+            ..settings.clone()
+        };
+        let result = estimate(&mut canvas, &cropped, &probe).unwrap();
+        assert_eq!(result.probe.samples, 2);
+        assert_eq!((result.cost.width, result.cost.height), (5, 5));
+
+        let invalid = Probe {
+            scale: 0.0,
+            ..probe
+        };
+        assert!(estimate(&mut canvas, &settings, &invalid).is_err());
+    }
+
+    #[test]
+    fn tile_orders() {
+        use saturno::render::settings::TileOrder;
+        use saturno::render::tiles;
+        use saturno::render::tiles::CostMap;
+
+        let region = Crop {
+            x: 10,
+            y: 5,
+            width: 100,
+            height: 70,
+        };
+        // Every pixel of the region in exactly one tile, whatever the order.
+        let covered = |schedule: &[Vec<Crop>]| {
+            let mut count = vec![0; 100 * 70];
+            for tile in schedule.iter().flatten() {
+                for (x, y) in tiles::pixels(tile) {
+                    count[((y - 5) * 100 + x - 10) as usize] += 1;
+                }
+            }
+            count.iter().all(|&c| c == 1)
+        };
+        for order in
+            [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert]
+        {
+            let schedule = tiles::schedule(&region, 16, order, 3, None);
+            assert_eq!(schedule.len(), 3);
+            assert!(covered(&schedule), "{:?}", order);
+        }
+
+        // The spiral starts at the center, the Hilbert curve steps from a
+        // tile to one next to it.
+        let mut spiral = tiles::tiles(&region, 16);
+        tiles::order(&mut spiral, &region, 16, TileOrder::Spiral);
+        assert_eq!((spiral[0].x, spiral[0].y), (10 + 48, 5 + 32));
+        let mut hilbert = tiles::tiles(&region, 16);
+        tiles::order(&mut hilbert, &region, 16, TileOrder::Hilbert);
+        assert_eq!((hilbert[0].x, hilbert[0].y), (10, 5));
+        let square = Crop {
+            width: 64,
+            height: 64,
+            ..region
+        };
+        let mut hilbert = tiles::tiles(&square, 16);
+        tiles::order(&mut hilbert, &square, 16, TileOrder::Hilbert);
+        for pair in hilbert.windows(2) {
+            let dx = (pair[0].x as i64 - pair[1].x as i64).abs();
+            let dy = (pair[0].y as i64 - pair[1].y as i64).abs();
+            assert_eq!(dx + dy, 16);
+        }
+
+        // An expensive corner is split, and the threads share the cost.
+        let probes = CostMap::probes(&region, 4);
+        let costs = CostMap {
+            region,
+            stride: 4,
+            costs: probes
+                .iter()
+                .map(|&(x, y)| if x < 30 && y < 25 { 100.0 } else { 1.0 })
+                .collect(),
+        };
+        let schedule =
+            tiles::schedule(&region, 32, TileOrder::Cost, 4, Some(&costs));
+        assert!(covered(&schedule));
+        let pieces = schedule.iter().flatten().collect::<Vec<_>>();
+        assert!(pieces.iter().any(|tile| tile.width < 32 && tile.x < 30));
+        let loads = schedule
+            .iter()
+            .map(|tiles| tiles.iter().map(|t| costs.cost(t)).sum::<f64>())
+            .collect::<Vec<_>>();
+        let total = costs.cost(&region);
+        assert!((loads.iter().sum::<f64>() - total).abs() < 1e-6 * total);
+        let most = loads.iter().cloned().fold(0.0, f64::max);
+        assert!(most < total / 4.0 * 1.25, "{:?}", loads);
+
+        // The nodes and triangles of a mesh cost, its tiles come first.
+        use saturno::scene::mesh::Mesh;
+        let cells = 16;
+        let mut positions = vec![];
+        for j in 0..=cells {
+            for i in 0..=cells {
+                let (u, v) = (i as Float / 16.0, j as Float / 16.0);
+                positions.push([0.5 * u - 0.25, 0.5 * v - 0.25, -1.0]);
+            }
+        }
+        let mut triangles = vec![];
+        for j in 0..cells {
+            for i in 0..cells {
+                let corner = j * (cells + 1) + i;
+                let next = corner + cells + 1;
+                triangles.push([corner, corner + 1, next]);
+                triangles.push([corner + 1, next + 1, next]);
+            }
+        }
+        let uvs = vec![[0.0, 0.0]; positions.len()];
+        let black = Arc::new(Lambertian::new(
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            Shading::COLOR,
+        ));
+        let mesh = Mesh::new(positions, uvs, triangles, black).unwrap();
+        let canvas = Canvas::new(
+            vec![Box::new(mesh) as Box<dyn RayTraceable>],
+            scenes::two_spheres_camera(40, 20),
+        );
+        let settings = RenderSettings::builder()
+            .size(40, 20)
+            .samples(1)
+            .seed(3)
+            .build()
+            .unwrap();
+        let settings = RenderSettings {
+            tile_size: 8,
+            tile_order: TileOrder::Cost,
+            ..settings
+        };
+        let costs = canvas.tile_costs(&settings);
+        let sky = Crop {
+            x: 0,
+            y: 0,
+            width: 8,
+            height: 8,
+        };
+        let center = Crop { x: 16, y: 8, ..sky };
+        assert!(costs.cost(&center) > 2.0 * costs.cost(&sky));
+        let schedule = tiles::schedule(
+            &settings.region(),
+            8,
+            TileOrder::Cost,
+            1,
+            Some(&costs),
+        );
+        let first = &schedule[0][0];
+        assert!((10..30).contains(&first.x) && (2..18).contains(&first.y));
+
+        // The same image in every order, with a seed.
+        let dims: [u32; 2] = [40, 30];
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(dims[0], dims[1]),
+        );
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(2)
+            .seed(5)
+            .threads(3)
+            .build()
+            .unwrap();
+        let reference = canvas.render_hdr(&settings).unwrap();
+        for order in [TileOrder::Spiral, TileOrder::Hilbert, TileOrder::Cost] {
+            let tiled = RenderSettings {
+                tile_size: 8,
+                tile_order: order,
+                ..settings.clone()
+            };
+            let hdr = canvas.render_hdr(&tiled).unwrap();
+            assert_eq!(hdr.data, reference.data, "{:?}", order);
+        }
+        let invalid = RenderSettings {
+            tile_size: 0,
+            ..settings.clone()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn work_stealing() {
+        use saturno::render::settings::TileOrder;
+        use saturno::render::tiles::WorkQueues;
+        use std::time::Duration;
+
+        let tile = |x, y, size| Crop {
+            x,
+            y,
+            width: size,
+            height: size,
+        };
+        // A thread done with its tiles takes half of those of another not
+        // started yet.
+        let queues = WorkQueues::new(&[
+            vec![tile(0, 0, 4), tile(4, 0, 4), tile(8, 0, 4), tile(12, 0, 4)],
+            vec![],
+        ]);
+        assert_eq!(queues.next(0), Some((0, 0)));
+        assert_eq!(queues.next(1), Some((8, 0)));
+        assert_eq!(queues.steals(), 1);
+
+        // Without tiles left, the second half of the pixels left in the
+        // tile being rendered: every pixel is still rendered once.
+        let queues = WorkQueues::new(&[vec![tile(0, 0, 4)], vec![], vec![]]);
+        assert_eq!(queues.next(0), Some((0, 0)));
+        assert_eq!(queues.next(1), Some((0, 2)));
+        let mut seen = vec![(0, 0), (0, 2)];
+        let mut by_thread = [1, 1, 0];
+        while by_thread.iter().sum::<usize>() < 16 {
+            for (t, count) in by_thread.iter_mut().enumerate() {
+                if let Some(pixel) = queues.next(t) {
+                    seen.push(pixel);
+                    *count += 1;
+                }
+            }
+        }
+        assert_eq!((0..3).find_map(|t| queues.next(t)), None);
+        seen.sort_by_key(|&(x, y)| (y, x));
+        let all: Vec<(u32, u32)> =
+            (0..4).flat_map(|y| (0..4).map(move |x| (x, y))).collect();
+        assert_eq!(seen, all);
+        assert!(by_thread.iter().all(|&n| n > 0), "{:?}", by_thread);
+
+        // A slow tile is shared by the threads.
+        let queues =
+            WorkQueues::new(&[vec![tile(0, 0, 32)], vec![], vec![], vec![]]);
+        let by_thread: Vec<usize> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..4)
+                .map(|t| {
+                    let queues = &queues;
+                    scope.spawn(move || {
+                        let mut count = 0;
+                        while queues.next(t).is_some() {
+                            std::thread::sleep(Duration::from_micros(20));
+                            count += 1;
+                        }
+                        count
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+        assert_eq!(by_thread.iter().sum::<usize>(), 32 * 32);
+        assert!(by_thread.iter().all(|&n| n > 0), "{:?}", by_thread);
+
+        // The same image, with a seed, however the pixels were shared.
+        let dims: [u32; 2] = [32, 16];
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(dims[0], dims[1]),
+        );
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .seed(3)
+            .threads(1)
+            .build()
+            .unwrap();
+        let reference = canvas.render_hdr(&settings).unwrap();
+        let stolen = RenderSettings {
+            threads: 4,
+            tile_size: 64,
+            tile_order: TileOrder::Spiral,
+            ..settings.clone()
+        };
+        assert_eq!(canvas.render_hdr(&stolen).unwrap().data, reference.data);
+    }
+
+    #[test]
+    fn streamed_meshes() {
+        use saturno::io::chunked;
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::geometry_cache::GeometryCache;
+        use saturno::scene::mesh::Mesh;
+
+        let mut path = init_image_testing();
+        path.push("streamed_meshes.smc");
+
+        // A bumpy height field of 24x24 cells: 1152 triangles.
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let cells = 24;
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        for j in 0..=cells {
+            for i in 0..=cells {
+                let (u, v) = (i as Float / 24.0, j as Float / 24.0);
+                let (x, z) = (4.0 * u - 2.0, 4.0 * v - 2.0);
+                positions.push([x, 0.3 * (2.0 * x).sin() * z.cos(), z]);
+                uvs.push([u, v]);
+            }
+        }
+        let mut triangles = vec![];
+        for j in 0..cells {
+            for i in 0..cells {
+                let corner = j * (cells + 1) + i;
+                let next = corner + cells + 1;
+                triangles.push([corner, next, corner + 1]);
+                triangles.push([corner + 1, next, next + 1]);
+            }
+        }
+        let mesh =
+            Mesh::new(positions, uvs, triangles, material.clone()).unwrap();
+        chunked::write_chunked_mesh(&path, &mesh, 64).unwrap();
+
+        // Opening reads only the bounds of the chunks.
+        let chunk_bytes = 4096 * std::mem::size_of::<Float>();
+        let cache = Arc::new(GeometryCache::new(chunk_bytes));
+        let mut streamed =
+            chunked::read_chunked_mesh(&path, cache.clone(), material.clone())
+                .unwrap();
+        assert_eq!(streamed.chunks(), 32);
+        assert_eq!(streamed.triangles(), mesh.triangles());
+        assert_eq!(cache.stats().misses, 0);
+
+        let hit = |actor: &dyn Hittable, [x, y, z]: [Float; 3]| {
+            let ray =
+                Ray::new(arr1(&[x, y, z, 1.0]), arr1(&[0.1, -1.0, -0.05, 0.0]));
+            let mut hit = Hit::new();
+            if actor.is_hit(&ray, 0.0001, Float::MAX, &mut hit) {
+                Some(hit)
+            } else {
+                None
+            }
+        };
+        // Same hits as the whole mesh (but for the rounding to f32), across
+        // the chunks and again after they were evicted.
+        for _ in 0..2 {
+            for k in 0..400 {
+                let (x, z) = (
+                    -2.4 + 4.8 * (k % 20) as Float / 19.0,
+                    -2.4 + 4.8 * (k / 20) as Float / 19.0,
+                );
+                match (hit(&mesh, [x, 5.0, z]), hit(&streamed, [x, 5.0, z])) {
+                    (Some(expected), Some(hit)) => {
+                        assert!((hit.t - expected.t).abs() < 1e-5);
+                        for i in 0..3 {
+                            assert!(
+                                (hit.normal[i] - expected.normal[i]).abs()
+                                    < 1e-5
+                            );
+                        }
+                        assert!((hit.uv[0] - expected.uv[0]).abs() < 1e-5);
+                    }
+                    (None, None) => {}
+                    _ => panic!("hit by only one mesh at ({}, {})", x, z),
+                }
+            }
+        }
+        let stats = cache.stats();
+        assert!(stats.evictions > 0);
+        assert!(stats.misses > streamed.chunks() as u64);
+        assert!(stats.resident_bytes <= chunk_bytes);
+        assert_eq!(stats.failures, 0);
+
+        // Moved as a whole, its chunks where they were.
+        streamed.translate(&[1.0, 2.0, 0.0]);
+        let moved = hit(&streamed, [0.5, 5.0, 0.0]).unwrap();
+        let expected = hit(&mesh, [-0.5, 3.0, 0.0]).unwrap();
+        assert!((moved.t - expected.t).abs() < 1e-5);
+        assert!((moved.point[1] - (expected.point[1] + 2.0)).abs() < 1e-5);
+        let low = streamed.bounding_box().unwrap().min[1];
+        assert!(
+            (low - (mesh.bounding_box().unwrap().min[1] + 2.0)).abs() < 1e-5
+        );
+
+        // Truncated files are refused when opened.
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 12]).unwrap();
+        assert!(chunked::read_chunked_mesh(
+            &path,
+            cache.clone(),
+            material.clone()
+        )
+        .is_err());
+        // As are chunk tables longer than the file.
+        let mut header = bytes[..8].to_vec();
+        header[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &header).unwrap();
+        assert!(chunked::read_chunked_mesh(&path, cache, material).is_err());
+    }
+
+    #[test]
+    fn compressed_hierarchy() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::mesh::Mesh;
+
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        // A bumpy height field of 64x64 cells, folded so some rays cross
+        // it several times.
+        let cells = 64;
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        for j in 0..=cells {
+            for i in 0..=cells {
+                let (u, v) = (i as Float / 64.0, j as Float / 64.0);
+                let (x, z) = (4.0 * u - 2.0, 4.0 * v - 2.0);
+                positions.push([x, (3.0 * x).sin() * (2.0 * z).cos(), z]);
+                uvs.push([u, v]);
+            }
+        }
+        let mut triangles = vec![];
+        for j in 0..cells {
+            for i in 0..cells {
+                let corner = j * (cells + 1) + i;
+                let next = corner + cells + 1;
+                triangles.push([corner, next, corner + 1]);
+                triangles.push([corner + 1, next, next + 1]);
+            }
+        }
+        let mesh = Mesh::new(positions, uvs, triangles, material).unwrap();
+        let compressed = mesh.clone().compressed().unwrap();
+        assert!(!mesh.is_compressed() && compressed.is_compressed());
+        assert!(3 * compressed.hierarchy_bytes() < mesh.hierarchy_bytes());
+        let (bounds, wide) = (
+            mesh.bounding_box().unwrap(),
+            compressed.bounding_box().unwrap(),
+        );
+        for i in 0..3 {
+            assert!(wide.min[i] <= bounds.min[i]);
+            assert!(wide.max[i] >= bounds.max[i]);
+            assert!(
+                wide.max[i] - wide.min[i]
+                    < 1.01 * (bounds.max[i] - bounds.min[i]) + 1e-6
+            );
+        }
+
+        // The same hits, through the quantized bounds.
+        let hit = |mesh: &Mesh, origin: [Float; 3], direction: [Float; 3]| {
+            let ray = Ray::new(
+                arr1(&[origin[0], origin[1], origin[2], 1.0]),
+                arr1(&[direction[0], direction[1], direction[2], 0.0]),
+            );
+            let mut hit = Hit::new();
+            if mesh.is_hit(&ray, 0.0001, Float::MAX, &mut hit) {
+                Some(hit)
+            } else {
+                None
+            }
+        };
+        let check = |moved: &Mesh, original: &Mesh, offset: [Float; 3]| {
+            let mut hits = 0;
+            for k in 0..900 {
+                let (a, b) =
+                    ((k % 30) as Float / 29.0, (k / 30) as Float / 29.0);
+                let origin = [5.0 * a - 2.5, 3.0, 5.0 * b - 2.5];
+                let direction = [0.6 * b - 0.3, -1.0, 0.8 * a - 0.4];
+                // Grazing too.
+                let sideways = [-3.0, 2.0 * a - 1.0, 4.0 * b - 2.0];
+                for (origin, direction) in
+                    [(origin, direction), (sideways, [1.0, 0.05, 0.02])]
+                {
+                    let shifted = [0, 1, 2].map(|i| origin[i] + offset[i]);
+                    match (
+                        hit(original, origin, direction),
+                        hit(moved, shifted, direction),
+                    ) {
+                        (Some(expected), Some(hit)) => {
+                            assert!((hit.t - expected.t).abs() < 1e-9);
+                            for i in 0..3 {
+                                assert!(
+                                    (hit.normal[i] - expected.normal[i]).abs()
+                                        < 1e-6
+                                );
+                            }
+                            hits += 1;
+                        }
+                        (None, None) => {}
+                        _ => panic!(
+                            "hit by only one hierarchy from {:?}",
+                            origin
+                        ),
+                    }
+                }
+            }
+            assert!(hits > 900);
+        };
+        check(&compressed, &mesh, [0.0; 3]);
+
+        // Still compressed once moved or subdivided.
+        let mut moved = compressed.clone();
+        moved.translate(&[10.0, -3.0, 0.5]);
+        assert!(moved.is_compressed());
+        check(&moved, &mesh, [10.0, -3.0, 0.5]);
+        let fine = compressed.subdivide();
+        assert!(fine.is_compressed());
+        check(&fine, &mesh.subdivide(), [0.0; 3]);
+    }
+
+    #[test]
+    fn parallel_hierarchy_build() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::bvh;
+        use saturno::scene::mesh::Mesh;
+
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        // 131072 triangles, enough to build on several threads.
+        let cells = 256;
+        let height =
+            |x: Float, z: Float| 0.2 * (3.0 * x).sin() * (2.0 * z).cos();
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        for j in 0..=cells {
+            for i in 0..=cells {
+                let (u, v) = (i as Float / 256.0, j as Float / 256.0);
+                let (x, z) = (4.0 * u - 2.0, 4.0 * v - 2.0);
+                positions.push([x, height(x, z), z]);
+                uvs.push([u, v]);
+            }
+        }
+        let mut triangles = vec![];
+        for j in 0..cells {
+            for i in 0..cells {
+                let corner = j * (cells + 1) + i;
+                let next = corner + cells + 1;
+                triangles.push([corner, next, corner + 1]);
+                triangles.push([corner + 1, next, next + 1]);
+            }
+        }
+        let build = |threads: usize| {
+            bvh::set_build_threads(threads);
+            Mesh::new(
+                positions.clone(),
+                uvs.clone(),
+                triangles.clone(),
+                material.clone(),
+            )
+            .unwrap()
+        };
+        let serial = build(1);
+        let parallel = build(4);
+        bvh::set_build_threads(0);
+
+        // The same hierarchy on any number of threads.
+        assert_eq!(serial.hierarchy_bytes(), parallel.hierarchy_bytes());
+        assert_eq!(serial.bounding_box(), parallel.bounding_box());
+        let hit = |mesh: &Mesh, x: Float, z: Float| {
+            let ray =
+                Ray::new(arr1(&[x, 3.0, z, 1.0]), arr1(&[0.0, -1.0, 0.0, 0.0]));
+            let mut hit = Hit::new();
+            assert!(mesh.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+            hit
+        };
+        for k in 0..400 {
+            let (x, z) = (
+                -1.9 + 3.8 * (k % 20) as Float / 19.0,
+                -1.9 + 3.8 * (k / 20) as Float / 19.0,
+            );
+            let (a, b) = (hit(&serial, x, z), hit(&parallel, x, z));
+            assert_eq!(a.t, b.t);
+            assert_eq!(a.normal, b.normal);
+            assert!((3.0 - a.t - height(x, z)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn mesh_cache() {
+        use saturno::io::mesh_cache::read_cached_mesh;
+        use saturno::io::mesh_cache::MeshCache;
+        use saturno::prelude::Units;
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::actor::Visibility;
+        use saturno::scene::description::MaterialDescription;
+        use saturno::scene::description::MeshDescription;
+        use saturno::scene::mesh::Mesh;
+
+        let mut base = init_image_testing();
+        base.push("mesh_cache");
+        let _ = std::fs::remove_dir_all(&base);
+        let cache = MeshCache::new(&base.join("built")).unwrap();
+
+        // A bumpy grid of 16x16 cells.
+        let obj = |bump: Float| {
+            let mut obj = String::new();
+            for j in 0..=16 {
+                for i in 0..=16 {
+                    let (x, z) =
+                        (i as Float / 4.0 - 2.0, j as Float / 4.0 - 2.0);
+                    let y = bump * (3.0 * x).sin() * (2.0 * z).cos();
+                    obj += &format!("v {} {} {}\n", x, y, z);
+                }
+            }
+            for j in 0..16 {
+                for i in 0..16 {
+                    let corner = j * 17 + i + 1;
+                    obj += &format!(
+                        "f {} {} {} {}\n",
+                        corner,
+                        corner + 17,
+                        corner + 18,
+                        corner + 1
+                    );
+                }
+            }
+            obj
+        };
+        std::fs::write(base.join("grid.obj"), obj(0.3)).unwrap();
+        let material = Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        ));
+        let description = |compressed: bool| MeshDescription {
+            path: "grid.obj".to_string(),
+            units: None,
+            material: MaterialDescription::Lambertian {
+                albedo: [0.5, 0.5, 0.5],
+            },
+            visibility: Visibility::default(),
+            deformation: vec![],
+            compressed,
+            displacement: None,
+        };
+        let read = |compressed: bool| {
+            read_cached_mesh(
+                &description(compressed),
+                &base,
+                Units::Meters,
+                material.clone(),
+                &cache,
+            )
+            .unwrap()
+        };
+        let cached_files =
+            || std::fs::read_dir(base.join("built")).unwrap().count();
+
+        let hit = |mesh: &Mesh, x: Float, z: Float| {
+            let ray = Ray::new(
+                arr1(&[x, 3.0, z, 1.0]),
+                arr1(&[0.1, -1.0, 0.05, 0.0]),
+            );
+            let mut hit = Hit::new();
+            assert!(mesh.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+            hit
+        };
+        let same = |a: &Mesh, b: &Mesh| {
+            assert_eq!(a.encode(), b.encode());
+            for k in 0..100 {
+                let (x, z) = (
+                    (k % 10) as Float / 3.0 - 1.5,
+                    (k / 10) as Float / 3.0 - 1.5,
+                );
+                let (a, b) = (hit(a, x, z), hit(b, x, z));
+                assert_eq!((a.t, a.normal, a.uv), (b.t, b.normal, b.uv));
+            }
+        };
+
+        // Built and cached the first time, read back the next ones, with
+        // either hierarchy.
+        let built = read(false);
+        assert_eq!(cached_files(), 1);
+        same(&read(false), &built);
+        let compressed = read(true);
+        assert!(compressed.is_compressed());
+        assert_eq!(cached_files(), 2);
+        let cached = read(true);
+        assert!(cached.is_compressed());
+        same(&cached, &compressed);
+
+        // Subdivided and deforming meshes too.
+        let fine = built.subdivide();
+        let moved = (0..fine.vertices())
+            .map(|v| {
+                let p = fine.position(v);
+                [p[0], p[1] + 0.1, p[2]]
+            })
+            .collect();
+        let deforming = fine.with_deformation(vec![moved]).unwrap();
+        let decoded =
+            Mesh::decode(&deforming.encode(), material.clone()).unwrap();
+        assert_eq!(decoded.deformation_samples(), 2);
+        same(&decoded, &deforming);
+
+        // Damaged files are built again, changed files get their own.
+        let files: Vec<_> = std::fs::read_dir(base.join("built"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        let contents: Vec<_> = files
+            .iter()
+            .map(|file| std::fs::read(file).unwrap())
+            .collect();
+        for (file, bytes) in files.iter().zip(&contents) {
+            std::fs::write(file, &bytes[..bytes.len() / 2]).unwrap();
+        }
+        same(&read(false), &built);
+        same(&read(true), &compressed);
+        for (file, bytes) in files.iter().zip(&contents) {
+            assert_eq!(&std::fs::read(file).unwrap(), bytes);
+        }
+        std::fs::write(base.join("grid.obj"), obj(0.2)).unwrap();
+        let changed = read(false);
+        assert_eq!(cached_files(), 3);
+        assert!(hit(&changed, 0.3, 0.3).t != hit(&built, 0.3, 0.3).t);
+        assert!(Mesh::decode(&built.encode()[..100], material).is_err());
+    }
+
+    #[test]
+    fn mesh_displacement() {
+        use saturno::io::mesh::read_meshes;
+        use saturno::io::mesh_cache::MeshCache;
+        use saturno::scene::actor::Hit;
+        use saturno::scene::description::SceneDescription;
+
+        let mut base = init_image_testing();
+        base.push("mesh_displacement");
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+
+        // A flat square facing up, and a white height map.
+        let obj = "v -1 0 -1\nv -1 0 1\nv 1 0 1\nv 1 0 -1\nf 1 2 3 4\n";
+        std::fs::write(base.join("square.obj"), obj).unwrap();
+        image::GrayImage::from_pixel(4, 4, image::Luma([255]))
+            .save(base.join("heights.png"))
+            .unwrap();
+        let scene = |displacement: &str| -> SceneDescription {
+            let json = format!(
+                r#"{{
+                    "camera": {{"look_from": [0, 5, 5], "look_at": [0, 0, 0]}},
+                    "meshes": [{{
+                        "path": "square.obj",
+                        "material": {{"type": "lambertian", "albedo": [0.5, 0.5, 0.5]}}
+                        {}
+                    }}]
+                }}"#,
+                displacement
+            );
+            serde_json::from_str(&json).unwrap()
+        };
+        let height = |scene: &SceneDescription, cache: Option<&MeshCache>| {
+            let meshes = read_meshes(scene, &base, cache).unwrap();
+            let ray = Ray::new(
+                arr1(&[0.3, 3.0, 0.2, 1.0]),
+                arr1(&[0.0, -1.0, 0.0, 0.0]),
+            );
+            let mut hit = Hit::new();
+            assert!(meshes[0].is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+            3.0 - hit.t
+        };
+
+        // Moved along the normals by the scale times the gray of the map,
+        // read by the scene with the mesh, cached or not.
+        let flat = scene("");
+        let displaced = scene(
+            r#", "displacement": {"path": "heights.png", "scale": 0.5, "subdivisions": 1}"#,
+        );
+        assert!(height(&flat, None).abs() < 1e-9);
+        assert!((height(&displaced, None) - 0.5).abs() < 1e-6);
+        let cache = MeshCache::new(&base.join("built")).unwrap();
+        assert!(height(&flat, Some(&cache)).abs() < 1e-9);
+        assert!((height(&displaced, Some(&cache)) - 0.5).abs() < 1e-6);
+
+        let missing =
+            scene(r#", "displacement": {"path": "missing.png", "scale": 1}"#);
+        assert!(read_meshes(&missing, &base, None).is_err());
+    }
+
+    #[test]
+    fn scene_canvas() {
+        use saturno::io::scene::build_without_files;
+        use saturno::io::scene::scene_canvas;
+        use saturno::scene::description::SceneDescription;
+
+        let json = r#"{
+            "camera": {"look_from": [0, 0, 5], "look_at": [0, 0, 0]},
+            "spheres": [{"center": [0, 0, 0], "radius": 1,
+                         "material": {"type": "lambertian",
+                                      "albedo": [0.5, 0.5, 0.5]}}],
+            "lights": [{"type": "sun", "direction": [0, 1, 1],
+                        "illuminance": 100000}],
+            "fog": {"density": 0.1}
+        }"#;
+        let scene: SceneDescription = serde_json::from_str(json).unwrap();
+        let canvas = scene_canvas(&scene, 32, 16, vec![]).unwrap();
+        assert_eq!(canvas.world.actors.len(), 1);
+        assert_eq!(canvas.lights.len(), 1);
+        assert_eq!(canvas.fog.as_ref().unwrap().density, 0.1);
+        assert!(canvas.atmosphere.is_none() && canvas.black_hole.is_none());
+        let camera = canvas.camera();
+        assert_eq!((camera.resolution_x, camera.resolution_y), (32, 16));
+
+        // The meshes read apart join the actors.
+        let meshes = scene.actors().unwrap();
+        let canvas = scene_canvas(&scene, 32, 16, meshes).unwrap();
+        assert_eq!(canvas.world.actors.len(), 2);
+
+        // Without files, instances of spheres are built, meshes refused
+        // rather than left out.
+        let mut scene: SceneDescription = serde_json::from_str(
+            r#"{
+                "camera": {"look_from": [0, 0, 5], "look_at": [0, 0, 0]},
+                "prototypes": {"ball": {"spheres": [{
+                    "center": [0, 0, 0],
+                    "radius": 1,
+                    "material": {"type": "lambertian", "albedo": [1, 1, 1]}
+                }]}},
+                "instances": [{"prototype": "ball"}]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(build_without_files(&scene).unwrap().len(), 1);
+        scene.meshes = serde_json::from_str(
+            r#"[{"path": "teapot.obj",
+                 "material": {"type": "lambertian", "albedo": [1, 1, 1]}}]"#,
+        )
+        .unwrap();
+        let error = build_without_files(&scene).err().unwrap();
+        assert!(error.contains("teapot.obj"));
+    }
+
+    #[test]
+    fn ray_differentials() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::material::Scattering;
+        use saturno::scene::texture::Texture;
+
+        let camera = Camera::new(
+            90.0,
+            100,
+            100,
+            arr1(&[0.0, 0.0, 0.0, 1.0]),
+            arr1(&[0.0, 0.0, -1.0, 1.0]),
+            arr1(&[0.0, 1.0, 0.0, 0.0]),
+            0.0,
+        );
+        // A pixel is 0.02 wide at the focus distance of 1.
+        let mut ray = camera.get_ray(50.0, 50.0);
+        let spread = |ray: &Ray| {
+            let differentials = ray.differentials.as_ref().unwrap();
+            let d = differentials.x_direction.clone() - ray.direction.clone();
+            d.dot(&d).sqrt()
+        };
+        assert!((spread(&ray) - 0.02).abs() < 1e-4);
+
+        // 256x256 checker of black and white texels, averaging to grey.
+        let texels = (0..256 * 256)
+            .map(|i| {
+                let value = ((i % 256 + i / 256) % 2) as Float;
+                [value, value, value, 1.0]
+            })
+            .collect();
+        let texture = Arc::new(Texture::new(256, 256, texels).unwrap());
+        let sphere = |distance: Float, material: Arc<dyn Scattering>| Sphere {
+            center: arr1(&[0.0, 0.0, -distance, 1.0]),
+            radius: 0.5,
+            material,
+        };
+        let textured = Arc::new(Lambertian::textured(
+            arr1(&[1.0, 1.0, 1.0, 1.0]),
+            texture,
+        ));
+
+        // The footprint grows with the distance: a pixel covers
+        // 0.02 * (distance - 0.5) of the sphere, latitude spans pi * 0.5.
+        let mut footprints = vec![];
+        for distance in [1.0, 3.0] {
+            let mut hit = Hit::new();
+            let target = sphere(distance, textured.clone());
+            assert!(target.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+            let expected = 0.02 * (distance - 0.5) / (consts::PI * 0.5);
+            assert!((hit.footprint / expected - 1.0).abs() < 0.05);
+            assert!(hit.differentials.is_some());
+            footprints.push(hit.footprint);
+
+            // Far away the texels are smaller than a pixel and filter
+            // to grey.
+            if distance == 3.0 {
+                let color = hit.material.color(&hit);
+                assert!((color[0] - 0.5).abs() < 1e-9);
+            }
+        }
+        assert!(footprints[1] > 4.0 * footprints[0]);
+
+        // Scaled down for several samples per pixel.
+        ray.scale_differentials(0.5);
+        assert!((spread(&ray) - 0.01).abs() < 1e-4);
+        let ray = camera.get_ray(50.0, 50.0);
+
+        // Specular bounces carry the differentials (a convex mirror
+        // spreads them), diffuse bounces drop them.
+        let mirror = sphere(
+            1.0,
+            Arc::new(Metal::new(
+                arr1(&[1.0, 1.0, 1.0, 1.0]),
+                Shading::COLOR,
+                0.0,
+            )),
+        );
+        let mut hit = Hit::new();
+        assert!(mirror.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+        let mut attenuation = arr1(&[0.0, 0.0, 0.0, 1.0]);
+        let mut scattered =
+            Ray::new(arr1(&[0.0, 0.0, 0.0, 1.0]), arr1(&[0.0, 0.0, 1.0, 0.0]));
+        assert!(hit.material.scatter(
+            &ray,
+            &hit,
+            &mut attenuation,
+            &mut scattered,
+            0
+        ));
+        assert!(spread(&scattered) > 2.0 * spread(&ray));
+        let differentials = scattered.differentials.as_ref().unwrap();
+        let offset = differentials.x_origin.clone() - hit.point.clone();
+        assert!((offset.dot(&offset).sqrt() - 0.01).abs() < 1e-4);
+
+        let glass = sphere(
+            1.0,
+            Arc::new(Dielectric::new(
+                arr1(&[1.0, 1.0, 1.0, 1.0]),
+                Shading::COLOR,
+                1.0,
+            )),
+        );
+        assert!(glass.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+        hit.material
+            .scatter(&ray, &hit, &mut attenuation, &mut scattered, 0);
+        // An index of 1 does not bend the rays.
+        assert!((spread(&scattered) - spread(&ray)).abs() < 1e-6);
+
+        let diffuse = sphere(1.0, textured);
+        assert!(diffuse.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+        hit.material
+            .scatter(&ray, &hit, &mut attenuation, &mut scattered, 0);
+        assert!(scattered.differentials.is_none());
+    }
+
+    #[test]
+    fn render_toon() {
+        use saturno::render::settings::Toon;
+
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.8, 0.4, 0.2, 1.0]),
+                Shading::COLOR,
+            )),
+        })];
+        let canvas = Canvas::new(actors, scenes::two_spheres_camera(40, 20));
+
+        let toon = Toon::default();
+        assert_eq!(toon.shade(-0.5), 0.2);
+        assert!((toon.shade(0.1) - (0.2 + 0.8 / 3.0)).abs() < 1e-12);
+        assert_eq!(toon.shade(1.0), 1.0);
+
+        let settings = RenderSettings::builder()
+            .size(40, 20)
+            .samples(4)
+            .seed(3)
+            .transparent_background(true)
+            .toon(toon)
+            .build()
+            .unwrap();
+        let image = canvas.render_hdr(&settings).unwrap();
+
+        // Flat bands of the base color, or ink.
+        let levels = [0.0, 0.2, 0.2 + 0.8 / 3.0, 0.2 + 1.6 / 3.0, 1.0];
+        let mut inked = 0;
+        for i in 0..image.size() {
+            let [r, g, _, alpha] = image.get_pixel(i);
+            if alpha == 0.0 {
+                continue;
+            }
+            // Samples of a pixel may fall in different bands.
+            assert!((r / 0.8 - g / 0.4).abs() < 1e-9);
+            if r == 0.0 {
+                inked += 1;
+            }
+        }
+        assert!(inked > 0);
+        let center = image.get_pixel(10 * 40 + 20);
+        assert!(levels[1..]
+            .iter()
+            .any(|l| (center[0] - 0.8 * l).abs() < 1e-9));
+
+        // Outlines (the sphere against the background) come first.
+        let edge = (0..40)
+            .map(|x| image.get_pixel(10 * 40 + x))
+            .find(|pixel| pixel[3] > 0.0)
+            .unwrap();
+        assert_eq!(edge, [0.0, 0.0, 0.0, 1.0]);
+
+        // Without ink every pixel is lit at least by the ambient.
+        let flat = RenderSettings {
+            toon: Some(Toon {
+                silhouette: 0.0,
+                outlines: false,
+                ..toon
+            }),
+            ..settings.clone()
+        };
+        let image = canvas.render_hdr(&flat).unwrap();
+        for i in 0..image.size() {
+            let [r, _, _, alpha] = image.get_pixel(i);
+            assert!(r >= 0.8 * 0.2 * alpha - 1e-9);
+        }
+
+        // Crops match the full render, outlines included.
+        let full = canvas.render_hdr(&settings).unwrap();
+        let crop = Crop {
+            x: 5,
+            y: 3,
+            width: 12,
+            height: 8,
+        };
+        let cropped = canvas
+            .render_hdr(&RenderSettings {
+                crop: Some(crop),
+                ..settings.clone()
+            })
+            .unwrap();
+        for y in 0..crop.height {
+            for x in 0..crop.width {
+                assert_eq!(
+                    cropped.get_pixel((y * crop.width + x) as usize),
+                    full.get_pixel(((y + crop.y) * 40 + x + crop.x) as usize)
+                );
+            }
+        }
+
+        assert!(RenderSettings::builder()
+            .toon(Toon { bands: 0, ..toon })
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn render_prelude() {
+        use saturno::prelude::*;
+
+        let actors: Vec<Box<dyn RayTraceable>> = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(Metal::new(
+                arr1(&[0.8, 0.6, 0.2, 1.0]),
+                Shading::COLOR,
+                0.3,
+            )),
+        })];
+        let canvas = Canvas::new(actors, scenes::two_spheres_camera(20, 10));
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .seed(3)
+            .build()
+            .unwrap();
+
+        let renderer = Renderer::new(canvas, settings);
+        let image: Image = renderer.render().unwrap();
+        assert_eq!(image.size(), 200);
+        assert_eq!(image.data, renderer.render().unwrap().data);
+    }
+
+    #[test]
+    fn render_progressive() {
+        let dims: [u32; 2] = [20, 10];
+        let canvas = Canvas::new(
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(dims[0], dims[1]),
+        );
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(4)
+            .seed(7)
+            .build()
+            .unwrap();
+
+        let mut progressive = Progressive::new(settings.clone());
+        assert!(progressive.step(&canvas).unwrap());
+
+        // The first pass is a single sample render.
+        let single = RenderSettings {
+            samples: 1,
+            ..settings.clone()
+        };
+        assert_eq!(
+            progressive.hdr().data,
+            canvas.render_hdr(&single).unwrap().data
+        );
+
+        while progressive.step(&canvas).unwrap() {}
+        assert!(progressive.is_done());
+        assert_eq!(progressive.passes(), 4);
+        assert!(!progressive.step(&canvas).unwrap());
+
+        let image = progressive.image();
+        assert_eq!((image.width, image.height), (dims[0], dims[1]));
+        assert_ne!(
+            progressive.hdr().data,
+            canvas.render_hdr(&single).unwrap().data
+        );
+    }
+
+    #[test]
+    fn render_c_api() {
+        use crate::raytracer::external::*;
+
+        let dims: [u32; 2] = [20, 10];
+        let vec3 = |x, y, z| SaturnoVec3 { x, y, z };
+        let material = SaturnoMaterial {
+            kind: SaturnoMaterialKind::Metal,
+            color: vec3(0.8, 0.6, 0.2),
+            parameter: 0.0,
+        };
+        let camera = SaturnoCamera {
+            look_from: vec3(0.0, 0.0, 0.0),
+            look_at: vec3(0.0, 0.0, -1.0),
+            up: vec3(0.0, 1.0, 0.0),
+            vertical_fov: 90.0,
+            aperture: 0.0,
+        };
+
+        let mut scene = saturno_scene_new();
+        let status = saturno_scene_add_sphere(
+            Some(&mut scene),
+            vec3(0.0, 0.0, -1.0),
+            0.5,
+            material,
+        );
+        assert_eq!(status, SaturnoStatus::Ok);
+        let status = saturno_scene_add_sphere(
+            Some(&mut scene),
+            vec3(0.0, 0.0, -1.0),
+            0.0,
+            material,
+        );
+        assert_eq!(status, SaturnoStatus::InvalidArgument);
+        let status = saturno_scene_set_camera(Some(&mut scene), camera);
+        assert_eq!(status, SaturnoStatus::Ok);
+
+        let size = (dims[0] * dims[1] * 4) as usize;
+        let mut buffer = vec![0u8; size];
+        let render = |scene: &mut SaturnoScene, buffer: &mut [u8], len| unsafe {
+            saturno_scene_render(
+                Some(scene),
+                dims[0],
+                dims[1],
+                1,
+                buffer.as_mut_ptr(),
+                len,
+            )
+        };
+        assert_eq!(
+            render(&mut scene, &mut buffer, size - 1),
+            SaturnoStatus::BufferTooSmall
+        );
+        assert_eq!(render(&mut scene, &mut buffer, size), SaturnoStatus::Ok);
+
+        // Without fuzz, the metal sphere renders deterministically.
+        let actors = vec![Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+            radius: 0.5,
+            material: Arc::new(Metal::new(
+                arr1(&[0.8, 0.6, 0.2, 1.0]),
+                Shading::COLOR,
+                0.0,
+            )),
+        }) as Box<dyn RayTraceable>];
+        let canvas =
+            Canvas::new(actors, scenes::two_spheres_camera(dims[0], dims[1]));
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .build()
+            .unwrap();
+        assert_eq!(buffer, canvas.render_scene(&settings).unwrap().data);
+
+        saturno_scene_free(Some(scene));
+        saturno_scene_free(None);
+    }
+
+    #[test]
+    fn render_stream() {
+        use futures::executor::block_on;
+        use futures::StreamExt;
+        use saturno::render::canvas::Renderer;
+        use saturno::render::stream::render_stream;
+
+        let dims: [u32; 2] = [20, 10];
+        let canvas = || {
+            Canvas::new(
+                scenes::two_spheres_normals(),
+                scenes::two_spheres_camera(dims[0], dims[1]),
+            )
+        };
+        let settings = RenderSettings::builder()
+            .size(dims[0], dims[1])
+            .samples(3)
+            .seed(11)
+            .build()
+            .unwrap();
+
+        let stream = render_stream(Renderer::new(canvas(), settings.clone()));
+        let passes: Vec<_> = block_on(stream.collect());
+        assert_eq!(passes.len(), 3);
+
+        let mut progressive = Progressive::new(settings);
+        for (i, update) in passes.into_iter().enumerate() {
+            let update = update.unwrap();
+            assert_eq!((update.pass, update.passes), (i as u32, 3));
+
+            progressive.step(&canvas()).unwrap();
+            assert_eq!(update.hdr.data, progressive.hdr().data);
+            assert_eq!(update.image.data, progressive.image().data);
+        }
+
+        // Errors end the stream.
+        let invalid = RenderSettings {
+            samples: 2,
+            width: 40,
+            ..RenderSettings::default()
+        };
+        let stream = render_stream(Renderer::new(canvas(), invalid));
+        let passes: Vec<_> = block_on(stream.collect());
+        assert_eq!(passes.len(), 1);
+        assert!(passes[0].is_err());
+    }
+
+    #[test]
+    fn render_scene_description() {
+        use saturno::scene::description::MaterialDescription;
+        use saturno::scene::description::SceneDescription;
+
+        let json = r#"{
+            "camera": {"look_from": [0, 0, 0], "look_at": [0, 0, -1]},
+            "spheres": [
+                {"center": [0, 0, -1], "radius": 0.5,
+                 "material": {"type": "metal", "color": [0.8, 0.6, 0.2]}},
+                {"center": [0, 2, -1], "radius": 0.5,
+                 "material": {"type": "diffuse_light", "color": [1, 1, 1],
+                              "light_group": "key"}}
+            ]
+        }"#;
+        let scene: SceneDescription = serde_json::from_str(json).unwrap();
+        assert_eq!(scene.camera.vertical_fov, 90.0);
+        assert_eq!(scene.camera.up, [0.0, 1.0, 0.0]);
+        assert_eq!(
+            scene.spheres[0].material,
+            MaterialDescription::Metal {
+                color: [0.8, 0.6, 0.2],
+                fuzz: 0.0,
+            }
+        );
+        assert_eq!(
+            scene.spheres[1].material,
+            MaterialDescription::DiffuseLight {
+                color: [1.0, 1.0, 1.0],
+                intensity: 1.0,
+                light_group: Some("key".to_string()),
+            }
+        );
+
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .seed(5)
+            .build()
+            .unwrap();
+        let canvas =
+            Canvas::new(scene.actors().unwrap(), scene.camera.build(20, 10));
+        assert!(canvas.validate(&settings).is_ok());
+        let image = canvas.render_scene(&settings).unwrap();
+        assert_eq!(image.size(), 200);
+
+        let unknown = r#"{"type": "glass", "refraction_idx": 1.5}"#;
+        assert!(serde_json::from_str::<MaterialDescription>(unknown).is_err());
+    }
+
+    #[test]
+    fn render_material_library() {
+        use saturno::scene::actor::Hit;
+        use saturno::scene::actor::Hittable;
+        use saturno::scene::description::SceneDescription;
+
+        let scene = |materials: &str, left: &str, right: &str| {
+            let json = format!(
+                r#"{{
+                    "camera": {{"look_from": [0, 0, 0], "look_at": [0, 0, -1]}},
+                    "materials": {{{}}},
+                    "spheres": [
+                        {{"center": [-0.6, 0, -1], "radius": 0.5,
+                          "material": {}}},
+                        {{"center": [0.6, 0, -1], "radius": 0.5,
+                          "material": {}}}
+                    ]
+                }}"#,
+                materials, left, right
+            );
+            serde_json::from_str::<SceneDescription>(&json).unwrap()
+        };
+        let paint = r#""paint": {"type": "lambertian", "albedo": [0.8, 0.3, 0.1]},
+            "coat": {"type": "clearcoat", "base": {"type": "named", "name": "paint"}}"#;
+        let named = r#"{"type": "named", "name": "coat"}"#;
+        let shared = scene(paint, named, named);
+
+        let library = shared.library().unwrap();
+        assert_eq!(library.names().collect::<Vec<_>>(), vec!["coat", "paint"]);
+
+        // Both spheres point to the same material.
+        let canvas =
+            Canvas::new(shared.actors().unwrap(), shared.camera.build(20, 10));
+        let hit = |x: Float| {
+            let ray = Ray::new(
+                arr1(&[x, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, -1.0, 0.0]),
+            );
+            let mut hit = Hit::new();
+            assert!(canvas.world.is_hit(&ray, 0.0001, Float::MAX, &mut hit));
+            hit
+        };
+        let (left, right) = (hit(-0.6), hit(0.6));
+        assert_ne!(left.actor_id, right.actor_id);
+        assert!(Arc::ptr_eq(&left.material, &right.material));
+
+        // Named materials render like the same materials inline.
+        let inline = r#"{"type": "clearcoat", "base":
+            {"type": "lambertian", "albedo": [0.8, 0.3, 0.1]}}"#;
+        let inline = scene("", inline, inline);
+        let settings = RenderSettings::builder()
+            .size(20, 10)
+            .samples(2)
+            .seed(7)
+            .build()
+            .unwrap();
+        let render = |scene: &SceneDescription| {
+            Canvas::new(scene.actors().unwrap(), scene.camera.build(20, 10))
+                .render_hdr(&settings)
+                .unwrap()
+                .data
+        };
+        assert_eq!(render(&shared), render(&inline));
+
+        let unknown =
+            scene("", r#"{"type": "named", "name": "chrome"}"#, named);
+        assert!(unknown.actors().is_err());
+        let circular = scene(
+            r#""a": {"type": "named", "name": "b"},
+               "b": {"type": "clearcoat", "base": {"type": "named", "name": "a"}}"#,
+            named,
+            named,
+        );
+        assert!(circular.library().is_err());
+        assert!(circular.actors().is_err());
+    }
 }
@@ -1,9 +1,20 @@
+use crate::raytracer::actor::RayTraceable;
+use crate::raytracer::actor::Sphere;
 use crate::raytracer::camera::Camera;
 use crate::raytracer::canvas::Canvas;
-use crate::raytracer::actor::RayTraceable;
-use crate::raytracer::Image;
+pub use crate::raytracer::canvas::Renderer;
+use crate::raytracer::common::Float;
+use crate::raytracer::material::Dielectric;
+use crate::raytracer::material::DiffuseLight;
+use crate::raytracer::material::Lambertian;
+use crate::raytracer::material::Metal;
+use crate::raytracer::material::Scattering;
+use crate::raytracer::material::Shading;
 use crate::raytracer::scenes;
-use ndarray::arr1;
+use crate::raytracer::settings::RenderSettings;
+use crate::raytracer::Image;
+use ndarray::{arr1, Array1};
+use std::sync::Arc;
 
 //extern crate web_sys;
 //use web_sys::console;
@@ -12,117 +23,359 @@ use ndarray::arr1;
 pub type Frame = Image;
 
 #[no_mangle]
-pub extern "C" fn get_renderer(scene_id: u32) -> Box<Canvas> {
-
+pub extern "C" fn get_renderer(scene_id: u32) -> Box<Renderer> {
     let dims: [u32; 2] = [200, 133];
-    let actors: Vec<Box<dyn RayTraceable>>;
-    let camera: Camera;
-    match scene_id {
-        0 => {
-                actors = scenes::random_book_cover();
-                camera = Camera::new(
-                    20.0,
-                    dims[0],
-                    dims[1],
-                    arr1(&[13.0, 2.0, 3.0, 1.0]),
-                    arr1(&[0.0, 0.0, 0.0, 1.0]),
-                    arr1(&[0.0, 1.0, 0.0, 0.0]),
-                    0.2,
-            );
-        },
-        _ => {
-                actors = scenes::two_spheres_normals();
-                camera = Camera::new(
-                    90.0,
-                    dims[0],
-                    dims[1],
-                    arr1(&[0.0, 0.0, 0.0, 1.0]),
-                    arr1(&[0.0, 0.0, -1.0, 1.0]),
-                    arr1(&[0.0, 1.0, 0.0, 0.0]),
-                    0.0,
-                );
-        }
-    }
+    let (actors, camera) = match scene_id {
+        0 => (
+            scenes::random_book_cover(),
+            scenes::book_cover_camera(dims[0], dims[1]),
+        ),
+        _ => (
+            scenes::two_spheres_normals(),
+            scenes::two_spheres_camera(dims[0], dims[1]),
+        ),
+    };
+
+    let settings = RenderSettings {
+        width: dims[0],
+        height: dims[1],
+        samples: 2,
+        ..RenderSettings::default()
+    };
 
-    Box::new(Canvas::new(dims[0], dims[1], actors, 2, camera))
+    Box::new(Renderer {
+        canvas: Canvas::new(actors, camera),
+        settings,
+    })
 }
 
+/**
+ * Returns a null pointer if the scene can not be rendered.
+ *
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Renderer>` handed out by this
+ * module.
+ */
 #[no_mangle]
-pub extern "C" fn render_scene(ptr: *mut Canvas) -> Box<Frame> {
-    let canvas = unsafe {
-        assert!(!ptr.is_null());
-        &mut *ptr
-    };
+pub unsafe extern "C" fn render_scene(
+    ptr: *mut Renderer,
+) -> Option<Box<Frame>> {
+    assert!(!ptr.is_null());
+    let renderer = &mut *ptr;
 
-    Box::new(canvas.render_scene())
+    renderer.render().ok().map(Box::new)
 }
 
 /**
  * This function recreates the entire renderer on every
- * frame.
+ * frame. Returns a null pointer if the scene can not be rendered.
  */
 #[no_mangle]
-pub extern "C" fn get_frame() -> Box<Frame> {
+pub extern "C" fn get_frame() -> Option<Box<Frame>> {
     let dims: [u32; 2] = [200, 133];
     let actors = scenes::random_book_cover();
-    let camera = Camera::new(
-        20.0,
-        dims[0],
-        dims[1],
-        arr1(&[13.0, 2.0, 3.0, 1.0]),
-        arr1(&[0.0, 0.0, 0.0, 1.0]),
-        arr1(&[0.0, 1.0, 0.0, 0.0]),
-        0.2,
-    );
-
-    let canvas = Canvas::new(200, 100, actors, 2, camera);
+    let camera = scenes::book_cover_camera(dims[0], dims[1]);
+
+    let canvas = Canvas::new(actors, camera);
+    let settings = RenderSettings {
+        width: dims[0],
+        height: dims[1],
+        samples: 2,
+        ..RenderSettings::default()
+    };
 
     //let now = Instant::now();
     //console::log_1(&"Before canvas::render_scene".into());
-    let image = canvas.render_scene();
+    let image = canvas.render_scene(&settings).ok()?;
     //console::log_1(&"After canvas::render_scene".into());
 
     // The Box smart pointer ensures the instance outlives the
     // underlying data pointer.
-    Box::new(image)
+    Some(Box::new(image))
 }
 
+/**
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
 #[no_mangle]
-pub extern "C" fn get_width(ptr: *mut Frame) -> u32 {
-    let frame = unsafe {
-        assert!(!ptr.is_null());
-        &mut *ptr
-    };
+pub unsafe extern "C" fn get_width(ptr: *mut Frame) -> u32 {
+    assert!(!ptr.is_null());
+    let frame = &mut *ptr;
     frame.width
 }
 
+/**
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
 #[no_mangle]
-pub extern "C" fn get_height(ptr: *mut Frame) -> u32 {
-    let frame = unsafe {
-        assert!(!ptr.is_null());
-        &mut *ptr
-    };
+pub unsafe extern "C" fn get_height(ptr: *mut Frame) -> u32 {
+    assert!(!ptr.is_null());
+    let frame = &mut *ptr;
     frame.height
 }
 
+/**
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
 #[no_mangle]
-pub extern "C" fn get_value(ptr: *mut Frame, x: u32, y: u32, c: u32) -> u8 {
-    let frame = unsafe {
-        assert!(!ptr.is_null());
-        &mut *ptr
-    };
+pub unsafe extern "C" fn get_value(
+    ptr: *mut Frame,
+    x: u32,
+    y: u32,
+    c: u32,
+) -> u8 {
+    assert!(!ptr.is_null());
+    let frame = &mut *ptr;
     frame.get_value(x, y, c)
 }
 
+/**
+ * # Safety
+ *
+ * The pointer must come from a live `Box<Frame>` handed out by this module.
+ */
 #[no_mangle]
-pub extern "C" fn get_data(ptr: *mut Frame) -> *const u8 {
-    let frame = unsafe {
-        assert!(!ptr.is_null());
-        &mut *ptr
+pub unsafe extern "C" fn get_data(ptr: *mut Frame) -> *const u8 {
+    assert!(!ptr.is_null());
+    let frame = &mut *ptr;
+
+    frame.data.as_ptr()
+}
+
+// Scene building API //////////////////////////////////////////////////////
+//
+// Lets C and C++ applications assemble their own scene of spheres, place
+// the camera and render into a buffer they own. The header is generated
+// with cbindgen, see `include/saturno.h`.
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaturnoStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidArgument = 2,
+    BufferTooSmall = 3,
+    RenderFailed = 4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SaturnoVec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub enum SaturnoMaterialKind {
+    Lambertian = 0,
+    Metal = 1,
+    Dielectric = 2,
+    DiffuseLight = 3,
+}
+
+/**
+ * `color` is the albedo (or the emitted color of lights), in [0, 1].
+ * `parameter` is the fuzz of metals, the refraction index of dielectrics
+ * and the intensity of lights; lambertians ignore it.
+ */
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SaturnoMaterial {
+    pub kind: SaturnoMaterialKind,
+    pub color: SaturnoVec3,
+    pub parameter: f64,
+}
+
+/**
+ * Camera placement, the resolution is given when rendering.
+ */
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct SaturnoCamera {
+    pub look_from: SaturnoVec3,
+    pub look_at: SaturnoVec3,
+    pub up: SaturnoVec3,
+    pub vertical_fov: f64,
+    pub aperture: f64,
+}
+
+/**
+ * Opaque scene handle of the C API.
+ */
+pub struct SaturnoScene {
+    canvas: Canvas,
+    camera: SaturnoCamera,
+}
+
+fn point(v: SaturnoVec3) -> Array1<Float> {
+    arr1(&[v.x as Float, v.y as Float, v.z as Float, 1.0])
+}
+
+fn direction(v: SaturnoVec3) -> Array1<Float> {
+    arr1(&[v.x as Float, v.y as Float, v.z as Float, 0.0])
+}
+
+fn color(v: SaturnoVec3) -> Array1<Float> {
+    point(v)
+}
+
+impl SaturnoMaterial {
+    fn build(&self) -> Arc<dyn Scattering> {
+        let parameter = self.parameter as Float;
+        match self.kind {
+            SaturnoMaterialKind::Lambertian => {
+                Arc::new(Lambertian::new(color(self.color), Shading::COLOR))
+            }
+            SaturnoMaterialKind::Metal => Arc::new(Metal::new(
+                color(self.color),
+                Shading::COLOR,
+                parameter,
+            )),
+            SaturnoMaterialKind::Dielectric => Arc::new(Dielectric::new(
+                color(self.color),
+                Shading::COLOR,
+                parameter,
+            )),
+            SaturnoMaterialKind::DiffuseLight => {
+                Arc::new(DiffuseLight::new(color(self.color), parameter))
+            }
+        }
+    }
+}
+
+impl SaturnoCamera {
+    fn build(&self, width: u32, height: u32) -> Camera {
+        Camera::new(
+            self.vertical_fov as Float,
+            width,
+            height,
+            point(self.look_from),
+            point(self.look_at),
+            direction(self.up),
+            self.aperture as Float,
+        )
+    }
+}
+
+/**
+ * Create an empty scene, with the camera at the origin looking down -z.
+ * Release it with `saturno_scene_free`.
+ */
+#[no_mangle]
+pub extern "C" fn saturno_scene_new() -> Box<SaturnoScene> {
+    let origin = SaturnoVec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    let camera = SaturnoCamera {
+        look_from: origin,
+        look_at: SaturnoVec3 { z: -1.0, ..origin },
+        up: SaturnoVec3 { y: 1.0, ..origin },
+        vertical_fov: 90.0,
+        aperture: 0.0,
     };
 
-    let data_ptr: *const u8 = frame.data.as_ptr();
-    data_ptr
+    Box::new(SaturnoScene {
+        canvas: Canvas::new(vec![], camera.build(1, 1)),
+        camera,
+    })
 }
 
+#[no_mangle]
+pub extern "C" fn saturno_scene_free(scene: Option<Box<SaturnoScene>>) {
+    drop(scene);
+}
 
+#[no_mangle]
+pub extern "C" fn saturno_scene_add_sphere(
+    scene: Option<&mut SaturnoScene>,
+    center: SaturnoVec3,
+    radius: f64,
+    material: SaturnoMaterial,
+) -> SaturnoStatus {
+    let scene = match scene {
+        Some(scene) => scene,
+        None => return SaturnoStatus::NullPointer,
+    };
+
+    let sphere = Sphere {
+        center: point(center),
+        radius: radius as Float,
+        material: material.build(),
+    };
+    if sphere.validate().is_err() {
+        return SaturnoStatus::InvalidArgument;
+    }
+
+    scene.canvas.world.actors.push(Box::new(sphere));
+    SaturnoStatus::Ok
+}
+
+#[no_mangle]
+pub extern "C" fn saturno_scene_set_camera(
+    scene: Option<&mut SaturnoScene>,
+    camera: SaturnoCamera,
+) -> SaturnoStatus {
+    match scene {
+        Some(scene) => {
+            scene.camera = camera;
+            SaturnoStatus::Ok
+        }
+        None => SaturnoStatus::NullPointer,
+    }
+}
+
+/**
+ * Render the scene into `buffer`: `width * height` RGBA pixels of 8 bits
+ * per channel, in scanlines from the top.
+ *
+ * # Safety
+ *
+ * `buffer` must point to `buffer_len` writable bytes.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn saturno_scene_render(
+    scene: Option<&mut SaturnoScene>,
+    width: u32,
+    height: u32,
+    samples: u32,
+    buffer: *mut u8,
+    buffer_len: usize,
+) -> SaturnoStatus {
+    let scene = match scene {
+        Some(scene) if !buffer.is_null() => scene,
+        _ => return SaturnoStatus::NullPointer,
+    };
+    if (buffer_len as u64) < width as u64 * height as u64 * 4 {
+        return SaturnoStatus::BufferTooSmall;
+    }
+
+    let settings = match RenderSettings::builder()
+        .size(width, height)
+        .samples(samples)
+        .build()
+    {
+        Ok(settings) => settings,
+        Err(_) => return SaturnoStatus::InvalidArgument,
+    };
+
+    scene.canvas.set_camera(scene.camera.build(width, height));
+    match scene.canvas.render_scene(&settings) {
+        Ok(image) => {
+            let buffer = std::slice::from_raw_parts_mut(buffer, buffer_len);
+            buffer[..image.data.len()].copy_from_slice(&image.data);
+            SaturnoStatus::Ok
+        }
+        Err(_) => SaturnoStatus::RenderFailed,
+    }
+}
@@ -1,9 +1,11 @@
 extern crate rendering;
 
 use rendering::raytracer::external;
-use rendering::raytracer::canvas::Canvas;
+use rendering::raytracer::progressive::Progressive;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
 use web_sys::console;
+use web_sys::{CanvasRenderingContext2d, ImageData};
 
 mod utils;
 
@@ -16,7 +18,6 @@ use utils::set_panic_hook;
 //#[global_allocator]
 //static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-
 #[wasm_bindgen]
 extern "C" {
     fn alert(s: &str);
@@ -28,36 +29,104 @@ pub fn greet() {
 }
 
 #[wasm_bindgen]
-pub fn render() -> ByteStream {
+pub fn render() -> Result<ByteStream, JsValue> {
     set_panic_hook();
     //greet();
 
     //console::log_1(&"Before get_frame".into());
-    let frame = external::get_frame();
+    let frame = external::get_frame()
+        .ok_or_else(|| JsValue::from_str("Failed to render the frame"))?;
     set_panic_hook();
     console::log_1(&"After external::get_frame".into());
-    ByteStream::new(&frame.data, frame.width, frame.height)
+    Ok(ByteStream::new(&frame.data, frame.width, frame.height))
 }
 
 #[wasm_bindgen]
 pub fn create_renderer(scene_id: u32) -> Renderer {
-    let canvas = external::get_renderer(scene_id);
-    Renderer {
-        canvas,
+    let renderer = external::get_renderer(scene_id);
+    Renderer { renderer }
+}
+
+/**
+ * Renderer drawing into an HTML canvas while it converges, one sample per
+ * pixel and `step`.
+ */
+#[wasm_bindgen]
+pub fn create_progressive_renderer(
+    scene_id: u32,
+    samples: u32,
+) -> ProgressiveRenderer {
+    set_panic_hook();
+    let renderer = external::get_renderer(scene_id);
+    let mut settings = renderer.settings.clone();
+    settings.samples = samples.max(1);
+
+    ProgressiveRenderer {
+        renderer,
+        progressive: Progressive::new(settings),
     }
 }
 
-//// Wasm wrappers ////////////////////////////////////////////////////////////
+// Wasm wrappers /////////////////////////////////////////////////////////////
 #[wasm_bindgen]
 pub struct Renderer {
-    canvas: Box<Canvas>
+    renderer: Box<external::Renderer>,
 }
 
 #[wasm_bindgen]
 impl Renderer {
-    pub fn render(&self) -> ByteStream {
-        let frame = self.canvas.render_scene();
-        ByteStream::new(&frame.data, frame.width, frame.height)
+    pub fn render(&self) -> Result<ByteStream, JsValue> {
+        let frame = self
+            .renderer
+            .render()
+            .map_err(|e| JsValue::from_str(&e.to_string()))?;
+        Ok(ByteStream::new(&frame.data, frame.width, frame.height))
+    }
+}
+
+#[wasm_bindgen]
+pub struct ProgressiveRenderer {
+    renderer: Box<external::Renderer>,
+    progressive: Progressive,
+}
+
+#[wasm_bindgen]
+impl ProgressiveRenderer {
+    pub fn width(&self) -> u32 {
+        self.progressive.settings.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.progressive.settings.height
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.progressive.passes()
+    }
+
+    /**
+     * Render the next pass, returns false once all of them are done.
+     */
+    pub fn step(&mut self) -> Result<bool, JsValue> {
+        self.progressive
+            .step(&self.renderer.canvas)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /**
+     * Draw the current estimate at the top left corner of the canvas.
+     */
+    pub fn draw(
+        &self,
+        context: &CanvasRenderingContext2d,
+    ) -> Result<(), JsValue> {
+        let image = self.progressive.image();
+        let data = ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&image.data),
+            image.width,
+            image.height,
+        )?;
+        context.put_image_data(&data, 0.0, 0.0)
     }
 }
 
@@ -73,9 +142,9 @@ pub struct ByteStream {
 #[wasm_bindgen]
 impl ByteStream {
     pub fn new(bytes: &[u8], width: u32, height: u32) -> ByteStream {
-        let byte_size = width as usize * height as usize * 4 as usize;
+        let byte_size = width as usize * height as usize * 4;
         ByteStream {
-            byte_size: byte_size,
+            byte_size,
             data: bytes.as_ptr(),
             size: bytes.len(),
             width,
@@ -91,6 +160,10 @@ impl ByteStream {
         self.size
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
     pub fn byte_size(&self) -> usize {
         self.byte_size
     }
@@ -0,0 +1,742 @@
+// Look-dev tool: a preview window of a scene description (the JSON format
+// of saturno-server) with controls for the camera, the image, the
+// sampling and the materials. The preview renders progressively and starts
+// over whenever the scene changes. Drag the preview to orbit, right drag to
+// pan and scroll to dolly.
+//
+//     saturno-gui --scene scene.json
+
+use clap::Parser;
+use eframe::egui;
+use futures::{FutureExt, Stream, StreamExt};
+use saturno::io::mesh;
+use saturno::io::output;
+use saturno::io::scene::{read_files, scene_canvas};
+use saturno::math::common::Float;
+use saturno::prelude::*;
+use saturno::render::canvas::develop;
+use saturno::render::stream::{render_stream, RenderPass};
+use saturno::scene::description::{
+    CameraDescription, MaterialDescription, SceneDescription, SphereDescription,
+};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Parser)]
+#[command(
+    name = "saturno-gui",
+    about = "Preview and tweak a scene with the Saturno ray tracer"
+)]
+struct Args {
+    /// Scene description as JSON, a demo scene when missing.
+    #[arg(long)]
+    scene: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 480)]
+    width: u32,
+
+    #[arg(long, default_value_t = 270)]
+    height: u32,
+
+    /// Where "Save" writes the current image.
+    #[arg(short, long, default_value = "render.png")]
+    output: PathBuf,
+}
+
+type PassStream =
+    Box<dyn Stream<Item = Result<RenderPass, SaturnoError>> + Unpin>;
+
+struct App {
+    scene: SceneDescription,
+    /**
+     * The folder of the scene file, its meshes, instances and Alembic
+     * caches are read from on every restart (with the materials as
+     * edited).
+     */
+    base: PathBuf,
+    settings: RenderSettings,
+    output: PathBuf,
+    stream: Option<PassStream>,
+    /**
+     * Whether the current stream delivered a pass yet. A new render only
+     * starts then, so dragging a slider does not pile up renders.
+     */
+    received: bool,
+    dirty: bool,
+    /**
+     * The camera is being moved with the mouse, render one sample per
+     * pixel until it stops.
+     */
+    interacting: bool,
+    latest: Option<RenderPass>,
+    texture: Option<egui::TextureHandle>,
+    status: Option<String>,
+}
+
+fn demo_scene() -> SceneDescription {
+    let sphere = |center: [Float; 3], radius, material| SphereDescription {
+        center,
+        radius,
+        material,
+        visibility: Visibility::default(),
+    };
+
+    SceneDescription {
+        units: Units::Meters,
+        camera: CameraDescription {
+            look_from: [0.0, 0.5, 1.5],
+            look_at: [0.0, 0.0, -1.0],
+            up: [0.0, 1.0, 0.0],
+            vertical_fov: 60.0,
+            aperture: 0.0,
+            shutter: [0.0, 0.0],
+        },
+        cameras: BTreeMap::new(),
+        materials: BTreeMap::new(),
+        lights: vec![],
+        fog: None,
+        atmosphere: None,
+        black_hole: None,
+        accretion_disks: vec![],
+        fractals: vec![],
+        metaballs: vec![],
+        prototypes: BTreeMap::new(),
+        instances: vec![],
+        scatters: vec![],
+        clipping: None,
+        volumes: vec![],
+        rings: vec![],
+        meshes: vec![],
+        alembic: vec![],
+        frame: 0.0,
+        fps: 24.0,
+        shutter: 0.0,
+        spheres: vec![
+            sphere(
+                [0.0, -100.5, -1.0],
+                100.0,
+                MaterialDescription::Lambertian {
+                    albedo: [0.8, 0.8, 0.0],
+                },
+            ),
+            sphere(
+                [0.0, 0.0, -1.0],
+                0.5,
+                MaterialDescription::Lambertian {
+                    albedo: [0.1, 0.2, 0.5],
+                },
+            ),
+            sphere(
+                [1.0, 0.0, -1.0],
+                0.5,
+                MaterialDescription::Metal {
+                    color: [0.8, 0.6, 0.2],
+                    fuzz: 0.2,
+                },
+            ),
+            sphere(
+                [-1.0, 0.0, -1.0],
+                0.5,
+                MaterialDescription::Dielectric {
+                    refraction_idx: 1.5,
+                    dispersion: None,
+                },
+            ),
+        ],
+    }
+}
+
+fn load_scene(
+    args: &Args,
+) -> Result<(SceneDescription, PathBuf), SaturnoError> {
+    match &args.scene {
+        Some(path) => {
+            let json = fs::read_to_string(path)
+                .map_err(|e| SaturnoError::io(path, e))?;
+            let mut scene: SceneDescription = serde_json::from_str(&json)
+                .map_err(|e| {
+                    SaturnoError::InvalidSettings(format!(
+                        "{}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+            let base = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+            mesh::read_textures(&mut scene, &base)?;
+            Ok((scene, base))
+        }
+        None => Ok((demo_scene(), PathBuf::from("."))),
+    }
+}
+
+impl App {
+    fn new(
+        scene: SceneDescription,
+        base: PathBuf,
+        settings: RenderSettings,
+        output: PathBuf,
+    ) -> App {
+        App {
+            scene,
+            base,
+            settings,
+            output,
+            stream: None,
+            received: false,
+            dirty: true,
+            interacting: false,
+            latest: None,
+            texture: None,
+            status: None,
+        }
+    }
+
+    fn restart(&mut self) {
+        self.dirty = false;
+        self.received = false;
+        let (width, height) = (self.settings.width, self.settings.height);
+        let canvas = match read_files(&self.scene, &self.base, None)
+            .map_err(|e| e.to_string())
+            .and_then(|actors| scene_canvas(&self.scene, width, height, actors))
+        {
+            Ok(canvas) => canvas,
+            Err(e) => {
+                self.stream = None;
+                self.status = Some(e);
+                return;
+            }
+        };
+
+        if let Err(e) = canvas.validate(&self.settings) {
+            self.stream = None;
+            self.status = Some(e.to_string());
+            return;
+        }
+        self.status = None;
+        let mut settings = self.settings.clone();
+        if self.interacting {
+            settings.samples = 1;
+        }
+        let renderer = Renderer::new(canvas, settings);
+        self.stream = Some(Box::new(render_stream(renderer)));
+    }
+
+    /**
+     * Take the passes finished since the last frame, without blocking.
+     */
+    fn poll(&mut self, ctx: &egui::Context) {
+        let stream = match &mut self.stream {
+            Some(stream) => stream,
+            None => return,
+        };
+
+        let mut updated = false;
+        loop {
+            match stream.next().now_or_never() {
+                Some(Some(Ok(pass))) => {
+                    self.latest = Some(pass);
+                    self.received = true;
+                    updated = true;
+                }
+                Some(Some(Err(e))) => {
+                    self.status = Some(e.to_string());
+                    self.received = true;
+                }
+                Some(None) => {
+                    self.stream = None;
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        if updated {
+            self.develop(ctx);
+        }
+    }
+
+    /**
+     * Show the latest pass with the current exposure and tone mapping.
+     */
+    fn develop(&mut self, ctx: &egui::Context) {
+        let pass = match &self.latest {
+            Some(pass) => pass,
+            None => return,
+        };
+        let image = develop(&pass.hdr, &self.settings);
+        let size = [image.width as usize, image.height as usize];
+        let pixels =
+            egui::ColorImage::from_rgba_unmultiplied(size, &image.data);
+
+        match &mut self.texture {
+            Some(texture) => texture.set(pixels, egui::TextureOptions::NEAREST),
+            None => {
+                self.texture = Some(ctx.load_texture(
+                    "render",
+                    pixels,
+                    egui::TextureOptions::NEAREST,
+                ))
+            }
+        }
+    }
+
+    /**
+     * Mouse navigation in the preview: drag to orbit around the point the
+     * camera looks at, right (or middle) drag to pan and scroll to dolly.
+     */
+    fn navigate(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        let camera = &mut self.scene.camera;
+        let before = camera.clone();
+
+        let delta = response.drag_delta();
+        let (dx, dy) = (delta.x as Float, delta.y as Float);
+        if response.dragged_by(egui::PointerButton::Primary) {
+            camera.orbit(-dx * 0.01, dy * 0.01);
+        } else if response.dragged_by(egui::PointerButton::Secondary)
+            || response.dragged_by(egui::PointerButton::Middle)
+        {
+            // World units per point at the distance of the focus.
+            let height = response.rect.height().max(1.0) as Float;
+            let half_fov = camera.vertical_fov.to_radians() / 2.0;
+            let scale = 2.0 * camera.distance() * half_fov.tan() / height;
+            camera.pan(-dx * scale, dy * scale);
+        }
+        if response.hovered() {
+            let scroll = ui.input(|input| input.smooth_scroll_delta.y);
+            if scroll != 0.0 {
+                camera.dolly((-scroll as Float * 0.002).exp());
+            }
+        }
+
+        let moved = *camera != before;
+        let interacting = moved || response.dragged();
+        // Start over on every move, and once more with every sample when
+        // the interaction ends.
+        if moved || (self.interacting && !interacting) {
+            self.dirty = true;
+        }
+        self.interacting = interacting;
+    }
+
+    fn save(&mut self) {
+        if let Some(pass) = &self.latest {
+            let image = develop(&pass.hdr, &self.settings);
+            self.status = Some(match output::write_png(&self.output, &image) {
+                Ok(()) => format!("saved {}", self.output.display()),
+                Err(e) => e.to_string(),
+            });
+        }
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let (done, passes) = match &self.latest {
+            Some(pass) => (pass.pass + 1, pass.passes),
+            None => (0, self.settings.samples),
+        };
+        ui.add(
+            egui::ProgressBar::new(done as f32 / passes as f32)
+                .text(format!("{} / {} spp", done, passes)),
+        );
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                self.save();
+            }
+            ui.label(self.output.display().to_string());
+        });
+        if let Some(status) = &self.status {
+            ui.label(status.as_str());
+        }
+
+        ui.collapsing("Render", |ui| {
+            let settings = &mut self.settings;
+            self.dirty |= ui
+                .add(
+                    egui::Slider::new(&mut settings.samples, 1..=4096)
+                        .logarithmic(true)
+                        .text("spp"),
+                )
+                .changed();
+            self.dirty |= ui
+                .add(
+                    egui::Slider::new(&mut settings.max_depth, 1..=100)
+                        .text("max depth"),
+                )
+                .changed();
+        });
+
+        ui.collapsing("Image", |ui| {
+            let settings = &mut self.settings;
+            let before =
+                (settings.exposure, settings.tonemapper, settings.gamma);
+            ui.add(
+                egui::Slider::new(&mut settings.exposure, -5.0..=5.0)
+                    .text("exposure"),
+            );
+            egui::ComboBox::from_label("tonemapper")
+                .selected_text(format!("{:?}", settings.tonemapper))
+                .show_ui(ui, |ui| {
+                    for tonemapper in [
+                        Tonemapper::Clamp,
+                        Tonemapper::Reinhard,
+                        Tonemapper::Aces,
+                    ] {
+                        let name = format!("{:?}", tonemapper);
+                        ui.selectable_value(
+                            &mut settings.tonemapper,
+                            tonemapper,
+                            name,
+                        );
+                    }
+                });
+            ui.add(
+                egui::Slider::new(&mut settings.gamma, 1.0..=3.0).text("gamma"),
+            );
+
+            // Only the development changed, the passes are still valid.
+            if before
+                != (settings.exposure, settings.tonemapper, settings.gamma)
+            {
+                self.develop(ctx);
+            }
+        });
+
+        ui.collapsing("Camera", |ui| {
+            let camera = &mut self.scene.camera;
+            self.dirty |= edit_vector(ui, "from", &mut camera.look_from);
+            self.dirty |= edit_vector(ui, "at", &mut camera.look_at);
+            self.dirty |= ui
+                .add(
+                    egui::Slider::new(&mut camera.vertical_fov, 5.0..=150.0)
+                        .text("fov"),
+                )
+                .changed();
+            self.dirty |= ui
+                .add(
+                    egui::Slider::new(&mut camera.aperture, 0.0..=1.0)
+                        .text("aperture"),
+                )
+                .changed();
+        });
+
+        let (scene, dirty) = (&mut self.scene, &mut self.dirty);
+        ui.collapsing("Materials", |ui| {
+            // Edits of a named material apply to every sphere using it.
+            for (name, material) in scene.materials.iter_mut() {
+                ui.push_id(name.as_str(), |ui| {
+                    ui.label(name.as_str());
+                    *dirty |= edit_material(ui, material);
+                    ui.separator();
+                });
+            }
+            for (i, sphere) in scene.spheres.iter_mut().enumerate() {
+                ui.push_id(i, |ui| {
+                    ui.label(format!("sphere {}", i));
+                    *dirty |= edit_material(ui, &mut sphere.material);
+                    ui.horizontal(|ui| {
+                        let visibility = &mut sphere.visibility;
+                        for (seen, rays) in [
+                            (&mut visibility.camera, "camera"),
+                            (&mut visibility.shadow, "shadow"),
+                            (&mut visibility.indirect, "indirect"),
+                            (&mut visibility.holdout, "holdout"),
+                        ] {
+                            *dirty |= ui.checkbox(seen, rays).changed();
+                        }
+                    });
+                    ui.separator();
+                });
+            }
+            for (i, ring) in scene.rings.iter_mut().enumerate() {
+                ui.push_id(("ring", i), |ui| {
+                    ui.label(format!("ring {}", i));
+                    *dirty |= edit_material(ui, &mut ring.material);
+                    ui.separator();
+                });
+            }
+        });
+    }
+}
+
+fn edit_vector(
+    ui: &mut egui::Ui,
+    label: &str,
+    vector: &mut [Float; 3],
+) -> bool {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        let mut changed = false;
+        for value in vector.iter_mut() {
+            changed |=
+                ui.add(egui::DragValue::new(value).speed(0.05)).changed();
+        }
+        changed
+    })
+    .inner
+}
+
+fn edit_color(ui: &mut egui::Ui, color: &mut [Float; 3]) -> bool {
+    // Float is f32 with the `f32` feature.
+    #[allow(clippy::unnecessary_cast)]
+    let mut rgb = color.map(|c| c as f32);
+    let changed = ui.color_edit_button_rgb(&mut rgb).changed();
+    if changed {
+        *color = rgb.map(|c| c as Float);
+    }
+    changed
+}
+
+fn edit_mask(ui: &mut egui::Ui, mask: &mut Mask, text: &str) -> bool {
+    let slider = |ui: &mut egui::Ui, value, range, text| {
+        ui.add(egui::Slider::new(value, range).text(text)).changed()
+    };
+
+    match mask {
+        Mask::Uniform { value } => slider(ui, value, 0.0..=1.0, text),
+        Mask::Checker { size, values } => {
+            let [even, odd] = values;
+            slider(ui, size, 0.01..=2.0, "checker size")
+                | slider(ui, even, 0.0..=1.0, text)
+                | slider(ui, odd, 0.0..=1.0, text)
+        }
+        Mask::Texture { path, .. } => {
+            ui.label(format!("{} {}", text, path));
+            false
+        }
+    }
+}
+
+fn edit_material(
+    ui: &mut egui::Ui,
+    material: &mut MaterialDescription,
+) -> bool {
+    let slider = |ui: &mut egui::Ui, value, range, text| {
+        ui.add(egui::Slider::new(value, range).text(text)).changed()
+    };
+
+    match material {
+        MaterialDescription::Lambertian { albedo } => {
+            ui.horizontal(|ui| {
+                ui.label("lambertian");
+                edit_color(ui, albedo)
+            })
+            .inner
+        }
+        MaterialDescription::Metal { color, fuzz } => {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("metal");
+                    edit_color(ui, color)
+                })
+                .inner;
+            changed | slider(ui, fuzz, 0.0..=1.0, "roughness")
+        }
+        MaterialDescription::AnisotropicMetal {
+            color,
+            alpha_x,
+            alpha_y,
+            rotation,
+        } => {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("anisotropic metal");
+                    edit_color(ui, color)
+                })
+                .inner;
+            changed
+                | slider(ui, alpha_x, 0.0..=1.0, "roughness along")
+                | slider(ui, alpha_y, 0.0..=1.0, "roughness across")
+                | edit_mask(ui, rotation, "rotation")
+        }
+        MaterialDescription::Dielectric { refraction_idx, .. } => {
+            ui.label("dielectric");
+            slider(ui, refraction_idx, 1.0..=2.5, "refraction index")
+        }
+        MaterialDescription::DiffuseLight {
+            color, intensity, ..
+        } => {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("light");
+                    edit_color(ui, color)
+                })
+                .inner;
+            changed | slider(ui, intensity, 0.0..=50.0, "intensity")
+        }
+        MaterialDescription::ShadowCatcher { strength } => {
+            ui.label("shadow catcher");
+            slider(ui, strength, 0.0..=1.0, "strength")
+        }
+        MaterialDescription::Subsurface {
+            albedo,
+            mean_free_path,
+            ..
+        } => {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("subsurface");
+                    edit_color(ui, albedo)
+                })
+                .inner;
+            changed
+                | ui.add(
+                    egui::Slider::new(mean_free_path, 0.001..=1.0)
+                        .logarithmic(true)
+                        .text("mean free path"),
+                )
+                .changed()
+        }
+        MaterialDescription::RingParticles {
+            albedo,
+            forward,
+            backward,
+            forward_weight,
+        } => {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("ring particles");
+                    edit_color(ui, albedo)
+                })
+                .inner;
+            changed
+                | slider(ui, forward, 0.0..=0.95, "forward lobe")
+                | slider(ui, backward, -0.95..=0.0, "backward lobe")
+                | slider(ui, forward_weight, 0.0..=1.0, "forward share")
+        }
+        MaterialDescription::Clearcoat {
+            base,
+            refraction_idx,
+            roughness,
+            tint,
+        } => {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("clear coat");
+                    edit_color(ui, tint)
+                })
+                .inner;
+            changed
+                | slider(ui, refraction_idx, 1.0..=2.5, "coat index")
+                | slider(ui, roughness, 0.0..=1.0, "coat roughness")
+                | edit_material(ui, base)
+        }
+        MaterialDescription::ThinFilm {
+            base,
+            thickness,
+            refraction_idx,
+            ..
+        } => {
+            ui.label("thin film");
+            let changed = slider(ui, thickness, 0.0..=1000.0, "thickness (nm)")
+                | slider(ui, refraction_idx, 1.0..=2.5, "film index");
+            changed | edit_material(ui, base)
+        }
+        MaterialDescription::AccretionGlow {
+            color, luminance, ..
+        } => {
+            let changed = ui
+                .horizontal(|ui| {
+                    ui.label("accretion glow");
+                    edit_color(ui, color)
+                })
+                .inner;
+            changed
+                | ui.add(
+                    egui::Slider::new(luminance, 0.0..=1e6)
+                        .logarithmic(true)
+                        .text("luminance"),
+                )
+                .changed()
+        }
+        MaterialDescription::Photosphere {
+            temperature,
+            luminance,
+            granulation,
+            ..
+        } => {
+            ui.label("photosphere");
+            slider(ui, temperature, 2000.0..=40000.0, "temperature (K)")
+                | ui.add(
+                    egui::Slider::new(luminance, 0.0..=1e9)
+                        .logarithmic(true)
+                        .text("luminance"),
+                )
+                .changed()
+                | slider(ui, granulation, 0.0..=1.0, "granulation")
+        }
+        MaterialDescription::Cutout { base, mask } => {
+            ui.label("cutout");
+            edit_mask(ui, mask, "opacity") | edit_material(ui, base)
+        }
+        MaterialDescription::Mix {
+            first,
+            second,
+            factor,
+        } => {
+            ui.label("mix");
+            edit_mask(ui, factor, "mix")
+                | edit_material(ui, first)
+                | edit_material(ui, second)
+        }
+        MaterialDescription::Named { name } => {
+            ui.label(format!("material {}", name));
+            false
+        }
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        if self.dirty && (self.stream.is_none() || self.received) {
+            self.restart();
+        }
+        self.poll(ctx);
+
+        egui::SidePanel::left("controls").show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| self.controls(ui, ctx));
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(texture) = &self.texture {
+                let image =
+                    egui::Image::new((texture.id(), texture.size_vec2()))
+                        .sense(egui::Sense::click_and_drag());
+                let response = ui.add(image);
+                self.navigate(ui, &response);
+            }
+        });
+
+        // Keep polling while passes are coming.
+        if self.stream.is_some() || self.dirty {
+            ctx.request_repaint();
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let (scene, base) = match load_scene(&args) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("saturno-gui: {}", e);
+            process::exit(1);
+        }
+    };
+    let settings = RenderSettings {
+        width: args.width,
+        height: args.height,
+        samples: 64,
+        ..RenderSettings::default()
+    };
+    let app = App::new(scene, base, settings, args.output);
+
+    let result = eframe::run_native(
+        "Saturno",
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::new(app))),
+    );
+    if let Err(e) = result {
+        eprintln!("saturno-gui: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,915 @@
+// Command line front end: renders one of the built-in scenes (or a scene
+// file) to an image file, with settings read from a JSON file and/or the
+// arguments, or a queue of such renders (see `queue`).
+
+mod queue;
+
+use clap::{Parser, Subcommand};
+use saturno::io::mesh;
+use saturno::io::mesh_cache::MeshCache;
+use saturno::io::output;
+use saturno::io::paths;
+use saturno::io::scene::{read_files, scene_canvas};
+use saturno::io::usd;
+use saturno::math::common::Float;
+use saturno::prelude::*;
+use saturno::render::analysis::false_color;
+use saturno::render::analysis::waveform;
+use saturno::render::analysis::Histogram;
+use saturno::render::aov::CostKind;
+use saturno::render::canvas::develop;
+use saturno::render::contact_sheet::contact_sheet;
+use saturno::render::estimate::estimate;
+use saturno::render::estimate::Probe;
+use saturno::render::metadata::RenderMetadata;
+use saturno::render::overlay;
+use saturno::scene::bvh;
+use saturno::scene::description::SceneDescription;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+use std::time::Instant;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Clone, Parser)]
+#[command(
+    name = "saturno",
+    about = "Render a scene with the Saturno ray tracer"
+)]
+struct Args {
+    /// Built-in scene (book_cover, two_spheres, mandelbulb or
+    /// menger_sponge), a scene file (.json) or a USD stage (.usda, .usdz).
+    #[arg(long, default_value = "book_cover")]
+    scene: String,
+
+    /// Render settings as JSON. Arguments below override its values.
+    #[arg(long)]
+    settings: Option<PathBuf>,
+
+    #[arg(long)]
+    width: Option<u32>,
+
+    #[arg(long)]
+    height: Option<u32>,
+
+    #[arg(long)]
+    samples: Option<u32>,
+
+    /// Render passes until this much time is spent instead of a number of
+    /// samples, e.g. 90 (seconds), 10m or 1h30m, then finish the image.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    time_budget: Option<f64>,
+
+    /// Worker threads, 0 uses every core but --reserved-cores.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Cores left free when the number of threads is automatic.
+    #[arg(long)]
+    reserved_cores: Option<usize>,
+
+    /// Render at the lowest priority, to keep working while it renders.
+    #[arg(long)]
+    low_priority: bool,
+
+    /// Width and height in pixels of the tiles the threads render.
+    #[arg(long)]
+    tile_size: Option<u32>,
+
+    /// Order the tiles are rendered in: scanline, spiral (center out),
+    /// hilbert (for cache coherence) or cost (the most expensive first,
+    /// split to keep the threads busy until the end).
+    #[arg(long, value_parser = ["scanline", "spiral", "hilbert", "cost"])]
+    tile_order: Option<String>,
+
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Trace paths at sampled wavelengths instead of RGB.
+    #[arg(long)]
+    spectral: bool,
+
+    /// Connect the lights through glass, for converged caustics. The
+    /// settings file tunes it (the caustics field).
+    #[arg(long)]
+    caustics: bool,
+
+    /// Interpolate the diffuse indirect light from an irradiance cache.
+    /// The settings file tunes it (the irradiance_cache field).
+    #[arg(long)]
+    irradiance_cache: bool,
+
+    /// Render with stochastic progressive photon mapping instead of path
+    /// tracing, a pass per sample. The settings file tunes it (the
+    /// photon_mapping field).
+    #[arg(long)]
+    photon_mapping: bool,
+
+    /// Explore the light paths with Metropolis light transport, for light
+    /// hard to find at random. The settings file tunes it (the metropolis
+    /// field).
+    #[arg(long)]
+    metropolis: bool,
+
+    /// Rebuild the image from the pixels and their gradients, for less
+    /// noise at the same samples. The settings file tunes it (the
+    /// gradient_domain field).
+    #[arg(long)]
+    gradient_domain: bool,
+
+    /// Learn the light first, and guide the diffuse bounces where it comes
+    /// from. The settings file tunes it (the guiding field).
+    #[arg(long)]
+    guiding: bool,
+
+    /// Resample the lights with reservoirs instead of sampling them all,
+    /// for scenes with many lights. The settings file tunes it (the
+    /// reservoirs field).
+    #[arg(long)]
+    reservoirs: bool,
+
+    /// Pick the lights from a hierarchy instead of sampling them all, for
+    /// scenes with many lights. The settings file tunes it (the
+    /// light_hierarchy field).
+    #[arg(long)]
+    light_hierarchy: bool,
+
+    /// Expose as a physical camera at this exposure value (at ISO 100),
+    /// for scenes lit in photometric units. The settings file can give
+    /// the f-number, shutter and ISO instead (the camera_exposure field).
+    #[arg(long)]
+    ev: Option<Float>,
+
+    /// Bloom around the bright parts of the image. The settings file
+    /// tunes it, and adds glare (the bloom field).
+    #[arg(long)]
+    bloom: bool,
+
+    /// Lens flare from the bright parts of the image: ghosts, a halo and
+    /// a starburst. The settings file tunes it (the lens_flare field).
+    #[arg(long)]
+    lens_flare: bool,
+
+    /// Vignetting, chromatic aberration and film grain, as of a real
+    /// camera. The settings file tunes them (the imperfections field).
+    #[arg(long)]
+    imperfections: bool,
+
+    /// White balance for lights of this color temperature (kelvins), so
+    /// they look white. The settings file adds a tint (the white_balance
+    /// field).
+    #[arg(long)]
+    white_balance: Option<Float>,
+
+    /// Develop through the ACES rendering and sRGB output transforms
+    /// instead of the tone mapper.
+    #[arg(long)]
+    aces: bool,
+
+    /// Develop through a 3D LUT (.cube), e.g. a display transform baked
+    /// from an OpenColorIO config with ociobakelut. The settings file
+    /// picks the working space of the render (the working_space field).
+    #[arg(long, conflicts_with = "aces")]
+    lut: Option<PathBuf>,
+
+    /// Move the world so the camera sits at the origin before rendering,
+    /// for scenes far from the origin (e.g. of planetary scale) which
+    /// otherwise lose the precision of their positions.
+    #[arg(long)]
+    camera_relative: bool,
+
+    /// Toon shading: flat bands of light and inked outlines instead of
+    /// path tracing. The settings file tunes it (the toon field).
+    #[arg(long)]
+    toon: bool,
+
+    /// Number of lit bands of the toon shading. Implies --toon.
+    #[arg(long)]
+    toon_bands: Option<u32>,
+
+    /// Folder caching the meshes of scene files once built, by the
+    /// contents of their files, so the next renders skip building them.
+    #[arg(long, value_name = "DIR")]
+    mesh_cache: Option<PathBuf>,
+
+    /// Output image; .hdr writes linear radiance, anything else a PNG.
+    #[arg(short, long, default_value = "render.png")]
+    output: PathBuf,
+
+    /// Frame of an animated scene to render, instead of its frame field
+    /// (the time its Alembic caches are read at), or the time code of a
+    /// USD stage.
+    #[arg(long)]
+    frame: Option<Float>,
+
+    /// Render the frames FIRST to LAST of an animated scene, each to the
+    /// output path with its number appended (e.g. out_0012.png).
+    #[arg(long, value_name = "FIRST-LAST", value_parser = parse_frames, conflicts_with = "frame")]
+    frames: Option<(u32, u32)>,
+
+    /// Instead of the image, render a heatmap of the traversal cost per
+    /// pixel: rays traced, hierarchy nodes visited or intersection tests
+    /// (of actors and of the triangles of meshes).
+    #[arg(long, value_parser = ["rays", "nodes", "tests"])]
+    heatmap: Option<String>,
+
+    /// Instead of the image, write an analysis of its exposure before the
+    /// tone mapping: a histogram or a waveform of the stops around middle
+    /// gray, or a false color image of the exposure zones. Logs the
+    /// clipped fraction.
+    #[arg(long, value_parser = ["histogram", "waveform", "false_color"])]
+    analysis: Option<String>,
+
+    /// Draw the bounding box of every actor over the render (overlay) or
+    /// on an empty image (only), to check where the geometry is.
+    #[arg(long, value_parser = ["overlay", "only"])]
+    bounds: Option<String>,
+
+    /// Highlight in green what is in focus, over the render (overlay) or
+    /// over a quick preview free of depth of field (only), to set the
+    /// focus before a long render.
+    #[arg(long, value_parser = ["overlay", "only"])]
+    focus_peaking: Option<String>,
+
+    /// Render the scene through every camera it names, each to the output
+    /// path with the name of the camera appended (e.g. out_top.png).
+    #[arg(long)]
+    all_cameras: bool,
+
+    /// Also composite the renders of --all-cameras into a contact sheet.
+    /// Implies --all-cameras.
+    #[arg(long)]
+    contact_sheet: Option<PathBuf>,
+
+    /// Columns of the --contact-sheet, 0 for a grid about square.
+    #[arg(long, default_value_t = 0)]
+    sheet_columns: usize,
+
+    /// Discard samples with NaN or infinite radiance instead of failing,
+    /// and warn about the actors and materials that produced them.
+    #[arg(long)]
+    quarantine: bool,
+
+    /// Also write a PNG of the pixels that lost samples to --quarantine,
+    /// in magenta. Implies --quarantine.
+    #[arg(long)]
+    quarantine_mask: Option<PathBuf>,
+
+    /// Instead of rendering, trace every sample of the pixel X,Y (or of the
+    /// rectangle X,Y,WIDTH,HEIGHT) and dump each bounce as JSON.
+    #[arg(long, value_name = "X,Y[,W,H]", value_parser = parse_rect)]
+    debug_pixel: Option<Crop>,
+
+    /// Where the --debug-pixel dump goes, standard output by default.
+    #[arg(long)]
+    debug_output: Option<PathBuf>,
+
+    /// Also export the --debug-pixel paths as lines, .ply or .obj.
+    #[arg(long, requires = "debug_pixel")]
+    debug_paths: Option<PathBuf>,
+
+    /// Length of the segments of escaped rays in --debug-paths.
+    #[arg(long, default_value_t = 1.0)]
+    escape_length: Float,
+
+    /// Log the time taken by every stage (RUST_LOG overrides the level).
+    #[arg(short, long)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Clone, Subcommand)]
+enum Command {
+    /// Print the statistics of the scene instead of rendering it: the
+    /// actors by kind, the triangles, the textures and their memory, the
+    /// bounds of the world and the lights.
+    Inspect,
+    /// Predict how long the render takes instead of rendering it, timing
+    /// a probe render at a fraction of the resolution and a few samples.
+    /// Under --time-budget, predicts the samples it affords instead.
+    Estimate {
+        /// Width and height of the probe, as a fraction of the render.
+        #[arg(long, default_value_t = 0.25)]
+        probe_scale: Float,
+        /// Samples per pixel of the probe (at most those of the render).
+        #[arg(long, default_value_t = 4)]
+        probe_samples: u32,
+    },
+    /// Render a queue of jobs instead of the scene: the .job.json files
+    /// of a directory, or a manifest file listing them. A job holds the
+    /// options of a render, e.g. {"scene": "a.json", "output": "a.png"}.
+    /// Every job logs to a file, and status.json records how they ended.
+    Queue {
+        /// Directory of job files, or manifest of jobs.
+        jobs: PathBuf,
+        /// Jobs rendering at the same time, sharing the cores.
+        #[arg(long, default_value_t = 1)]
+        workers: usize,
+        /// Folder of the logs and status.json, logs next to the jobs by
+        /// default.
+        #[arg(long)]
+        logs: Option<PathBuf>,
+    },
+}
+
+fn parse_frames(value: &str) -> Result<(u32, u32), String> {
+    let (first, last) = value
+        .split_once('-')
+        .ok_or_else(|| "expected FIRST-LAST".to_string())?;
+    let number = |n: &str| n.trim().parse::<u32>().map_err(|e| e.to_string());
+    let (first, last) = (number(first)?, number(last)?);
+    if last < first {
+        return Err(format!("frame {} before {}", last, first));
+    }
+    Ok((first, last))
+}
+
+/**
+ * Seconds of a duration in hours, minutes and seconds (1h30m, 10m, 90s),
+ * plain numbers in seconds.
+ */
+fn parse_duration(value: &str) -> Result<f64, String> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Ok(seconds);
+    }
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in value.trim().chars() {
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => {
+                number.push(c);
+                continue;
+            }
+        };
+        let amount: f64 = number
+            .parse()
+            .map_err(|_| format!("invalid duration {}", value))?;
+        seconds += amount * unit;
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(format!("missing unit after {} in {}", number, value));
+    }
+    Ok(seconds)
+}
+
+/**
+ * `seconds` in hours, minutes and seconds, milliseconds under a second.
+ */
+fn format_duration(seconds: f64) -> String {
+    if seconds < 1.0 {
+        return format!("{:.1}ms", seconds * 1000.0);
+    }
+    if seconds < 60.0 {
+        return format!("{:.1}s", seconds);
+    }
+    let seconds = seconds.round() as u64;
+    let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+    match hours {
+        0 => format!("{}m{:02}s", minutes, seconds % 60),
+        _ => format!("{}h{:02}m{:02}s", hours, minutes, seconds % 60),
+    }
+}
+
+fn parse_rect(value: &str) -> Result<Crop, String> {
+    let numbers = value
+        .split(',')
+        .map(|n| n.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    match numbers[..] {
+        [x, y] => Ok(Crop {
+            x,
+            y,
+            width: 1,
+            height: 1,
+        }),
+        [x, y, width, height] => Ok(Crop {
+            x,
+            y,
+            width,
+            height,
+        }),
+        _ => Err("expected X,Y or X,Y,WIDTH,HEIGHT".to_string()),
+    }
+}
+
+fn load_settings(args: &Args) -> Result<RenderSettings, SaturnoError> {
+    let mut settings = match &args.settings {
+        Some(path) => {
+            let json = fs::read_to_string(path)
+                .map_err(|e| SaturnoError::io(path, e))?;
+            serde_json::from_str(&json).map_err(|e| {
+                SaturnoError::InvalidSettings(format!(
+                    "{}: {}",
+                    path.display(),
+                    e
+                ))
+            })?
+        }
+        None => RenderSettings::default(),
+    };
+
+    if let Some(width) = args.width {
+        settings.width = width;
+    }
+    if let Some(height) = args.height {
+        settings.height = height;
+    }
+    if let Some(samples) = args.samples {
+        settings.samples = samples;
+    }
+    if args.time_budget.is_some() {
+        settings.time_budget = args.time_budget;
+    }
+    if let Some(threads) = args.threads {
+        settings.threads = threads;
+    }
+    if let Some(reserved_cores) = args.reserved_cores {
+        settings.reserved_cores = reserved_cores;
+    }
+    if args.low_priority {
+        settings.priority = ThreadPriority::Low;
+    }
+    if let Some(size) = args.tile_size {
+        settings.tile_size = size;
+    }
+    if let Some(order) = &args.tile_order {
+        settings.tile_order = match order.as_str() {
+            "spiral" => TileOrder::Spiral,
+            "hilbert" => TileOrder::Hilbert,
+            "cost" => TileOrder::Cost,
+            _ => TileOrder::Scanline,
+        };
+    }
+    if args.seed.is_some() {
+        settings.seed = args.seed;
+    }
+    if args.spectral {
+        settings.spectral = true;
+    }
+    if args.caustics && settings.caustics.is_none() {
+        settings.caustics = Some(Caustics::default());
+    }
+    if args.irradiance_cache && settings.irradiance_cache.is_none() {
+        settings.irradiance_cache = Some(IrradianceCaching::default());
+    }
+    if args.photon_mapping && settings.photon_mapping.is_none() {
+        settings.photon_mapping = Some(PhotonMapping::default());
+    }
+    if args.metropolis && settings.metropolis.is_none() {
+        settings.metropolis = Some(Metropolis::default());
+    }
+    if args.gradient_domain && settings.gradient_domain.is_none() {
+        settings.gradient_domain = Some(GradientDomain::default());
+    }
+    if args.guiding && settings.guiding.is_none() {
+        settings.guiding = Some(PathGuiding::default());
+    }
+    if args.reservoirs && settings.reservoirs.is_none() {
+        settings.reservoirs = Some(ReservoirSampling::default());
+    }
+    if args.light_hierarchy && settings.light_hierarchy.is_none() {
+        settings.light_hierarchy = Some(LightHierarchy::default());
+    }
+    if let Some(ev100) = args.ev {
+        let camera = settings
+            .camera_exposure
+            .get_or_insert_with(CameraExposure::default);
+        camera.ev100 = Some(ev100);
+    }
+    if args.bloom && settings.bloom.is_none() {
+        settings.bloom = Some(Bloom::default());
+    }
+    if args.lens_flare && settings.lens_flare.is_none() {
+        settings.lens_flare = Some(LensFlare::default());
+    }
+    if args.imperfections && settings.imperfections.is_none() {
+        settings.imperfections = Some(Imperfections::default());
+    }
+    if let Some(temperature) = args.white_balance {
+        let balance = settings
+            .white_balance
+            .get_or_insert_with(WhiteBalance::default);
+        balance.temperature = temperature;
+    }
+    if args.aces {
+        settings.output_transform = OutputTransform::Aces;
+    }
+    if let Some(path) = &args.lut {
+        let text =
+            fs::read_to_string(path).map_err(|e| SaturnoError::io(path, e))?;
+        let lut = Lut3d::parse_cube(&text).map_err(|e| {
+            SaturnoError::InvalidSettings(format!("{}: {}", path.display(), e))
+        })?;
+        settings.output_transform = OutputTransform::Lut(lut);
+    }
+    if args.toon || args.toon_bands.is_some() {
+        let toon = settings.toon.get_or_insert_with(Toon::default);
+        if let Some(bands) = args.toon_bands {
+            toon.bands = bands;
+        }
+    }
+    if args.quarantine || args.quarantine_mask.is_some() {
+        settings.quarantine = true;
+    }
+
+    settings.validate()?;
+    // The meshes of the scene build on the threads of the render.
+    bvh::set_build_threads(settings.thread_count());
+    Ok(settings)
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/**
+ * Cameras of a scene with their names, the main one first.
+ */
+type NamedCameras = Vec<(String, Camera)>;
+
+/**
+ * The canvas of the scene of `args`, its cameras by name and its source
+ * (for the metadata): a built-in scene, or a scene file.
+ */
+fn load_scene(
+    args: &Args,
+    width: u32,
+    height: u32,
+) -> Result<(Canvas, NamedCameras, Vec<u8>), SaturnoError> {
+    let path = Path::new(&args.scene);
+    if ["usd", "usda", "usdz"]
+        .iter()
+        .any(|e| has_extension(path, e))
+    {
+        return load_stage(args, path, width, height);
+    }
+    if !has_extension(path, "json") {
+        let (canvas, camera) = match args.scene.as_str() {
+            "book_cover" => (
+                Canvas::new(
+                    scenes::random_book_cover(),
+                    scenes::book_cover_camera(width, height),
+                ),
+                scenes::book_cover_camera(width, height),
+            ),
+            "two_spheres" => (
+                Canvas::new(
+                    scenes::two_spheres_normals(),
+                    scenes::two_spheres_camera(width, height),
+                ),
+                scenes::two_spheres_camera(width, height),
+            ),
+            "mandelbulb" | "menger_sponge" => {
+                let kind = match args.scene.as_str() {
+                    "mandelbulb" => FractalKind::Mandelbulb {
+                        power: 8.0,
+                        iterations: 12,
+                    },
+                    _ => FractalKind::MengerSponge { iterations: 5 },
+                };
+                (
+                    Canvas::new(
+                        scenes::fractal(kind),
+                        scenes::fractal_camera(width, height),
+                    ),
+                    scenes::fractal_camera(width, height),
+                )
+            }
+            other => {
+                return Err(SaturnoError::InvalidSettings(format!(
+                    "unknown scene '{}'",
+                    other
+                )))
+            }
+        };
+        let cameras = vec![("camera".to_string(), camera)];
+        return Ok((canvas, cameras, args.scene.clone().into_bytes()));
+    }
+
+    let source = fs::read(path).map_err(|e| SaturnoError::io(path, e))?;
+    let invalid = |e: String| {
+        SaturnoError::InvalidSettings(format!("{}: {}", path.display(), e))
+    };
+    let mut scene: SceneDescription =
+        serde_json::from_slice(&source).map_err(|e| invalid(e.to_string()))?;
+    if let Some(frame) = args.frame {
+        scene.frame = frame;
+    }
+    let base = path.parent().unwrap_or(Path::new("."));
+    mesh::read_textures(&mut scene, base)?;
+    let cache = args.mesh_cache.as_deref().map(MeshCache::new).transpose()?;
+    let meshes = read_files(&scene, base, cache.as_ref())?;
+    let canvas =
+        scene_canvas(&scene, width, height, meshes).map_err(invalid)?;
+    let cameras = scene
+        .views()
+        .into_iter()
+        .map(|(name, camera)| (name.to_string(), camera.build(width, height)))
+        .collect();
+    Ok((canvas, cameras, source))
+}
+
+/**
+ * Load the USD stage at `path` at the frame of `args` (its default values
+ * without one), seen from its first camera or from one framing it.
+ */
+fn load_stage(
+    args: &Args,
+    path: &Path,
+    width: u32,
+    height: u32,
+) -> Result<(Canvas, NamedCameras, Vec<u8>), SaturnoError> {
+    let source = fs::read(path).map_err(|e| SaturnoError::io(path, e))?;
+    let stage = usd::read_stage(path, Units::Meters, args.frame)?;
+    let mut views = stage.cameras.clone();
+    if views.is_empty() {
+        views.push(("camera".to_string(), stage.framing()));
+    }
+    let mut canvas = Canvas::new(stage.actors, views[0].1.build(width, height));
+    for light in &stage.lights {
+        canvas
+            .lights
+            .extend(light.build_in(Units::Meters).map_err(|e| {
+                SaturnoError::InvalidSettings(format!(
+                    "{}: {}",
+                    path.display(),
+                    e
+                ))
+            })?);
+    }
+    let cameras = views
+        .into_iter()
+        .map(|(name, camera)| (name, camera.build(width, height)))
+        .collect();
+    Ok((canvas, cameras, source))
+}
+
+/**
+ * `path` with `name` appended to its stem: out.png and top give
+ * out_top.png.
+ */
+fn camera_path(path: &Path, name: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut file = format!("{}_{}", stem, name);
+    if let Some(extension) = path.extension() {
+        file = format!("{}.{}", file, extension.to_string_lossy());
+    }
+    path.with_file_name(file)
+}
+
+/**
+ * Render the queue of jobs, every frame of `--frames`, or the scene once.
+ */
+fn run(args: &Args) -> Result<(), SaturnoError> {
+    if let Some(Command::Queue {
+        jobs,
+        workers,
+        logs,
+    }) = &args.command
+    {
+        return queue::run_queue(jobs, *workers, logs.as_deref());
+    }
+    let (first, last) = match args.frames {
+        Some(frames) => frames,
+        None => return render(args),
+    };
+    for frame in first..=last {
+        let mut frame_args = args.clone();
+        frame_args.frame = Some(frame as Float);
+        frame_args.output = camera_path(&args.output, &format!("{:04}", frame));
+        tracing::info!(frame, "rendering");
+        render(&frame_args)?;
+    }
+    Ok(())
+}
+
+fn render(args: &Args) -> Result<(), SaturnoError> {
+    let settings = load_settings(args)?;
+    let (width, height) = (settings.width, settings.height);
+    let (mut canvas, mut cameras, source) = load_scene(args, width, height)?;
+    if args.camera_relative {
+        canvas.recenter();
+        let offset = canvas.world_origin().map(|c| -c as Float);
+        for (_, camera) in &mut cameras {
+            camera.translate(&offset);
+        }
+        tracing::info!(origin = ?canvas.world_origin(), "camera relative");
+    }
+
+    if let Some(Command::Inspect) = args.command {
+        let names = cameras.iter().map(|(n, _)| n.as_str()).collect::<Vec<_>>();
+        return writeln!(
+            io::stdout(),
+            "scene: {}\ncameras: {} ({})\n{}",
+            args.scene,
+            cameras.len(),
+            names.join(", "),
+            canvas.stats()
+        )
+        .map_err(|e| SaturnoError::io(Path::new("stdout"), e));
+    }
+
+    if let Some(Command::Estimate {
+        probe_scale,
+        probe_samples,
+    }) = args.command
+    {
+        let probe = Probe {
+            scale: probe_scale,
+            samples: probe_samples,
+        };
+        let estimate = estimate(&mut canvas, &settings, &probe)?;
+        let region = settings.region();
+        let budget = match settings.time_budget {
+            Some(budget) => format!(" in {}", format_duration(budget)),
+            None => String::new(),
+        };
+        return writeln!(
+            io::stdout(),
+            "render: {}x{}, {} samples{}\n\
+             probe: {}x{}, {} samples, {}\n\
+             pass: {} ({:.1} rays, {:.1} nodes and {:.1} tests per pixel \
+             sample)\n\
+             estimate: {}",
+            region.width,
+            region.height,
+            estimate.samples,
+            budget,
+            estimate.probe.region().width,
+            estimate.probe.region().height,
+            estimate.probe.samples,
+            format_duration(estimate.probe_seconds),
+            format_duration(estimate.seconds_per_pass),
+            estimate.rays_per_sample(),
+            estimate.nodes_per_sample(),
+            estimate.tests_per_sample(),
+            format_duration(estimate.seconds)
+        )
+        .map_err(|e| SaturnoError::io(Path::new("stdout"), e));
+    }
+
+    if let Some(rect) = args.debug_pixel {
+        let traces = canvas.trace_pixels(&settings, rect)?;
+        if let Some(path) = &args.debug_paths {
+            let lines = paths::path_lines(&traces, args.escape_length);
+            if has_extension(path, "obj") {
+                paths::write_paths_obj(path, &lines)?;
+            } else {
+                paths::write_paths_ply(path, &lines)?;
+            }
+        }
+
+        let json = serde_json::to_string_pretty(&traces)
+            .map_err(|e| SaturnoError::InvalidSettings(e.to_string()))?;
+        return match &args.debug_output {
+            Some(path) => {
+                fs::write(path, json).map_err(|e| SaturnoError::io(path, e))
+            }
+            None => writeln!(io::stdout(), "{}", json)
+                .map_err(|e| SaturnoError::io(Path::new("stdout"), e)),
+        };
+    }
+
+    if let Some(heatmap) = &args.heatmap {
+        let kind = match heatmap.as_str() {
+            "rays" => CostKind::Rays,
+            "nodes" => CostKind::Nodes,
+            _ => CostKind::Tests,
+        };
+        let cost = canvas.render_cost(&settings)?;
+        let values = cost.values(kind);
+        tracing::info!(
+            max = values.iter().max(),
+            mean = values.iter().map(|v| *v as u64).sum::<u64>()
+                / values.len() as u64,
+            "{} per pixel",
+            heatmap
+        );
+        return output::write_png(&args.output, &cost.heatmap(kind));
+    }
+
+    if let Some(analysis) = &args.analysis {
+        let hdr = canvas.render_hdr(&settings)?;
+        let scale = settings.exposure_scale();
+        let histogram = Histogram::new(&hdr, scale);
+        tracing::info!(
+            clipped = histogram.clipped_fraction(),
+            black = histogram.black,
+            "exposure"
+        );
+        let image = match analysis.as_str() {
+            "histogram" => histogram.image(480, 240),
+            "waveform" => waveform(&hdr, scale, 240, -10.0, 6.0),
+            _ => false_color(&hdr, scale),
+        };
+        return output::write_png(&args.output, &image);
+    }
+
+    if let Some(bounds) = &args.bounds {
+        let mut image = if bounds == "only" {
+            let region = settings.region();
+            Image::new(region.width, region.height, 4)
+        } else {
+            canvas.render_scene(&settings)?
+        };
+        canvas.draw_bounds(&settings, &mut image);
+        return output::write_png(&args.output, &image);
+    }
+
+    if let Some(peaking) = &args.focus_peaking {
+        let image = if peaking == "only" {
+            canvas.render_focus_peaking(&settings)?
+        } else {
+            let mut image = canvas.render_scene(&settings)?;
+            let blur = canvas.focus_blur(&settings)?;
+            overlay::draw_focus_peaking(&mut image, &blur);
+            image
+        };
+        return output::write_png(&args.output, &image);
+    }
+
+    if args.all_cameras || args.contact_sheet.is_some() {
+        let names = cameras.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+        let images = canvas.render_cameras(&settings, cameras)?;
+        for (name, image) in &images {
+            output::write_png(&camera_path(&args.output, name), image)?;
+        }
+        if let Some(path) = &args.contact_sheet {
+            let images = images.iter().map(|(_, i)| i).collect::<Vec<_>>();
+            let sheet = contact_sheet(&images, args.sheet_columns, 8);
+            let mut metadata =
+                RenderMetadata::new(&settings).scene(&args.scene, &source);
+            metadata.insert("saturno:cameras", names.join(","));
+            output::write_png_metadata(path, &sheet, &metadata)?;
+        }
+        return Ok(());
+    }
+
+    if let Some(mask) = &args.quarantine_mask {
+        let quarantine = canvas.render_quarantine(&settings)?;
+        output::write_png(
+            mask,
+            &develop(&quarantine.mask, &RenderSettings::default()),
+        )?;
+        return if has_extension(&args.output, "hdr") {
+            output::write_hdr(&args.output, &quarantine.image)
+        } else {
+            output::write_png(
+                &args.output,
+                &develop(&quarantine.image, &settings),
+            )
+        };
+    }
+
+    let start = Instant::now();
+    let (hdr, settings) = match settings.time_budget {
+        Some(budget) => {
+            let budget = Duration::from_secs_f64(budget);
+            let progressive = canvas.render_budget(&settings, budget)?;
+            (progressive.hdr(), progressive.settings)
+        }
+        None => (canvas.render_hdr(&settings)?, settings),
+    };
+    let mut metadata = RenderMetadata::new(&settings)
+        .scene(&args.scene, &source)
+        .render_time(start.elapsed());
+    if let Ok(json) = serde_json::to_string(&settings) {
+        metadata.insert("saturno:settings", json);
+    }
+    if has_extension(&args.output, "hdr") {
+        output::write_hdr_metadata(&args.output, &hdr, &metadata)
+    } else {
+        let image = develop(&hdr, &settings);
+        output::write_png_metadata(&args.output, &image, &metadata)
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let level = if args.verbose { "info" } else { "warn" };
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new(level)),
+        )
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr)
+        .init();
+
+    if let Err(e) = run(&args) {
+        eprintln!("saturno: {}", e);
+        process::exit(1);
+    }
+}
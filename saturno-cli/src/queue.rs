@@ -0,0 +1,407 @@
+// Batch rendering of a queue of jobs, for renders left running overnight.
+// A job is a JSON object of the options of the command line, its paths
+// relative to the file it is in:
+//
+//     {"scene": "shot_010.json", "output": "shot_010.png", "samples": 1024}
+//
+// The jobs are the .job.json files of a directory, in the order of their
+// names (the scenes and settings next to them are not jobs), or the array
+// of a manifest file (named by their name field).
+// Every job renders in a process of its own, so that one failing does not
+// stop the others, `workers` at a time sharing the cores. The output of a
+// job goes to a log named after it, and the exit status of every job to
+// status.json next to the logs.
+
+use saturno::prelude::*;
+use serde_json::json;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+/**
+ * Options of the command line naming files, relative to the job file.
+ */
+const PATHS: [&str; 8] = [
+    "scene",
+    "settings",
+    "output",
+    "lut",
+    "contact_sheet",
+    "quarantine_mask",
+    "debug_output",
+    "debug_paths",
+];
+
+/**
+ * Suffix of the job files of a directory.
+ */
+const JOB_SUFFIX: &str = ".job.json";
+
+/**
+ * A render of the queue: its name (that of its log) and the arguments of
+ * the command line rendering it.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Job {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+/**
+ * How a job ended: its exit code (None if it was killed or could not
+ * start, see `error`) and how long it took.
+ */
+#[derive(Clone, Debug, PartialEq)]
+struct Outcome {
+    code: Option<i32>,
+    error: Option<String>,
+    seconds: f64,
+}
+
+impl Outcome {
+    fn succeeded(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+/**
+ * The arguments of the options of a job, its paths relative to `base`.
+ * True flags are given, false ones left out.
+ */
+fn job_args(options: &Value, base: &Path) -> Result<Vec<String>, String> {
+    let options = options
+        .as_object()
+        .ok_or_else(|| "a job is an object of options".to_string())?;
+    let mut args = vec![];
+    for (key, value) in options {
+        if key == "name" {
+            continue;
+        }
+        let flag = format!("--{}", key.replace('_', "-"));
+        match value {
+            Value::Bool(true) => args.push(flag),
+            Value::Bool(false) | Value::Null => {}
+            Value::Number(number) => args.extend([flag, number.to_string()]),
+            Value::String(text) => {
+                let path = Path::new(text);
+                // Built-in scenes are names, not files.
+                let file = key != "scene" || path.extension().is_some();
+                let text = if PATHS.contains(&key.as_str())
+                    && file
+                    && path.is_relative()
+                {
+                    base.join(path).display().to_string()
+                } else {
+                    text.clone()
+                };
+                args.extend([flag, text]);
+            }
+            _ => return Err(format!("unsupported value of {}", key)),
+        }
+    }
+    Ok(args)
+}
+
+/**
+ * Check that `name` names a log of the logs folder, not a path out of it.
+ */
+fn check_name(name: &str) -> Result<(), String> {
+    let parts = Path::new(name).components().collect::<Vec<_>>();
+    let plain = matches!(parts[..], [std::path::Component::Normal(_)]);
+    if !plain || name.contains(['/', '\\']) {
+        return Err(format!("invalid job name {:?}", name));
+    }
+    Ok(())
+}
+
+fn read_json(path: &Path) -> Result<Value, SaturnoError> {
+    let text = fs::read(path).map_err(|e| SaturnoError::io(path, e))?;
+    serde_json::from_slice(&text).map_err(|e| {
+        SaturnoError::InvalidSettings(format!("{}: {}", path.display(), e))
+    })
+}
+
+/**
+ * The jobs of the directory of job files or of the manifest at `path`.
+ */
+pub fn read_jobs(path: &Path) -> Result<Vec<Job>, SaturnoError> {
+    let invalid = |path: &Path, e: String| {
+        SaturnoError::InvalidSettings(format!("{}: {}", path.display(), e))
+    };
+    let mut jobs = vec![];
+    if path.is_dir() {
+        let mut files = fs::read_dir(path)
+            .map_err(|e| SaturnoError::io(path, e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|file| file.is_file())
+            .filter_map(|file| {
+                let name = file.file_name()?.to_str()?;
+                let name = name.strip_suffix(JOB_SUFFIX)?.to_string();
+                Some((name, file))
+            })
+            .collect::<Vec<_>>();
+        files.sort();
+        for (name, file) in files {
+            check_name(&name).map_err(|e| invalid(&file, e))?;
+            let args = job_args(&read_json(&file)?, path)
+                .map_err(|e| invalid(&file, e))?;
+            jobs.push(Job { name, args });
+        }
+    } else {
+        let base = path.parent().unwrap_or(Path::new("."));
+        let manifest = read_json(path)?;
+        let entries = manifest.as_array().ok_or_else(|| {
+            invalid(path, "a manifest is an array of jobs".into())
+        })?;
+        for (i, options) in entries.iter().enumerate() {
+            let name = match options.get("name").and_then(Value::as_str) {
+                Some(name) => name.to_string(),
+                None => format!("job_{:04}", i + 1),
+            };
+            check_name(&name).map_err(|e| invalid(path, e))?;
+            let args = job_args(options, base)
+                .map_err(|e| invalid(path, format!("{}: {}", name, e)))?;
+            jobs.push(Job { name, args });
+        }
+    }
+    let mut names = jobs.iter().map(|job| &job.name).collect::<Vec<_>>();
+    names.sort();
+    if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(invalid(path, format!("two jobs named {}", pair[0])));
+    }
+    Ok(jobs)
+}
+
+/**
+ * Render `job` with the program `program`, logging its output to the
+ * file `log`. With `threads`, the job renders with as many (unless it
+ * sets them).
+ */
+fn run_job(
+    program: &Path,
+    job: &Job,
+    log: &Path,
+    threads: Option<usize>,
+) -> Outcome {
+    let start = Instant::now();
+    let mut command = process::Command::new(program);
+    command.args(&job.args);
+    let has = |flag: &str| job.args.iter().any(|arg| arg == flag);
+    if let Some(threads) = threads.filter(|_| !has("--threads")) {
+        command.args(["--threads".to_string(), threads.to_string()]);
+    }
+    if !has("--verbose") {
+        command.arg("--verbose");
+    }
+    // Plain text logs, without the escapes of the colors.
+    command.env("NO_COLOR", "1");
+    let status = File::create(log)
+        .and_then(|file| {
+            command
+                .stdout(Stdio::from(file.try_clone()?))
+                .stderr(Stdio::from(file));
+            command.status()
+        })
+        .map_err(|e| e.to_string());
+    Outcome {
+        code: status.as_ref().ok().and_then(|status| status.code()),
+        error: status.err(),
+        seconds: start.elapsed().as_secs_f64(),
+    }
+}
+
+/**
+ * Render the jobs at `path` (see `read_jobs`), `workers` at a time. Their
+ * logs and status.json go to `logs`, a logs folder next to the jobs by
+ * default. Fails if any job failed, once all have run.
+ */
+pub fn run_queue(
+    path: &Path,
+    workers: usize,
+    logs: Option<&Path>,
+) -> Result<(), SaturnoError> {
+    let jobs = read_jobs(path)?;
+    let logs = match logs {
+        Some(logs) => logs.to_path_buf(),
+        None if path.is_dir() => path.join("logs"),
+        None => path.with_file_name("logs"),
+    };
+    fs::create_dir_all(&logs).map_err(|e| SaturnoError::io(&logs, e))?;
+    let program = env::current_exe()
+        .map_err(|e| SaturnoError::io(Path::new("saturno"), e))?;
+    let workers = workers.clamp(1, jobs.len().max(1));
+    // The cores shared by the workers.
+    let threads = (workers > 1).then(|| {
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        (cores / workers).max(1)
+    });
+
+    let log_path =
+        |job: &Job| -> PathBuf { logs.join(format!("{}.log", job.name)) };
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(vec![None; jobs.len()]);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let job = match jobs.get(i) {
+                    Some(job) => job,
+                    None => break,
+                };
+                tracing::info!(job = %job.name, "rendering");
+                let outcome = run_job(&program, job, &log_path(job), threads);
+                if outcome.succeeded() {
+                    tracing::info!(job = %job.name, seconds = outcome.seconds, "done");
+                } else {
+                    tracing::warn!(
+                        job = %job.name,
+                        code = ?outcome.code,
+                        error = ?outcome.error,
+                        "failed"
+                    );
+                }
+                outcomes.lock().unwrap()[i] = Some(outcome);
+            });
+        }
+    });
+
+    let outcomes: Vec<Outcome> = outcomes
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(Option::unwrap)
+        .collect();
+    let status: Vec<Value> = jobs
+        .iter()
+        .zip(&outcomes)
+        .map(|(job, outcome)| {
+            json!({
+                "job": job.name,
+                "args": job.args,
+                "exit_code": outcome.code,
+                "error": outcome.error,
+                "seconds": outcome.seconds,
+                "log": log_path(job),
+            })
+        })
+        .collect();
+    let status_path = logs.join("status.json");
+    let text = serde_json::to_string_pretty(&status)
+        .map_err(|e| SaturnoError::InvalidSettings(e.to_string()))?;
+    fs::write(&status_path, text)
+        .map_err(|e| SaturnoError::io(&status_path, e))?;
+
+    let failed = outcomes.iter().filter(|o| !o.succeeded()).count();
+    if failed > 0 {
+        return Err(SaturnoError::InvalidSettings(format!(
+            "{} of {} jobs failed, see {}",
+            failed,
+            jobs.len(),
+            status_path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(name: &str) -> PathBuf {
+        let folder = env::temp_dir().join("saturno_queue").join(name);
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn job_paths() {
+        let base = Path::new("/jobs");
+        let options = json!({
+            "scene": "shot.json",
+            "output": "/renders/shot.png",
+            "settings": "../settings.json",
+            "samples": 64,
+            "denoise": true,
+            "progress": false,
+        });
+        let args = job_args(&options, base).unwrap();
+        assert_eq!(
+            args,
+            [
+                "--denoise",
+                "--output",
+                "/renders/shot.png",
+                "--samples",
+                "64",
+                "--scene",
+                "/jobs/shot.json",
+                "--settings",
+                "/jobs/../settings.json",
+            ]
+        );
+        // Built-in scenes and options that are not paths are kept.
+        let options = json!({"scene": "cornell", "camera": "top"});
+        let args = job_args(&options, base).unwrap();
+        assert_eq!(args, ["--camera", "top", "--scene", "cornell"]);
+
+        assert!(job_args(&json!({"scene": ["a", "b"]}), base).is_err());
+        assert!(job_args(&json!(["a"]), base).is_err());
+    }
+
+    #[test]
+    fn job_directory() {
+        let folder = folder("directory");
+        fs::write(folder.join("b.job.json"), r#"{"samples": 2}"#).unwrap();
+        fs::write(folder.join("a.job.json"), r#"{"scene": "a.json"}"#).unwrap();
+        // Scenes and settings next to the jobs are not jobs.
+        fs::write(folder.join("a.json"), r#"{"actors": []}"#).unwrap();
+        fs::write(folder.join("settings.json"), "[1, 2]").unwrap();
+
+        let jobs = read_jobs(&folder).unwrap();
+        let names = jobs.iter().map(|job| job.name.as_str());
+        assert_eq!(names.collect::<Vec<_>>(), ["a", "b"]);
+        let scene = folder.join("a.json").display().to_string();
+        assert_eq!(jobs[0].args, ["--scene".to_string(), scene]);
+        assert_eq!(jobs[1].args, ["--samples", "2"]);
+    }
+
+    #[test]
+    fn job_manifest() {
+        let folder = folder("manifest");
+        let manifest = folder.join("jobs.json");
+        fs::write(
+            &manifest,
+            r#"[{"name": "first", "scene": "a.json"}, {"samples": 2}]"#,
+        )
+        .unwrap();
+        let jobs = read_jobs(&manifest).unwrap();
+        assert_eq!(jobs[0].name, "first");
+        let scene = folder.join("a.json").display().to_string();
+        assert_eq!(jobs[0].args, ["--scene".to_string(), scene]);
+        assert_eq!(jobs[1].name, "job_0002");
+
+        // Names are unique, and name files of the logs folder.
+        for jobs in [
+            r#"[{"name": "a"}, {"name": "a"}]"#,
+            r#"[{"name": "../../x"}]"#,
+            r#"[{"name": "logs/x"}]"#,
+            r#"[{"name": ".."}]"#,
+            r#"[{"name": ""}]"#,
+            r#"{"name": "a"}"#,
+        ] {
+            fs::write(&manifest, jobs).unwrap();
+            assert!(read_jobs(&manifest).is_err(), "{}", jobs);
+        }
+    }
+}
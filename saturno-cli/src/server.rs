@@ -0,0 +1,443 @@
+// HTTP render service: scene descriptions are posted as JSON and rendered
+// progressively in the background, while their progress and latest
+// estimate (PNG or EXR) can be fetched.
+//
+//     POST /renders                 {"scene": {...}, "settings": {...}}
+//     GET  /renders/{id}            progress
+//     GET  /renders/{id}/image.png  tone mapped estimate
+//     GET  /renders/{id}/image.exr  linear radiance estimate
+//     GET  /renders/{id}/stream     WebSocket of the passes as they finish
+//     DELETE /renders/{id}          stop the render and forget it
+//
+// At most `--max-renders` render at once, new ones are refused with 503
+// Service Unavailable until one ends. Finished renders are forgotten after
+// `--keep-seconds`, with their images. Renders of more than `--max-pixels`
+// or `--max-samples`, or under a time budget, are refused with 400 Bad
+// Request, as are scenes with meshes or Alembic caches: there are no files
+// to read them from.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Path, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clap::Parser;
+use futures::StreamExt;
+use saturno::io::output;
+use saturno::io::scene::{build_without_files, scene_canvas};
+use saturno::prelude::*;
+use saturno::render::stream::{render_stream, RenderPass};
+use saturno::scene::description::SceneDescription;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(
+    name = "saturno-server",
+    about = "Serve renders of the Saturno ray tracer over HTTP"
+)]
+struct Args {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8088")]
+    address: String,
+
+    /// Log as JSON lines instead of text (RUST_LOG sets the level).
+    #[arg(long)]
+    json_logs: bool,
+
+    /// Renders rendering at once, more are refused until one ends.
+    #[arg(long, default_value_t = 2)]
+    max_renders: usize,
+
+    /// Seconds finished renders are kept before they are forgotten.
+    #[arg(long, default_value_t = 3600)]
+    keep_seconds: u64,
+
+    /// Pixels of a render (width times height), at most.
+    #[arg(long, default_value_t = 4096 * 4096)]
+    max_pixels: u64,
+
+    /// Samples per pixel of a render, at most.
+    #[arg(long, default_value_t = 4096)]
+    max_samples: u32,
+}
+
+#[derive(Deserialize)]
+struct RenderRequest {
+    scene: SceneDescription,
+    #[serde(default)]
+    settings: RenderSettings,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JobState {
+    Rendering,
+    Done,
+    Failed,
+}
+
+struct Job {
+    state: JobState,
+    passes: u32,
+    error: Option<String>,
+    latest: Option<Arc<RenderPass>>,
+    /** Notified on every pass or error, with the passes done. */
+    updates: watch::Sender<u32>,
+}
+
+/**
+ * Progress of a job as reported to clients. `passes_done` out of
+ * `passes` have been accumulated into the latest estimate.
+ */
+#[derive(Serialize)]
+struct Progress {
+    id: u64,
+    state: JobState,
+    passes_done: u32,
+    passes: u32,
+    error: Option<String>,
+}
+
+impl Job {
+    fn progress(&self, id: u64) -> Progress {
+        Progress {
+            id,
+            state: self.state,
+            passes_done: self.latest.as_ref().map_or(0, |pass| pass.pass + 1),
+            passes: self.passes,
+            error: self.error.clone(),
+        }
+    }
+}
+
+struct Jobs {
+    next_id: u64,
+    jobs: HashMap<u64, Job>,
+    /** Jobs rendering at once, at most. */
+    max_renders: usize,
+    /** How long finished jobs are kept. */
+    keep: Duration,
+    /** Pixels and samples per pixel of a job, at most. */
+    max_pixels: u64,
+    max_samples: u32,
+}
+
+impl Jobs {
+    fn rendering(&self) -> usize {
+        let jobs = self.jobs.values();
+        jobs.filter(|job| matches!(job.state, JobState::Rendering))
+            .count()
+    }
+}
+
+type SharedJobs = Arc<Mutex<Jobs>>;
+
+async fn create_render(
+    State(jobs): State<SharedJobs>,
+    Json(request): Json<RenderRequest>,
+) -> Response {
+    let settings = request.settings;
+    let (width, height) = (settings.width, settings.height);
+    let refused = {
+        let jobs = jobs.lock().unwrap();
+        if width as u64 * height as u64 > jobs.max_pixels {
+            Some(format!("more than {} pixels", jobs.max_pixels))
+        } else if settings.samples > jobs.max_samples {
+            Some(format!("more than {} samples", jobs.max_samples))
+        } else if settings.time_budget.is_some() {
+            // The passes are streamed, `samples` of them.
+            Some("time budgets are not supported, give samples".to_string())
+        } else {
+            None
+        }
+    };
+    if let Some(e) = refused {
+        tracing::info!(error = %e, "rejected render");
+        return (StatusCode::BAD_REQUEST, e).into_response();
+    }
+    // There are no files next to a scene sent over the network.
+    let scene = &request.scene;
+    let canvas = match build_without_files(scene)
+        .and_then(|actors| scene_canvas(scene, width, height, actors))
+    {
+        Ok(canvas) => canvas,
+        Err(e) => {
+            tracing::info!(error = %e, "rejected render");
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    };
+    if let Err(e) = canvas.validate(&settings) {
+        tracing::info!(error = %e, "rejected render");
+        return (StatusCode::BAD_REQUEST, e.to_string()).into_response();
+    }
+
+    let job = Job {
+        state: JobState::Rendering,
+        passes: settings.samples,
+        error: None,
+        latest: None,
+        updates: watch::Sender::new(0),
+    };
+    let (id, progress) = {
+        let mut jobs = jobs.lock().unwrap();
+        if jobs.rendering() >= jobs.max_renders {
+            tracing::info!("rejected render, too many rendering");
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                [(header::RETRY_AFTER, "10")],
+                "too many renders, retry later",
+            )
+                .into_response();
+        }
+        jobs.next_id += 1;
+        let id = jobs.next_id;
+        let progress = job.progress(id);
+        jobs.jobs.insert(id, job);
+        (id, progress)
+    };
+
+    tracing::info!(
+        id,
+        width = settings.width,
+        height = settings.height,
+        samples = settings.samples,
+        actors = canvas.world.actors.len(),
+        "render started"
+    );
+    let mut stream = tracing::info_span!("render", id)
+        .in_scope(|| render_stream(Renderer::new(canvas, settings)));
+    tokio::spawn(async move {
+        while let Some(update) = stream.next().await {
+            let mut jobs = jobs.lock().unwrap();
+            // Deleted: dropping the stream stops the render.
+            let job = match jobs.jobs.get_mut(&id) {
+                Some(job) => job,
+                None => return,
+            };
+            match update {
+                Ok(pass) => {
+                    if pass.pass + 1 == pass.passes {
+                        tracing::info!(id, "render done");
+                        job.state = JobState::Done;
+                    }
+                    job.latest = Some(Arc::new(pass));
+                }
+                Err(e) => {
+                    tracing::warn!(id, error = %e, "render failed");
+                    job.state = JobState::Failed;
+                    job.error = Some(e.to_string());
+                }
+            }
+            let progress = job.progress(id);
+            job.updates.send_replace(progress.passes_done);
+        }
+
+        // Kept for its clients to fetch, then forgotten.
+        let keep = {
+            let mut jobs = jobs.lock().unwrap();
+            if let Some(job) = jobs.jobs.get_mut(&id) {
+                // Ended without an error: the render thread panicked.
+                if matches!(job.state, JobState::Rendering) {
+                    job.state = JobState::Failed;
+                    job.error = Some("render stopped".to_string());
+                    job.updates.send_modify(|_| {});
+                }
+            }
+            jobs.keep
+        };
+        tokio::time::sleep(keep).await;
+        if jobs.lock().unwrap().jobs.remove(&id).is_some() {
+            tracing::debug!(id, "render forgotten");
+        }
+    });
+
+    let location = format!("/renders/{}", id);
+    (
+        StatusCode::CREATED,
+        [(header::LOCATION, location)],
+        Json(progress),
+    )
+        .into_response()
+}
+
+async fn delete_render(
+    State(jobs): State<SharedJobs>,
+    Path(id): Path<u64>,
+) -> Response {
+    match jobs.lock().unwrap().jobs.remove(&id) {
+        Some(_) => {
+            tracing::info!(id, "render deleted");
+            StatusCode::NO_CONTENT.into_response()
+        }
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn get_progress(
+    State(jobs): State<SharedJobs>,
+    Path(id): Path<u64>,
+) -> Response {
+    match jobs.lock().unwrap().jobs.get(&id) {
+        Some(job) => Json(job.progress(id)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/**
+ * Encode the latest estimate of a job, if it has one yet, once the jobs are
+ * unlocked.
+ */
+fn latest_image(
+    jobs: &SharedJobs,
+    id: u64,
+    content_type: &'static str,
+    encode: impl Fn(&mut Vec<u8>, &RenderPass) -> std::io::Result<()>,
+) -> Response {
+    let pass = match jobs.lock().unwrap().jobs.get(&id) {
+        Some(Job {
+            latest: Some(pass), ..
+        }) => pass.clone(),
+        Some(_) => {
+            return (StatusCode::CONFLICT, "no pass rendered yet")
+                .into_response()
+        }
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+
+    let mut bytes = Vec::new();
+    match encode(&mut bytes, &pass) {
+        Ok(()) => {
+            ([(header::CONTENT_TYPE, content_type)], bytes).into_response()
+        }
+        Err(e) => {
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+async fn get_png(
+    State(jobs): State<SharedJobs>,
+    Path(id): Path<u64>,
+) -> Response {
+    latest_image(&jobs, id, "image/png", |bytes, pass| {
+        output::encode_png(bytes, &pass.image)
+    })
+}
+
+async fn get_exr(
+    State(jobs): State<SharedJobs>,
+    Path(id): Path<u64>,
+) -> Response {
+    latest_image(&jobs, id, "image/x-exr", |bytes, pass| {
+        output::encode_exr_rgba(bytes, &pass.hdr)
+    })
+}
+
+async fn stream_render(
+    State(jobs): State<SharedJobs>,
+    Path(id): Path<u64>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let updates = match jobs.lock().unwrap().jobs.get(&id) {
+        Some(job) => job.updates.subscribe(),
+        None => return StatusCode::NOT_FOUND.into_response(),
+    };
+    upgrade.on_upgrade(move |socket| send_passes(socket, jobs, id, updates))
+}
+
+/**
+ * Send the latest estimate and then every new one until the job ends, each
+ * as its progress (JSON text message) followed by the tone mapped PNG
+ * (binary message). A client that falls behind skips passes instead of
+ * queueing them.
+ */
+async fn send_passes(
+    mut socket: WebSocket,
+    jobs: SharedJobs,
+    id: u64,
+    mut updates: watch::Receiver<u32>,
+) {
+    loop {
+        updates.borrow_and_update();
+        let (progress, latest) = match jobs.lock().unwrap().jobs.get(&id) {
+            Some(job) => (job.progress(id), job.latest.clone()),
+            None => break,
+        };
+        let mut png = Vec::new();
+        if let Some(pass) = latest {
+            if output::encode_png(&mut png, &pass.image).is_err() {
+                return;
+            }
+        }
+        let rendering = matches!(progress.state, JobState::Rendering);
+
+        let json = match serde_json::to_string(&progress) {
+            Ok(json) => json,
+            Err(_) => return,
+        };
+        if socket.send(Message::Text(json.into())).await.is_err() {
+            return;
+        }
+        if !png.is_empty()
+            && socket.send(Message::Binary(png.into())).await.is_err()
+        {
+            return;
+        }
+
+        if !rendering || updates.changed().await.is_err() {
+            break;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+    let logs = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+    if args.json_logs {
+        logs.json().init();
+    } else {
+        logs.init();
+    }
+
+    let app = Router::new()
+        .route("/renders", post(create_render))
+        .route("/renders/{id}", get(get_progress).delete(delete_render))
+        .route("/renders/{id}/image.png", get(get_png))
+        .route("/renders/{id}/image.exr", get(get_exr))
+        .route("/renders/{id}/stream", get(stream_render))
+        .with_state(Arc::new(Mutex::new(Jobs {
+            next_id: 0,
+            jobs: HashMap::new(),
+            max_renders: args.max_renders.max(1),
+            keep: Duration::from_secs(args.keep_seconds),
+            max_pixels: args.max_pixels,
+            max_samples: args.max_samples,
+        })));
+
+    let listener = match tokio::net::TcpListener::bind(&args.address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("saturno-server: {}: {}", args.address, e);
+            process::exit(1);
+        }
+    };
+    tracing::info!(address = %args.address, "listening");
+    if let Err(e) = axum::serve(listener, app).await {
+        eprintln!("saturno-server: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,928 @@
+use crate::mesh::CornerUvs;
+use crate::mesh::MeshData;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::actor::RayTraceable;
+use saturno_scene::description::AlembicDescription;
+use saturno_scene::description::SceneDescription;
+use saturno_scene::units::Units;
+use std::path::Path;
+use tracing::instrument;
+
+// Animated geometry from Alembic (.abc) caches, as exported by Maya,
+// Houdini or Blender for cached simulations and animated shots: the
+// polygon meshes (and subdivision surfaces, as their polygons) placed by
+// the transforms above them, at a time of the animation.
+//
+// Alembic files are stored in Ogawa containers, a tree of groups (lists of
+// the offsets of their children) and blocks of data. The archive is a
+// group of its versions, the top object, its metadata, its time samplings
+// (when the samples of the properties are) and the metadata shared by its
+// objects and properties. An object is a group of its properties, its
+// children and their headers; a compound of properties a group of them
+// and their headers, bit packed. The samples of a property are blocks of
+// data after a 16 byte hash (an array's with its dimensions), those equal
+// to the previous ones not stored again.
+//
+// Between two samples, the positions of a mesh whose vertices stay the
+// same and the values of the transforms are interpolated linearly, so
+// that the meshes move smoothly at the times between frames: read at
+// several times over the shutter, they deform for the motion blur (see
+// `Mesh::with_deformation`). Alembic faces wind clockwise, they are turned
+// counterclockwise (the front of the triangles of a `Mesh`).
+
+/**
+ * A transform of column vectors, row by row.
+ */
+pub(crate) type Matrix = [[Float; 4]; 4];
+
+pub(crate) const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/**
+ * The time per cycle of acyclic time samplings, which list the time of
+ * every sample.
+ */
+const ACYCLIC: Float = Float::MAX / 32.0;
+
+/**
+ * A node of an Ogawa tree, at its offset in the file (0 if empty).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Node {
+    Group(usize),
+    Data(usize),
+}
+
+/**
+ * Reads the values of a block of data in turn, little endian.
+ */
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    fn take(&mut self, n: usize) -> std::result::Result<&'a [u8], String> {
+        if n > self.remaining() {
+            return Err("truncated data".to_string());
+        }
+        self.pos += n;
+        Ok(&self.bytes[self.pos - n..self.pos])
+    }
+
+    fn array<const N: usize>(
+        &mut self,
+    ) -> std::result::Result<[u8; N], String> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> std::result::Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> std::result::Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> std::result::Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f64(&mut self) -> std::result::Result<f64, String> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    /**
+     * An integer of 1, 2 or 4 bytes by the size `hint` (0, 1 or 2) of a
+     * property header.
+     */
+    fn hinted(&mut self, hint: u32) -> std::result::Result<usize, String> {
+        Ok(match hint {
+            0 => self.u8()? as usize,
+            1 => u16::from_le_bytes(self.array()?) as usize,
+            _ => self.u32()? as usize,
+        })
+    }
+
+    fn string(&mut self, n: usize) -> std::result::Result<String, String> {
+        Ok(String::from_utf8_lossy(self.take(n)?).into_owned())
+    }
+}
+
+/**
+ * The value of `key` in Alembic metadata ("key=value;key=value").
+ */
+fn metadata_value<'a>(metadata: &'a str, key: &str) -> Option<&'a str> {
+    metadata
+        .split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value)
+}
+
+/**
+ * When the samples of a property are: every `per_cycle` seconds from the
+ * first time (uniform), cycling through the `times` within each cycle
+ * (cyclic), or at the `times` (acyclic).
+ */
+#[derive(Clone, Debug, PartialEq)]
+struct TimeSampling {
+    per_cycle: Float,
+    times: Vec<Float>,
+}
+
+impl TimeSampling {
+    fn time(&self, index: usize) -> Float {
+        let n = self.times.len().max(1);
+        let start = self.times.get(index % n).copied().unwrap_or(0.0);
+        if self.per_cycle >= ACYCLIC {
+            self.times.get(index).copied().unwrap_or(start)
+        } else {
+            start + (index / n) as Float * self.per_cycle
+        }
+    }
+
+    /**
+     * The samples of `count` around `time`, and how far it is from the
+     * first to the second (clamped to the first and last samples).
+     */
+    fn around(&self, time: Float, count: usize) -> (usize, usize, Float) {
+        if count <= 1 || time <= self.time(0) {
+            return (0, 0, 0.0);
+        }
+        let last = count - 1;
+        if time >= self.time(last) {
+            return (last, last, 0.0);
+        }
+        // time(low) <= time < time(high)
+        let (mut low, mut high) = (0, last);
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            if self.time(middle) <= time {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        let (t0, t1) = (self.time(low), self.time(high));
+        (low, high, (time - t0) / (t1 - t0))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    Compound,
+    Scalar,
+    Array,
+}
+
+/**
+ * A property of an object, as its header tells.
+ */
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    kind: Kind,
+    /**
+     * The plain old data type of its values (see `pod_size`).
+     */
+    pod: u32,
+    samples: usize,
+    first_changed: usize,
+    last_changed: usize,
+    time_sampling: usize,
+    node: Node,
+}
+
+impl Property {
+    /**
+     * The stored sample of the sample `index`, those before the first
+     * change and after the last being the same.
+     */
+    fn stored(&self, index: usize) -> usize {
+        let (first, last) = (self.first_changed, self.last_changed);
+        if index < first || (first == 0 && last == 0) {
+            0
+        } else if index >= last {
+            last - first + 1
+        } else {
+            index - first + 1
+        }
+    }
+}
+
+/**
+ * Bytes of a value of the plain old data type `pod` (booleans, 8 to 64
+ * bit integers, 16 to 64 bit floats), None for strings.
+ */
+fn pod_size(pod: u32) -> Option<usize> {
+    match pod {
+        0..=2 => Some(1),
+        3 | 4 | 9 => Some(2),
+        5 | 6 | 10 => Some(4),
+        7 | 8 | 11 => Some(8),
+        _ => None,
+    }
+}
+
+/**
+ * An object of the archive, its `name` a path from the top object.
+ */
+#[derive(Clone, Debug)]
+struct Object {
+    name: String,
+    metadata: String,
+    properties: Node,
+    children: Vec<Object>,
+}
+
+/**
+ * A mesh of an archive at a time, placed, in the units of the archive.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedMesh {
+    /**
+     * Path of the object of the mesh from the top of the archive.
+     */
+    pub name: String,
+    pub data: MeshData,
+}
+
+/**
+ * An Alembic archive, its objects read but not their samples.
+ */
+pub struct Archive {
+    bytes: Vec<u8>,
+    samplings: Vec<TimeSampling>,
+    metadata: Vec<String>,
+    top: Object,
+}
+
+impl Archive {
+    /**
+     * Read the archive of an Ogawa file.
+     */
+    pub fn parse(bytes: Vec<u8>) -> std::result::Result<Archive, String> {
+        if bytes.len() < 16 || &bytes[..5] != b"Ogawa" {
+            return Err("not an Alembic (Ogawa) file".to_string());
+        }
+        if bytes[5] != 0xff {
+            return Err("the file was not finished".to_string());
+        }
+        let mut archive = Archive {
+            bytes,
+            samplings: vec![],
+            metadata: vec![],
+            top: Object {
+                name: String::new(),
+                metadata: String::new(),
+                properties: Node::Group(0),
+                children: vec![],
+            },
+        };
+        let root = Reader::new(&archive.bytes[8..16]).u64()?;
+        let root = archive.children(Node::Group(root as usize))?;
+        if root.len() < 5 {
+            return Err("archive of missing parts".to_string());
+        }
+
+        let mut samplings = vec![];
+        let mut reader = Reader::new(archive.data(root[4])?);
+        while reader.remaining() > 0 {
+            // The largest sample, unused.
+            reader.u32()?;
+            let per_cycle = reader.f64()? as Float;
+            let count = reader.u32()? as usize;
+            let times = (0..count)
+                .map(|_| reader.f64().map(|t| t as Float))
+                .collect::<std::result::Result<_, _>>()?;
+            samplings.push(TimeSampling { per_cycle, times });
+        }
+        archive.samplings = samplings;
+
+        let mut metadata = vec![String::new()];
+        if let Some(&node) = root.get(5) {
+            let mut reader = Reader::new(archive.data(node)?);
+            while reader.remaining() > 0 {
+                let size = reader.u8()? as usize;
+                metadata.push(reader.string(size)?);
+            }
+        }
+        archive.metadata = metadata;
+
+        let metadata = String::from_utf8_lossy(archive.data(root[3])?);
+        archive.top =
+            archive.object(root[2], String::new(), metadata.into_owned(), 0)?;
+        Ok(archive)
+    }
+
+    fn u64_at(&self, pos: usize) -> std::result::Result<u64, String> {
+        match self.bytes.get(pos..pos + 8) {
+            Some(bytes) => Ok(Reader::new(bytes).u64()?),
+            None => Err(format!("offset {} out of the file", pos)),
+        }
+    }
+
+    fn children(&self, node: Node) -> std::result::Result<Vec<Node>, String> {
+        let pos = match node {
+            Node::Group(0) => return Ok(vec![]),
+            Node::Group(pos) => pos,
+            Node::Data(_) => return Err("data where a group was".to_string()),
+        };
+        let count = self.u64_at(pos)? as usize;
+        if count > (self.bytes.len() - pos) / 8 {
+            return Err(format!("group of {} children", count));
+        }
+        (0..count)
+            .map(|i| {
+                let offset = self.u64_at(pos + 8 * (i + 1))?;
+                let at = (offset & !(1 << 63)) as usize;
+                Ok(if offset >> 63 == 1 {
+                    Node::Data(at)
+                } else {
+                    Node::Group(at)
+                })
+            })
+            .collect()
+    }
+
+    fn data(&self, node: Node) -> std::result::Result<&[u8], String> {
+        let pos = match node {
+            Node::Data(0) => return Ok(&[]),
+            Node::Data(pos) => pos,
+            Node::Group(_) => return Err("a group where data was".to_string()),
+        };
+        let size = self.u64_at(pos)? as usize;
+        self.bytes
+            .get(pos + 8..(pos + 8).saturating_add(size))
+            .ok_or_else(|| format!("data of {} bytes out of the file", size))
+    }
+
+    fn indexed_metadata(
+        &self,
+        index: usize,
+    ) -> std::result::Result<String, String> {
+        self.metadata
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("no metadata {}", index))
+    }
+
+    /**
+     * The object of the group `node` and its children, `depth` below the
+     * top one.
+     */
+    fn object(
+        &self,
+        node: Node,
+        name: String,
+        metadata: String,
+        depth: usize,
+    ) -> std::result::Result<Object, String> {
+        if depth > 256 {
+            return Err("objects nested too deep".to_string());
+        }
+        let nodes = self.children(node)?;
+        let mut object = Object {
+            name,
+            metadata,
+            properties: nodes.first().copied().unwrap_or(Node::Group(0)),
+            children: vec![],
+        };
+        let headers = match nodes.last() {
+            Some(&last @ Node::Data(_)) if nodes.len() > 1 => last,
+            _ => return Ok(object),
+        };
+        // The headers of the children, then two hashes of 16 bytes.
+        let data = self.data(headers)?;
+        let mut reader = Reader::new(&data[..data.len().saturating_sub(32)]);
+        let mut index = 1;
+        while reader.remaining() > 0 {
+            let size = reader.u32()? as usize;
+            let child = reader.string(size)?;
+            let metadata = match reader.u8()? {
+                0xff => {
+                    let size = reader.u32()? as usize;
+                    reader.string(size)?
+                }
+                i => self.indexed_metadata(i as usize)?,
+            };
+            let node = match nodes.get(index) {
+                Some(&node @ Node::Group(_)) if index < nodes.len() - 1 => node,
+                _ => return Err(format!("object {} without its group", child)),
+            };
+            let path = format!("{}/{}", object.name, child);
+            object.children.push(self.object(
+                node,
+                path,
+                metadata,
+                depth + 1,
+            )?);
+            index += 1;
+        }
+        Ok(object)
+    }
+
+    /**
+     * The properties of the compound `node`.
+     */
+    fn properties(
+        &self,
+        node: Node,
+    ) -> std::result::Result<Vec<Property>, String> {
+        let nodes = self.children(node)?;
+        let headers = match nodes.last() {
+            Some(&last @ Node::Data(_)) => last,
+            _ => return Ok(vec![]),
+        };
+        let mut reader = Reader::new(self.data(headers)?);
+        let mut properties = vec![];
+        while reader.remaining() > 0 {
+            let info = reader.u32()?;
+            let hint = (info >> 2) & 0x3;
+            let kind = match info & 0x3 {
+                0 => Kind::Compound,
+                1 => Kind::Scalar,
+                // 3 for arrays of one value per sample.
+                _ => Kind::Array,
+            };
+            let mut property = Property {
+                name: String::new(),
+                kind,
+                pod: (info >> 4) & 0xf,
+                samples: 0,
+                first_changed: 0,
+                last_changed: 0,
+                time_sampling: 0,
+                node: nodes
+                    .get(properties.len())
+                    .copied()
+                    .unwrap_or(Node::Group(0)),
+            };
+            if kind != Kind::Compound {
+                property.samples = reader.hinted(hint)?;
+                if info & 0x200 != 0 {
+                    property.first_changed = reader.hinted(hint)?;
+                    property.last_changed = reader.hinted(hint)?;
+                } else if info & 0x800 == 0 {
+                    property.first_changed = 1;
+                    property.last_changed = property.samples.saturating_sub(1);
+                }
+                if info & 0x100 != 0 {
+                    property.time_sampling = reader.hinted(hint)?;
+                }
+            }
+            let size = reader.hinted(hint)?;
+            property.name = reader.string(size)?;
+            if (info >> 20) & 0xff == 0xff {
+                // The metadata of the property, unused.
+                let size = reader.hinted(hint)?;
+                reader.take(size)?;
+            }
+            properties.push(property);
+        }
+        Ok(properties)
+    }
+
+    fn sampling(
+        &self,
+        property: &Property,
+    ) -> std::result::Result<&TimeSampling, String> {
+        self.samplings.get(property.time_sampling).ok_or_else(|| {
+            format!("{} of a missing time sampling", property.name)
+        })
+    }
+
+    /**
+     * The values of the sample `index` of a scalar or array property, as
+     * numbers.
+     */
+    fn values(
+        &self,
+        property: &Property,
+        index: usize,
+    ) -> std::result::Result<Vec<Float>, String> {
+        let nodes = self.children(property.node)?;
+        let stored = property.stored(index);
+        let node = match property.kind {
+            Kind::Scalar => nodes.get(stored),
+            Kind::Array => nodes.get(2 * stored),
+            Kind::Compound => None,
+        };
+        let node = node.ok_or_else(|| {
+            format!("sample {} of {} missing", index, property.name)
+        })?;
+        let data = self.data(*node)?;
+        // After the hash of the sample.
+        let data = data.get(16..).unwrap_or(&[]);
+        let size = pod_size(property.pod).ok_or_else(|| {
+            format!("{} of strings, not numbers", property.name)
+        })?;
+        let eight = |v: &[u8]| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(v);
+            bytes
+        };
+        Ok(data
+            .chunks_exact(size)
+            .map(|v| match property.pod {
+                0 | 1 => v[0] as Float,
+                2 => v[0] as i8 as Float,
+                3 => u16::from_le_bytes([v[0], v[1]]) as Float,
+                4 => i16::from_le_bytes([v[0], v[1]]) as Float,
+                5 => u32::from_le_bytes([v[0], v[1], v[2], v[3]]) as Float,
+                6 => i32::from_le_bytes([v[0], v[1], v[2], v[3]]) as Float,
+                7 => u64::from_le_bytes(eight(v)) as Float,
+                8 => i64::from_le_bytes(eight(v)) as Float,
+                9 => half(u16::from_le_bytes([v[0], v[1]])),
+                10 => f32::from_le_bytes([v[0], v[1], v[2], v[3]]) as Float,
+                _ => f64::from_le_bytes(eight(v)) as Float,
+            })
+            .collect())
+    }
+
+    /**
+     * The values of `property` at `time`: interpolated between the
+     * samples around it if `blend`, those of the sample before it
+     * otherwise (or if the samples differ in length).
+     */
+    fn values_at(
+        &self,
+        property: &Property,
+        time: Float,
+        blend: bool,
+    ) -> std::result::Result<Vec<Float>, String> {
+        let sampling = self.sampling(property)?;
+        let (a, b, f) = sampling.around(time, property.samples);
+        let first = self.values(property, a)?;
+        if !blend || a == b || f <= 0.0 {
+            return Ok(first);
+        }
+        let second = self.values(property, b)?;
+        if second.len() != first.len() {
+            return Ok(first);
+        }
+        Ok(first
+            .iter()
+            .zip(&second)
+            .map(|(x, y)| x + f * (y - x))
+            .collect())
+    }
+
+    /**
+     * The transform of the Xform object of `properties` at `time`, and
+     * whether it is placed by those of its parents.
+     */
+    fn transform(
+        &self,
+        properties: &[Property],
+        time: Float,
+    ) -> std::result::Result<(Matrix, bool), String> {
+        let xform = match properties.iter().find(|p| p.name == ".xform") {
+            Some(xform) => self.properties(xform.node)?,
+            None => return Ok((IDENTITY, true)),
+        };
+        let find = |name: &str| xform.iter().find(|p| p.name == name);
+        let inherits = match find(".inherits") {
+            Some(p) => self.values_at(p, time, false)?.first() != Some(&0.0),
+            None => true,
+        };
+        let (ops, values) = match (find(".ops"), find(".vals")) {
+            (Some(ops), Some(values)) => (
+                self.values_at(ops, time, false)?,
+                self.values_at(values, time, true)?,
+            ),
+            _ => return Ok((IDENTITY, inherits)),
+        };
+
+        let mut matrix = IDENTITY;
+        let mut channels = values.iter().copied();
+        let mut next = || {
+            channels
+                .next()
+                .ok_or_else(|| "transform of missing values".to_string())
+        };
+        for op in ops {
+            let op = match op as u32 >> 4 {
+                0 => scaling([next()?, next()?, next()?]),
+                1 => translation([next()?, next()?, next()?]),
+                2 => rotation([next()?, next()?, next()?], next()?),
+                3 => {
+                    // Row by row, for row vectors: transposed.
+                    let mut m = [[0.0; 4]; 4];
+                    for row in 0..4 {
+                        for column in m.iter_mut() {
+                            column[row] = next()?;
+                        }
+                    }
+                    m
+                }
+                4 => rotation([1.0, 0.0, 0.0], next()?),
+                5 => rotation([0.0, 1.0, 0.0], next()?),
+                6 => rotation([0.0, 0.0, 1.0], next()?),
+                other => return Err(format!("unknown transform {}", other)),
+            };
+            // The first operation is applied last.
+            matrix = multiply(&matrix, &op);
+        }
+        Ok((matrix, inherits))
+    }
+
+    /**
+     * The mesh of the PolyMesh or SubD object of `properties` at `time`.
+     */
+    fn mesh(
+        &self,
+        properties: &[Property],
+        time: Float,
+    ) -> std::result::Result<MeshData, String> {
+        let geometry = match properties.iter().find(|p| p.name == ".geom") {
+            Some(geometry) => self.properties(geometry.node)?,
+            None => return Ok(MeshData::default()),
+        };
+        let find = |name: &str| {
+            geometry
+                .iter()
+                .find(|p| p.name == name)
+                .ok_or_else(|| format!("mesh without {}", name))
+        };
+        let counts = self.values_at(find(".faceCounts")?, time, false)?;
+        let indices = self.values_at(find(".faceIndices")?, time, false)?;
+        let positions = self.values_at(find("P")?, time, true)?;
+        let positions: Vec<[Float; 3]> = positions
+            .chunks_exact(3)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect();
+
+        // Texture coordinates by corner or by position, indexed or not.
+        let (uvs, uv_indices) = match geometry.iter().find(|p| p.name == "uv") {
+            Some(uv) if uv.kind == Kind::Compound => {
+                let parts = self.properties(uv.node)?;
+                let part = |name: &str| parts.iter().find(|p| p.name == name);
+                match (part(".vals"), part(".indices")) {
+                    (Some(values), Some(indices)) => (
+                        self.values_at(values, time, false)?,
+                        Some(self.values_at(indices, time, false)?),
+                    ),
+                    _ => (vec![], None),
+                }
+            }
+            Some(uv) => (self.values_at(uv, time, false)?, None),
+            None => (vec![], None),
+        };
+        let integers =
+            |values: Vec<Float>| values.iter().map(|&v| v as usize).collect();
+        let indices: Vec<usize> = integers(indices);
+        // Face varying if there are as many as corners.
+        let by_corner = match &uv_indices {
+            Some(uv_indices) => uv_indices.len() == indices.len(),
+            None => uvs.len() / 2 == indices.len(),
+        };
+        let uvs = CornerUvs {
+            values: uvs.chunks_exact(2).map(|uv| [uv[0], uv[1]]).collect(),
+            indices: uv_indices.map(integers),
+            by_corner,
+        };
+        MeshData::polygons(&positions, &integers(counts), &indices, &uvs, true)
+    }
+
+    /**
+     * The meshes of `object` and of its children at `time`, placed by
+     * `parent` (the transform of the objects above it).
+     */
+    fn collect(
+        &self,
+        object: &Object,
+        parent: &Matrix,
+        time: Float,
+        meshes: &mut Vec<CachedMesh>,
+    ) -> std::result::Result<(), String> {
+        let at = |e: String| format!("{}: {}", object.name, e);
+        let schema = metadata_value(&object.metadata, "schema").unwrap_or("");
+        let mut placement = *parent;
+        if schema.starts_with("AbcGeom_Xform") {
+            let properties = self.properties(object.properties).map_err(at)?;
+            let (local, inherits) =
+                self.transform(&properties, time).map_err(at)?;
+            placement = if inherits {
+                multiply(parent, &local)
+            } else {
+                local
+            };
+        } else if schema.starts_with("AbcGeom_PolyMesh")
+            || schema.starts_with("AbcGeom_SubD")
+        {
+            let properties = self.properties(object.properties).map_err(at)?;
+            let mut data = self.mesh(&properties, time).map_err(at)?;
+            for position in &mut data.positions {
+                *position = apply(&placement, position);
+            }
+            meshes.push(CachedMesh {
+                name: object.name.clone(),
+                data,
+            });
+        }
+        for child in &object.children {
+            self.collect(child, &placement, time, meshes)?;
+        }
+        Ok(())
+    }
+
+    /**
+     * The meshes of the archive at `time` (seconds), placed by the
+     * transforms above them.
+     */
+    pub fn meshes(
+        &self,
+        time: Float,
+    ) -> std::result::Result<Vec<CachedMesh>, String> {
+        let mut meshes = vec![];
+        self.collect(&self.top, &IDENTITY, time, &mut meshes)?;
+        Ok(meshes)
+    }
+}
+
+/**
+ * A half precision float.
+ */
+fn half(bits: u16) -> Float {
+    let sign = if bits >> 15 == 1 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as Float;
+    sign * match exponent {
+        0 => mantissa * (2.0 as Float).powi(-24),
+        0x1f if mantissa == 0.0 => Float::INFINITY,
+        0x1f => Float::NAN,
+        e => (1.0 + mantissa / 1024.0) * (2.0 as Float).powi(e - 15),
+    }
+}
+
+pub(crate) fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+pub(crate) fn apply(m: &Matrix, p: &[Float; 3]) -> [Float; 3] {
+    [0, 1, 2]
+        .map(|i| m[i][0] * p[0] + m[i][1] * p[1] + m[i][2] * p[2] + m[i][3])
+}
+
+pub(crate) fn scaling(s: [Float; 3]) -> Matrix {
+    let mut m = IDENTITY;
+    for (i, factor) in s.iter().enumerate() {
+        m[i][i] = *factor;
+    }
+    m
+}
+
+pub(crate) fn translation(t: [Float; 3]) -> Matrix {
+    let mut m = IDENTITY;
+    for (i, offset) in t.iter().enumerate() {
+        m[i][3] = *offset;
+    }
+    m
+}
+
+/**
+ * Rotation by `degrees` about `axis`, counterclockwise looking down it.
+ */
+pub(crate) fn rotation(axis: [Float; 3], degrees: Float) -> Matrix {
+    let length = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2])
+        .sqrt()
+        .max(Float::MIN_POSITIVE);
+    let [x, y, z] = axis.map(|c| c / length);
+    let (sin, cos) = (degrees * consts::PI / 180.0).sin_cos();
+    let t = 1.0 - cos;
+    [
+        [
+            t * x * x + cos,
+            t * x * y - sin * z,
+            t * x * z + sin * y,
+            0.0,
+        ],
+        [
+            t * x * y + sin * z,
+            t * y * y + cos,
+            t * y * z - sin * x,
+            0.0,
+        ],
+        [
+            t * x * z - sin * y,
+            t * y * z + sin * x,
+            t * z * z + cos,
+            0.0,
+        ],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+/**
+ * Read the archive of an Alembic file.
+ */
+pub fn read_archive(path: &Path) -> Result<Archive> {
+    let bytes = std::fs::read(path).map_err(|e| SaturnoError::io(path, e))?;
+    Archive::parse(bytes).map_err(|reason| SaturnoError::InvalidActor {
+        actor: path.display().to_string(),
+        reason,
+    })
+}
+
+/**
+ * Read the meshes of `cache` at the time of the `scene`, relative to
+ * `base`, sampled over its shutter: those keeping their vertices deform
+ * between the samples (see `Mesh::with_deformation`).
+ */
+#[instrument(skip(scene, cache), fields(path = %cache.path))]
+pub fn read_cache(
+    scene: &SceneDescription,
+    cache: &AlembicDescription,
+    base: &Path,
+) -> Result<Vec<Box<dyn RayTraceable>>> {
+    let invalid = |reason: String| SaturnoError::InvalidActor {
+        actor: cache.path.clone(),
+        reason,
+    };
+    let archive = read_archive(&base.join(&cache.path))?;
+    let library = scene.library().map_err(invalid)?;
+    let material = cache.material.build(&library).map_err(invalid)?;
+    let factor = cache.units.unwrap_or(Units::Meters).factor(scene.units);
+
+    let samples = if scene.shutter > 0.0 {
+        cache.samples.max(2)
+    } else {
+        1
+    };
+    let mut sampled = vec![];
+    for i in 0..samples {
+        let fraction = i as Float / (samples - 1).max(1) as Float;
+        let mut meshes =
+            archive.meshes(scene.time(fraction)).map_err(invalid)?;
+        for mesh in &mut meshes {
+            mesh.data.scale(factor);
+        }
+        sampled.push(meshes);
+    }
+
+    let mut actors: Vec<Box<dyn RayTraceable>> = vec![];
+    let first = sampled.remove(0);
+    for (index, mesh) in first.into_iter().enumerate() {
+        if mesh.data.triangles.is_empty() {
+            continue;
+        }
+        let at = |reason: String| invalid(format!("{}: {}", mesh.name, reason));
+        // The later samples, if the vertices stay the same.
+        let deformation: Option<Vec<Vec<[Float; 3]>>> = sampled
+            .iter()
+            .map(|meshes| {
+                meshes
+                    .get(index)
+                    .filter(|later| later.data.triangles == mesh.data.triangles)
+                    .map(|later| later.data.positions.clone())
+            })
+            .collect();
+        let mut built =
+            mesh.data.clone().build(material.clone()).map_err(at)?;
+        if let Some(deformation) = deformation.filter(|d| !d.is_empty()) {
+            built = built.with_deformation(deformation).map_err(at)?;
+        }
+        actors.push(cache.visibility.apply(Box::new(built)));
+    }
+    Ok(actors)
+}
+
+/**
+ * Read the Alembic caches of `scene`, their paths relative to `base` (the
+ * folder of the scene file).
+ */
+pub fn read_caches(
+    scene: &SceneDescription,
+    base: &Path,
+) -> Result<Vec<Box<dyn RayTraceable>>> {
+    let mut actors = vec![];
+    for cache in &scene.alembic {
+        actors.extend(read_cache(scene, cache, base)?);
+    }
+    Ok(actors)
+}
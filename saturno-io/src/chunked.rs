@@ -0,0 +1,342 @@
+use saturno_math::common::Float;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::actor::Aabb;
+use saturno_scene::geometry_cache::StreamedMesh;
+use saturno_scene::geometry_cache::{ChunkData, ChunkSource, GeometryCache};
+use saturno_scene::material::Scattering;
+use saturno_scene::mesh::Mesh;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::instrument;
+
+// Chunked meshes: a mesh split in chunks of nearby triangles stored one
+// after the other, so a geometry cache reads the chunks rays cross without
+// loading the whole mesh. Meshes are converted once (e.g. from an OBJ
+// file) and then rendered from the chunked file.
+//
+// Layout, little endian: "SMC1" and the number of chunks (u32), then for
+// every chunk its bounds (6 f32, min then max) and its numbers of vertices
+// and triangles (u32), then every chunk: its vertices as their position,
+// normal and texture coordinates (8 f32), and its triangles (3 u32).
+
+const MAGIC: &[u8; 4] = b"SMC1";
+const HEADER_BYTES: u64 = 8;
+const ENTRY_BYTES: u64 = 32;
+const VERTEX_BYTES: u64 = 32;
+const TRIANGLE_BYTES: u64 = 12;
+
+/**
+ * Write a mesh as a chunked mesh file, in chunks of at most
+ * `chunk_triangles` triangles.
+ */
+#[instrument(skip(mesh), fields(path = %path.display()))]
+pub fn write_chunked_mesh(
+    path: &Path,
+    mesh: &Mesh,
+    chunk_triangles: usize,
+) -> Result<()> {
+    if chunk_triangles == 0 {
+        return Err(SaturnoError::InvalidActor {
+            actor: path.display().to_string(),
+            reason: "chunks of no triangles".to_string(),
+        });
+    }
+    File::create(path)
+        .and_then(|file| {
+            encode_chunked_mesh(
+                &mut BufWriter::new(file),
+                mesh,
+                chunk_triangles,
+            )
+        })
+        .map_err(|e| SaturnoError::io(path, e))
+}
+
+/**
+ * Encode a mesh as a chunked mesh into any writer.
+ */
+pub fn encode_chunked_mesh<W: Write>(
+    writer: &mut W,
+    mesh: &Mesh,
+    chunk_triangles: usize,
+) -> std::io::Result<()> {
+    let mut triangles: Vec<usize> = (0..mesh.triangles()).collect();
+    let mut groups = vec![];
+    split(mesh, &mut triangles, chunk_triangles.max(1), &mut groups);
+    let chunks: Vec<ChunkData> =
+        groups.iter().map(|group| chunk(mesh, group)).collect();
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(chunks.len() as u32).to_le_bytes())?;
+    for chunk in &chunks {
+        let bounds = bounds(&chunk.positions);
+        // Float is f32 with the `f32` feature.
+        #[allow(clippy::unnecessary_cast)]
+        for value in bounds.min.iter().chain(&bounds.max) {
+            writer.write_all(&(*value as f32).to_le_bytes())?;
+        }
+        for count in [chunk.positions.len(), chunk.triangles.len()] {
+            writer.write_all(&(count as u32).to_le_bytes())?;
+        }
+    }
+
+    for chunk in &chunks {
+        for vertex in 0..chunk.positions.len() {
+            let values = chunk.positions[vertex]
+                .iter()
+                .chain(&chunk.normals[vertex])
+                .chain(&chunk.uvs[vertex]);
+            #[allow(clippy::unnecessary_cast)]
+            for value in values {
+                writer.write_all(&(*value as f32).to_le_bytes())?;
+            }
+        }
+        for triangle in &chunk.triangles {
+            for vertex in triangle {
+                writer.write_all(&(*vertex as u32).to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/**
+ * Split `triangles` into groups of at most `size`, at the median of their
+ * centroids along the longest axis of their bounds, so the triangles of a
+ * group are close together.
+ */
+fn split(
+    mesh: &Mesh,
+    triangles: &mut [usize],
+    size: usize,
+    groups: &mut Vec<Vec<usize>>,
+) {
+    if triangles.len() <= size {
+        if !triangles.is_empty() {
+            groups.push(triangles.to_vec());
+        }
+        return;
+    }
+
+    let centroid = |triangle: usize| {
+        let vertices = mesh.triangle(triangle).map(|v| mesh.position(v));
+        [0, 1, 2]
+            .map(|axis| vertices.iter().map(|p| p[axis]).sum::<Float>() / 3.0)
+    };
+    let centroids: Vec<[Float; 3]> =
+        triangles.iter().map(|&t| centroid(t)).collect();
+    let extent = bounds(&centroids);
+    let axis = (0..3)
+        .max_by(|&a, &b| {
+            let length = |axis: usize| extent.max[axis] - extent.min[axis];
+            length(a).total_cmp(&length(b))
+        })
+        .unwrap();
+
+    let middle = triangles.len() / 2;
+    triangles.select_nth_unstable_by(middle, |&a, &b| {
+        centroid(a)[axis].total_cmp(&centroid(b)[axis])
+    });
+    let (left, right) = triangles.split_at_mut(middle);
+    split(mesh, left, size, groups);
+    split(mesh, right, size, groups);
+}
+
+/**
+ * The `triangles` of a mesh with their own vertices, rounded to f32 as
+ * they are stored, keeping the normals of the whole mesh.
+ */
+fn chunk(mesh: &Mesh, triangles: &[usize]) -> ChunkData {
+    // Float is f32 with the `f32` feature.
+    #[allow(clippy::unnecessary_cast)]
+    let round = |value: Float| value as f32 as Float;
+    let mut chunk = ChunkData::default();
+    let mut vertices: HashMap<usize, usize> = HashMap::new();
+    for &triangle in triangles {
+        let corners = mesh.triangle(triangle).map(|vertex| {
+            *vertices.entry(vertex).or_insert_with(|| {
+                chunk.positions.push(mesh.position(vertex).map(round));
+                chunk.normals.push(mesh.normal(vertex).map(round));
+                chunk.uvs.push(mesh.uv(vertex).map(round));
+                chunk.positions.len() - 1
+            })
+        });
+        chunk.triangles.push(corners);
+    }
+    chunk
+}
+
+fn bounds(points: &[[Float; 3]]) -> Aabb {
+    points
+        .iter()
+        .fold(Aabb::new(points[0], points[0]), |bounds, point| {
+            bounds.union(&Aabb::new(*point, *point))
+        })
+}
+
+/**
+ * Open a chunked mesh file as a mesh of `material` whose chunks are read
+ * in `cache` when first crossed by a ray.
+ */
+#[instrument(skip(cache, material), fields(path = %path.display()))]
+pub fn read_chunked_mesh(
+    path: &Path,
+    cache: Arc<GeometryCache>,
+    material: Arc<dyn Scattering>,
+) -> Result<StreamedMesh> {
+    let source = ChunkedFile::open(path)?;
+    StreamedMesh::new(cache, Arc::new(source), material).map_err(|reason| {
+        SaturnoError::InvalidActor {
+            actor: path.display().to_string(),
+            reason,
+        }
+    })
+}
+
+struct Entry {
+    bounds: Aabb,
+    vertices: usize,
+    triangles: usize,
+    /**
+     * Position of the first vertex of the chunk.
+     */
+    offset: u64,
+}
+
+/**
+ * The chunks of a chunked mesh file, read on demand.
+ */
+pub struct ChunkedFile {
+    path: PathBuf,
+    entries: Vec<Entry>,
+    file: Mutex<File>,
+}
+
+impl ChunkedFile {
+    /**
+     * Open a chunked mesh file, reading only its table of chunks.
+     */
+    pub fn open(path: &Path) -> Result<ChunkedFile> {
+        let invalid = |reason: String| SaturnoError::InvalidActor {
+            actor: path.display().to_string(),
+            reason,
+        };
+
+        let mut file = File::open(path).map_err(|e| invalid(e.to_string()))?;
+        let mut header = [0u8; HEADER_BYTES as usize];
+        file.read_exact(&mut header)
+            .map_err(|e| invalid(e.to_string()))?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid("not a chunked mesh".to_string()));
+        }
+        let chunks = u32_at(&header, 1) as usize;
+        if chunks == 0 {
+            return Err(invalid("mesh without chunks".to_string()));
+        }
+
+        // The table is checked against the file before it is allocated.
+        let length = file.metadata().map_err(|e| invalid(e.to_string()))?.len();
+        let table_bytes = (chunks as u64)
+            .checked_mul(ENTRY_BYTES)
+            .filter(|&bytes| HEADER_BYTES + bytes <= length)
+            .ok_or_else(|| {
+                invalid(format!("{} bytes for {} chunks", length, chunks))
+            })?;
+        let mut table = vec![0u8; table_bytes as usize];
+        file.read_exact(&mut table)
+            .map_err(|e| invalid(e.to_string()))?;
+        let mut entries = vec![];
+        let mut offset = HEADER_BYTES + table.len() as u64;
+        for entry in table.chunks_exact(ENTRY_BYTES as usize) {
+            let value = |i: usize| f32_at(entry, i);
+            let bounds = Aabb::new(
+                [value(0), value(1), value(2)],
+                [value(3), value(4), value(5)],
+            );
+            let vertices = u32_at(entry, 6) as usize;
+            let triangles = u32_at(entry, 7) as usize;
+            entries.push(Entry {
+                bounds,
+                vertices,
+                triangles,
+                offset,
+            });
+            offset += vertices as u64 * VERTEX_BYTES
+                + triangles as u64 * TRIANGLE_BYTES;
+        }
+        if length != offset {
+            return Err(invalid(format!(
+                "{} bytes, expected {}",
+                length, offset
+            )));
+        }
+
+        Ok(ChunkedFile {
+            path: path.to_path_buf(),
+            entries,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    let b = &bytes[4 * i..4 * i + 4];
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn f32_at(bytes: &[u8], i: usize) -> Float {
+    let b = &bytes[4 * i..4 * i + 4];
+    f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Float
+}
+
+impl ChunkSource for ChunkedFile {
+    fn chunk_bounds(&self) -> Vec<Aabb> {
+        self.entries.iter().map(|entry| entry.bounds).collect()
+    }
+
+    fn chunk_triangles(&self) -> Vec<usize> {
+        self.entries.iter().map(|entry| entry.triangles).collect()
+    }
+
+    fn read_chunk(
+        &self,
+        chunk: usize,
+    ) -> std::result::Result<ChunkData, String> {
+        let entry = &self.entries[chunk];
+        let vertex_bytes = entry.vertices * VERTEX_BYTES as usize;
+        let mut bytes =
+            vec![0u8; vertex_bytes + entry.triangles * TRIANGLE_BYTES as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(entry.offset))
+                .and_then(|_| file.read_exact(&mut bytes))
+                .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        }
+
+        let mut data = ChunkData::default();
+        let (vertices, triangles) = bytes.split_at(vertex_bytes);
+        for vertex in vertices.chunks_exact(VERTEX_BYTES as usize) {
+            let value = |i: usize| f32_at(vertex, i);
+            data.positions.push([value(0), value(1), value(2)]);
+            data.normals.push([value(3), value(4), value(5)]);
+            data.uvs.push([value(6), value(7)]);
+        }
+        for triangle in triangles.chunks_exact(TRIANGLE_BYTES as usize) {
+            let corners = [0, 1, 2].map(|i| u32_at(triangle, i) as usize);
+            if corners.iter().any(|&v| v >= entry.vertices) {
+                return Err(format!(
+                    "{}: triangle of a vertex out of chunk {}",
+                    self.path.display(),
+                    chunk
+                ));
+            }
+            data.triangles.push(corners);
+        }
+        Ok(data)
+    }
+}
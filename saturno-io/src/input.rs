@@ -0,0 +1,69 @@
+use saturno_math::common::Float;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::color::ColorSpace;
+use saturno_scene::texture::Texture;
+use std::path::Path;
+use tracing::instrument;
+
+pub use saturno_scene::color::srgb_to_linear;
+
+/**
+ * Read an 8-bit image (PNG, JPEG, ...) as a texture. The colors are
+ * assumed sRGB encoded and converted to linear, the alpha is kept as is.
+ */
+pub fn read_texture(path: &Path) -> Result<Texture> {
+    read_texture_as(path, ColorSpace::Srgb, ColorSpace::LinearSrgb)
+}
+
+/**
+ * Read an 8-bit image (PNG, JPEG, ...) holding colors in the space
+ * `space` as a texture of colors in the working space `working`, the
+ * alpha kept as is.
+ */
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn read_texture_as(
+    path: &Path,
+    space: ColorSpace,
+    working: ColorSpace,
+) -> Result<Texture> {
+    let image = image::open(path).map_err(|e| {
+        SaturnoError::InvalidImage(format!("{}: {}", path.display(), e))
+    })?;
+    texture_of(image, space, working).map_err(|e| {
+        SaturnoError::InvalidImage(format!("{}: {}", path.display(), e))
+    })
+}
+
+/**
+ * Decode an 8-bit image (PNG, JPEG, ...) held in memory, `name` in the
+ * errors, as a texture like `read_texture`.
+ */
+pub fn decode_texture(bytes: &[u8], name: &str) -> Result<Texture> {
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| SaturnoError::InvalidImage(format!("{}: {}", name, e)))?;
+    texture_of(image, ColorSpace::Srgb, ColorSpace::LinearSrgb)
+        .map_err(|e| SaturnoError::InvalidImage(format!("{}: {}", name, e)))
+}
+
+fn texture_of(
+    image: image::DynamicImage,
+    space: ColorSpace,
+    working: ColorSpace,
+) -> std::result::Result<Texture, String> {
+    let image = image.to_rgba();
+    let (width, height) = image.dimensions();
+
+    let texels = image
+        .into_raw()
+        .chunks_exact(4)
+        .map(|rgba| {
+            let channel = |c: u8| c as Float / 255.0;
+            let rgb = [rgba[0], rgba[1], rgba[2]].map(channel);
+            let [r, g, b] = space.convert(rgb, working);
+            [r, g, b, channel(rgba[3])]
+        })
+        .collect();
+
+    Texture::new(width as usize, height as usize, texels)
+}
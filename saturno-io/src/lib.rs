@@ -0,0 +1,18 @@
+// Encoding of the rendered images into files (PNG, Radiance, OpenEXR), and
+// of traced ray paths (OBJ, PLY); decoding of textures, tiled textures read
+// on demand, UDIM texture sets, meshes (OBJ, STL) and caches of them once
+// built, chunked meshes read on demand, animated caches (Alembic) and
+// scenes (USD); canvases of scene descriptions.
+
+pub mod alembic;
+pub mod chunked;
+#[cfg(feature = "png")]
+pub mod input;
+pub mod mesh;
+pub mod mesh_cache;
+pub mod output;
+pub mod paths;
+pub mod scene;
+pub mod tiled;
+pub mod udim;
+pub mod usd;
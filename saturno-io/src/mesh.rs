@@ -0,0 +1,548 @@
+#[cfg(feature = "png")]
+use crate::input::read_texture;
+#[cfg(feature = "png")]
+use crate::input::read_texture_as;
+use crate::mesh_cache::read_cached_mesh;
+use crate::mesh_cache::MeshCache;
+use saturno_math::common::Float;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::actor::RayTraceable;
+#[cfg(feature = "png")]
+use saturno_scene::color::ColorSpace;
+use saturno_scene::description::MeshDescription;
+use saturno_scene::description::SceneDescription;
+use saturno_scene::description::SceneFiles;
+use saturno_scene::material::Scattering;
+use saturno_scene::mesh::Displacement;
+use saturno_scene::mesh::Mesh;
+use saturno_scene::texture::TextureSampler;
+use saturno_scene::units::Units;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::instrument;
+
+// Meshes from OBJ (Wavefront) and STL (stereolithography, binary or ASCII)
+// files, scaled from the units they were modelled in to those of the scene.
+// The files do not tell their units: STL files mostly come from CAD tools
+// working in millimeters, OBJ files from modelling tools working in meters.
+//
+// OBJ faces of more than three vertices are split into fans of triangles,
+// and the vertices sharing a position and texture coordinates are shared
+// by the triangles (for smooth normals). The vertices of STL triangles are
+// not, their facets stay flat.
+
+/**
+ * Vertices and triangles of a mesh read from a file, before it is built.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshData {
+    pub positions: Vec<[Float; 3]>,
+    pub uvs: Vec<[Float; 2]>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl MeshData {
+    /**
+     * Scale the positions by `factor` (see `Units::factor`).
+     */
+    pub fn scale(&mut self, factor: Float) {
+        for position in &mut self.positions {
+            *position = position.map(|c| c * factor);
+        }
+    }
+
+    pub fn build(
+        self,
+        material: Arc<dyn Scattering>,
+    ) -> std::result::Result<Mesh, String> {
+        Mesh::new(self.positions, self.uvs, self.triangles, material)
+    }
+
+    /**
+     * Polygons of `counts` corners, the `indices` of their `positions` in
+     * turn (clockwise if `clockwise`, turned counterclockwise), split into
+     * fans of triangles. The corners sharing a position and texture
+     * coordinates share a vertex.
+     */
+    pub fn polygons(
+        positions: &[[Float; 3]],
+        counts: &[usize],
+        indices: &[usize],
+        uvs: &CornerUvs,
+        clockwise: bool,
+    ) -> std::result::Result<MeshData, String> {
+        let mut mesh = MeshData::default();
+        let mut vertices: HashMap<(usize, Option<usize>), usize> =
+            HashMap::new();
+        let mut corner = 0;
+        for &count in counts {
+            let face = corner..corner + count;
+            if face.end > indices.len() {
+                return Err("faces of missing vertices".to_string());
+            }
+            let corners: Vec<usize> = if clockwise {
+                face.rev().collect()
+            } else {
+                face.collect()
+            };
+            let mut polygon = vec![];
+            for k in corners {
+                let position = indices[k];
+                if position >= positions.len() {
+                    return Err(format!("vertex {} out of the mesh", position));
+                }
+                let uv = uvs.index(k, position);
+                let vertex =
+                    *vertices.entry((position, uv)).or_insert_with(|| {
+                        mesh.positions.push(positions[position]);
+                        mesh.uvs.push(uv.map_or([0.0, 0.0], |i| uvs.values[i]));
+                        mesh.positions.len() - 1
+                    });
+                polygon.push(vertex);
+            }
+            for i in 1..polygon.len().saturating_sub(1) {
+                mesh.triangles
+                    .push([polygon[0], polygon[i], polygon[i + 1]]);
+            }
+            corner += count;
+        }
+        Ok(mesh)
+    }
+}
+
+/**
+ * Texture coordinates of the corners of polygons (see
+ * `MeshData::polygons`): the `values` of each corner (face varying) or of
+ * each position, through `indices` into them if any.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CornerUvs {
+    pub values: Vec<[Float; 2]>,
+    pub indices: Option<Vec<usize>>,
+    pub by_corner: bool,
+}
+
+impl CornerUvs {
+    /**
+     * The value of the corner `corner` of the position `position`, None
+     * if there is none.
+     */
+    pub fn index(&self, corner: usize, position: usize) -> Option<usize> {
+        let at = if self.by_corner { corner } else { position };
+        let index = match &self.indices {
+            Some(indices) => *indices.get(at)?,
+            None => at,
+        };
+        (index < self.values.len()).then_some(index)
+    }
+}
+
+/**
+ * The units the vertices of the file at `path` are usually in:
+ * millimeters for STL, meters otherwise.
+ */
+pub fn default_units(path: &Path) -> Units {
+    match path.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("stl") => Units::Millimeters,
+        _ => Units::Meters,
+    }
+}
+
+/**
+ * The first `n` numbers of `words`.
+ */
+fn numbers(
+    words: &[&str],
+    n: usize,
+) -> std::result::Result<Vec<Float>, String> {
+    if words.len() < n {
+        return Err(format!("expected {} values: {}", n, words.join(" ")));
+    }
+    words[..n]
+        .iter()
+        .map(|w| w.parse().map_err(|_| format!("invalid number {}", w)))
+        .collect()
+}
+
+/**
+ * Index into `count` elements of an OBJ reference: 1-based, or negative to
+ * count back from the last element.
+ */
+fn index(word: &str, count: usize) -> std::result::Result<usize, String> {
+    let i: i64 = word
+        .parse()
+        .map_err(|_| format!("invalid index {}", word))?;
+    let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(format!("index {} out of {}", i, count));
+    }
+    Ok(resolved as usize)
+}
+
+/**
+ * Parse the vertices (v), texture coordinates (vt) and faces (f) of an OBJ
+ * file, ignoring the rest.
+ */
+pub fn parse_obj(text: &str) -> std::result::Result<MeshData, String> {
+    let mut positions: Vec<[Float; 3]> = vec![];
+    let mut uvs: Vec<[Float; 2]> = vec![];
+    let mut mesh = MeshData::default();
+    let mut vertices: HashMap<(usize, Option<usize>), usize> = HashMap::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let at = |e: String| format!("line {}: {}", number + 1, e);
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"v") => {
+                let v = numbers(&words[1..], 3).map_err(at)?;
+                positions.push([v[0], v[1], v[2]]);
+            }
+            Some(&"vt") => {
+                let vt = numbers(&words[1..], 2).map_err(at)?;
+                uvs.push([vt[0], vt[1]]);
+            }
+            Some(&"f") => {
+                let mut face = vec![];
+                for corner in &words[1..] {
+                    let mut refs = corner.split('/');
+                    let v = index(refs.next().unwrap_or(""), positions.len())
+                        .map_err(at)?;
+                    let vt = match refs.next() {
+                        Some(w) if !w.is_empty() => {
+                            Some(index(w, uvs.len()).map_err(at)?)
+                        }
+                        _ => None,
+                    };
+                    let vertex =
+                        *vertices.entry((v, vt)).or_insert_with(|| {
+                            mesh.positions.push(positions[v]);
+                            mesh.uvs.push(vt.map_or([0.0, 0.0], |t| uvs[t]));
+                            mesh.positions.len() - 1
+                        });
+                    face.push(vertex);
+                }
+                if face.len() < 3 {
+                    return Err(at(format!("face of {} vertices", face.len())));
+                }
+                for i in 1..face.len() - 1 {
+                    mesh.triangles.push([face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
+
+/**
+ * Parse the triangles of a binary or ASCII STL file.
+ */
+pub fn parse_stl(bytes: &[u8]) -> std::result::Result<MeshData, String> {
+    let mut mesh = MeshData::default();
+    let mut push = |triangle: [[Float; 3]; 3]| {
+        let first = mesh.positions.len();
+        mesh.positions.extend(triangle);
+        mesh.uvs.extend([[0.0, 0.0]; 3]);
+        mesh.triangles.push([first, first + 1, first + 2]);
+    };
+
+    // A binary file: an 80 byte header, the count of the triangles and 50
+    // bytes per triangle (the normal, the 3 vertices and 2 unused bytes).
+    if bytes.len() >= 84 {
+        let count =
+            u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]);
+        if bytes.len() == 84 + 50 * count as usize {
+            let float = |at: usize| {
+                f32::from_le_bytes([
+                    bytes[at],
+                    bytes[at + 1],
+                    bytes[at + 2],
+                    bytes[at + 3],
+                ]) as Float
+            };
+            for t in 0..count as usize {
+                let start = 84 + 50 * t + 12;
+                push(
+                    [0, 1, 2].map(|v| {
+                        [0, 1, 2].map(|c| float(start + 12 * v + 4 * c))
+                    }),
+                );
+            }
+            return Ok(mesh);
+        }
+    }
+
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| "neither a binary nor an ASCII STL file".to_string())?;
+    if !text.trim_start().starts_with("solid") {
+        return Err("neither a binary nor an ASCII STL file".to_string());
+    }
+    let mut corners = vec![];
+    for line in text.lines() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.first() != Some(&"vertex") {
+            continue;
+        }
+        if words.len() != 4 {
+            return Err(format!("invalid vertex: {}", line.trim()));
+        }
+        let mut vertex = [0.0; 3];
+        for (value, word) in vertex.iter_mut().zip(&words[1..]) {
+            *value = word
+                .parse()
+                .map_err(|_| format!("invalid number {}", word))?;
+        }
+        corners.push(vertex);
+        if corners.len() == 3 {
+            push([corners[0], corners[1], corners[2]]);
+            corners.clear();
+        }
+    }
+    if !corners.is_empty() {
+        return Err("facet of less than 3 vertices".to_string());
+    }
+    Ok(mesh)
+}
+
+/**
+ * Read the vertices and triangles of an OBJ or STL file, in `units` (or
+ * the `default_units` of the file) converted to `scene_units`.
+ */
+pub fn read_data(
+    path: &Path,
+    units: Option<Units>,
+    scene_units: Units,
+) -> Result<MeshData> {
+    let bytes = std::fs::read(path).map_err(|e| SaturnoError::io(path, e))?;
+    let is_stl = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("stl"));
+    let mut data = if is_stl {
+        parse_stl(&bytes)
+    } else {
+        std::str::from_utf8(&bytes)
+            .map_err(|e| e.to_string())
+            .and_then(parse_obj)
+    }
+    .map_err(|reason| SaturnoError::InvalidActor {
+        actor: path.display().to_string(),
+        reason,
+    })?;
+
+    let units = units.unwrap_or_else(|| default_units(path));
+    data.scale(units.factor(scene_units));
+    Ok(data)
+}
+
+/**
+ * Read the mesh of an OBJ or STL file, its vertices in `units` (or the
+ * `default_units` of the file) converted to `scene_units`.
+ */
+#[instrument(skip(material), fields(path = %path.display()))]
+pub fn read_mesh(
+    path: &Path,
+    units: Option<Units>,
+    scene_units: Units,
+    material: Arc<dyn Scattering>,
+) -> Result<Mesh> {
+    read_data(path, units, scene_units)?
+        .build(material)
+        .map_err(|reason| SaturnoError::InvalidActor {
+            actor: path.display().to_string(),
+            reason,
+        })
+}
+
+/**
+ * Read the mesh of `description`, and the files of its deformation if
+ * any, their paths relative to `base`.
+ */
+pub fn read_described_mesh(
+    description: &MeshDescription,
+    base: &Path,
+    scene_units: Units,
+    material: Arc<dyn Scattering>,
+) -> Result<Mesh> {
+    let invalid = |reason: String| SaturnoError::InvalidActor {
+        actor: description.path.clone(),
+        reason,
+    };
+    let units = description.units;
+    let data = read_data(&base.join(&description.path), units, scene_units)?;
+    let mut samples = vec![];
+    for sample in &description.deformation {
+        let deformed = read_data(&base.join(sample), units, scene_units)?;
+        if deformed.triangles != data.triangles {
+            return Err(invalid(format!(
+                "the triangles of {} differ from those of the mesh",
+                sample
+            )));
+        }
+        samples.push(deformed.positions);
+    }
+    let mut mesh = data.build(material).map_err(invalid)?;
+    if !samples.is_empty() {
+        mesh = mesh.with_deformation(samples).map_err(invalid)?;
+    }
+    if let Some(displacement) = &description.displacement {
+        mesh = mesh.displace(&Displacement {
+            texture: read_height_map(&base.join(&displacement.path))?,
+            scale: displacement.scale,
+            subdivisions: displacement.subdivisions,
+        });
+    }
+    if description.compressed {
+        mesh = mesh.compressed().map_err(invalid)?;
+    }
+    Ok(mesh)
+}
+
+/**
+ * The height map of a displacement, its values as they are in the image
+ * (not sRGB colors).
+ */
+#[cfg(feature = "png")]
+fn read_height_map(path: &Path) -> Result<Arc<dyn TextureSampler>> {
+    let linear = ColorSpace::LinearSrgb;
+    Ok(Arc::new(read_texture_as(path, linear, linear)?))
+}
+
+#[cfg(not(feature = "png"))]
+fn read_height_map(path: &Path) -> Result<Arc<dyn TextureSampler>> {
+    Err(SaturnoError::InvalidImage(format!(
+        "reading {} needs the png feature",
+        path.display()
+    )))
+}
+
+/**
+ * The mesh of `description`, from `cache` if any (see `read_cached_mesh`).
+ */
+fn read_maybe_cached(
+    description: &MeshDescription,
+    base: &Path,
+    scene_units: Units,
+    material: Arc<dyn Scattering>,
+    cache: Option<&MeshCache>,
+) -> Result<Mesh> {
+    match cache {
+        Some(cache) => {
+            read_cached_mesh(description, base, scene_units, material, cache)
+        }
+        None => read_described_mesh(description, base, scene_units, material),
+    }
+}
+
+/**
+ * Read the meshes of `scene`, their paths relative to `base` (the folder
+ * of the scene file), from `cache` if any once built.
+ */
+pub fn read_meshes(
+    scene: &SceneDescription,
+    base: &Path,
+    cache: Option<&MeshCache>,
+) -> Result<Vec<Box<dyn RayTraceable>>> {
+    let library =
+        scene
+            .library()
+            .map_err(|reason| SaturnoError::InvalidActor {
+                actor: "materials".to_string(),
+                reason,
+            })?;
+    scene
+        .meshes
+        .iter()
+        .map(|mesh| {
+            let material = mesh.material.build(&library).map_err(|reason| {
+                SaturnoError::InvalidActor {
+                    actor: mesh.path.clone(),
+                    reason,
+                }
+            })?;
+            let actor =
+                read_maybe_cached(mesh, base, scene.units, material, cache)?;
+            Ok(mesh.visibility.apply(Box::new(actor)))
+        })
+        .collect()
+}
+
+/**
+ * The files of a scene, relative to `base` (the folder of the scene file).
+ */
+struct Files<'a> {
+    base: &'a Path,
+    units: Units,
+    cache: Option<&'a MeshCache>,
+}
+
+impl SceneFiles for Files<'_> {
+    fn mesh(
+        &mut self,
+        mesh: &MeshDescription,
+        material: Arc<dyn Scattering>,
+    ) -> std::result::Result<Mesh, String> {
+        read_maybe_cached(mesh, self.base, self.units, material, self.cache)
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(feature = "png")]
+    fn texture(
+        &mut self,
+        path: &str,
+    ) -> std::result::Result<Arc<dyn TextureSampler>, String> {
+        read_texture(&self.base.join(path))
+            .map(|texture| Arc::new(texture) as Arc<dyn TextureSampler>)
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "png"))]
+    fn texture(
+        &mut self,
+        path: &str,
+    ) -> std::result::Result<Arc<dyn TextureSampler>, String> {
+        Err(format!("reading {} needs the png feature", path))
+    }
+}
+
+/**
+ * Read the images of the texture masks of `scene` (see
+ * `SceneDescription::read_textures`), their paths relative to `base`.
+ */
+pub fn read_textures(scene: &mut SceneDescription, base: &Path) -> Result<()> {
+    let mut files = Files {
+        base,
+        units: scene.units,
+        cache: None,
+    };
+    scene.read_textures(&mut files).map_err(|reason| {
+        SaturnoError::InvalidActor {
+            actor: "textures".to_string(),
+            reason,
+        }
+    })
+}
+
+/**
+ * Build the instances of `scene` (see `SceneDescription::instances`), the
+ * files they refer to read relative to `base`, their meshes from `cache`
+ * if any once built.
+ */
+pub fn read_instances(
+    scene: &SceneDescription,
+    base: &Path,
+    cache: Option<&MeshCache>,
+) -> Result<Vec<Box<dyn RayTraceable>>> {
+    let mut files = Files {
+        base,
+        units: scene.units,
+        cache,
+    };
+    scene
+        .instances(&mut files)
+        .map_err(|reason| SaturnoError::InvalidActor {
+            actor: "instances".to_string(),
+            reason,
+        })
+}
@@ -0,0 +1,178 @@
+use crate::mesh::read_described_mesh;
+use saturno_math::common::Float;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::description::MeshDescription;
+use saturno_scene::material::Scattering;
+use saturno_scene::mesh::Mesh;
+use saturno_scene::units::Units;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, instrument, warn};
+
+// Caches of built meshes: a mesh as built (its vertices, normals and
+// hierarchy, after any subdivision or displacement) written to a folder
+// under a hash of what it was built from, so the next renders of the
+// scene read it back instead of parsing and building it again. Changing
+// a file of the mesh changes the hash, and the mesh is built again.
+//
+// Layout: "SMB1" then the mesh as `Mesh::encode` writes it, in a file
+// named after the hash. A file that cannot be read back (damaged, or
+// from another version) is built again and replaced.
+
+const MAGIC: &[u8; 4] = b"SMB1";
+
+/**
+ * 64-bit FNV-1a hash of `parts`, each after its length (so that moving
+ * bytes from a part to the next changes it): the key of a cached mesh.
+ */
+pub fn content_hash(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let lengths: Vec<[u8; 8]> = parts
+        .iter()
+        .map(|part| (part.len() as u64).to_le_bytes())
+        .collect();
+    for (length, part) in lengths.iter().zip(parts) {
+        for &byte in length.iter().chain(part.iter()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/**
+ * A folder of built meshes, by key (see `content_hash`).
+ */
+pub struct MeshCache {
+    directory: PathBuf,
+}
+
+impl MeshCache {
+    /**
+     * The cache in `directory`, created if missing.
+     */
+    pub fn new(directory: &Path) -> Result<MeshCache> {
+        fs::create_dir_all(directory)
+            .map_err(|e| SaturnoError::io(directory, e))?;
+        Ok(MeshCache {
+            directory: directory.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self, key: u64) -> PathBuf {
+        self.directory.join(format!("{:016x}.smb", key))
+    }
+
+    /**
+     * The mesh cached under `key`, of `material`, if any can be read.
+     */
+    pub fn load(
+        &self,
+        key: u64,
+        material: Arc<dyn Scattering>,
+    ) -> Option<Mesh> {
+        let path = self.path(key);
+        let bytes = fs::read(&path).ok()?;
+        let mesh = match bytes.strip_prefix(MAGIC) {
+            Some(encoded) => Mesh::decode(encoded, material),
+            None => Err("not a built mesh".to_string()),
+        };
+        match mesh {
+            Ok(mesh) => Some(mesh),
+            Err(e) => {
+                warn!(path = %path.display(), "ignoring cached mesh: {}", e);
+                None
+            }
+        }
+    }
+
+    /**
+     * Cache `mesh` under `key`. The file is written aside and then moved
+     * in place, so renders reading the cache at the same time never see
+     * it half written.
+     */
+    pub fn store(&self, key: u64, mesh: &Mesh) -> Result<()> {
+        let path = self.path(key);
+        let partial =
+            path.with_extension(format!("{}.tmp", std::process::id()));
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(mesh.encode());
+        fs::write(&partial, &bytes)
+            .and_then(|_| fs::rename(&partial, &path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&partial);
+                SaturnoError::io(&path, e)
+            })
+    }
+
+    /**
+     * The mesh cached under `key`, or else the one `build` gives, cached
+     * for the next time. Failing to cache it only warns.
+     */
+    pub fn get_or_build(
+        &self,
+        key: u64,
+        material: Arc<dyn Scattering>,
+        build: impl FnOnce(Arc<dyn Scattering>) -> Result<Mesh>,
+    ) -> Result<Mesh> {
+        if let Some(mesh) = self.load(key, material.clone()) {
+            debug!(key, "cached mesh");
+            return Ok(mesh);
+        }
+        let mesh = build(material)?;
+        if let Err(e) = self.store(key, &mesh) {
+            warn!("failed to cache the mesh: {}", e);
+        }
+        Ok(mesh)
+    }
+}
+
+/**
+ * The mesh of `description` (see `read_described_mesh`) from `cache`,
+ * keyed by the contents of its files and how it is read, built and cached
+ * if it is not there.
+ */
+#[instrument(skip_all, fields(path = %description.path))]
+pub fn read_cached_mesh(
+    description: &MeshDescription,
+    base: &Path,
+    scene_units: Units,
+    material: Arc<dyn Scattering>,
+    cache: &MeshCache,
+) -> Result<Mesh> {
+    let read = |path: &str| {
+        let path = base.join(path);
+        fs::read(&path).map_err(|e| SaturnoError::io(&path, e))
+    };
+    let mut files = vec![read(&description.path)?];
+    for sample in &description.deformation {
+        files.push(read(sample)?);
+    }
+    if let Some(displacement) = &description.displacement {
+        files.push(read(&displacement.path)?);
+    }
+    // The extension tells the format and the default units.
+    let extension = Path::new(&description.path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let settings = format!(
+        "{:?} {:?} {} {} {} {:?}",
+        description.units,
+        scene_units,
+        extension,
+        description.compressed,
+        std::mem::size_of::<Float>(),
+        description.displacement
+    );
+    // Built again when the layout changes.
+    let mut parts = vec![&MAGIC[..], settings.as_bytes()];
+    parts.extend(files.iter().map(|file| file.as_slice()));
+    let key = content_hash(&parts);
+
+    cache.get_or_build(key, material, |material| {
+        read_described_mesh(description, base, scene_units, material)
+    })
+}
@@ -0,0 +1,536 @@
+use saturno_math::common::Float;
+#[cfg(feature = "exr")]
+use saturno_render::aov::id_to_float;
+#[cfg(feature = "exr")]
+use saturno_render::aov::Cryptomatte;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_render::metadata::RenderMetadata;
+use saturno_render::HdrImage;
+#[cfg(feature = "png")]
+use saturno_render::Image;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::instrument;
+
+/**
+ * Shared exponent encoding (RGBE) used by the Radiance format. The three
+ * channels share the exponent of the largest one, so each pixel takes 4
+ * bytes while keeping a high dynamic range.
+ */
+pub fn encode_rgbe(color: [Float; 3]) -> [u8; 4] {
+    let max = color[0].max(color[1]).max(color[2]);
+
+    if max.is_nan() || max <= 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // Infinite channels saturate at the largest exponent.
+    let max = max.min(Float::MAX);
+    // max = mantissa * 2^exponent, mantissa in [0.5, 1.0)
+    let exponent = (max.log2().floor() as i32 + 1).min(127);
+    let scale = 256.0 / Float::powi(2.0, exponent);
+
+    [
+        (color[0].max(0.0) * scale) as u8,
+        (color[1].max(0.0) * scale) as u8,
+        (color[2].max(0.0) * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+pub fn decode_rgbe(rgbe: [u8; 4]) -> [Float; 3] {
+    if rgbe[3] == 0 {
+        return [0.0, 0.0, 0.0];
+    }
+
+    let scale = Float::powi(2.0, rgbe[3] as i32 - (128 + 8));
+    [
+        (rgbe[0] as Float + 0.5) * scale,
+        (rgbe[1] as Float + 0.5) * scale,
+        (rgbe[2] as Float + 0.5) * scale,
+    ]
+}
+
+/**
+ * Write the 8-bit framebuffer as a PNG. The alpha channel is kept, so
+ * transparent backgrounds survive.
+ */
+#[cfg(feature = "png")]
+pub fn write_png(path: &Path, image: &Image) -> Result<()> {
+    write_png_metadata(path, image, &RenderMetadata::default())
+}
+
+/**
+ * Write the 8-bit framebuffer as a PNG holding `metadata` in text chunks.
+ */
+#[cfg(feature = "png")]
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn write_png_metadata(
+    path: &Path,
+    image: &Image,
+    metadata: &RenderMetadata,
+) -> Result<()> {
+    if image.chan != 4 || image.data.len() != image.size() * 4 {
+        return Err(SaturnoError::InvalidImage(format!(
+            "expected RGBA data for {}x{} pixels",
+            image.width, image.height
+        )));
+    }
+
+    create_file(path)
+        .and_then(|writer| encode_png_metadata(writer, image, metadata))
+        .map_err(|e| SaturnoError::io(path, e))
+}
+
+/**
+ * Encode the 8-bit RGBA framebuffer as a PNG into any writer.
+ */
+#[cfg(feature = "png")]
+#[instrument(level = "debug", skip_all)]
+pub fn encode_png<W: Write>(writer: W, image: &Image) -> std::io::Result<()> {
+    image::png::PNGEncoder::new(writer).encode(
+        &image.data,
+        image.width,
+        image.height,
+        image::RGBA(8),
+    )
+}
+
+/**
+ * Encode the 8-bit RGBA framebuffer as a PNG holding `metadata` into any
+ * writer.
+ */
+#[cfg(feature = "png")]
+pub fn encode_png_metadata<W: Write>(
+    mut writer: W,
+    image: &Image,
+    metadata: &RenderMetadata,
+) -> std::io::Result<()> {
+    let mut png = vec![];
+    encode_png(&mut png, image)?;
+    writer.write_all(&add_png_text(&png, metadata)?)?;
+    writer.flush()
+}
+
+/**
+ * CRC-32 (ISO 3309) of `bytes`, as PNG chunks carry.
+ */
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/**
+ * The chunks of the PNG `png`, as (type, data) pairs.
+ */
+fn png_chunks(png: &[u8]) -> std::io::Result<Vec<([u8; 4], &[u8])>> {
+    let invalid =
+        || std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid PNG");
+    if !png.starts_with(&PNG_SIGNATURE) {
+        return Err(invalid());
+    }
+    let mut chunks = vec![];
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            return Err(invalid());
+        }
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        let end = 12 + length as usize;
+        if rest.len() < end {
+            return Err(invalid());
+        }
+        let kind = [rest[4], rest[5], rest[6], rest[7]];
+        chunks.push((kind, &rest[8..end - 4]));
+        rest = &rest[end..];
+    }
+    Ok(chunks)
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/**
+ * The PNG `png` with a text chunk for every entry of `metadata`, after
+ * its header: tEXt chunks for Latin-1 text, iTXt (UTF-8) chunks else.
+ * Keys are cut to the 79 bytes PNG allows.
+ */
+pub fn add_png_text(
+    png: &[u8],
+    metadata: &RenderMetadata,
+) -> std::io::Result<Vec<u8>> {
+    let chunks = png_chunks(png)?;
+    let mut tagged = PNG_SIGNATURE.to_vec();
+    for (i, (kind, data)) in chunks.iter().enumerate() {
+        write_png_chunk(&mut tagged, kind, data);
+        if i > 0 || kind != b"IHDR" {
+            continue;
+        }
+        for (key, value) in &metadata.entries {
+            let mut data: Vec<u8> = key
+                .chars()
+                .filter(|c| c.is_ascii())
+                .take(79)
+                .map(|c| c as u8)
+                .collect();
+            data.push(0);
+            if value.chars().all(|c| (c as u32) < 256) {
+                data.extend(value.chars().map(|c| c as u8));
+                write_png_chunk(&mut tagged, b"tEXt", &data);
+            } else {
+                // Uncompressed, no language nor translated key.
+                data.extend_from_slice(&[0, 0, 0, 0]);
+                data.extend_from_slice(value.as_bytes());
+                write_png_chunk(&mut tagged, b"iTXt", &data);
+            }
+        }
+    }
+    Ok(tagged)
+}
+
+/**
+ * The text of the tEXt and uncompressed iTXt chunks of the PNG `png`,
+ * as (key, value) pairs.
+ */
+pub fn png_text(png: &[u8]) -> std::io::Result<Vec<(String, String)>> {
+    let mut entries = vec![];
+    for (kind, data) in png_chunks(png)? {
+        let Some(split) = data.iter().position(|b| *b == 0) else {
+            continue;
+        };
+        let key: String = data[..split].iter().map(|b| *b as char).collect();
+        let rest = &data[split + 1..];
+        match &kind {
+            b"tEXt" => {
+                entries.push((key, rest.iter().map(|b| *b as char).collect()))
+            }
+            b"iTXt" if rest.len() >= 2 && rest[0] == 0 => {
+                // Skip the language and translated key.
+                let mut text = &rest[2..];
+                for _ in 0..2 {
+                    let end = text.iter().position(|b| *b == 0);
+                    text = &text[end.map_or(text.len(), |e| e + 1)..];
+                }
+                let value = String::from_utf8_lossy(text).into_owned();
+                entries.push((key, value));
+            }
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/**
+ * Write the framebuffer as a Radiance (.hdr) image. Scanlines are stored
+ * flat (not run-length encoded), top to bottom, which every reader
+ * supports. The alpha channel is dropped.
+ */
+pub fn write_hdr(path: &Path, image: &HdrImage) -> Result<()> {
+    write_hdr_metadata(path, image, &RenderMetadata::default())
+}
+
+/**
+ * Write the framebuffer as a Radiance (.hdr) image holding `metadata` in
+ * its header, a `key=value` line per entry.
+ */
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn write_hdr_metadata(
+    path: &Path,
+    image: &HdrImage,
+    metadata: &RenderMetadata,
+) -> Result<()> {
+    create_file(path)
+        .and_then(|mut writer| {
+            encode_hdr_metadata(&mut writer, image, metadata)
+        })
+        .map_err(|e| SaturnoError::io(path, e))
+}
+
+/**
+ * Encode the framebuffer as a Radiance image into any writer.
+ */
+pub fn encode_hdr<W: Write>(
+    writer: &mut W,
+    image: &HdrImage,
+) -> std::io::Result<()> {
+    encode_hdr_metadata(writer, image, &RenderMetadata::default())
+}
+
+/**
+ * Encode the framebuffer as a Radiance image holding `metadata` into any
+ * writer. Line breaks in the values turn into spaces, the header being
+ * made of lines.
+ */
+#[instrument(level = "debug", skip_all)]
+pub fn encode_hdr_metadata<W: Write>(
+    writer: &mut W,
+    image: &HdrImage,
+    metadata: &RenderMetadata,
+) -> std::io::Result<()> {
+    writeln!(writer, "#?RADIANCE")?;
+    writeln!(writer, "FORMAT=32-bit_rle_rgbe")?;
+    for (key, value) in &metadata.entries {
+        let line = format!("{}={}", key, value).replace(['\n', '\r'], " ");
+        writeln!(writer, "{}", line)?;
+    }
+    writeln!(writer)?;
+    writeln!(writer, "-Y {} +X {}", image.height, image.width)?;
+
+    for i in 0..image.size() {
+        let color = image.get_pixel(i);
+        writer.write_all(&encode_rgbe([color[0], color[1], color[2]]))?;
+    }
+
+    writer.flush()
+}
+
+fn create_file(path: &Path) -> std::io::Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+#[cfg(feature = "exr")]
+fn write_exr_attribute<W: Write>(
+    writer: &mut W,
+    name: &str,
+    kind: &str,
+    value: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(name.as_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(kind.as_bytes())?;
+    writer.write_all(&[0])?;
+    writer.write_all(&(value.len() as i32).to_le_bytes())?;
+    writer.write_all(value)
+}
+
+/**
+ * Write float channels as an OpenEXR image (single part, scanlines, no
+ * compression). Channels are stored sorted by name as the format
+ * requires. Attributes are added to the header as strings.
+ */
+#[cfg(feature = "exr")]
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn write_exr(
+    path: &Path,
+    width: u32,
+    height: u32,
+    channels: &[(&str, Vec<f32>)],
+    attributes: &[(&str, String)],
+) -> Result<()> {
+    let size = width as usize * height as usize;
+    if let Some((name, data)) =
+        channels.iter().find(|(_, data)| data.len() != size)
+    {
+        return Err(SaturnoError::InvalidImage(format!(
+            "channel {} has {} values, expected {}",
+            name,
+            data.len(),
+            size
+        )));
+    }
+
+    create_file(path)
+        .and_then(|mut writer| {
+            encode_exr(&mut writer, width, height, channels, attributes)
+        })
+        .map_err(|e| SaturnoError::io(path, e))
+}
+
+/**
+ * Encode the framebuffer as an RGBA OpenEXR image into any writer.
+ */
+#[cfg(feature = "exr")]
+pub fn encode_exr_rgba<W: Write>(
+    writer: &mut W,
+    image: &HdrImage,
+) -> std::io::Result<()> {
+    let channels: Vec<(&str, Vec<f32>)> = ["R", "G", "B", "A"]
+        .iter()
+        .enumerate()
+        .map(|(c, name)| {
+            // Float is f32 with the `f32` feature.
+            #[allow(clippy::unnecessary_cast)]
+            let values = (0..image.size())
+                .map(|i| image.get_pixel(i)[c] as f32)
+                .collect();
+            (*name, values)
+        })
+        .collect();
+
+    encode_exr(writer, image.width, image.height, &channels, &[])
+}
+
+/**
+ * Encode float channels as an OpenEXR image into any writer. Every
+ * channel must hold width * height values.
+ */
+#[cfg(feature = "exr")]
+#[instrument(level = "debug", skip_all)]
+pub fn encode_exr<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    channels: &[(&str, Vec<f32>)],
+    attributes: &[(&str, String)],
+) -> std::io::Result<()> {
+    let mut sorted: Vec<&(&str, Vec<f32>)> = channels.iter().collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut header: Vec<u8> = vec![];
+
+    let mut chlist: Vec<u8> = vec![];
+    for (name, _) in sorted.iter() {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        // FLOAT pixels, pLinear and reserved bytes, x/y sampling.
+        chlist.extend_from_slice(&2i32.to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]);
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    write_exr_attribute(&mut header, "channels", "chlist", &chlist)?;
+    write_exr_attribute(&mut header, "compression", "compression", &[0])?;
+
+    let mut window: Vec<u8> = vec![];
+    for value in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    write_exr_attribute(&mut header, "dataWindow", "box2i", &window)?;
+    write_exr_attribute(&mut header, "displayWindow", "box2i", &window)?;
+    write_exr_attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    write_exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    )?;
+    write_exr_attribute(
+        &mut header,
+        "screenWindowCenter",
+        "v2f",
+        &[0, 0, 0, 0, 0, 0, 0, 0],
+    )?;
+    write_exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    )?;
+    for (name, value) in attributes.iter() {
+        write_exr_attribute(&mut header, name, "string", value.as_bytes())?;
+    }
+    header.push(0);
+
+    // Version 2, with the long names flag if any name exceeds 31 bytes.
+    let long_names = sorted.iter().any(|(name, _)| name.len() > 31)
+        || attributes.iter().any(|(name, _)| name.len() > 31);
+    let version: u32 = if long_names { 2 | 0x400 } else { 2 };
+
+    writer.write_all(&20000630i32.to_le_bytes())?;
+    writer.write_all(&version.to_le_bytes())?;
+    writer.write_all(&header)?;
+
+    // Offset table, one block per scanline.
+    let block_size = 8 + 4 * width as u64 * sorted.len() as u64;
+    let first_block = 8 + header.len() as u64 + 8 * height as u64;
+    for y in 0..height as u64 {
+        writer.write_all(&(first_block + y * block_size).to_le_bytes())?;
+    }
+
+    for y in 0..height as usize {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&((block_size - 8) as i32).to_le_bytes())?;
+        for (_, data) in sorted.iter() {
+            let row = &data[y * width as usize..(y + 1) * width as usize];
+            for value in row {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/**
+ * Write a Cryptomatte layer as an EXR, with the ranked (ID, coverage)
+ * pairs packed two per RGBA channel set and the manifest in the header.
+ */
+#[cfg(feature = "exr")]
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn write_cryptomatte(path: &Path, cryptomatte: &Cryptomatte) -> Result<()> {
+    let name = cryptomatte.kind.layer_name();
+    let size = cryptomatte.ranks.len();
+    let levels = cryptomatte.depth.div_ceil(2);
+
+    let mut names: Vec<String> = vec![];
+    let mut data: Vec<Vec<f32>> = vec![];
+    for level in 0..levels {
+        for (c, suffix) in ["R", "G", "B", "A"].iter().enumerate() {
+            let rank = 2 * level + c / 2;
+            let values = cryptomatte
+                .ranks
+                .iter()
+                .map(|ranked| match ranked.get(rank) {
+                    Some((id, _)) if c % 2 == 0 => id_to_float(*id),
+                    Some((_, coverage)) => *coverage,
+                    None => 0.0,
+                })
+                .collect::<Vec<f32>>();
+            debug_assert_eq!(values.len(), size);
+
+            names.push(format!("{}{:02}.{}", name, level, suffix));
+            data.push(values);
+        }
+    }
+
+    let channels: Vec<(&str, Vec<f32>)> =
+        names.iter().map(|n| n.as_str()).zip(data).collect();
+
+    let key = cryptomatte.key();
+    let attributes = [
+        (format!("cryptomatte/{}/name", key), name.to_string()),
+        (
+            format!("cryptomatte/{}/hash", key),
+            "MurmurHash3_32".to_string(),
+        ),
+        (
+            format!("cryptomatte/{}/conversion", key),
+            "uint32_to_float32".to_string(),
+        ),
+        (
+            format!("cryptomatte/{}/manifest", key),
+            cryptomatte.manifest_json(),
+        ),
+    ];
+    let attributes: Vec<(&str, String)> = attributes
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.clone()))
+        .collect();
+
+    write_exr(
+        path,
+        cryptomatte.width,
+        cryptomatte.height,
+        &channels,
+        &attributes,
+    )
+}
@@ -0,0 +1,178 @@
+use saturno_math::common::Float;
+use saturno_render::debug::BounceEvent;
+use saturno_render::debug::PixelTrace;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::instrument;
+
+// Export of traced paths (see `Canvas::trace_pixels`) as polylines, to look
+// at them in a 3D viewer next to the scene geometry.
+
+/**
+ * The path of a camera sample as a polyline: from the camera through every
+ * hit point. Escaped rays end `escape_length` along their direction.
+ * `events[i]` is what happened at `points[i + 1]`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct PathLine {
+    pub name: String,
+    pub points: Vec<[Float; 3]>,
+    pub events: Vec<BounceEvent>,
+}
+
+pub fn path_lines(
+    traces: &[PixelTrace],
+    escape_length: Float,
+) -> Vec<PathLine> {
+    let mut lines = vec![];
+    for pixel in traces {
+        for sample in &pixel.samples {
+            let first = match sample.bounces.first() {
+                Some(bounce) => bounce.origin,
+                None => continue,
+            };
+
+            let mut points = vec![first];
+            let mut events = vec![];
+            for bounce in &sample.bounces {
+                let end = match &bounce.hit {
+                    Some(hit) => hit.point,
+                    None => {
+                        let d = bounce.direction;
+                        let norm = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2])
+                            .sqrt()
+                            .max(Float::EPSILON);
+                        let scale = escape_length / norm;
+                        [
+                            bounce.origin[0] + d[0] * scale,
+                            bounce.origin[1] + d[1] * scale,
+                            bounce.origin[2] + d[2] * scale,
+                        ]
+                    }
+                };
+                points.push(end);
+                events.push(bounce.event);
+            }
+
+            lines.push(PathLine {
+                name: format!(
+                    "pixel_{}_{}_sample_{}",
+                    pixel.x, pixel.y, sample.sample
+                ),
+                points,
+                events,
+            });
+        }
+    }
+    lines
+}
+
+/**
+ * Write the paths as a Wavefront OBJ, one object of line elements per
+ * camera sample.
+ */
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn write_paths_obj(path: &Path, lines: &[PathLine]) -> Result<()> {
+    create_file(path)
+        .and_then(|mut writer| encode_paths_obj(&mut writer, lines))
+        .map_err(|e| SaturnoError::io(path, e))
+}
+
+pub fn encode_paths_obj<W: Write>(
+    writer: &mut W,
+    lines: &[PathLine],
+) -> std::io::Result<()> {
+    let mut first = 1;
+    for line in lines {
+        writeln!(writer, "o {}", line.name)?;
+        for p in &line.points {
+            writeln!(writer, "v {} {} {}", p[0], p[1], p[2])?;
+        }
+
+        write!(writer, "l")?;
+        for i in 0..line.points.len() {
+            write!(writer, " {}", first + i)?;
+        }
+        writeln!(writer)?;
+        first += line.points.len();
+    }
+    writer.flush()
+}
+
+/**
+ * Write the paths as an ASCII PLY of vertices and edges. Vertices are
+ * colored by what happened there: green for the camera, white where the
+ * path scattered, yellow where it was absorbed, red at the maximum depth,
+ * gray on shadow catchers, orange where it scattered inside a medium and
+ * blue where it escaped.
+ */
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn write_paths_ply(path: &Path, lines: &[PathLine]) -> Result<()> {
+    create_file(path)
+        .and_then(|mut writer| encode_paths_ply(&mut writer, lines))
+        .map_err(|e| SaturnoError::io(path, e))
+}
+
+pub fn encode_paths_ply<W: Write>(
+    writer: &mut W,
+    lines: &[PathLine],
+) -> std::io::Result<()> {
+    let vertices: usize = lines.iter().map(|line| line.points.len()).sum();
+    let edges: usize = lines.iter().map(|line| line.events.len()).sum();
+
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format ascii 1.0")?;
+    writeln!(writer, "element vertex {}", vertices)?;
+    writeln!(writer, "property float x")?;
+    writeln!(writer, "property float y")?;
+    writeln!(writer, "property float z")?;
+    writeln!(writer, "property uchar red")?;
+    writeln!(writer, "property uchar green")?;
+    writeln!(writer, "property uchar blue")?;
+    writeln!(writer, "element edge {}", edges)?;
+    writeln!(writer, "property int vertex1")?;
+    writeln!(writer, "property int vertex2")?;
+    writeln!(writer, "end_header")?;
+
+    for line in lines {
+        let colors = std::iter::once([0, 255, 0])
+            .chain(line.events.iter().map(|event| event_color(*event)));
+        for (p, c) in line.points.iter().zip(colors) {
+            writeln!(
+                writer,
+                "{} {} {} {} {} {}",
+                p[0], p[1], p[2], c[0], c[1], c[2]
+            )?;
+        }
+    }
+
+    let mut first = 0;
+    for line in lines {
+        for i in 0..line.events.len() {
+            writeln!(writer, "{} {}", first + i, first + i + 1)?;
+        }
+        first += line.points.len();
+    }
+    writer.flush()
+}
+
+fn event_color(event: BounceEvent) -> [u8; 3] {
+    match event {
+        BounceEvent::Scattered => [255, 255, 255],
+        BounceEvent::Absorbed => [255, 255, 0],
+        BounceEvent::MaxDepth => [255, 0, 0],
+        BounceEvent::ShadowCatcher => [128, 128, 128],
+        BounceEvent::Holdout => [255, 0, 255],
+        BounceEvent::Escaped => [0, 128, 255],
+        BounceEvent::Volume => [255, 128, 0],
+        BounceEvent::Captured => [0, 0, 0],
+    }
+}
+
+fn create_file(path: &Path) -> std::io::Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(path)?))
+}
@@ -0,0 +1,68 @@
+use crate::alembic;
+use crate::mesh;
+use crate::mesh_cache::MeshCache;
+use saturno_render::canvas::Canvas;
+use saturno_render::error::SaturnoError;
+use saturno_scene::actor::RayTraceable;
+use saturno_scene::description::NoFiles;
+use saturno_scene::description::SceneDescription;
+use std::path::Path;
+
+// Canvases of scene descriptions, as the command line, the server and the
+// GUI render them: the actors and the lights of the scene, and the media
+// the rays cross between them, seen from its camera, with the actors read
+// from the files it names.
+
+/**
+ * The canvas of `scene` through its camera at `width` by `height` pixels.
+ * `meshes` are added to the actors of the scene, cut by its clipping
+ * planes: those read from files (see `read_files`), if any.
+ */
+pub fn scene_canvas(
+    scene: &SceneDescription,
+    width: u32,
+    height: u32,
+    meshes: Vec<Box<dyn RayTraceable>>,
+) -> Result<Canvas, String> {
+    let mut actors = scene.actors()?;
+    actors.extend(scene.clip(meshes)?);
+    let mut canvas = Canvas::new(actors, scene.camera.build(width, height));
+    canvas.lights = scene.lights()?;
+    canvas.fog = scene.fog.clone();
+    canvas.atmosphere = scene.atmosphere.as_ref().map(|a| a.build());
+    canvas.black_hole = scene.black_hole.clone();
+    canvas.volumes = scene.volumes.clone();
+    Ok(canvas)
+}
+
+/**
+ * The meshes, instances and Alembic caches of `scene` for its canvas, read
+ * from files relative to `base` (the folder of the scene file).
+ */
+pub fn read_files(
+    scene: &SceneDescription,
+    base: &Path,
+    cache: Option<&MeshCache>,
+) -> Result<Vec<Box<dyn RayTraceable>>, SaturnoError> {
+    let mut actors = mesh::read_meshes(scene, base, cache)?;
+    actors.extend(mesh::read_instances(scene, base, cache)?);
+    actors.extend(alembic::read_caches(scene, base)?);
+    Ok(actors)
+}
+
+/**
+ * The instances of `scene` for its canvas when there are no files to read
+ * (a scene sent over the network). Meshes and Alembic caches, of the scene
+ * or of its prototypes, are an error rather than left out of the image.
+ */
+pub fn build_without_files(
+    scene: &SceneDescription,
+) -> Result<Vec<Box<dyn RayTraceable>>, String> {
+    if let Some(mesh) = scene.meshes.first() {
+        return Err(format!("cannot read the mesh {}", mesh.path));
+    }
+    if let Some(cache) = scene.alembic.first() {
+        return Err(format!("cannot read the Alembic cache {}", cache.path));
+    }
+    scene.instances(&mut NoFiles)
+}
@@ -0,0 +1,212 @@
+use saturno_math::common::Float;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::texture::Texture;
+use saturno_scene::texture_cache::{TextureCache, TileSource, TILE_SIZE};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::instrument;
+
+// Tiled textures: the mipmap levels of a texture split in tiles stored one
+// after the other, so a texture cache reads the tiles it needs without
+// decoding the whole image. Textures are converted once (e.g. from a PNG)
+// and then rendered from the tiled file.
+//
+// Layout, little endian: "STX1", the width, height and number of levels
+// (u32), then every level from the full resolution, its tiles in rows from
+// the top, each tile its texels in rows as 4 f32.
+
+const MAGIC: &[u8; 4] = b"STX1";
+const HEADER_BYTES: u64 = 16;
+const TEXEL_BYTES: u64 = 16;
+
+/**
+ * Write a texture as a tiled texture file.
+ */
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn write_tiled_texture(path: &Path, texture: &Texture) -> Result<()> {
+    File::create(path)
+        .and_then(|file| {
+            encode_tiled_texture(&mut BufWriter::new(file), texture)
+        })
+        .map_err(|e| SaturnoError::io(path, e))
+}
+
+/**
+ * Encode a texture as a tiled texture into any writer.
+ */
+pub fn encode_tiled_texture<W: Write>(
+    writer: &mut W,
+    texture: &Texture,
+) -> std::io::Result<()> {
+    writer.write_all(MAGIC)?;
+    for value in [texture.width(), texture.height(), texture.levels()] {
+        writer.write_all(&(value as u32).to_le_bytes())?;
+    }
+
+    for level in 0..texture.levels() {
+        let (width, height) = texture.level_size(level);
+        for tile_y in 0..height.div_ceil(TILE_SIZE) {
+            for tile_x in 0..width.div_ceil(TILE_SIZE) {
+                for texel in texture.tile(level, tile_x, tile_y) {
+                    // Float is f32 with the `f32` feature.
+                    #[allow(clippy::unnecessary_cast)]
+                    for channel in texel {
+                        writer.write_all(&(channel as f32).to_le_bytes())?;
+                    }
+                }
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/**
+ * Open a tiled texture file as a texture whose tiles are read in `cache`
+ * when first looked up.
+ */
+#[instrument(skip(cache), fields(path = %path.display()))]
+pub fn read_tiled_texture(
+    path: &Path,
+    cache: Arc<TextureCache>,
+) -> Result<Texture> {
+    let source = TiledFile::open(path)?;
+    Texture::cached(cache, Arc::new(source)).map_err(|e| {
+        SaturnoError::InvalidImage(format!("{}: {}", path.display(), e))
+    })
+}
+
+/**
+ * The tiles of a tiled texture file, read on demand.
+ */
+pub struct TiledFile {
+    path: PathBuf,
+    sizes: Vec<(usize, usize)>,
+    /**
+     * Position of the first texel of every level.
+     */
+    offsets: Vec<u64>,
+    file: Mutex<File>,
+}
+
+impl TiledFile {
+    /**
+     * Open a tiled texture file, reading only its header.
+     */
+    pub fn open(path: &Path) -> Result<TiledFile> {
+        let invalid = |reason: String| {
+            SaturnoError::InvalidImage(format!(
+                "{}: {}",
+                path.display(),
+                reason
+            ))
+        };
+
+        let mut file = File::open(path).map_err(|e| invalid(e.to_string()))?;
+        let mut header = [0u8; HEADER_BYTES as usize];
+        file.read_exact(&mut header)
+            .map_err(|e| invalid(e.to_string()))?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid("not a tiled texture".to_string()));
+        }
+        let field = |i: usize| {
+            let bytes = [
+                header[4 * i],
+                header[4 * i + 1],
+                header[4 * i + 2],
+                header[4 * i + 3],
+            ];
+            u32::from_le_bytes(bytes) as usize
+        };
+        let (width, height, levels) = (field(1), field(2), field(3));
+        if width == 0 || height == 0 {
+            return Err(invalid(format!(
+                "empty texture ({}x{})",
+                width, height
+            )));
+        }
+
+        // Halving (rounded up) down to 1x1, as textures build their levels.
+        let mut sizes = vec![(width, height)];
+        while sizes.len() < levels {
+            let (width, height) = sizes[sizes.len() - 1];
+            if width == 1 && height == 1 {
+                break;
+            }
+            sizes.push((width.div_ceil(2), height.div_ceil(2)));
+        }
+        if sizes.len() != levels {
+            return Err(invalid(format!(
+                "{} levels for a {}x{} texture",
+                levels, width, height
+            )));
+        }
+
+        let mut offsets = vec![];
+        let mut offset = HEADER_BYTES;
+        for (width, height) in &sizes {
+            offsets.push(offset);
+            offset += (width * height) as u64 * TEXEL_BYTES;
+        }
+        let length = file.metadata().map_err(|e| invalid(e.to_string()))?.len();
+        if length != offset {
+            return Err(invalid(format!(
+                "{} bytes, expected {}",
+                length, offset
+            )));
+        }
+
+        Ok(TiledFile {
+            path: path.to_path_buf(),
+            sizes,
+            offsets,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl TileSource for TiledFile {
+    fn level_sizes(&self) -> Vec<(usize, usize)> {
+        self.sizes.clone()
+    }
+
+    fn read_tile(
+        &self,
+        level: usize,
+        tile_x: usize,
+        tile_y: usize,
+    ) -> std::result::Result<Vec<[Float; 4]>, String> {
+        let (width, height) = self.sizes[level];
+        let tile_width = TILE_SIZE.min(width - tile_x * TILE_SIZE);
+        let tile_height = TILE_SIZE.min(height - tile_y * TILE_SIZE);
+
+        // The rows of tiles above span the whole width, the tiles on the
+        // left of the same row are as high as this one.
+        let texels_before =
+            tile_y * TILE_SIZE * width + tile_x * TILE_SIZE * tile_height;
+        let position = self.offsets[level] + texels_before as u64 * TEXEL_BYTES;
+
+        let mut bytes =
+            vec![0u8; tile_width * tile_height * TEXEL_BYTES as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(position))
+                .and_then(|_| file.read_exact(&mut bytes))
+                .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+        }
+
+        Ok(bytes
+            .chunks_exact(TEXEL_BYTES as usize)
+            .map(|texel| {
+                let channel = |c: usize| {
+                    let b = &texel[4 * c..4 * c + 4];
+                    f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Float
+                };
+                [channel(0), channel(1), channel(2), channel(3)]
+            })
+            .collect())
+    }
+}
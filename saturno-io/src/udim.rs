@@ -0,0 +1,81 @@
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::texture::Texture;
+use saturno_scene::texture::Udim;
+use saturno_scene::texture::UDIM_FIRST;
+use std::path::{Path, PathBuf};
+use tracing::instrument;
+
+// UDIM texture sets on disk: the files of the tiles share a name pattern
+// where `<UDIM>` stands for the 4 digit tile number, e.g.
+// `wood.<UDIM>.png` for `wood.1001.png`, `wood.1002.png`, ...
+
+/**
+ * Placeholder of the tile number in the paths of UDIM sets.
+ */
+pub const UDIM_TOKEN: &str = "<UDIM>";
+
+/**
+ * The tiles of a UDIM set found next to the pattern, by increasing tile
+ * number.
+ */
+pub fn udim_tiles(pattern: &Path) -> Result<Vec<(u32, PathBuf)>> {
+    let invalid = |reason: &str| {
+        SaturnoError::InvalidImage(format!("{}: {}", pattern.display(), reason))
+    };
+
+    let file_pattern = pattern
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| invalid("invalid file name"))?;
+    let (prefix, suffix) = file_pattern
+        .split_once(UDIM_TOKEN)
+        .ok_or_else(|| invalid("no <UDIM> in the file name"))?;
+    let directory = match pattern.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let entries =
+        std::fs::read_dir(directory).map_err(|e| invalid(&e.to_string()))?;
+    let mut tiles = vec![];
+    for entry in entries {
+        let entry = entry.map_err(|e| invalid(&e.to_string()))?;
+        let name = entry.file_name();
+        let number = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(prefix))
+            .and_then(|name| name.strip_suffix(suffix))
+            .filter(|digits| {
+                digits.len() == 4 && digits.bytes().all(|b| b.is_ascii_digit())
+            })
+            .and_then(|digits| digits.parse::<u32>().ok())
+            .filter(|number| *number >= UDIM_FIRST);
+        if let Some(number) = number {
+            tiles.push((number, entry.path()));
+        }
+    }
+    tiles.sort();
+
+    if tiles.is_empty() {
+        return Err(invalid("no tiles found"));
+    }
+    Ok(tiles)
+}
+
+/**
+ * Read every tile of a UDIM set with `read`, e.g. `read_texture` or a
+ * tiled texture read in a cache.
+ */
+#[instrument(skip(read), fields(pattern = %pattern.display()))]
+pub fn read_udim<F>(pattern: &Path, read: F) -> Result<Udim>
+where
+    F: Fn(&Path) -> Result<Texture>,
+{
+    let mut udim = Udim::new();
+    for (number, path) in udim_tiles(pattern)? {
+        udim.insert(number, read(&path)?)
+            .map_err(SaturnoError::InvalidImage)?;
+    }
+    Ok(udim)
+}
@@ -0,0 +1,1586 @@
+use crate::alembic::apply;
+use crate::alembic::multiply;
+use crate::alembic::rotation;
+use crate::alembic::scaling;
+use crate::alembic::translation;
+use crate::alembic::Matrix;
+use crate::alembic::IDENTITY;
+#[cfg(feature = "png")]
+use crate::input::decode_texture;
+#[cfg(feature = "png")]
+use crate::input::read_texture;
+use crate::mesh::CornerUvs;
+use crate::mesh::MeshData;
+use ndarray::arr1;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_render::error::Result;
+use saturno_render::error::SaturnoError;
+use saturno_scene::actor::Aabb;
+use saturno_scene::actor::RayTraceable;
+use saturno_scene::actor::Sphere;
+use saturno_scene::description::CameraDescription;
+use saturno_scene::description::IntensityUnit;
+use saturno_scene::description::LightDescription;
+use saturno_scene::description::MaterialDescription;
+use saturno_scene::description::MeshDescription;
+use saturno_scene::description::SceneFiles;
+use saturno_scene::library::MaterialLibrary;
+use saturno_scene::light::MAX_EFFICACY;
+use saturno_scene::material::Lambertian;
+use saturno_scene::material::Mask;
+use saturno_scene::material::Scattering;
+use saturno_scene::mesh::Mesh;
+use saturno_scene::texture::TextureSampler;
+use saturno_scene::units::Units;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::instrument;
+use tracing::warn;
+
+// Scenes from USD (Universal Scene Description) stages, as exchanged by the
+// tools of studio pipelines: the meshes, spheres and cubes placed by their
+// transforms, their UsdPreviewSurface materials, the cameras and the
+// lights. Text layers (.usda, or .usd holding text) are parsed; usdz
+// packages are zip archives of stored (not compressed) files, the first of
+// them the root layer and the others its textures. Binary crate layers
+// (.usdc) are not read, `usdcat -o scene.usda scene.usdc` converts them to
+// text. Composition arcs (references, payloads, sublayers, variants) are
+// not followed either, `usdcat --flatten` composes the stage into a single
+// layer.
+//
+// Attributes sampled over time are interpolated linearly at the time code
+// asked for (the frame). Stages are measured in `metersPerUnit` (1
+// centimeter by default) with their `upAxis` up, they are scaled to the
+// units of the scene and turned Y up. The transform of a prim applies its
+// `xformOpOrder` operations last to first, then those of its parents.
+//
+// UsdPreviewSurface materials map to the nearest of the renderer: a mix of
+// lambertian and metal by `metallic`, coated by `clearcoat`, glass below
+// `opacity` and a light if `emissiveColor`. A texture (UsdUVTexture) read
+// through the `st` coordinates colors the diffuse; the other inputs
+// connected to textures take their fallback values. Lights keep their
+// luminance (nits, `intensity` times 2 to the `exposure`): spheres become
+// omni lights, distant lights suns, and rectangles and disks emissive
+// meshes.
+
+/**
+ * A value of an attribute or of metadata. Booleans are numbers, tokens,
+ * strings and assets are text, tuples and arrays are lists.
+ */
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Number(Float),
+    Text(String),
+    Path(String),
+    List(Vec<Value>),
+    None,
+}
+
+impl Value {
+    fn number(&self) -> Option<Float> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::List(values) => values.first()?.number(),
+            _ => None,
+        }
+    }
+
+    /**
+     * The numbers of the value, those of nested lists in turn.
+     */
+    fn numbers(&self) -> Vec<Float> {
+        match self {
+            Value::Number(n) => vec![*n],
+            Value::List(values) => {
+                values.iter().flat_map(Value::numbers).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    fn text(&self) -> Option<&str> {
+        match self {
+            Value::Text(text) | Value::Path(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn texts(&self) -> Vec<String> {
+        match self {
+            Value::List(values) => values
+                .iter()
+                .filter_map(|v| v.text().map(str::to_string))
+                .collect(),
+            value => value.text().map(str::to_string).into_iter().collect(),
+        }
+    }
+
+    /**
+     * The value `t` of the way to `other`: the numbers are interpolated
+     * linearly, values of other kinds or shapes held.
+     */
+    fn lerp(&self, other: &Value, t: Float) -> Value {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => {
+                Value::Number(a + (b - a) * t)
+            }
+            (Value::List(a), Value::List(b)) if a.len() == b.len() => {
+                Value::List(
+                    a.iter().zip(b).map(|(a, b)| a.lerp(b, t)).collect(),
+                )
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Attribute {
+    type_name: String,
+    default: Option<Value>,
+    /**
+     * Time samples, by time code.
+     */
+    samples: Vec<(Float, Value)>,
+    /**
+     * Path of the attribute it takes its value from, if connected.
+     */
+    connection: Option<String>,
+    metadata: HashMap<String, Value>,
+}
+
+impl Attribute {
+    /**
+     * The value at the time code `time`, interpolated between its samples
+     * if it has any, its default otherwise (or without a time).
+     */
+    fn value(&self, time: Option<Float>) -> Option<Value> {
+        let time = match (time, &self.default) {
+            (Some(time), _) => time,
+            (None, Some(_)) => return self.default.clone(),
+            (None, None) => self.samples.first()?.0,
+        };
+        let after = self.samples.iter().position(|(t, _)| *t > time);
+        match after {
+            _ if self.samples.is_empty() => self.default.clone(),
+            Some(0) => Some(self.samples[0].1.clone()),
+            None => self.samples.last().map(|(_, value)| value.clone()),
+            Some(i) => {
+                let (t0, v0) = &self.samples[i - 1];
+                let (t1, v1) = &self.samples[i];
+                Some(v0.lerp(v1, (time - t0) / (t1 - t0)))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Prim {
+    /**
+     * def, over or class.
+     */
+    specifier: String,
+    /**
+     * The schema of the prim (Mesh, Xform, Camera...), empty if untyped.
+     */
+    kind: String,
+    name: String,
+    path: String,
+    metadata: HashMap<String, Value>,
+    attributes: HashMap<String, Attribute>,
+    relationships: HashMap<String, Vec<String>>,
+    children: Vec<Prim>,
+}
+
+impl Prim {
+    fn value(&self, name: &str, time: Option<Float>) -> Option<Value> {
+        self.attributes.get(name)?.value(time)
+    }
+
+    fn number(&self, name: &str, time: Option<Float>) -> Option<Float> {
+        self.value(name, time)?.number()
+    }
+
+    fn numbers(&self, name: &str, time: Option<Float>) -> Vec<Float> {
+        self.value(name, time).map_or(vec![], |v| v.numbers())
+    }
+
+    fn token(&self, name: &str, time: Option<Float>) -> Option<String> {
+        self.value(name, time)?.text().map(str::to_string)
+    }
+
+    /**
+     * The number of the input `name` of a light, `inputs:name` or `name`
+     * as the schemas before USD 21.
+     */
+    fn input(&self, name: &str, default: Float, time: Option<Float>) -> Float {
+        self.number(&format!("inputs:{}", name), time)
+            .or_else(|| self.number(name, time))
+            .unwrap_or(default)
+    }
+
+    fn color(&self, name: &str, time: Option<Float>) -> [Float; 3] {
+        let inputs = self.numbers(&format!("inputs:{}", name), time);
+        let values = if inputs.len() < 3 {
+            self.numbers(name, time)
+        } else {
+            inputs
+        };
+        match values[..] {
+            [r, g, b, ..] => [r, g, b],
+            _ => [1.0; 3],
+        }
+    }
+
+    /**
+     * The transform of the prim, the transforms of its `xformOpOrder`
+     * composed, and whether it resets those of its parents.
+     */
+    fn local(
+        &self,
+        time: Option<Float>,
+    ) -> std::result::Result<(Matrix, bool), String> {
+        let order = self
+            .value("xformOpOrder", time)
+            .map_or(vec![], |order| order.texts());
+        let mut local = IDENTITY;
+        let mut reset = false;
+        for op in order {
+            if op == "!resetXformStack!" {
+                local = IDENTITY;
+                reset = true;
+                continue;
+            }
+            let (inverse, name) = match op.strip_prefix("!invert!") {
+                Some(name) => (true, name),
+                None => (false, op.as_str()),
+            };
+            let values = self.numbers(name, time);
+            let kind = name.trim_start_matches("xformOp:");
+            let kind = kind.split(':').next().unwrap_or_default();
+            let vector = |default: Float| match values[..] {
+                [x, y, z, ..] => [x, y, z],
+                [s] => [s; 3],
+                _ => [default; 3],
+            };
+            let angle = values.first().copied().unwrap_or_default();
+            let mut matrix = match kind {
+                "translate" | "translation" => translation(vector(0.0)),
+                "scale" => scaling(vector(1.0)),
+                "rotateX" => rotation([1.0, 0.0, 0.0], angle),
+                "rotateY" => rotation([0.0, 1.0, 0.0], angle),
+                "rotateZ" => rotation([0.0, 0.0, 1.0], angle),
+                "orient" => orientation(&values),
+                "transform" if values.len() == 16 => {
+                    // Rows of row vectors, their translation last.
+                    let mut matrix = IDENTITY;
+                    for (i, row) in matrix.iter_mut().enumerate() {
+                        for (j, value) in row.iter_mut().enumerate() {
+                            *value = values[j * 4 + i];
+                        }
+                    }
+                    matrix
+                }
+                _ if kind.starts_with("rotate") && kind.len() == 9 => {
+                    // The first axis first, then the others.
+                    let angles = vector(0.0);
+                    let mut matrix = IDENTITY;
+                    for (k, axis) in kind[6..].chars().enumerate() {
+                        let axis = match axis {
+                            'X' => [1.0, 0.0, 0.0],
+                            'Y' => [0.0, 1.0, 0.0],
+                            _ => [0.0, 0.0, 1.0],
+                        };
+                        matrix = multiply(&rotation(axis, angles[k]), &matrix);
+                    }
+                    matrix
+                }
+                _ => {
+                    return Err(format!(
+                        "{}: unknown operation {}",
+                        self.path, op
+                    ))
+                }
+            };
+            if inverse {
+                matrix = invert(&matrix).ok_or_else(|| {
+                    format!("{}: {} not invertible", self.path, op)
+                })?;
+            }
+            local = multiply(&local, &matrix);
+        }
+        Ok((local, reset))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Punct(char),
+    Ident(String),
+    Number(Float),
+    Text(String),
+    Path(String),
+    Asset(String),
+    End,
+}
+
+/**
+ * The tokens of a text layer, with their lines.
+ */
+fn tokenize(text: &str) -> std::result::Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    // The text from `i` to `end`, past `end`.
+    let until = |i: &mut usize, line: &mut usize, end: &str| {
+        let end: Vec<char> = end.chars().collect();
+        let start = *i;
+        while *i < chars.len() && !chars[*i..].starts_with(&end) {
+            if chars[*i] == '\n' {
+                *line += 1;
+            }
+            *i += 1;
+        }
+        if *i == chars.len() {
+            return Err(format!(
+                "line {}: unterminated {}",
+                line,
+                end.iter().collect::<String>()
+            ));
+        }
+        let text: String = chars[start..*i].iter().collect();
+        *i += end.len();
+        Ok(text)
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied().unwrap_or_default();
+        let token = match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+                continue;
+            }
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                let triple: String = [c; 3].iter().collect();
+                if chars[i..].starts_with(&[c; 3]) {
+                    i += 3;
+                    Token::Text(until(&mut i, &mut line, &triple)?)
+                } else {
+                    i += 1;
+                    let mut text = String::new();
+                    while i < chars.len() && chars[i] != c {
+                        if chars[i] == '\\' && i + 1 < chars.len() {
+                            i += 1;
+                            text.push(match chars[i] {
+                                'n' => '\n',
+                                't' => '\t',
+                                other => other,
+                            });
+                        } else {
+                            text.push(chars[i]);
+                        }
+                        i += 1;
+                    }
+                    if i == chars.len() {
+                        return Err(format!(
+                            "line {}: unterminated string",
+                            line
+                        ));
+                    }
+                    i += 1;
+                    Token::Text(text)
+                }
+            }
+            '<' => {
+                i += 1;
+                Token::Path(until(&mut i, &mut line, ">")?)
+            }
+            '@' if chars[i..].starts_with(&['@'; 3]) => {
+                i += 3;
+                Token::Asset(until(&mut i, &mut line, "@@@")?)
+            }
+            '@' => {
+                i += 1;
+                Token::Asset(until(&mut i, &mut line, "@")?)
+            }
+            '-' if chars[i + 1..].starts_with(&['i', 'n', 'f']) => {
+                i += 4;
+                Token::Number(Float::NEG_INFINITY)
+            }
+            _ if c.is_ascii_digit()
+                || ((c == '-' || c == '+' || c == '.')
+                    && next.is_ascii_digit())
+                || ((c == '-' || c == '+') && next == '.') =>
+            {
+                let start = i;
+                i += 1;
+                while i < chars.len()
+                    && (chars[i].is_ascii_digit()
+                        || chars[i] == '.'
+                        || chars[i] == 'e'
+                        || chars[i] == 'E'
+                        || ((chars[i] == '-' || chars[i] == '+')
+                            && (chars[i - 1] == 'e' || chars[i - 1] == 'E')))
+                {
+                    i += 1;
+                }
+                let number: String = chars[start..i].iter().collect();
+                Token::Number(number.parse().map_err(|_| {
+                    format!("line {}: invalid number {}", line, number)
+                })?)
+            }
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || "_:.".contains(chars[i]))
+                {
+                    i += 1;
+                }
+                Token::Ident(chars[start..i].iter().collect())
+            }
+            _ => {
+                i += 1;
+                Token::Punct(c)
+            }
+        };
+        tokens.push((token, line));
+    }
+    tokens.push((Token::End, line));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    at: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.at].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.at].0.clone();
+        if token != Token::End {
+            self.at += 1;
+        }
+        token
+    }
+
+    fn error(&self, expected: &str) -> String {
+        let (token, line) = &self.tokens[self.at];
+        format!("line {}: expected {}, found {:?}", line, expected, token)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = *self.peek() == Token::Punct(c);
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, c: char) -> std::result::Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", c)))
+        }
+    }
+
+    fn ident(&mut self) -> std::result::Result<String, String> {
+        match self.peek().clone() {
+            Token::Ident(ident) => {
+                self.at += 1;
+                Ok(ident)
+            }
+            _ => Err(self.error("a name")),
+        }
+    }
+
+    /**
+     * Skip a group in brackets, parentheses or braces and those nested in
+     * it.
+     */
+    fn skip_group(&mut self) -> std::result::Result<(), String> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Token::Punct('(') | Token::Punct('[') | Token::Punct('{') => {
+                    depth += 1
+                }
+                Token::Punct(')') | Token::Punct(']') | Token::Punct('}') => {
+                    depth -= 1
+                }
+                Token::End => return Err(self.error("the end of a group")),
+                _ => {}
+            }
+            if depth <= 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    fn value(&mut self) -> std::result::Result<Value, String> {
+        let value = match self.next() {
+            Token::Number(n) => Value::Number(n),
+            Token::Text(text) => Value::Text(text),
+            Token::Path(path) => Value::Path(path),
+            Token::Ident(ident) => match ident.as_str() {
+                "None" => Value::None,
+                "true" => Value::Number(1.0),
+                "false" => Value::Number(0.0),
+                "inf" => Value::Number(Float::INFINITY),
+                "nan" => Value::Number(Float::NAN),
+                _ => Value::Text(ident),
+            },
+            Token::Asset(asset) => {
+                // The prim of a reference, and its layer offset.
+                if let Token::Path(_) = self.peek() {
+                    self.at += 1;
+                }
+                if *self.peek() == Token::Punct('(') {
+                    self.skip_group()?;
+                }
+                Value::Text(asset)
+            }
+            Token::Punct(open) if open == '(' || open == '[' => {
+                let close = if open == '(' { ')' } else { ']' };
+                let mut values = vec![];
+                while !self.eat(close) {
+                    values.push(self.value()?);
+                    if !self.eat(',') && *self.peek() != Token::Punct(close) {
+                        return Err(self.error(&format!("',' or '{}'", close)));
+                    }
+                }
+                Value::List(values)
+            }
+            Token::Punct('{') => {
+                // Dictionaries are of no use to render.
+                self.at -= 1;
+                self.skip_group()?;
+                Value::None
+            }
+            _ => {
+                self.at -= 1;
+                return Err(self.error("a value"));
+            }
+        };
+        Ok(value)
+    }
+
+    /**
+     * The metadata in parentheses after a layer, prim or property, if
+     * there are any. The edits of lists (prepend, append...) set them.
+     */
+    fn metadata(
+        &mut self,
+    ) -> std::result::Result<HashMap<String, Value>, String> {
+        let mut metadata = HashMap::new();
+        if !self.eat('(') {
+            return Ok(metadata);
+        }
+        while !self.eat(')') {
+            match self.next() {
+                Token::Text(doc) => {
+                    metadata.insert("doc".to_string(), Value::Text(doc));
+                }
+                Token::Punct(';') => {}
+                Token::Ident(mut key) => {
+                    let edits =
+                        ["prepend", "append", "add", "delete", "reorder"];
+                    if edits.contains(&key.as_str()) {
+                        if let Token::Ident(_) = self.peek() {
+                            key = self.ident()?;
+                        }
+                    }
+                    if self.eat('=') {
+                        metadata.insert(key, self.value()?);
+                    }
+                }
+                _ => {
+                    self.at -= 1;
+                    return Err(self.error("metadata"));
+                }
+            }
+        }
+        Ok(metadata)
+    }
+
+    fn prim(&mut self, parent: &str) -> std::result::Result<Prim, String> {
+        let specifier = self.ident()?;
+        let kind = match self.peek() {
+            Token::Ident(_) => self.ident()?,
+            _ => String::new(),
+        };
+        let name = match self.next() {
+            Token::Text(name) => name,
+            _ => {
+                self.at -= 1;
+                return Err(self.error("the name of a prim"));
+            }
+        };
+        let mut prim = Prim {
+            specifier,
+            kind,
+            path: format!("{}/{}", parent, name),
+            name,
+            metadata: self.metadata()?,
+            ..Prim::default()
+        };
+        self.expect('{')?;
+        while !self.eat('}') {
+            match self.peek().clone() {
+                Token::Ident(word)
+                    if ["def", "over", "class"].contains(&word.as_str()) =>
+                {
+                    let child = self.prim(&prim.path)?;
+                    prim.children.push(child);
+                }
+                Token::Ident(word) if word == "variantSet" => {
+                    warn!(prim = %prim.path, "variants are not followed");
+                    self.at += 2;
+                    self.expect('=')?;
+                    self.skip_group()?;
+                }
+                Token::Ident(word) if word == "reorder" => {
+                    self.at += 2;
+                    self.expect('=')?;
+                    self.value()?;
+                }
+                Token::Punct(';') => self.at += 1,
+                Token::Ident(_) => self.property(&mut prim)?,
+                _ => return Err(self.error("a property or a prim")),
+            }
+        }
+        Ok(prim)
+    }
+
+    fn property(&mut self, prim: &mut Prim) -> std::result::Result<(), String> {
+        let qualifiers = [
+            "custom", "uniform", "varying", "config", "prepend", "append",
+            "add", "delete",
+        ];
+        let mut type_name = self.ident()?;
+        while qualifiers.contains(&type_name.as_str()) {
+            type_name = self.ident()?;
+        }
+        if type_name == "rel" {
+            let name = self.ident()?;
+            let targets = if self.eat('=') {
+                self.value()?.texts()
+            } else {
+                vec![]
+            };
+            self.metadata()?;
+            prim.relationships.insert(name, targets);
+            return Ok(());
+        }
+        if self.eat('[') {
+            self.expect(']')?;
+            type_name.push_str("[]");
+        }
+        let name = self.ident()?;
+        let (name, part) = match name.rsplit_once('.') {
+            Some((name, part))
+                if part == "connect" || part == "timeSamples" =>
+            {
+                (name.to_string(), part.to_string())
+            }
+            _ => (name, String::new()),
+        };
+        let attribute = prim.attributes.entry(name).or_default();
+        attribute.type_name = type_name;
+        if self.eat('=') {
+            match part.as_str() {
+                "connect" => {
+                    attribute.connection =
+                        self.value()?.texts().into_iter().next();
+                }
+                "timeSamples" => {
+                    self.expect('{')?;
+                    while !self.eat('}') {
+                        let time = match self.next() {
+                            Token::Number(time) => time,
+                            _ => {
+                                self.at -= 1;
+                                return Err(self.error("a time code"));
+                            }
+                        };
+                        self.expect(':')?;
+                        attribute.samples.push((time, self.value()?));
+                        if !self.eat(',') && *self.peek() != Token::Punct('}') {
+                            return Err(self.error("',' or '}'"));
+                        }
+                    }
+                    attribute
+                        .samples
+                        .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+                }
+                _ => attribute.default = Some(self.value()?),
+            }
+        }
+        let metadata = self.metadata()?;
+        attribute.metadata.extend(metadata);
+        Ok(())
+    }
+}
+
+/**
+ * The layer metadata and the root prims of a text layer.
+ */
+fn parse_layer(
+    text: &str,
+) -> std::result::Result<(HashMap<String, Value>, Vec<Prim>), String> {
+    if !text.starts_with("#usda") {
+        return Err("not a text (usda) layer".to_string());
+    }
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        at: 0,
+    };
+    let metadata = parser.metadata()?;
+    if metadata.contains_key("subLayers") {
+        warn!("sublayers are not followed");
+    }
+    let mut prims = vec![];
+    while *parser.peek() != Token::End {
+        prims.push(parser.prim("")?);
+    }
+    Ok((metadata, prims))
+}
+
+/**
+ * Rotation of the quaternion of real part `w` and imaginary `x, y, z`.
+ */
+fn orientation(values: &[Float]) -> Matrix {
+    let (w, x, y, z) = match values[..] {
+        [w, x, y, z] => (w, x, y, z),
+        _ => return IDENTITY,
+    };
+    let norm = (w * w + x * x + y * y + z * z)
+        .sqrt()
+        .max(Float::MIN_POSITIVE);
+    let (w, x, y, z) = (w / norm, x / norm, y / norm, z / norm);
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+            0.0,
+        ],
+        [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+            0.0,
+        ],
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+            0.0,
+        ],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+/**
+ * The inverse of `m` by Gauss-Jordan elimination, None if it is singular.
+ */
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let mut a = *m;
+    let mut inverse = IDENTITY;
+    for column in 0..4 {
+        let pivot = (column..4).max_by(|&i, &j| {
+            a[i][column].abs().partial_cmp(&a[j][column].abs()).unwrap()
+        })?;
+        if a[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(column, pivot);
+        inverse.swap(column, pivot);
+        let divisor = a[column][column];
+        for j in 0..4 {
+            a[column][j] /= divisor;
+            inverse[column][j] /= divisor;
+        }
+        for row in 0..4 {
+            if row != column {
+                let factor = a[row][column];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[column][j];
+                    inverse[row][j] -= factor * inverse[column][j];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
+
+fn determinant(m: &Matrix) -> Float {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/**
+ * The direction `m` takes `v` to (not moved by translations).
+ */
+fn direction(m: &Matrix, v: [Float; 3]) -> [Float; 3] {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+fn normalize(v: [Float; 3]) -> [Float; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2])
+        .sqrt()
+        .max(Float::MIN_POSITIVE);
+    v.map(|c| c / length)
+}
+
+/**
+ * The renderable contents of a USD stage at a time, in the units of the
+ * scene with Y up.
+ */
+pub struct UsdScene {
+    pub actors: Vec<Box<dyn RayTraceable>>,
+    pub lights: Vec<LightDescription>,
+    /**
+     * The cameras by the names of their prims.
+     */
+    pub cameras: Vec<(String, CameraDescription)>,
+}
+
+impl UsdScene {
+    /**
+     * A camera framing the bounds of the actors from the front and a bit
+     * above, for stages without cameras.
+     */
+    pub fn framing(&self) -> CameraDescription {
+        let bounds = self
+            .actors
+            .iter()
+            .filter_map(|actor| actor.bounding_box())
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or_else(|| Aabb::new([-1.0; 3], [1.0; 3]));
+        let center = [0, 1, 2].map(|i| (bounds.min[i] + bounds.max[i]) / 2.0);
+        let radius = [0, 1, 2]
+            .map(|i| bounds.max[i] - bounds.min[i])
+            .iter()
+            .map(|side| side * side)
+            .sum::<Float>()
+            .sqrt()
+            .max(Float::MIN_POSITIVE)
+            / 2.0;
+        let vertical_fov: Float = 40.0;
+        let distance = radius / (vertical_fov / 2.0).to_radians().sin();
+        CameraDescription {
+            look_from: [
+                center[0],
+                center[1] + distance * 0.3,
+                center[2] + distance,
+            ],
+            look_at: center,
+            up: [0.0, 1.0, 0.0],
+            vertical_fov,
+            aperture: 0.0,
+            shutter: [0.0, 0.0],
+        }
+    }
+}
+
+/**
+ * Builds the prims of a stage into a scene.
+ */
+struct Builder<'a> {
+    prims: HashMap<String, &'a Prim>,
+    time: Option<Float>,
+    scene_units: Units,
+    files: &'a mut dyn SceneFiles,
+    library: MaterialLibrary,
+    scene: UsdScene,
+}
+
+impl<'a> Builder<'a> {
+    fn visit(
+        &mut self,
+        prim: &Prim,
+        parent: &Matrix,
+        binding: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let hidden =
+            prim.token("visibility", self.time).as_deref() == Some("invisible");
+        let purpose = prim.token("purpose", self.time);
+        let helper =
+            matches!(purpose.as_deref(), Some("guide") | Some("proxy"));
+        if prim.specifier == "class" || hidden || helper {
+            return Ok(());
+        }
+        for arc in &["references", "payload", "inherits", "specializes"] {
+            if prim.metadata.contains_key(*arc) {
+                warn!(prim = %prim.path, "{} are not followed", arc);
+            }
+        }
+        let (local, reset) = prim.local(self.time)?;
+        let world = if reset {
+            local
+        } else {
+            multiply(parent, &local)
+        };
+        let binding = prim
+            .relationships
+            .get("material:binding")
+            .and_then(|targets| targets.first())
+            .map(String::as_str)
+            .or(binding);
+
+        let at = |e: String| format!("{}: {}", prim.path, e);
+        match prim.kind.as_str() {
+            "Mesh" => self.mesh(prim, &world, binding).map_err(at)?,
+            "Sphere" => {
+                let radius = prim.number("radius", self.time).unwrap_or(1.0);
+                let material = self.material(prim, binding).map_err(at)?;
+                let center = apply(&world, &[0.0; 3]);
+                self.scene.actors.push(Box::new(Sphere {
+                    center: arr1(&[center[0], center[1], center[2], 1.0]),
+                    radius: radius * determinant(&world).abs().cbrt(),
+                    material,
+                }));
+            }
+            "Cube" => self.cube(prim, &world, binding).map_err(at)?,
+            "Camera" => self.camera(prim, &world),
+            "SphereLight" => self.sphere_light(prim, &world),
+            "DistantLight" => {
+                let t = self.time;
+                self.scene.lights.push(LightDescription::Sun {
+                    direction: normalize(direction(&world, [0.0, 0.0, 1.0])),
+                    illuminance: luminance(prim, t),
+                    angular_diameter: prim.input("angle", 0.53, t),
+                    color: prim.color("color", t),
+                    light_group: None,
+                });
+            }
+            "RectLight" | "DiskLight" => {
+                self.area_light(prim, &world).map_err(at)?
+            }
+            "" | "Xform" | "Scope" | "Material" | "Shader" | "NodeGraph"
+            | "GeomSubset" => {}
+            kind => warn!(prim = %prim.path, "unsupported {} prim", kind),
+        }
+        for child in &prim.children {
+            self.visit(child, &world, binding)?;
+        }
+        Ok(())
+    }
+
+    fn mesh(
+        &mut self,
+        prim: &Prim,
+        world: &Matrix,
+        binding: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let time = self.time;
+        let integers = |name: &str| -> Vec<usize> {
+            prim.numbers(name, time)
+                .iter()
+                .map(|&n| n as usize)
+                .collect()
+        };
+        let positions: Vec<[Float; 3]> = prim
+            .numbers("points", time)
+            .chunks_exact(3)
+            .map(|p| apply(world, &[p[0], p[1], p[2]]))
+            .collect();
+        let indices = integers("faceVertexIndices");
+
+        // The texture coordinates, st by convention.
+        let st = prim.attributes.get_key_value("primvars:st").or_else(|| {
+            prim.attributes.iter().find(|(name, attribute)| {
+                name.starts_with("primvars:")
+                    && attribute.type_name.starts_with("texCoord2")
+            })
+        });
+        let uvs = match st {
+            Some((name, attribute)) => {
+                let uv_indices =
+                    prim.attributes.get(&format!("{}:indices", name));
+                let interpolation = attribute.metadata.get("interpolation");
+                CornerUvs {
+                    values: attribute
+                        .value(time)
+                        .map_or(vec![], |uvs| uvs.numbers())
+                        .chunks_exact(2)
+                        .map(|uv| [uv[0], uv[1]])
+                        .collect(),
+                    indices: uv_indices
+                        .map(|_| integers(&format!("{}:indices", name))),
+                    by_corner: interpolation.and_then(Value::text)
+                        == Some("faceVarying"),
+                }
+            }
+            None => CornerUvs::default(),
+        };
+
+        let left =
+            prim.token("orientation", time).as_deref() == Some("leftHanded");
+        let clockwise = left != (determinant(world) < 0.0);
+        let counts = integers("faceVertexCounts");
+        let data =
+            MeshData::polygons(&positions, &counts, &indices, &uvs, clockwise)?;
+        if data.triangles.is_empty() {
+            return Ok(());
+        }
+        let material = self.material(prim, binding)?;
+        self.scene.actors.push(Box::new(data.build(material)?));
+        Ok(())
+    }
+
+    fn cube(
+        &mut self,
+        prim: &Prim,
+        world: &Matrix,
+        binding: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let half = prim.number("size", self.time).unwrap_or(2.0) / 2.0;
+        let mut positions = vec![];
+        for axis in 0..3 {
+            for side in &[-1.0, 1.0] {
+                // Counterclockwise around the outward normal.
+                let (u, v) = if *side > 0.0 {
+                    ((axis + 1) % 3, (axis + 2) % 3)
+                } else {
+                    ((axis + 2) % 3, (axis + 1) % 3)
+                };
+                for (a, b) in
+                    &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                {
+                    let mut corner = [0.0; 3];
+                    corner[axis] = side * half;
+                    corner[u] = a * half;
+                    corner[v] = b * half;
+                    positions.push(apply(world, &corner));
+                }
+            }
+        }
+        let uvs = CornerUvs {
+            values: vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            indices: Some((0..24).map(|corner| corner % 4).collect()),
+            by_corner: true,
+        };
+        let indices: Vec<usize> = (0..24).collect();
+        let clockwise = determinant(world) < 0.0;
+        let data =
+            MeshData::polygons(&positions, &[4; 6], &indices, &uvs, clockwise)?;
+        let material = self.material(prim, binding)?;
+        self.scene.actors.push(Box::new(data.build(material)?));
+        Ok(())
+    }
+
+    /**
+     * A camera looking down its -Z axis, Y up. The focal length and the
+     * apertures are in tenths of the units of the stage (millimeters of
+     * a stage in centimeters).
+     */
+    fn camera(&mut self, prim: &Prim, world: &Matrix) {
+        let time = self.time;
+        let focal_length = prim.number("focalLength", time).unwrap_or(50.0);
+        let vertical_aperture =
+            prim.number("verticalAperture", time).unwrap_or(15.2908);
+        let f_stop = prim.number("fStop", time).unwrap_or_default();
+        let focus = prim
+            .number("focusDistance", time)
+            .filter(|distance| *distance > 0.0)
+            .unwrap_or(1.0);
+        // The length of a unit of the stage in the scene.
+        let scale = determinant(world).abs().cbrt();
+        let aperture = if f_stop > 0.0 {
+            focal_length / 10.0 / f_stop * scale
+        } else {
+            0.0
+        };
+        let camera = CameraDescription {
+            look_from: apply(world, &[0.0; 3]),
+            look_at: apply(world, &[0.0, 0.0, -focus]),
+            up: normalize(direction(world, [0.0, 1.0, 0.0])),
+            vertical_fov: 2.0
+                * (vertical_aperture / 2.0 / focal_length).atan().to_degrees(),
+            aperture,
+            shutter: [0.0, 0.0],
+        };
+        self.scene.cameras.push((prim.name.clone(), camera));
+    }
+
+    /**
+     * An omni light of the intensity of a sphere of its luminance, or of
+     * its luminance in candela if it is a point.
+     */
+    fn sphere_light(&mut self, prim: &Prim, world: &Matrix) {
+        let time = self.time;
+        let radius =
+            prim.input("radius", 0.5, time) * determinant(world).abs().cbrt();
+        let point =
+            prim.input("treatAsPoint", 0.0, time) > 0.0 || radius <= 0.0;
+        let meters = radius * self.scene_units.meters();
+        let intensity = if point {
+            luminance(prim, time)
+        } else {
+            luminance(prim, time) * consts::PI * meters * meters
+        };
+        self.scene.lights.push(LightDescription::Omni {
+            position: apply(world, &[0.0; 3]),
+            intensity,
+            unit: IntensityUnit::Candela,
+            efficacy: MAX_EFFICACY,
+            radius: if point { 0.0 } else { radius },
+            color: prim.color("color", time),
+            light_group: None,
+        });
+    }
+
+    /**
+     * A rectangle or a disk facing its -Z axis, emitting its luminance.
+     */
+    fn area_light(
+        &mut self,
+        prim: &Prim,
+        world: &Matrix,
+    ) -> std::result::Result<(), String> {
+        let time = self.time;
+        let outline: Vec<[Float; 3]> = if prim.kind == "RectLight" {
+            let w = prim.input("width", 1.0, time) / 2.0;
+            let h = prim.input("height", 1.0, time) / 2.0;
+            vec![[-w, -h, 0.0], [-w, h, 0.0], [w, h, 0.0], [w, -h, 0.0]]
+        } else {
+            let r = prim.input("radius", 0.5, time);
+            (0..32)
+                .map(|k| {
+                    let angle = -2.0 * consts::PI * k as Float / 32.0;
+                    [r * angle.cos(), r * angle.sin(), 0.0]
+                })
+                .collect()
+        };
+        let positions: Vec<[Float; 3]> =
+            outline.iter().map(|p| apply(world, p)).collect();
+        let indices: Vec<usize> = (0..positions.len()).collect();
+        let clockwise = determinant(world) < 0.0;
+        let data = MeshData::polygons(
+            &positions,
+            &[positions.len()],
+            &indices,
+            &CornerUvs::default(),
+            clockwise,
+        )?;
+        let material = MaterialDescription::DiffuseLight {
+            color: prim.color("color", time),
+            intensity: luminance(prim, time),
+            light_group: None,
+        }
+        .build(&self.library)?;
+        self.scene.actors.push(Box::new(data.build(material)?));
+        Ok(())
+    }
+
+    /**
+     * The material bound to `prim`, built once for every prim bound to it,
+     * or its display color if it is not bound to a preview surface.
+     */
+    fn material(
+        &mut self,
+        prim: &Prim,
+        binding: Option<&str>,
+    ) -> std::result::Result<Arc<dyn Scattering>, String> {
+        if let Some(path) = binding {
+            if let Some(material) = self.library.get(path) {
+                return Ok(material);
+            }
+            if let Some(description) = self.preview_surface(path)? {
+                let material = description.build(&self.library)?;
+                return Ok(self.library.insert(path, material));
+            }
+        }
+        let color = match prim.numbers("primvars:displayColor", self.time)[..] {
+            [r, g, b, ..] => [r, g, b],
+            _ => [0.5; 3],
+        };
+        MaterialDescription::Lambertian { albedo: color }.build(&self.library)
+    }
+
+    /**
+     * The values of the input `name` of `shader`, those of the output it
+     * is connected to if it is: the fallback of a texture, or the input of
+     * a material or node graph it is connected to in turn.
+     */
+    fn input(
+        &self,
+        shader: &Prim,
+        name: &str,
+        depth: usize,
+    ) -> Option<Vec<Float>> {
+        let attribute = shader.attributes.get(name)?;
+        let connection = match &attribute.connection {
+            Some(connection) if depth < 16 => connection,
+            _ => return attribute.value(self.time).map(|v| v.numbers()),
+        };
+        let (path, output) = connection.rsplit_once('.')?;
+        let source = self.prims.get(path)?;
+        if source.token("info:id", None).as_deref() != Some("UsdUVTexture") {
+            return self.input(source, output, depth + 1);
+        }
+        let mut fallback = source.numbers("inputs:fallback", self.time);
+        fallback.resize(4, if fallback.is_empty() { 0.0 } else { 1.0 });
+        Some(match output {
+            "outputs:r" => vec![fallback[0]],
+            "outputs:g" => vec![fallback[1]],
+            "outputs:b" => vec![fallback[2]],
+            "outputs:a" => vec![fallback[3]],
+            _ => fallback[..3].to_vec(),
+        })
+    }
+
+    /**
+     * A lambertian of the texture the input `name` of `shader` is
+     * connected to, tinted by its scale.
+     */
+    fn texture(
+        &mut self,
+        shader: &Prim,
+        name: &str,
+    ) -> std::result::Result<Option<Lambertian>, String> {
+        let connection = shader
+            .attributes
+            .get(name)
+            .and_then(|attribute| attribute.connection.as_ref());
+        let mut source = match connection.and_then(|c| c.rsplit_once('.')) {
+            Some((path, _)) => self.prims.get(path).copied(),
+            None => None,
+        };
+        // Through the inputs of materials and node graphs.
+        for _ in 0..16 {
+            match source {
+                Some(prim)
+                    if prim.token("info:id", None).as_deref()
+                        != Some("UsdUVTexture") =>
+                {
+                    let connection = prim
+                        .attributes
+                        .values()
+                        .find_map(|attribute| attribute.connection.as_ref());
+                    source = connection
+                        .and_then(|c| c.rsplit_once('.'))
+                        .and_then(|(path, _)| self.prims.get(path).copied());
+                }
+                _ => break,
+            }
+        }
+        let texture = match source {
+            Some(texture) => texture,
+            None => return Ok(None),
+        };
+        let file = match texture.token("inputs:file", None) {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let scale = match texture.numbers("inputs:scale", self.time)[..] {
+            [r, g, b, ..] => [r, g, b],
+            _ => [1.0; 3],
+        };
+        let texture = self.files.texture(&file)?;
+        Ok(Some(Lambertian::textured(arr1(&scale), texture)))
+    }
+
+    /**
+     * The description of the UsdPreviewSurface of the material at `path`,
+     * None if it has none.
+     */
+    fn preview_surface(
+        &mut self,
+        path: &str,
+    ) -> std::result::Result<Option<MaterialDescription>, String> {
+        let surface = self
+            .prims
+            .get(path)
+            .and_then(|material| material.attributes.get("outputs:surface"))
+            .and_then(|output| output.connection.as_ref())
+            .and_then(|connection| connection.rsplit_once('.'))
+            .and_then(|(shader, _)| self.prims.get(shader).copied());
+        let shader = match surface {
+            Some(shader)
+                if shader.token("info:id", None).as_deref()
+                    == Some("UsdPreviewSurface") =>
+            {
+                shader
+            }
+            _ => {
+                warn!(
+                    material = path,
+                    "no UsdPreviewSurface, displayColor used"
+                );
+                return Ok(None);
+            }
+        };
+        let number = |name: &str, default: Float| {
+            self.input(shader, &format!("inputs:{}", name), 0)
+                .and_then(|values| values.first().copied())
+                .unwrap_or(default)
+        };
+        let color = |name: &str, default: [Float; 3]| match self
+            .input(shader, &format!("inputs:{}", name), 0)
+            .as_deref()
+        {
+            Some([r, g, b, ..]) => [*r, *g, *b],
+            _ => default,
+        };
+        let diffuse = color("diffuseColor", [0.18; 3]);
+        let emissive = color("emissiveColor", [0.0; 3]);
+        let metallic = number("metallic", 0.0).clamp(0.0, 1.0);
+        let roughness = number("roughness", 0.5).clamp(0.0, 1.0);
+        let clearcoat = number("clearcoat", 0.0).clamp(0.0, 1.0);
+        let clearcoat_roughness = number("clearcoatRoughness", 0.01);
+        let opacity = number("opacity", 1.0).clamp(0.0, 1.0);
+        let ior = number("ior", 1.5);
+
+        if emissive.iter().any(|c| *c > 0.0) {
+            return Ok(Some(MaterialDescription::DiffuseLight {
+                color: emissive,
+                intensity: 1.0,
+                light_group: None,
+            }));
+        }
+        let mut lambertian =
+            MaterialDescription::Lambertian { albedo: diffuse };
+        if let Some(material) = self.texture(shader, "inputs:diffuseColor")? {
+            let name = format!("{}:diffuseColor", path);
+            self.library.insert(&name, Arc::new(material));
+            lambertian = MaterialDescription::Named { name };
+        }
+        let metal = MaterialDescription::Metal {
+            color: diffuse,
+            fuzz: roughness,
+        };
+        let mut material = match metallic {
+            m if m <= 0.0 => lambertian,
+            m if m >= 1.0 => metal,
+            m => MaterialDescription::Mix {
+                first: Box::new(lambertian),
+                second: Box::new(metal),
+                factor: Mask::Uniform { value: m },
+            },
+        };
+        if clearcoat > 0.0 {
+            let coated = MaterialDescription::Clearcoat {
+                base: Box::new(material.clone()),
+                refraction_idx: 1.5,
+                roughness: clearcoat_roughness,
+                tint: [1.0; 3],
+            };
+            material = MaterialDescription::Mix {
+                first: Box::new(material),
+                second: Box::new(coated),
+                factor: Mask::Uniform { value: clearcoat },
+            };
+        }
+        if opacity < 1.0 {
+            material = MaterialDescription::Mix {
+                first: Box::new(MaterialDescription::Dielectric {
+                    refraction_idx: ior,
+                    dispersion: None,
+                }),
+                second: Box::new(material),
+                factor: Mask::Uniform { value: opacity },
+            };
+        }
+        Ok(Some(material))
+    }
+}
+
+/**
+ * The luminance of a light (nits), its intensity times 2 to its exposure.
+ */
+fn luminance(light: &Prim, time: Option<Float>) -> Float {
+    light.input("intensity", 1.0, time)
+        * (2.0 as Float).powf(light.input("exposure", 0.0, time))
+}
+
+fn index<'a>(prims: &'a [Prim], index: &mut HashMap<String, &'a Prim>) {
+    for prim in prims {
+        index.insert(prim.path.clone(), prim);
+        self::index(&prim.children, index);
+    }
+}
+
+/**
+ * Parse a text (usda) layer into the scene it describes at the time code
+ * `time` (or at its defaults), in `scene_units`. The textures are read
+ * from `files`.
+ */
+pub fn parse_usda(
+    text: &str,
+    scene_units: Units,
+    time: Option<Float>,
+    files: &mut dyn SceneFiles,
+) -> std::result::Result<UsdScene, String> {
+    let (metadata, prims) = parse_layer(text)?;
+    let meters = metadata
+        .get("metersPerUnit")
+        .and_then(Value::number)
+        .unwrap_or(0.01);
+    let mut root = scaling([meters / scene_units.meters(); 3]);
+    if metadata.get("upAxis").and_then(Value::text) == Some("Z") {
+        root = multiply(&root, &rotation([1.0, 0.0, 0.0], -90.0));
+    }
+
+    let mut builder = Builder {
+        prims: HashMap::new(),
+        time,
+        scene_units,
+        files,
+        library: MaterialLibrary::new(),
+        scene: UsdScene {
+            actors: vec![],
+            lights: vec![],
+            cameras: vec![],
+        },
+    };
+    index(&prims, &mut builder.prims);
+    for prim in &prims {
+        builder.visit(prim, &root, None)?;
+    }
+    Ok(builder.scene)
+}
+
+/**
+ * The files of a usdz package by name, the root layer first.
+ */
+fn unpack(bytes: &[u8]) -> std::result::Result<Vec<(String, Vec<u8>)>, String> {
+    let u16_at = |at: usize| {
+        bytes
+            .get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let u32_at = |at: usize| {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    let mut files = vec![];
+    let mut at = 0;
+    // The local headers of the files, up to the central directory.
+    while u32_at(at) == Some(0x0403_4b50) {
+        let truncated = || "truncated package".to_string();
+        let flags = u16_at(at + 6).ok_or_else(truncated)?;
+        let compression = u16_at(at + 8).ok_or_else(truncated)?;
+        let size = u32_at(at + 18).ok_or_else(truncated)?;
+        let name_length = u16_at(at + 26).ok_or_else(truncated)?;
+        let extra_length = u16_at(at + 28).ok_or_else(truncated)?;
+        let name = bytes
+            .get(at + 30..at + 30 + name_length)
+            .ok_or_else(truncated)?;
+        let name = String::from_utf8_lossy(name).to_string();
+        if compression != 0 || flags & 8 != 0 {
+            return Err(format!(
+                "{} is compressed, usdz files are stored",
+                name
+            ));
+        }
+        let start = at + 30 + name_length + extra_length;
+        let data = bytes.get(start..start + size).ok_or_else(truncated)?;
+        files.push((name, data.to_vec()));
+        at = start + size;
+    }
+    if files.is_empty() {
+        return Err("not a usdz package".to_string());
+    }
+    Ok(files)
+}
+
+/**
+ * The textures of a stage, in its usdz package or in files relative to
+ * the folder of the stage.
+ */
+#[cfg_attr(not(feature = "png"), allow(dead_code))]
+struct Assets<'a> {
+    base: &'a Path,
+    package: HashMap<String, Vec<u8>>,
+}
+
+impl SceneFiles for Assets<'_> {
+    fn mesh(
+        &mut self,
+        mesh: &MeshDescription,
+        _material: Arc<dyn Scattering>,
+    ) -> std::result::Result<Mesh, String> {
+        Err(format!("cannot read the mesh {}", mesh.path))
+    }
+
+    #[cfg(feature = "png")]
+    fn texture(
+        &mut self,
+        path: &str,
+    ) -> std::result::Result<Arc<dyn TextureSampler>, String> {
+        let texture = match self.package.get(path.trim_start_matches("./")) {
+            Some(bytes) => decode_texture(bytes, path),
+            None => read_texture(&self.base.join(path)),
+        };
+        texture
+            .map(|texture| Arc::new(texture) as Arc<dyn TextureSampler>)
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "png"))]
+    fn texture(
+        &mut self,
+        path: &str,
+    ) -> std::result::Result<Arc<dyn TextureSampler>, String> {
+        Err(format!("reading {} needs the png feature", path))
+    }
+}
+
+/**
+ * Read the USD stage (usda, or a usdz package) at `path` into the scene it
+ * describes at the time code `time`, in `scene_units`.
+ */
+#[instrument(skip_all, fields(path = %path.display()))]
+pub fn read_stage(
+    path: &Path,
+    scene_units: Units,
+    time: Option<Float>,
+) -> Result<UsdScene> {
+    let invalid = |reason: String| SaturnoError::InvalidActor {
+        actor: path.display().to_string(),
+        reason,
+    };
+    let bytes = fs::read(path).map_err(|e| SaturnoError::io(path, e))?;
+    let mut package = if bytes.starts_with(b"PK") {
+        unpack(&bytes).map_err(invalid)?
+    } else {
+        vec![(String::new(), bytes)]
+    };
+    let (name, layer) = package.remove(0);
+    if layer.starts_with(b"PXR-USDC") {
+        return Err(invalid(format!(
+            "{} is a binary (usdc) layer, convert it with usdcat -o scene.usda",
+            name
+        )));
+    }
+    let text = String::from_utf8(layer).map_err(|e| invalid(e.to_string()))?;
+    let mut assets = Assets {
+        base: path.parent().unwrap_or(Path::new(".")),
+        package: package.into_iter().collect(),
+    };
+    parse_usda(&text, scene_units, time, &mut assets).map_err(invalid)
+}
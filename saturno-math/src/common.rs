@@ -0,0 +1,150 @@
+use ndarray::{arr1, Array1, ArrayView1};
+
+/**
+ * Floating point type of the math and render core. Double precision by
+ * default; the `f32` feature switches to single precision, which is about
+ * twice as fast and enough for most images.
+ */
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+
+pub struct Vec4 {
+    data: Array1<Float>,
+}
+
+impl Vec4 {
+    pub fn x(&self) -> Float {
+        self.data[0]
+    }
+    pub fn y(&self) -> Float {
+        self.data[1]
+    }
+    pub fn z(&self) -> Float {
+        self.data[2]
+    }
+    pub fn w(&self) -> Float {
+        self.data[3]
+    }
+    pub fn r(&self) -> Float {
+        self.data[0]
+    }
+    pub fn g(&self) -> Float {
+        self.data[1]
+    }
+    pub fn b(&self) -> Float {
+        self.data[2]
+    }
+    pub fn a(&self) -> Float {
+        self.data[3]
+    }
+
+    pub fn normalized(&self) -> Vec4 {
+        Vec4 {
+            data: Vec4::normalize(self.data.clone()),
+        }
+    }
+
+    pub fn l2_norm(x: ArrayView1<Float>) -> Float {
+        x.dot(&x).sqrt()
+    }
+
+    pub fn squared_length(x: ArrayView1<Float>) -> Float {
+        x.dot(&x)
+    }
+
+    pub fn normalize(x: Array1<Float>) -> Array1<Float> {
+        // TODO Need to create Vec3 and use that instead in
+        // here
+        let mut vec3 = arr1(&[x[0], x[1], x[2]]);
+        let norm: Float = Vec4::l2_norm(vec3.view());
+        vec3.mapv_inplace(|e| e / norm);
+        arr1(&[vec3[0], vec3[1], vec3[2], x[3]])
+    }
+
+    pub fn cross(a: Array1<Float>, b: Array1<Float>) -> Array1<Float> {
+        let mut c = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        c[0] = a[1] * b[2] - a[2] * b[1];
+        c[1] = -a[0] * b[2] + a[2] * b[0];
+        c[2] = a[0] * b[1] - a[1] * b[0];
+
+        c
+    }
+}
+
+/**
+ * The rays through the neighbouring pixels (one pixel right, one pixel
+ * down) of a camera ray, followed along its specular bounces. How far
+ * they land from the ray is the footprint of the ray on the surfaces it
+ * hits, to filter textures.
+ */
+#[derive(Clone, Debug)]
+pub struct RayDifferentials {
+    pub x_origin: Array1<Float>,
+    pub x_direction: Array1<Float>,
+    pub y_origin: Array1<Float>,
+    pub y_direction: Array1<Float>,
+}
+
+pub struct Ray {
+    pub origin: Array1<Float>,
+    pub direction: Array1<Float>,
+    /**
+     * None for rays whose footprint is unknown (e.g. diffuse bounces).
+     */
+    pub differentials: Option<RayDifferentials>,
+    /**
+     * When the ray is cast, as a fraction of the shutter interval (0 when
+     * it opens, 1 when it closes): what moving actors are hit where.
+     */
+    pub time: Float,
+}
+
+impl Ray {
+    pub fn new(origin: Array1<Float>, direction: Array1<Float>) -> Ray {
+        Ray {
+            origin,
+            direction: Vec4::normalize(direction),
+            differentials: None,
+            time: 0.0,
+        }
+    }
+
+    /**
+     * The ray cast at `time` instead, e.g. that of the ray it continues.
+     */
+    pub fn at_time(mut self, time: Float) -> Ray {
+        self.time = time;
+        self
+    }
+
+    pub fn point_at_parameter(&self, t: Float) -> Array1<Float> {
+        self.origin.clone() + t * self.direction.clone()
+    }
+
+    /**
+     * Bring the differential rays closer to the ray by `scale` (e.g. when
+     * several samples share a pixel, each covers a fraction of it).
+     */
+    pub fn scale_differentials(&mut self, scale: Float) {
+        if let Some(differentials) = &mut self.differentials {
+            let scaled = |offset: &Array1<Float>, base: &Array1<Float>| {
+                base.clone() + (offset.clone() - base.clone()) * scale
+            };
+            differentials.x_origin =
+                scaled(&differentials.x_origin, &self.origin);
+            differentials.x_direction =
+                scaled(&differentials.x_direction, &self.direction);
+            differentials.y_origin =
+                scaled(&differentials.y_origin, &self.origin);
+            differentials.y_direction =
+                scaled(&differentials.y_direction, &self.direction);
+        }
+    }
+}
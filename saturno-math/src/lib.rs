@@ -0,0 +1,5 @@
+// Math core of the ray tracer: vectors, rays and the random numbers used
+// for sampling.
+
+pub mod common;
+pub mod random;
@@ -0,0 +1,220 @@
+use crate::common::consts;
+use crate::common::Float;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::cell::RefCell;
+
+// Random numbers used while rendering. By default they come from the
+// (entropy seeded) thread rng. In deterministic mode, every pixel draws
+// from its own stream derived from the render seed, so the output does not
+// depend on which thread renders which pixel, nor in which order.
+// Metropolis light transport draws them from the mutated primary samples
+// of the path instead.
+
+thread_local! {
+    static SEEDED: RefCell<Option<ChaCha8Rng>> = const { RefCell::new(None) };
+    static PRIMARY: RefCell<Option<PrimarySamples>> =
+        const { RefCell::new(None) };
+}
+
+/**
+ * Switch the current thread to the deterministic stream of a pixel.
+ */
+pub fn seed_stream(seed: u64, stream: u64) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    SEEDED.with(|seeded| *seeded.borrow_mut() = Some(rng));
+}
+
+/**
+ * Switch the current thread back to the non deterministic thread rng.
+ */
+pub fn unseed() {
+    SEEDED.with(|seeded| *seeded.borrow_mut() = None);
+}
+
+/**
+ * Uniform sample in [low, high).
+ */
+pub fn gen_range(low: Float, high: Float) -> Float {
+    let primary = PRIMARY.with(|primary| {
+        primary.borrow_mut().as_mut().map(PrimarySamples::next)
+    });
+    if let Some(u) = primary {
+        return low + u * (high - low);
+    }
+    SEEDED.with(|seeded| match seeded.borrow_mut().as_mut() {
+        Some(rng) => rng.gen_range(low, high),
+        None => rand::thread_rng().gen_range(low, high),
+    })
+}
+
+/**
+ * Draw the random numbers of the current thread from `samples` while
+ * running `f`: a path then is a function of its primary samples.
+ */
+pub fn with_primary_samples<R, F: FnOnce() -> R>(
+    samples: &mut PrimarySamples,
+    f: F,
+) -> R {
+    let taken = std::mem::take(samples);
+    PRIMARY.with(|primary| *primary.borrow_mut() = Some(taken));
+    let result = f();
+    *samples = PRIMARY
+        .with(|primary| primary.borrow_mut().take())
+        .unwrap_or_default();
+    result
+}
+
+#[derive(Clone, Debug, Default)]
+struct PrimarySample {
+    value: Float,
+    modified: u64,
+    backup: (Float, u64),
+}
+
+/**
+ * The random numbers a path is made of (its primary samples), mutated by
+ * Metropolis light transport (Kelemen et al. 2002): either all drawn
+ * anew (a large step, with probability `large_step_probability`), or
+ * each moved by a normal offset of deviation `sigma`. The numbers are
+ * only drawn (or mutated, catching up with the iterations they missed)
+ * when the path asks for them.
+ */
+#[derive(Clone, Debug)]
+pub struct PrimarySamples {
+    samples: Vec<PrimarySample>,
+    index: usize,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    sigma: Float,
+    large_step_probability: Float,
+    rng: ChaCha8Rng,
+}
+
+impl Default for PrimarySamples {
+    fn default() -> PrimarySamples {
+        PrimarySamples::new(0, 0, 0.01, 0.3)
+    }
+}
+
+impl PrimarySamples {
+    /**
+     * Samples drawn from the stream `stream` of `seed`: the first path
+     * (before any iteration) is the same for the same stream.
+     */
+    pub fn new(
+        seed: u64,
+        stream: u64,
+        sigma: Float,
+        large_step_probability: Float,
+    ) -> PrimarySamples {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(stream);
+        PrimarySamples {
+            samples: vec![],
+            index: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            sigma,
+            large_step_probability,
+            rng,
+        }
+    }
+
+    /**
+     * Draw the next numbers from the stream `stream` of `seed`, keeping
+     * the samples: chains starting from the same path then part ways.
+     */
+    pub fn reseed(&mut self, seed: u64, stream: u64) {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self.rng.set_stream(stream);
+    }
+
+    /**
+     * A uniform number in [0, 1) which is not a primary sample (to decide
+     * on the mutations).
+     */
+    pub fn uniform(&mut self) -> Float {
+        self.rng.gen::<Float>()
+    }
+
+    /**
+     * Mutate the samples for the next path.
+     */
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.uniform() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    /**
+     * Keep the mutated samples.
+     */
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /**
+     * Go back to the samples before the mutation.
+     */
+    pub fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.modified == self.iteration {
+                (sample.value, sample.modified) = sample.backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    fn next(&mut self) -> Float {
+        if self.index >= self.samples.len() {
+            // A number the path never asked for is uniform, as if drawn at
+            // the last large step.
+            let value = self.uniform();
+            self.samples.push(PrimarySample {
+                value,
+                modified: self.last_large_step,
+                backup: (value, self.last_large_step),
+            });
+        }
+        let index = self.index;
+        self.index += 1;
+
+        // Drawn anew at the last large step it missed.
+        if self.samples[index].modified < self.last_large_step {
+            let value = self.uniform();
+            self.samples[index].value = value;
+            self.samples[index].modified = self.last_large_step;
+        }
+        let sample = &self.samples[index];
+        let backup = (sample.value, sample.modified);
+        let value = if self.large_step {
+            self.uniform()
+        } else {
+            // The small steps it missed add up to one normal offset.
+            let steps = (self.iteration - sample.modified) as Float;
+            let (u1, u2) = (self.uniform(), self.uniform());
+            let normal =
+                (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * consts::PI * u2).cos();
+            let value = backup.0 + normal * self.sigma * steps.sqrt();
+            let wrapped = value - value.floor();
+            if wrapped < 1.0 {
+                wrapped
+            } else {
+                0.0
+            }
+        };
+        self.samples[index] = PrimarySample {
+            value,
+            modified: self.iteration,
+            backup,
+        };
+        value
+    }
+}
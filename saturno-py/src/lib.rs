@@ -0,0 +1,289 @@
+// Python bindings: build a scene of spheres, point a camera at it and
+// render it into a numpy array.
+//
+//     import saturno
+//
+//     scene = saturno.Scene()
+//     scene.add_sphere((0, 0, -1), 0.5, saturno.Material.metal((0.8, 0.6, 0.2)))
+//     camera = saturno.Camera(look_from=(0, 0, 0), look_at=(0, 0, -1))
+//     image = scene.render(camera, 200, 100, samples=16)  # (100, 200, 4) uint8
+
+use ndarray::arr1;
+use numpy::ndarray::Array3;
+use numpy::{IntoPyArray, PyArray3};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use saturno::math::common::Float;
+use saturno::prelude::*;
+use std::sync::Arc;
+
+/**
+ * Materials are kept as descriptions, so one can be assigned to several
+ * spheres. They are instantiated when the scene is rendered.
+ */
+#[derive(Clone)]
+enum MaterialKind {
+    Lambertian { albedo: [Float; 3] },
+    Metal { color: [Float; 3], fuzz: Float },
+    Dielectric { refraction_idx: Float },
+    DiffuseLight { color: [Float; 3], intensity: Float },
+    ShadowCatcher { strength: Float },
+}
+
+impl MaterialKind {
+    fn build(&self) -> Arc<dyn Scattering> {
+        let rgba = |c: &[Float; 3]| arr1(&[c[0], c[1], c[2], 1.0]);
+        match self {
+            MaterialKind::Lambertian { albedo } => {
+                Arc::new(Lambertian::new(rgba(albedo), Shading::COLOR))
+            }
+            MaterialKind::Metal { color, fuzz } => {
+                Arc::new(Metal::new(rgba(color), Shading::COLOR, *fuzz))
+            }
+            MaterialKind::Dielectric { refraction_idx } => {
+                Arc::new(Dielectric::new(
+                    rgba(&[1.0, 1.0, 1.0]),
+                    Shading::COLOR,
+                    *refraction_idx,
+                ))
+            }
+            MaterialKind::DiffuseLight { color, intensity } => {
+                Arc::new(DiffuseLight::new(rgba(color), *intensity))
+            }
+            MaterialKind::ShadowCatcher { strength } => {
+                Arc::new(ShadowCatcher::new(*strength))
+            }
+        }
+    }
+}
+
+#[pyclass(name = "Material")]
+#[derive(Clone)]
+struct PyMaterial {
+    kind: MaterialKind,
+}
+
+#[pymethods]
+impl PyMaterial {
+    #[staticmethod]
+    fn lambertian(albedo: [Float; 3]) -> PyMaterial {
+        PyMaterial {
+            kind: MaterialKind::Lambertian { albedo },
+        }
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (color, fuzz = 0.0))]
+    fn metal(color: [Float; 3], fuzz: Float) -> PyMaterial {
+        PyMaterial {
+            kind: MaterialKind::Metal { color, fuzz },
+        }
+    }
+
+    #[staticmethod]
+    fn dielectric(refraction_idx: Float) -> PyMaterial {
+        PyMaterial {
+            kind: MaterialKind::Dielectric { refraction_idx },
+        }
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (color, intensity = 1.0))]
+    fn diffuse_light(color: [Float; 3], intensity: Float) -> PyMaterial {
+        PyMaterial {
+            kind: MaterialKind::DiffuseLight { color, intensity },
+        }
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (strength = 1.0))]
+    fn shadow_catcher(strength: Float) -> PyMaterial {
+        PyMaterial {
+            kind: MaterialKind::ShadowCatcher { strength },
+        }
+    }
+}
+
+/**
+ * Camera placement. The resolution is given when rendering.
+ */
+#[pyclass(name = "Camera")]
+#[derive(Clone)]
+struct PyCamera {
+    look_from: [Float; 3],
+    look_at: [Float; 3],
+    vertical_fov: Float,
+    up: [Float; 3],
+    aperture: Float,
+}
+
+#[pymethods]
+impl PyCamera {
+    #[new]
+    #[pyo3(signature = (
+        look_from,
+        look_at,
+        vertical_fov = 90.0,
+        up = [0.0, 1.0, 0.0],
+        aperture = 0.0
+    ))]
+    fn new(
+        look_from: [Float; 3],
+        look_at: [Float; 3],
+        vertical_fov: Float,
+        up: [Float; 3],
+        aperture: Float,
+    ) -> PyCamera {
+        PyCamera {
+            look_from,
+            look_at,
+            vertical_fov,
+            up,
+            aperture,
+        }
+    }
+}
+
+impl PyCamera {
+    fn build(&self, width: u32, height: u32) -> Camera {
+        let (from, at, up) = (self.look_from, self.look_at, self.up);
+        Camera::new(
+            self.vertical_fov,
+            width,
+            height,
+            arr1(&[from[0], from[1], from[2], 1.0]),
+            arr1(&[at[0], at[1], at[2], 1.0]),
+            arr1(&[up[0], up[1], up[2], 0.0]),
+            self.aperture,
+        )
+    }
+}
+
+#[pyclass(name = "Scene")]
+#[derive(Default)]
+struct PyScene {
+    spheres: Vec<([Float; 3], Float, MaterialKind)>,
+}
+
+#[pymethods]
+impl PyScene {
+    #[new]
+    fn new() -> PyScene {
+        PyScene::default()
+    }
+
+    fn add_sphere(
+        &mut self,
+        center: [Float; 3],
+        radius: Float,
+        material: &PyMaterial,
+    ) {
+        self.spheres.push((center, radius, material.kind.clone()));
+    }
+
+    fn __len__(&self) -> usize {
+        self.spheres.len()
+    }
+
+    /**
+     * Render into a (height, width, 4) uint8 RGBA array.
+     */
+    #[pyo3(signature = (camera, width, height, samples = 1, seed = None, threads = 0))]
+    #[allow(clippy::too_many_arguments)]
+    fn render<'py>(
+        &self,
+        py: Python<'py>,
+        camera: &PyCamera,
+        width: u32,
+        height: u32,
+        samples: u32,
+        seed: Option<u64>,
+        threads: usize,
+    ) -> PyResult<Bound<'py, PyArray3<u8>>> {
+        let renderer =
+            self.renderer(camera, width, height, samples, seed, threads)?;
+        let image = py.allow_threads(|| renderer.render()).map_err(to_py)?;
+
+        let shape = (image.height as usize, image.width as usize, 4);
+        let array = Array3::from_shape_vec(shape, image.data)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(array.into_pyarray(py))
+    }
+
+    /**
+     * Render the linear radiance (no tone mapping nor gamma) into a
+     * (height, width, 4) float array, premultiplied by alpha.
+     */
+    #[pyo3(signature = (camera, width, height, samples = 1, seed = None, threads = 0))]
+    #[allow(clippy::too_many_arguments)]
+    fn render_hdr<'py>(
+        &self,
+        py: Python<'py>,
+        camera: &PyCamera,
+        width: u32,
+        height: u32,
+        samples: u32,
+        seed: Option<u64>,
+        threads: usize,
+    ) -> PyResult<Bound<'py, PyArray3<Float>>> {
+        let renderer =
+            self.renderer(camera, width, height, samples, seed, threads)?;
+        let image = py
+            .allow_threads(|| renderer.canvas.render_hdr(&renderer.settings))
+            .map_err(to_py)?;
+
+        let shape = (image.height as usize, image.width as usize, 4);
+        let array = Array3::from_shape_vec(shape, image.data)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(array.into_pyarray(py))
+    }
+}
+
+impl PyScene {
+    fn renderer(
+        &self,
+        camera: &PyCamera,
+        width: u32,
+        height: u32,
+        samples: u32,
+        seed: Option<u64>,
+        threads: usize,
+    ) -> PyResult<Renderer> {
+        let mut builder = RenderSettings::builder()
+            .size(width, height)
+            .samples(samples)
+            .threads(threads);
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
+        }
+        let settings = builder.build().map_err(to_py)?;
+
+        let actors = self
+            .spheres
+            .iter()
+            .map(|(center, radius, material)| {
+                Box::new(Sphere {
+                    center: arr1(&[center[0], center[1], center[2], 1.0]),
+                    radius: *radius,
+                    material: material.build(),
+                }) as Box<dyn RayTraceable>
+            })
+            .collect();
+        let canvas = Canvas::new(actors, camera.build(width, height));
+
+        Ok(Renderer::new(canvas, settings))
+    }
+}
+
+fn to_py(error: SaturnoError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+#[pymodule]
+#[pyo3(name = "saturno")]
+fn saturno_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyMaterial>()?;
+    m.add_class::<PyCamera>()?;
+    m.add_class::<PyScene>()?;
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use crate::HdrImage;
+use crate::Image;
+use saturno_math::common::Float;
+
+// Exposure analysis of the HDR framebuffer, before the tone mapping: the
+// luminance of the pixels, once exposed, in stops above or below middle
+// gray (18%). A histogram counts the pixels per stop, a waveform plots
+// them per column of the image, and a false color image paints the zones
+// of the exposure: the crushed shadows, middle gray, a stop over (skin
+// highlights) and the highlights about to clip or clipped. The clipping
+// point is an exposed luminance of 1, the white of the tone mappers.
+
+/**
+ * Exposed luminance of middle gray, the origin of the stops.
+ */
+pub const MIDDLE_GRAY: Float = 0.18;
+
+/**
+ * Stops of the clipping point (a luminance of 1) above middle gray.
+ */
+pub fn clipping_stops() -> Float {
+    (1.0 / MIDDLE_GRAY).log2()
+}
+
+/**
+ * Exposed luminance of the pixels of `hdr` (unpremultiplied), the values
+ * exposed by `scale`.
+ */
+pub fn luminances(hdr: &HdrImage, scale: Float) -> Vec<Float> {
+    (0..hdr.size())
+        .map(|i| {
+            let pixel = hdr.get_pixel(i);
+            let alpha = if pixel[3] > 0.0 { pixel[3] } else { 1.0 };
+            let luminance =
+                0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2];
+            (luminance / alpha * scale).max(0.0)
+        })
+        .collect()
+}
+
+/**
+ * Stops of the exposed `luminance` above middle gray, -infinity for
+ * black.
+ */
+pub fn stops(luminance: Float) -> Float {
+    (luminance / MIDDLE_GRAY).log2()
+}
+
+/**
+ * Pixels counted per range of stops: `bins` ranges of the same width from
+ * `min_stops` to `max_stops` around middle gray, the pixels out of the
+ * range in the first or last one.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    pub min_stops: Float,
+    pub max_stops: Float,
+    pub bins: Vec<u64>,
+    /**
+     * Pixels of no light at all.
+     */
+    pub black: u64,
+    /**
+     * Pixels at or over the clipping point.
+     */
+    pub clipped: u64,
+}
+
+impl Histogram {
+    /**
+     * Histogram of `hdr`, exposed by `scale`, over 16 stops: 10 under and
+     * 6 over middle gray, in thirds of a stop.
+     */
+    pub fn new(hdr: &HdrImage, scale: Float) -> Histogram {
+        Histogram::with_range(hdr, scale, 48, -10.0, 6.0)
+    }
+
+    pub fn with_range(
+        hdr: &HdrImage,
+        scale: Float,
+        bins: usize,
+        min_stops: Float,
+        max_stops: Float,
+    ) -> Histogram {
+        let mut histogram = Histogram {
+            min_stops,
+            max_stops,
+            bins: vec![0; bins.max(1)],
+            black: 0,
+            clipped: 0,
+        };
+        for luminance in luminances(hdr, scale) {
+            if luminance <= 0.0 {
+                histogram.black += 1;
+            }
+            if luminance >= 1.0 {
+                histogram.clipped += 1;
+            }
+            let bin = histogram.bin(stops(luminance));
+            histogram.bins[bin] += 1;
+        }
+        histogram
+    }
+
+    /**
+     * Bin of the pixels at `stops`.
+     */
+    pub fn bin(&self, stops: Float) -> usize {
+        let count = self.bins.len();
+        let relative =
+            (stops - self.min_stops) / (self.max_stops - self.min_stops);
+        // NaN (never) and -infinity (black) land in the first bin.
+        ((relative * count as Float).max(0.0) as usize).min(count - 1)
+    }
+
+    /**
+     * Fraction of the pixels clipped.
+     */
+    pub fn clipped_fraction(&self) -> Float {
+        let total = self.bins.iter().sum::<u64>();
+        self.clipped as Float / total.max(1) as Float
+    }
+
+    /**
+     * Bars of the bins, `width` by `height`, scaled to the fullest: gray,
+     * red over the clipping point, with a white line at middle gray.
+     */
+    pub fn image(&self, width: u32, height: u32) -> Image {
+        let mut image = Image::new(width, height, 4);
+        let max = self.bins.iter().copied().max().unwrap_or(0).max(1);
+        let span = self.max_stops - self.min_stops;
+        let column = |stops: Float| {
+            ((stops - self.min_stops) / span * width as Float) as i64
+        };
+        let (gray, clip) = (column(0.0), column(clipping_stops()));
+        for x in 0..width {
+            let bin = (x as usize * self.bins.len()) / width as usize;
+            let bar = (self.bins[bin] as Float / max as Float * height as Float)
+                .round() as u32;
+            for y in 0..height {
+                let color = if x as i64 == gray {
+                    [255, 255, 255, 255]
+                } else if height - y <= bar {
+                    if x as i64 >= clip {
+                        [220, 40, 40, 255]
+                    } else {
+                        [180, 180, 180, 255]
+                    }
+                } else {
+                    [0, 0, 0, 255]
+                };
+                image.set_pixel((y * width + x) as usize, color);
+            }
+        }
+        image
+    }
+}
+
+/**
+ * Waveform of `hdr`, exposed by `scale`: for every column of the image, its
+ * pixels plotted by their stops, from `min_stops` (bottom) to `max_stops`
+ * (top) over `height` rows, brighter where more pixels land. A red line
+ * marks the clipping point.
+ */
+pub fn waveform(
+    hdr: &HdrImage,
+    scale: Float,
+    height: u32,
+    min_stops: Float,
+    max_stops: Float,
+) -> Image {
+    let width = hdr.width;
+    let mut counts = vec![0u32; (width * height) as usize];
+    let row = |stops: Float| {
+        let relative = (stops - min_stops) / (max_stops - min_stops);
+        let row = (relative * height as Float).max(0.0) as u32;
+        height - 1 - row.min(height - 1)
+    };
+    for (i, luminance) in luminances(hdr, scale).into_iter().enumerate() {
+        let x = i as u32 % width;
+        counts[(row(stops(luminance)) * width + x) as usize] += 1;
+    }
+
+    let mut image = Image::new(width, height, 4);
+    let max = counts.iter().copied().max().unwrap_or(0).max(1) as Float;
+    let clip = row(clipping_stops());
+    for (i, count) in counts.iter().enumerate() {
+        let level = if *count > 0 {
+            // Square root, so the rare values still show.
+            (64.0 + 191.0 * (*count as Float / max).sqrt()) as u8
+        } else {
+            0
+        };
+        let color = if level == 0 && i as u32 / width == clip {
+            [160, 0, 0, 255]
+        } else {
+            [0, level, 0, 255]
+        };
+        image.set_pixel(i, color);
+    }
+    image
+}
+
+/**
+ * Zones of the false color image: the color of the pixels below the
+ * stops of each, gray (by the luminance) for `None`.
+ */
+const ZONES: [(Float, Option<[u8; 3]>); 8] = [
+    (-6.5, Some([128, 0, 160])),
+    (-4.5, Some([0, 60, 255])),
+    (-0.5, None),
+    (0.5, Some([0, 190, 0])),
+    (0.8, None),
+    (1.5, Some([255, 130, 190])),
+    (2.0, None),
+    (Float::INFINITY, Some([255, 230, 0])),
+];
+
+/**
+ * False color image of the exposure of `hdr`, exposed by `scale`: purple
+ * for crushed shadows (6.5 stops under middle gray), blue for deep
+ * shadows, green for middle gray, pink one stop over (the highlights of
+ * skin), yellow for the highlights about to clip and red for the clipped
+ * ones. The rest is gray.
+ */
+pub fn false_color(hdr: &HdrImage, scale: Float) -> Image {
+    let mut image = Image::new(hdr.width, hdr.height, 4);
+    for (i, luminance) in luminances(hdr, scale).into_iter().enumerate() {
+        let stops = stops(luminance);
+        let color = if luminance >= 1.0 {
+            [255, 0, 0]
+        } else {
+            let zone = ZONES.iter().find(|(upper, _)| stops < *upper);
+            match zone {
+                Some((_, Some(color))) => *color,
+                _ => [(luminance.sqrt() * 255.0) as u8; 3],
+            }
+        };
+        image.set_pixel(i, [color[0], color[1], color[2], 255]);
+    }
+    image
+}
@@ -0,0 +1,286 @@
+// Arbitrary output variables (AOVs): auxiliary images rendered alongside
+// the beauty pass, used in compositing.
+
+use crate::Image;
+use std::collections::BTreeMap;
+
+/**
+ * Light group of the emitters not tagged into any group.
+ */
+pub const DEFAULT_LIGHT_GROUP: &str = "default";
+
+/**
+ * Light group of the rays escaping the scene.
+ */
+pub const BACKGROUND_LIGHT_GROUP: &str = "background";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum IdKind {
+    OBJECT,
+    MATERIAL,
+}
+
+impl IdKind {
+    /**
+     * Cryptomatte layer name.
+     */
+    pub fn layer_name(&self) -> &'static str {
+        match self {
+            IdKind::OBJECT => "CryptoObject",
+            IdKind::MATERIAL => "CryptoMaterial",
+        }
+    }
+}
+
+/**
+ * Name of the actors in ID passes, based on their index in the world.
+ */
+pub fn actor_name(actor_id: usize) -> String {
+    format!("actor{}", actor_id)
+}
+
+/**
+ * MurmurHash3 (x86, 32 bit), the hash mandated by Cryptomatte.
+ */
+pub fn murmur3_32(key: &[u8], seed: u32) -> u32 {
+    let c1: u32 = 0xcc9e_2d51;
+    let c2: u32 = 0x1b87_3593;
+    let mut h = seed;
+
+    let mut chunks = key.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k =
+            u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(c1).rotate_left(15).wrapping_mul(c2);
+        h ^= k;
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    let tail = chunks.remainder();
+    let mut k: u32 = 0;
+    for (i, byte) in tail.iter().enumerate() {
+        k |= (*byte as u32) << (8 * i);
+    }
+    if !tail.is_empty() {
+        k = k.wrapping_mul(c1).rotate_left(15).wrapping_mul(c2);
+        h ^= k;
+    }
+
+    h ^= key.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
+
+/**
+ * Hash a name into an ID. Cryptomatte stores IDs as float32 bit patterns,
+ * so hashes that would yield denormals, infinities or NaNs are nudged
+ * into normal floats (uint32_to_float32 conversion).
+ */
+pub fn name_hash(name: &str) -> u32 {
+    let hash = murmur3_32(name.as_bytes(), 0);
+    let exponent = (hash >> 23) & 255;
+    if exponent == 0 || exponent == 255 {
+        hash ^ (1 << 23)
+    } else {
+        hash
+    }
+}
+
+pub fn id_to_float(id: u32) -> f32 {
+    f32::from_bits(id)
+}
+
+/**
+ * One ID per pixel (taken from the pixel center, IDs can not be
+ * filtered). Zero means nothing was hit.
+ */
+pub struct IdImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u32>,
+}
+
+impl IdImage {
+    pub fn new(width: u32, height: u32) -> IdImage {
+        IdImage {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize],
+        }
+    }
+
+    pub fn get_value(&self, x: u32, y: u32) -> u32 {
+        self.data[(y * self.width + x) as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CostKind {
+    /**
+     * Rays traced through the world.
+     */
+    Rays,
+    /**
+     * Nodes of the hierarchies of the actors visited.
+     */
+    Nodes,
+    /**
+     * Intersection tests of actors and of their primitives.
+     */
+    Tests,
+}
+
+/**
+ * Traversal cost of every pixel, summed over all the rays of its samples.
+ */
+pub struct CostImage {
+    pub width: u32,
+    pub height: u32,
+    pub rays: Vec<u32>,
+    pub nodes: Vec<u32>,
+    pub tests: Vec<u32>,
+}
+
+impl CostImage {
+    pub fn new(width: u32, height: u32) -> CostImage {
+        let size = width as usize * height as usize;
+        CostImage {
+            width,
+            height,
+            rays: vec![0; size],
+            nodes: vec![0; size],
+            tests: vec![0; size],
+        }
+    }
+
+    pub fn values(&self, kind: CostKind) -> &[u32] {
+        match kind {
+            CostKind::Rays => &self.rays,
+            CostKind::Nodes => &self.nodes,
+            CostKind::Tests => &self.tests,
+        }
+    }
+
+    /**
+     * Color the cost from blue (free) through cyan, green and yellow to
+     * red (the most expensive pixel).
+     */
+    pub fn heatmap(&self, kind: CostKind) -> Image {
+        const RAMP: [[f32; 3]; 5] = [
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 1.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ];
+
+        let values = self.values(kind);
+        let max = values.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let mut image = Image::new(self.width, self.height, 4);
+        for (i, value) in values.iter().enumerate() {
+            let position = *value as f32 / max * (RAMP.len() - 1) as f32;
+            let low = (position.floor() as usize).min(RAMP.len() - 2);
+            let t = position - low as f32;
+            let mix = |c: usize| {
+                let value = RAMP[low][c] * (1.0 - t) + RAMP[low + 1][c] * t;
+                (value * 255.0).round() as u8
+            };
+            image.set_pixel(i, [mix(0), mix(1), mix(2), 255]);
+        }
+        image
+    }
+}
+
+/**
+ * Cryptomatte layer: for every pixel, the IDs covering it ranked by
+ * coverage, plus the manifest mapping names to their hashes.
+ */
+pub struct Cryptomatte {
+    pub kind: IdKind,
+    pub width: u32,
+    pub height: u32,
+    pub depth: usize,
+    pub ranks: Vec<Vec<(u32, f32)>>,
+    pub manifest: BTreeMap<String, u32>,
+}
+
+impl Cryptomatte {
+    pub fn new(
+        kind: IdKind,
+        width: u32,
+        height: u32,
+        depth: usize,
+    ) -> Cryptomatte {
+        Cryptomatte {
+            kind,
+            width,
+            height,
+            depth,
+            ranks: vec![vec![]; width as usize * height as usize],
+            manifest: BTreeMap::new(),
+        }
+    }
+
+    /**
+     * Store the coverage of a pixel, given the number of samples that hit
+     * each ID. Only the `depth` most covering IDs are kept.
+     */
+    pub fn set_pixel(
+        &mut self,
+        index: usize,
+        counts: &BTreeMap<u32, u32>,
+        samples: u32,
+    ) {
+        let mut ranked: Vec<(u32, f32)> = counts
+            .iter()
+            .map(|(id, count)| (*id, *count as f32 / samples as f32))
+            .collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+        ranked.truncate(self.depth);
+
+        self.ranks[index] = ranked;
+    }
+
+    /**
+     * Metadata key of the layer: the first 7 hex digits of its name hash.
+     */
+    pub fn key(&self) -> String {
+        let hash = murmur3_32(self.kind.layer_name().as_bytes(), 0);
+        format!("{:08x}", hash)[..7].to_string()
+    }
+
+    /**
+     * The manifest as a JSON object of the names to their hashes in hex,
+     * the names escaped (actors may be named anything).
+     */
+    pub fn manifest_json(&self) -> String {
+        let entries: Vec<String> = self
+            .manifest
+            .iter()
+            .map(|(name, id)| {
+                format!("\"{}\":\"{:08x}\"", escape_json(name), id)
+            })
+            .collect();
+
+        format!("{{{}}}", entries.join(","))
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                escaped.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,193 @@
+use crate::settings::Bloom;
+use crate::HdrImage;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+
+// Bloom and glare (see `Bloom`), the light a lens and an eye scatter around
+// the bright spots of an image. The light above the threshold is kept,
+// blurred (three box blurs, close to a Gaussian) and added back, and also
+// drawn out in the streaks of a diffraction star: the straight edges of an
+// aperture of n blades diffract the light in n directions and their
+// opposites, 2n streaks for n odd and n for n even (the opposites
+// overlap). Along a streak the light fades exponentially.
+//
+// Both are added over the image, `intensity` and `glare` times the light
+// they spread.
+
+fn luminance(color: &[Float]) -> Float {
+    0.2126 * color[0] + 0.7152 * color[1] + 0.0722 * color[2]
+}
+
+/**
+ * Blur a `width` by `height` RGB image by a box of `radius` pixels each
+ * way, along the rows if `rows`, else the columns, clamping at the edges.
+ */
+pub(crate) fn box_blur(
+    image: &[[Float; 3]],
+    width: usize,
+    height: usize,
+    radius: usize,
+    rows: bool,
+) -> Vec<[Float; 3]> {
+    let (lines, length) = if rows {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let index = |line: usize, i: usize| {
+        if rows {
+            line * width + i
+        } else {
+            i * width + line
+        }
+    };
+    let weight = 1.0 / (2 * radius + 1) as Float;
+    let mut blurred = vec![[0.0; 3]; image.len()];
+    for line in 0..lines {
+        let at = |i: isize| {
+            image[index(line, i.clamp(0, length as isize - 1) as usize)]
+        };
+        let r = radius as isize;
+        let mut sum = [0.0; 3];
+        for i in -r..=r {
+            let value = at(i);
+            for c in 0..3 {
+                sum[c] += value[c];
+            }
+        }
+        for i in 0..length as isize {
+            blurred[index(line, i as usize)] = sum.map(|s| s * weight);
+            let (entering, leaving) = (at(i + r + 1), at(i - r));
+            for c in 0..3 {
+                sum[c] += entering[c] - leaving[c];
+            }
+        }
+    }
+    blurred
+}
+
+/**
+ * The light of `hdr` above `threshold` (of luminance, once exposed by
+ * `scale`), in RGB.
+ */
+pub(crate) fn bright(
+    hdr: &HdrImage,
+    threshold: Float,
+    scale: Float,
+) -> Vec<[Float; 3]> {
+    let threshold = threshold / scale;
+    (0..hdr.size())
+        .map(|i| {
+            let pixel = hdr.get_pixel(i);
+            let luminance = luminance(&pixel);
+            if luminance > threshold {
+                let kept = (luminance - threshold) / luminance;
+                [0, 1, 2].map(|c| pixel[c].max(0.0) * kept)
+            } else {
+                [0.0; 3]
+            }
+        })
+        .collect()
+}
+
+/**
+ * The light of `image` (`width` pixels wide) drawn out in `count` streaks
+ * evenly around, the first at `rotation` radians, fading exponentially
+ * over `length` pixels. The streaks carry all of the light of the image.
+ */
+pub(crate) fn streaks(
+    image: &[[Float; 3]],
+    width: usize,
+    count: u32,
+    rotation: Float,
+    length: Float,
+) -> Vec<[Float; 3]> {
+    let height = image.len() / width;
+    let mut streaked = vec![[0.0; 3]; image.len()];
+    let steps = length.ceil() as usize * 3;
+    let falloff: Vec<Float> = (1..=steps)
+        .map(|step| (-(step as Float) / length).exp())
+        .collect();
+    let share = 1.0 / (count as Float * falloff.iter().sum::<Float>());
+    for (i, light) in image.iter().enumerate() {
+        if luminance(light) <= 0.0 {
+            continue;
+        }
+        let (x, y) = ((i % width) as Float, (i / width) as Float);
+        for streak in 0..count {
+            let angle =
+                2.0 * consts::PI * streak as Float / count as Float + rotation;
+            let (dx, dy) = (angle.cos(), angle.sin());
+            for (step, weight) in falloff.iter().enumerate() {
+                let distance = (step + 1) as Float;
+                let (px, py) =
+                    ((x + dx * distance).round(), (y + dy * distance).round());
+                if px < 0.0
+                    || py < 0.0
+                    || px >= width as Float
+                    || py >= height as Float
+                {
+                    break;
+                }
+                let j = py as usize * width + px as usize;
+                for c in 0..3 {
+                    streaked[j][c] += share * weight * light[c];
+                }
+            }
+        }
+    }
+    streaked
+}
+
+/**
+ * `hdr` with the bloom and the glare of `settings`, its values exposed by
+ * `scale` for the threshold.
+ */
+pub fn bloom(hdr: &HdrImage, settings: &Bloom, scale: Float) -> HdrImage {
+    let (width, height) = (hdr.width as usize, hdr.height as usize);
+    let diagonal = ((width * width + height * height) as Float).sqrt();
+
+    let excess = bright(hdr, settings.threshold, scale);
+
+    let mut spread = vec![[0.0; 3]; excess.len()];
+    // A Gaussian of deviation sigma is about three boxes of radius sigma.
+    let radius = (settings.radius * diagonal).round() as usize;
+    if settings.intensity > 0.0 && radius > 0 {
+        let mut blurred = excess.clone();
+        for _ in 0..3 {
+            blurred = box_blur(&blurred, width, height, radius, true);
+            blurred = box_blur(&blurred, width, height, radius, false);
+        }
+        for (s, b) in spread.iter_mut().zip(&blurred) {
+            for c in 0..3 {
+                s[c] += settings.intensity * b[c];
+            }
+        }
+    }
+
+    let length = settings.glare_length * diagonal;
+    if settings.glare > 0.0 && settings.blades > 0 && length >= 1.0 {
+        let count = if settings.blades.is_multiple_of(2) {
+            settings.blades
+        } else {
+            2 * settings.blades
+        };
+        let rotation = consts::PI / 4.0;
+        let streaked = streaks(&excess, width, count, rotation, length);
+        for (s, light) in spread.iter_mut().zip(&streaked) {
+            for c in 0..3 {
+                s[c] += settings.glare * light[c];
+            }
+        }
+    }
+
+    let mut result = HdrImage::new(hdr.width, hdr.height, hdr.chan);
+    for (i, light) in spread.iter().enumerate() {
+        let mut pixel = hdr.get_pixel(i);
+        for c in 0..3 {
+            pixel[c] += light[c];
+        }
+        result.set_pixel(i, pixel);
+    }
+    result
+}
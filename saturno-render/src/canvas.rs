@@ -0,0 +1,3370 @@
+use crate::aov::actor_name;
+use crate::aov::name_hash;
+use crate::aov::CostImage;
+use crate::aov::Cryptomatte;
+use crate::aov::IdImage;
+use crate::aov::IdKind;
+use crate::aov::BACKGROUND_LIGHT_GROUP;
+use crate::aov::DEFAULT_LIGHT_GROUP;
+use crate::bloom::bloom;
+use crate::caustics;
+use crate::caustics::Target;
+use crate::debug::vec3;
+use crate::debug::Bounce;
+use crate::debug::BounceEvent;
+use crate::debug::Culprit;
+use crate::debug::HitRecord;
+use crate::debug::PixelTrace;
+use crate::debug::Quarantine;
+use crate::debug::Recorder;
+use crate::debug::SampleTrace;
+use crate::display;
+use crate::error::Result;
+use crate::error::SaturnoError;
+use crate::flare::lens_flare;
+use crate::gradient;
+use crate::guiding::Guide;
+use crate::imperfections::camera_imperfections;
+use crate::irradiance::IrradianceCache;
+use crate::irradiance::Record;
+use crate::light_tree::LightTree;
+use crate::overlay;
+use crate::photons::Grid;
+use crate::photons::PhotonMap;
+use crate::photons::VisiblePoint;
+use crate::progressive::Progressive;
+use crate::reservoir::ReservoirGrid;
+use crate::reservoir::Stream;
+use crate::settings::Caustics;
+use crate::settings::Crop;
+use crate::settings::GradientDomain;
+use crate::settings::IrradianceCaching;
+use crate::settings::Metropolis;
+use crate::settings::OutputTransform;
+use crate::settings::PathGuiding;
+use crate::settings::PhotonMapping;
+use crate::settings::RenderSettings;
+use crate::settings::ReservoirSampling;
+use crate::settings::ThreadPriority;
+use crate::settings::TileOrder;
+use crate::settings::Toon;
+use crate::spectral::Wavelengths;
+use crate::stats::SceneStats;
+use crate::tiles;
+use crate::tiles::CostMap;
+use crate::tiles::WorkQueues;
+use crate::HdrImage;
+use crate::Image;
+use ndarray::{arr1, Array1};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+use saturno_math::random::PrimarySamples;
+use saturno_scene::actor::take_traversal_stats;
+use saturno_scene::actor::Aabb;
+use saturno_scene::actor::Hit;
+use saturno_scene::actor::Hittable;
+use saturno_scene::actor::HittableList;
+use saturno_scene::actor::RayKind;
+use saturno_scene::actor::RayTraceable;
+use saturno_scene::atmosphere::Atmosphere;
+use saturno_scene::black_hole::BlackHole;
+use saturno_scene::black_hole::Geodesic;
+use saturno_scene::camera::Camera;
+use saturno_scene::color;
+use saturno_scene::color::ColorSpace;
+use saturno_scene::fog::Fog;
+use saturno_scene::light::Emission;
+use saturno_scene::light::Light;
+use saturno_scene::light::LightSample;
+use saturno_scene::volume::EmissiveVolume;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+use std::vec::Vec;
+use tracing::instrument;
+
+/**
+ * Lights sampled directly at the bounce a ray comes from, that the ray
+ * must not gather again when it reaches them.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Sampled {
+    Nothing,
+    /**
+     * Every light that can be sampled along the ray.
+     */
+    Lights,
+    /**
+     * The lights, and the caustics they cast (see `Caustics`): after as
+     * many smooth refractions, every light but the environments.
+     */
+    Caustics(u32),
+}
+
+/**
+ * The scene to render: the world and the camera looking at it. How it
+ * is rendered is up to the `RenderSettings` passed to the render
+ * functions.
+ */
+pub struct Canvas {
+    pub world: HittableList,
+    /**
+     * Lights sampled at every diffuse bounce, besides the emissive actors.
+     */
+    pub lights: Vec<Arc<dyn Light>>,
+    /**
+     * Medium filling the space between the actors.
+     */
+    pub fog: Option<Fog>,
+    /**
+     * Air around a planet, scattering the light of the lights.
+     */
+    pub atmosphere: Option<Atmosphere>,
+    /**
+     * Mass bending the rays around it (gravitational lensing).
+     */
+    pub black_hole: Option<BlackHole>,
+    /**
+     * Glowing volumes (fire, explosions, nebulae), adding their light
+     * to the rays going through them.
+     */
+    pub volumes: Vec<EmissiveVolume>,
+    camera: Camera,
+    /**
+     * Where the origin of the canvas is in the world, in double precision
+     * whatever the precision of `Float` (see `recenter`).
+     */
+    world_origin: [f64; 3],
+    /**
+     * Records of the diffuse indirect light (see `IrradianceCaching`),
+     * kept from the first sample of a render to the last.
+     */
+    irradiance: IrradianceCache,
+    /**
+     * Estimates of the pixels over the passes of photon mapping (see
+     * `PhotonMapping`).
+     */
+    photons: Mutex<Option<PhotonMap>>,
+    /**
+     * Light learned by path guiding (see `PathGuiding`), before the
+     * first sample of a render.
+     */
+    guide: Guide,
+    /**
+     * Lights kept by the points of the surfaces for the points around
+     * them to reuse (see `ReservoirSampling`), from the first sample of a
+     * render to the last.
+     */
+    reservoirs: ReservoirGrid,
+    /**
+     * Hierarchy of the lights (see `LightHierarchy`), built when a render
+     * starts.
+     */
+    light_tree: RwLock<Option<LightTree>>,
+    /**
+     * Cost of the pixels for the cost-aware tile order (see
+     * `TileOrder::Cost`), probed at the first render of a region with
+     * the camera.
+     */
+    tile_costs: Mutex<Option<Arc<CostMap>>>,
+}
+
+/**
+ * Photons shot from the same random stream, for deterministic renders.
+ */
+const PHOTON_BLOCK: usize = 1024;
+
+/**
+ * First random stream of the photons, after those of the pixels.
+ */
+const PHOTON_STREAMS: u64 = 1 << 48;
+
+/**
+ * First random stream of the paths of Metropolis light transport, after
+ * those of the pixels and the photons.
+ */
+const METROPOLIS_STREAMS: u64 = 1 << 56;
+
+/**
+ * Seed of the pass `pass` of a progressive render, its samples drawn from
+ * streams of their own.
+ */
+fn pass_seed(seed: u64, pass: u32) -> u64 {
+    seed.wrapping_add((pass as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+}
+
+/**
+ * A scene together with the settings it is rendered with.
+ */
+pub struct Renderer {
+    pub canvas: Canvas,
+    pub settings: RenderSettings,
+}
+
+impl Renderer {
+    pub fn new(canvas: Canvas, settings: RenderSettings) -> Renderer {
+        Renderer { canvas, settings }
+    }
+
+    pub fn render(&self) -> Result<Image> {
+        self.canvas.render_scene(&self.settings)
+    }
+}
+
+impl Canvas {
+    #[instrument(
+        name = "build_scene",
+        level = "debug",
+        skip_all,
+        fields(actors = actors.len())
+    )]
+    pub fn new(actors: Vec<Box<dyn RayTraceable>>, camera: Camera) -> Canvas {
+        let mut world = HittableList::new(actors);
+        for actor in &mut world.actors {
+            actor.select_detail(&camera);
+        }
+
+        Canvas {
+            world,
+            lights: vec![],
+            fog: None,
+            atmosphere: None,
+            black_hole: None,
+            volumes: vec![],
+            camera,
+            irradiance: IrradianceCache::new(),
+            photons: Mutex::new(None),
+            guide: Guide::new(),
+            reservoirs: ReservoirGrid::new(),
+            light_tree: RwLock::new(None),
+            tile_costs: Mutex::new(None),
+            world_origin: [0.0; 3],
+        }
+    }
+
+    pub fn add_light(&mut self, light: Arc<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /**
+     * Set the camera, and the detail of the actors seen through it.
+     */
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        *self.tile_costs.lock().unwrap() = None;
+        self.select_detail();
+    }
+
+    /**
+     * Set the camera keeping the detail of the actors, returning the
+     * previous one.
+     */
+    pub(crate) fn swap_camera(&mut self, camera: Camera) -> Camera {
+        std::mem::replace(&mut self.camera, camera)
+    }
+
+    fn select_detail(&mut self) {
+        for actor in &mut self.world.actors {
+            actor.select_detail(&self.camera);
+        }
+    }
+
+    /**
+     * Move the world so the camera sits at the origin, for scenes far from
+     * it (e.g. of planetary scale): the rays then start at the origin and
+     * the actors around the camera have small coordinates, which keep
+     * their precision, instead of huge ones which round their differences
+     * off. Call it whenever the camera moves (per frame), `world_origin`
+     * keeps where the world went.
+     */
+    pub fn recenter(&mut self) {
+        let offset: [Float; 3] = [0, 1, 2].map(|i| -self.camera.origin[i]);
+        for actor in &mut self.world.actors {
+            actor.translate(&offset);
+        }
+        for light in &mut self.lights {
+            if let Some(moved) = light.translated(&offset) {
+                *light = moved;
+            }
+        }
+        if let Some(top) = self.fog.as_mut().and_then(|fog| fog.top.as_mut()) {
+            *top += offset[1];
+        }
+        let moved = |point: &mut [Float; 3]| {
+            *point = [0, 1, 2].map(|i| point[i] + offset[i]);
+        };
+        if let Some(atmosphere) = &mut self.atmosphere {
+            moved(&mut atmosphere.center);
+        }
+        if let Some(black_hole) = &mut self.black_hole {
+            moved(&mut black_hole.center);
+        }
+        for volume in &mut self.volumes {
+            moved(&mut volume.min);
+            moved(&mut volume.max);
+        }
+        self.camera.translate(&offset);
+        // Float is f32 with the `f32` feature.
+        #[allow(clippy::unnecessary_cast)]
+        for (origin, delta) in self.world_origin.iter_mut().zip(offset) {
+            *origin -= delta as f64;
+        }
+    }
+
+    /**
+     * Where the origin of the canvas is in the world, moved by `recenter`.
+     */
+    pub fn world_origin(&self) -> [f64; 3] {
+        self.world_origin
+    }
+
+    /**
+     * Counts and bounds of the contents of the scene, to check what was
+     * built or imported.
+     */
+    pub fn stats(&self) -> SceneStats {
+        SceneStats::new(self)
+    }
+
+    /**
+     *  Compute the background color based on the ray direction.
+     *  Use LERP (linear interpolation), to generate a gradient on the
+     *  y-direction (similar to front-to-back blending).
+     */
+    fn background_color(&self, ray: &Ray) -> Array1<Float> {
+        let dir = Vec4::normalize(ray.direction.clone());
+        let param_y: Float = 0.5 * (dir[1] + 1.0);
+
+        //let white = arr1(&[0.8, 0.8, 0.8, 0.9]);
+        //let blue = arr1(&[0.1, 0.2, 0.65, 0.9]);
+        let white = arr1(&[1.0, 1.0, 1.0, 1.0]);
+        let blue = arr1(&[0.5, 0.7, 1.0, 1.0]);
+        (1.0 - param_y) * white + param_y * blue
+    }
+
+    fn cast_rays(&self, settings: &RenderSettings, ray: &Ray) -> Array1<Float> {
+        self.trace_camera_ray(settings, ray, &mut |_, _| {}, &mut None)
+    }
+
+    /**
+     * Trace a camera ray and return its RGB color. In spectral mode the
+     * path samples its own wavelengths, and its radiance (and every
+     * splat) is converted back to RGB; the bounces handed to `record`
+     * keep the radiance at the sampled wavelengths.
+     */
+    fn trace_camera_ray<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        settings: &RenderSettings,
+        ray: &Ray,
+        splat: &mut F,
+        record: &mut Recorder,
+    ) -> Array1<Float> {
+        let throughput = arr1(&[1.0, 1.0, 1.0, 1.0]);
+        if !settings.spectral {
+            return self.trace(
+                settings,
+                ray,
+                1,
+                &throughput,
+                splat,
+                record,
+                None,
+                Sampled::Nothing,
+            );
+        }
+
+        let wavelengths = Wavelengths::sample();
+        let mut splat_rgb = |group: &str, value: Array1<Float>| {
+            splat(group, wavelengths.to_rgb(&value))
+        };
+        let color = self.trace(
+            settings,
+            ray,
+            1,
+            &throughput,
+            &mut splat_rgb,
+            record,
+            Some(&wavelengths),
+            Sampled::Nothing,
+        );
+        wavelengths.to_rgb(&color)
+    }
+
+    /**
+     * Trace a ray through the scene. The returned alpha is the surface
+     * coverage: 1.0 whenever the ray hits an actor, and 0.0 for
+     * escaped camera rays if the background is transparent.
+     *
+     * Every radiance contribution (emitters, background) is also handed
+     * to `splat`, weighted by the throughput of the path and tagged with
+     * the light group of its source. Every bounce is handed to `record`,
+     * if set. With `wavelengths`, the path carries the radiance at those
+     * wavelengths in its color channels. `lights_sampled` tells which
+     * lights were sampled at the bounce the ray comes from, so it must
+     * not gather them again.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn trace<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        settings: &RenderSettings,
+        ray: &Ray,
+        depth: u32,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        record: &mut Recorder,
+        wavelengths: Option<&Wavelengths>,
+        lights_sampled: Sampled,
+    ) -> Array1<Float> {
+        let current_hit = &mut Hit::new();
+
+        // Some of the reflected rays hit the object they are reflecting
+        // off of not at exactly t=0, but instead at t=-0.0000001 or
+        // t=0.00000001 or whatever floating point approximation the (sphere)
+        // intersector gives us. So we need to ignore hits very near zero and
+        // we do this by raising the minimum to 0.001.
+        let bent;
+        let (ray, hit) = match &self.black_hole {
+            None => {
+                // The first ray of a path is the camera's.
+                let kind = if depth == 1 {
+                    RayKind::Camera
+                } else {
+                    RayKind::Indirect
+                };
+                let hit = self.world.is_hit_by(
+                    kind,
+                    ray,
+                    0.0001,
+                    Float::MAX,
+                    current_hit,
+                );
+                (ray, hit)
+            }
+            // The ray goes on along the last chord of its geodesic.
+            Some(black_hole) => {
+                match black_hole.trace(ray, &self.world, 0.0001, current_hit) {
+                    Geodesic::Hit(ray) => {
+                        bent = ray;
+                        (&bent, true)
+                    }
+                    Geodesic::Escaped(ray) => {
+                        bent = ray;
+                        (&bent, false)
+                    }
+                    Geodesic::Captured => {
+                        if let Some(record) = record {
+                            record(Bounce {
+                                depth,
+                                event: BounceEvent::Captured,
+                                origin: vec3(&ray.origin),
+                                direction: vec3(&ray.direction),
+                                hit: None,
+                                throughput: vec3(throughput),
+                                attenuation: None,
+                                scattered: None,
+                                radiance: Some([0.0, 0.0, 0.0]),
+                                shadow: None,
+                            });
+                        }
+                        return arr1(&[0.0, 0.0, 0.0, 1.0]);
+                    }
+                }
+            }
+        };
+        current_hit.time = ray.time;
+
+        // The glowing volumes add their light on the way.
+        let mut emitted = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        if !self.volumes.is_empty() {
+            let t_max = if hit { current_hit.t } else { Float::INFINITY };
+            for volume in &self.volumes {
+                let [r, g, b] = volume.radiance(ray, t_max);
+                emitted += &arr1(&[r, g, b, 0.0]);
+            }
+            if let Some(wavelengths) = wavelengths {
+                emitted = wavelengths.uplift(&emitted);
+            }
+            splat(DEFAULT_LIGHT_GROUP, throughput * &emitted);
+        }
+
+        // The atmosphere dims whatever lies at the end of the ray and adds
+        // the light it scatters on the way.
+        if let Some(atmosphere) = &self.atmosphere {
+            let t_max = if hit { current_hit.t } else { Float::INFINITY };
+            if let Some(segment) = atmosphere.segment(ray, t_max, &self.lights)
+            {
+                let [r, g, b] = segment.transmittance;
+                let mut transmittance = arr1(&[r, g, b, 1.0]);
+                if let Some(wavelengths) = wavelengths {
+                    transmittance = wavelengths.uplift(&transmittance);
+                }
+                let mut gathered = arr1(&[0.0, 0.0, 0.0, 0.0]);
+                for (light, [r, g, b]) in
+                    self.lights.iter().zip(segment.in_scattering)
+                {
+                    let mut radiance = arr1(&[r, g, b, 0.0]);
+                    if let Some(wavelengths) = wavelengths {
+                        radiance = wavelengths.uplift(&radiance);
+                    }
+                    splat(&light_group(light.as_ref()), throughput * &radiance);
+                    gathered += &radiance;
+                }
+
+                let throughput = throughput * &transmittance;
+                let color = self.shade(
+                    settings,
+                    ray,
+                    hit,
+                    current_hit,
+                    depth,
+                    &throughput,
+                    splat,
+                    record,
+                    wavelengths,
+                    lights_sampled,
+                );
+                let alpha = color[3];
+                let mut color = color * &transmittance + gathered + emitted;
+                color[3] = alpha;
+                return color;
+            }
+        }
+
+        self.shade(
+            settings,
+            ray,
+            hit,
+            current_hit,
+            depth,
+            throughput,
+            splat,
+            record,
+            wavelengths,
+            lights_sampled,
+        ) + emitted
+    }
+
+    /**
+     * Color of `ray`, which hits `current_hit` if `hit`, or escapes.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn shade<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        settings: &RenderSettings,
+        ray: &Ray,
+        hit: bool,
+        current_hit: &mut Hit,
+        depth: u32,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        record: &mut Recorder,
+        wavelengths: Option<&Wavelengths>,
+        lights_sampled: Sampled,
+    ) -> Array1<Float> {
+        let bounce = |event| Bounce {
+            depth,
+            event,
+            origin: vec3(&ray.origin),
+            direction: vec3(&ray.direction),
+            hit: None,
+            throughput: vec3(throughput),
+            attenuation: None,
+            scattered: None,
+            radiance: None,
+            shadow: None,
+        };
+
+        // The fog may scatter the ray before it gets to the actor, unless
+        // it is inside a medium of its own.
+        if let Some(fog) = &self.fog {
+            let in_medium = hit
+                && current_hit.material.medium().is_some()
+                && ray.direction.dot(&current_hit.normal) > 0.0;
+            let t_max = if hit { current_hit.t } else { Float::INFINITY };
+            if !in_medium {
+                if let Some(t) = fog.sample_interaction(ray, t_max) {
+                    return self.scatter_in_fog(
+                        settings,
+                        fog,
+                        ray,
+                        t,
+                        depth,
+                        throughput,
+                        splat,
+                        record,
+                        wavelengths,
+                    );
+                }
+            }
+        }
+
+        // A holdout cuts a hole through the image.
+        let holdout = hit
+            && depth == 1
+            && self.world.actors[current_hit.actor_id].visibility().holdout;
+        if holdout {
+            if let Some(record) = record {
+                record(Bounce {
+                    hit: Some(HitRecord::new(current_hit)),
+                    radiance: Some([0.0, 0.0, 0.0]),
+                    ..bounce(BounceEvent::Holdout)
+                });
+            }
+            return arr1(&[0.0, 0.0, 0.0, 0.0]);
+        }
+
+        if hit {
+            current_hit.wavelength = wavelengths.map(|w| w.hero());
+
+            // Inside a medium the ray may scatter before it gets out.
+            if let Some(medium) = current_hit.material.medium() {
+                let length = Vec4::l2_norm(ray.direction.view());
+                if ray.direction.dot(&current_hit.normal) > 0.0 {
+                    let distance = medium.sample_distance();
+                    if distance < current_hit.t * length {
+                        return self.scatter_in_medium(
+                            settings,
+                            ray,
+                            current_hit,
+                            distance / length,
+                            depth,
+                            throughput,
+                            splat,
+                            record,
+                            wavelengths,
+                        );
+                    }
+                }
+            }
+            if let Some(strength) = current_hit.material.shadow_catcher() {
+                return self.catch_shadow(
+                    settings,
+                    ray,
+                    current_hit,
+                    strength,
+                    depth,
+                    throughput,
+                    splat,
+                    record,
+                    wavelengths,
+                    lights_sampled,
+                );
+            }
+
+            let mut attenuation = arr1(&[0.0, 0.0, 0.0, 1.0]);
+            let mut scattered = Ray::new(
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, 0.0, 0.0]),
+            );
+
+            let scatters = current_hit.material.scatter(
+                ray,
+                current_hit,
+                &mut attenuation,
+                &mut scattered,
+                depth,
+            );
+            // Diffuse bounces may follow the light learned by path
+            // guiding.
+            let guided = match &settings.guiding {
+                Some(guiding) if scatters => self.guide_bounce(
+                    guiding,
+                    current_hit,
+                    &mut attenuation,
+                    &mut scattered,
+                ),
+                _ => None,
+            };
+            scattered.time = ray.time;
+            let direct = if scatters {
+                self.sample_lights(
+                    settings,
+                    current_hit,
+                    throughput,
+                    splat,
+                    wavelengths,
+                )
+            } else {
+                None
+            };
+
+            let mut wavelengths = wavelengths.copied();
+            if let Some(spectrum) = &mut wavelengths {
+                attenuation = spectrum.uplift(&attenuation);
+                if current_hit.material.dispersive() {
+                    *spectrum = spectrum.terminate_secondary(&mut attenuation);
+                }
+            }
+
+            let mut color = if scatters && depth < settings.max_depth {
+                if let Some(record) = record {
+                    record(Bounce {
+                        hit: Some(HitRecord::new(current_hit)),
+                        attenuation: Some(vec3(&attenuation)),
+                        scattered: Some(vec3(&scattered.direction)),
+                        ..bounce(BounceEvent::Scattered)
+                    });
+                }
+
+                // Through the glass, the lights were sampled along with
+                // the caustics they cast.
+                let refracted =
+                    current_hit.material.refraction(current_hit).is_some()
+                        && ray.direction.dot(&current_hit.normal)
+                            * scattered.direction.dot(&current_hit.normal)
+                            > 0.0;
+                let sampled = match (
+                    direct.is_some(),
+                    lights_sampled,
+                    &settings.caustics,
+                ) {
+                    (true, _, Some(_)) => Sampled::Caustics(0),
+                    (true, _, None) => Sampled::Lights,
+                    (false, Sampled::Caustics(vertices), Some(caustics))
+                        if refracted && vertices < caustics.max_vertices =>
+                    {
+                        Sampled::Caustics(vertices + 1)
+                    }
+                    _ => Sampled::Nothing,
+                };
+
+                // Seen from the camera, the indirect light of diffuse
+                // surfaces may come from the irradiance cache.
+                let cached = match (
+                    &settings.irradiance_cache,
+                    current_hit.material.diffuse(current_hit),
+                ) {
+                    (Some(caching), Some(albedo)) if depth == 1 => Some((
+                        albedo,
+                        self.cached_irradiance(
+                            settings,
+                            caching,
+                            ray,
+                            current_hit,
+                            sampled,
+                        ),
+                    )),
+                    _ => None,
+                };
+
+                match cached {
+                    Some((albedo, [r, g, b])) => {
+                        let mut radiance = albedo * arr1(&[r, g, b, 0.0]);
+                        if let Some(wavelengths) = &wavelengths {
+                            radiance = wavelengths.uplift(&radiance);
+                        }
+                        splat(DEFAULT_LIGHT_GROUP, throughput * &radiance);
+                        radiance
+                    }
+                    None => {
+                        let throughput = throughput * &attenuation;
+                        let incoming = self.trace(
+                            settings,
+                            &scattered,
+                            depth + 1,
+                            &throughput,
+                            splat,
+                            record,
+                            wavelengths.as_ref(),
+                            sampled,
+                        );
+                        if let Some(density) = guided {
+                            let point = &current_hit.point;
+                            let direction =
+                                Vec4::normalize(scattered.direction.clone());
+                            self.guide.record(
+                                &[point[0], point[1], point[2]],
+                                &[direction[0], direction[1], direction[2]],
+                                (incoming[0] + incoming[1] + incoming[2]) / 3.0,
+                                density,
+                            );
+                        }
+                        attenuation * incoming
+                    }
+                }
+            } else {
+                let mut color = if lights_sampled != Sampled::Nothing
+                    && current_hit.material.sampled_light()
+                {
+                    arr1(&[0.0, 0.0, 0.0, 1.0])
+                } else {
+                    current_hit.material.color_noscatter(current_hit)
+                };
+                if let Some(wavelengths) = wavelengths {
+                    color = wavelengths.uplift(&color);
+                }
+                if let Some(record) = record {
+                    let event = if scatters {
+                        BounceEvent::MaxDepth
+                    } else {
+                        BounceEvent::Absorbed
+                    };
+                    record(Bounce {
+                        hit: Some(HitRecord::new(current_hit)),
+                        radiance: Some(vec3(&color)),
+                        ..bounce(event)
+                    });
+                }
+
+                let group = current_hit
+                    .material
+                    .light_group()
+                    .unwrap_or_else(|| DEFAULT_LIGHT_GROUP.to_string());
+                splat(&group, throughput * &color);
+                color
+            };
+            if let Some(direct) = direct {
+                color += &direct;
+            }
+
+            color[3] = 1.0;
+            color
+        } else if settings.transparent_background && depth == 1 {
+            if let Some(record) = record {
+                record(Bounce {
+                    radiance: Some([0.0, 0.0, 0.0]),
+                    ..bounce(BounceEvent::Escaped)
+                });
+            }
+            arr1(&[0.0, 0.0, 0.0, 0.0])
+        } else {
+            let mut color = if self.atmosphere.is_some()
+                || self.lights.iter().any(|l| l.environment())
+            {
+                arr1(&[0.0, 0.0, 0.0, 1.0])
+            } else {
+                let mut color = self.background_color(ray);
+                if let Some(wavelengths) = wavelengths {
+                    color = wavelengths.uplift(&color);
+                }
+                splat(BACKGROUND_LIGHT_GROUP, throughput * &color);
+                color
+            };
+
+            // Lights of some size (the sun disk, stars, the environment),
+            // unless they were sampled along this direction at the
+            // previous bounce.
+            for light in &self.lights {
+                let gathered = match lights_sampled {
+                    Sampled::Nothing => false,
+                    Sampled::Lights => {
+                        light.can_sample(&ray.origin, &ray.direction)
+                    }
+                    Sampled::Caustics(0) => {
+                        light.can_sample(&ray.origin, &ray.direction)
+                    }
+                    Sampled::Caustics(_) => !light.environment(),
+                };
+                if !gathered {
+                    if let Some(mut radiance) =
+                        light.emitted(&ray.origin, &ray.direction)
+                    {
+                        if let Some(wavelengths) = wavelengths {
+                            radiance = wavelengths.uplift(&radiance);
+                        }
+                        splat(
+                            &light_group(light.as_ref()),
+                            throughput * &radiance,
+                        );
+                        color += &radiance;
+                    }
+                }
+            }
+            if let Some(record) = record {
+                record(Bounce {
+                    radiance: Some(vec3(&color)),
+                    ..bounce(BounceEvent::Escaped)
+                });
+            }
+            color
+        }
+    }
+
+    /**
+     * The ray scatters in the medium of the actor it would exit through
+     * (`hit`), at parameter `t`: it goes on in a new direction from there,
+     * attenuated by the albedo of the medium. Actors filled with a medium
+     * are assumed closed and not to overlap others.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn scatter_in_medium<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        settings: &RenderSettings,
+        ray: &Ray,
+        hit: &Hit,
+        t: Float,
+        depth: u32,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        record: &mut Recorder,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Array1<Float> {
+        let medium = hit.material.medium().unwrap();
+        let point = ray.point_at_parameter(t);
+        let mut attenuation = medium.albedo.clone();
+        attenuation[3] = 1.0;
+        if let Some(wavelengths) = wavelengths {
+            attenuation = wavelengths.uplift(&attenuation);
+        }
+        let scattered = Ray::new(point.clone(), medium.sample_direction())
+            .at_time(ray.time);
+
+        let mut bounce = |event, scattered: Option<&Ray>| {
+            if let Some(record) = record {
+                record(Bounce {
+                    depth,
+                    event,
+                    origin: vec3(&ray.origin),
+                    direction: vec3(&ray.direction),
+                    hit: Some(HitRecord {
+                        t,
+                        point: vec3(&point),
+                        normal: [0.0, 0.0, 0.0],
+                        actor: hit.actor_id,
+                        material: hit.material.name(),
+                    }),
+                    throughput: vec3(throughput),
+                    attenuation: scattered.map(|_| vec3(&attenuation)),
+                    scattered: scattered.map(|ray| vec3(&ray.direction)),
+                    radiance: None,
+                    shadow: None,
+                });
+            }
+        };
+
+        if depth >= settings.max_depth {
+            bounce(BounceEvent::MaxDepth, None);
+            return arr1(&[0.0, 0.0, 0.0, 1.0]);
+        }
+        bounce(BounceEvent::Volume, Some(&scattered));
+
+        let throughput = throughput * &attenuation;
+        let mut color = attenuation
+            * self.trace(
+                settings,
+                &scattered,
+                depth + 1,
+                &throughput,
+                splat,
+                record,
+                wavelengths,
+                Sampled::Nothing,
+            );
+        color[3] = 1.0;
+        color
+    }
+
+    /**
+     * Light reaching the hit straight from the lights, through its
+     * material (next event estimation): a shadow ray towards a direction
+     * sampled on every light. Every contribution is also splatted to the
+     * light group of its light. None if the material does not sample
+     * lights.
+     */
+    fn sample_lights<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        settings: &RenderSettings,
+        hit: &Hit,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Option<Array1<Float>> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        hit.material.evaluate(hit, &hit.normal)?;
+
+        let mut direct = match &settings.reservoirs {
+            Some(resampling) => self.resampled_light(
+                resampling,
+                hit,
+                throughput,
+                splat,
+                wavelengths,
+            ),
+            None => self.direct_light(
+                settings,
+                &hit.point,
+                hit.time,
+                |direction| hit.material.evaluate(hit, direction),
+                throughput,
+                splat,
+                wavelengths,
+            ),
+        };
+        if let Some(caustics) = &settings.caustics {
+            direct += &self.caustic_light(
+                caustics,
+                hit,
+                throughput,
+                splat,
+                wavelengths,
+            );
+        }
+        Some(direct)
+    }
+
+    /**
+     * Mean radiance reaching the diffuse `hit` of `ray` (in RGB), from the
+     * records of the irradiance cache around it, or from a new record if
+     * there are none close enough. The rays of a new record trace full
+     * paths, which do not gather the lights of `sampled` again, and their
+     * contributions are not split into light groups: the cached light
+     * all goes to the default group.
+     *
+     * With several threads, which records get made depends on the order
+     * the pixels are rendered in, so the images differ slightly from run
+     * to run.
+     */
+    fn cached_irradiance(
+        &self,
+        settings: &RenderSettings,
+        caching: &IrradianceCaching,
+        ray: &Ray,
+        hit: &Hit,
+        sampled: Sampled,
+    ) -> [Float; 3] {
+        let point = [hit.point[0], hit.point[1], hit.point[2]];
+        let mut normal = Vec4::normalize(hit.normal.clone());
+        if ray.direction.dot(&normal) > 0.0 {
+            normal = -normal;
+        }
+        let normal = [normal[0], normal[1], normal[2]];
+        if let Some(radiance) =
+            self.irradiance.lookup(&point, &normal, caching.accuracy)
+        {
+            return radiance;
+        }
+
+        let throughput = arr1(&[1.0, 1.0, 1.0, 1.0]);
+        let record = Record::sample(
+            point,
+            normal,
+            caching.rays,
+            caching.min_radius,
+            caching.max_radius,
+            |d| {
+                let ray =
+                    Ray::new(hit.point.clone(), arr1(&[d[0], d[1], d[2], 0.0]))
+                        .at_time(hit.time);
+                let mut first = Hit::new();
+                let distance = if self.world.is_hit(
+                    &ray,
+                    0.0001,
+                    Float::MAX,
+                    &mut first,
+                ) {
+                    first.t
+                } else {
+                    Float::INFINITY
+                };
+                let color = self.trace(
+                    settings,
+                    &ray,
+                    2,
+                    &throughput,
+                    &mut |_, _| {},
+                    &mut None,
+                    None,
+                    sampled,
+                );
+                ([color[0], color[1], color[2]], distance)
+            },
+        );
+        let radiance = record.radiance;
+        self.irradiance.insert(record, caching.accuracy);
+        radiance
+    }
+
+    /**
+     * Bounce off a diffuse surface (see `PathGuiding`): where light was
+     * learned, the bounce heads along a direction drawn from it with
+     * probability `1 - bsdf_fraction`, and is weighed by the density of
+     * both strategies (one-sample MIS). Returns that density while the
+     * guide is learning, for the light found along the bounce to be
+     * recorded. Other materials bounce as they scatter.
+     */
+    fn guide_bounce(
+        &self,
+        guiding: &PathGuiding,
+        hit: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+    ) -> Option<Float> {
+        hit.material.diffuse(hit)?;
+        let point = [hit.point[0], hit.point[1], hit.point[2]];
+        let guides = self.guide.guides(&point);
+        if !guides && !self.guide.learning() {
+            return None;
+        }
+        if guides && random::gen_range(0.0, 1.0) >= guiding.bsdf_fraction {
+            if let Some((d, _)) = self.guide.sample(&point) {
+                *scattered = Ray::new(
+                    scattered.origin.clone(),
+                    arr1(&[d[0], d[1], d[2], 0.0]),
+                )
+                .at_time(hit.time);
+            }
+        }
+
+        let direction = Vec4::normalize(scattered.direction.clone());
+        let normal = Vec4::normalize(hit.normal.clone());
+        let cosine = normal.dot(&direction).max(0.0) / consts::PI;
+        let density = if guides {
+            let d = [direction[0], direction[1], direction[2]];
+            guiding.bsdf_fraction * cosine
+                + (1.0 - guiding.bsdf_fraction) * self.guide.density(&point, &d)
+        } else {
+            cosine
+        };
+        let weight = if density > 0.0 { cosine / density } else { 0.0 };
+        for c in 0..3 {
+            attenuation[c] *= weight;
+        }
+        self.guide.learning().then_some(density)
+    }
+
+    /**
+     * Light reaching the hit from the lights through the glass in the
+     * way (see `Caustics`), through its material.
+     */
+    fn caustic_light<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        caustics: &Caustics,
+        hit: &Hit,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Array1<Float> {
+        let mut total = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        let origin = [hit.point[0], hit.point[1], hit.point[2]];
+        let wavelength = wavelengths.map(|w| w.hero());
+        for light in self.lights.iter().filter(|l| !l.environment()) {
+            let sample = match light.sample(&hit.point) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            // Only the lights behind glass cast caustics.
+            let straight =
+                Ray::new(hit.point.clone(), sample.direction.clone())
+                    .at_time(hit.time);
+            let mut blocker = Hit::new();
+            blocker.wavelength = wavelength;
+            if !self.world.is_hit_by(
+                RayKind::Shadow,
+                &straight,
+                0.0001,
+                sample.distance,
+                &mut blocker,
+            ) || blocker.material.refraction(&blocker).is_none()
+            {
+                continue;
+            }
+
+            let d = &sample.direction;
+            let target = if sample.distance.is_finite() {
+                Target::Point(
+                    [0, 1, 2].map(|i| hit.point[i] + sample.distance * d[i]),
+                )
+            } else {
+                Target::Direction([d[0], d[1], d[2]])
+            };
+            let connection = match caustics::connect(
+                &self.world,
+                &origin,
+                &target,
+                caustics,
+                wavelength,
+            ) {
+                Some(connection) => connection,
+                None => continue,
+            };
+            let c = connection.direction;
+            let reflected = match hit
+                .material
+                .evaluate(hit, &arr1(&[c[0], c[1], c[2], 0.0]))
+            {
+                Some(reflected) => reflected,
+                None => continue,
+            };
+
+            // Irradiance of the light, spread by the glass: a point light
+            // seen from the last refraction, of intensity radiance d^2.
+            let irradiance = match target {
+                Target::Point(position) => {
+                    let l = connection.last;
+                    let last = arr1(&[l[0], l[1], l[2], 1.0]);
+                    let seen = match light.sample(&last) {
+                        Some(seen) => seen,
+                        None => continue,
+                    };
+                    let squared = (0..3)
+                        .map(|i| (position[i] - l[i]).powi(2))
+                        .sum::<Float>();
+                    seen.radiance * squared
+                }
+                Target::Direction(_) => sample.radiance.clone(),
+            } / connection.spread;
+
+            let t = connection.transmittance;
+            let mut radiance =
+                reflected * &irradiance * &arr1(&[t[0], t[1], t[2], 0.0]);
+            if let Some(wavelengths) = wavelengths {
+                radiance = wavelengths.uplift(&radiance);
+            }
+            splat(&light_group(light.as_ref()), throughput * &radiance);
+            total += &radiance;
+        }
+        total
+    }
+
+    /**
+     * Light of every light reaching `point` unoccluded at `time`, through
+     * the fog, reflected (or scattered) along the path by `evaluate` (the
+     * BSDF or the phase function, cosine included, None if it does not
+     * apply).
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn direct_light<E, F>(
+        &self,
+        settings: &RenderSettings,
+        point: &Array1<Float>,
+        time: Float,
+        evaluate: E,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Array1<Float>
+    where
+        E: Fn(&Array1<Float>) -> Option<Array1<Float>>,
+        F: FnMut(&str, Array1<Float>),
+    {
+        // With the hierarchy, the lights with a position are picked from
+        // it, each weighed by the probability of picking it.
+        let tree = self.light_tree.read().unwrap();
+        let picked: Vec<(&Arc<dyn Light>, Float)> =
+            match (&settings.light_hierarchy, tree.as_ref()) {
+                (Some(hierarchy), Some(tree)) => {
+                    let at = [point[0], point[1], point[2]];
+                    let samples = hierarchy.samples as Float;
+                    let mut picked: Vec<_> = tree
+                        .distant()
+                        .iter()
+                        .map(|light| (light, 1.0))
+                        .collect();
+                    for _ in 0..hierarchy.samples {
+                        if let Some((light, probability)) = tree.sample(&at) {
+                            picked.push((light, 1.0 / (probability * samples)));
+                        }
+                    }
+                    picked
+                }
+                _ => self.lights.iter().map(|light| (light, 1.0)).collect(),
+            };
+
+        let mut total = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        for (light, weight) in picked {
+            let sample = match light.sample(point) {
+                Some(sample) => sample,
+                None => continue,
+            };
+            let reflected = match evaluate(&sample.direction) {
+                Some(reflected) => reflected,
+                None => continue,
+            };
+            if reflected.iter().take(3).all(|c| *c <= 0.0) {
+                continue;
+            }
+
+            let transmittance = match self.unoccluded(point, time, &sample) {
+                Some(transmittance) => transmittance,
+                None => continue,
+            };
+
+            let mut radiance =
+                reflected * transmittance * &sample.radiance * weight;
+            if let Some(wavelengths) = wavelengths {
+                radiance = wavelengths.uplift(&radiance);
+            }
+            splat(&light_group(light.as_ref()), throughput * &radiance);
+            total += &radiance;
+        }
+        total
+    }
+
+    /**
+     * Transmittance (through the fog and the atmosphere) of the shadow
+     * ray from `point` towards the light of `sample` at `time`, None if an
+     * actor is in the way.
+     */
+    fn unoccluded(
+        &self,
+        point: &Array1<Float>,
+        time: Float,
+        sample: &LightSample,
+    ) -> Option<Array1<Float>> {
+        let shadow =
+            Ray::new(point.clone(), sample.direction.clone()).at_time(time);
+        let mut occluder = Hit::new();
+        if self.world.is_hit_by(
+            RayKind::Shadow,
+            &shadow,
+            0.0001,
+            sample.distance,
+            &mut occluder,
+        ) && occluder.material.shadow_catcher().is_none()
+        {
+            return None;
+        }
+        let transmittance = match &self.fog {
+            Some(fog) => fog.transmittance(&shadow, sample.distance),
+            None => 1.0,
+        };
+        if transmittance <= 0.0 {
+            return None;
+        }
+        let mut transmittance = arr1(&[1.0, 1.0, 1.0, 1.0]) * transmittance;
+        if let Some(atmosphere) = &self.atmosphere {
+            let [r, g, b] = atmosphere.transmittance(&shadow, sample.distance);
+            transmittance *= &arr1(&[r, g, b, 1.0]);
+        }
+        Some(transmittance)
+    }
+
+    /**
+     * Light reaching the hit from a light resampled among a few picked
+     * at random and those kept around it (see `ReservoirSampling`),
+     * through its material: one shadow ray. The light kept is left for
+     * the points around to reuse.
+     */
+    fn resampled_light<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        resampling: &ReservoirSampling,
+        hit: &Hit,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Array1<Float> {
+        let point = [hit.point[0], hit.point[1], hit.point[2]];
+        let normal = Vec4::normalize(hit.normal.clone());
+        let normal = [normal[0], normal[1], normal[2]];
+        // The light a light reflects at the hit, and how much (its
+        // luminance): the target of the resampling.
+        let target = |light: usize| {
+            let sample = self.lights[light].sample(&hit.point)?;
+            let reflected = hit.material.evaluate(hit, &sample.direction)?;
+            let radiance = reflected * &sample.radiance;
+            let luminance = 0.2126 * radiance[0]
+                + 0.7152 * radiance[1]
+                + 0.0722 * radiance[2];
+            (luminance > 0.0).then_some((sample, radiance, luminance))
+        };
+
+        let lights = self.lights.len();
+        let mut stream = Stream::new();
+        for _ in 0..resampling.candidates {
+            let light = ((random::gen_range(0.0, 1.0) * lights as Float)
+                as usize)
+                .min(lights - 1);
+            match target(light) {
+                Some((sample, radiance, luminance)) => stream.add(
+                    light,
+                    (sample, radiance),
+                    luminance,
+                    luminance * lights as Float,
+                    1.0,
+                ),
+                None => stream.skip(1.0),
+            }
+        }
+        let reused =
+            self.reservoirs
+                .around(&point, &normal, resampling.reuse as usize);
+        for reservoir in reused {
+            match target(reservoir.light) {
+                Some((sample, radiance, luminance)) => stream.add(
+                    reservoir.light,
+                    (sample, radiance),
+                    luminance,
+                    luminance * reservoir.weight * reservoir.count,
+                    reservoir.count,
+                ),
+                None => stream.skip(reservoir.count),
+            }
+        }
+
+        let mut total = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        let Some(((sample, radiance), mut reservoir)) = stream.finish() else {
+            return total;
+        };
+        match self.unoccluded(&hit.point, hit.time, &sample) {
+            Some(transmittance) => {
+                let mut radiance = radiance * transmittance * reservoir.weight;
+                if let Some(wavelengths) = wavelengths {
+                    radiance = wavelengths.uplift(&radiance);
+                }
+                let light = &self.lights[reservoir.light];
+                splat(&light_group(light.as_ref()), throughput * &radiance);
+                total += &radiance;
+            }
+            None => reservoir.weight = 0.0,
+        }
+        let history = (resampling.history * resampling.candidates) as Float;
+        reservoir.count = reservoir.count.min(history);
+        self.reservoirs.keep(
+            &point,
+            &normal,
+            reservoir,
+            2 * resampling.reuse as usize,
+        );
+        total
+    }
+
+    /**
+     * The ray interacts with the fog at parameter `t`: the lights are
+     * sampled from there, and the ray goes on in a direction picked by
+     * the phase function, attenuated by the albedo of the fog.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn scatter_in_fog<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        settings: &RenderSettings,
+        fog: &Fog,
+        ray: &Ray,
+        t: Float,
+        depth: u32,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        record: &mut Recorder,
+        wavelengths: Option<&Wavelengths>,
+    ) -> Array1<Float> {
+        let point = ray.point_at_parameter(t);
+        let incoming = Vec4::normalize(ray.direction.clone());
+        let a = fog.albedo;
+        let mut albedo = arr1(&[a[0], a[1], a[2], 1.0]);
+        if let Some(wavelengths) = wavelengths {
+            albedo = wavelengths.uplift(&albedo);
+        }
+        let scattered =
+            Ray::new(point.clone(), fog.sample_direction(&incoming))
+                .at_time(ray.time);
+
+        let mut bounce = |event, scattered: Option<&Ray>| {
+            if let Some(record) = record {
+                record(Bounce {
+                    depth,
+                    event,
+                    origin: vec3(&ray.origin),
+                    direction: vec3(&ray.direction),
+                    // Not on an actor, the next bounce starts at the point.
+                    hit: None,
+                    throughput: vec3(throughput),
+                    attenuation: scattered.map(|_| vec3(&albedo)),
+                    scattered: scattered.map(|ray| vec3(&ray.direction)),
+                    radiance: None,
+                    shadow: None,
+                });
+            }
+        };
+
+        if depth >= settings.max_depth {
+            bounce(BounceEvent::MaxDepth, None);
+            return arr1(&[0.0, 0.0, 0.0, 1.0]);
+        }
+        bounce(BounceEvent::Volume, Some(&scattered));
+
+        let throughput = throughput * &albedo;
+        let direct = self.direct_light(
+            settings,
+            &point,
+            ray.time,
+            |direction| {
+                let phase = fog.phase(incoming.dot(direction));
+                Some(arr1(&[phase, phase, phase, 0.0]))
+            },
+            &throughput,
+            splat,
+            wavelengths,
+        );
+        let mut color = albedo.clone()
+            * (self.trace(
+                settings,
+                &scattered,
+                depth + 1,
+                &throughput,
+                splat,
+                record,
+                wavelengths,
+                if self.lights.is_empty() {
+                    Sampled::Nothing
+                } else {
+                    Sampled::Lights
+                },
+            ) + direct);
+        color[3] = 1.0;
+        color
+    }
+
+    /**
+     * The ray continues through the shadow catcher, and a black layer
+     * whose alpha is the occlusion of the catcher's hemisphere is
+     * composited (over) on top of what lies behind. With a transparent
+     * background only the shadow itself remains.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn catch_shadow<F: FnMut(&str, Array1<Float>)>(
+        &self,
+        settings: &RenderSettings,
+        ray: &Ray,
+        hit: &Hit,
+        strength: Float,
+        depth: u32,
+        throughput: &Array1<Float>,
+        splat: &mut F,
+        record: &mut Recorder,
+        wavelengths: Option<&Wavelengths>,
+        lights_sampled: Sampled,
+    ) -> Array1<Float> {
+        let mut attenuation = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        let mut probe =
+            Ray::new(arr1(&[0.0, 0.0, 0.0, 1.0]), arr1(&[0.0, 0.0, 0.0, 0.0]));
+        hit.material
+            .scatter(ray, hit, &mut attenuation, &mut probe, depth);
+        probe.time = ray.time;
+
+        let mut occluder = Hit::new();
+        let occluded = self.world.is_hit_by(
+            RayKind::Shadow,
+            &probe,
+            0.0001,
+            Float::MAX,
+            &mut occluder,
+        ) && occluder.material.shadow_catcher().is_none();
+
+        let shadow = if occluded {
+            strength.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        if let Some(record) = record {
+            record(Bounce {
+                depth,
+                event: BounceEvent::ShadowCatcher,
+                origin: vec3(&ray.origin),
+                direction: vec3(&ray.direction),
+                hit: Some(HitRecord::new(hit)),
+                throughput: vec3(throughput),
+                attenuation: None,
+                scattered: None,
+                radiance: None,
+                shadow: Some(shadow),
+            });
+        }
+
+        let mut passed = Ray::new(hit.point.clone(), ray.direction.clone())
+            .at_time(ray.time);
+        passed.differentials = ray.differentials.clone();
+        let mut color = self.trace(
+            settings,
+            &passed,
+            depth,
+            &(throughput * (1.0 - shadow)),
+            splat,
+            record,
+            wavelengths,
+            lights_sampled,
+        );
+
+        let alpha = color[3];
+        color *= 1.0 - shadow;
+        color[3] = shadow + alpha * (1.0 - shadow);
+
+        color
+    }
+
+    /**
+     * Toon shading of the surface a camera ray sees: the base color of
+     * its material in flat bands of light (see `Toon::shade`), inked
+     * where it is seen at a grazing angle. Emitters keep their color.
+     */
+    fn toon_sample(
+        &self,
+        settings: &RenderSettings,
+        toon: &Toon,
+        ray: &Ray,
+    ) -> Array1<Float> {
+        let mut hit = Hit::new();
+        if !self.world.is_hit_by(
+            RayKind::Camera,
+            ray,
+            0.0001,
+            Float::MAX,
+            &mut hit,
+        ) {
+            if settings.transparent_background {
+                return arr1(&[0.0, 0.0, 0.0, 0.0]);
+            }
+            return self.background_color(ray);
+        }
+
+        let mut attenuation = arr1(&[0.0, 0.0, 0.0, 1.0]);
+        let mut scattered =
+            Ray::new(arr1(&[0.0, 0.0, 0.0, 1.0]), arr1(&[0.0, 0.0, 0.0, 0.0]));
+        if !hit
+            .material
+            .scatter(ray, &hit, &mut attenuation, &mut scattered, 1)
+        {
+            let mut color = hit.material.color_noscatter(&hit);
+            color[3] = 1.0;
+            return color;
+        }
+
+        // Shade the side of the surface facing the camera.
+        let view = Vec4::normalize(ray.direction.clone());
+        let mut normal = [hit.normal[0], hit.normal[1], hit.normal[2]];
+        let mut facing = -(0..3).map(|i| normal[i] * view[i]).sum::<Float>();
+        if facing < 0.0 {
+            normal = normal.map(|x| -x);
+            facing = -facing;
+        }
+        if facing < toon.silhouette {
+            return arr1(&[toon.ink[0], toon.ink[1], toon.ink[2], 1.0]);
+        }
+
+        let light = toon.light_direction();
+        let mut cosine: Float = (0..3).map(|i| normal[i] * light[i]).sum();
+        if toon.shadows && cosine > 0.0 {
+            let shadow_ray = Ray::new(
+                hit.point.clone(),
+                arr1(&[light[0], light[1], light[2], 0.0]),
+            )
+            .at_time(ray.time);
+            if self.world.is_hit_by(
+                RayKind::Shadow,
+                &shadow_ray,
+                0.0001,
+                Float::MAX,
+                &mut Hit::new(),
+            ) {
+                cosine = 0.0;
+            }
+        }
+
+        let mut color = hit.material.color(&hit) * toon.shade(cosine);
+        color[3] = 1.0;
+        color
+    }
+
+    /**
+     * Whether the pixel (x, y) lies on an outline: the depth, actor or
+     * normal seen through its center differs too much from one of its
+     * neighbors. Depth edges are inked on the nearer side, and creases
+     * on the left and top side, so outlines are one pixel wide.
+     */
+    fn toon_outline(
+        &self,
+        settings: &RenderSettings,
+        toon: &Toon,
+        x: u32,
+        y: u32,
+    ) -> bool {
+        let surface = |x: u32, y: u32| {
+            let ray = self
+                .camera
+                .get_center_ray(x as Float + 0.5, y as Float + 0.5);
+            let mut hit = Hit::new();
+            if self.world.is_hit_by(
+                RayKind::Camera,
+                &ray,
+                0.0001,
+                Float::MAX,
+                &mut hit,
+            ) {
+                let point = [hit.point[0], hit.point[1], hit.point[2]];
+                let normal = [hit.normal[0], hit.normal[1], hit.normal[2]];
+                Some((self.camera.view_depth(&point), normal, hit.actor_id))
+            } else {
+                None
+            }
+        };
+
+        let center = match surface(x, y) {
+            Some(center) => center,
+            None => return false,
+        };
+        let crease = toon.crease_angle.to_radians().cos();
+        let neighbors = [
+            (x.wrapping_sub(1), y, false),
+            (x + 1, y, true),
+            (x, y.wrapping_sub(1), false),
+            (x, y + 1, true),
+        ];
+        neighbors.iter().any(|&(nx, ny, after)| {
+            if nx >= settings.width || ny >= settings.height {
+                return false;
+            }
+            let (depth, normal, actor) = match surface(nx, ny) {
+                Some(neighbor) => neighbor,
+                None => return true,
+            };
+            let cosine: Float = (0..3).map(|i| normal[i] * center.1[i]).sum();
+            if actor == center.2 && after && cosine < crease {
+                return true;
+            }
+            center.0 <= depth
+                && (actor != center.2
+                    || depth - center.0 > toon.depth_threshold * center.0)
+        })
+    }
+
+    /**
+     * Run `shade` on every pixel (x, y) of the rendered region, spread
+     * over the worker threads, and collect the results in scanline
+     * order. Each thread starts with its tiles of the region (see
+     * `tiles`) and then steals from the others. Renders at low priority
+     * always use worker threads, so the priority of the calling thread
+     * is left alone.
+     */
+    fn render_pixels<T, F>(&self, settings: &RenderSettings, shade: F) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(u32, u32) -> T + Sync,
+    {
+        let region = settings.region();
+        let count = region.width as usize * region.height as usize;
+        let threads = settings.thread_count().clamp(1, count.max(1));
+        let costs = (settings.tile_order == TileOrder::Cost)
+            .then(|| self.tile_costs(settings));
+        let schedule = tiles::schedule(
+            &region,
+            settings.tile_size,
+            settings.tile_order,
+            threads,
+            costs.as_deref(),
+        );
+        let queues = WorkQueues::new(&schedule);
+
+        let shade_pixels = |t: usize| -> Vec<((u32, u32), T)> {
+            let mut values = vec![];
+            while let Some((x, y)) = queues.next(t) {
+                self.seed_pixel(settings, x, y);
+                values.push(((x, y), shade(x, y)));
+            }
+            random::unseed();
+            values
+        };
+
+        let low_priority = settings.priority == ThreadPriority::Low
+            && !cfg!(target_arch = "wasm32");
+        let shaded: Vec<Vec<_>> = if schedule.len() == 1 && !low_priority {
+            vec![shade_pixels(0)]
+        } else {
+            // Workers trace under the span of the render that spawned them.
+            let parent = tracing::Span::current();
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..schedule.len())
+                    .map(|t| {
+                        let shade_pixels = &shade_pixels;
+                        let span =
+                            tracing::trace_span!(parent: &parent, "worker", t);
+                        scope.spawn(move || {
+                            let _entered = span.entered();
+                            settings.priority.apply();
+                            shade_pixels(t)
+                        })
+                    })
+                    .collect();
+
+                workers
+                    .into_iter()
+                    .map(|worker| worker.join().unwrap())
+                    .collect()
+            })
+        };
+        tracing::debug!(steals = queues.steals(), "tiles rendered");
+
+        let mut pixels: Vec<Option<T>> = (0..count).map(|_| None).collect();
+        for ((x, y), value) in shaded.into_iter().flatten() {
+            let i = (y - region.y) as usize * region.width as usize
+                + (x - region.x) as usize;
+            pixels[i] = Some(value);
+        }
+        pixels.into_iter().map(Option::unwrap).collect()
+    }
+
+    /**
+     * Cost of the pixels of the rendered region for the cost-aware tile
+     * order: the traversal cost (rays, visited nodes and intersection
+     * tests) of a sample of a pixel every quarter of a tile. Kept for the
+     * renders after the first, the passes of a progressive render, until
+     * the camera or the region change.
+     */
+    pub fn tile_costs(&self, settings: &RenderSettings) -> Arc<CostMap> {
+        let region = settings.region();
+        let stride = (settings.tile_size / 4).max(1);
+        let mut cached = self.tile_costs.lock().unwrap();
+        if let Some(costs) = cached.as_ref() {
+            if costs.region == region && costs.stride == stride {
+                return costs.clone();
+            }
+        }
+
+        // The probes leave the irradiance cache to the render.
+        let probe_settings = RenderSettings {
+            irradiance_cache: None,
+            ..settings.clone()
+        };
+        let probes = CostMap::probes(&region, stride);
+        let threads = settings.thread_count().clamp(1, probes.len().max(1));
+        let costs = self.run_threads(settings, threads, |t, threads| {
+            let costs: Vec<f64> = probes
+                .iter()
+                .skip(t)
+                .step_by(threads)
+                .map(|&(x, y)| {
+                    self.seed_pixel(settings, x, y);
+                    take_traversal_stats();
+                    let ray = self.camera_ray(&probe_settings, x, y, 0);
+                    self.cast_rays(&probe_settings, &ray);
+                    let stats = take_traversal_stats();
+                    stats.rays as f64 + stats.nodes as f64 + stats.tests as f64
+                })
+                .collect();
+            random::unseed();
+            costs
+        });
+        let mut map = CostMap {
+            region,
+            stride,
+            costs: vec![0.0; probes.len()],
+        };
+        for (t, costs) in costs.into_iter().enumerate() {
+            for (i, cost) in costs.into_iter().enumerate() {
+                map.costs[t + i * threads] = cost;
+            }
+        }
+        let map = Arc::new(map);
+        *cached = Some(map.clone());
+        map
+    }
+
+    /**
+     * Render the beauty pass together with one radiance AOV per light
+     * group. The AOVs add up to the beauty pass; their alpha channel is
+     * not used. Escaped rays contribute to the background group, and
+     * emitters without a group to the default one.
+     */
+    #[instrument(skip_all, fields(
+        width = settings.width,
+        height = settings.height,
+        samples = settings.samples,
+    ))]
+    pub fn render_light_groups(
+        &self,
+        settings: &RenderSettings,
+    ) -> Result<(HdrImage, BTreeMap<String, HdrImage>)> {
+        self.validate(settings)?;
+        self.irradiance.clear(self.world.bounding_box());
+        let region = settings.region();
+
+        let pixels = self.render_pixels(settings, |x, y| {
+            let mut color = arr1(&[0.0, 0.0, 0.0, 0.0]);
+            let mut contributions: BTreeMap<String, Array1<Float>> =
+                BTreeMap::new();
+
+            for sample in 0..settings.samples {
+                let ray = self.camera_ray(settings, x, y, sample);
+                let mut splat = |group: &str, value: Array1<Float>| {
+                    let sum = contributions
+                        .entry(group.to_string())
+                        .or_insert_with(|| arr1(&[0.0, 0.0, 0.0, 0.0]));
+                    *sum += &value;
+                };
+
+                color += &self
+                    .trace_camera_ray(settings, &ray, &mut splat, &mut None);
+            }
+
+            color /= settings.samples as Float;
+            for sum in contributions.values_mut() {
+                *sum /= settings.samples as Float;
+            }
+            (color, contributions)
+        });
+
+        let mut image = HdrImage::new(region.width, region.height, 4);
+        let mut groups: BTreeMap<String, HdrImage> = BTreeMap::new();
+        for (i, (color, contributions)) in pixels.into_iter().enumerate() {
+            image.set_pixel(i, [color[0], color[1], color[2], color[3]]);
+
+            for (group, value) in contributions {
+                groups
+                    .entry(group)
+                    .or_insert_with(|| {
+                        HdrImage::new(region.width, region.height, 4)
+                    })
+                    .set_pixel(i, [value[0], value[1], value[2], 0.0]);
+            }
+        }
+
+        check_finite(&image, settings)?;
+        Ok((image, groups))
+    }
+
+    pub fn render_scene(&self, settings: &RenderSettings) -> Result<Image> {
+        let hdr = self.render_hdr(settings)?;
+        Ok(develop(&hdr, settings))
+    }
+
+    /**
+     * Render the scene through each of the named `cameras` in turn, in a
+     * batch (e.g. the views of a product, or to check the coverage of a
+     * set). The camera of the canvas is restored afterwards.
+     */
+    pub fn render_cameras(
+        &mut self,
+        settings: &RenderSettings,
+        cameras: Vec<(String, Camera)>,
+    ) -> Result<Vec<(String, Image)>> {
+        let mut previous = None;
+        let images = cameras
+            .into_iter()
+            .map(|(name, camera)| {
+                let replaced = std::mem::replace(&mut self.camera, camera);
+                *self.tile_costs.get_mut().unwrap() = None;
+                self.select_detail();
+                previous.get_or_insert(replaced);
+                Ok((name, self.render_scene(settings)?))
+            })
+            .collect();
+        if let Some(camera) = previous {
+            self.set_camera(camera);
+        }
+        images
+    }
+
+    /**
+     * Render the scene into a floating point framebuffer. Samples are
+     * averaged but no gamma correction (or clamping) is applied, so the
+     * output keeps the full dynamic range. Colors are premultiplied by
+     * the alpha coverage.
+     */
+    #[instrument(skip_all, fields(
+        width = settings.width,
+        height = settings.height,
+        samples = settings.samples,
+    ))]
+    pub fn render_hdr(&self, settings: &RenderSettings) -> Result<HdrImage> {
+        self.validate(settings)?;
+        if let Some(budget) = settings.time_budget {
+            let budget = Duration::from_secs_f64(budget);
+            return Ok(self.render_budget(settings, budget)?.hdr());
+        }
+        self.render_samples(settings, 0, settings.samples)
+    }
+
+    /**
+     * Render passes of a sample per pixel (see `render_pass`) until
+     * `budget` is spent, however many samples that makes: a pass starts
+     * only if it should end within the budget, as long as the passes
+     * before took on average. At least one pass is rendered. The
+     * settings of the render returned count its passes as the samples.
+     * Refused in the browser, which has no clock to spend it by.
+     */
+    #[instrument(skip_all, fields(budget = ?budget))]
+    pub fn render_budget(
+        &self,
+        settings: &RenderSettings,
+        budget: Duration,
+    ) -> Result<Progressive> {
+        self.validate(settings)?;
+        if cfg!(target_arch = "wasm32") {
+            return Err(SaturnoError::InvalidSettings(
+                "time budgets are not supported in the browser".to_string(),
+            ));
+        }
+        let start = Instant::now();
+        let mut progressive = Progressive::new(RenderSettings {
+            samples: u32::MAX,
+            time_budget: None,
+            ..settings.clone()
+        });
+        while progressive.step(self)? {
+            let elapsed = start.elapsed();
+            if elapsed + elapsed / progressive.passes() > budget {
+                break;
+            }
+        }
+        progressive.settings.samples = progressive.passes();
+        tracing::info!(
+            samples = progressive.passes(),
+            elapsed = ?start.elapsed(),
+            "time budget spent"
+        );
+        Ok(progressive)
+    }
+
+    /**
+     * Render the `pass`-th sample of every pixel, for progressive
+     * rendering: averaging passes 0 to n - 1 converges like `render_hdr`
+     * with n samples. In deterministic mode every pass draws from its own
+     * streams, pass 0 matches a single sample render.
+     */
+    #[instrument(level = "debug", skip(self, settings))]
+    pub fn render_pass(
+        &self,
+        settings: &RenderSettings,
+        pass: u32,
+    ) -> Result<HdrImage> {
+        self.validate(settings)?;
+        let pass_settings = RenderSettings {
+            seed: settings.seed.map(|seed| pass_seed(seed, pass)),
+            ..settings.clone()
+        };
+        self.render_samples(&pass_settings, pass, 1)
+    }
+
+    /**
+     * Average `count` samples per pixel, starting at sample `first`.
+     */
+    fn render_samples(
+        &self,
+        settings: &RenderSettings,
+        first: u32,
+        count: u32,
+    ) -> Result<HdrImage> {
+        if first == 0 {
+            *self.light_tree.write().unwrap() = settings
+                .light_hierarchy
+                .map(|_| LightTree::new(&self.lights));
+        }
+        if let Some(mapping) = &settings.photon_mapping {
+            return self.photon_passes(settings, mapping, first, count);
+        }
+        if let Some(metropolis) = &settings.metropolis {
+            return self.metropolis_samples(settings, metropolis, count);
+        }
+        if settings.quarantine {
+            return Ok(self.quarantine_samples(settings, first, count).image);
+        }
+        if first == 0 {
+            self.irradiance.clear(self.world.bounding_box());
+            if let Some(resampling) = &settings.reservoirs {
+                self.reservoirs
+                    .clear(self.world.bounding_box(), resampling.radius);
+            }
+        }
+        if let (Some(guiding), 0) = (&settings.guiding, first) {
+            self.train_guide(settings, guiding);
+        }
+        if let Some(gradient) = &settings.gradient_domain {
+            return self.gradient_samples(settings, gradient, first, count);
+        }
+        let region = settings.region();
+
+        let pixels = self.render_pixels(settings, |x, y| {
+            if let Some(toon) = &settings.toon {
+                if toon.outlines && self.toon_outline(settings, toon, x, y) {
+                    return [toon.ink[0], toon.ink[1], toon.ink[2], 1.0];
+                }
+            }
+
+            let mut color = arr1(&[0.0, 0.0, 0.0, 0.0]);
+
+            // TODO review why the statement below produces weird results...
+            // for i in 0..=number_samples {
+            for i in first..first + count {
+                let ray = self.camera_ray(settings, x, y, i);
+
+                color = color
+                    + match &settings.toon {
+                        Some(toon) => self.toon_sample(settings, toon, &ray),
+                        None => self.cast_rays(settings, &ray),
+                    };
+            }
+
+            color /= count as Float;
+            [color[0], color[1], color[2], color[3]]
+        });
+
+        let mut image = HdrImage::new(region.width, region.height, 4);
+        for (i, color) in pixels.into_iter().enumerate() {
+            image.set_pixel(i, color);
+        }
+
+        check_finite(&image, settings)?;
+        Ok(image)
+    }
+
+    /**
+     * Passes `first` to `first + count - 1` of photon mapping (see
+     * `PhotonMapping`), after the passes rendered before (from scratch at
+     * pass 0, or if they were not these). The image returned is what the
+     * passes change to the sum of the estimates, so that averaging the
+     * images of the passes, as `Progressive` does, gives the estimate after
+     * the last one.
+     *
+     * The photons leave the `lights` but the environments, which only
+     * light the points by sampling (and the background not at all). The
+     * camera paths see the emitters, the environments and the background,
+     * but go through neither the fog nor the atmosphere, nor around black
+     * holes. Paths are traced in RGB.
+     */
+    fn photon_passes(
+        &self,
+        settings: &RenderSettings,
+        mapping: &PhotonMapping,
+        first: u32,
+        count: u32,
+    ) -> Result<HdrImage> {
+        let region = settings.region();
+        let pixels = region.width as usize * region.height as usize;
+        let mut state = self.photons.lock().unwrap();
+        let continued = state.as_ref().is_some_and(|map| {
+            map.pixels.len() == pixels && map.passes == first
+        });
+        if first == 0 || !continued {
+            *state = Some(PhotonMap::new(pixels, mapping.initial_radius));
+        }
+        let map = state.as_mut().unwrap();
+
+        let (before, passes_before) = (map.estimate(), map.passes as Float);
+        for pass in first..first + count {
+            // The caustics come from the photons.
+            let pass_settings = RenderSettings {
+                seed: settings.seed.map(|seed| pass_seed(seed, pass)),
+                caustics: None,
+                ..settings.clone()
+            };
+            self.photon_pass(&pass_settings, mapping, map, pass);
+        }
+        let (after, passes_after) = (map.estimate(), map.passes as Float);
+
+        let mut image = HdrImage::new(region.width, region.height, 4);
+        for (i, (after, before)) in after.iter().zip(&before).enumerate() {
+            image.set_pixel(
+                i,
+                [0, 1, 2, 3].map(|c| {
+                    (after[c] * passes_after - before[c] * passes_before)
+                        / count as Float
+                }),
+            );
+        }
+        check_finite(&image, settings)?;
+        Ok(image)
+    }
+
+    /**
+     * A pass of photon mapping: the camera rays find the points of the
+     * pixels, where the photons of the pass are gathered.
+     */
+    fn photon_pass(
+        &self,
+        settings: &RenderSettings,
+        mapping: &PhotonMapping,
+        map: &mut PhotonMap,
+        pass: u32,
+    ) {
+        let (points, direct): (Vec<_>, Vec<_>) = self
+            .render_pixels(settings, |x, y| {
+                let ray = self.camera_ray(settings, x, y, pass);
+                self.visible_point(settings, ray)
+            })
+            .into_iter()
+            .unzip();
+        let grid = Grid::new(&points, &map.radii());
+        let gathered =
+            self.shoot_photons(settings, mapping, map, &points, &grid);
+        map.update(&direct, &gathered, mapping.alpha);
+    }
+
+    /**
+     * Follow a camera ray through the smooth surfaces to a Lambertian one,
+     * its point for the photons. Also returns the light found on the way:
+     * the emitters seen, and the lights sampled at the point, with the
+     * coverage in alpha.
+     */
+    fn visible_point(
+        &self,
+        settings: &RenderSettings,
+        mut ray: Ray,
+    ) -> (Option<VisiblePoint>, [Float; 4]) {
+        let mut throughput = arr1(&[1.0, 1.0, 1.0, 1.0]);
+        let mut light = arr1(&[0.0, 0.0, 0.0, 0.0]);
+        let mut alpha = 1.0;
+        for depth in 1..=settings.max_depth {
+            let mut hit = Hit::new();
+            let kind = if depth == 1 {
+                RayKind::Camera
+            } else {
+                RayKind::Indirect
+            };
+            hit.time = ray.time;
+            if !self
+                .world
+                .is_hit_by(kind, &ray, 0.0001, Float::MAX, &mut hit)
+            {
+                let color = self.shade(
+                    settings,
+                    &ray,
+                    false,
+                    &mut hit,
+                    depth,
+                    &throughput,
+                    &mut |_, _| {},
+                    &mut None,
+                    None,
+                    Sampled::Nothing,
+                );
+                if depth == 1 {
+                    alpha = color[3];
+                }
+                light += &(&throughput * &color);
+                break;
+            }
+
+            let mut attenuation = arr1(&[0.0, 0.0, 0.0, 1.0]);
+            let mut scattered = Ray::new(
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, 0.0, 0.0]),
+            );
+            if !hit.material.scatter(
+                &ray,
+                &hit,
+                &mut attenuation,
+                &mut scattered,
+                depth,
+            ) {
+                light += &(&throughput * &hit.material.color_noscatter(&hit));
+                break;
+            }
+            scattered.time = ray.time;
+
+            if let Some(albedo) = hit.material.diffuse(&hit) {
+                if let Some(direct) = self.sample_lights(
+                    settings,
+                    &hit,
+                    &throughput,
+                    &mut |_, _| {},
+                    None,
+                ) {
+                    light += &(&throughput * &direct);
+                }
+                let mut normal = Vec4::normalize(hit.normal.clone());
+                if ray.direction.dot(&normal) > 0.0 {
+                    normal = -normal;
+                }
+                let point = VisiblePoint {
+                    point: [hit.point[0], hit.point[1], hit.point[2]],
+                    normal: [normal[0], normal[1], normal[2]],
+                    weight: [0, 1, 2]
+                        .map(|c| throughput[c] * albedo[c] / consts::PI),
+                };
+                return (Some(point), [light[0], light[1], light[2], alpha]);
+            }
+            throughput *= &attenuation;
+            ray = scattered;
+        }
+        (None, [light[0], light[1], light[2], alpha])
+    }
+
+    /**
+     * Shoot the photons of a pass, sharing the power of the lights, and
+     * return the flux (weighted by the points) and the number of the
+     * photons each pixel gathers. The photons coming straight from the
+     * lights are left out: the points sampled the lights.
+     */
+    fn shoot_photons(
+        &self,
+        settings: &RenderSettings,
+        mapping: &PhotonMapping,
+        map: &PhotonMap,
+        points: &[Option<VisiblePoint>],
+        grid: &Grid,
+    ) -> Vec<([Float; 3], u32)> {
+        let emitters: Vec<&Arc<dyn Light>> =
+            self.lights.iter().filter(|l| !l.environment()).collect();
+        let bounds = self.world.bounding_box().or_else(|| {
+            let mut seen = points.iter().flatten();
+            let first = seen.next()?.point;
+            Some(seen.fold(Aabb::new(first, first), |bounds, point| {
+                Aabb::new(
+                    [0, 1, 2].map(|i| bounds.min[i].min(point.point[i])),
+                    [0, 1, 2].map(|i| bounds.max[i].max(point.point[i])),
+                )
+            }))
+        });
+        let (Some(bounds), false) = (bounds, emitters.is_empty()) else {
+            return vec![([0.0; 3], 0); points.len()];
+        };
+        let center = [0, 1, 2].map(|i| (bounds.min[i] + bounds.max[i]) / 2.0);
+        let radius = (0..3)
+            .map(|i| (bounds.max[i] - bounds.min[i]).powi(2))
+            .sum::<Float>()
+            .sqrt()
+            / 2.0;
+        let sphere = (center, radius.max(1e-3));
+
+        let photons = mapping.photons as usize;
+        let share = emitters.len() as Float / photons as Float;
+        let blocks = photons.div_ceil(PHOTON_BLOCK);
+        let threads = settings.thread_count().clamp(1, blocks);
+        let shoot = |thread: usize| {
+            let mut gathered = vec![([0.0; 3], 0); points.len()];
+            for block in (thread..blocks).step_by(threads) {
+                if let Some(seed) = settings.seed {
+                    random::seed_stream(seed, PHOTON_STREAMS + block as u64);
+                }
+                let end = ((block + 1) * PHOTON_BLOCK).min(photons);
+                for _ in block * PHOTON_BLOCK..end {
+                    let pick = random::gen_range(0.0, emitters.len() as Float);
+                    let light =
+                        emitters[(pick as usize).min(emitters.len() - 1)];
+                    if let Some(emission) = light.emit(&sphere) {
+                        self.shoot_photon(
+                            settings,
+                            emission,
+                            share,
+                            map,
+                            points,
+                            grid,
+                            &mut gathered,
+                        );
+                    }
+                }
+            }
+            random::unseed();
+            gathered
+        };
+        if threads == 1 {
+            return shoot(0);
+        }
+
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|t| {
+                    let shoot = &shoot;
+                    scope.spawn(move || {
+                        settings.priority.apply();
+                        shoot(t)
+                    })
+                })
+                .collect();
+            let mut total = vec![([0.0; 3], 0); points.len()];
+            for worker in workers {
+                for (sum, (flux, count)) in
+                    total.iter_mut().zip(worker.join().unwrap())
+                {
+                    sum.0 = [0, 1, 2].map(|c| sum.0[c] + flux[c]);
+                    sum.1 += count;
+                }
+            }
+            total
+        })
+    }
+
+    /**
+     * Follow a photon carrying `share` of the power of its light through
+     * the scene, into the points of the pixels it lands near.
+     */
+    #[allow(clippy::too_many_arguments)]
+    fn shoot_photon(
+        &self,
+        settings: &RenderSettings,
+        emission: Emission,
+        share: Float,
+        map: &PhotonMap,
+        points: &[Option<VisiblePoint>],
+        grid: &Grid,
+        gathered: &mut [([Float; 3], u32)],
+    ) {
+        let (o, d) = (emission.origin, emission.direction);
+        let mut ray = Ray::new(
+            arr1(&[o[0], o[1], o[2], 1.0]),
+            arr1(&[d[0], d[1], d[2], 0.0]),
+        )
+        .at_time(self.camera.sample_time());
+        let mut power = emission.power * share;
+        for depth in 1..=settings.max_depth {
+            let mut hit = Hit::new();
+            if !self.world.is_hit(&ray, 0.0001, Float::MAX, &mut hit) {
+                return;
+            }
+            let mut attenuation = arr1(&[0.0, 0.0, 0.0, 1.0]);
+            let mut scattered = Ray::new(
+                arr1(&[0.0, 0.0, 0.0, 1.0]),
+                arr1(&[0.0, 0.0, 0.0, 0.0]),
+            );
+            if !hit.material.scatter(
+                &ray,
+                &hit,
+                &mut attenuation,
+                &mut scattered,
+                depth,
+            ) {
+                return;
+            }
+            scattered.time = ray.time;
+
+            if depth > 1 && hit.material.diffuse(&hit).is_some() {
+                let point = [hit.point[0], hit.point[1], hit.point[2]];
+                let length = Vec4::l2_norm(ray.direction.view());
+                let from = [0, 1, 2].map(|i| -ray.direction[i] / length);
+                for &pixel in grid.around(&point) {
+                    if let Some(visible) = &points[pixel] {
+                        if map.gathers(pixel, visible, &point, &from) {
+                            let (flux, count) = &mut gathered[pixel];
+                            for c in 0..3 {
+                                flux[c] += power[c] * visible.weight[c];
+                            }
+                            *count += 1;
+                        }
+                    }
+                }
+            }
+
+            // Russian roulette on the attenuation keeps the power of the
+            // photons about the same.
+            let survival = attenuation
+                .iter()
+                .take(3)
+                .fold(0.0, |a: Float, b| a.max(*b))
+                .min(1.0);
+            if random::gen_range(0.0, 1.0) >= survival {
+                return;
+            }
+            power = power * &attenuation / survival;
+            ray = scattered;
+        }
+    }
+
+    /**
+     * Render with Metropolis light transport (see `Metropolis`), `count`
+     * mutations per pixel. Each contributes the radiance of its path
+     * divided by its luminance, the density the chains sample the paths
+     * with, scaled by the mean luminance of the bootstrap paths: the
+     * proposed paths are splatted too, weighted by their acceptance
+     * probability. The image is fully opaque.
+     */
+    fn metropolis_samples(
+        &self,
+        settings: &RenderSettings,
+        metropolis: &Metropolis,
+        count: u32,
+    ) -> Result<HdrImage> {
+        let region = settings.region();
+        let pixels = region.width as usize * region.height as usize;
+        let seed = settings
+            .seed
+            .unwrap_or_else(|| (random::gen_range(0.0, 1.0) * 2e15) as u64);
+        let primary = |stream: usize| {
+            PrimarySamples::new(
+                seed,
+                METROPOLIS_STREAMS + stream as u64,
+                metropolis.mutation_size,
+                metropolis.large_step_probability,
+            )
+        };
+        // The pixel, radiance and luminance of the path of the samples.
+        let path = |samples: &mut PrimarySamples| {
+            random::with_primary_samples(samples, || {
+                let x = random::gen_range(0.0, region.width as Float);
+                let y = random::gen_range(0.0, region.height as Float);
+                let mut ray = self
+                    .camera
+                    .get_ray(region.x as Float + x, region.y as Float + y);
+                ray.scale_differentials(
+                    (1.0 / (settings.samples as Float).sqrt()).max(0.125),
+                );
+                let color = self.trace_camera_ray(
+                    settings,
+                    &ray,
+                    &mut |_, _| {},
+                    &mut None,
+                );
+                let radiance = [color[0], color[1], color[2]];
+                let luminance = 0.2126 * radiance[0]
+                    + 0.7152 * radiance[1]
+                    + 0.0722 * radiance[2];
+                let pixel = (y as usize).min(region.height as usize - 1)
+                    * region.width as usize
+                    + (x as usize).min(region.width as usize - 1);
+                if luminance > 0.0 && luminance.is_finite() {
+                    (pixel, radiance, luminance)
+                } else {
+                    (pixel, radiance, 0.0)
+                }
+            })
+        };
+
+        let threads = settings.thread_count();
+        let bootstrap = metropolis.bootstrap as usize;
+        let luminances: Vec<Float> = self
+            .run_threads(settings, threads.min(bootstrap), |t, threads| {
+                (t..bootstrap)
+                    .step_by(threads)
+                    .map(|i| (i, path(&mut primary(i)).2))
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .flatten()
+            .fold(vec![0.0; bootstrap], |mut luminances, (i, l)| {
+                luminances[i] = l;
+                luminances
+            });
+        let total: Float = luminances.iter().sum();
+        let mut image = HdrImage::new(region.width, region.height, 4);
+        if total <= 0.0 {
+            return Ok(image);
+        }
+        let normalization = total / bootstrap as Float;
+
+        // The chains start from bootstrap paths picked by their luminance.
+        let chains = metropolis.chains as usize;
+        let mut cumulative = Vec::with_capacity(bootstrap);
+        let mut sum = 0.0;
+        for l in &luminances {
+            sum += l;
+            cumulative.push(sum);
+        }
+        let starts: Vec<usize> = (0..chains)
+            .map(|c| {
+                let target = (c as Float + 0.5) / chains as Float * total;
+                cumulative
+                    .partition_point(|&s| s <= target)
+                    .min(bootstrap - 1)
+            })
+            .collect();
+
+        let mutations = count as u64 * pixels as u64;
+        let sums =
+            self.run_threads(settings, threads.min(chains), |t, threads| {
+                let mut sums = vec![[0.0; 3]; pixels];
+                let mut splat = |pixel: usize, radiance: [Float; 3], weight| {
+                    for c in 0..3 {
+                        sums[pixel][c] += radiance[c] * weight;
+                    }
+                };
+                for chain in (t..chains).step_by(threads) {
+                    let length = mutations / chains as u64
+                        + (mutations % chains as u64 > chain as u64) as u64;
+                    let mut samples = primary(starts[chain]);
+                    let mut current = path(&mut samples);
+                    samples.reseed(
+                        seed,
+                        METROPOLIS_STREAMS + (bootstrap + chain) as u64,
+                    );
+                    if current.2 <= 0.0 {
+                        continue;
+                    }
+                    for _ in 0..length {
+                        samples.start_iteration();
+                        let proposed = path(&mut samples);
+                        let accept = (proposed.2 / current.2).min(1.0);
+                        if accept > 0.0 {
+                            splat(proposed.0, proposed.1, accept / proposed.2);
+                        }
+                        splat(current.0, current.1, (1.0 - accept) / current.2);
+                        if samples.uniform() < accept {
+                            samples.accept();
+                            current = proposed;
+                        } else {
+                            samples.reject();
+                        }
+                    }
+                }
+                sums
+            });
+
+        let scale = normalization / count as Float;
+        for i in 0..pixels {
+            let mut color = [0.0, 0.0, 0.0, 1.0];
+            for sums in &sums {
+                for c in 0..3 {
+                    color[c] += sums[i][c] * scale;
+                }
+            }
+            image.set_pixel(i, color);
+        }
+        check_finite(&image, settings)?;
+        Ok(image)
+    }
+
+    /**
+     * Learn the light of the scene from scratch, over the training passes
+     * of `guiding` (see `PathGuiding`). The passes draw from streams of
+     * their own. With several threads, the light is recorded in whatever
+     * order the paths come, so the images differ slightly from run to run.
+     */
+    fn train_guide(&self, settings: &RenderSettings, guiding: &PathGuiding) {
+        self.guide.clear(self.world.bounding_box());
+        self.guide.set_learning(true);
+        for pass in 0..guiding.training_passes {
+            let mut training = settings.clone();
+            training.seed =
+                settings.seed.map(|seed| pass_seed(seed, u32::MAX - pass));
+            self.render_pixels(&training, |x, y| {
+                for i in 1..=1 << pass {
+                    let ray = self.camera_ray(&training, x, y, i);
+                    self.cast_rays(&training, &ray);
+                }
+            });
+            self.guide
+                .refine(guiding.spatial_threshold, guiding.subdivision);
+        }
+        self.guide.set_learning(false);
+    }
+
+    /**
+     * Samples `first` to `first + count - 1` of gradient-domain path
+     * tracing (see `GradientDomain`): the mean of the pixels and of their
+     * gradients, rebuilt into the image. A sample and its shifts to the
+     * neighbors replay the same random numbers, drawn from a stream of
+     * the sample. The solve being linear, averaging the images of passes
+     * (as `Progressive` does) is about rebuilding their mean.
+     */
+    fn gradient_samples(
+        &self,
+        settings: &RenderSettings,
+        gradient: &GradientDomain,
+        first: u32,
+        count: u32,
+    ) -> Result<HdrImage> {
+        let region = settings.region();
+        let seed = settings
+            .seed
+            .unwrap_or_else(|| (random::gen_range(0.0, 1.0) * 2e15) as u64);
+        // The pixel, its right neighbor and its lower one, shifted.
+        let shifts = [(0, 0), (1, 0), (0, 1)];
+        let means = self.render_pixels(settings, |x, y| {
+            let stream = y as u64 * settings.width as u64 + x as u64;
+            let mut means = [[0.0; 4]; 3];
+            for i in first..first + count {
+                for (mean, (dx, dy)) in means.iter_mut().zip(shifts) {
+                    random::seed_stream(pass_seed(seed, i), stream);
+                    let ray = self.camera_ray(settings, x + dx, y + dy, i);
+                    let color = self.cast_rays(settings, &ray);
+                    for c in 0..4 {
+                        mean[c] += color[c] / count as Float;
+                    }
+                }
+            }
+            means
+        });
+
+        let difference = |shift: usize| -> Vec<[Float; 3]> {
+            means
+                .iter()
+                .map(|m| [0, 1, 2].map(|c| m[shift][c] - m[0][c]))
+                .collect()
+        };
+        let pixels: Vec<[Float; 3]> =
+            means.iter().map(|m| [m[0][0], m[0][1], m[0][2]]).collect();
+        let rebuilt = gradient::reconstruct(
+            &pixels,
+            &difference(1),
+            &difference(2),
+            region.width as usize,
+            region.height as usize,
+            gradient.alpha,
+            gradient.iterations,
+        );
+
+        let mut image = HdrImage::new(region.width, region.height, 4);
+        for (i, (color, m)) in rebuilt.iter().zip(&means).enumerate() {
+            image.set_pixel(i, [color[0], color[1], color[2], m[0][3]]);
+        }
+        check_finite(&image, settings)?;
+        Ok(image)
+    }
+
+    /**
+     * Run `job(t, threads)` on `threads` threads (the calling one if
+     * only one), returning their results in order.
+     */
+    fn run_threads<T, F>(
+        &self,
+        settings: &RenderSettings,
+        threads: usize,
+        job: F,
+    ) -> Vec<T>
+    where
+        T: Send,
+        F: Fn(usize, usize) -> T + Sync,
+    {
+        let threads = threads.max(1);
+        if threads == 1 {
+            return vec![job(0, 1)];
+        }
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|t| {
+                    let job = &job;
+                    scope.spawn(move || {
+                        settings.priority.apply();
+                        job(t, threads)
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        })
+    }
+
+    /**
+     * Render like `render_hdr` in quarantine mode (whatever the
+     * settings say), and also return where samples were discarded and
+     * which actors and materials produced them.
+     */
+    #[instrument(skip_all, fields(
+        width = settings.width,
+        height = settings.height,
+        samples = settings.samples,
+    ))]
+    pub fn render_quarantine(
+        &self,
+        settings: &RenderSettings,
+    ) -> Result<Quarantine> {
+        self.validate(settings)?;
+        Ok(self.quarantine_samples(settings, 0, settings.samples))
+    }
+
+    /**
+     * `render_samples` without the non-finite samples. When a pixel
+     * loses none, it is exactly the pixel `render_samples` renders.
+     */
+    fn quarantine_samples(
+        &self,
+        settings: &RenderSettings,
+        first: u32,
+        count: u32,
+    ) -> Quarantine {
+        if first == 0 {
+            self.irradiance.clear(self.world.bounding_box());
+        }
+        let region = settings.region();
+
+        let pixels = self.render_pixels(settings, |x, y| {
+            let mut color = arr1(&[0.0, 0.0, 0.0, 0.0]);
+            let mut kept = 0;
+            let mut culprits = vec![];
+
+            for i in first..first + count {
+                let ray = self.camera_ray(settings, x, y, i);
+                match self.quarantine_sample(settings, &ray) {
+                    Ok(value) => {
+                        color = color + value;
+                        kept += 1;
+                    }
+                    Err(culprit) => culprits.push(culprit),
+                }
+            }
+
+            if kept > 0 {
+                color /= kept as Float;
+            }
+            ([color[0], color[1], color[2], color[3]], culprits)
+        });
+
+        let mut image = HdrImage::new(region.width, region.height, 4);
+        let mut mask = HdrImage::new(region.width, region.height, 4);
+        let mut counts: BTreeMap<Culprit, (u64, u32, u32)> = BTreeMap::new();
+        for (i, (color, culprits)) in pixels.into_iter().enumerate() {
+            image.set_pixel(i, color);
+
+            let fraction = culprits.len() as Float / count as Float;
+            mask.set_pixel(i, [fraction, 0.0, fraction, fraction]);
+
+            let (x, y) = image.get_pixel_coordinate(i);
+            for culprit in culprits {
+                counts
+                    .entry(culprit)
+                    .or_insert((0, region.x + x, region.y + y))
+                    .0 += 1;
+            }
+        }
+
+        for (culprit, (samples, x, y)) in &counts {
+            tracing::warn!(
+                actor = %culprit.actor,
+                material = %culprit.material,
+                quantity = %culprit.quantity,
+                samples,
+                first_pixel = ?(x, y),
+                "discarded non finite samples"
+            );
+        }
+
+        Quarantine {
+            image,
+            mask,
+            culprits: counts
+                .into_iter()
+                .map(|(culprit, (samples, _, _))| (culprit, samples))
+                .collect(),
+        }
+    }
+
+    /**
+     * Trace a camera ray, checking every bounce of its path. A sample
+     * whose radiance is not finite is blamed on the first bounce holding
+     * a non-finite value, or on the throughput of the path if every
+     * bounce looked fine (e.g. an overflow).
+     */
+    fn quarantine_sample(
+        &self,
+        settings: &RenderSettings,
+        ray: &Ray,
+    ) -> std::result::Result<Array1<Float>, Culprit> {
+        let mut culprit = None;
+        let mut last = None;
+        let mut check = |bounce: Bounce| {
+            if culprit.is_none() {
+                culprit = Culprit::find(&bounce);
+            }
+            last = bounce.hit;
+        };
+
+        let color = self.trace_camera_ray(
+            settings,
+            ray,
+            &mut |_, _| {},
+            &mut Some(&mut check),
+        );
+        if color.iter().all(|value| value.is_finite()) {
+            return Ok(color);
+        }
+
+        Err(culprit.unwrap_or_else(|| {
+            let (actor, material) = match last {
+                Some(hit) => (actor_name(hit.actor), hit.material),
+                None => ("background".to_string(), "background".to_string()),
+            };
+            Culprit {
+                actor,
+                material,
+                quantity: "path throughput".to_string(),
+            }
+        }))
+    }
+
+    /**
+     * Check the canvas can be rendered with the given settings: the
+     * settings are consistent with the camera and every actor and light
+     * is well formed.
+     */
+    pub fn validate(&self, settings: &RenderSettings) -> Result<()> {
+        settings.validate()?;
+
+        if self.camera.resolution_x != settings.width
+            || self.camera.resolution_y != settings.height
+        {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "image is {}x{} but the camera resolution is {}x{}",
+                settings.width,
+                settings.height,
+                self.camera.resolution_x,
+                self.camera.resolution_y
+            )));
+        }
+
+        for (index, actor) in self.world.actors.iter().enumerate() {
+            actor
+                .validate()
+                .map_err(|reason| SaturnoError::InvalidActor {
+                    actor: actor_name(index),
+                    reason,
+                })?;
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            light
+                .validate()
+                .map_err(|reason| SaturnoError::InvalidActor {
+                    actor: format!("light{}", index),
+                    reason,
+                })?;
+        }
+
+        if let Some(fog) = &self.fog {
+            fog.validate()
+                .map_err(|reason| SaturnoError::InvalidActor {
+                    actor: "fog".to_string(),
+                    reason,
+                })?;
+        }
+
+        for (index, volume) in self.volumes.iter().enumerate() {
+            volume
+                .validate()
+                .map_err(|reason| SaturnoError::InvalidActor {
+                    actor: format!("volume{}", index),
+                    reason,
+                })?;
+        }
+
+        if let Some(atmosphere) = &self.atmosphere {
+            atmosphere.validate().map_err(|reason| {
+                SaturnoError::InvalidActor {
+                    actor: "atmosphere".to_string(),
+                    reason,
+                }
+            })?;
+        }
+
+        if let Some(black_hole) = &self.black_hole {
+            black_hole.validate().map_err(|reason| {
+                SaturnoError::InvalidActor {
+                    actor: "black hole".to_string(),
+                    reason,
+                }
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Streams are keyed by the index of the pixel in the full image,
+     * so a crop renders exactly like the same region of the full frame.
+     */
+    fn seed_pixel(&self, settings: &RenderSettings, x: u32, y: u32) {
+        if let Some(seed) = settings.seed {
+            let index = y as u64 * settings.width as u64 + x as u64;
+            random::seed_stream(seed, index);
+        }
+    }
+
+    /**
+     * Camera ray of the given sample of a pixel. The first sample goes
+     * through the pixel's corner, the rest are distributed following
+     * the pixel filter.
+     */
+    fn camera_ray(
+        &self,
+        settings: &RenderSettings,
+        x: u32,
+        y: u32,
+        sample: u32,
+    ) -> Ray {
+        let mut x_final = x as Float;
+        let mut y_final = y as Float;
+
+        if sample > 0 {
+            x_final = x as Float + settings.filter.sample_offset();
+            y_final = y as Float + settings.filter.sample_offset();
+        }
+
+        // The samples of a pixel split it, each covering a fraction of
+        // it (but not less than an eighth: finer texture levels would
+        // only add noise).
+        let mut ray = self.camera.get_ray(x_final, y_final);
+        ray.scale_differentials(
+            (1.0 / (settings.samples as Float).sqrt()).max(0.125),
+        );
+        ray
+    }
+
+    /**
+     * Trace every sample of the pixels in `rect` and record each bounce
+     * of their paths, to debug fireflies and black pixels. With a seed
+     * the samples are exactly those of the render.
+     */
+    #[instrument(skip(self, settings))]
+    pub fn trace_pixels(
+        &self,
+        settings: &RenderSettings,
+        rect: Crop,
+    ) -> Result<Vec<PixelTrace>> {
+        self.validate(settings)?;
+        self.validate(&RenderSettings {
+            crop: Some(rect),
+            ..settings.clone()
+        })?;
+
+        let mut pixels = vec![];
+        for y in rect.y..rect.y + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                self.seed_pixel(settings, x, y);
+
+                let mut color = arr1(&[0.0, 0.0, 0.0, 0.0]);
+                let mut samples = vec![];
+                for sample in 0..settings.samples {
+                    let ray = self.camera_ray(settings, x, y, sample);
+                    let mut bounces = vec![];
+                    let mut push = |bounce| bounces.push(bounce);
+                    let value = self.trace_camera_ray(
+                        settings,
+                        &ray,
+                        &mut |_, _| {},
+                        &mut Some(&mut push),
+                    );
+
+                    color += &value;
+                    samples.push(SampleTrace {
+                        sample,
+                        color: [value[0], value[1], value[2], value[3]],
+                        bounces,
+                    });
+                }
+                random::unseed();
+
+                color /= settings.samples as Float;
+                pixels.push(PixelTrace {
+                    x,
+                    y,
+                    color: [color[0], color[1], color[2], color[3]],
+                    samples,
+                });
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /**
+     * Traversal cost of every pixel (see `TraversalStats`), to find the
+     * expensive parts of a scene.
+     */
+    #[instrument(skip_all, fields(
+        width = settings.width,
+        height = settings.height,
+        samples = settings.samples,
+    ))]
+    pub fn render_cost(&self, settings: &RenderSettings) -> Result<CostImage> {
+        self.validate(settings)?;
+        let region = settings.region();
+
+        let pixels = self.render_pixels(settings, |x, y| {
+            take_traversal_stats();
+            for sample in 0..settings.samples {
+                let ray = self.camera_ray(settings, x, y, sample);
+                self.cast_rays(settings, &ray);
+            }
+            take_traversal_stats()
+        });
+
+        let mut cost = CostImage::new(region.width, region.height);
+        for (i, stats) in pixels.into_iter().enumerate() {
+            cost.rays[i] = stats.rays;
+            cost.nodes[i] = stats.nodes;
+            cost.tests[i] = stats.tests;
+        }
+
+        Ok(cost)
+    }
+
+    /**
+     * Draw the bounding box of every actor over `image`, a render of the
+     * canvas with `settings` (or an empty image of the same size, for
+     * the boxes alone).
+     */
+    pub fn draw_bounds(&self, settings: &RenderSettings, image: &mut Image) {
+        let region = settings.region();
+        overlay::draw_bounds(
+            image,
+            &self.world,
+            &self.camera,
+            (region.x, region.y),
+        );
+    }
+
+    /**
+     * Circle of confusion of every pixel: the radius, in pixels, of the
+     * blur of the depth of field at the surface seen through its center
+     * (infinite where nothing is hit).
+     */
+    pub fn focus_blur(&self, settings: &RenderSettings) -> Result<Vec<Float>> {
+        self.validate(settings)?;
+        Ok(self.render_pixels(settings, |x, y| {
+            self.focus_sample(x, y)
+                .map_or(Float::INFINITY, |(_, blur)| blur)
+        }))
+    }
+
+    /**
+     * A quick preview free of depth of field, the surfaces shaded by how
+     * much they face the camera, with the parts in focus highlighted (see
+     * `overlay::draw_focus_peaking`), to set the focus before rendering.
+     */
+    #[instrument(skip_all, fields(
+        width = settings.width,
+        height = settings.height,
+    ))]
+    pub fn render_focus_peaking(
+        &self,
+        settings: &RenderSettings,
+    ) -> Result<Image> {
+        self.validate(settings)?;
+        let region = settings.region();
+        let samples =
+            self.render_pixels(settings, |x, y| self.focus_sample(x, y));
+
+        let mut image = Image::new(region.width, region.height, 4);
+        let mut blur = vec![Float::INFINITY; samples.len()];
+        for (i, sample) in samples.into_iter().enumerate() {
+            if let Some((facing, radius)) = sample {
+                let gray = ((0.2 + 0.6 * facing) * 255.0) as u8;
+                image.set_pixel(i, [gray, gray, gray, 255]);
+                blur[i] = radius;
+            } else {
+                image.set_pixel(i, [0, 0, 0, 255]);
+            }
+        }
+        overlay::draw_focus_peaking(&mut image, &blur);
+        Ok(image)
+    }
+
+    /**
+     * How much the surface seen through the center of the pixel (`x`,
+     * `y`) faces the camera, and its circle of confusion in pixels. None
+     * if nothing is hit.
+     */
+    fn focus_sample(&self, x: u32, y: u32) -> Option<(Float, Float)> {
+        let ray = self.camera.get_center_ray(x as Float, y as Float);
+        let mut hit = Hit::new();
+        if !self.world.is_hit_by(
+            RayKind::Camera,
+            &ray,
+            0.0001,
+            Float::MAX,
+            &mut hit,
+        ) {
+            return None;
+        }
+        let point = [hit.point[0], hit.point[1], hit.point[2]];
+        let facing = (0..3)
+            .map(|i| hit.normal[i] * ray.direction[i])
+            .sum::<Float>()
+            .abs();
+        let depth = self.camera.view_depth(&point);
+        Some((facing, self.camera.blur_radius(depth)))
+    }
+
+    /**
+     * Object and material ID passes. Object IDs are the actor index
+     * plus one, material IDs are hashes of the material names. Zero
+     * means no actor was hit.
+     */
+    #[instrument(skip_all, fields(
+        width = settings.width,
+        height = settings.height,
+        samples = settings.samples,
+    ))]
+    pub fn render_ids(
+        &self,
+        settings: &RenderSettings,
+    ) -> Result<(IdImage, IdImage)> {
+        self.validate(settings)?;
+        let region = settings.region();
+
+        let pixels = self.render_pixels(settings, |x, y| {
+            let ray = self.camera_ray(settings, x, y, 0);
+
+            let mut hit = Hit::new();
+            if self.world.is_hit_by(
+                RayKind::Camera,
+                &ray,
+                0.0001,
+                Float::MAX,
+                &mut hit,
+            ) {
+                (hit.actor_id as u32 + 1, name_hash(&hit.material.name()))
+            } else {
+                (0, 0)
+            }
+        });
+
+        let mut objects = IdImage::new(region.width, region.height);
+        let mut materials = IdImage::new(region.width, region.height);
+        for (i, (object, material)) in pixels.into_iter().enumerate() {
+            objects.data[i] = object;
+            materials.data[i] = material;
+        }
+
+        Ok((objects, materials))
+    }
+
+    /**
+     * Trace the camera rays of every sample and rank, per pixel, the
+     * IDs of the actors (or materials) they hit by coverage.
+     */
+    #[instrument(skip_all, fields(
+        width = settings.width,
+        height = settings.height,
+        samples = settings.samples,
+    ))]
+    pub fn render_cryptomatte(
+        &self,
+        settings: &RenderSettings,
+        kind: IdKind,
+        depth: usize,
+    ) -> Result<Cryptomatte> {
+        self.validate(settings)?;
+        let region = settings.region();
+
+        let pixels = self.render_pixels(settings, |x, y| {
+            let mut counts: BTreeMap<u32, u32> = BTreeMap::new();
+            let mut names: BTreeMap<String, u32> = BTreeMap::new();
+
+            for sample in 0..settings.samples {
+                let ray = self.camera_ray(settings, x, y, sample);
+
+                let mut hit = Hit::new();
+                if self.world.is_hit_by(
+                    RayKind::Camera,
+                    &ray,
+                    0.0001,
+                    Float::MAX,
+                    &mut hit,
+                ) {
+                    let name = match kind {
+                        IdKind::OBJECT => actor_name(hit.actor_id),
+                        IdKind::MATERIAL => hit.material.name(),
+                    };
+                    let id = name_hash(&name);
+                    names.insert(name, id);
+                    *counts.entry(id).or_insert(0) += 1;
+                }
+            }
+
+            (counts, names)
+        });
+
+        let mut cryptomatte =
+            Cryptomatte::new(kind, region.width, region.height, depth);
+        for (i, (counts, names)) in pixels.into_iter().enumerate() {
+            cryptomatte.manifest.extend(names);
+            cryptomatte.set_pixel(i, &counts, settings.samples);
+        }
+
+        Ok(cryptomatte)
+    }
+}
+
+fn light_group(light: &dyn Light) -> String {
+    light.light_group().unwrap_or_else(|| {
+        if light.environment() {
+            BACKGROUND_LIGHT_GROUP.to_string()
+        } else {
+            DEFAULT_LIGHT_GROUP.to_string()
+        }
+    })
+}
+
+/**
+ * Turn a floating point framebuffer into an 8 bit image: bloom and lens
+ * flare, exposure (see `RenderSettings::exposure_scale`), white balance,
+ * the output
+ * transform (tone mapping from the working space), the imperfections of
+ * the camera, then gamma correction.
+ */
+#[instrument(level = "debug", skip_all)]
+pub fn develop(hdr: &HdrImage, settings: &RenderSettings) -> Image {
+    let mut image = Image::new(hdr.width, hdr.height, 4);
+    let scale = settings.exposure_scale();
+    let bloomed;
+    let hdr = match &settings.bloom {
+        Some(settings) => {
+            bloomed = bloom(hdr, settings, scale);
+            &bloomed
+        }
+        None => hdr,
+    };
+    let flared;
+    let hdr = match &settings.lens_flare {
+        Some(settings) => {
+            flared = lens_flare(hdr, settings, scale);
+            &flared
+        }
+        None => hdr,
+    };
+
+    let balance = settings.white_balance.map(|balance| balance.matrix());
+    let (mut mapped, alphas): (Vec<[Float; 3]>, Vec<Float>) = (0..hdr.size())
+        .map(|i| {
+            let mut color = arr1(&hdr.get_pixel(i));
+            let alpha = color[3];
+
+            // The framebuffer is premultiplied, 8 bit images are not.
+            if alpha > 0.0 {
+                color /= alpha;
+            }
+
+            color *= scale;
+            let mut color = [color[0], color[1], color[2]];
+            if let Some(balance) = &balance {
+                let working = settings.working_space;
+                let linear = working.convert(color, ColorSpace::LinearSrgb);
+                let balanced = color::multiply(balance, linear);
+                color = ColorSpace::LinearSrgb.convert(balanced, working);
+            }
+            let linear = || {
+                settings
+                    .working_space
+                    .convert(color, ColorSpace::LinearSrgb)
+            };
+            let mapped = match &settings.output_transform {
+                OutputTransform::Standard => {
+                    linear().map(|x| settings.tonemapper.map(x))
+                }
+                OutputTransform::Aces => display::aces(linear()),
+                OutputTransform::Lut(lut) => lut.apply(color),
+            };
+            (mapped, alpha)
+        })
+        .unzip();
+    if let Some(imperfections) = &settings.imperfections {
+        let (width, height) = (hdr.width as usize, hdr.height as usize);
+        mapped = camera_imperfections(&mapped, width, height, imperfections);
+    }
+
+    for (i, (color, alpha)) in mapped.iter().zip(&alphas).enumerate() {
+        // The LUTs give display values already.
+        let color = match settings.output_transform {
+            OutputTransform::Lut(_) => color.map(|x| x.clamp(0.0, 1.0)),
+            _ => color.map(|x| x.powf(1.0 / settings.gamma)),
+        }
+        .map(|x| x * 255.0);
+        image.set_pixel(
+            i,
+            [
+                color[0] as u8,
+                color[1] as u8,
+                color[2] as u8,
+                (alpha * 255.0) as u8,
+            ],
+        );
+    }
+    image
+}
+
+/**
+ * NaNs and infinities would silently turn into black or white pixels
+ * once quantized, report the first pixel holding one instead.
+ */
+fn check_finite(image: &HdrImage, settings: &RenderSettings) -> Result<()> {
+    match image.data.iter().position(|value| !value.is_finite()) {
+        Some(j) => {
+            let region = settings.region();
+            let (x, y) = image.get_pixel_coordinate(j / image.chan as usize);
+            Err(SaturnoError::NonFinitePixel {
+                x: region.x + x,
+                y: region.y + y,
+            })
+        }
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,282 @@
+use crate::settings::Caustics;
+use ndarray::arr1;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_scene::actor::Hit;
+use saturno_scene::actor::Hittable;
+use saturno_scene::light::orthonormal_basis;
+
+// Manifold next event estimation (Hanika et al. 2015): a light behind
+// smooth glass is connected to a diffuse point along the refracted path
+// between them. The direction leaving the point is solved for by Newton's
+// method, so that the path, refracted at every glass surface it meets,
+// ends on the light. Its throughput comes from the derivatives of the
+// path: by the conservation of etendue, a point light of intensity I lights
+// the point with I / |det J|, J the derivative of where the path passes the
+// light with respect to the direction leaving the point.
+//
+// The lit points and the lights are in the outer medium (air), and the
+// solver starts from the straight line towards the light, so it finds the
+// caustic through the glass in the way.
+
+/**
+ * Where the path through the glass must end.
+ */
+#[derive(Clone, Copy, Debug)]
+pub enum Target {
+    /**
+     * On a point light.
+     */
+    Point([Float; 3]),
+    /**
+     * Along the unit direction towards a distant light.
+     */
+    Direction([Float; 3]),
+}
+
+/**
+ * A refracted path from a point to the light.
+ */
+#[derive(Clone, Debug)]
+pub struct Connection {
+    /**
+     * Unit direction leaving the point.
+     */
+    pub direction: [Float; 3],
+    /**
+     * The last refraction, and the unit direction from it to the light.
+     */
+    pub last: [Float; 3],
+    pub towards: [Float; 3],
+    /**
+     * Fresnel transmittance and color of the glass, along the path.
+     */
+    pub transmittance: [Float; 3],
+    /**
+     * |det J|: area at the light (or, for a distant light, solid angle)
+     * the paths leaving the point spread over, per unit solid angle.
+     */
+    pub spread: Float,
+    pub vertices: u32,
+}
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: [Float; 3]) -> [Float; 3] {
+    let length = dot(&a, &a).sqrt();
+    a.map(|c| c / length)
+}
+
+/**
+ * Fresnel reflectance of a smooth interface, where `ratio` is the index
+ * of the incident side over the other's.
+ */
+fn fresnel(cos_i: Float, cos_t: Float, ratio: Float) -> Float {
+    let s = (ratio * cos_i - cos_t) / (ratio * cos_i + cos_t);
+    let p = (cos_i - ratio * cos_t) / (cos_i + ratio * cos_t);
+    (s * s + p * p) / 2.0
+}
+
+/**
+ * The path leaving `origin` along `direction`, refracted by every glass
+ * it meets, up to `max_vertices` of them, until it passes the target:
+ * None if it is blocked by anything else or totally reflected.
+ */
+fn follow(
+    world: &dyn Hittable,
+    origin: &[Float; 3],
+    direction: [Float; 3],
+    target: &Target,
+    max_vertices: u32,
+    wavelength: Option<Float>,
+) -> Option<Connection> {
+    let mut path = Connection {
+        direction,
+        last: *origin,
+        towards: direction,
+        transmittance: [1.0, 1.0, 1.0],
+        spread: 0.0,
+        vertices: 0,
+    };
+    loop {
+        let (o, d) = (path.last, path.towards);
+        let reach = match target {
+            Target::Point(light) => {
+                let reach = dot(&[0, 1, 2].map(|i| light[i] - o[i]), &d);
+                if reach <= 0.0 {
+                    return None;
+                }
+                reach
+            }
+            Target::Direction(_) => Float::MAX,
+        };
+        let ray = Ray::new(
+            arr1(&[o[0], o[1], o[2], 1.0]),
+            arr1(&[d[0], d[1], d[2], 0.0]),
+        );
+        let mut hit = Hit::new();
+        hit.wavelength = wavelength;
+        if !world.is_hit(&ray, 0.0001, reach, &mut hit) {
+            return Some(path);
+        }
+        hit.wavelength = wavelength;
+        let eta = hit.material.refraction(&hit)?;
+        if path.vertices == max_vertices {
+            return None;
+        }
+
+        let mut n = normalize([hit.normal[0], hit.normal[1], hit.normal[2]]);
+        let mut cos_i = -dot(&d, &n);
+        let mut ratio = 1.0 / eta;
+        if cos_i < 0.0 {
+            n = n.map(|c| -c);
+            cos_i = -cos_i;
+            ratio = eta;
+        }
+        let k = 1.0 - ratio * ratio * (1.0 - cos_i * cos_i);
+        if k <= 0.0 {
+            return None;
+        }
+        let cos_t = k.sqrt();
+        let refracted = normalize(
+            [0, 1, 2].map(|i| ratio * d[i] + (ratio * cos_i - cos_t) * n[i]),
+        );
+
+        let transmitted = 1.0 - fresnel(cos_i, cos_t, ratio);
+        let color = hit.material.color(&hit);
+        path.transmittance =
+            [0, 1, 2].map(|i| path.transmittance[i] * transmitted * color[i]);
+        path.last = [hit.point[0], hit.point[1], hit.point[2]];
+        path.towards = refracted;
+        path.vertices += 1;
+    }
+}
+
+/**
+ * How far the end of `path` misses the target, in the plane through a
+ * point light perpendicular to `axis` (or, for a distant light, as a
+ * direction), in the basis `frame` of that plane.
+ */
+fn miss(
+    path: &Connection,
+    target: &Target,
+    axis: &[Float; 3],
+    frame: &([Float; 3], [Float; 3]),
+) -> Option<[Float; 2]> {
+    let offset = match target {
+        Target::Point(light) => {
+            let along = dot(&path.towards, axis);
+            if along <= 0.0 {
+                return None;
+            }
+            let to_light = [0, 1, 2].map(|i| light[i] - path.last[i]);
+            let t = dot(&to_light, axis) / along;
+            [0, 1, 2].map(|i| path.last[i] + t * path.towards[i] - light[i])
+        }
+        Target::Direction(toward) => {
+            [0, 1, 2].map(|i| path.towards[i] - toward[i])
+        }
+    };
+    Some([dot(&offset, &frame.0), dot(&offset, &frame.1)])
+}
+
+/**
+ * Solve for the path from `origin` to the target through the glass of
+ * `world`, starting from the straight line. None if the straight line
+ * meets no glass, or Newton's method does not converge.
+ */
+pub fn connect(
+    world: &dyn Hittable,
+    origin: &[Float; 3],
+    target: &Target,
+    caustics: &Caustics,
+    wavelength: Option<Float>,
+) -> Option<Connection> {
+    let (mut direction, scale) = match target {
+        Target::Point(light) => {
+            let offset = [0, 1, 2].map(|i| light[i] - origin[i]);
+            let distance = dot(&offset, &offset).sqrt();
+            (offset.map(|c| c / distance), distance)
+        }
+        Target::Direction(toward) => (normalize(*toward), 1.0),
+    };
+    let trace = |direction: [Float; 3]| {
+        follow(
+            world,
+            origin,
+            direction,
+            target,
+            caustics.max_vertices,
+            wavelength,
+        )
+    };
+    let mut path = trace(direction)?;
+    if path.vertices == 0 {
+        return None;
+    }
+
+    let h = 1e-6;
+    for _ in 0..caustics.iterations {
+        let axis = match target {
+            Target::Point(_) => path.towards,
+            Target::Direction(toward) => normalize(*toward),
+        };
+        let frame = orthonormal_basis(&axis);
+        let error = miss(&path, target, &axis, &frame)?;
+
+        // Derivatives along two directions perpendicular to the current
+        // one, a solid angle of h^2.
+        let (u, v) = orthonormal_basis(&direction);
+        let mut jacobian = [[0.0; 2]; 2];
+        for (column, side) in [u, v].iter().enumerate() {
+            let nudged = trace(normalize(
+                [0, 1, 2].map(|i| direction[i] + h * side[i]),
+            ))?;
+            if nudged.vertices != path.vertices {
+                return None;
+            }
+            let moved = miss(&nudged, target, &axis, &frame)?;
+            for row in 0..2 {
+                jacobian[row][column] = (moved[row] - error[row]) / h;
+            }
+        }
+        let det =
+            jacobian[0][0] * jacobian[1][1] - jacobian[0][1] * jacobian[1][0];
+        let distance = error[0].hypot(error[1]);
+        if distance < caustics.tolerance * scale {
+            path.spread = det.abs();
+            return Some(path);
+        }
+        if det.abs() < 1e-12 {
+            return None;
+        }
+
+        // Newton step, halved until the path gets closer.
+        let step = [
+            -(jacobian[1][1] * error[0] - jacobian[0][1] * error[1]) / det,
+            -(jacobian[0][0] * error[1] - jacobian[1][0] * error[0]) / det,
+        ];
+        let mut length = 1.0;
+        let mut improved = None;
+        for _ in 0..10 {
+            let candidate = normalize([0, 1, 2].map(|i| {
+                direction[i] + length * (step[0] * u[i] + step[1] * v[i])
+            }));
+            if let Some(next) = trace(candidate) {
+                if next.vertices == path.vertices {
+                    if let Some(e) = miss(&next, target, &axis, &frame) {
+                        if e[0].hypot(e[1]) < distance {
+                            improved = Some((candidate, next));
+                            break;
+                        }
+                    }
+                }
+            }
+            length /= 2.0;
+        }
+        (direction, path) = improved?;
+    }
+    None
+}
@@ -0,0 +1,62 @@
+use crate::Image;
+
+// Contact sheet of a batch of renders (e.g. the views of `render_cameras`):
+// the images side by side in a grid, row by row in their order, over a dark
+// background, to look over all of them at once. The cells are as large as
+// the largest image, the smaller ones centered in theirs.
+
+/**
+ * Gray of the background between the images.
+ */
+pub const SHEET_BACKGROUND: [u8; 4] = [32, 32, 32, 255];
+
+/**
+ * Columns of a sheet of `count` images when not given: the grid closest
+ * to a square.
+ */
+pub fn sheet_columns(count: usize) -> usize {
+    ((count as f64).sqrt().ceil() as usize).max(1)
+}
+
+/**
+ * The `images` composited in a grid of `columns` (or `sheet_columns` for
+ * 0), `gap` pixels apart and around the edges.
+ */
+pub fn contact_sheet(images: &[&Image], columns: usize, gap: u32) -> Image {
+    let columns = if columns == 0 {
+        sheet_columns(images.len())
+    } else {
+        columns.min(images.len().max(1))
+    };
+    let rows = images.len().div_ceil(columns).max(1);
+    let cell_width = images.iter().map(|i| i.width).max().unwrap_or(0);
+    let cell_height = images.iter().map(|i| i.height).max().unwrap_or(0);
+    let width = columns as u32 * (cell_width + gap) + gap;
+    let height = rows as u32 * (cell_height + gap) + gap;
+
+    let mut sheet = Image::new(width, height, 4);
+    for i in 0..sheet.size() {
+        sheet.set_pixel(i, SHEET_BACKGROUND);
+    }
+    for (n, image) in images.iter().enumerate() {
+        let (column, row) = ((n % columns) as u32, (n / columns) as u32);
+        let left =
+            gap + column * (cell_width + gap) + (cell_width - image.width) / 2;
+        let top =
+            gap + row * (cell_height + gap) + (cell_height - image.height) / 2;
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let color: [u8; 4] = std::array::from_fn(|c| {
+                    if (c as u32) < image.chan {
+                        image.get_value(x, y, c as u32)
+                    } else {
+                        255
+                    }
+                });
+                let index = (top + y) * width + left + x;
+                sheet.set_pixel(index as usize, color);
+            }
+        }
+    }
+    sheet
+}
@@ -0,0 +1,212 @@
+use crate::aov::actor_name;
+use crate::HdrImage;
+use saturno_math::common::Float;
+use saturno_scene::actor::Hit;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/**
+ * What happened to a path at a bounce.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BounceEvent {
+    /**
+     * The material scattered the ray, the path goes on.
+     */
+    Scattered,
+    /**
+     * The material did not scatter (e.g. an emitter): the path ends with
+     * the material's color as radiance.
+     */
+    Absorbed,
+    /**
+     * The path reached `max_depth` and ends like an absorbed one.
+     */
+    MaxDepth,
+    /**
+     * Hit a shadow catcher, the path goes on through it.
+     */
+    ShadowCatcher,
+    /**
+     * The camera saw a holdout, the path ends transparent.
+     */
+    Holdout,
+    /**
+     * The ray left the scene and picks up the background.
+     */
+    Escaped,
+    /**
+     * The ray scattered inside the medium of an actor (subsurface
+     * scattering), at the point of the hit record.
+     */
+    Volume,
+    /**
+     * The ray fell into a black hole, the path ends in the dark.
+     */
+    Captured,
+}
+
+/**
+ * The surface hit by a ray.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HitRecord {
+    pub t: Float,
+    pub point: [Float; 3],
+    pub normal: [Float; 3],
+    /**
+     * Index of the actor in the scene.
+     */
+    pub actor: usize,
+    pub material: String,
+}
+
+impl HitRecord {
+    pub(crate) fn new(hit: &Hit) -> HitRecord {
+        HitRecord {
+            t: hit.t,
+            point: vec3(&hit.point),
+            normal: vec3(&hit.normal),
+            actor: hit.actor_id,
+            material: hit.material.name(),
+        }
+    }
+}
+
+/**
+ * One segment of a traced path: the ray, what it hit and what the
+ * material did. The materials sample their directions implicitly, so
+ * `attenuation` is the weight of the sample (BSDF times cosine over pdf)
+ * and there is no separate pdf.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Bounce {
+    /**
+     * 1 for camera rays.
+     */
+    pub depth: u32,
+    pub event: BounceEvent,
+    pub origin: [Float; 3],
+    pub direction: [Float; 3],
+    pub hit: Option<HitRecord>,
+    /**
+     * Product of the attenuations of the previous bounces.
+     */
+    pub throughput: [Float; 3],
+    pub attenuation: Option<[Float; 3]>,
+    pub scattered: Option<[Float; 3]>,
+    /**
+     * Radiance picked up where the path ends, before the throughput.
+     */
+    pub radiance: Option<[Float; 3]>,
+    /**
+     * Occlusion of a shadow catcher.
+     */
+    pub shadow: Option<Float>,
+}
+
+/**
+ * A camera sample of a pixel: its color and every bounce of its path.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SampleTrace {
+    pub sample: u32,
+    pub color: [Float; 4],
+    pub bounces: Vec<Bounce>,
+}
+
+/**
+ * Every sample of a pixel, and their average (the rendered color).
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PixelTrace {
+    pub x: u32,
+    pub y: u32,
+    pub color: [Float; 4],
+    pub samples: Vec<SampleTrace>,
+}
+
+/**
+ * Where a non-finite value first showed up along a path: the actor and
+ * material of the hit ("background" for escaped rays) and the quantity
+ * that went wrong.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Culprit {
+    pub actor: String,
+    pub material: String,
+    pub quantity: String,
+}
+
+impl Culprit {
+    /**
+     * The first non-finite quantity of a bounce, if any.
+     */
+    pub(crate) fn find(bounce: &Bounce) -> Option<Culprit> {
+        let (actor, material) = match &bounce.hit {
+            Some(hit) => (actor_name(hit.actor), hit.material.clone()),
+            None => ("background".to_string(), "background".to_string()),
+        };
+        let quantities = [
+            ("ray origin", Some(bounce.origin)),
+            ("ray direction", Some(bounce.direction)),
+            ("hit point", bounce.hit.as_ref().map(|hit| hit.point)),
+            ("normal", bounce.hit.as_ref().map(|hit| hit.normal)),
+            ("attenuation", bounce.attenuation),
+            ("scattered direction", bounce.scattered),
+            ("radiance", bounce.radiance),
+        ];
+
+        let t_finite = bounce.hit.as_ref().is_none_or(|hit| hit.t.is_finite());
+        let quantity = if t_finite {
+            quantities.iter().find_map(|(name, values)| {
+                values
+                    .filter(|v| v.iter().any(|x| !x.is_finite()))
+                    .map(|_| *name)
+            })?
+        } else {
+            "hit distance"
+        };
+
+        Some(Culprit {
+            actor,
+            material,
+            quantity: quantity.to_string(),
+        })
+    }
+}
+
+/**
+ * Result of a quarantined render (see `Canvas::render_quarantine`).
+ */
+pub struct Quarantine {
+    /**
+     * The render without the discarded samples.
+     */
+    pub image: HdrImage,
+    /**
+     * Magenta where samples were discarded, weighted (and with alpha)
+     * by the fraction of the pixel's samples that were.
+     */
+    pub mask: HdrImage,
+    /**
+     * How many samples each culprit spoiled.
+     */
+    pub culprits: Vec<(Culprit, u64)>,
+}
+
+/**
+ * Receives the bounces of a path while it is traced, if any.
+ */
+pub(crate) type Recorder<'a> = Option<&'a mut dyn FnMut(Bounce)>;
+
+pub(crate) fn vec3(values: &ndarray::Array1<Float>) -> [Float; 3] {
+    [values[0], values[1], values[2]]
+}
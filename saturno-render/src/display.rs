@@ -0,0 +1,176 @@
+use saturno_math::common::Float;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Display transforms (see `OutputTransform`), from the linear light of the
+// working space to the values shown on a display. The built-in ACES one is
+// the fit of the reference rendering and sRGB output transforms (RRT and
+// ODT) by Stephen Hill: a matrix into a space close to AP1 (with the
+// saturation of the RRT), a rational curve, and a matrix back to linear
+// sRGB.
+//
+// Any other transform, e.g. of an OpenColorIO config, comes baked into a 3D
+// LUT in the .cube format (`ociobakelut --format resolve_cube`), from the
+// working space to the display values, looked up trilinearly.
+
+const ACES_INPUT: [[Float; 3]; 3] = [
+    [0.597_19, 0.354_58, 0.048_23],
+    [0.076_00, 0.908_34, 0.015_66],
+    [0.028_40, 0.133_83, 0.837_77],
+];
+
+const ACES_OUTPUT: [[Float; 3]; 3] = [
+    [1.604_75, -0.531_08, -0.073_67],
+    [-0.102_08, 1.108_13, -0.006_05],
+    [-0.003_27, -0.072_76, 1.076_02],
+];
+
+/**
+ * Linear sRGB display values of the linear sRGB scene values `rgb`
+ * through the ACES rendering and sRGB output transforms, in [0, 1].
+ */
+pub fn aces(rgb: [Float; 3]) -> [Float; 3] {
+    let multiply = |matrix: &[[Float; 3]; 3], v: [Float; 3]| {
+        matrix.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+    };
+    let curve = |v: Float| {
+        let a = v * (v + 0.024_578_6) - 0.000_090_537;
+        let b = v * (0.983_729 * v + 0.432_951) + 0.238_081;
+        a / b
+    };
+    let v = multiply(&ACES_INPUT, rgb.map(|c| c.max(0.0)));
+    multiply(&ACES_OUTPUT, v.map(curve)).map(|c| c.clamp(0.0, 1.0))
+}
+
+/**
+ * A 3D lookup table of `size`^3 colors, red varying fastest, over the
+ * input colors from `domain_min` to `domain_max`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Lut3d {
+    pub size: usize,
+    pub domain_min: [Float; 3],
+    pub domain_max: [Float; 3],
+    pub table: Vec<[Float; 3]>,
+}
+
+impl Lut3d {
+    /**
+     * Parse a 3D LUT in the .cube format (Resolve and Adobe).
+     */
+    pub fn parse_cube(text: &str) -> Result<Lut3d, String> {
+        let mut lut = Lut3d {
+            size: 0,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table: vec![],
+        };
+        let triple = |words: &[&str]| -> Result<[Float; 3], String> {
+            if words.len() != 3 {
+                return Err(format!("expected 3 values: {}", words.join(" ")));
+            }
+            let mut values = [0.0; 3];
+            for (value, word) in values.iter_mut().zip(words) {
+                *value = word
+                    .parse()
+                    .map_err(|_| format!("invalid number {}", word))?;
+            }
+            Ok(values)
+        };
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "TITLE" => {}
+                "LUT_3D_SIZE" => {
+                    lut.size = words
+                        .get(1)
+                        .and_then(|w| w.parse().ok())
+                        .filter(|size| *size >= 2)
+                        .ok_or_else(|| format!("invalid size: {}", line))?;
+                }
+                "DOMAIN_MIN" => lut.domain_min = triple(&words[1..])?,
+                "DOMAIN_MAX" => lut.domain_max = triple(&words[1..])?,
+                "LUT_3D_INPUT_RANGE" => {
+                    let range: Vec<Float> = words[1..]
+                        .iter()
+                        .filter_map(|w| w.parse().ok())
+                        .collect();
+                    if range.len() != 2 {
+                        return Err(format!("invalid input range: {}", line));
+                    }
+                    lut.domain_min = [range[0]; 3];
+                    lut.domain_max = [range[1]; 3];
+                }
+                "LUT_1D_SIZE" => {
+                    return Err("1D LUTs are not supported".to_string())
+                }
+                _ => lut.table.push(triple(&words)?),
+            }
+        }
+        lut.validate()?;
+        Ok(lut)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size < 2 {
+            return Err(format!("invalid LUT size {}", self.size));
+        }
+        if self.table.len() != self.size.pow(3) {
+            return Err(format!(
+                "LUT of size {} holds {} colors",
+                self.size,
+                self.table.len()
+            ));
+        }
+        if !(0..3).all(|c| self.domain_min[c] < self.domain_max[c]) {
+            return Err(format!(
+                "invalid LUT domain {:?} to {:?}",
+                self.domain_min, self.domain_max
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+     * The color of the table for `rgb`, interpolated trilinearly, the
+     * input clamped to the domain.
+     */
+    pub fn apply(&self, rgb: [Float; 3]) -> [Float; 3] {
+        let last = (self.size - 1) as Float;
+        let mut lower = [0; 3];
+        let mut fraction = [0.0; 3];
+        for c in 0..3 {
+            let relative = (rgb[c] - self.domain_min[c])
+                / (self.domain_max[c] - self.domain_min[c]);
+            let position = (relative * last).clamp(0.0, last);
+            lower[c] = (position.floor() as usize).min(self.size - 2);
+            fraction[c] = position - lower[c] as Float;
+        }
+
+        let mut color = [0.0; 3];
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for c in 0..3 {
+                let offset = (corner >> c) & 1;
+                index[c] = lower[c] + offset;
+                weight *= if offset == 1 {
+                    fraction[c]
+                } else {
+                    1.0 - fraction[c]
+                };
+            }
+            let value = self.table
+                [index[0] + self.size * (index[1] + self.size * index[2])];
+            for c in 0..3 {
+                color[c] += weight * value[c];
+            }
+        }
+        color
+    }
+}
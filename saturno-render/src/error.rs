@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/**
+ * Errors of the crate. Every variant carries enough context (the file,
+ * the actor or the pixel involved) to be reported as is to the user of an
+ * application embedding the renderer.
+ */
+#[derive(Debug, Error)]
+pub enum SaturnoError {
+    #[error("failed to write {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("invalid actor {actor}: {reason}")]
+    InvalidActor { actor: String, reason: String },
+
+    #[error("invalid settings: {0}")]
+    InvalidSettings(String),
+
+    #[error("invalid image: {0}")]
+    InvalidImage(String),
+
+    #[error("non finite radiance at pixel ({x}, {y})")]
+    NonFinitePixel { x: u32, y: u32 },
+}
+
+pub type Result<T> = std::result::Result<T, SaturnoError>;
+
+impl SaturnoError {
+    /**
+     * Wraps an I/O error with the path of the file it happened on.
+     */
+    pub fn io(path: &std::path::Path, source: std::io::Error) -> SaturnoError {
+        SaturnoError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
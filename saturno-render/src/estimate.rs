@@ -0,0 +1,205 @@
+use crate::aov::CostImage;
+use crate::canvas::Canvas;
+use crate::error::Result;
+use crate::error::SaturnoError;
+use crate::settings::Crop;
+use crate::settings::RenderSettings;
+use saturno_math::common::Float;
+use std::time::Instant;
+use tracing::instrument;
+
+// Prediction of how long a render takes, before committing to it. A probe
+// renders the scene at a fraction of the resolution and a few samples, and
+// is timed: the time of a sample of a pixel, scaled to the pixels and the
+// samples asked for, is that of the render. Under a time budget, it is the
+// samples the budget affords instead. The probe also measures the traversal
+// cost of its pixels (see `Canvas::render_cost`), to show where the time
+// goes.
+//
+// The prediction assumes the cost of a sample does not depend on the
+// resolution nor on the samples before it: work done once per render
+// (building a light hierarchy, training the guiding, shooting photons) is
+// counted as part of every sample of the probe, so it is overestimated
+// for renders of many samples. There is no clock to time the probe in the
+// browser, where estimates are refused.
+
+/**
+ * Size of the probe of an estimate: `scale` of the width and height of
+ * the render, at `samples` per pixel (fewer if the render has fewer).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Probe {
+    pub scale: Float,
+    pub samples: u32,
+}
+
+impl Default for Probe {
+    fn default() -> Probe {
+        Probe {
+            scale: 0.25,
+            samples: 4,
+        }
+    }
+}
+
+impl Probe {
+    fn validate(&self) -> Result<()> {
+        if !(self.scale > 0.0 && self.scale <= 1.0) || self.samples == 0 {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid probe of scale {} and {} samples",
+                self.scale, self.samples
+            )));
+        }
+        Ok(())
+    }
+
+    /**
+     * The settings of the probe of a render with `settings`: its size and
+     * crop scaled down, its samples and no time budget.
+     */
+    pub fn settings(&self, settings: &RenderSettings) -> RenderSettings {
+        let scale = |length: u32| {
+            ((length as Float * self.scale).round() as u32).max(1)
+        };
+        let (width, height) = (scale(settings.width), scale(settings.height));
+        let crop = settings.crop.map(|crop| {
+            let x = (crop.x as Float * self.scale) as u32;
+            let y = (crop.y as Float * self.scale) as u32;
+            Crop {
+                x: x.min(width - 1),
+                y: y.min(height - 1),
+                width: scale(crop.width).min(width - x.min(width - 1)),
+                height: scale(crop.height).min(height - y.min(height - 1)),
+            }
+        });
+        let samples = match settings.time_budget {
+            Some(_) => self.samples,
+            None => self.samples.min(settings.samples),
+        };
+        RenderSettings {
+            width,
+            height,
+            crop,
+            samples,
+            time_budget: None,
+            ..settings.clone()
+        }
+    }
+}
+
+/**
+ * The predicted cost of a render.
+ */
+pub struct Estimate {
+    /**
+     * Seconds the render should take.
+     */
+    pub seconds: f64,
+    /**
+     * Samples per pixel of the render: those of its settings, or those
+     * its time budget affords.
+     */
+    pub samples: u32,
+    /**
+     * Seconds a sample of every pixel of the render takes.
+     */
+    pub seconds_per_pass: f64,
+    /**
+     * Seconds the probe took to render, and its settings.
+     */
+    pub probe_seconds: f64,
+    pub probe: RenderSettings,
+    /**
+     * Traversal cost of the pixels of the probe, for a sample each.
+     */
+    pub cost: CostImage,
+}
+
+impl Estimate {
+    /**
+     * Rays traced on average for a sample of a pixel.
+     */
+    pub fn rays_per_sample(&self) -> f64 {
+        mean(&self.cost.rays)
+    }
+
+    /**
+     * Acceleration structure nodes visited on average for a sample of a
+     * pixel.
+     */
+    pub fn nodes_per_sample(&self) -> f64 {
+        mean(&self.cost.nodes)
+    }
+
+    /**
+     * Intersection tests (actors and triangles) on average for a sample
+     * of a pixel.
+     */
+    pub fn tests_per_sample(&self) -> f64 {
+        mean(&self.cost.tests)
+    }
+}
+
+fn mean(counts: &[u32]) -> f64 {
+    let total = counts.iter().map(|&c| c as f64).sum::<f64>();
+    total / counts.len().max(1) as f64
+}
+
+/**
+ * Predict how long rendering `canvas` with `settings` takes from the
+ * render of a `probe`, through its camera resized to the probe (keeping
+ * the detail of the actors chosen for the render).
+ */
+#[instrument(skip_all, fields(scale = probe.scale, samples = probe.samples))]
+pub fn estimate(
+    canvas: &mut Canvas,
+    settings: &RenderSettings,
+    probe: &Probe,
+) -> Result<Estimate> {
+    settings.validate()?;
+    probe.validate()?;
+    if cfg!(target_arch = "wasm32") {
+        return Err(SaturnoError::InvalidSettings(
+            "no clock to time a probe in the browser".to_string(),
+        ));
+    }
+    let probe_settings = probe.settings(settings);
+    let camera = canvas
+        .camera()
+        .resized(probe_settings.width, probe_settings.height);
+    let camera = canvas.swap_camera(camera);
+    let start = Instant::now();
+    let probe_render = canvas.render_hdr(&probe_settings);
+    let probe_seconds = start.elapsed().as_secs_f64();
+    let cost = canvas.render_cost(&RenderSettings {
+        samples: 1,
+        ..probe_settings.clone()
+    });
+    canvas.swap_camera(camera);
+    probe_render?;
+    let cost = cost?;
+
+    let pixels = |settings: &RenderSettings| {
+        let region = settings.region();
+        region.width as f64 * region.height as f64
+    };
+    let per_sample = probe_seconds
+        / (pixels(&probe_settings) * probe_settings.samples as f64);
+    let seconds_per_pass = per_sample * pixels(settings);
+    let (samples, seconds) = match settings.time_budget {
+        Some(budget) => {
+            let samples = ((budget / seconds_per_pass) as u32).max(1);
+            (samples, (samples as f64 * seconds_per_pass).max(budget))
+        }
+        None => (settings.samples, settings.samples as f64 * seconds_per_pass),
+    };
+    tracing::info!(seconds, samples, probe_seconds, "estimated");
+    Ok(Estimate {
+        seconds,
+        samples,
+        seconds_per_pass,
+        probe_seconds,
+        probe: probe_settings,
+        cost,
+    })
+}
@@ -0,0 +1,144 @@
+use crate::bloom::box_blur;
+use crate::bloom::bright;
+use crate::bloom::streaks;
+use crate::settings::LensFlare;
+use crate::HdrImage;
+use saturno_math::common::Float;
+
+// Lens flare (see `LensFlare`), the light of the bright spots reflecting
+// between the elements of a lens. Each reflection images the spots again,
+// mirrored through the center of the image and scaled: the ghosts, softened
+// by a blur and each lens element bending the colors slightly apart. A
+// light near the center also makes a halo, a ring around the center, and
+// the spots shine a starburst of thin rays.
+//
+// The ghosts and the halo are gathered: a pixel takes the light of the
+// point of the (blurred) bright spots it images.
+
+/**
+ * The RGB `image` (`width` by `height`) interpolated bilinearly at the
+ * pixel coordinates `x` and `y`, black out of the image.
+ */
+fn sample(
+    image: &[[Float; 3]],
+    width: usize,
+    height: usize,
+    x: Float,
+    y: Float,
+) -> [Float; 3] {
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut color = [0.0; 3];
+    for (dx, dy, weight) in [
+        (0, 0, (1.0 - fx) * (1.0 - fy)),
+        (1, 0, fx * (1.0 - fy)),
+        (0, 1, (1.0 - fx) * fy),
+        (1, 1, fx * fy),
+    ] {
+        let (px, py) = (x0 as i64 + dx, y0 as i64 + dy);
+        if px < 0 || py < 0 || px >= width as i64 || py >= height as i64 {
+            continue;
+        }
+        let value = image[py as usize * width + px as usize];
+        for c in 0..3 {
+            color[c] += weight * value[c];
+        }
+    }
+    color
+}
+
+/**
+ * Scale of the ghost `index`: the odd ones mirrored through the center,
+ * the even ones on the side of the lights.
+ */
+fn ghost_scale(settings: &LensFlare, index: u32) -> Float {
+    let scale = settings.ghost_spacing * (index / 2 + 1) as Float;
+    if index.is_multiple_of(2) {
+        -scale
+    } else {
+        scale
+    }
+}
+
+/**
+ * `hdr` with the lens flare of `settings`, its values exposed by `scale`
+ * for the threshold.
+ */
+pub fn lens_flare(
+    hdr: &HdrImage,
+    settings: &LensFlare,
+    scale: Float,
+) -> HdrImage {
+    let (width, height) = (hdr.width as usize, hdr.height as usize);
+    let diagonal = ((width * width + height * height) as Float).sqrt();
+    let center = (width as Float / 2.0, height as Float / 2.0);
+
+    let excess = bright(hdr, settings.threshold, scale);
+    let radius = ((0.005 * diagonal).round() as usize).max(1);
+    let mut soft = excess.clone();
+    for _ in 0..3 {
+        soft = box_blur(&soft, width, height, radius, true);
+        soft = box_blur(&soft, width, height, radius, false);
+    }
+
+    let mut flare = vec![[0.0; 3]; excess.len()];
+    let halo = settings.halo_width * diagonal / 2.0;
+    for (i, pixel) in flare.iter_mut().enumerate() {
+        let x = (i % width) as Float + 0.5 - center.0;
+        let y = (i / width) as Float + 0.5 - center.1;
+
+        // A ghost of scale k images the point -x / k at x, as much as
+        // 1 / k^2 of its light spread over k^2 times the area.
+        for ghost in 0..settings.ghosts {
+            let k = ghost_scale(settings, ghost);
+            for (c, value) in pixel.iter_mut().enumerate() {
+                let k = k * (1.0 + settings.dispersion * (c as Float - 1.0));
+                let light = sample(
+                    &soft,
+                    width,
+                    height,
+                    center.0 - x / k,
+                    center.1 - y / k,
+                );
+                *value += settings.ghost_intensity * light[c] / (k * k);
+            }
+        }
+
+        // The halo images the lights a halo radius closer to the center,
+        // the more the closer they are.
+        let distance = (x * x + y * y).sqrt();
+        if settings.halo_intensity > 0.0 && distance > 0.0 {
+            let (sx, sy) = (x - x / distance * halo, y - y / distance * halo);
+            let off = (sx * sx + sy * sy).sqrt() / (diagonal / 2.0);
+            let weight = (1.0 - off).max(0.0).powi(5);
+            let light =
+                sample(&soft, width, height, center.0 + sx, center.1 + sy);
+            for c in 0..3 {
+                pixel[c] += settings.halo_intensity * weight * light[c];
+            }
+        }
+    }
+
+    let length = settings.starburst_length * diagonal;
+    if settings.starburst > 0.0 && settings.starburst_rays > 0 && length >= 1.0
+    {
+        let rays =
+            streaks(&excess, width, settings.starburst_rays, 0.0, length);
+        for (pixel, light) in flare.iter_mut().zip(&rays) {
+            for c in 0..3 {
+                pixel[c] += settings.starburst * light[c];
+            }
+        }
+    }
+
+    let mut result = HdrImage::new(hdr.width, hdr.height, hdr.chan);
+    for (i, light) in flare.iter().enumerate() {
+        let mut pixel = hdr.get_pixel(i);
+        for c in 0..3 {
+            pixel[c] += light[c];
+        }
+        result.set_pixel(i, pixel);
+    }
+    result
+}
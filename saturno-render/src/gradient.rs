@@ -0,0 +1,139 @@
+use saturno_math::common::Float;
+
+// Screened Poisson reconstruction of gradient-domain rendering (see
+// `GradientDomain`): the image closest to both the pixels, weighted by
+// alpha squared, and the gradients, in the least squares sense. It solves
+//
+//     (alpha^2 + D^T D) image = alpha^2 pixels + D^T gradients
+//
+// where D takes the forward differences of an image (to the right and
+// down), by conjugate gradients, from the pixels. The differences summing
+// to nothing over the image, the mean of the pixels is kept.
+
+/**
+ * The forward differences of `image`: to the right neighbor, then to the
+ * lower one, 0 on the last column and row.
+ */
+fn differences(
+    image: &[Float],
+    width: usize,
+    height: usize,
+) -> (Vec<Float>, Vec<Float>) {
+    let mut dx = vec![0.0; image.len()];
+    let mut dy = vec![0.0; image.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width {
+                dx[i] = image[i + 1] - image[i];
+            }
+            if y + 1 < height {
+                dy[i] = image[i + width] - image[i];
+            }
+        }
+    }
+    (dx, dy)
+}
+
+/**
+ * D^T of the differences `dx` and `dy`: minus their divergence.
+ */
+fn adjoint(
+    dx: &[Float],
+    dy: &[Float],
+    width: usize,
+    height: usize,
+) -> Vec<Float> {
+    let mut image = vec![0.0; dx.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width {
+                image[i] -= dx[i];
+                image[i + 1] += dx[i];
+            }
+            if y + 1 < height {
+                image[i] -= dy[i];
+                image[i + width] += dy[i];
+            }
+        }
+    }
+    image
+}
+
+fn dot(a: &[Float], b: &[Float]) -> Float {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+/**
+ * Rebuild a `width` by `height` RGB image from its noisy `pixels` and
+ * the gradients `dx` (to the right neighbor of each pixel) and `dy` (to
+ * the lower one), those of the last column and row ignored. `alpha`
+ * weighs the pixels against the gradients.
+ */
+pub fn reconstruct(
+    pixels: &[[Float; 3]],
+    dx: &[[Float; 3]],
+    dy: &[[Float; 3]],
+    width: usize,
+    height: usize,
+    alpha: Float,
+    iterations: u32,
+) -> Vec<[Float; 3]> {
+    let weight = alpha * alpha;
+    let apply = |image: &[Float]| -> Vec<Float> {
+        let (dx, dy) = differences(image, width, height);
+        adjoint(&dx, &dy, width, height)
+            .iter()
+            .zip(image)
+            .map(|(d, v)| weight * v + d)
+            .collect()
+    };
+
+    let mut image = vec![[0.0; 3]; pixels.len()];
+    for c in 0..3 {
+        let primal: Vec<Float> = pixels.iter().map(|p| p[c]).collect();
+        let mut gx: Vec<Float> = dx.iter().map(|g| g[c]).collect();
+        let mut gy: Vec<Float> = dy.iter().map(|g| g[c]).collect();
+        for y in 0..height {
+            gx[y * width + width - 1] = 0.0;
+        }
+        for g in &mut gy[(height - 1) * width..] {
+            *g = 0.0;
+        }
+        let target: Vec<Float> = adjoint(&gx, &gy, width, height)
+            .iter()
+            .zip(&primal)
+            .map(|(d, v)| weight * v + d)
+            .collect();
+
+        let mut solution = primal;
+        let mut residual: Vec<Float> = target
+            .iter()
+            .zip(apply(&solution))
+            .map(|(t, a)| t - a)
+            .collect();
+        let mut direction = residual.clone();
+        let mut norm = dot(&residual, &residual);
+        for _ in 0..iterations {
+            if norm <= Float::EPSILON * Float::EPSILON {
+                break;
+            }
+            let applied = apply(&direction);
+            let step = norm / dot(&direction, &applied);
+            for i in 0..solution.len() {
+                solution[i] += step * direction[i];
+                residual[i] -= step * applied[i];
+            }
+            let next = dot(&residual, &residual);
+            for i in 0..direction.len() {
+                direction[i] = residual[i] + next / norm * direction[i];
+            }
+            norm = next;
+        }
+        for (pixel, value) in image.iter_mut().zip(solution) {
+            pixel[c] = value;
+        }
+    }
+    image
+}
@@ -0,0 +1,412 @@
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::random;
+use saturno_scene::actor::Aabb;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+// Path guiding with an SD-tree (Müller et al. 2017): the light arriving at
+// the diffuse surfaces is learned during training passes, and their bounces
+// then head where it comes from. Space is split by a binary tree (halving
+// a cell along its axes in turn, where enough paths went through it), each
+// cell holding the distribution of the directions the light came from: a
+// quadtree over the square the sphere maps to (by equal areas, cosine and
+// azimuth), finer where more light came from.
+//
+// A pass records the light (divided by the probability of its direction,
+// for an estimate of the integral) into a copy of the tree of the pass
+// before, which the bounces of the pass sample from; the copy is then
+// refined for the next one.
+
+/**
+ * Deepest level of the quadtrees.
+ */
+const MAX_DEPTH: u32 = 20;
+
+/**
+ * The point of the unit square a unit direction maps to.
+ */
+fn to_square(direction: &[Float; 3]) -> [Float; 2] {
+    let u = ((direction[2] + 1.0) / 2.0).clamp(0.0, 1.0);
+    let phi = direction[1].atan2(direction[0]);
+    let v = phi / (2.0 * consts::PI);
+    [u, v - v.floor()]
+}
+
+/**
+ * The unit direction a point of the unit square maps to.
+ */
+fn to_direction(square: &[Float; 2]) -> [Float; 3] {
+    let cos = 2.0 * square[0] - 1.0;
+    let sin = (1.0 - cos * cos).max(0.0).sqrt();
+    let phi = 2.0 * consts::PI * square[1];
+    [sin * phi.cos(), sin * phi.sin(), cos]
+}
+
+/**
+ * The quadrant of a point of the square (of a node) and the point in it.
+ */
+fn quadrant(square: &[Float; 2]) -> (usize, [Float; 2]) {
+    let half = [0, 1].map(|i| square[i] >= 0.5);
+    let index = half[0] as usize + 2 * half[1] as usize;
+    let inner = [0, 1].map(|i| {
+        let offset = if half[i] { 1.0 } else { 0.0 };
+        (2.0 * square[i] - offset).min(1.0 - Float::EPSILON)
+    });
+    (index, inner)
+}
+
+#[derive(Clone, Debug)]
+struct Quad {
+    /**
+     * Light of each quadrant (their whole subtree).
+     */
+    energy: [Float; 4],
+    /**
+     * Node of each quadrant, 0 for leaves (the root is never a child).
+     */
+    children: [usize; 4],
+}
+
+/**
+ * Distribution of the directions over the unit square.
+ */
+#[derive(Clone, Debug)]
+struct Quadtree {
+    nodes: Vec<Quad>,
+}
+
+impl Quadtree {
+    fn new() -> Quadtree {
+        Quadtree {
+            nodes: vec![Quad {
+                energy: [0.0; 4],
+                children: [0; 4],
+            }],
+        }
+    }
+
+    fn total(&self) -> Float {
+        self.nodes[0].energy.iter().sum()
+    }
+
+    fn add(&mut self, square: &[Float; 2], energy: Float) {
+        let (mut node, mut square) = (0, *square);
+        loop {
+            let (index, inner) = quadrant(&square);
+            self.nodes[node].energy[index] += energy;
+            match self.nodes[node].children[index] {
+                0 => return,
+                child => (node, square) = (child, inner),
+            }
+        }
+    }
+
+    /**
+     * The same tree with no light, to record a pass into.
+     */
+    fn cleared(&self) -> Quadtree {
+        let mut tree = self.clone();
+        for node in &mut tree.nodes {
+            node.energy = [0.0; 4];
+        }
+        tree
+    }
+
+    /**
+     * A tree subdividing the quadrants holding more than `subdivision`
+     * of the light (a split quadrant sharing its light evenly), and
+     * merging the others.
+     */
+    fn refined(&self, subdivision: Float) -> Quadtree {
+        let mut tree = Quadtree { nodes: vec![] };
+        let threshold = subdivision * self.total();
+        self.rebuild(&mut tree, Some(0), self.nodes[0].energy, threshold, 1);
+        tree
+    }
+
+    fn rebuild(
+        &self,
+        tree: &mut Quadtree,
+        old: Option<usize>,
+        energy: [Float; 4],
+        threshold: Float,
+        depth: u32,
+    ) -> usize {
+        let index = tree.nodes.len();
+        tree.nodes.push(Quad {
+            energy,
+            children: [0; 4],
+        });
+        for (q, &light) in energy.iter().enumerate() {
+            if threshold > 0.0 && light > threshold && depth < MAX_DEPTH {
+                let child = old
+                    .map(|old| self.nodes[old].children[q])
+                    .filter(|&child| child != 0);
+                let split = match child {
+                    Some(child) => self.nodes[child].energy,
+                    None => [light / 4.0; 4],
+                };
+                let node =
+                    self.rebuild(tree, child, split, threshold, depth + 1);
+                tree.nodes[index].children[q] = node;
+            }
+        }
+        index
+    }
+
+    /**
+     * A point of the square drawn following the light, and its density.
+     */
+    fn sample(&self) -> ([Float; 2], Float) {
+        let (mut node, mut density) = (0, 1.0);
+        let (mut origin, mut size) = ([0.0, 0.0], 1.0);
+        loop {
+            let energy = self.nodes[node].energy;
+            let total: Float = energy.iter().sum();
+            let mut pick = random::gen_range(0.0, total);
+            let mut index =
+                (0..4).rev().find(|&q| energy[q] > 0.0).unwrap_or(0);
+            for (q, e) in energy.iter().enumerate() {
+                if pick < *e {
+                    index = q;
+                    break;
+                }
+                pick -= e;
+            }
+            density *= 4.0 * energy[index] / total;
+            size /= 2.0;
+            origin[0] += size * (index % 2) as Float;
+            origin[1] += size * (index / 2) as Float;
+            match self.nodes[node].children[index] {
+                0 => {
+                    let point = [
+                        origin[0] + size * random::gen_range(0.0, 1.0),
+                        origin[1] + size * random::gen_range(0.0, 1.0),
+                    ];
+                    return (point, density);
+                }
+                child => node = child,
+            }
+        }
+    }
+
+    /**
+     * Density of the distribution at a point of the square.
+     */
+    fn density(&self, square: &[Float; 2]) -> Float {
+        let (mut node, mut square, mut density) = (0, *square, 1.0);
+        loop {
+            let energy = self.nodes[node].energy;
+            let total: Float = energy.iter().sum();
+            if total <= 0.0 {
+                return 0.0;
+            }
+            let (index, inner) = quadrant(&square);
+            density *= 4.0 * energy[index] / total;
+            match self.nodes[node].children[index] {
+                0 => return density,
+                child => (node, square) = (child, inner),
+            }
+        }
+    }
+}
+
+/**
+ * A cell of space: split in two along `axis` at its middle, or holding
+ * the distribution of the light its bounces sample (`sampling`), and the
+ * one the pass records (with the number of records).
+ */
+struct Cell {
+    min: [Float; 3],
+    max: [Float; 3],
+    axis: usize,
+    children: Option<[usize; 2]>,
+    sampling: Quadtree,
+    recording: Mutex<(Quadtree, u64)>,
+}
+
+impl Cell {
+    fn new(
+        min: [Float; 3],
+        max: [Float; 3],
+        axis: usize,
+        tree: Quadtree,
+    ) -> Cell {
+        Cell {
+            min,
+            max,
+            axis,
+            children: None,
+            recording: Mutex::new((tree.cleared(), 0)),
+            sampling: tree,
+        }
+    }
+}
+
+/**
+ * The learned light of a render, shared by the threads rendering it.
+ */
+pub struct Guide {
+    cells: RwLock<Vec<Cell>>,
+    learning: AtomicBool,
+}
+
+impl Default for Guide {
+    fn default() -> Guide {
+        Guide::new()
+    }
+}
+
+impl Guide {
+    pub fn new() -> Guide {
+        Guide {
+            cells: RwLock::new(vec![]),
+            learning: AtomicBool::new(false),
+        }
+    }
+
+    /**
+     * Whether the bounces record the light they find (in training).
+     */
+    pub fn learning(&self) -> bool {
+        self.learning.load(Ordering::Relaxed)
+    }
+
+    pub fn set_learning(&self, learning: bool) {
+        self.learning.store(learning, Ordering::Relaxed);
+    }
+
+    /**
+     * Forget the light learned, space covering `bounds` from now on.
+     */
+    pub fn clear(&self, bounds: Option<Aabb>) {
+        let (min, max) = match bounds {
+            Some(b) => (
+                [b.min[0], b.min[1], b.min[2]],
+                [b.max[0], b.max[1], b.max[2]],
+            ),
+            None => ([-1e6; 3], [1e6; 3]),
+        };
+        *self.cells.write().unwrap() =
+            vec![Cell::new(min, max, 0, Quadtree::new())];
+    }
+
+    /**
+     * Number of cells space is split into.
+     */
+    pub fn len(&self) -> usize {
+        let cells = self.cells.read().unwrap();
+        cells.iter().filter(|cell| cell.children.is_none()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn cell(cells: &[Cell], point: &[Float; 3]) -> Option<usize> {
+        let mut index = 0;
+        while let Some(children) = cells.get(index)?.children {
+            let cell = &cells[index];
+            let middle = (cell.min[cell.axis] + cell.max[cell.axis]) / 2.0;
+            index = children[(point[cell.axis] >= middle) as usize];
+        }
+        Some(index)
+    }
+
+    /**
+     * Whether light was learned around `point`.
+     */
+    pub fn guides(&self, point: &[Float; 3]) -> bool {
+        let cells = self.cells.read().unwrap();
+        Guide::cell(&cells, point)
+            .is_some_and(|cell| cells[cell].sampling.total() > 0.0)
+    }
+
+    /**
+     * A unit direction at `point` drawn following the light learned
+     * there, and its density (over the sphere).
+     */
+    pub fn sample(&self, point: &[Float; 3]) -> Option<([Float; 3], Float)> {
+        let cells = self.cells.read().unwrap();
+        let tree = &cells[Guide::cell(&cells, point)?].sampling;
+        if tree.total() <= 0.0 {
+            return None;
+        }
+        let (square, density) = tree.sample();
+        Some((to_direction(&square), density / (4.0 * consts::PI)))
+    }
+
+    /**
+     * Density (over the sphere) of the unit `direction` at `point`.
+     */
+    pub fn density(&self, point: &[Float; 3], direction: &[Float; 3]) -> Float {
+        let cells = self.cells.read().unwrap();
+        match Guide::cell(&cells, point) {
+            Some(cell) => {
+                cells[cell].sampling.density(&to_square(direction))
+                    / (4.0 * consts::PI)
+            }
+            None => 0.0,
+        }
+    }
+
+    /**
+     * Record the `radiance` (a scalar) arriving at `point` from the unit
+     * `direction`, which was drawn with the density `density`.
+     */
+    pub fn record(
+        &self,
+        point: &[Float; 3],
+        direction: &[Float; 3],
+        radiance: Float,
+        density: Float,
+    ) {
+        if !(radiance.is_finite() && density > 0.0) {
+            return;
+        }
+        let cells = self.cells.read().unwrap();
+        if let Some(cell) = Guide::cell(&cells, point) {
+            let mut recording = cells[cell].recording.lock().unwrap();
+            recording.1 += 1;
+            if radiance > 0.0 {
+                recording.0.add(&to_square(direction), radiance / density);
+            }
+        }
+    }
+
+    /**
+     * End a training pass: split the cells which recorded more than
+     * `threshold` paths, and refine the light they recorded (see
+     * `Quadtree::refined`) into what the next pass samples.
+     */
+    pub fn refine(&self, threshold: u64, subdivision: Float) {
+        let mut cells = self.cells.write().unwrap();
+        for index in 0..cells.len() {
+            if cells[index].children.is_some() {
+                continue;
+            }
+            let recording = cells[index].recording.get_mut().unwrap();
+            let (recorded, count) =
+                std::mem::replace(recording, (Quadtree::new(), 0));
+            let tree = recorded.refined(subdivision);
+            let (min, max, axis) =
+                (cells[index].min, cells[index].max, cells[index].axis);
+            if count > threshold {
+                let middle = (min[axis] + max[axis]) / 2.0;
+                let (mut below, mut above) = (max, min);
+                below[axis] = middle;
+                above[axis] = middle;
+                let next = (axis + 1) % 3;
+                let first = cells.len();
+                cells[index].children = Some([first, first + 1]);
+                cells.push(Cell::new(min, below, next, tree.clone()));
+                cells.push(Cell::new(above, max, next, tree));
+            } else {
+                cells[index] = Cell::new(min, max, axis, tree);
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::settings::Imperfections;
+use saturno_math::common::Float;
+
+// Imperfections of a camera (see `Imperfections`), on the tone mapped
+// image: its lens darkens the corners (vignetting, falling off as a power
+// of the distance to the center) and bends the colors slightly apart
+// towards the edges (lateral chromatic aberration: the red image a little
+// larger than the green one, the blue one a little smaller), and its film
+// or sensor adds grain.
+//
+// The grain is noise of about unit deviation, the same for the three
+// channels, hashed from the seed and the position: the same seed gives the
+// same grain, change it per frame for the grain of a film.
+
+/**
+ * Mix the bits of `x` (the finalizer of SplitMix64).
+ */
+fn hash(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/**
+ * Noise of mean 0 and deviation 1 at the lattice point `x`, `y` for
+ * `seed`, as the sum of four uniform values.
+ */
+fn lattice(seed: u64, x: i64, y: i64) -> Float {
+    let mut state = hash(seed ^ hash((x as u64) ^ hash(y as u64)));
+    let mut sum = 0.0;
+    for _ in 0..4 {
+        state = hash(state.wrapping_add(0x9e37_79b9_7f4a_7c15));
+        sum += (state >> 11) as Float / (1u64 << 53) as Float;
+    }
+    (sum - 2.0) * (3.0 as Float).sqrt()
+}
+
+/**
+ * Grain at the pixel `x`, `y`: the lattice noise `size` pixels apart,
+ * interpolated bilinearly.
+ */
+fn grain(seed: u64, size: Float, x: usize, y: usize) -> Float {
+    let (gx, gy) = (x as Float / size, y as Float / size);
+    let (x0, y0) = (gx.floor(), gy.floor());
+    let (fx, fy) = (gx - x0, gy - y0);
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    (1.0 - fx) * (1.0 - fy) * lattice(seed, x0, y0)
+        + fx * (1.0 - fy) * lattice(seed, x0 + 1, y0)
+        + (1.0 - fx) * fy * lattice(seed, x0, y0 + 1)
+        + fx * fy * lattice(seed, x0 + 1, y0 + 1)
+}
+
+/**
+ * The channel `c` of the RGB `image` (`width` by `height`) interpolated
+ * bilinearly at the pixel coordinates `x` and `y`, clamped to the image.
+ */
+fn sample(
+    image: &[[Float; 3]],
+    width: usize,
+    height: usize,
+    x: Float,
+    y: Float,
+    c: usize,
+) -> Float {
+    let x = (x - 0.5).clamp(0.0, (width - 1) as Float);
+    let y = (y - 0.5).clamp(0.0, (height - 1) as Float);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as Float, y - y0 as Float);
+    let at = |x: usize, y: usize| image[y * width + x][c];
+    (1.0 - fx) * (1.0 - fy) * at(x0, y0)
+        + fx * (1.0 - fy) * at(x1, y0)
+        + (1.0 - fx) * fy * at(x0, y1)
+        + fx * fy * at(x1, y1)
+}
+
+/**
+ * The tone mapped RGB `image` (`width` by `height`) with the vignetting,
+ * chromatic aberration and grain of `settings`.
+ */
+pub fn camera_imperfections(
+    image: &[[Float; 3]],
+    width: usize,
+    height: usize,
+    settings: &Imperfections,
+) -> Vec<[Float; 3]> {
+    let center = (width as Float / 2.0, height as Float / 2.0);
+    let corner = (center.0 * center.0 + center.1 * center.1).sqrt();
+
+    (0..image.len())
+        .map(|i| {
+            let (px, py) = (i % width, i / width);
+            let x = px as Float + 0.5 - center.0;
+            let y = py as Float + 0.5 - center.1;
+
+            // Each channel shows the point its image, scaled about the
+            // center, brings to the pixel.
+            let mut color = if settings.chromatic_aberration != 0.0 {
+                [0, 1, 2].map(|c| {
+                    let k = 1.0
+                        + settings.chromatic_aberration * (1.0 - c as Float);
+                    sample(
+                        image,
+                        width,
+                        height,
+                        center.0 + x / k,
+                        center.1 + y / k,
+                        c,
+                    )
+                })
+            } else {
+                image[i]
+            };
+
+            if settings.vignette > 0.0 {
+                let distance = (x * x + y * y).sqrt() / corner;
+                let darkening = settings.vignette
+                    * distance.powf(settings.vignette_falloff);
+                color = color.map(|v| v * (1.0 - darkening).max(0.0));
+            }
+
+            if settings.grain > 0.0 {
+                let noise = grain(settings.seed, settings.grain_size, px, py);
+                color = color.map(|v| (v + settings.grain * noise).max(0.0));
+            }
+            color
+        })
+        .collect()
+}
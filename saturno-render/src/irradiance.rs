@@ -0,0 +1,363 @@
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::random;
+use saturno_scene::actor::Aabb;
+use saturno_scene::light::orthonormal_basis;
+use std::sync::RwLock;
+
+// Irradiance caching (Ward et al. 1988): the indirect light reaching a
+// diffuse surface changes slowly over it, so it is only computed at a few
+// points, by sampling their whole hemisphere, and interpolated in between.
+// Each record is valid up to a distance proportional to the harmonic mean
+// distance of the surfaces around it (closer walls, faster changes), and
+// carries the gradients of its irradiance with the position and the
+// orientation of the surface (Ward and Heckbert 1992) to extrapolate it.
+//
+// The records are kept in an octree, each in the smallest node about its
+// size: the records valid at a point are found in the nodes around it.
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[Float; 3], b: &[Float; 3]) -> [Float; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/**
+ * The indirect light reaching a point of a surface. The irradiance is
+ * kept divided by pi: the mean incoming radiance, which a Lambertian
+ * surface reflects times its albedo.
+ */
+#[derive(Clone, Debug)]
+pub struct Record {
+    pub point: [Float; 3],
+    pub normal: [Float; 3],
+    pub radiance: [Float; 3],
+    /**
+     * Harmonic mean distance of the surfaces seen from the point.
+     */
+    pub radius: Float,
+    /**
+     * Gradients of each channel with the rotation of the normal (a
+     * rotation of the normal by a small vector `r` changes it by
+     * `r · gradient`) and with the position.
+     */
+    pub rotation: [[Float; 3]; 3],
+    pub translation: [[Float; 3]; 3],
+}
+
+impl Record {
+    /**
+     * Sample the hemisphere above `point` (facing the unit `normal`) with
+     * about `rays` rays, stratified by the cosine. `incoming` returns the
+     * radiance arriving along a direction and the distance of the surface
+     * it comes from (infinite if none). The radius is clamped between
+     * `min_radius` and `max_radius`.
+     */
+    pub fn sample<F>(
+        point: [Float; 3],
+        normal: [Float; 3],
+        rays: u32,
+        min_radius: Float,
+        max_radius: Float,
+        mut incoming: F,
+    ) -> Record
+    where
+        F: FnMut(&[Float; 3]) -> ([Float; 3], Float),
+    {
+        let m = ((rays as Float / consts::PI).sqrt().round() as usize).max(2);
+        let n = ((rays as Float / m as Float).round() as usize).max(3);
+        let (u, v) = orthonormal_basis(&normal);
+        let tangent =
+            |phi: Float| [0, 1, 2].map(|i| phi.cos() * u[i] + phi.sin() * v[i]);
+
+        let mut radiance = vec![[0.0; 3]; m * n];
+        let mut distance = vec![0.0; m * n];
+        let mut mean = [0.0; 3];
+        let mut inverse_distances = 0.0;
+        for j in 0..m {
+            for k in 0..n {
+                let sin2 =
+                    (j as Float + random::gen_range(0.0, 1.0)) / m as Float;
+                let phi = 2.0
+                    * consts::PI
+                    * (k as Float + random::gen_range(0.0, 1.0))
+                    / n as Float;
+                let (sin, cos) = (sin2.sqrt(), (1.0 - sin2).max(0.0).sqrt());
+                let side = tangent(phi);
+                let direction =
+                    [0, 1, 2].map(|i| sin * side[i] + cos * normal[i]);
+                let (l, d) = incoming(&direction);
+                let d = d.clamp(min_radius, max_radius);
+                for c in 0..3 {
+                    mean[c] += l[c] / (m * n) as Float;
+                }
+                inverse_distances += 1.0 / d;
+                radiance[j * n + k] = l;
+                distance[j * n + k] = d;
+            }
+        }
+        let radius = ((m * n) as Float / inverse_distances)
+            .clamp(min_radius, max_radius);
+
+        // Gradients of the irradiance (over pi) from the differences
+        // between neighboring cells (Ward and Heckbert 1992).
+        let mut rotation = [[0.0; 3]; 3];
+        let mut translation = [[0.0; 3]; 3];
+        let theta = |j: usize| (j as Float / m as Float).sqrt().asin();
+        for k in 0..n {
+            let phi = 2.0 * consts::PI * k as Float / n as Float;
+            let center = tangent(phi + consts::PI / n as Float);
+            let across = tangent(phi + consts::PI / 2.0);
+            let previous = (k + n - 1) % n;
+            for j in 0..m {
+                let l = radiance[j * n + k];
+                let middle = ((j as Float + 0.5) / m as Float).sqrt().asin();
+                let turned = cross(&normal, &center);
+                for c in 0..3 {
+                    let r = middle.tan() * l[c] / (m * n) as Float;
+                    for i in 0..3 {
+                        rotation[c][i] += r * turned[i];
+                    }
+                }
+
+                let side = (theta(j + 1).sin() - theta(j).sin())
+                    / distance[j * n + k].min(distance[j * n + previous]);
+                let azimuthal = [0, 1, 2]
+                    .map(|c| side * (l[c] - radiance[j * n + previous][c]));
+                for c in 0..3 {
+                    for i in 0..3 {
+                        translation[c][i] +=
+                            azimuthal[c] * across[i] / consts::PI;
+                    }
+                }
+                if j > 0 {
+                    let t = theta(j);
+                    let below = radiance[(j - 1) * n + k];
+                    let polar = 2.0 * consts::PI / n as Float
+                        * t.sin()
+                        * t.cos().powi(2)
+                        / distance[j * n + k].min(distance[(j - 1) * n + k]);
+                    for c in 0..3 {
+                        for i in 0..3 {
+                            translation[c][i] +=
+                                polar * (l[c] - below[c]) * center[i]
+                                    / consts::PI;
+                        }
+                    }
+                }
+            }
+        }
+
+        Record {
+            point,
+            normal,
+            radiance: mean,
+            radius,
+            rotation,
+            translation,
+        }
+    }
+
+    /**
+     * Weight of the record at `point` of unit `normal` (Ward's error
+     * estimate, inverted), 0 where it does not apply: in front of or
+     * behind the point.
+     */
+    fn weight(&self, point: &[Float; 3], normal: &[Float; 3]) -> Float {
+        let offset = [0, 1, 2].map(|i| point[i] - self.point[i]);
+        let average = [0, 1, 2].map(|i| (normal[i] + self.normal[i]) / 2.0);
+        if dot(&offset, &average) < -0.05 * self.radius {
+            return 0.0;
+        }
+        let turn = (1.0 - dot(normal, &self.normal)).max(0.0).sqrt();
+        let error = dot(&offset, &offset).sqrt() / self.radius + turn;
+        if error <= 0.0 {
+            Float::MAX
+        } else {
+            1.0 / error
+        }
+    }
+
+    /**
+     * The radiance extrapolated to `point` of unit `normal`.
+     */
+    fn extrapolate(
+        &self,
+        point: &[Float; 3],
+        normal: &[Float; 3],
+    ) -> [Float; 3] {
+        let offset = [0, 1, 2].map(|i| point[i] - self.point[i]);
+        let turn = cross(&self.normal, normal);
+        [0, 1, 2].map(|c| {
+            (self.radiance[c]
+                + dot(&turn, &self.rotation[c])
+                + dot(&offset, &self.translation[c]))
+            .max(0.0)
+        })
+    }
+}
+
+struct Node {
+    center: [Float; 3],
+    half: Float,
+    records: Vec<Record>,
+    children: Option<Box<[Node; 8]>>,
+}
+
+impl Node {
+    fn new(center: [Float; 3], half: Float) -> Node {
+        Node {
+            center,
+            half,
+            records: vec![],
+            children: None,
+        }
+    }
+
+    fn child(&self, point: &[Float; 3]) -> usize {
+        (0..3)
+            .filter(|&i| point[i] > self.center[i])
+            .map(|i| 1 << i)
+            .sum()
+    }
+
+    fn insert(&mut self, record: Record, reach: Float, depth: u32) {
+        if reach > self.half / 2.0 || depth >= 24 {
+            self.records.push(record);
+            return;
+        }
+        let (center, half) = (self.center, self.half / 2.0);
+        let index = self.child(&record.point);
+        let children = self.children.get_or_insert_with(|| {
+            Box::new(std::array::from_fn(|index| {
+                Node::new(
+                    [0, 1, 2].map(|i| {
+                        let sign =
+                            if index & (1 << i) != 0 { 1.0 } else { -1.0 };
+                        center[i] + sign * half
+                    }),
+                    half,
+                )
+            }))
+        });
+        children[index].insert(record, reach, depth + 1);
+    }
+
+    /**
+     * Visit the records of the nodes whose bounds, grown by their half
+     * size (as far as their records reach), contain `point`.
+     */
+    fn visit<F: FnMut(&Record)>(&self, point: &[Float; 3], visitor: &mut F) {
+        for record in &self.records {
+            visitor(record);
+        }
+        if let Some(children) = &self.children {
+            for child in children.iter() {
+                let reach = 2.0 * child.half;
+                if (0..3).all(|i| (point[i] - child.center[i]).abs() <= reach) {
+                    child.visit(point, visitor);
+                }
+            }
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.records.len()
+            + self
+                .children
+                .as_ref()
+                .map_or(0, |children| children.iter().map(Node::count).sum())
+    }
+}
+
+/**
+ * The records of a render, shared by the threads rendering it.
+ */
+pub struct IrradianceCache {
+    root: RwLock<Option<Node>>,
+}
+
+impl Default for IrradianceCache {
+    fn default() -> IrradianceCache {
+        IrradianceCache::new()
+    }
+}
+
+impl IrradianceCache {
+    pub fn new() -> IrradianceCache {
+        IrradianceCache {
+            root: RwLock::new(None),
+        }
+    }
+
+    /**
+     * Forget every record, the octree covering `bounds` from now on.
+     */
+    pub fn clear(&self, bounds: Option<Aabb>) {
+        let (center, half) = match bounds {
+            Some(b) => (
+                [0, 1, 2].map(|i| (b.min[i] + b.max[i]) / 2.0),
+                (0..3)
+                    .map(|i| (b.max[i] - b.min[i]) / 2.0)
+                    .fold(1e-3, Float::max),
+            ),
+            None => ([0.0; 3], 1e6),
+        };
+        *self.root.write().unwrap() = Some(Node::new(center, half));
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.read().unwrap().as_ref().map_or(0, Node::count)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Keep `record`, valid up to `accuracy` times its radius.
+     */
+    pub fn insert(&self, record: Record, accuracy: Float) {
+        let mut root = self.root.write().unwrap();
+        let root = root.get_or_insert_with(|| Node::new([0.0; 3], 1e6));
+        let reach = accuracy * record.radius;
+        root.insert(record, reach, 0);
+    }
+
+    /**
+     * The radiance at `point` of unit `normal` interpolated between the
+     * records whose error stays within `accuracy`, None if there are none.
+     */
+    pub fn lookup(
+        &self,
+        point: &[Float; 3],
+        normal: &[Float; 3],
+        accuracy: Float,
+    ) -> Option<[Float; 3]> {
+        let root = self.root.read().unwrap();
+        let mut total = [0.0; 3];
+        let mut weights = 0.0;
+        root.as_ref()?.visit(point, &mut |record| {
+            let weight = record.weight(point, normal);
+            if weight > 1.0 / accuracy {
+                let weight = weight.min(1e6);
+                let radiance = record.extrapolate(point, normal);
+                for c in 0..3 {
+                    total[c] += weight * radiance[c];
+                }
+                weights += weight;
+            }
+        });
+        if weights > 0.0 {
+            Some(total.map(|c| c / weights))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Rendering of a scene: the canvas traces the camera rays through the world
+// into framebuffers and auxiliary images (AOVs), following the settings.
+// The modules hidden from the documentation are internals of the canvas,
+// public only for the tests of the rendering crate; the photon and
+// reservoir grids are private.
+use saturno_math::common::Float;
+
+pub mod analysis;
+pub mod aov;
+pub mod bloom;
+pub mod canvas;
+#[doc(hidden)]
+pub mod caustics;
+pub mod contact_sheet;
+pub mod debug;
+pub mod display;
+pub mod error;
+pub mod estimate;
+pub mod flare;
+#[doc(hidden)]
+pub mod gradient;
+#[doc(hidden)]
+pub mod guiding;
+pub mod imperfections;
+#[doc(hidden)]
+pub mod irradiance;
+#[doc(hidden)]
+pub mod light_tree;
+pub mod metadata;
+pub mod overlay;
+pub(crate) mod photons;
+pub mod progressive;
+pub(crate) mod reservoir;
+pub mod settings;
+#[doc(hidden)]
+pub mod spectral;
+pub mod stats;
+#[cfg(feature = "async")]
+pub mod stream;
+#[doc(hidden)]
+pub mod tiles;
+
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub chan: u32,
+    pub data: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: u32, height: u32, chan: u32) -> Image {
+        // Allocate for 4C (RGBA)
+        let size = width as usize * height as usize * chan as usize;
+        let data: Vec<u8> = vec![0; size];
+        Image {
+            width,
+            height,
+            chan,
+            data,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len() / self.chan as usize
+    }
+
+    pub fn get_pixel_coordinate(&mut self, index: usize) -> (u32, u32) {
+        // index = y * stride + x
+        let stride = self.width as usize;
+        let y = index / stride;
+        let x = index - y * stride;
+
+        (x as u32, y as u32)
+    }
+
+    pub fn get_value(&self, x: u32, y: u32, c: u32) -> u8 {
+        let index = (y * self.width + x) * self.chan + c;
+        self.data[index as usize]
+    }
+
+    pub fn set_pixel(&mut self, index: usize, color: [u8; 4]) {
+        let j = index * self.chan as usize;
+        self.data[j] = color[0];
+        self.data[j + 1] = color[1];
+        self.data[j + 2] = color[2];
+        self.data[j + 3] = color[3];
+    }
+
+    pub fn print(&self) {
+        for i in 0..self.size() {
+            let j = i * 4;
+            if i % 800 == 0 {
+                println!(
+                    ">> data: {0}, {1}, {2}, {3}",
+                    self.data[j],
+                    self.data[j + 1],
+                    self.data[j + 2],
+                    self.data[j + 3]
+                );
+            }
+        }
+    }
+}
+
+/**
+ * Floating point framebuffer holding the linear (not gamma corrected)
+ * radiance of every pixel, in RGBA.
+ */
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub chan: u32,
+    pub data: Vec<Float>,
+}
+
+impl HdrImage {
+    pub fn new(width: u32, height: u32, chan: u32) -> HdrImage {
+        let size = width as usize * height as usize * chan as usize;
+        HdrImage {
+            width,
+            height,
+            chan,
+            data: vec![0.0; size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len() / self.chan as usize
+    }
+
+    pub fn get_pixel_coordinate(&self, index: usize) -> (u32, u32) {
+        let stride = self.width as usize;
+        let y = index / stride;
+        let x = index - y * stride;
+
+        (x as u32, y as u32)
+    }
+
+    pub fn get_pixel(&self, index: usize) -> [Float; 4] {
+        let j = index * self.chan as usize;
+        [
+            self.data[j],
+            self.data[j + 1],
+            self.data[j + 2],
+            self.data[j + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, index: usize, color: [Float; 4]) {
+        let j = index * self.chan as usize;
+        self.data[j] = color[0];
+        self.data[j + 1] = color[1];
+        self.data[j + 2] = color[2];
+        self.data[j + 3] = color[3];
+    }
+}
@@ -0,0 +1,225 @@
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::random;
+use saturno_scene::light::Light;
+use saturno_scene::light::LightBounds;
+use std::sync::Arc;
+
+// Hierarchy of the lights (a light BVH, Conty Estevez and Kulla 2018), see
+// `LightHierarchy`. The lights with a position are split in two along the
+// longest axis of their positions, recursively, each node bounding the
+// positions of its lights, their total intensity and the cone they shine
+// in. A light is picked by walking down from the root, going to either
+// child by how much light it may send to the lit point (its intensity, by
+// the square of the distance, where its cone faces the point): picking
+// among n lights takes about log n steps, and the close and bright lights
+// are picked more often.
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: [Float; 3]) -> Option<[Float; 3]> {
+    let length = dot(&a, &a).sqrt();
+    (length > 0.0).then(|| a.map(|c| c / length))
+}
+
+enum Content {
+    Light(usize),
+    Children([usize; 2]),
+}
+
+struct Node {
+    min: [Float; 3],
+    max: [Float; 3],
+    intensity: Float,
+    axis: [Float; 3],
+    /**
+     * Half angle of the cone of the lights, pi for every direction.
+     */
+    cone: Float,
+    content: Content,
+}
+
+impl Node {
+    fn leaf(index: usize, bounds: &LightBounds) -> Node {
+        Node {
+            min: bounds.position.map(|c| c - bounds.radius),
+            max: bounds.position.map(|c| c + bounds.radius),
+            intensity: bounds.intensity.max(0.0),
+            axis: bounds.axis,
+            cone: bounds.cos_cone.clamp(-1.0, 1.0).acos(),
+            content: Content::Light(index),
+        }
+    }
+
+    fn union(a: &Node, b: &Node, children: [usize; 2]) -> Node {
+        // A cone around both cones, about their mean axis.
+        let mean = [0, 1, 2].map(|i| a.axis[i] + b.axis[i]);
+        let (axis, cone) = match normalize(mean) {
+            Some(axis) if a.cone < consts::PI && b.cone < consts::PI => {
+                let angle =
+                    |n: &Node| dot(&axis, &n.axis).clamp(-1.0, 1.0).acos();
+                let cone = (angle(a) + a.cone).max(angle(b) + b.cone);
+                (axis, cone.min(consts::PI))
+            }
+            _ => ([0.0, 0.0, 1.0], consts::PI),
+        };
+        Node {
+            min: [0, 1, 2].map(|i| a.min[i].min(b.min[i])),
+            max: [0, 1, 2].map(|i| a.max[i].max(b.max[i])),
+            intensity: a.intensity + b.intensity,
+            axis,
+            cone,
+            content: Content::Children(children),
+        }
+    }
+
+    /**
+     * Upper bound of the light the node may send to `point`, up to a
+     * constant: its intensity, where its cone may face the point, over
+     * the square of the distance to its center (at least its radius).
+     */
+    fn importance(&self, point: &[Float; 3]) -> Float {
+        let center = [0, 1, 2].map(|i| (self.min[i] + self.max[i]) / 2.0);
+        let offset = [0, 1, 2].map(|i| point[i] - center[i]);
+        let radius_squared = [0, 1, 2]
+            .map(|i| (self.max[i] - self.min[i]) / 2.0)
+            .iter()
+            .map(|h| h * h)
+            .sum::<Float>();
+        let distance_squared = dot(&offset, &offset);
+        let falloff = distance_squared.max(radius_squared).max(1e-12);
+        if self.cone >= consts::PI {
+            return self.intensity / falloff;
+        }
+
+        let distance = distance_squared.sqrt();
+        if distance * distance <= radius_squared || distance <= 0.0 {
+            return self.intensity / falloff;
+        }
+        let angle = (dot(&self.axis, &offset) / distance)
+            .clamp(-1.0, 1.0)
+            .acos();
+        let spread = (radius_squared.sqrt() / distance).min(1.0).asin();
+        let outside = (angle - self.cone - spread).max(0.0);
+        if outside >= consts::PI / 2.0 {
+            return 0.0;
+        }
+        self.intensity * outside.cos() / falloff
+    }
+}
+
+/**
+ * The lights of a scene, those with a position in a hierarchy.
+ */
+pub struct LightTree {
+    lights: Vec<Arc<dyn Light>>,
+    distant: Vec<Arc<dyn Light>>,
+    nodes: Vec<Node>,
+}
+
+impl LightTree {
+    pub fn new(lights: &[Arc<dyn Light>]) -> LightTree {
+        let mut tree = LightTree {
+            lights: vec![],
+            distant: vec![],
+            nodes: vec![],
+        };
+        let mut leaves = vec![];
+        for light in lights {
+            match light.bounds() {
+                Some(bounds) => {
+                    leaves.push(Node::leaf(tree.lights.len(), &bounds));
+                    tree.lights.push(light.clone());
+                }
+                None => tree.distant.push(light.clone()),
+            }
+        }
+        if !leaves.is_empty() {
+            tree.build(leaves);
+        }
+        tree
+    }
+
+    /**
+     * Add the node over `leaves`, after its children, returning its
+     * index. The root is the last node.
+     */
+    fn build(&mut self, mut leaves: Vec<Node>) -> usize {
+        if leaves.len() == 1 {
+            self.nodes.push(leaves.pop().unwrap());
+            return self.nodes.len() - 1;
+        }
+        let center = |n: &Node, i: usize| (n.min[i] + n.max[i]) / 2.0;
+        let extent = |i: usize| {
+            let centers = leaves.iter().map(|n| center(n, i));
+            let low = centers.clone().fold(Float::MAX, Float::min);
+            centers.fold(Float::MIN, Float::max) - low
+        };
+        let axis = (0..3)
+            .max_by(|&a, &b| extent(a).total_cmp(&extent(b)))
+            .unwrap();
+        leaves.sort_by(|a, b| center(a, axis).total_cmp(&center(b, axis)));
+        let upper = leaves.split_off(leaves.len() / 2);
+        let low = self.build(leaves);
+        let high = self.build(upper);
+        let node =
+            Node::union(&self.nodes[low], &self.nodes[high], [low, high]);
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /**
+     * Lights with a position, in the hierarchy.
+     */
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /**
+     * Lights without a position, not in the hierarchy: they light every
+     * point.
+     */
+    pub fn distant(&self) -> &[Arc<dyn Light>] {
+        &self.distant
+    }
+
+    /**
+     * Pick a light of the hierarchy to light `point`, returning it with
+     * the probability it was picked with. None if no light may reach it.
+     */
+    pub fn sample(
+        &self,
+        point: &[Float; 3],
+    ) -> Option<(&Arc<dyn Light>, Float)> {
+        let mut node = self.nodes.last()?;
+        let mut probability = 1.0;
+        loop {
+            match node.content {
+                Content::Light(index) => {
+                    return Some((&self.lights[index], probability));
+                }
+                Content::Children([low, high]) => {
+                    let (a, b) = (&self.nodes[low], &self.nodes[high]);
+                    let (ia, ib) = (a.importance(point), b.importance(point));
+                    if ia + ib <= 0.0 {
+                        return None;
+                    }
+                    let share = ia / (ia + ib);
+                    if random::gen_range(0.0, 1.0) < share {
+                        probability *= share;
+                        node = a;
+                    } else {
+                        probability *= 1.0 - share;
+                        node = b;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::settings::RenderSettings;
+use std::time::Duration;
+
+// Metadata of a render, written into the image files (PNG text chunks,
+// Radiance header lines, OpenEXR string attributes) so that any image can
+// be traced back to how it was produced: the settings, the scene and a
+// hash of its source, the seed, the samples per pixel, the integrator and
+// the time taken.
+
+/**
+ * 64 bit FNV-1a hash of `bytes`, to tell the sources of scenes apart.
+ */
+pub fn scene_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/**
+ * Key-value pairs describing a render, in the order they were added.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderMetadata {
+    pub entries: Vec<(String, String)>,
+}
+
+impl RenderMetadata {
+    /**
+     * Metadata of a render with `settings`: the software, the settings
+     * (in their debug form), the seed, the samples per pixel and the
+     * integrator.
+     */
+    pub fn new(settings: &RenderSettings) -> RenderMetadata {
+        let mut metadata = RenderMetadata::default();
+        metadata.insert(
+            "Software",
+            format!("saturno {}", env!("CARGO_PKG_VERSION")),
+        );
+        metadata.insert("saturno:settings", format!("{:?}", settings));
+        let seed = settings.seed.map_or("none".to_string(), |s| s.to_string());
+        metadata.insert("saturno:seed", seed);
+        metadata.insert("saturno:samples", settings.samples.to_string());
+        metadata.insert("saturno:integrator", settings.integrator());
+        metadata
+    }
+
+    /**
+     * Add the scene `name` and the hash of its `source` (e.g. the scene
+     * file).
+     */
+    pub fn scene(mut self, name: &str, source: &[u8]) -> RenderMetadata {
+        self.insert("saturno:scene", name);
+        self.insert(
+            "saturno:scene_hash",
+            format!("{:016x}", scene_hash(source)),
+        );
+        self
+    }
+
+    /**
+     * Add the time the render took.
+     */
+    pub fn render_time(mut self, time: Duration) -> RenderMetadata {
+        self.insert(
+            "saturno:render_time",
+            format!("{:.3}s", time.as_secs_f64()),
+        );
+        self
+    }
+
+    /**
+     * Set `key` to `value`, replacing its value if already set.
+     */
+    pub fn insert<V: Into<String>>(&mut self, key: &str, value: V) {
+        let value = value.into();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_string(), value)),
+        }
+    }
+
+    /**
+     * The entries as the string attributes of `write_exr`.
+     */
+    pub fn attributes(&self) -> Vec<(&str, String)> {
+        self.entries
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.clone()))
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
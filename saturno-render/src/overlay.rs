@@ -0,0 +1,178 @@
+use crate::aov::actor_name;
+use crate::aov::name_hash;
+use crate::Image;
+use saturno_math::common::Float;
+use saturno_scene::actor::HittableList;
+use saturno_scene::camera::Camera;
+
+// Wireframe overlays, drawn with the camera's projection over the beauty
+// pass (or an empty image) to check where the geometry and its bounds are,
+// and focus peaking, to check what the depth of field keeps sharp.
+
+/**
+ * Points closer to the camera are clipped away before projecting.
+ */
+const NEAR: Float = 1e-3;
+
+/**
+ * Color of an actor in overlays, derived from its name so it stays the
+ * same from render to render.
+ */
+pub fn actor_color(actor_id: usize) -> [u8; 4] {
+    let hash = name_hash(&actor_name(actor_id));
+    // Keep the colors bright enough to stand out.
+    let channel = |shift: u32| 96 + ((hash >> shift) & 0xff) as u8 % 160;
+    [channel(0), channel(8), channel(16), 255]
+}
+
+/**
+ * Draw the edges of the bounding box of every bounded actor into `image`,
+ * which holds the pixels of `region` ((x, y) offset, in pixels) of what
+ * `camera` sees.
+ */
+pub fn draw_bounds(
+    image: &mut Image,
+    world: &HittableList,
+    camera: &Camera,
+    region: (u32, u32),
+) {
+    for (index, actor) in world.actors.iter().enumerate() {
+        if let Some(aabb) = actor.bounding_box() {
+            for (from, to) in aabb.edges().iter() {
+                draw_segment(
+                    image,
+                    camera,
+                    region,
+                    from,
+                    to,
+                    actor_color(index),
+                );
+            }
+        }
+    }
+}
+
+/**
+ * Draw the projection of a world space segment, clipped to the part in
+ * front of the camera.
+ */
+pub fn draw_segment(
+    image: &mut Image,
+    camera: &Camera,
+    region: (u32, u32),
+    from: &[Float; 3],
+    to: &[Float; 3],
+    color: [u8; 4],
+) {
+    let (depth_from, depth_to) =
+        (camera.view_depth(from), camera.view_depth(to));
+    if depth_from < NEAR && depth_to < NEAR {
+        return;
+    }
+
+    let clip = |inside: &[Float; 3], outside: &[Float; 3], d_in, d_out| {
+        let t = (d_in - NEAR) / (d_in - d_out);
+        let mut point = [0.0; 3];
+        for (i, value) in point.iter_mut().enumerate() {
+            *value = inside[i] + (outside[i] - inside[i]) * t;
+        }
+        point
+    };
+    let (from, to) = if depth_from < NEAR {
+        (clip(to, from, depth_to, depth_from), *to)
+    } else if depth_to < NEAR {
+        (*from, clip(from, to, depth_from, depth_to))
+    } else {
+        (*from, *to)
+    };
+
+    if let (Some(a), Some(b)) = (camera.project(&from), camera.project(&to)) {
+        let offset = (region.0 as Float, region.1 as Float);
+        draw_line(
+            image,
+            (a.0 - offset.0, a.1 - offset.1),
+            (b.0 - offset.0, b.1 - offset.1),
+            color,
+        );
+    }
+}
+
+/**
+ * Draw a one pixel wide line between two points in pixel coordinates,
+ * clipped to the image.
+ */
+pub fn draw_line(
+    image: &mut Image,
+    from: (Float, Float),
+    to: (Float, Float),
+    color: [u8; 4],
+) {
+    let (width, height) = (image.width as Float, image.height as Float);
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+
+    // Liang-Barsky: keep the parameters t in [t0, t1] within the image.
+    let (mut t0, mut t1): (Float, Float) = (0.0, 1.0);
+    for (p, q) in [
+        (-dx, from.0),
+        (dx, width - from.0),
+        (-dy, from.1),
+        (dy, height - from.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return;
+            }
+        } else if p < 0.0 {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    if t0 > t1 {
+        return;
+    }
+
+    // Step along the whole line so a clipped (e.g. cropped) line lights
+    // up the same pixels as the full one.
+    let steps = dx.abs().max(dy.abs()).ceil().max(1.0);
+    let first = (t0 * steps).ceil() as u64;
+    let last = (t1 * steps).floor() as u64;
+    for step in first..=last {
+        let t = step as Float / steps;
+        let x = (from.0 + dx * t).floor();
+        let y = (from.1 + dy * t).floor();
+        if x >= 0.0 && y >= 0.0 && x < width && y < height {
+            let index = y as usize * image.width as usize + x as usize;
+            image.set_pixel(index, color);
+        }
+    }
+}
+
+/**
+ * Color of the in focus parts in focus peaking.
+ */
+pub const FOCUS_COLOR: [u8; 3] = [40, 255, 40];
+
+/**
+ * Highlight in `image` the pixels in focus: those whose circle of
+ * confusion (`blur`, the radius in pixels of each pixel) is within a
+ * pixel, the more the sharper.
+ */
+pub fn draw_focus_peaking(image: &mut Image, blur: &[Float]) {
+    for (i, radius) in blur.iter().enumerate() {
+        let sharpness = (1.0 - radius).clamp(0.0, 1.0);
+        if sharpness <= 0.0 {
+            continue;
+        }
+        let amount = 0.25 + 0.5 * sharpness;
+        let mut pixel = [0; 4];
+        for (c, value) in pixel.iter_mut().enumerate().take(3) {
+            let current = image.data[i * 4 + c] as Float;
+            *value = (current * (1.0 - amount)
+                + FOCUS_COLOR[c] as Float * amount)
+                .round() as u8;
+        }
+        pixel[3] = 255;
+        image.set_pixel(i, pixel);
+    }
+}
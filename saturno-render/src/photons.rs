@@ -0,0 +1,207 @@
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use std::collections::HashMap;
+
+// Stochastic progressive photon mapping (see `PhotonMapping`): the state
+// of every pixel carried from pass to pass, and the grid the photons of a
+// pass find the pixels' points in.
+//
+// Each pixel gathers the photons landing within its radius around the
+// point its camera ray reached this pass. The radius then shrinks so that
+// only `alpha` of the photons gathered are kept, and the flux gathered so
+// far is scaled down with the area (Hachisuka and Jensen 2009): the
+// estimate converges as the radii go to zero.
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/**
+ * Where the camera ray of a pixel reached a Lambertian surface, in a pass.
+ */
+#[derive(Clone, Debug)]
+pub struct VisiblePoint {
+    pub point: [Float; 3],
+    /**
+     * Unit normal of the surface, on the side of the camera.
+     */
+    pub normal: [Float; 3],
+    /**
+     * Throughput of the camera path times the BRDF of the surface
+     * (albedo / pi): the radiance towards the camera per unit of
+     * irradiance.
+     */
+    pub weight: [Float; 3],
+}
+
+/**
+ * What a pixel gathered over the passes.
+ */
+#[derive(Clone, Debug)]
+pub struct PixelEstimate {
+    pub radius: Float,
+    /**
+     * Photons kept, fractional after the reductions.
+     */
+    pub photons: Float,
+    /**
+     * Flux gathered within the current radius, weighted by the points.
+     */
+    pub flux: [Float; 3],
+    /**
+     * Sum over the passes of the light found by the camera paths: seen
+     * directly, and sampled from the lights at the points. Its alpha is
+     * the coverage.
+     */
+    pub direct: [Float; 4],
+}
+
+/**
+ * The pixels' points of a pass, filed in every cell of the grid their
+ * radius overlaps: a photon only looks at the points of its cell.
+ */
+pub struct Grid {
+    cell: Float,
+    cells: HashMap<[i64; 3], Vec<usize>>,
+}
+
+impl Grid {
+    pub fn new(points: &[Option<VisiblePoint>], radii: &[Float]) -> Grid {
+        let cell = points
+            .iter()
+            .zip(radii)
+            .filter(|(point, _)| point.is_some())
+            .map(|(_, radius)| 2.0 * radius)
+            .fold(Float::MIN_POSITIVE, Float::max);
+        let mut grid = Grid {
+            cell,
+            cells: HashMap::new(),
+        };
+        for (index, (point, radius)) in points.iter().zip(radii).enumerate() {
+            if let Some(point) = point {
+                let low = grid.key(&point.point.map(|c| c - radius));
+                let high = grid.key(&point.point.map(|c| c + radius));
+                for x in low[0]..=high[0] {
+                    for y in low[1]..=high[1] {
+                        for z in low[2]..=high[2] {
+                            grid.cells
+                                .entry([x, y, z])
+                                .or_default()
+                                .push(index);
+                        }
+                    }
+                }
+            }
+        }
+        grid
+    }
+
+    fn key(&self, point: &[Float; 3]) -> [i64; 3] {
+        point.map(|c| (c / self.cell).floor() as i64)
+    }
+
+    /**
+     * Pixels whose points may be within their radius of `point`.
+     */
+    pub fn around(&self, point: &[Float; 3]) -> &[usize] {
+        self.cells
+            .get(&self.key(point))
+            .map_or(&[], |pixels| pixels.as_slice())
+    }
+}
+
+/**
+ * The estimates of the pixels of a render.
+ */
+pub struct PhotonMap {
+    pub pixels: Vec<PixelEstimate>,
+    pub passes: u32,
+}
+
+impl PhotonMap {
+    pub fn new(pixels: usize, radius: Float) -> PhotonMap {
+        PhotonMap {
+            pixels: vec![
+                PixelEstimate {
+                    radius,
+                    photons: 0.0,
+                    flux: [0.0; 3],
+                    direct: [0.0; 4],
+                };
+                pixels
+            ],
+            passes: 0,
+        }
+    }
+
+    pub fn radii(&self) -> Vec<Float> {
+        self.pixels.iter().map(|pixel| pixel.radius).collect()
+    }
+
+    /**
+     * Whether a photon landing at `point` from the unit direction `from`
+     * lights the point `visible` of a pixel.
+     */
+    pub fn gathers(
+        &self,
+        pixel: usize,
+        visible: &VisiblePoint,
+        point: &[Float; 3],
+        from: &[Float; 3],
+    ) -> bool {
+        let offset = [0, 1, 2].map(|i| point[i] - visible.point[i]);
+        let radius = self.pixels[pixel].radius;
+        dot(&offset, &offset) <= radius * radius
+            && dot(&visible.normal, from) > 0.0
+    }
+
+    /**
+     * End a pass: add the light the camera paths found (`direct`) and
+     * the flux (weighted by the points) and number of the photons each
+     * pixel gathered, then shrink the radii.
+     */
+    pub fn update(
+        &mut self,
+        direct: &[[Float; 4]],
+        gathered: &[([Float; 3], u32)],
+        alpha: Float,
+    ) {
+        for ((pixel, direct), (flux, count)) in
+            self.pixels.iter_mut().zip(direct).zip(gathered)
+        {
+            pixel.direct = [0, 1, 2, 3].map(|c| pixel.direct[c] + direct[c]);
+            if *count > 0 {
+                let count = *count as Float;
+                let kept = pixel.photons + alpha * count;
+                let scale = kept / (pixel.photons + count);
+                pixel.flux =
+                    [0, 1, 2].map(|c| (pixel.flux[c] + flux[c]) * scale);
+                pixel.radius *= scale.sqrt();
+                pixel.photons = kept;
+            }
+        }
+        self.passes += 1;
+    }
+
+    /**
+     * Radiance of the pixels after the passes so far, each photon carrying
+     * the power of the lights shared by the photons of its pass.
+     */
+    pub fn estimate(&self) -> Vec<[Float; 4]> {
+        let passes = self.passes.max(1) as Float;
+        self.pixels
+            .iter()
+            .map(|pixel| {
+                let area = consts::PI * pixel.radius * pixel.radius;
+                let gathered = |c: usize| pixel.flux[c] / (passes * area);
+                let direct = pixel.direct.map(|c| c / passes);
+                [
+                    direct[0] + gathered(0),
+                    direct[1] + gathered(1),
+                    direct[2] + gathered(2),
+                    direct[3],
+                ]
+            })
+            .collect()
+    }
+}
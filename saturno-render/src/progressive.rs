@@ -0,0 +1,74 @@
+use crate::canvas::develop;
+use crate::canvas::Canvas;
+use crate::error::Result;
+use crate::settings::RenderSettings;
+use crate::HdrImage;
+use crate::Image;
+use saturno_math::common::Float;
+
+/**
+ * Progressive render: one sample per pixel and pass, accumulated so the
+ * image can be shown while it converges. Stops after `settings.samples`
+ * passes.
+ */
+pub struct Progressive {
+    pub settings: RenderSettings,
+    sum: HdrImage,
+    passes: u32,
+}
+
+impl Progressive {
+    pub fn new(settings: RenderSettings) -> Progressive {
+        let region = settings.region();
+        Progressive {
+            settings,
+            sum: HdrImage::new(region.width, region.height, 4),
+            passes: 0,
+        }
+    }
+
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.passes >= self.settings.samples
+    }
+
+    /**
+     * Render and accumulate the next pass. Returns whether there are
+     * passes left.
+     */
+    pub fn step(&mut self, canvas: &Canvas) -> Result<bool> {
+        if self.is_done() {
+            return Ok(false);
+        }
+
+        let pass = canvas.render_pass(&self.settings, self.passes)?;
+        for (sum, value) in self.sum.data.iter_mut().zip(pass.data.iter()) {
+            *sum += value;
+        }
+        self.passes += 1;
+
+        Ok(!self.is_done())
+    }
+
+    /**
+     * Average of the passes rendered so far.
+     */
+    pub fn hdr(&self) -> HdrImage {
+        let mut image = HdrImage::new(self.sum.width, self.sum.height, 4);
+        if self.passes > 0 {
+            let passes = self.passes as Float;
+            for (value, sum) in image.data.iter_mut().zip(self.sum.data.iter())
+            {
+                *value = sum / passes;
+            }
+        }
+        image
+    }
+
+    pub fn image(&self) -> Image {
+        develop(&self.hdr(), &self.settings)
+    }
+}
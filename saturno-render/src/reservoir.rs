@@ -0,0 +1,207 @@
+use saturno_math::common::Float;
+use saturno_math::random;
+use saturno_scene::actor::Aabb;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+// Reservoir resampling of the lights (ReSTIR, Bitterli et al. 2020), see
+// `ReservoirSampling`. A point of a surface streams a few lights picked at
+// random through a reservoir, which keeps one of them with a probability
+// following the light it would reflect (unoccluded): the one shadow ray
+// goes towards a light likely to matter. The reservoirs of the points
+// around, kept from the samples before (in the same pixel or the next
+// ones), are streamed in too, each weighing as many lights as it saw:
+// over the samples, a point resamples among far more lights than it
+// picks.
+//
+// The reservoirs are kept in a grid over the scene, a few per cell, in
+// the order they come: the reuse is in world space (Boissé 2021), so it
+// does not need the pixels of the neighbors.
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/**
+ * The light a point kept, as reused by the points around it.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Reservoir {
+    /**
+     * Index of the light.
+     */
+    pub light: usize,
+    /**
+     * Contribution weight of the light: the sum of the weights streamed
+     * over their count, divided by its target. 0 if it was occluded.
+     */
+    pub weight: Float,
+    /**
+     * Number of lights streamed.
+     */
+    pub count: Float,
+}
+
+/**
+ * Weighted reservoir sampling of a stream of candidates, keeping one.
+ */
+pub struct Stream<T> {
+    chosen: Option<(usize, T, Float)>,
+    weights: Float,
+    count: Float,
+}
+
+impl<T> Default for Stream<T> {
+    fn default() -> Stream<T> {
+        Stream::new()
+    }
+}
+
+impl<T> Stream<T> {
+    pub fn new() -> Stream<T> {
+        Stream {
+            chosen: None,
+            weights: 0.0,
+            count: 0.0,
+        }
+    }
+
+    /**
+     * Stream the light `light` (with its `sample`), of target `target`,
+     * weighing `weight` and standing for `count` candidates.
+     */
+    pub fn add(
+        &mut self,
+        light: usize,
+        sample: T,
+        target: Float,
+        weight: Float,
+        count: Float,
+    ) {
+        self.count += count;
+        if !(weight > 0.0 && weight.is_finite()) {
+            return;
+        }
+        self.weights += weight;
+        if random::gen_range(0.0, 1.0) * self.weights < weight {
+            self.chosen = Some((light, sample, target));
+        }
+    }
+
+    /**
+     * Stream `count` candidates which reflect no light.
+     */
+    pub fn skip(&mut self, count: Float) {
+        self.count += count;
+    }
+
+    /**
+     * The light kept, its sample and the reservoir it makes.
+     */
+    pub fn finish(self) -> Option<(T, Reservoir)> {
+        let (light, sample, target) = self.chosen?;
+        let weight = self.weights / (self.count * target);
+        Some((
+            sample,
+            Reservoir {
+                light,
+                weight,
+                count: self.count,
+            },
+        ))
+    }
+}
+
+struct Kept {
+    normal: [Float; 3],
+    reservoir: Reservoir,
+}
+
+/**
+ * The reservoirs of a render, shared by the threads rendering it.
+ */
+pub struct ReservoirGrid {
+    cell: RwLock<Float>,
+    cells: RwLock<HashMap<[i64; 3], Vec<Kept>>>,
+}
+
+impl Default for ReservoirGrid {
+    fn default() -> ReservoirGrid {
+        ReservoirGrid::new()
+    }
+}
+
+impl ReservoirGrid {
+    pub fn new() -> ReservoirGrid {
+        ReservoirGrid {
+            cell: RwLock::new(1.0),
+            cells: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /**
+     * Forget every reservoir, the cells `radius` times the size of
+     * `bounds` from now on.
+     */
+    pub fn clear(&self, bounds: Option<Aabb>, radius: Float) {
+        let size = match bounds {
+            Some(b) => (0..3)
+                .map(|i| b.max[i] - b.min[i])
+                .filter(|d| d.is_finite())
+                .fold(0.0, Float::max),
+            None => 1.0,
+        };
+        *self.cell.write().unwrap() = (radius * size).max(1e-6);
+        self.cells.write().unwrap().clear();
+    }
+
+    fn key(&self, point: &[Float; 3]) -> [i64; 3] {
+        let cell = *self.cell.read().unwrap();
+        point.map(|c| (c / cell).floor() as i64)
+    }
+
+    /**
+     * The reservoirs kept in the cell of `point`, on surfaces facing
+     * about as the unit `normal`, the last `count` of them.
+     */
+    pub fn around(
+        &self,
+        point: &[Float; 3],
+        normal: &[Float; 3],
+        count: usize,
+    ) -> Vec<Reservoir> {
+        let key = self.key(point);
+        let cells = self.cells.read().unwrap();
+        cells.get(&key).map_or(vec![], |kept| {
+            kept.iter()
+                .rev()
+                .filter(|kept| dot(&kept.normal, normal) > 0.9)
+                .take(count)
+                .map(|kept| kept.reservoir)
+                .collect()
+        })
+    }
+
+    /**
+     * Keep the reservoir of `point`, of unit `normal`, in its cell, which
+     * keeps the last `keep` of them.
+     */
+    pub fn keep(
+        &self,
+        point: &[Float; 3],
+        normal: &[Float; 3],
+        reservoir: Reservoir,
+        keep: usize,
+    ) {
+        let key = self.key(point);
+        let mut cells = self.cells.write().unwrap();
+        let kept = cells.entry(key).or_default();
+        if kept.len() >= keep.max(1) {
+            kept.remove(0);
+        }
+        kept.push(Kept {
+            normal: *normal,
+            reservoir,
+        });
+    }
+}
@@ -0,0 +1,1718 @@
+use crate::display::Lut3d;
+use crate::error::Result;
+use crate::error::SaturnoError;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::random;
+use saturno_scene::color::white_balance;
+use saturno_scene::color::ColorSpace;
+use saturno_scene::color::Matrix;
+use saturno_scene::color::WHITE_TEMPERATURE;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/**
+ * Reconstruction filter of the pixels. Camera rays are distributed
+ * following the filter (importance sampling), so the samples of a pixel
+ * are simply averaged.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Filter {
+    /**
+     * Uniform over the pixel.
+     */
+    Box,
+    /**
+     * Triangle of radius one pixel, centered on the pixel.
+     */
+    Tent,
+    /**
+     * Gaussian centered on the pixel, standard deviation in pixels.
+     */
+    Gaussian { sigma: Float },
+}
+
+impl Filter {
+    /**
+     * Offset of a camera ray from the pixel corner, along one axis.
+     */
+    pub(crate) fn sample_offset(&self) -> Float {
+        match self {
+            Filter::Box => random::gen_range(0.0, 0.999999),
+            Filter::Tent => {
+                let u = random::gen_range(0.0, 2.0);
+                let t = if u < 1.0 {
+                    u.sqrt() - 1.0
+                } else {
+                    1.0 - (2.0 - u).sqrt()
+                };
+                0.5 + t
+            }
+            Filter::Gaussian { sigma } => {
+                // Box-Muller transform.
+                let u1: Float = random::gen_range(Float::EPSILON, 1.0);
+                let u2: Float = random::gen_range(0.0, 1.0);
+                let normal =
+                    (-2.0 * u1.ln()).sqrt() * (2.0 * consts::PI * u2).cos();
+                0.5 + sigma * normal
+            }
+        }
+    }
+}
+
+/**
+ * Maps the (linear) radiance into the displayable range, before the gamma
+ * correction.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Tonemapper {
+    /**
+     * Values above 1.0 are clipped.
+     */
+    Clamp,
+    /**
+     * x / (1 + x)
+     */
+    Reinhard,
+    /**
+     * Filmic curve fitted to the ACES reference transform (Narkowicz).
+     */
+    Aces,
+}
+
+impl Tonemapper {
+    pub fn map(&self, value: Float) -> Float {
+        match self {
+            Tonemapper::Clamp => value.clamp(0.0, 1.0),
+            Tonemapper::Reinhard => value.max(0.0) / (1.0 + value.max(0.0)),
+            Tonemapper::Aces => {
+                let x = value.max(0.0);
+                let mapped =
+                    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+                mapped.clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/**
+ * How the developed images turn the light of the working space into
+ * display values.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OutputTransform {
+    /**
+     * The light in linear sRGB through the `tonemapper`, then the gamma
+     * correction.
+     */
+    #[default]
+    Standard,
+    /**
+     * The light through the ACES rendering and sRGB output transforms,
+     * then the gamma correction (instead of the `tonemapper`).
+     */
+    Aces,
+    /**
+     * The light (of the working space) through a 3D LUT giving the
+     * display values, e.g. baked from an OpenColorIO config.
+     */
+    Lut(Lut3d),
+}
+
+/**
+ * Scheduling priority of the worker threads.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ThreadPriority {
+    Normal,
+    /**
+     * Lowest (nice) priority, so a background render yields to
+     * interactive work. Only supported on Unix, elsewhere it renders at
+     * normal priority.
+     */
+    Low,
+}
+
+impl ThreadPriority {
+    /**
+     * Apply the priority to the calling thread.
+     */
+    pub(crate) fn apply(&self) {
+        #[cfg(unix)]
+        {
+            if *self == ThreadPriority::Low {
+                // On Linux this only affects the calling thread, on other
+                // Unix systems the whole process.
+                unsafe {
+                    libc::setpriority(libc::PRIO_PROCESS, 0, 19);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Order the tiles of the image are rendered in (see `tiles`). With a seed,
+ * the image is the same whatever the order.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TileOrder {
+    /**
+     * Rows of tiles, from the top.
+     */
+    #[default]
+    Scanline,
+    /**
+     * From the center out, where the subject usually is, for previews.
+     */
+    Spiral,
+    /**
+     * Along a Hilbert curve, every tile next to the one before, so the
+     * threads keep the same part of the scene in their caches.
+     */
+    Hilbert,
+    /**
+     * The most expensive tiles first, split until none takes more than
+     * a share of a thread, so that the threads finish together. The cost
+     * of the tiles is probed on a coarse grid of pixels first.
+     */
+    Cost,
+}
+
+/**
+ * Non-photorealistic shading, for illustrative renders: instead of tracing
+ * light paths, the surface seen by each camera ray is lit by a single
+ * directional light, its shading quantized into flat bands, and its
+ * silhouettes and creases inked.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Toon {
+    /**
+     * Number of lit bands between the shadowed and the fully lit color.
+     */
+    pub bands: u32,
+    /**
+     * Direction towards the light, in world space.
+     */
+    pub light: [Float; 3],
+    /**
+     * Brightness of the shadowed side, relative to the base color.
+     */
+    pub ambient: Float,
+    /**
+     * Whether actors cast shadows from the light.
+     */
+    pub shadows: bool,
+    /**
+     * Surfaces seen at a grazing angle, where normal·view falls below
+     * this, are inked. 0 disables it.
+     */
+    pub silhouette: Float,
+    /**
+     * Ink the pixels on a depth or normal discontinuity with their
+     * neighbors: the outline of every actor, and its creases.
+     */
+    pub outlines: bool,
+    /**
+     * Relative difference of depth between neighboring pixels that
+     * makes an outline.
+     */
+    pub depth_threshold: Float,
+    /**
+     * Angle between neighboring normals that makes a crease, in degrees.
+     */
+    pub crease_angle: Float,
+    pub ink: [Float; 3],
+}
+
+impl Default for Toon {
+    fn default() -> Toon {
+        Toon {
+            bands: 3,
+            light: [1.0, 1.0, 1.0],
+            ambient: 0.2,
+            shadows: true,
+            silhouette: 0.2,
+            outlines: true,
+            depth_threshold: 0.1,
+            crease_angle: 30.0,
+            ink: [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl Toon {
+    /**
+     * Brightness of a surface whose normal·light is `cosine`: the lit
+     * part is quantized into `bands` steps above the ambient level.
+     */
+    pub fn shade(&self, cosine: Float) -> Float {
+        let bands = self.bands as Float;
+        let level = (cosine.clamp(0.0, 1.0) * bands).ceil() / bands;
+        self.ambient + (1.0 - self.ambient) * level
+    }
+
+    pub(crate) fn light_direction(&self) -> [Float; 3] {
+        let norm = self.light.iter().map(|x| x * x).sum::<Float>().sqrt();
+        self.light.map(|x| x / norm)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.bands == 0 {
+            return Err(SaturnoError::InvalidSettings(
+                "toon shading needs at least one band".to_string(),
+            ));
+        }
+
+        let norm: Float = self.light.iter().map(|x| x * x).sum();
+        if !norm.is_finite() || norm == 0.0 {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid toon light direction {:?}",
+                self.light
+            )));
+        }
+
+        for (name, value) in [
+            ("ambient", self.ambient),
+            ("silhouette", self.silhouette),
+            ("depth threshold", self.depth_threshold),
+            ("crease angle", self.crease_angle),
+        ] {
+            if value.is_nan() || value < 0.0 {
+                return Err(SaturnoError::InvalidSettings(format!(
+                    "invalid toon {} {}",
+                    name, value
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * Caustics by manifold next event estimation: at every diffuse bounce, the
+ * lights seen through smooth glass (dielectrics) are connected to the
+ * point along the refracted path between them, found by Newton's method.
+ * Caustics through glass then converge like direct light, even from point
+ * lights that paths could never hit.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Caustics {
+    /**
+     * Maximum number of refractions between the point and the light.
+     */
+    pub max_vertices: u32,
+    /**
+     * Maximum number of Newton iterations.
+     */
+    pub iterations: u32,
+    /**
+     * The path reaches the light when it passes this close to it,
+     * relative to the distance (or, for distant lights, in radians).
+     */
+    pub tolerance: Float,
+}
+
+impl Default for Caustics {
+    fn default() -> Caustics {
+        Caustics {
+            max_vertices: 2,
+            iterations: 20,
+            tolerance: 1e-5,
+        }
+    }
+}
+
+impl Caustics {
+    fn validate(&self) -> Result<()> {
+        if self.max_vertices == 0 || self.iterations == 0 {
+            return Err(SaturnoError::InvalidSettings(
+                "caustics need at least one vertex and one iteration"
+                    .to_string(),
+            ));
+        }
+        if !(self.tolerance > 0.0 && self.tolerance < 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid caustics tolerance {}",
+                self.tolerance
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Irradiance caching: the indirect light of Lambertian surfaces, seen
+ * straight from the camera, is computed by sampling the hemisphere at a
+ * few points only and interpolated in between (see `irradiance`). Much
+ * faster than tracing it at every pixel in diffuse interiors, at the cost
+ * of some bias (blotches where the records are too sparse).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct IrradianceCaching {
+    /**
+     * Largest interpolation error allowed: records are reused up to this
+     * fraction of the distance of the surfaces around them.
+     */
+    pub accuracy: Float,
+    /**
+     * Rays sampling the hemisphere of each record.
+     */
+    pub rays: u32,
+    /**
+     * Bounds of the reach of the records, in scene units: the smallest
+     * keeps them from piling up in corners, the largest from spreading
+     * too far in open spaces.
+     */
+    pub min_radius: Float,
+    pub max_radius: Float,
+}
+
+impl Default for IrradianceCaching {
+    fn default() -> IrradianceCaching {
+        IrradianceCaching {
+            accuracy: 0.2,
+            rays: 256,
+            min_radius: 0.01,
+            max_radius: 10.0,
+        }
+    }
+}
+
+impl IrradianceCaching {
+    fn validate(&self) -> Result<()> {
+        if !(self.accuracy > 0.0 && self.accuracy <= 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid irradiance cache accuracy {}",
+                self.accuracy
+            )));
+        }
+        if self.rays < 6 {
+            return Err(SaturnoError::InvalidSettings(
+                "irradiance cache records need at least 6 rays".to_string(),
+            ));
+        }
+        let radii = self.min_radius > 0.0
+            && self.min_radius <= self.max_radius
+            && self.max_radius.is_finite();
+        if !radii {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid irradiance cache radii {} to {}",
+                self.min_radius, self.max_radius
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Stochastic progressive photon mapping (Hachisuka and Jensen 2009), in
+ * place of path tracing for beauty renders: every pass (sample) follows
+ * the camera rays through the smooth surfaces to a Lambertian one, then
+ * shoots `photons` from the lights and gathers those landing within a
+ * radius of each pixel's point. The radii shrink from `initial_radius`
+ * (in scene units) as the passes go, `alpha` the fraction of the photons
+ * kept at each, so the estimate converges. For the light seen through
+ * glass and mirrors (specular, diffuse, specular paths) the path tracer
+ * can hardly find.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PhotonMapping {
+    pub photons: u32,
+    pub initial_radius: Float,
+    pub alpha: Float,
+}
+
+impl Default for PhotonMapping {
+    fn default() -> PhotonMapping {
+        PhotonMapping {
+            photons: 100000,
+            initial_radius: 0.1,
+            alpha: 0.7,
+        }
+    }
+}
+
+impl PhotonMapping {
+    fn validate(&self) -> Result<()> {
+        if self.photons == 0 {
+            return Err(SaturnoError::InvalidSettings(
+                "photon mapping needs at least one photon per pass".to_string(),
+            ));
+        }
+        if !(self.initial_radius > 0.0 && self.initial_radius.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid photon radius {}",
+                self.initial_radius
+            )));
+        }
+        if !(self.alpha > 0.0 && self.alpha < 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid photon mapping alpha {}",
+                self.alpha
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Metropolis light transport in primary sample space (Kelemen et al.
+ * 2002), over the path tracer: `chains` Markov chains wander among the
+ * paths of the image, each step mutating the random numbers of the last
+ * path (by about `mutation_size`, or all of them with probability
+ * `large_step_probability`). Bright paths, once found, are explored
+ * around, for the light hard to find at random: through a gap, or off
+ * small bright sources. The chains start from paths picked among
+ * `bootstrap` paths at random, which also normalize the image. Samples
+ * are mutations per pixel.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Metropolis {
+    pub chains: u32,
+    pub mutation_size: Float,
+    pub large_step_probability: Float,
+    pub bootstrap: u32,
+}
+
+impl Default for Metropolis {
+    fn default() -> Metropolis {
+        Metropolis {
+            chains: 1000,
+            mutation_size: 0.01,
+            large_step_probability: 0.3,
+            bootstrap: 100000,
+        }
+    }
+}
+
+impl Metropolis {
+    fn validate(&self) -> Result<()> {
+        if self.chains == 0 || self.bootstrap < self.chains {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "metropolis needs at least one chain, and as many bootstrap \
+                 paths ({} chains, {} paths)",
+                self.chains, self.bootstrap
+            )));
+        }
+        if !(self.mutation_size > 0.0 && self.mutation_size < 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid mutation size {}",
+                self.mutation_size
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.large_step_probability) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid large step probability {}",
+                self.large_step_probability
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Gradient-domain path tracing (Kettunen et al. 2015): each sample of a
+ * pixel also traces the paths of its right and lower neighbors from the
+ * same random numbers (the shift of the path to the neighbor), and their
+ * differences estimate the gradients of the image. Close paths being
+ * much alike, the gradients are far less noisy than the pixels, and the
+ * image is rebuilt from both by a screened Poisson solve: the pixels
+ * weigh `alpha` against the gradients (the lower, the smoother), solved
+ * in `iterations` steps of conjugate gradients. A sample traces three
+ * paths.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct GradientDomain {
+    pub alpha: Float,
+    pub iterations: u32,
+}
+
+impl Default for GradientDomain {
+    fn default() -> GradientDomain {
+        GradientDomain {
+            alpha: 0.2,
+            iterations: 100,
+        }
+    }
+}
+
+impl GradientDomain {
+    fn validate(&self) -> Result<()> {
+        if !(self.alpha > 0.0 && self.alpha.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid gradient-domain alpha {}",
+                self.alpha
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Reservoir resampling of the lights (ReSTIR, Bitterli et al. 2020), for
+ * scenes with many lights: instead of a shadow ray towards every light,
+ * a surface resamples `candidates` lights picked at random, and the
+ * lights kept by the last `reuse` points around it (within `radius`
+ * times the size of the scene, in the samples before), by the light they
+ * would reflect, and casts one shadow ray towards the light it keeps.
+ * What a point keeps stands for at most `history` times the candidates,
+ * so that old samples do not weigh too much. Reusing the neighbors'
+ * lights is biased (slightly, where they differ), and with several
+ * threads the images differ slightly from run to run.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ReservoirSampling {
+    pub candidates: u32,
+    pub reuse: u32,
+    pub radius: Float,
+    pub history: u32,
+}
+
+impl Default for ReservoirSampling {
+    fn default() -> ReservoirSampling {
+        ReservoirSampling {
+            candidates: 8,
+            reuse: 4,
+            radius: 0.01,
+            history: 20,
+        }
+    }
+}
+
+impl ReservoirSampling {
+    fn validate(&self) -> Result<()> {
+        if self.candidates == 0 || self.history == 0 {
+            return Err(SaturnoError::InvalidSettings(
+                "reservoir sampling needs candidates and history".to_string(),
+            ));
+        }
+        if !(self.radius > 0.0 && self.radius.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid reservoir radius {}",
+                self.radius
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Hierarchy of the lights (a light BVH), for scenes with hundreds or
+ * thousands of lights: instead of a shadow ray towards every light, the
+ * lights with a position are picked `samples` times from a tree over
+ * them, by how much light each part of the tree may send to the lit
+ * point, so that picking takes about the logarithm of their number. The
+ * distant lights and the environments are still all sampled. It applies
+ * to the beauty renders (`render_hdr`, `render_pass`), and not to
+ * `ReservoirSampling`, which picks its candidates evenly.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LightHierarchy {
+    pub samples: u32,
+}
+
+impl Default for LightHierarchy {
+    fn default() -> LightHierarchy {
+        LightHierarchy { samples: 1 }
+    }
+}
+
+impl LightHierarchy {
+    fn validate(&self) -> Result<()> {
+        if self.samples == 0 {
+            return Err(SaturnoError::InvalidSettings(
+                "the light hierarchy needs at least one sample".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Path guiding (Müller et al. 2017): before the samples of a render,
+ * `training_passes` passes (of 1, 2, 4... samples, not kept) learn the
+ * light arriving at the diffuse surfaces, and the bounces of the render
+ * then head where it comes from, for indirect light coming through a
+ * few openings. Space is split where more than `spatial_threshold`
+ * paths went through a cell in a pass, and the directions where more
+ * than `subdivision` of the light of a cell comes from. A bounce still
+ * follows the material with probability `bsdf_fraction`, so that no
+ * light goes unsampled.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PathGuiding {
+    pub training_passes: u32,
+    pub spatial_threshold: u64,
+    pub subdivision: Float,
+    pub bsdf_fraction: Float,
+}
+
+impl Default for PathGuiding {
+    fn default() -> PathGuiding {
+        PathGuiding {
+            training_passes: 5,
+            spatial_threshold: 4000,
+            subdivision: 0.01,
+            bsdf_fraction: 0.5,
+        }
+    }
+}
+
+impl PathGuiding {
+    fn validate(&self) -> Result<()> {
+        if self.training_passes == 0 || self.training_passes > 16 {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "path guiding needs 1 to 16 training passes, not {}",
+                self.training_passes
+            )));
+        }
+        if !(self.subdivision > 0.0 && self.subdivision < 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid guiding subdivision {}",
+                self.subdivision
+            )));
+        }
+        if !(self.bsdf_fraction > 0.0 && self.bsdf_fraction <= 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid guiding bsdf fraction {}",
+                self.bsdf_fraction
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Exposure of a physical camera, for the scenes lit in photometric units
+ * (candela, lux, nits): the `f_number` of the lens, the `shutter` time in
+ * seconds and the `iso` sensitivity, or the exposure value at ISO 100
+ * `ev100` directly, which wins when set. A luminance of 1.2 2^EV100 nits
+ * (the saturation of the sensor, ISO 12232) exposes to 1 before the tone
+ * mapping, and `exposure` stops are added. The default is the "sunny 16"
+ * rule (f/16, 1/100 s, ISO 100): a white surface in the noon sun exposes
+ * to about 1.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct CameraExposure {
+    pub f_number: Float,
+    pub shutter: Float,
+    pub iso: Float,
+    pub ev100: Option<Float>,
+}
+
+impl Default for CameraExposure {
+    fn default() -> CameraExposure {
+        CameraExposure {
+            f_number: 16.0,
+            shutter: 0.01,
+            iso: 100.0,
+            ev100: None,
+        }
+    }
+}
+
+impl CameraExposure {
+    /**
+     * Exposure value at ISO 100.
+     */
+    pub fn ev100(&self) -> Float {
+        self.ev100.unwrap_or_else(|| {
+            (self.f_number * self.f_number / self.shutter * 100.0 / self.iso)
+                .log2()
+        })
+    }
+
+    /**
+     * Factor from luminance (nits) to the exposed values.
+     */
+    pub fn scale(&self) -> Float {
+        1.0 / (1.2 * Float::powf(2.0, self.ev100()))
+    }
+
+    fn validate(&self) -> Result<()> {
+        let positive = |value: Float| value > 0.0 && value.is_finite();
+        if !positive(self.f_number) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid f-number {}",
+                self.f_number
+            )));
+        }
+        if !positive(self.shutter) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid shutter time {}",
+                self.shutter
+            )));
+        }
+        if !positive(self.iso) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid iso {}",
+                self.iso
+            )));
+        }
+        if self.ev100.is_some_and(|ev| !ev.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid exposure value {:?}",
+                self.ev100
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Bloom and glare, added when the image is developed (the HDR renders
+ * keep the plain light): the light above `threshold` (of luminance, once
+ * exposed) is blurred over `radius` of the diagonal of the image and
+ * added back `intensity` times, and drawn out `glare` times in the
+ * streaks of a diffraction star of an aperture of `blades` blades, fading
+ * over `glare_length` of the diagonal. Bright emitters then glow even
+ * where the tone mapping clips them.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Bloom {
+    pub threshold: Float,
+    pub intensity: Float,
+    pub radius: Float,
+    pub glare: Float,
+    pub blades: u32,
+    pub glare_length: Float,
+}
+
+impl Default for Bloom {
+    fn default() -> Bloom {
+        Bloom {
+            threshold: 1.0,
+            intensity: 0.1,
+            radius: 0.01,
+            glare: 0.0,
+            blades: 6,
+            glare_length: 0.05,
+        }
+    }
+}
+
+impl Bloom {
+    fn validate(&self) -> Result<()> {
+        if !(self.threshold >= 0.0 && self.threshold.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid bloom threshold {}",
+                self.threshold
+            )));
+        }
+        let amounts = [self.intensity, self.radius, self.glare];
+        if !amounts.iter().all(|a| *a >= 0.0 && a.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid bloom intensity {}, radius {} or glare {}",
+                self.intensity, self.radius, self.glare
+            )));
+        }
+        if !(self.glare_length >= 0.0 && self.glare_length <= 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid glare length {}",
+                self.glare_length
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Lens flare, added when the image is developed after the bloom: the
+ * light above `threshold` (of luminance, once exposed) makes `ghosts`
+ * ghosts, images of the bright spots mirrored through the center at
+ * `ghost_spacing`, twice, three times... their size, `ghost_intensity`
+ * times as bright and their colors `dispersion` apart; a halo
+ * `halo_width` of the diagonal around the center, `halo_intensity` times
+ * as bright for the lights near it; and a starburst of `starburst_rays`
+ * rays `starburst_length` of the diagonal long, carrying `starburst` of
+ * the light.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LensFlare {
+    pub threshold: Float,
+    pub ghosts: u32,
+    pub ghost_spacing: Float,
+    pub ghost_intensity: Float,
+    pub dispersion: Float,
+    pub halo_width: Float,
+    pub halo_intensity: Float,
+    pub starburst: Float,
+    pub starburst_rays: u32,
+    pub starburst_length: Float,
+}
+
+impl Default for LensFlare {
+    fn default() -> LensFlare {
+        LensFlare {
+            threshold: 1.0,
+            ghosts: 4,
+            ghost_spacing: 0.5,
+            ghost_intensity: 0.05,
+            dispersion: 0.02,
+            halo_width: 0.5,
+            halo_intensity: 0.01,
+            starburst: 0.05,
+            starburst_rays: 12,
+            starburst_length: 0.1,
+        }
+    }
+}
+
+impl LensFlare {
+    fn validate(&self) -> Result<()> {
+        if !(self.threshold >= 0.0 && self.threshold.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid lens flare threshold {}",
+                self.threshold
+            )));
+        }
+        if !(self.ghost_spacing > 0.0 && self.ghost_spacing.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid ghost spacing {}",
+                self.ghost_spacing
+            )));
+        }
+        let amounts = [
+            self.ghost_intensity,
+            self.halo_width,
+            self.halo_intensity,
+            self.starburst,
+        ];
+        if !amounts.iter().all(|a| *a >= 0.0 && a.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid lens flare intensities {:?}",
+                amounts
+            )));
+        }
+        if self.dispersion.is_nan() || self.dispersion.abs() >= 0.5 {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid lens flare dispersion {}",
+                self.dispersion
+            )));
+        }
+        if !(self.starburst_length >= 0.0 && self.starburst_length <= 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid starburst length {}",
+                self.starburst_length
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * White balance, applied when the image is developed before the tone
+ * mapping: the colors are adapted (Bradford) as if the light of a
+ * blackbody of `temperature` kelvins, `tint` greener (or more magenta, if
+ * negative) by a fraction of its green, were white. Renders lit by warm
+ * lamps look neutral at their temperature, and 6504 K with no tint keeps
+ * the colors.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WhiteBalance {
+    pub temperature: Float,
+    pub tint: Float,
+}
+
+impl Default for WhiteBalance {
+    fn default() -> WhiteBalance {
+        WhiteBalance {
+            temperature: WHITE_TEMPERATURE,
+            tint: 0.0,
+        }
+    }
+}
+
+impl WhiteBalance {
+    fn validate(&self) -> Result<()> {
+        if !(self.temperature >= 1000.0 && self.temperature <= 40000.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid white balance temperature {}",
+                self.temperature
+            )));
+        }
+        if self.tint.is_nan() || self.tint.abs() >= 1.0 {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid white balance tint {}",
+                self.tint
+            )));
+        }
+        Ok(())
+    }
+
+    /**
+     * The adaptation of the linear sRGB colors.
+     */
+    pub fn matrix(&self) -> Matrix {
+        white_balance(self.temperature, self.tint)
+    }
+}
+
+/**
+ * Imperfections of the camera, applied when the image is developed after
+ * the tone mapping: the corners darkened by `vignette` (a fraction of
+ * their light, falling off as the distance to the center to the power
+ * `vignette_falloff`), the red and blue images scaled `chromatic_aberration`
+ * larger and smaller than the green one, and grain of deviation `grain`
+ * (of the tone mapped values) and of `grain_size` pixels, from `seed`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Imperfections {
+    pub vignette: Float,
+    pub vignette_falloff: Float,
+    pub chromatic_aberration: Float,
+    pub grain: Float,
+    pub grain_size: Float,
+    pub seed: u64,
+}
+
+impl Default for Imperfections {
+    fn default() -> Imperfections {
+        Imperfections {
+            vignette: 0.3,
+            vignette_falloff: 2.0,
+            chromatic_aberration: 0.002,
+            grain: 0.02,
+            grain_size: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Imperfections {
+    fn validate(&self) -> Result<()> {
+        if !(self.vignette >= 0.0 && self.vignette <= 1.0) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid vignette {}",
+                self.vignette
+            )));
+        }
+        if !(self.vignette_falloff > 0.0 && self.vignette_falloff.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid vignette falloff {}",
+                self.vignette_falloff
+            )));
+        }
+        if self.chromatic_aberration.is_nan()
+            || self.chromatic_aberration.abs() >= 0.5
+        {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid chromatic aberration {}",
+                self.chromatic_aberration
+            )));
+        }
+        if !(self.grain >= 0.0 && self.grain.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid grain {}",
+                self.grain
+            )));
+        }
+        if !(self.grain_size >= 1.0 && self.grain_size.is_finite()) {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid grain size {}",
+                self.grain_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Region of the image to render, in pixels. The rendered image has the
+ * size of the crop window.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/**
+ * Every knob of a render. Passed to the render entry points of the
+ * canvas, which keeps only the scene (actors and camera).
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    /**
+     * Samples per pixel.
+     */
+    pub samples: u32,
+    /**
+     * When set, beauty renders (`render_hdr`) render passes until this
+     * many seconds are spent instead of `samples`, see
+     * `Canvas::render_budget`. Not supported in the browser.
+     */
+    pub time_budget: Option<f64>,
+    /**
+     * Maximum number of bounces of a path.
+     */
+    pub max_depth: u32,
+    pub filter: Filter,
+    pub tonemapper: Tonemapper,
+    /**
+     * Color space of the light of the render: of the colors of the scene
+     * and the textures, and of the HDR images.
+     */
+    pub working_space: ColorSpace,
+    /**
+     * Display transform of the developed images, see `OutputTransform`.
+     */
+    pub output_transform: OutputTransform,
+    /**
+     * Exposure adjustment in stops, applied before the tone mapping.
+     */
+    pub exposure: Float,
+    pub gamma: Float,
+    /**
+     * Number of worker threads, 0 uses every core but `reserved_cores`.
+     */
+    pub threads: usize,
+    /**
+     * Cores left free when the number of threads is automatic, so the
+     * machine stays responsive while rendering. At least one thread is
+     * always used.
+     */
+    pub reserved_cores: usize,
+    pub priority: ThreadPriority,
+    /**
+     * Width and height in pixels of the tiles the threads render the
+     * image in, and their order.
+     */
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    /**
+     * Deterministic mode: when set, every pixel draws its random
+     * numbers from a stream derived from this seed and its index, so
+     * the same scene, seed and settings always render the exact same
+     * image, whatever the threads or the order the pixels are
+     * rendered in.
+     */
+    pub seed: Option<u64>,
+    pub crop: Option<Crop>,
+    /**
+     * When set, camera rays that escape the scene contribute zero
+     * alpha (and color) instead of the background gradient.
+     */
+    pub transparent_background: bool,
+    /**
+     * When set, samples whose radiance is not finite (NaN or infinite)
+     * are discarded and the rest of the pixel is averaged, instead of
+     * failing the render. The culprits are logged as warnings.
+     */
+    pub quarantine: bool,
+    /**
+     * When set, paths carry radiance at a few sampled wavelengths
+     * (see `spectral`) instead of RGB, and the colors of the scene are
+     * uplifted to spectra.
+     */
+    pub spectral: bool,
+    /**
+     * When set, beauty renders (`render_hdr`, `render_pass`) use toon
+     * shading instead of tracing light paths.
+     */
+    pub toon: Option<Toon>,
+    /**
+     * When set, the path tracer also connects the lights through the
+     * glass, see `Caustics`.
+     */
+    pub caustics: Option<Caustics>,
+    /**
+     * When set, the diffuse indirect light is interpolated from cached
+     * records, see `IrradianceCaching`.
+     */
+    pub irradiance_cache: Option<IrradianceCaching>,
+    /**
+     * When set, beauty renders (`render_hdr`, `render_pass`) use photon
+     * mapping instead of path tracing, see `PhotonMapping`.
+     */
+    pub photon_mapping: Option<PhotonMapping>,
+    /**
+     * When set, beauty renders (`render_hdr`, `render_pass`) explore the
+     * paths of the path tracer with Markov chains, see `Metropolis`.
+     */
+    pub metropolis: Option<Metropolis>,
+    /**
+     * When set, beauty renders (`render_hdr`, `render_pass`) are rebuilt
+     * from the pixels and their gradients, see `GradientDomain`.
+     */
+    pub gradient_domain: Option<GradientDomain>,
+    /**
+     * When set, beauty renders (`render_hdr`, `render_pass`) learn the
+     * light first and guide the bounces by it, see `PathGuiding`.
+     */
+    pub guiding: Option<PathGuiding>,
+    /**
+     * When set, the surfaces resample the lights instead of sampling
+     * them all, see `ReservoirSampling`.
+     */
+    pub reservoirs: Option<ReservoirSampling>,
+    /**
+     * When set, the lights are picked from a hierarchy instead of all
+     * sampled, see `LightHierarchy`.
+     */
+    pub light_hierarchy: Option<LightHierarchy>,
+    /**
+     * When set, the image is exposed as by a physical camera, see
+     * `CameraExposure`, before the `exposure` adjustment.
+     */
+    pub camera_exposure: Option<CameraExposure>,
+    /**
+     * When set, the bright parts of the developed images bloom and glare,
+     * see `Bloom`.
+     */
+    pub bloom: Option<Bloom>,
+    /**
+     * When set, the bright parts of the developed images flare, see
+     * `LensFlare`.
+     */
+    pub lens_flare: Option<LensFlare>,
+    /**
+     * When set, the developed images show the vignetting, chromatic
+     * aberration and grain of a camera, see `Imperfections`.
+     */
+    pub imperfections: Option<Imperfections>,
+    /**
+     * When set, the developed images are white balanced, see
+     * `WhiteBalance`.
+     */
+    pub white_balance: Option<WhiteBalance>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            width: 200,
+            height: 100,
+            samples: 1,
+            time_budget: None,
+            max_depth: 50,
+            filter: Filter::Box,
+            tonemapper: Tonemapper::Clamp,
+            working_space: ColorSpace::LinearSrgb,
+            output_transform: OutputTransform::Standard,
+            exposure: 0.0,
+            gamma: 2.0,
+            threads: 0,
+            reserved_cores: 1,
+            priority: ThreadPriority::Normal,
+            tile_size: 32,
+            tile_order: TileOrder::Scanline,
+            seed: None,
+            crop: None,
+            transparent_background: false,
+            quarantine: false,
+            spectral: false,
+            toon: None,
+            caustics: None,
+            irradiance_cache: None,
+            photon_mapping: None,
+            metropolis: None,
+            gradient_domain: None,
+            guiding: None,
+            reservoirs: None,
+            light_hierarchy: None,
+            camera_exposure: None,
+            bloom: None,
+            lens_flare: None,
+            imperfections: None,
+            white_balance: None,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn builder() -> RenderSettingsBuilder {
+        RenderSettingsBuilder {
+            settings: RenderSettings::default(),
+        }
+    }
+
+    /**
+     * Rendered region: the crop window, or the whole image.
+     */
+    pub fn region(&self) -> Crop {
+        self.crop.unwrap_or(Crop {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        })
+    }
+
+    /**
+     * Factor the rendered values are multiplied by before the tone
+     * mapping: the `exposure` stops, and the camera exposure.
+     */
+    pub fn exposure_scale(&self) -> Float {
+        let camera = self.camera_exposure.map_or(1.0, |camera| camera.scale());
+        camera * Float::powf(2.0, self.exposure)
+    }
+
+    /**
+     * Name of the algorithm rendering the images with these settings.
+     */
+    pub fn integrator(&self) -> &'static str {
+        if self.photon_mapping.is_some() {
+            "photon mapping"
+        } else if self.metropolis.is_some() {
+            "metropolis"
+        } else if self.gradient_domain.is_some() {
+            "gradient domain path tracing"
+        } else if self.toon.is_some() {
+            "toon"
+        } else if self.spectral {
+            "spectral path tracing"
+        } else {
+            "path tracing"
+        }
+    }
+
+    /**
+     * Number of worker threads to spawn.
+     */
+    pub fn thread_count(&self) -> usize {
+        // There are no threads to spawn in the browser, render on the
+        // calling one.
+        if cfg!(target_arch = "wasm32") {
+            return 1;
+        }
+
+        if self.threads > 0 {
+            return self.threads;
+        }
+
+        let cores = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        cores.saturating_sub(self.reserved_cores).max(1)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "empty image ({}x{})",
+                self.width, self.height
+            )));
+        }
+
+        if self.samples == 0 {
+            return Err(SaturnoError::InvalidSettings(
+                "at least one sample per pixel is required".to_string(),
+            ));
+        }
+
+        if let Some(budget) = self.time_budget {
+            if !(budget > 0.0 && budget.is_finite()) {
+                return Err(SaturnoError::InvalidSettings(format!(
+                    "invalid time budget {}",
+                    budget
+                )));
+            }
+            // There is no clock to spend it by in the browser.
+            if cfg!(target_arch = "wasm32") {
+                return Err(SaturnoError::InvalidSettings(
+                    "time budgets are not supported in the browser".to_string(),
+                ));
+            }
+        }
+
+        if self.tile_size == 0 {
+            return Err(SaturnoError::InvalidSettings(
+                "tiles of at least one pixel are required".to_string(),
+            ));
+        }
+
+        if !self.exposure.is_finite() {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid exposure {}",
+                self.exposure
+            )));
+        }
+
+        if self.gamma.is_nan() || self.gamma <= 0.0 {
+            return Err(SaturnoError::InvalidSettings(format!(
+                "invalid gamma {}",
+                self.gamma
+            )));
+        }
+
+        if let Filter::Gaussian { sigma } = self.filter {
+            if sigma.is_nan() || sigma <= 0.0 {
+                return Err(SaturnoError::InvalidSettings(format!(
+                    "invalid gaussian filter sigma {}",
+                    sigma
+                )));
+            }
+        }
+
+        if let Some(crop) = self.crop {
+            if crop.width == 0
+                || crop.height == 0
+                || crop.x as u64 + crop.width as u64 > self.width as u64
+                || crop.y as u64 + crop.height as u64 > self.height as u64
+            {
+                return Err(SaturnoError::InvalidSettings(format!(
+                    "crop window {}x{}+{}+{} out of the {}x{} image",
+                    crop.width,
+                    crop.height,
+                    crop.x,
+                    crop.y,
+                    self.width,
+                    self.height
+                )));
+            }
+        }
+
+        if let Some(toon) = &self.toon {
+            toon.validate()?;
+        }
+
+        if let Some(caustics) = &self.caustics {
+            caustics.validate()?;
+        }
+
+        if let Some(caching) = &self.irradiance_cache {
+            caching.validate()?;
+        }
+
+        if let Some(mapping) = &self.photon_mapping {
+            mapping.validate()?;
+            if self.toon.is_some() {
+                return Err(SaturnoError::InvalidSettings(
+                    "photon mapping and toon shading are exclusive".to_string(),
+                ));
+            }
+        }
+
+        if let Some(metropolis) = &self.metropolis {
+            metropolis.validate()?;
+            if self.toon.is_some() || self.photon_mapping.is_some() {
+                return Err(SaturnoError::InvalidSettings(
+                    "metropolis excludes toon shading and photon mapping"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if let Some(gradient) = &self.gradient_domain {
+            gradient.validate()?;
+            if self.toon.is_some()
+                || self.photon_mapping.is_some()
+                || self.metropolis.is_some()
+            {
+                return Err(SaturnoError::InvalidSettings(
+                    "gradient-domain rendering excludes toon shading, photon \
+                     mapping and metropolis"
+                        .to_string(),
+                ));
+            }
+        }
+
+        if let Some(guiding) = &self.guiding {
+            guiding.validate()?;
+        }
+
+        if let Some(resampling) = &self.reservoirs {
+            resampling.validate()?;
+        }
+
+        if let Some(hierarchy) = &self.light_hierarchy {
+            hierarchy.validate()?;
+        }
+
+        if let Some(camera) = &self.camera_exposure {
+            camera.validate()?;
+        }
+
+        if let Some(bloom) = &self.bloom {
+            bloom.validate()?;
+        }
+
+        if let Some(flare) = &self.lens_flare {
+            flare.validate()?;
+        }
+
+        if let Some(imperfections) = &self.imperfections {
+            imperfections.validate()?;
+        }
+
+        if let Some(balance) = &self.white_balance {
+            balance.validate()?;
+        }
+
+        if let OutputTransform::Lut(lut) = &self.output_transform {
+            lut.validate().map_err(SaturnoError::InvalidSettings)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct RenderSettingsBuilder {
+    settings: RenderSettings,
+}
+
+impl RenderSettingsBuilder {
+    pub fn size(mut self, width: u32, height: u32) -> RenderSettingsBuilder {
+        self.settings.width = width;
+        self.settings.height = height;
+        self
+    }
+
+    pub fn samples(mut self, samples: u32) -> RenderSettingsBuilder {
+        self.settings.samples = samples;
+        self
+    }
+
+    pub fn time_budget(mut self, seconds: f64) -> RenderSettingsBuilder {
+        self.settings.time_budget = Some(seconds);
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: u32) -> RenderSettingsBuilder {
+        self.settings.max_depth = max_depth;
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> RenderSettingsBuilder {
+        self.settings.filter = filter;
+        self
+    }
+
+    pub fn tonemapper(
+        mut self,
+        tonemapper: Tonemapper,
+    ) -> RenderSettingsBuilder {
+        self.settings.tonemapper = tonemapper;
+        self
+    }
+
+    pub fn exposure(mut self, exposure: Float) -> RenderSettingsBuilder {
+        self.settings.exposure = exposure;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: Float) -> RenderSettingsBuilder {
+        self.settings.gamma = gamma;
+        self
+    }
+
+    pub fn working_space(mut self, space: ColorSpace) -> RenderSettingsBuilder {
+        self.settings.working_space = space;
+        self
+    }
+
+    pub fn output_transform(
+        mut self,
+        transform: OutputTransform,
+    ) -> RenderSettingsBuilder {
+        self.settings.output_transform = transform;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> RenderSettingsBuilder {
+        self.settings.threads = threads;
+        self
+    }
+
+    pub fn reserved_cores(
+        mut self,
+        reserved_cores: usize,
+    ) -> RenderSettingsBuilder {
+        self.settings.reserved_cores = reserved_cores;
+        self
+    }
+
+    pub fn priority(
+        mut self,
+        priority: ThreadPriority,
+    ) -> RenderSettingsBuilder {
+        self.settings.priority = priority;
+        self
+    }
+
+    pub fn tiles(
+        mut self,
+        size: u32,
+        order: TileOrder,
+    ) -> RenderSettingsBuilder {
+        self.settings.tile_size = size;
+        self.settings.tile_order = order;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> RenderSettingsBuilder {
+        self.settings.seed = Some(seed);
+        self
+    }
+
+    pub fn crop(mut self, crop: Crop) -> RenderSettingsBuilder {
+        self.settings.crop = Some(crop);
+        self
+    }
+
+    pub fn transparent_background(
+        mut self,
+        transparent_background: bool,
+    ) -> RenderSettingsBuilder {
+        self.settings.transparent_background = transparent_background;
+        self
+    }
+
+    pub fn quarantine(mut self, quarantine: bool) -> RenderSettingsBuilder {
+        self.settings.quarantine = quarantine;
+        self
+    }
+
+    pub fn spectral(mut self, spectral: bool) -> RenderSettingsBuilder {
+        self.settings.spectral = spectral;
+        self
+    }
+
+    pub fn toon(mut self, toon: Toon) -> RenderSettingsBuilder {
+        self.settings.toon = Some(toon);
+        self
+    }
+
+    pub fn caustics(mut self, caustics: Caustics) -> RenderSettingsBuilder {
+        self.settings.caustics = Some(caustics);
+        self
+    }
+
+    pub fn irradiance_cache(
+        mut self,
+        caching: IrradianceCaching,
+    ) -> RenderSettingsBuilder {
+        self.settings.irradiance_cache = Some(caching);
+        self
+    }
+
+    pub fn photon_mapping(
+        mut self,
+        mapping: PhotonMapping,
+    ) -> RenderSettingsBuilder {
+        self.settings.photon_mapping = Some(mapping);
+        self
+    }
+
+    pub fn metropolis(
+        mut self,
+        metropolis: Metropolis,
+    ) -> RenderSettingsBuilder {
+        self.settings.metropolis = Some(metropolis);
+        self
+    }
+
+    pub fn gradient_domain(
+        mut self,
+        gradient: GradientDomain,
+    ) -> RenderSettingsBuilder {
+        self.settings.gradient_domain = Some(gradient);
+        self
+    }
+
+    pub fn guiding(mut self, guiding: PathGuiding) -> RenderSettingsBuilder {
+        self.settings.guiding = Some(guiding);
+        self
+    }
+
+    pub fn reservoirs(
+        mut self,
+        resampling: ReservoirSampling,
+    ) -> RenderSettingsBuilder {
+        self.settings.reservoirs = Some(resampling);
+        self
+    }
+
+    pub fn light_hierarchy(
+        mut self,
+        hierarchy: LightHierarchy,
+    ) -> RenderSettingsBuilder {
+        self.settings.light_hierarchy = Some(hierarchy);
+        self
+    }
+
+    pub fn camera_exposure(
+        mut self,
+        camera: CameraExposure,
+    ) -> RenderSettingsBuilder {
+        self.settings.camera_exposure = Some(camera);
+        self
+    }
+
+    pub fn bloom(mut self, bloom: Bloom) -> RenderSettingsBuilder {
+        self.settings.bloom = Some(bloom);
+        self
+    }
+
+    pub fn lens_flare(mut self, flare: LensFlare) -> RenderSettingsBuilder {
+        self.settings.lens_flare = Some(flare);
+        self
+    }
+
+    pub fn imperfections(
+        mut self,
+        imperfections: Imperfections,
+    ) -> RenderSettingsBuilder {
+        self.settings.imperfections = Some(imperfections);
+        self
+    }
+
+    pub fn white_balance(
+        mut self,
+        balance: WhiteBalance,
+    ) -> RenderSettingsBuilder {
+        self.settings.white_balance = Some(balance);
+        self
+    }
+
+    pub fn build(self) -> Result<RenderSettings> {
+        self.settings.validate()?;
+        Ok(self.settings)
+    }
+}
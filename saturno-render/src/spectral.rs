@@ -0,0 +1,209 @@
+use ndarray::{arr1, Array1};
+use saturno_math::common::Float;
+use saturno_math::random;
+pub use saturno_scene::color::cie_xyz;
+pub use saturno_scene::color::xyz_to_srgb;
+use std::sync::OnceLock;
+
+// Spectral rendering: every camera sample carries radiance at a few
+// wavelengths instead of RGB. The RGB colors of the scene (albedos,
+// emission, background) are uplifted to spectra where the path meets them,
+// and the radiance is projected back to linear sRGB once the path is done.
+
+/**
+ * Range of the sampled wavelengths, in nanometers.
+ */
+pub const LAMBDA_MIN: Float = 380.0;
+pub const LAMBDA_MAX: Float = 780.0;
+
+/**
+ * Number of wavelengths carried by a path: they fit in the color channels
+ * of the tracer, next to the alpha channel.
+ */
+pub const WAVELENGTHS: usize = 3;
+
+/**
+ * Wavelengths of a sample (hero wavelength sampling): the hero wavelength
+ * is drawn uniformly and the others are spread evenly after it, wrapping
+ * around the visible range, so every wavelength is uniformly distributed.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wavelengths {
+    pub lambda: [Float; WAVELENGTHS],
+    /**
+     * Set once the secondary wavelengths were terminated (see
+     * `terminate_secondary`).
+     */
+    pub hero_only: bool,
+}
+
+impl Wavelengths {
+    pub fn new(hero: Float) -> Wavelengths {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [hero; WAVELENGTHS];
+        for (j, value) in lambda.iter_mut().enumerate() {
+            let offset = (hero - LAMBDA_MIN) + j as Float * range / 3.0;
+            *value = LAMBDA_MIN + offset % range;
+        }
+        Wavelengths {
+            lambda,
+            hero_only: false,
+        }
+    }
+
+    pub fn sample() -> Wavelengths {
+        Wavelengths::new(random::gen_range(LAMBDA_MIN, LAMBDA_MAX))
+    }
+
+    pub fn hero(&self) -> Float {
+        self.lambda[0]
+    }
+
+    /**
+     * After a wavelength dependent bounce (e.g. dispersion) the
+     * wavelengths no longer share a path: the secondary ones end there and
+     * the hero carries the whole estimate, `values` being the weights of
+     * the bounce. Returns the wavelengths the path goes on with.
+     */
+    pub fn terminate_secondary(
+        &self,
+        values: &mut Array1<Float>,
+    ) -> Wavelengths {
+        if self.hero_only {
+            return *self;
+        }
+
+        values[0] *= WAVELENGTHS as Float;
+        for j in 1..WAVELENGTHS {
+            values[j] = 0.0;
+        }
+        Wavelengths {
+            hero_only: true,
+            ..*self
+        }
+    }
+
+    /**
+     * The spectrum of an RGB color (or reflectance) at these wavelengths.
+     * Alpha is kept as is.
+     */
+    pub fn uplift(&self, rgba: &Array1<Float>) -> Array1<Float> {
+        let mut values = rgba.clone();
+        for (j, lambda) in self.lambda.iter().enumerate() {
+            values[j] = uplift([rgba[0], rgba[1], rgba[2]], *lambda);
+        }
+        values
+    }
+
+    /**
+     * Monte Carlo estimate of the linear sRGB color of a radiance sampled
+     * at these wavelengths. Alpha is kept as is.
+     */
+    pub fn to_rgb(&self, values: &Array1<Float>) -> Array1<Float> {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut rgb = arr1(&[0.0, 0.0, 0.0, values[3]]);
+        for (j, lambda) in self.lambda.iter().enumerate() {
+            let weights = rgb_weights(*lambda);
+            for c in 0..3 {
+                rgb[c] += values[j] * weights[c] * range / WAVELENGTHS as Float;
+            }
+        }
+        rgb
+    }
+}
+
+/**
+ * Linear sRGB response to a wavelength, normalized so that a constant
+ * spectrum of 1 integrates to white (1, 1, 1).
+ */
+pub fn rgb_weights(lambda: Float) -> [Float; 3] {
+    let white = tables().white;
+    let rgb = xyz_to_srgb(cie_xyz(lambda));
+    [rgb[0] / white[0], rgb[1] / white[1], rgb[2] / white[2]]
+}
+
+/**
+ * Smooth blue, green and red spectra adding up to 1 at every wavelength,
+ * the basis RGB colors are uplifted to.
+ */
+fn basis(lambda: Float) -> [Float; 3] {
+    let blue = 1.0 / (1.0 + ((lambda - 490.0) / 10.0).exp());
+    let red = 1.0 / (1.0 + ((590.0 - lambda) / 10.0).exp());
+    [red, 1.0 - red - blue, blue]
+}
+
+/**
+ * Spectrum of an RGB color at a wavelength: the mix of the basis spectra
+ * whose color is `rgb`. White uplifts to a constant spectrum of 1, and
+ * any color within the gamut of the basis renders back as itself;
+ * negative values of saturated colors are clamped, which shifts them
+ * slightly.
+ */
+pub fn uplift(rgb: [Float; 3], lambda: Float) -> Float {
+    let inverse = &tables().inverse_basis;
+    let b = basis(lambda);
+    let mut value = 0.0;
+    for i in 0..3 {
+        let weight: Float = (0..3).map(|k| inverse[i][k] * rgb[k]).sum();
+        value += weight * b[i];
+    }
+    value.max(0.0)
+}
+
+struct Tables {
+    white: [Float; 3],
+    inverse_basis: [[Float; 3]; 3],
+}
+
+/**
+ * Integrals of the color matching functions (1 nm steps), computed once.
+ */
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let lambdas = (LAMBDA_MIN as u32..LAMBDA_MAX as u32)
+            .map(|lambda| lambda as Float + 0.5);
+
+        let mut white = [0.0; 3];
+        for lambda in lambdas.clone() {
+            let rgb = xyz_to_srgb(cie_xyz(lambda));
+            for c in 0..3 {
+                white[c] += rgb[c];
+            }
+        }
+
+        // Color of each basis spectrum (a column per spectrum).
+        let mut colors = [[0.0; 3]; 3];
+        for lambda in lambdas {
+            let rgb = xyz_to_srgb(cie_xyz(lambda));
+            let b = basis(lambda);
+            for c in 0..3 {
+                for i in 0..3 {
+                    colors[c][i] += rgb[c] / white[c] * b[i];
+                }
+            }
+        }
+
+        Tables {
+            white,
+            inverse_basis: invert(&colors),
+        }
+    })
+}
+
+fn invert(m: &[[Float; 3]; 3]) -> [[Float; 3]; 3] {
+    let cofactor = |r: usize, c: usize| {
+        let (r0, r1) = ((r + 1) % 3, (r + 2) % 3);
+        let (c0, c1) = ((c + 1) % 3, (c + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det: Float = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum();
+
+    let mut inverse = [[0.0; 3]; 3];
+    for (r, row) in inverse.iter_mut().enumerate() {
+        for (c, value) in row.iter_mut().enumerate() {
+            *value = cofactor(c, r) / det;
+        }
+    }
+    inverse
+}
@@ -0,0 +1,141 @@
+use crate::canvas::Canvas;
+use saturno_scene::actor::Aabb;
+use saturno_scene::texture::TextureSampler;
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Statistics of a scene, to sanity check what was built or imported before
+// rendering it: the actors by kind, their triangles, the textures and the
+// memory their texels take (each texture counted once however many
+// materials share it), the box around the bounded actors and the lights by
+// kind.
+
+/**
+ * Counts and bounds of the contents of a scene (see `Canvas::stats`).
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SceneStats {
+    /**
+     * Actors by kind (sphere, mesh, ring...).
+     */
+    pub actors: BTreeMap<&'static str, usize>,
+    pub triangles: usize,
+    /**
+     * Distinct textures of the actors and the lights.
+     */
+    pub textures: usize,
+    /**
+     * Bytes of the texels of the textures held in memory.
+     */
+    pub texture_memory: usize,
+    /**
+     * Box around the bounded actors, None without any.
+     */
+    pub bounds: Option<Aabb>,
+    /**
+     * Actors without bounds (e.g. infinite planes).
+     */
+    pub unbounded: usize,
+    /**
+     * Lights by kind (sun, spot, omni, environment).
+     */
+    pub lights: BTreeMap<String, usize>,
+    pub volumes: usize,
+}
+
+impl SceneStats {
+    pub fn new(canvas: &Canvas) -> SceneStats {
+        let mut stats = SceneStats::default();
+        let mut textures: Vec<&dyn TextureSampler> = vec![];
+        for actor in &canvas.world.actors {
+            *stats.actors.entry(actor.kind()).or_default() += 1;
+            stats.triangles += actor.triangles();
+            actor.textures(&mut textures);
+        }
+        stats.bounds = canvas.world.bounding_box();
+        stats.unbounded = canvas
+            .world
+            .actors
+            .iter()
+            .filter(|actor| actor.bounding_box().is_none())
+            .count();
+        for light in &canvas.lights {
+            let name = light.name();
+            let kind = name.split('(').next().unwrap_or_default();
+            *stats.lights.entry(kind.to_string()).or_default() += 1;
+            light.textures(&mut textures);
+        }
+        stats.volumes = canvas.volumes.len();
+
+        // Shared textures are the same object, counted once.
+        let address =
+            |texture: &&dyn TextureSampler| *texture as *const _ as *const ();
+        textures.sort_by_key(|texture| address(texture) as usize);
+        textures.dedup_by_key(|texture| address(texture));
+        stats.textures = textures.len();
+        stats.texture_memory = textures.iter().map(|t| t.memory()).sum();
+        stats
+    }
+
+    pub fn actor_count(&self) -> usize {
+        self.actors.values().sum()
+    }
+
+    pub fn light_count(&self) -> usize {
+        self.lights.values().sum()
+    }
+}
+
+/**
+ * Counts by kind, as "3 sphere, 1 mesh".
+ */
+fn kinds<K: fmt::Display>(counts: &BTreeMap<K, usize>) -> String {
+    if counts.is_empty() {
+        return "none".to_string();
+    }
+    let kinds: Vec<String> = counts
+        .iter()
+        .map(|(kind, count)| format!("{} {}", count, kind))
+        .collect();
+    kinds.join(", ")
+}
+
+impl fmt::Display for SceneStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "actors: {} ({})",
+            self.actor_count(),
+            kinds(&self.actors)
+        )?;
+        writeln!(f, "triangles: {}", self.triangles)?;
+        writeln!(
+            f,
+            "textures: {} ({:.1} MiB)",
+            self.textures,
+            self.texture_memory as f64 / (1024.0 * 1024.0)
+        )?;
+        match &self.bounds {
+            Some(bounds) => {
+                let size: Vec<_> =
+                    (0..3).map(|i| bounds.max[i] - bounds.min[i]).collect();
+                writeln!(
+                    f,
+                    "bounds: {:?} to {:?} (size {:?})",
+                    bounds.min, bounds.max, size
+                )?;
+            }
+            None => writeln!(f, "bounds: none")?,
+        }
+        if self.unbounded > 0 {
+            writeln!(f, "unbounded actors: {}", self.unbounded)?;
+        }
+        writeln!(
+            f,
+            "lights: {} ({})",
+            self.light_count(),
+            kinds(&self.lights)
+        )?;
+        write!(f, "volumes: {}", self.volumes)
+    }
+}
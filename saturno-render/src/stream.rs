@@ -0,0 +1,64 @@
+use crate::canvas::develop;
+use crate::canvas::Renderer;
+use crate::error::Result;
+use crate::progressive::Progressive;
+use crate::HdrImage;
+use crate::Image;
+use futures::channel::mpsc;
+use futures::executor::block_on;
+use futures::SinkExt;
+use futures::Stream;
+use std::thread;
+
+/**
+ * The estimate after a progressive pass: the average of the passes so
+ * far, both as a floating point framebuffer and tone mapped.
+ */
+pub struct RenderPass {
+    pub pass: u32,
+    pub passes: u32,
+    pub hdr: HdrImage,
+    pub image: Image,
+}
+
+/**
+ * Render progressively on a background thread, yielding the estimate
+ * after every pass (`settings.samples` of them). The stream does not
+ * depend on a particular async runtime. Only one pass is buffered, so a
+ * slow consumer slows the render down, and dropping the stream stops it
+ * after the pass in flight. An error ends the stream. The render traces
+ * under the span current when the stream is created.
+ */
+pub fn render_stream(
+    renderer: Renderer,
+) -> impl Stream<Item = Result<RenderPass>> + Unpin {
+    let (mut sender, receiver) = mpsc::channel(1);
+
+    let span = tracing::info_span!("render_stream");
+    thread::spawn(move || {
+        let _entered = span.entered();
+        let Renderer { canvas, settings } = renderer;
+        let passes = settings.samples;
+        let mut progressive = Progressive::new(settings);
+
+        while !progressive.is_done() {
+            let update = progressive.step(&canvas).map(|_| {
+                let hdr = progressive.hdr();
+                let image = develop(&hdr, &progressive.settings);
+                RenderPass {
+                    pass: progressive.passes() - 1,
+                    passes,
+                    hdr,
+                    image,
+                }
+            });
+
+            let failed = update.is_err();
+            if block_on(sender.send(update)).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
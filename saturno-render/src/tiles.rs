@@ -0,0 +1,418 @@
+use crate::settings::Crop;
+use crate::settings::TileOrder;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+// Tiles of the rendered region and the threads rendering them (see
+// `TileOrder`). The region is cut into square tiles, put in order and
+// dealt to the threads, each rendering its own in that order: one after
+// the other across the threads, so that they all work on the start of
+// the order first (the center of the image for the spiral).
+//
+// The cost-aware order instead deals the most expensive tiles first, each
+// to the thread with the least work so far (longest processing time
+// first), after splitting those costing more than a share of a thread into
+// quarters. The cost of a tile is that of the probes on a coarse grid of
+// the image falling in it (see `CostMap`), so that a few tiles of glass or
+// dense geometry do not keep a thread busy long after the others finish.
+//
+// The tiles dealt are where the threads start (see `WorkQueues`): a thread
+// done with its own steals from the others, half of the tiles they have
+// not started, or when there are none left, the second half of the pixels
+// left in the tile one of them is rendering. Expensive tiles the estimate
+// missed are so split as they render, down to single pixels.
+
+/**
+ * Smallest width and height the cost-aware order splits tiles into.
+ */
+const MIN_TILE: u32 = 4;
+
+/**
+ * Pieces of the image per thread the cost-aware order splits it into at
+ * least, so that the threads can balance their work.
+ */
+const PIECES_PER_THREAD: f64 = 4.0;
+
+/**
+ * The pixels (x, y) of `tile`, in rows from the top.
+ */
+pub fn pixels(tile: &Crop) -> impl Iterator<Item = (u32, u32)> {
+    let (x, width) = (tile.x, tile.width);
+    (tile.y..tile.y + tile.height)
+        .flat_map(move |y| (x..x + width).map(move |x| (x, y)))
+}
+
+/**
+ * The tiles of `region`, `size` pixels square (smaller along its right
+ * and bottom edges), in rows from the top.
+ */
+pub fn tiles(region: &Crop, size: u32) -> Vec<Crop> {
+    let size = size.max(1);
+    let mut tiles = vec![];
+    for y in (0..region.height).step_by(size as usize) {
+        for x in (0..region.width).step_by(size as usize) {
+            tiles.push(Crop {
+                x: region.x + x,
+                y: region.y + y,
+                width: size.min(region.width - x),
+                height: size.min(region.height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/**
+ * Distance along the Hilbert curve filling a square of `n` (a power of
+ * two) cells of the cell (x, y).
+ */
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        index += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // Rotate the quadrant so that the curve continues across it.
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/**
+ * Put the tiles of `region`, `size` pixels square, in `order` (the
+ * scanline order for the cost-aware one, which needs their costs).
+ */
+pub fn order(tiles: &mut [Crop], region: &Crop, size: u32, order: TileOrder) {
+    let size = size.max(1);
+    let cell =
+        |tile: &Crop| ((tile.x - region.x) / size, (tile.y - region.y) / size);
+    match order {
+        TileOrder::Scanline | TileOrder::Cost => {
+            tiles.sort_by_key(|tile| (tile.y, tile.x));
+        }
+        TileOrder::Spiral => {
+            // Rings of tiles around the center, each turned around.
+            let columns = region.width.div_ceil(size) as f64;
+            let rows = region.height.div_ceil(size) as f64;
+            let key = |tile: &Crop| {
+                let (column, row) = cell(tile);
+                let dx = column as f64 - (columns - 1.0) / 2.0;
+                let dy = row as f64 - (rows - 1.0) / 2.0;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            tiles.sort_by(|a, b| {
+                let (a, b) = (key(a), key(b));
+                a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+            });
+        }
+        TileOrder::Hilbert => {
+            let cells = region.width.max(region.height).div_ceil(size);
+            let n = cells.next_power_of_two();
+            tiles.sort_by_key(|tile| {
+                let (column, row) = cell(tile);
+                hilbert_index(n, column, row)
+            });
+        }
+    }
+}
+
+/**
+ * Cost of rendering the pixels of a region, probed at the pixel in the
+ * center of every cell of `stride` pixels: the cost of a cell is that of
+ * its probe.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostMap {
+    pub region: Crop,
+    pub stride: u32,
+    /**
+     * Cost of the cells, in rows from the top.
+     */
+    pub costs: Vec<f64>,
+}
+
+impl CostMap {
+    /**
+     * The probes (x, y) of `region` every `stride` pixels, the order of
+     * the costs of its map.
+     */
+    pub fn probes(region: &Crop, stride: u32) -> Vec<(u32, u32)> {
+        let stride = stride.max(1);
+        let mut probes = vec![];
+        for y in (0..region.height).step_by(stride as usize) {
+            for x in (0..region.width).step_by(stride as usize) {
+                let half = |start: u32, length: u32| {
+                    start + (stride / 2).min(length - start - 1)
+                };
+                probes.push((
+                    region.x + half(x, region.width),
+                    region.y + half(y, region.height),
+                ));
+            }
+        }
+        probes
+    }
+
+    /**
+     * Predicted cost of the pixels of `tile`: the cost of the cells it
+     * covers, in proportion of the area covered.
+     */
+    pub fn cost(&self, tile: &Crop) -> f64 {
+        let stride = self.stride.max(1);
+        let columns = self.region.width.div_ceil(stride);
+        let (left, top) = (tile.x - self.region.x, tile.y - self.region.y);
+        let (right, bottom) = (left + tile.width, top + tile.height);
+        let overlap = |cell: u32, start: u32, end: u32| {
+            let (from, to) = (cell * stride, (cell + 1) * stride);
+            to.min(end).saturating_sub(from.max(start)) as f64
+        };
+        let mut cost = 0.0;
+        for row in top / stride..bottom.div_ceil(stride) {
+            for column in left / stride..right.div_ceil(stride) {
+                let area =
+                    overlap(column, left, right) * overlap(row, top, bottom);
+                let index = (row * columns + column) as usize;
+                cost += self.costs.get(index).copied().unwrap_or(0.0) * area;
+            }
+        }
+        cost / (stride as f64 * stride as f64)
+    }
+}
+
+/**
+ * Split `tile` into quarters until none of them costs more than `target`
+ * or is smaller than `MIN_TILE`.
+ */
+fn split(tile: Crop, costs: &CostMap, target: f64, pieces: &mut Vec<Crop>) {
+    let (wide, tall) = (tile.width > MIN_TILE, tile.height > MIN_TILE);
+    if costs.cost(&tile) <= target || !(wide || tall) {
+        pieces.push(tile);
+        return;
+    }
+    let halve = |length: u32, split: bool| match split {
+        true => [(0, length / 2), (length / 2, length - length / 2)],
+        false => [(0, length), (length, 0)],
+    };
+    let (columns, rows) = (halve(tile.width, wide), halve(tile.height, tall));
+    for &(dy, height) in &rows {
+        for &(dx, width) in &columns {
+            if width > 0 && height > 0 {
+                let quarter = Crop {
+                    x: tile.x + dx,
+                    y: tile.y + dy,
+                    width,
+                    height,
+                };
+                split(quarter, costs, target, pieces);
+            }
+        }
+    }
+}
+
+/**
+ * The tiles of `region` each of `threads` threads starts with (none for
+ * some if there are fewer tiles), in order, for tiles `size` pixels
+ * square in `order`. The cost-aware order needs the `costs` of the
+ * region, and falls back to the scanline order without them.
+ */
+pub fn schedule(
+    region: &Crop,
+    size: u32,
+    tile_order: TileOrder,
+    threads: usize,
+    costs: Option<&CostMap>,
+) -> Vec<Vec<Crop>> {
+    let mut tiles = tiles(region, size);
+    order(&mut tiles, region, size, tile_order);
+    let threads = threads.max(1);
+    let mut schedule = vec![vec![]; threads];
+
+    let costs = costs.filter(|_| tile_order == TileOrder::Cost);
+    let Some(costs) = costs else {
+        for (i, tile) in tiles.into_iter().enumerate() {
+            schedule[i % threads].push(tile);
+        }
+        return schedule;
+    };
+
+    let total = costs.cost(region);
+    let target = total / (threads as f64 * PIECES_PER_THREAD);
+    let mut pieces = vec![];
+    for tile in tiles {
+        split(tile, costs, target, &mut pieces);
+    }
+    let mut pieces: Vec<(f64, Crop)> = pieces
+        .into_iter()
+        .map(|piece| (costs.cost(&piece), piece))
+        .collect();
+    pieces.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut loads = vec![0.0_f64; threads];
+    for (cost, piece) in pieces {
+        let least = (0..threads)
+            .min_by(|&a, &b| loads[a].total_cmp(&loads[b]))
+            .unwrap_or(0);
+        loads[least] += cost;
+        schedule[least].push(piece);
+    }
+    schedule
+}
+
+/**
+ * Pixels `start..end` of `tile`, counted in rows from its top left.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Span {
+    tile: Crop,
+    start: u32,
+    end: u32,
+}
+
+impl Span {
+    fn new(tile: Crop) -> Span {
+        Span {
+            tile,
+            start: 0,
+            end: tile.width * tile.height,
+        }
+    }
+
+    fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    fn pixel(&self, i: u32) -> (u32, u32) {
+        (
+            self.tile.x + i % self.tile.width,
+            self.tile.y + i / self.tile.width,
+        )
+    }
+}
+
+/**
+ * The work of a thread: the pixels left in the tile it is rendering, and
+ * the tiles after it.
+ */
+#[derive(Default)]
+struct Queue {
+    current: Option<Span>,
+    spans: VecDeque<Span>,
+}
+
+impl Queue {
+    fn next(&mut self) -> Option<(u32, u32)> {
+        loop {
+            if let Some(span) = self.current.as_mut().filter(|s| s.len() > 0) {
+                span.start += 1;
+                return Some(span.pixel(span.start - 1));
+            }
+            self.current = Some(self.spans.pop_front()?);
+        }
+    }
+
+    /**
+     * The part of the work left a thief takes: half of the spans not
+     * started (the last ones), or else the second half of the current.
+     */
+    fn steal(&mut self) -> Option<VecDeque<Span>> {
+        if !self.spans.is_empty() {
+            let keep = self.spans.len() / 2;
+            return Some(self.spans.split_off(keep));
+        }
+        let current = self.current.as_mut().filter(|s| s.len() > 1)?;
+        let middle = current.start + current.len() / 2;
+        let stolen = Span {
+            start: middle,
+            ..*current
+        };
+        current.end = middle;
+        Some(VecDeque::from(vec![stolen]))
+    }
+
+    /**
+     * How much a thief would take: spans not started count as whole
+     * tiles, ahead of the pixels of the current one.
+     */
+    fn left(&self) -> (usize, u32) {
+        let current = self.current.map_or(0, |span| span.len());
+        (self.spans.len(), current)
+    }
+}
+
+/**
+ * The pixels the threads render, starting from the tiles of a `schedule`
+ * and stealing from each other once done with them. Thread-safe; every
+ * pixel is handed out exactly once.
+ */
+pub struct WorkQueues {
+    queues: Vec<Mutex<Queue>>,
+    steals: AtomicUsize,
+}
+
+impl WorkQueues {
+    pub fn new(schedule: &[Vec<Crop>]) -> WorkQueues {
+        let queues = schedule
+            .iter()
+            .map(|tiles| {
+                Mutex::new(Queue {
+                    current: None,
+                    spans: tiles.iter().map(|&tile| Span::new(tile)).collect(),
+                })
+            })
+            .collect();
+        WorkQueues {
+            queues,
+            steals: AtomicUsize::new(0),
+        }
+    }
+
+    /**
+     * The next pixel (x, y) thread `t` renders, None once there are no
+     * pixels left to take.
+     */
+    pub fn next(&self, t: usize) -> Option<(u32, u32)> {
+        loop {
+            if let Some(pixel) = self.queues[t].lock().unwrap().next() {
+                return Some(pixel);
+            }
+            let stolen = self.steal(t)?;
+            self.queues[t].lock().unwrap().spans.extend(stolen);
+        }
+    }
+
+    /**
+     * Take work from the thread with the most left for thread `t`, None
+     * if no thread has any to give. Locks one queue at a time.
+     */
+    fn steal(&self, t: usize) -> Option<VecDeque<Span>> {
+        loop {
+            let victim = (0..self.queues.len())
+                .filter(|&v| v != t)
+                .map(|v| (self.queues[v].lock().unwrap().left(), v))
+                .filter(|&((spans, pixels), _)| spans > 0 || pixels > 1)
+                .max()?
+                .1;
+            // The victim may have run out meanwhile, look again.
+            if let Some(stolen) = self.queues[victim].lock().unwrap().steal() {
+                self.steals.fetch_add(1, Ordering::Relaxed);
+                return Some(stolen);
+            }
+        }
+    }
+
+    /**
+     * Times a thread took work from another.
+     */
+    pub fn steals(&self) -> usize {
+        self.steals.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,810 @@
+use crate::camera::Camera;
+use crate::material::Lambertian;
+use crate::material::Scattering;
+use crate::material::Shading;
+use crate::texture::TextureSampler;
+use ndarray::{arr1, Array1};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::sync::Arc;
+
+/**
+ * Offsets of the hit point and of the normal for the differential rays of
+ * the ray that hit (see `RayDifferentials`).
+ */
+#[derive(Clone, Debug)]
+pub struct HitDifferentials {
+    pub dpdx: Array1<Float>,
+    pub dpdy: Array1<Float>,
+    pub dndx: Array1<Float>,
+    pub dndy: Array1<Float>,
+}
+
+pub struct Hit {
+    pub t: Float,
+    pub point: Array1<Float>,
+    pub normal: Array1<Float>,
+    /**
+     * Unit direction of the ray that hit, towards the surface (set by the
+     * world).
+     */
+    pub incident: Array1<Float>,
+    /**
+     * Unit tangent of the surface at the hit point, perpendicular to the
+     * normal: the reference direction of anisotropic materials.
+     */
+    pub tangent: Array1<Float>,
+    /**
+     * Texture coordinates of the hit point.
+     */
+    pub uv: [Float; 2],
+    /**
+     * Width, in texture coordinates, of the surface the ray covers at the
+     * hit point (0 when unknown), to filter textures.
+     */
+    pub footprint: Float,
+    pub differentials: Option<HitDifferentials>,
+    pub material: Arc<dyn Scattering>,
+    /**
+     * Index of the hit actor in the world it belongs to.
+     */
+    pub actor_id: usize,
+    /**
+     * Wavelength of the path in nanometers (its hero wavelength), in
+     * spectral mode.
+     */
+    pub wavelength: Option<Float>,
+    /**
+     * When the ray that hit was cast (see `Ray::time`), for the rays
+     * leaving the hit to be cast then too (set by the renderer).
+     */
+    pub time: Float,
+}
+
+impl Hit {
+    pub fn new() -> Hit {
+        Hit {
+            t: 0.0,
+            point: arr1(&[0.0, 0.0, 0.0, 1.0]),
+            normal: arr1(&[1.0, 1.0, 1.0, 0.0]),
+            incident: arr1(&[0.0, 0.0, -1.0, 0.0]),
+            tangent: arr1(&[1.0, 0.0, 0.0, 0.0]),
+            uv: [0.0, 0.0],
+            footprint: 0.0,
+            differentials: None,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.0, 0.0, 1.0, 1.0]),
+                Shading::COLOR,
+            )),
+            actor_id: 0,
+            wavelength: None,
+            time: 0.0,
+        }
+    }
+
+    pub(crate) fn copy(hit: &Hit) -> Hit {
+        Hit {
+            t: hit.t,
+            point: hit.point.clone(),
+            normal: hit.normal.clone(),
+            incident: hit.incident.clone(),
+            tangent: hit.tangent.clone(),
+            uv: hit.uv,
+            footprint: hit.footprint,
+            differentials: hit.differentials.clone(),
+            material: hit.material.clone(),
+            actor_id: hit.actor_id,
+            wavelength: hit.wavelength,
+            time: hit.time,
+        }
+    }
+}
+
+impl Hit {
+    /**
+     * Stochastic alpha test: a hit on a partly transparent surface is
+     * kept with the probability of its opacity, so on average rays see
+     * through it in proportion to its transparency. Opaque surfaces do
+     * not draw random numbers.
+     */
+    pub fn alpha_test(&self) -> bool {
+        let opacity = self.material.opacity(self);
+        opacity >= 1.0
+            || (opacity > 0.0 && random::gen_range(0.0, 1.0) < opacity)
+    }
+}
+
+impl Default for Hit {
+    fn default() -> Hit {
+        Hit::new()
+    }
+}
+
+/**
+ * Traits in rust are how interfaces are implemented. Depending on their
+ * usage, they can be statically or dinamically dispatched.
+ */
+pub trait Hittable {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool;
+
+    // FIXME Removed from the trait, as HittableList now implements
+    // Hittable. Compute normal needs to be part of a different trait
+    // (e.g. Renderable ?).
+    //fn compute_normal(&self, point_sphere: &Array1<Float>) -> Array1<Float>;
+}
+
+pub trait RayTraceable: Hittable + Send + Sync {
+    /**
+     * Check the actor is well formed, otherwise return the reason why it
+     * can not be rendered.
+     */
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /**
+     * Axis aligned box enclosing the actor, if it is bounded.
+     */
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+
+    /**
+     * Move the actor by `offset` (see `Canvas::recenter`).
+     */
+    fn translate(&mut self, offset: &[Float; 3]);
+
+    /**
+     * Kind of the actor (sphere, mesh...), for the statistics of a scene.
+     */
+    fn kind(&self) -> &'static str {
+        "actor"
+    }
+
+    /**
+     * Triangles the actor is made of, none but for meshes.
+     */
+    fn triangles(&self) -> usize {
+        0
+    }
+
+    /**
+     * Add the textures of the actor (those of its material) to `textures`.
+     */
+    fn textures<'a>(&'a self, _textures: &mut Vec<&'a dyn TextureSampler>) {}
+
+    /**
+     * The rays the actor is visible to, all but for those wrapped in
+     * `WithVisibility`.
+     */
+    fn visibility(&self) -> Visibility {
+        Visibility::default()
+    }
+
+    /**
+     * Choose the detail to render through `camera` (the levels of detail
+     * of instances), whenever the camera of the scene is set.
+     */
+    fn select_detail(&mut self, _camera: &Camera) {}
+}
+
+/**
+ * What a ray is traced for: the first hit seen by the camera, the
+ * occlusion of a light, or a bounce of a path (reflections, refractions,
+ * diffuse light).
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RayKind {
+    Camera,
+    Shadow,
+    Indirect,
+}
+
+/**
+ * The kinds of rays that see an actor: hidden from the camera, an actor
+ * still casts its shadows and shows in reflections, and without shadows
+ * it lets the light through while the camera still sees it.
+ *
+ * A `holdout` is seen by the camera as a hole: transparent black, hiding
+ * whatever is behind it, for the objects of a live action plate that
+ * must occlude the rendered ones when composited over it. The other rays
+ * still see its material.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Visibility {
+    pub camera: bool,
+    pub shadow: bool,
+    pub indirect: bool,
+    pub holdout: bool,
+}
+
+impl Default for Visibility {
+    fn default() -> Visibility {
+        Visibility {
+            camera: true,
+            shadow: true,
+            indirect: true,
+            holdout: false,
+        }
+    }
+}
+
+impl Visibility {
+    pub fn sees(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Indirect => self.indirect,
+        }
+    }
+
+    /**
+     * `actor` visible to these rays, as is if to all (and not a holdout).
+     */
+    pub fn apply(self, actor: Box<dyn RayTraceable>) -> Box<dyn RayTraceable> {
+        if self == Visibility::default() {
+            return actor;
+        }
+        Box::new(WithVisibility {
+            actor,
+            visibility: self,
+        })
+    }
+}
+
+/**
+ * `actor` visible to the rays of `visibility` only.
+ */
+pub struct WithVisibility {
+    pub actor: Box<dyn RayTraceable>,
+    pub visibility: Visibility,
+}
+
+impl Hittable for WithVisibility {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        self.actor.is_hit(ray, t_min, t_max, record)
+    }
+}
+
+impl RayTraceable for WithVisibility {
+    fn validate(&self) -> Result<(), String> {
+        self.actor.validate()
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.actor.bounding_box()
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        self.actor.translate(offset);
+    }
+
+    fn kind(&self) -> &'static str {
+        self.actor.kind()
+    }
+
+    fn triangles(&self) -> usize {
+        self.actor.triangles()
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.actor.textures(textures);
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.visibility
+    }
+
+    fn select_detail(&mut self, camera: &Camera) {
+        self.actor.select_detail(camera);
+    }
+}
+
+// -----------------------------------------------------------------------------
+/**
+ * Widening of the far distances of the slab test: 1 + 2 gamma(3), the
+ * bound of the rounding error of the 3 operations computing them (Ize,
+ * Robust BVH Ray Traversal).
+ */
+const SLAB_ROUNDING: Float = {
+    let unit = Float::EPSILON * 0.5;
+    1.0 + 2.0 * (3.0 * unit) / (1.0 - 3.0 * unit)
+};
+
+/**
+ * Axis aligned bounding box, from its `min` to its `max` corner.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: [Float; 3],
+    pub max: [Float; 3],
+}
+
+impl Aabb {
+    pub fn new(min: [Float; 3], max: [Float; 3]) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /**
+     * The box moved by `offset`.
+     */
+    pub fn translated(&self, offset: &[Float; 3]) -> Aabb {
+        Aabb {
+            min: [0, 1, 2].map(|i| self.min[i] + offset[i]),
+            max: [0, 1, 2].map(|i| self.max[i] + offset[i]),
+        }
+    }
+
+    /**
+     * The smallest box enclosing both boxes.
+     */
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [
+                self.min[0].min(other.min[0]),
+                self.min[1].min(other.min[1]),
+                self.min[2].min(other.min[2]),
+            ],
+            max: [
+                self.max[0].max(other.max[0]),
+                self.max[1].max(other.max[1]),
+                self.max[2].max(other.max[2]),
+            ],
+        }
+    }
+
+    /**
+     * Surface area, for the surface area heuristic (the chance a ray
+     * crossing a parent box crosses this one too).
+     */
+    pub fn area(&self) -> Float {
+        let [x, y, z] = [0, 1, 2].map(|i| self.max[i] - self.min[i]);
+        2.0 * (x * y + y * z + z * x)
+    }
+
+    /**
+     * Whether a ray, given by its origin and the inverse of its direction,
+     * crosses the box between `t_min` and `t_max` (slab test). The test is
+     * conservative: the far distances are widened by their rounding error,
+     * so rays grazing the box (or a flat box) are never missed.
+     */
+    pub fn is_hit(
+        &self,
+        origin: &[Float; 3],
+        inverse_direction: &[Float; 3],
+        t_min: Float,
+        t_max: Float,
+    ) -> bool {
+        self.entry(origin, inverse_direction, t_min, t_max)
+            .is_some()
+    }
+
+    /**
+     * Where the ray enters the box, if it crosses it between `t_min` and
+     * `t_max` (`t_min` if it starts inside), see `is_hit`.
+     */
+    pub fn entry(
+        &self,
+        origin: &[Float; 3],
+        inverse_direction: &[Float; 3],
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<Float> {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for axis in 0..3 {
+            let near =
+                (self.min[axis] - origin[axis]) * inverse_direction[axis];
+            let far = (self.max[axis] - origin[axis]) * inverse_direction[axis];
+            let (near, far) = if inverse_direction[axis] >= 0.0 {
+                (near, far)
+            } else {
+                (far, near)
+            };
+            let far = far * SLAB_ROUNDING;
+            // NaN (0 * infinity, rays in the plane of a face) keeps the
+            // interval as is.
+            t0 = if near > t0 { near } else { t0 };
+            t1 = if far < t1 { far } else { t1 };
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some(t0)
+    }
+
+    /**
+     * The 8 corners, the i-th corner takes the max coordinate on the
+     * axes whose bit is set in i (x is bit 0).
+     */
+    pub fn corners(&self) -> [[Float; 3]; 8] {
+        let mut corners = [[0.0; 3]; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            for (axis, value) in corner.iter_mut().enumerate() {
+                *value = if i & (1 << axis) != 0 {
+                    self.max[axis]
+                } else {
+                    self.min[axis]
+                };
+            }
+        }
+        corners
+    }
+
+    /**
+     * The 12 edges, as pairs of corners.
+     */
+    pub fn edges(&self) -> [([Float; 3], [Float; 3]); 12] {
+        let corners = self.corners();
+        let mut edges = [([0.0; 3], [0.0; 3]); 12];
+        let mut n = 0;
+        for (i, corner) in corners.iter().enumerate() {
+            for axis in 0..3 {
+                if i & (1 << axis) == 0 {
+                    edges[n] = (*corner, corners[i | (1 << axis)]);
+                    n += 1;
+                }
+            }
+        }
+        edges
+    }
+}
+
+// -----------------------------------------------------------------------------
+pub struct Sphere {
+    pub center: Array1<Float>,
+    pub radius: Float,
+    /**
+     * Shared with the other actors of the same material (see
+     * `MaterialLibrary`).
+     */
+    pub material: Arc<dyn Scattering>,
+}
+
+impl Sphere {
+    pub fn new(
+        &self,
+        center: Array1<Float>,
+        radius: Float,
+        material: Arc<dyn Scattering>,
+    ) -> Sphere {
+        Sphere {
+            center,
+            radius,
+            material,
+        }
+    }
+
+    /**
+     * P - C = Radial Vector
+     *
+     * Note that the range of the normalized components of the unit normals
+     * is [-1.0, 1.0].
+     */
+    fn compute_normal(&self, point_sphere: &Array1<Float>) -> Array1<Float> {
+        (point_sphere.clone() - self.center.clone()) / self.radius
+    }
+
+    /**
+     * Direction of increasing longitude around the vertical axis through
+     * the center (along the parallels), or x at the poles.
+     */
+    fn compute_tangent(&self, point_sphere: &Array1<Float>) -> Array1<Float> {
+        let d = point_sphere.clone() - self.center.clone();
+        let length = (d[0] * d[0] + d[2] * d[2]).sqrt();
+        if length > 0.0 {
+            arr1(&[d[2] / length, 0.0, -d[0] / length, 0.0])
+        } else {
+            arr1(&[1.0, 0.0, 0.0, 0.0])
+        }
+    }
+
+    /**
+     * Longitude (u, increasing along the tangent) and latitude (v, from
+     * the bottom pole) around the vertical axis, both in [0, 1].
+     */
+    fn compute_uv(&self, point_sphere: &Array1<Float>) -> [Float; 2] {
+        let d = point_sphere.clone() - self.center.clone();
+        let length = Vec4::l2_norm(d.view());
+        let longitude = d[0].atan2(d[2]);
+        let latitude = (d[1] / length).clamp(-1.0, 1.0).asin();
+        [
+            0.5 + longitude / (2.0 * consts::PI),
+            0.5 + latitude / consts::PI,
+        ]
+    }
+
+    /**
+     * Where the differential rays of `ray` cross the plane tangent at the
+     * hit point, and the footprint they span in texture coordinates. The
+     * normal of a sphere varies by the offset over the radius.
+     */
+    fn compute_differentials(&self, ray: &Ray, record: &mut Hit) {
+        record.footprint = 0.0;
+        record.differentials = None;
+        let differentials = match &ray.differentials {
+            Some(differentials) => differentials,
+            None => return,
+        };
+
+        let offset = |origin: &Array1<Float>, direction: &Array1<Float>| {
+            let cosine = direction.dot(&record.normal);
+            if cosine.abs() < 1e-9 {
+                return None;
+            }
+            let t = (record.point.clone() - origin.clone()).dot(&record.normal)
+                / cosine;
+            Some(origin.clone() + t * direction.clone() - record.point.clone())
+        };
+        let (dpdx, dpdy) = match (
+            offset(&differentials.x_origin, &differentials.x_direction),
+            offset(&differentials.y_origin, &differentials.y_direction),
+        ) {
+            (Some(dpdx), Some(dpdy)) => (dpdx, dpdy),
+            _ => return,
+        };
+
+        let width = |dp: &Array1<Float>| {
+            let uv = self.compute_uv(&(record.point.clone() + dp.clone()));
+            // Across the seam, u wraps around.
+            let du = (uv[0] - record.uv[0]).abs();
+            let du = du.min(1.0 - du);
+            let dv = uv[1] - record.uv[1];
+            (du * du + dv * dv).sqrt()
+        };
+        record.footprint = width(&dpdx).max(width(&dpdy));
+        record.differentials = Some(HitDifferentials {
+            dndx: dpdx.clone() / self.radius,
+            dndy: dpdy.clone() / self.radius,
+            dpdx,
+            dpdy,
+        });
+    }
+}
+
+impl Hittable for Sphere {
+    /**
+     * Solving the sphere equation analitically, leads to real solutions
+     * (hit front / back) or a complex solution (miss).
+     *
+     * vec{radius} = vec{Ray} - vec{Center}
+     *           X = Y
+     *   dot(X, X) = dot(Y, Y)
+     *
+     * Substitute Ray = Origin + t * Dir and solve for t ...
+     *
+     * t^2 dot(Dir, Dir) + 2*t*dot(Dir, Orig - Cent) +
+     *      dot(Orig-Cent, Orig-Cent) = radius^2
+     *
+     */
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let oc = ray.origin.clone() - self.center.clone();
+        let a = ray.direction.dot(&ray.direction);
+        let b = oc.dot(&ray.direction);
+        let c = oc.dot(&oc) - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+
+        if discriminant > 0.0 {
+            // Solution (-) first, then (+): the ray goes on to the far
+            // side through the holes of cutout materials.
+            for t in [
+                (-b - discriminant.sqrt()) / a,
+                (-b + discriminant.sqrt()) / a,
+            ] {
+                if t_min < t && t < t_max {
+                    record.t = t;
+                    record.point = ray.point_at_parameter(t);
+                    record.normal = self.compute_normal(&record.point);
+                    record.tangent = self.compute_tangent(&record.point);
+                    record.uv = self.compute_uv(&record.point);
+                    self.compute_differentials(ray, record);
+                    record.material = self.material.clone();
+                    if record.alpha_test() {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+impl RayTraceable for Sphere {
+    fn validate(&self) -> Result<(), String> {
+        if self.center.len() != 4 {
+            return Err(format!(
+                "center has {} components, expected 4",
+                self.center.len()
+            ));
+        }
+
+        if !self.center.iter().all(|c| c.is_finite()) {
+            return Err("center is not finite".to_string());
+        }
+
+        // Negative radii are fine, they flip the normals (e.g. bubbles).
+        if self.radius == 0.0 || !self.radius.is_finite() {
+            return Err(format!("invalid radius {}", self.radius));
+        }
+
+        Ok(())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.radius.abs();
+        let c = &self.center;
+        Some(Aabb::new(
+            [c[0] - r, c[1] - r, c[2] - r],
+            [c[0] + r, c[1] + r, c[2] + r],
+        ))
+    }
+
+    fn kind(&self) -> &'static str {
+        "sphere"
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.material.textures(textures);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        for (c, delta) in offset.iter().enumerate() {
+            self.center[c] += delta;
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+/**
+ * Work done by the intersection queries of a thread, for cost
+ * visualization: rays traced through the world, nodes of the hierarchies
+ * of the actors visited, and intersection tests of actors and of their
+ * primitives (the triangles of meshes, the instances of prototypes).
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TraversalStats {
+    pub rays: u32,
+    pub nodes: u32,
+    pub tests: u32,
+}
+
+thread_local! {
+    static TRAVERSAL: Cell<TraversalStats> = const {
+        Cell::new(TraversalStats {
+            rays: 0,
+            nodes: 0,
+            tests: 0,
+        })
+    };
+}
+
+/**
+ * The statistics of the current thread since the last call, which starts
+ * them over.
+ */
+pub fn take_traversal_stats() -> TraversalStats {
+    TRAVERSAL.with(|stats| stats.replace(TraversalStats::default()))
+}
+
+fn count_traversal(tests: usize) {
+    TRAVERSAL.with(|stats| {
+        let mut current = stats.get();
+        current.rays = current.rays.saturating_add(1);
+        stats.set(current);
+    });
+    count_work(0, tests);
+}
+
+/**
+ * Count the `nodes` visited and the primitives `tests` of a hierarchy
+ * traversed by the current ray.
+ */
+pub(crate) fn count_work(nodes: usize, tests: usize) {
+    TRAVERSAL.with(|stats| {
+        let mut current = stats.get();
+        current.nodes = current.nodes.saturating_add(nodes as u32);
+        current.tests = current.tests.saturating_add(tests as u32);
+        stats.set(current);
+    });
+}
+
+pub struct HittableList {
+    pub actors: Vec<Box<dyn RayTraceable>>,
+}
+
+impl HittableList {
+    pub fn new(actors: Vec<Box<dyn RayTraceable>>) -> HittableList {
+        HittableList { actors }
+    }
+
+    /**
+     * Box enclosing every bounded actor, if any.
+     */
+    pub fn bounding_box(&self) -> Option<Aabb> {
+        self.actors
+            .iter()
+            .filter_map(|actor| actor.bounding_box())
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /**
+     * Traverse the vector of RayTraceable instances visible to rays of
+     * `kind` (see `Visibility`), and keep track of the closest hit (e.g.
+     * closest to the camera hence, not occluded). The closest (t),
+     * becomes the maximum depth t we willing to accept as a hit in the
+     * following actors.
+     */
+    pub fn is_hit_by(
+        &self,
+        kind: RayKind,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let mut hit_anything = false;
+        let mut closest_so_far = t_max;
+        let mut temp_record = Hit::new();
+        count_traversal(self.actors.len());
+
+        for (index, actor) in self.actors.iter().enumerate() {
+            if !actor.visibility().sees(kind) {
+                continue;
+            }
+            if actor.is_hit(ray, t_min, closest_so_far, &mut temp_record) {
+                hit_anything = true;
+                closest_so_far = temp_record.t;
+                temp_record.actor_id = index;
+
+                // Dereferencing the borrow (e.g. pointer) to assign to
+                // the mutable borrowed piece of memory
+                *record = Hit::copy(&temp_record);
+            }
+        }
+        if hit_anything {
+            record.incident = Vec4::normalize(ray.direction.clone());
+        }
+
+        hit_anything
+    }
+}
+
+impl Hittable for HittableList {
+    /**
+     * The closest hit of a bounce of a path (see `is_hit_by`).
+     */
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        self.is_hit_by(RayKind::Indirect, ray, t_min, t_max, record)
+    }
+}
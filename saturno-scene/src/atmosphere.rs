@@ -0,0 +1,337 @@
+use crate::light::Light;
+use ndarray::Array1;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::Vec4;
+use std::sync::Arc;
+
+// Air around a planet: molecules (Rayleigh scattering, blue) and aerosols
+// (Mie scattering, white and forward) thinning out exponentially with the
+// altitude. Light is scattered once (single scattering) towards the rays
+// crossing the shell, which gives the blue sky, the red sunsets and the
+// glowing limb of planets seen from space.
+
+/**
+ * Earth's scattering at sea level per meter, for red, green and blue.
+ */
+const EARTH_RAYLEIGH: [Float; 3] = [5.802e-6, 13.558e-6, 33.1e-6];
+const EARTH_MIE: Float = 3.996e-6;
+const EARTH_RADIUS: Float = 6.371e6;
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/**
+ * Mean of a density decreasing exponentially from `a` to `b` (the
+ * logarithmic mean), so steps many scale heights long stay accurate.
+ */
+fn log_mean(a: Float, b: Float) -> Float {
+    if (a - b).abs() <= 1e-9 * a.max(b) {
+        (a + b) / 2.0
+    } else {
+        (a - b) / (a.ln() - b.ln())
+    }
+}
+
+/**
+ * The atmosphere of a planet centered at `center`, from its surface at
+ * `planet_radius` up to `atmosphere_radius`, all in scene units. The
+ * scattering coefficients (per scene unit, at the surface) decrease with
+ * the altitude over their scale heights. Aerosols also absorb, their
+ * extinction being `mie_extinction` times their scattering.
+ *
+ * The planet itself is an actor of the scene, e.g. a sphere of the same
+ * center and radius. Only the planet shadows the atmosphere, the other
+ * actors do not.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Atmosphere {
+    pub center: [Float; 3],
+    pub planet_radius: Float,
+    pub atmosphere_radius: Float,
+    pub rayleigh: [Float; 3],
+    pub rayleigh_height: Float,
+    pub mie: Float,
+    pub mie_extinction: Float,
+    pub mie_height: Float,
+    pub mie_anisotropy: Float,
+    /**
+     * Steps of the integration along the rays (the light is integrated
+     * towards the lights over half as many).
+     */
+    pub steps: usize,
+}
+
+/**
+ * Scattered light and transmittance of a ray across the atmosphere.
+ */
+pub struct Segment {
+    pub transmittance: [Float; 3],
+    /**
+     * Light of every light scattered towards the origin of the ray, in
+     * the order of the lights.
+     */
+    pub in_scattering: Vec<[Float; 3]>,
+}
+
+impl Atmosphere {
+    /**
+     * The Earth's atmosphere, 100 km thick, for a planet of
+     * `planet_radius` scene units.
+     */
+    pub fn earth(center: [Float; 3], planet_radius: Float) -> Atmosphere {
+        // Meters per scene unit.
+        let scale = EARTH_RADIUS / planet_radius;
+        Atmosphere {
+            center,
+            planet_radius,
+            atmosphere_radius: planet_radius * (EARTH_RADIUS + 1.0e5)
+                / EARTH_RADIUS,
+            rayleigh: EARTH_RAYLEIGH.map(|beta| beta * scale),
+            rayleigh_height: 8000.0 / scale,
+            mie: EARTH_MIE * scale,
+            mie_extinction: 1.11,
+            mie_height: 1200.0 / scale,
+            mie_anisotropy: 0.8,
+            steps: 16,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = |x: Float| x > 0.0 && x.is_finite();
+        let shell = self.center.iter().all(|c| c.is_finite())
+            && positive(self.planet_radius)
+            && self.atmosphere_radius > self.planet_radius
+            && self.atmosphere_radius.is_finite();
+        if !shell {
+            return Err(format!(
+                "invalid atmosphere shell from {} to {} around {:?}",
+                self.planet_radius, self.atmosphere_radius, self.center
+            ));
+        }
+        let scattering = self
+            .rayleigh
+            .iter()
+            .all(|beta| *beta >= 0.0 && beta.is_finite())
+            && self.mie >= 0.0
+            && self.mie.is_finite()
+            && self.mie_extinction >= 1.0
+            && self.mie_extinction.is_finite()
+            && positive(self.rayleigh_height)
+            && positive(self.mie_height);
+        if !scattering {
+            return Err("invalid atmosphere scattering".to_string());
+        }
+        if !(self.mie_anisotropy > -1.0 && self.mie_anisotropy < 1.0) {
+            return Err(format!(
+                "invalid atmosphere anisotropy {}",
+                self.mie_anisotropy
+            ));
+        }
+        if self.steps == 0 {
+            return Err("no atmosphere integration steps".to_string());
+        }
+        Ok(())
+    }
+
+    /**
+     * Distances along the unit `direction` from `origin` where it enters
+     * and leaves the sphere of `radius`, if it crosses it.
+     */
+    fn crossing(
+        &self,
+        origin: &[Float; 3],
+        direction: &[Float; 3],
+        radius: Float,
+    ) -> Option<(Float, Float)> {
+        let offset = [0, 1, 2].map(|i| origin[i] - self.center[i]);
+        let b = dot(&offset, direction);
+        let c = dot(&offset, &offset) - radius * radius;
+        let discriminant = b * b - c;
+        if discriminant <= 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        Some((-b - root, -b + root))
+    }
+
+    /**
+     * Rayleigh and Mie densities (relative to the surface) at `point`.
+     */
+    fn densities(&self, point: &[Float; 3]) -> (Float, Float) {
+        let offset = [0, 1, 2].map(|i| point[i] - self.center[i]);
+        let altitude =
+            (dot(&offset, &offset).sqrt() - self.planet_radius).max(0.0);
+        (
+            (-altitude / self.rayleigh_height).exp(),
+            (-altitude / self.mie_height).exp(),
+        )
+    }
+
+    fn extinction(&self, rayleigh: Float, mie: Float) -> [Float; 3] {
+        let mie = mie * self.mie * self.mie_extinction;
+        self.rayleigh.map(|beta| beta * rayleigh + mie)
+    }
+
+    /**
+     * Rayleigh and Mie densities at `t` along the unit `direction` from
+     * `origin`.
+     */
+    fn densities_at(
+        &self,
+        origin: &[Float; 3],
+        direction: &[Float; 3],
+        t: Float,
+    ) -> (Float, Float) {
+        self.densities(&[0, 1, 2].map(|c| origin[c] + t * direction[c]))
+    }
+
+    /**
+     * Rayleigh and Mie densities integrated from `origin` along the unit
+     * `direction`, over `steps` steps up to `distance` or out of the
+     * atmosphere.
+     */
+    fn optical_depth(
+        &self,
+        origin: &[Float; 3],
+        direction: &[Float; 3],
+        distance: Float,
+        steps: usize,
+    ) -> (Float, Float) {
+        let (enter, leave) =
+            match self.crossing(origin, direction, self.atmosphere_radius) {
+                Some(crossing) => crossing,
+                None => return (0.0, 0.0),
+            };
+        let (start, end) = (enter.max(0.0), leave.min(distance));
+        if start >= end {
+            return (0.0, 0.0);
+        }
+        let step = (end - start) / steps as Float;
+        let mut depth = (0.0, 0.0);
+        let mut near = self.densities_at(origin, direction, start);
+        for i in 1..=steps {
+            let far =
+                self.densities_at(origin, direction, start + i as Float * step);
+            depth.0 += log_mean(near.0, far.0) * step;
+            depth.1 += log_mean(near.1, far.1) * step;
+            near = far;
+        }
+        depth
+    }
+
+    /**
+     * Fraction of the light going through the atmosphere along `ray`, up
+     * to the parameter `t_max`.
+     */
+    pub fn transmittance(&self, ray: &Ray, t_max: Float) -> [Float; 3] {
+        let length = Vec4::l2_norm(ray.direction.view());
+        let origin = [ray.origin[0], ray.origin[1], ray.origin[2]];
+        let direction = [0, 1, 2].map(|i| ray.direction[i] / length);
+        let (rayleigh, mie) = self.optical_depth(
+            &origin,
+            &direction,
+            t_max * length,
+            (self.steps / 2).max(1),
+        );
+        self.extinction(rayleigh, mie).map(|tau| (-tau).exp())
+    }
+
+    /**
+     * Transmittance of `ray` up to the parameter `t_max` and the light of
+     * `lights` it gathers on the way, None if it misses the atmosphere.
+     */
+    pub fn segment(
+        &self,
+        ray: &Ray,
+        t_max: Float,
+        lights: &[Arc<dyn Light>],
+    ) -> Option<Segment> {
+        let length = Vec4::l2_norm(ray.direction.view());
+        let origin = [ray.origin[0], ray.origin[1], ray.origin[2]];
+        let direction = [0, 1, 2].map(|i| ray.direction[i] / length);
+        let (enter, leave) =
+            self.crossing(&origin, &direction, self.atmosphere_radius)?;
+        let (start, end) = (enter.max(0.0), leave.min(t_max * length));
+        if start >= end {
+            return None;
+        }
+
+        let g = self.mie_anisotropy;
+        let step = (end - start) / self.steps as Float;
+        let mut depth = (0.0, 0.0);
+        let mut in_scattering = vec![[0.0; 3]; lights.len()];
+        let mut near = self.densities_at(&origin, &direction, start);
+        for i in 0..self.steps {
+            let t = start + (i as Float + 0.5) * step;
+            let point = [0, 1, 2].map(|c| origin[c] + t * direction[c]);
+            let middle = self.densities(&point);
+            let far = self.densities_at(&origin, &direction, t + step / 2.0);
+            // Up to the middle of the step, and over all of it.
+            let view = (
+                depth.0 + log_mean(near.0, middle.0) * step / 2.0,
+                depth.1 + log_mean(near.1, middle.1) * step / 2.0,
+            );
+            let (rayleigh, mie) = (
+                log_mean(near.0, far.0) * step,
+                log_mean(near.1, far.1) * step,
+            );
+            depth.0 += rayleigh;
+            depth.1 += mie;
+            near = far;
+
+            let sample_point =
+                Array1::from(vec![point[0], point[1], point[2], 1.0]);
+            for (light, gathered) in lights.iter().zip(&mut in_scattering) {
+                let sample = match light.sample(&sample_point) {
+                    Some(sample) => sample,
+                    None => continue,
+                };
+                let towards = [
+                    sample.direction[0],
+                    sample.direction[1],
+                    sample.direction[2],
+                ];
+                if let Some((near, _)) =
+                    self.crossing(&point, &towards, self.planet_radius)
+                {
+                    if near > 0.0 && near < sample.distance {
+                        continue;
+                    }
+                }
+                let sun = self.optical_depth(
+                    &point,
+                    &towards,
+                    sample.distance,
+                    (self.steps / 2).max(1),
+                );
+                let attenuation =
+                    self.extinction(view.0 + sun.0, view.1 + sun.1);
+
+                let cosine = dot(&direction, &towards);
+                let rayleigh_phase =
+                    3.0 / (16.0 * consts::PI) * (1.0 + cosine * cosine);
+                let denominator = 1.0 + g * g - 2.0 * g * cosine;
+                let mie_phase = (1.0 - g * g)
+                    / (4.0 * consts::PI * denominator * denominator.sqrt());
+                for c in 0..3 {
+                    let scattering =
+                        self.rayleigh[c] * rayleigh * rayleigh_phase
+                            + self.mie * mie * mie_phase;
+                    gathered[c] += (-attenuation[c]).exp()
+                        * scattering
+                        * sample.radiance[c];
+                }
+            }
+        }
+
+        Some(Segment {
+            transmittance: self
+                .extinction(depth.0, depth.1)
+                .map(|tau| (-tau).exp()),
+            in_scattering,
+        })
+    }
+}
@@ -0,0 +1,368 @@
+use crate::actor::Aabb;
+use crate::actor::Hit;
+use crate::actor::Hittable;
+use crate::actor::RayTraceable;
+use crate::light::orthonormal_basis;
+use crate::material::Scattering;
+use crate::texture::TextureSampler;
+use ndarray::arr1;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Gravitational lensing around a black hole: near it the rays do not go
+// straight but follow the light-like geodesics of a Schwarzschild mass,
+// bending around it (the Einstein ring, the far side of an accretion disk
+// showing above and below the hole) or falling through its horizon.
+//
+// Rays are marched in short straight chords, tested against the world
+// one after the other. Far from the hole, out of its sphere of influence,
+// the bending is negligible and the rays go straight. The shadow rays
+// towards the lights go straight too.
+
+/**
+ * A non-rotating black hole at `center`, of Schwarzschild (horizon)
+ * radius `radius`. Rays within `influence` radii of the center are bent,
+ * each step `step` times their distance to the center long. Rays still
+ * marching after `max_steps` orbit the photon sphere and are captured.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlackHole {
+    pub center: [Float; 3],
+    pub radius: Float,
+    #[cfg_attr(feature = "serde", serde(default = "influence"))]
+    pub influence: Float,
+    #[cfg_attr(feature = "serde", serde(default = "step"))]
+    pub step: Float,
+    #[cfg_attr(feature = "serde", serde(default = "max_steps"))]
+    pub max_steps: u32,
+}
+
+#[cfg(feature = "serde")]
+fn influence() -> Float {
+    50.0
+}
+
+#[cfg(feature = "serde")]
+fn step() -> Float {
+    0.02
+}
+
+#[cfg(feature = "serde")]
+fn max_steps() -> u32 {
+    5000
+}
+
+/**
+ * Where a ray bent by a black hole ends.
+ */
+pub enum Geodesic {
+    /**
+     * It hits an actor along the chord `Ray` (the hit record is filled,
+     * its parameter along the chord).
+     */
+    Hit(Ray),
+    /**
+     * It leaves the scene along `Ray`.
+     */
+    Escaped(Ray),
+    /**
+     * It falls through the horizon.
+     */
+    Captured,
+}
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[Float; 3], b: &[Float; 3]) -> [Float; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+impl BlackHole {
+    pub fn new(center: [Float; 3], radius: Float) -> BlackHole {
+        BlackHole {
+            center,
+            radius,
+            influence: 50.0,
+            step: 0.02,
+            max_steps: 5000,
+        }
+    }
+
+    /**
+     * Acceleration of a ray at `x` (from the center) whose squared
+     * angular momentum is `h2`: in these coordinates the orbit equation
+     * of light, u'' + u = 3/2 rs u^2, is that of a particle pulled by
+     * 3/2 rs h^2 / r^4.
+     */
+    fn acceleration(&self, x: &[Float; 3], h2: Float) -> [Float; 3] {
+        let r2 = dot(x, x);
+        let pull = -1.5 * self.radius * h2 / (r2 * r2 * r2.sqrt());
+        x.map(|c| c * pull)
+    }
+
+    /**
+     * Follow `ray` around the black hole until it hits an actor of
+     * `world` (beyond `t_min` from its origin) into `record`, escapes or
+     * is captured.
+     */
+    pub fn trace(
+        &self,
+        ray: &Ray,
+        world: &dyn Hittable,
+        t_min: Float,
+        record: &mut Hit,
+    ) -> Geodesic {
+        let c = self.center;
+        let origin = [0, 1, 2].map(|i| ray.origin[i] - c[i]);
+        let direction = [ray.direction[0], ray.direction[1], ray.direction[2]];
+        let reach = self.influence * self.radius;
+
+        // Straight to the sphere of influence, if the ray crosses it.
+        let b = dot(&origin, &direction);
+        let outside = dot(&origin, &origin) - reach * reach;
+        let discriminant = b * b - outside;
+        let enter = if outside <= 0.0 {
+            0.0
+        } else if discriminant > 0.0 && b < 0.0 {
+            -b - discriminant.sqrt()
+        } else {
+            return straight(ray, world, t_min, Float::MAX, record);
+        };
+        if enter > 0.0 && world.is_hit(ray, t_min, enter, record) {
+            return Geodesic::Hit(copy(ray));
+        }
+
+        let mut x = [0, 1, 2].map(|i| origin[i] + enter * direction[i]);
+        let mut v = direction;
+        let angular = cross(&x, &v);
+        let h2 = dot(&angular, &angular);
+        let mut t_min = if enter > 0.0 { 0.0 } else { t_min };
+
+        for _ in 0..self.max_steps {
+            let r = dot(&x, &x).sqrt();
+            if r <= self.radius {
+                return Geodesic::Captured;
+            }
+            if r > reach && dot(&x, &v) > 0.0 {
+                let out = Ray::new(
+                    arr1(&[x[0] + c[0], x[1] + c[1], x[2] + c[2], 1.0]),
+                    arr1(&[v[0], v[1], v[2], 0.0]),
+                )
+                .at_time(ray.time);
+                return straight(&out, world, 0.0, Float::MAX, record);
+            }
+
+            // Runge-Kutta step of the position and the velocity.
+            let dt = self.step * r;
+            let a = |x: &[Float; 3]| self.acceleration(x, h2);
+            let offset = |x: &[Float; 3], d: &[Float; 3], s: Float| {
+                [0, 1, 2].map(|i| x[i] + d[i] * s)
+            };
+            let (k1x, k1v) = (v, a(&x));
+            let (k2x, k2v) =
+                (offset(&v, &k1v, dt / 2.0), a(&offset(&x, &k1x, dt / 2.0)));
+            let (k3x, k3v) =
+                (offset(&v, &k2v, dt / 2.0), a(&offset(&x, &k2x, dt / 2.0)));
+            let (k4x, k4v) = (offset(&v, &k3v, dt), a(&offset(&x, &k3x, dt)));
+            let next = [0, 1, 2].map(|i| {
+                x[i] + dt / 6.0
+                    * (k1x[i] + 2.0 * k2x[i] + 2.0 * k3x[i] + k4x[i])
+            });
+            v = [0, 1, 2].map(|i| {
+                v[i] + dt / 6.0
+                    * (k1v[i] + 2.0 * k2v[i] + 2.0 * k3v[i] + k4v[i])
+            });
+
+            let chord = [0, 1, 2].map(|i| next[i] - x[i]);
+            let length = dot(&chord, &chord).sqrt();
+            let segment = Ray::new(
+                arr1(&[x[0] + c[0], x[1] + c[1], x[2] + c[2], 1.0]),
+                arr1(&[chord[0], chord[1], chord[2], 0.0]),
+            )
+            .at_time(ray.time);
+            if world.is_hit(&segment, t_min, length, record) {
+                return Geodesic::Hit(segment);
+            }
+            x = next;
+            t_min = 0.0;
+        }
+        Geodesic::Captured
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.center.iter().all(|c| c.is_finite()) {
+            return Err("black hole center is not finite".to_string());
+        }
+        if !(self.radius > 0.0 && self.radius.is_finite()) {
+            return Err(format!("invalid black hole radius {}", self.radius));
+        }
+        if !(self.influence > 1.0 && self.influence.is_finite()) {
+            return Err(format!(
+                "invalid black hole influence {}",
+                self.influence
+            ));
+        }
+        if !(self.step > 0.0 && self.step < 1.0) {
+            return Err(format!("invalid black hole step {}", self.step));
+        }
+        Ok(())
+    }
+}
+
+fn copy(ray: &Ray) -> Ray {
+    let mut copy =
+        Ray::new(ray.origin.clone(), ray.direction.clone()).at_time(ray.time);
+    copy.differentials = ray.differentials.clone();
+    copy
+}
+
+fn straight(
+    ray: &Ray,
+    world: &dyn Hittable,
+    t_min: Float,
+    t_max: Float,
+    record: &mut Hit,
+) -> Geodesic {
+    if world.is_hit(ray, t_min, t_max, record) {
+        Geodesic::Hit(copy(ray))
+    } else {
+        Geodesic::Escaped(copy(ray))
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * The disk of gas spiralling into a black hole: a flat opaque annulus
+ * around `center`, perpendicular to `normal`, from `inner_radius` to
+ * `outer_radius` (typically from 3 Schwarzschild radii, the innermost
+ * stable orbit). Its texture coordinates are the inner radius over the
+ * radius of the hit (u, 1 on the inner edge) and the azimuth (v), as
+ * `AccretionGlow` expects.
+ */
+pub struct AccretionDisk {
+    pub center: [Float; 3],
+    pub normal: [Float; 3],
+    pub inner_radius: Float,
+    pub outer_radius: Float,
+    pub material: Arc<dyn Scattering>,
+}
+
+impl AccretionDisk {
+    pub fn new(
+        center: [Float; 3],
+        normal: [Float; 3],
+        inner_radius: Float,
+        outer_radius: Float,
+        material: Arc<dyn Scattering>,
+    ) -> AccretionDisk {
+        AccretionDisk {
+            center,
+            normal,
+            inner_radius,
+            outer_radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for AccretionDisk {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let length = dot(&self.normal, &self.normal).sqrt();
+        let n = self.normal.map(|c| c / length);
+        let direction = [ray.direction[0], ray.direction[1], ray.direction[2]];
+        let rise = dot(&direction, &n);
+        if rise.abs() < 1e-12 {
+            return false;
+        }
+        let to_center = [0, 1, 2].map(|i| self.center[i] - ray.origin[i]);
+        let t = dot(&to_center, &n) / rise;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+
+        let point = ray.point_at_parameter(t);
+        let offset = [0, 1, 2].map(|i| point[i] - self.center[i]);
+        let radius = dot(&offset, &offset).sqrt();
+        if radius < self.inner_radius || radius > self.outer_radius {
+            return false;
+        }
+
+        let side = if rise > 0.0 { -1.0 } else { 1.0 };
+        let (u, v) = orthonormal_basis(&n);
+        let azimuth = dot(&offset, &v).atan2(dot(&offset, &u));
+        let tangent = cross(&n, &offset).map(|c| c / radius);
+
+        record.t = t;
+        record.point = point;
+        record.normal = arr1(&[n[0] * side, n[1] * side, n[2] * side, 0.0]);
+        record.tangent = arr1(&[tangent[0], tangent[1], tangent[2], 0.0]);
+        record.uv = [
+            self.inner_radius / radius,
+            0.5 + azimuth / (2.0 * consts::PI),
+        ];
+        record.footprint = 0.0;
+        record.differentials = None;
+        record.material = self.material.clone();
+        record.alpha_test()
+    }
+}
+
+impl RayTraceable for AccretionDisk {
+    fn validate(&self) -> Result<(), String> {
+        if !self.center.iter().all(|c| c.is_finite()) {
+            return Err("center is not finite".to_string());
+        }
+        let length = dot(&self.normal, &self.normal).sqrt();
+        if !(length > 0.0 && length.is_finite()) {
+            return Err(format!("invalid disk normal {:?}", self.normal));
+        }
+        let radii = self.inner_radius > 0.0
+            && self.inner_radius < self.outer_radius
+            && self.outer_radius.is_finite();
+        if !radii {
+            return Err(format!(
+                "invalid disk radii {} to {}",
+                self.inner_radius, self.outer_radius
+            ));
+        }
+        Ok(())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.outer_radius;
+        let c = &self.center;
+        Some(Aabb::new(
+            [c[0] - r, c[1] - r, c[2] - r],
+            [c[0] + r, c[1] + r, c[2] + r],
+        ))
+    }
+
+    fn kind(&self) -> &'static str {
+        "accretion_disk"
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.material.textures(textures);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        self.center = [0, 1, 2].map(|i| self.center[i] + offset[i]);
+    }
+}
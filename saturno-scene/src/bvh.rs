@@ -0,0 +1,306 @@
+use crate::actor::Aabb;
+use crate::mesh::{Node, LEAF_SIZE};
+use saturno_math::common::Float;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Building the hierarchies of meshes with the surface area heuristic:
+// every node splits its triangles where the expected cost of the rays
+// crossing its children, their areas times their triangles, is the
+// lowest. The candidate splits are the boundaries of 16 bins of equal
+// width along the longest axis of the centroids (binned SAH, Wald, On
+// Fast Construction of SAH-based Bounding Volume Hierarchies), so a node
+// takes two passes over its triangles.
+//
+// Multi-million triangle meshes build on several threads: the upper nodes
+// count their bins in parallel, each thread over a part of the triangles,
+// and their two children are built on threads of their own down to where
+// there are too few triangles to be worth a thread. The hierarchy is the
+// same on any number of threads.
+
+/**
+ * Bins of the candidate splits of a node.
+ */
+const BINS: usize = 16;
+
+/**
+ * Triangles under which a node is built on a single thread.
+ */
+const PARALLEL_TRIANGLES: usize = 16384;
+
+/**
+ * Threads building the hierarchies, 0 for every core.
+ */
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/**
+ * Build the hierarchies of the meshes on `threads` threads (0, the
+ * default, for every core).
+ */
+pub fn set_build_threads(threads: usize) {
+    THREADS.store(threads, Ordering::Relaxed);
+}
+
+/**
+ * Threads building the hierarchies, see `set_build_threads`.
+ */
+pub fn build_threads() -> usize {
+    // There are no threads to spawn in the browser.
+    if cfg!(target_arch = "wasm32") {
+        return 1;
+    }
+    match THREADS.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        threads => threads,
+    }
+}
+
+/**
+ * The bounds of the triangles in a bin, and how many.
+ */
+#[derive(Clone, Copy)]
+struct Bin {
+    bounds: Option<Aabb>,
+    count: usize,
+}
+
+impl Bin {
+    const EMPTY: Bin = Bin {
+        bounds: None,
+        count: 0,
+    };
+
+    fn add(&mut self, bounds: &Aabb, count: usize) {
+        self.bounds = Some(match &self.bounds {
+            Some(own) => own.union(bounds),
+            None => *bounds,
+        });
+        self.count += count;
+    }
+
+    fn merge(&mut self, other: &Bin) {
+        if let Some(bounds) = &other.bounds {
+            self.add(bounds, other.count);
+        }
+    }
+
+    fn cost(&self) -> Float {
+        self.bounds
+            .map_or(0.0, |bounds| bounds.area() * self.count as Float)
+    }
+}
+
+struct Builder<'a> {
+    bounds: &'a [Aabb],
+    centroids: Vec<[Float; 3]>,
+}
+
+/**
+ * Build the hierarchy of triangles of `bounds`. Also gives their order,
+ * the leaves being ranges of it.
+ */
+pub(crate) fn build(bounds: &[Aabb]) -> (Vec<Node>, Vec<usize>) {
+    let threads = build_threads().max(1);
+    let centroid = |b: &Aabb| [0, 1, 2].map(|i| (b.min[i] + b.max[i]) / 2.0);
+    let builder = Builder {
+        bounds,
+        centroids: parallel_map(bounds, threads, |part| {
+            part.iter().map(centroid).collect::<Vec<_>>()
+        })
+        .concat(),
+    };
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    let mut nodes = vec![];
+    builder.build_node(&mut order, 0, threads, &mut nodes);
+    (nodes, order)
+}
+
+/**
+ * `f` of `threads` parts of `items`, in order, the first on the calling
+ * thread.
+ */
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    threads: usize,
+    f: impl Fn(&[T]) -> R + Sync,
+) -> Vec<R> {
+    if threads <= 1 || items.len() < PARALLEL_TRIANGLES {
+        return vec![f(items)];
+    }
+    let size = items.len().div_ceil(threads);
+    let mut parts = items.chunks(size);
+    let first = parts.next().unwrap();
+    std::thread::scope(|scope| {
+        let f = &f;
+        let workers: Vec<_> =
+            parts.map(|part| scope.spawn(move || f(part))).collect();
+        std::iter::once(f(first))
+            .chain(workers.into_iter().map(|worker| worker.join().unwrap()))
+            .collect()
+    })
+}
+
+impl Builder<'_> {
+    fn build_node(
+        &self,
+        order: &mut [usize],
+        first: usize,
+        threads: usize,
+        nodes: &mut Vec<Node>,
+    ) -> usize {
+        let node_bounds = parallel_map(order, threads, |part| {
+            part.iter()
+                .map(|&i| self.bounds[i])
+                .reduce(|a, b| a.union(&b))
+                .unwrap()
+        })
+        .into_iter()
+        .reduce(|a, b| a.union(&b))
+        .unwrap();
+        let index = nodes.len();
+        if order.len() <= LEAF_SIZE {
+            nodes.push(Node::Leaf {
+                bounds: node_bounds,
+                first,
+                count: order.len(),
+            });
+            return index;
+        }
+
+        let middle = self.split(order, threads);
+        let parallel = threads > 1 && order.len() >= PARALLEL_TRIANGLES;
+
+        // Placeholder, replaced once the children are built.
+        nodes.push(Node::Leaf {
+            bounds: node_bounds,
+            first,
+            count: 0,
+        });
+        let (left_order, right_order) = order.split_at_mut(middle);
+        let (left, right) = if parallel {
+            // Each child in its own hierarchy, appended after this node.
+            let left_threads = threads / 2;
+            let (left_nodes, right_nodes) = std::thread::scope(|scope| {
+                let left = scope.spawn(|| {
+                    let mut nodes = vec![];
+                    self.build_node(
+                        left_order,
+                        first,
+                        left_threads,
+                        &mut nodes,
+                    );
+                    nodes
+                });
+                let mut right = vec![];
+                self.build_node(
+                    right_order,
+                    first + middle,
+                    threads - left_threads,
+                    &mut right,
+                );
+                (left.join().unwrap(), right)
+            });
+            (append(nodes, left_nodes), append(nodes, right_nodes))
+        } else {
+            let left = self.build_node(left_order, first, 1, nodes);
+            let right = self.build_node(right_order, first + middle, 1, nodes);
+            (left, right)
+        };
+        nodes[index] = Node::Inner {
+            bounds: node_bounds,
+            left,
+            right,
+        };
+        index
+    }
+
+    /**
+     * Partition `order` at the split of the lowest cost, the triangles of
+     * the left child first, and give where they end. At the median when
+     * the centroids are at the same point.
+     */
+    fn split(&self, order: &mut [usize], threads: usize) -> usize {
+        let centroid_bounds = parallel_map(order, threads, |part| {
+            part.iter()
+                .map(|&i| Aabb::new(self.centroids[i], self.centroids[i]))
+                .reduce(|a, b| a.union(&b))
+                .unwrap()
+        })
+        .into_iter()
+        .reduce(|a, b| a.union(&b))
+        .unwrap();
+        let extent =
+            [0, 1, 2].map(|i| centroid_bounds.max[i] - centroid_bounds.min[i]);
+        let axis = if extent[0] >= extent[1] && extent[0] >= extent[2] {
+            0
+        } else if extent[1] >= extent[2] {
+            1
+        } else {
+            2
+        };
+        if extent[axis] <= 0.0 {
+            return order.len() / 2;
+        }
+
+        let low = centroid_bounds.min[axis];
+        let bin = |i: usize| {
+            let offset = (self.centroids[i][axis] - low) / extent[axis];
+            ((offset * BINS as Float) as usize).min(BINS - 1)
+        };
+        let mut bins = [Bin::EMPTY; BINS];
+        let counted = parallel_map(order, threads, |part| {
+            let mut bins = [Bin::EMPTY; BINS];
+            for &i in part {
+                bins[bin(i)].add(&self.bounds[i], 1);
+            }
+            bins
+        });
+        for part in &counted {
+            for (bin, counted) in bins.iter_mut().zip(part) {
+                bin.merge(counted);
+            }
+        }
+
+        // The cost of the left side of every split, then the best split
+        // sweeping from the right.
+        let mut left_costs = [0.0; BINS];
+        let mut left = Bin::EMPTY;
+        for split in 1..BINS {
+            left.merge(&bins[split - 1]);
+            left_costs[split] = left.cost();
+        }
+        let mut right = Bin::EMPTY;
+        let mut best = (Float::INFINITY, BINS / 2);
+        for split in (1..BINS).rev() {
+            right.merge(&bins[split]);
+            let cost = left_costs[split] + right.cost();
+            if right.count < order.len() && right.count > 0 && cost < best.0 {
+                best = (cost, split);
+            }
+        }
+
+        let mut middle = 0;
+        for j in 0..order.len() {
+            if bin(order[j]) < best.1 {
+                order.swap(middle, j);
+                middle += 1;
+            }
+        }
+        middle
+    }
+}
+
+/**
+ * Append the nodes of a hierarchy built on its own, giving where its
+ * root went.
+ */
+fn append(nodes: &mut Vec<Node>, mut other: Vec<Node>) -> usize {
+    let offset = nodes.len();
+    for node in &mut other {
+        if let Node::Inner { left, right, .. } = node {
+            *left += offset;
+            *right += offset;
+        }
+    }
+    nodes.append(&mut other);
+    offset
+}
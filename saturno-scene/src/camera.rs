@@ -0,0 +1,327 @@
+use ndarray::{arr1, arr2, Array1, Array2};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::RayDifferentials;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+
+/**
+ * Transformation is from pixel-coordinates to world-coordinates.
+ * Origin is in world-coordinates.
+ * Resolution in pixels.
+ *
+ */
+pub struct Camera {
+    pub resolution_x: u32,
+    pub resolution_y: u32,
+    pub origin: Array1<Float>,
+    transformation: Array2<Float>,
+    camera_orientation: Array2<Float>,
+    lens_radius: Float,
+    /**
+     * Span of the shutter interval (from 0 when it opens to 1 when it
+     * closes) the rays are cast at, uniformly: all at its start if empty.
+     */
+    pub shutter: [Float; 2],
+}
+
+/**
+ *  Transform image pixel (i,j) to image plane coordinates (u, v).
+ */
+fn compute_image_to_world(
+    resolution_x: u32,
+    resolution_y: u32,
+    half_width: Float,
+    half_height: Float,
+    focus_dist: Float,
+    origin: &Array1<Float>,
+    camera_orientation: &Array2<Float>,
+) -> Array2<Float> {
+    let spacing = arr1(&[
+        half_width * 2.0 / resolution_x as Float,
+        half_height * 2.0 / resolution_y as Float,
+    ]);
+
+    // Lower-left corner is the image-plane's origin
+    // (-hw, -hl, -1.0)
+    let to_image_plane = arr2(&[
+        [spacing[0], 0.0, 0.0, -half_width],
+        [0.0, spacing[1], 0.0, -half_height],
+        [0.0, 0.0, 1.0, -focus_dist],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    let camera_translation = arr2(&[
+        [1.0, 0.0, 0.0, origin[0]],
+        [0.0, 1.0, 0.0, origin[1]],
+        [0.0, 0.0, 1.0, origin[2]],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    let to_world = camera_translation.dot(camera_orientation);
+
+    let flip_y = arr2(&[
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, -1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    to_world.dot(&flip_y.dot(&to_image_plane))
+}
+
+/**
+ *  Transform image pixel (i,j) to image plane coordinates (u, v).
+ */
+fn compute_camera_orientation(
+    u: Array1<Float>,
+    v: Array1<Float>,
+    w: Array1<Float>,
+) -> Array2<Float> {
+    arr2(&[
+        [u[0], v[0], w[0], 0.0],
+        [u[1], v[1], w[1], 0.0],
+        [u[2], v[2], w[2], 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ])
+}
+
+pub(crate) fn random_in_unit_disk() -> Array1<Float> {
+    let mut p = arr1(&[Float::MAX, Float::MAX]);
+    let min = -1.0;
+    let max = 1.0;
+
+    while p.dot(&p) >= 1.0 {
+        p[0] = random::gen_range(min, max);
+        p[1] = random::gen_range(min, max);
+    }
+
+    arr1(&[p[0], p[1], 0.0, 1.0])
+}
+
+impl Camera {
+    pub fn new(
+        vertical_fov: Float,
+        resolution_x: u32,
+        resolution_y: u32,
+        origin: Array1<Float>,
+        lookat: Array1<Float>,
+        up: Array1<Float>,
+        aperture: Float,
+    ) -> Camera {
+        let lens_radius = aperture / 2.0;
+        let focus_dist =
+            Vec4::l2_norm((origin.clone() - lookat.clone()).view());
+        //let focus_dist = 10.0;
+        let theta = vertical_fov * consts::PI / 180.0;
+        let aspect = resolution_x as Float / resolution_y as Float;
+        let half_height = focus_dist * (theta / 2.0).tan();
+        let half_width = aspect * half_height;
+
+        // Camera orthonormal basis
+        let w = Vec4::normalize(origin.clone() - lookat.clone());
+        let u = Vec4::normalize(Vec4::cross(up.clone(), w.clone()));
+        let v = Vec4::cross(w.clone(), u.clone());
+
+        let camera_orientation =
+            compute_camera_orientation(u.clone(), v.clone(), w.clone());
+
+        let transformation = compute_image_to_world(
+            resolution_x,
+            resolution_y,
+            half_width,
+            half_height,
+            focus_dist,
+            &origin,
+            &camera_orientation,
+        );
+
+        Camera {
+            resolution_x,
+            resolution_y,
+            origin,
+            transformation,
+            lens_radius,
+            camera_orientation,
+            shutter: [0.0, 0.0],
+        }
+    }
+
+    /**
+     * Distance of a world point in front of the camera, along the
+     * viewing direction (negative behind it).
+     */
+    pub fn view_depth(&self, point: &[Float; 3]) -> Float {
+        (0..3)
+            .map(|i| {
+                (self.origin[i] - point[i]) * self.camera_orientation[[i, 2]]
+            })
+            .sum()
+    }
+
+    /**
+     * Move the camera by `offset`, keeping where it looks.
+     */
+    pub fn translate(&mut self, offset: &[Float; 3]) {
+        for (i, delta) in offset.iter().enumerate() {
+            self.origin[i] += delta;
+            self.transformation[[i, 3]] += delta;
+        }
+    }
+
+    /**
+     * The camera with the same view and lens at another resolution: its
+     * pixels are larger or smaller, not its field of view.
+     */
+    pub fn resized(&self, resolution_x: u32, resolution_y: u32) -> Camera {
+        let mut transformation = self.transformation.clone();
+        let scale_x = self.resolution_x as Float / resolution_x as Float;
+        let scale_y = self.resolution_y as Float / resolution_y as Float;
+        for i in 0..3 {
+            transformation[[i, 0]] *= scale_x;
+            transformation[[i, 1]] *= scale_y;
+        }
+        Camera {
+            resolution_x,
+            resolution_y,
+            origin: self.origin.clone(),
+            transformation,
+            camera_orientation: self.camera_orientation.clone(),
+            lens_radius: self.lens_radius,
+            shutter: self.shutter,
+        }
+    }
+
+    /**
+     * Depth (see `view_depth`) of the focus plane, where the rays through
+     * every point of the lens meet.
+     */
+    pub fn focus_distance(&self) -> Float {
+        let t = &self.transformation;
+        self.view_depth(&[t[[0, 3]], t[[1, 3]], t[[2, 3]]])
+    }
+
+    /**
+     * Radius, in pixels, of the circle of confusion of the points at
+     * `depth` in front of the camera: the disk the lens blurs them into,
+     * 0 at the focus distance.
+     */
+    pub fn blur_radius(&self, depth: Float) -> Float {
+        let t = &self.transformation;
+        let pixel = (0..3).map(|i| t[[i, 1]] * t[[i, 1]]).sum::<Float>().sqrt();
+        let focus = self.focus_distance();
+        self.lens_radius * (depth - focus).abs() / depth / pixel
+    }
+
+    /**
+     * Width in the world of a pixel, at `depth` in front of the camera.
+     */
+    pub fn pixel_size(&self, depth: Float) -> Float {
+        let t = &self.transformation;
+        let pixel = (0..3).map(|i| t[[i, 1]] * t[[i, 1]]).sum::<Float>().sqrt();
+        pixel * depth / self.focus_distance()
+    }
+
+    /**
+     * Pixel coordinates (x, y) where a world point in front of the
+     * camera is seen, the inverse of `get_ray` through the center of the
+     * lens. None for points that are not in front of the camera.
+     */
+    pub fn project(&self, point: &[Float; 3]) -> Option<(Float, Float)> {
+        let depth = self.view_depth(point);
+        if depth <= 0.0 {
+            return None;
+        }
+
+        // Intersect the ray to the point with the image plane, spanned
+        // by the pixel axes (columns 0 and 1 of the transformation)
+        // from the image origin (column 3).
+        let t = &self.transformation;
+        let corner = [t[[0, 3]], t[[1, 3]], t[[2, 3]]];
+        let plane_depth = self.view_depth(&corner);
+        let scale = plane_depth / depth;
+        let mut offset = [0.0; 3];
+        for i in 0..3 {
+            offset[i] = self.origin[i] + (point[i] - self.origin[i]) * scale
+                - corner[i];
+        }
+
+        let axis = |column: usize| -> Float {
+            let dot: Float = (0..3).map(|i| offset[i] * t[[i, column]]).sum();
+            let norm: Float =
+                (0..3).map(|i| t[[i, column]] * t[[i, column]]).sum();
+            dot / norm
+        };
+        Some((axis(0), axis(1)))
+    }
+
+    /**
+     * Ray through the pixel coordinates (x, y) from a random point of the
+     * lens, with the differentials of the rays through (x + 1, y) and
+     * (x, y + 1) from the same point.
+     */
+    pub fn get_ray(&self, x: Float, y: Float) -> Ray {
+        let mut rd = self
+            .camera_orientation
+            .dot(&(self.lens_radius * random_in_unit_disk()));
+        // Artificially set w to 0 (as the offset will be added).
+        rd[3] = 0.0;
+        let origin = self.origin.clone() + rd.clone();
+
+        Ray {
+            direction: self.direction_through(x, y, &origin),
+            differentials: Some(RayDifferentials {
+                x_origin: origin.clone(),
+                x_direction: self.direction_through(x + 1.0, y, &origin),
+                y_origin: origin.clone(),
+                y_direction: self.direction_through(x, y + 1.0, &origin),
+            }),
+            origin,
+            time: self.sample_time(),
+        }
+    }
+
+    /**
+     * A random time within the shutter span.
+     */
+    pub fn sample_time(&self) -> Float {
+        let [open, close] = self.shutter;
+        if close > open {
+            random::gen_range(open, close)
+        } else {
+            open
+        }
+    }
+
+    /**
+     * Ray through the center of the lens, sharp whatever the depth of
+     * field.
+     */
+    pub fn get_center_ray(&self, x: Float, y: Float) -> Ray {
+        Ray::new(
+            self.origin.clone(),
+            self.direction_through(x, y, &self.origin),
+        )
+        .at_time(self.sample_time())
+    }
+
+    /**
+     * Unit direction from a point of the lens to the pixel coordinates
+     * (x, y) on the focus plane.
+     */
+    fn direction_through(
+        &self,
+        x: Float,
+        y: Float,
+        origin: &Array1<Float>,
+    ) -> Array1<Float> {
+        let point_pixels = arr1(&[x, y, 0.0, 1.0]);
+        let point_world = self.get_transformation().dot(&point_pixels);
+        Vec4::normalize(point_world - origin.clone())
+    }
+
+    pub(crate) fn get_transformation(&self) -> Array2<Float> {
+        self.transformation.clone()
+    }
+}
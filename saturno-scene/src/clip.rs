@@ -0,0 +1,257 @@
+use crate::actor::Aabb;
+use crate::actor::Hit;
+use crate::actor::Hittable;
+use crate::actor::RayTraceable;
+use crate::actor::Visibility;
+use crate::camera::Camera;
+use crate::light::orthonormal_basis;
+use crate::material::Scattering;
+use crate::texture::TextureSampler;
+use ndarray::arr1;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Clipping planes, for cutaway illustrations: an actor wrapped in
+// `Clipped` loses what is in front of the planes, and what lies behind
+// them shows through the cut.
+//
+// The planes keep a convex region, which a ray crosses over one span of
+// its parameter: the actor is only hit within it. A closed actor can also
+// be capped, filling the cut with a material (or that of the actor where
+// the ray leaves it, the inside of a solid): where the ray enters the
+// region through a plane, the plane is hit if the point there is inside
+// the actor, which the first hit of the actor beyond it tells (its normal,
+// pointing outwards, faces away from the ray). Nested actors (the parts
+// of an assembly, the organs of a body) cut by the same plane would cap at
+// the same distance: the caps are pushed back by a hair, more for larger
+// actors, so that the cut shows the inner ones.
+
+/**
+ * A plane through `point`, cutting away the half space its `normal`
+ * points to.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClipPlane {
+    pub point: [Float; 3],
+    pub normal: [Float; 3],
+}
+
+impl ClipPlane {
+    fn unit_normal(&self) -> [Float; 3] {
+        let n = self.normal;
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        n.map(|c| c / length)
+    }
+
+    /**
+     * Whether the plane cuts `point` away.
+     */
+    pub fn clips(&self, point: &[Float; 3]) -> bool {
+        (0..3)
+            .map(|i| (point[i] - self.point[i]) * self.normal[i])
+            .sum::<Float>()
+            > 0.0
+    }
+}
+
+/**
+ * What fills the cuts of a closed actor.
+ */
+#[derive(Clone)]
+pub enum Cap {
+    Material(Arc<dyn Scattering>),
+    /**
+     * The material of the actor inside (where the ray leaves it), as if
+     * the actor were a solid of it.
+     */
+    Actor,
+}
+
+/**
+ * `actor` cut by the `planes`, the cuts filled by the `cap` if any (for
+ * closed actors, whose normals point outwards).
+ */
+pub struct Clipped {
+    pub actor: Box<dyn RayTraceable>,
+    pub planes: Vec<ClipPlane>,
+    pub cap: Option<Cap>,
+}
+
+impl Clipped {
+    pub fn new(
+        actor: Box<dyn RayTraceable>,
+        planes: Vec<ClipPlane>,
+    ) -> Clipped {
+        Clipped {
+            actor,
+            planes,
+            cap: None,
+        }
+    }
+
+    /**
+     * Span of the ray parameter kept by the planes, and the plane the ray
+     * enters it through (None if it starts in it), None if the ray misses
+     * it.
+     */
+    fn span(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<(Float, Float, Option<usize>)> {
+        let (mut start, mut end, mut entry) = (t_min, t_max, None);
+        for (index, plane) in self.planes.iter().enumerate() {
+            let rise = (0..3)
+                .map(|i| ray.direction[i] * plane.normal[i])
+                .sum::<Float>();
+            let height = (0..3)
+                .map(|i| (ray.origin[i] - plane.point[i]) * plane.normal[i])
+                .sum::<Float>();
+            if rise == 0.0 {
+                if height > 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let t = -height / rise;
+            if rise < 0.0 {
+                // Going in, from the side cut away.
+                if t > start {
+                    start = t;
+                    entry = Some(index);
+                }
+            } else {
+                end = end.min(t);
+            }
+        }
+        (start < end).then_some((start, end, entry))
+    }
+
+    /**
+     * How far behind the cut along `ray` the cap is: a millionth of the
+     * diagonal of the actor.
+     */
+    fn cap_offset(&self, ray: &Ray) -> Float {
+        let diagonal = match self.actor.bounding_box() {
+            Some(bounds) => (0..3)
+                .map(|i| (bounds.max[i] - bounds.min[i]).powi(2))
+                .sum::<Float>()
+                .sqrt(),
+            None => return 0.0,
+        };
+        let speed = (0..3)
+            .map(|i| ray.direction[i] * ray.direction[i])
+            .sum::<Float>()
+            .sqrt();
+        1e-6 * diagonal / speed
+    }
+}
+
+impl Hittable for Clipped {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let (start, end, entry) = match self.span(ray, t_min, t_max) {
+            Some(span) => span,
+            None => return false,
+        };
+
+        if let (Some(cap), Some(index)) = (&self.cap, entry) {
+            let mut beyond = Hit::new();
+            let inside =
+                self.actor.is_hit(ray, start, Float::INFINITY, &mut beyond)
+                    && (0..3)
+                        .map(|i| beyond.normal[i] * ray.direction[i])
+                        .sum::<Float>()
+                        > 0.0;
+            if inside {
+                let plane = &self.planes[index];
+                let n = plane.unit_normal();
+                let (u, v) = orthonormal_basis(&n);
+                let t = start + self.cap_offset(ray);
+                record.t = t;
+                record.point = ray.point_at_parameter(t);
+                let offset =
+                    [0, 1, 2].map(|i| record.point[i] - plane.point[i]);
+                record.normal = arr1(&[n[0], n[1], n[2], 0.0]);
+                record.tangent = arr1(&[u[0], u[1], u[2], 0.0]);
+                record.uv = [
+                    (0..3).map(|i| offset[i] * u[i]).sum(),
+                    (0..3).map(|i| offset[i] * v[i]).sum(),
+                ];
+                record.footprint = 0.0;
+                record.differentials = None;
+                record.material = match cap {
+                    Cap::Material(material) => material.clone(),
+                    Cap::Actor => beyond.material.clone(),
+                };
+                if record.alpha_test() {
+                    return true;
+                }
+            }
+        }
+        self.actor.is_hit(ray, start, end, record)
+    }
+}
+
+impl RayTraceable for Clipped {
+    fn validate(&self) -> Result<(), String> {
+        for plane in &self.planes {
+            let n = plane.normal;
+            let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+            if !(length > 0.0 && length.is_finite()) {
+                return Err(format!("invalid clip plane normal {:?}", n));
+            }
+            if !plane.point.iter().all(|c| c.is_finite()) {
+                return Err("clip plane point is not finite".to_string());
+            }
+        }
+        self.actor.validate()
+    }
+
+    /**
+     * That of the actor: the cut would only shrink it.
+     */
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.actor.bounding_box()
+    }
+
+    fn kind(&self) -> &'static str {
+        self.actor.kind()
+    }
+
+    fn triangles(&self) -> usize {
+        self.actor.triangles()
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.actor.textures(textures);
+        if let Some(Cap::Material(material)) = &self.cap {
+            material.textures(textures);
+        }
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.actor.visibility()
+    }
+
+    fn select_detail(&mut self, camera: &Camera) {
+        self.actor.select_detail(camera);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        self.actor.translate(offset);
+        for plane in &mut self.planes {
+            plane.point = [0, 1, 2].map(|i| plane.point[i] + offset[i]);
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use saturno_math::common::Float;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Colors of the light: the CIE 1931 observer, and the light of blackbodies
+// by their temperature in kelvins (the color temperature of lamps, "6500K
+// daylight" or "1800K candle", and of stars and fire). The spectrum of a
+// blackbody (Planck's law) is integrated against the color matching
+// functions, then taken relative to the white point (6504 K, about D65) so
+// that daylight is white, and scaled to a luminance of 1.
+//
+// The color spaces of textures and renders (see `ColorSpace`): sRGB, as
+// encoded in 8 bit images or linear, and the linear ACES spaces of the
+// film pipelines, ACEScg (the AP1 primaries) to render in and ACES2065-1
+// (AP0) to exchange. The linear spaces convert through ACEScg, the sRGB
+// primaries adapted from the D65 white point to the D60 of ACES (Bradford).
+//
+// White balance adapts the colors seen under a light to those seen under
+// the white point, as the eye does: a von Kries scaling of the cone
+// responses of the Bradford transform, from the white of the light (a
+// blackbody at its temperature, tinted) to the white of sRGB.
+
+/**
+ * Temperature of the white point, in kelvins.
+ */
+pub const WHITE_TEMPERATURE: Float = 6504.0;
+
+/**
+ * Range of the wavelengths (nanometers) integrated over, and the step.
+ */
+const LAMBDA_MIN: Float = 360.0;
+const LAMBDA_MAX: Float = 830.0;
+const LAMBDA_STEP: Float = 5.0;
+
+/**
+ * Analytic fit of the CIE 1931 color matching functions (Wyman, Sloan and
+ * Shirley, "Simple Analytic Approximations to the CIE XYZ Color Matching
+ * Functions", 2013).
+ */
+pub fn cie_xyz(lambda: Float) -> [Float; 3] {
+    let g = |mu: Float, sigma_low: Float, sigma_high: Float| {
+        let sigma = if lambda < mu { sigma_low } else { sigma_high };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    [
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7)
+            - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    ]
+}
+
+pub fn xyz_to_srgb(xyz: [Float; 3]) -> [Float; 3] {
+    [
+        3.2406 * xyz[0] - 1.5372 * xyz[1] - 0.4986 * xyz[2],
+        -0.9689 * xyz[0] + 1.8758 * xyz[1] + 0.0415 * xyz[2],
+        0.0557 * xyz[0] - 0.2040 * xyz[1] + 1.0570 * xyz[2],
+    ]
+}
+
+pub fn srgb_to_xyz(rgb: [Float; 3]) -> [Float; 3] {
+    multiply(&SRGB_TO_XYZ, rgb)
+}
+
+fn luminance(rgb: &[Float; 3]) -> Float {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
+
+/**
+ * Spectral radiance of a blackbody at `temperature` kelvins (Planck's
+ * law), at `wavelength` nanometers, in W / (sr m^2 nm).
+ */
+pub fn planck(wavelength: Float, temperature: Float) -> Float {
+    // 2 h c^2 and h c / k.
+    const RADIANCE: Float = 1.191_043e-16;
+    const SECOND: Float = 0.014_387_769;
+    let meters = wavelength * 1e-9;
+    RADIANCE / (meters.powi(5) * ((SECOND / (meters * temperature)).exp_m1()))
+        * 1e-9
+}
+
+/**
+ * The integral of `spectrum` (of the wavelength in nanometers) against
+ * the color matching functions.
+ */
+fn integrate_xyz<F: Fn(Float) -> Float>(spectrum: F) -> [Float; 3] {
+    let mut xyz = [0.0; 3];
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        let value = spectrum(lambda) * LAMBDA_STEP;
+        let matching = cie_xyz(lambda);
+        for c in 0..3 {
+            xyz[c] += value * matching[c];
+        }
+        lambda += LAMBDA_STEP;
+    }
+    xyz
+}
+
+/**
+ * Linear sRGB of a blackbody at `temperature` kelvins, of luminance 1,
+ * white at `WHITE_TEMPERATURE`. Colors out of the gamut (below about
+ * 1900 K, with no blue) are clipped.
+ */
+pub fn blackbody_color(temperature: Float) -> [Float; 3] {
+    let rgb = |temperature: Float| {
+        xyz_to_srgb(integrate_xyz(|lambda| planck(lambda, temperature)))
+    };
+    let (color, white) = (rgb(temperature), rgb(WHITE_TEMPERATURE));
+    let color = [0, 1, 2].map(|c| (color[c] / white[c]).max(0.0));
+    let luminance = luminance(&color);
+    if luminance > 0.0 && luminance.is_finite() {
+        color.map(|c| c / luminance)
+    } else {
+        [0.0; 3]
+    }
+}
+
+/**
+ * Spectrum of a blackbody at `temperature` kelvins, at `wavelength`
+ * nanometers, scaled to the luminance of a constant spectrum of 1 (the
+ * white of the spectral renders).
+ */
+pub fn blackbody_spectrum(temperature: Float, wavelength: Float) -> Float {
+    let white = integrate_xyz(|_| 1.0)[1];
+    let luminance = integrate_xyz(|lambda| planck(lambda, temperature))[1];
+    if luminance > 0.0 {
+        planck(wavelength, temperature) * white / luminance
+    } else {
+        0.0
+    }
+}
+
+/**
+ * A color temperature written as "6500K" (or "6500 K", "6500k"), in
+ * kelvins. None if the text is not a positive temperature.
+ */
+pub fn parse_temperature(text: &str) -> Option<Float> {
+    let number = text.trim().strip_suffix(['K', 'k'])?;
+    let temperature: Float = number.trim_end().parse().ok()?;
+    (temperature > 0.0 && temperature.is_finite()).then_some(temperature)
+}
+
+/**
+ * A color space of RGB values.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ColorSpace {
+    /**
+     * sRGB primaries with the sRGB transfer curve, as 8 bit images.
+     */
+    Srgb,
+    /**
+     * sRGB (Rec. 709) primaries, linear.
+     */
+    #[default]
+    LinearSrgb,
+    /**
+     * ACES AP1 primaries, linear: the working space of ACES.
+     */
+    #[cfg_attr(feature = "serde", serde(rename = "acescg"))]
+    AcesCg,
+    /**
+     * ACES AP0 primaries, linear: the interchange space of ACES.
+     */
+    Aces2065,
+}
+
+/**
+ * A 3 by 3 matrix transforming colors, by rows.
+ */
+pub type Matrix = [[Float; 3]; 3];
+
+const SRGB_TO_ACESCG: Matrix = [
+    [0.613_097_4, 0.339_523_1, 0.047_379_5],
+    [0.070_193_7, 0.916_353_9, 0.013_452_5],
+    [0.020_615_6, 0.109_569_8, 0.869_814_7],
+];
+
+const ACESCG_TO_SRGB: Matrix = [
+    [1.704_858_6, -0.621_716, -0.083_142_6],
+    [-0.130_076_8, 1.140_735_7, -0.010_658_9],
+    [-0.023_964_0, -0.128_975_5, 1.152_939_5],
+];
+
+const ACES2065_TO_ACESCG: Matrix = [
+    [1.451_439_3, -0.236_510_75, -0.214_928_57],
+    [-0.076_553_77, 1.176_229_7, -0.099_675_92],
+    [0.008_316_148, -0.006_032_45, 0.997_716_3],
+];
+
+const ACESCG_TO_ACES2065: Matrix = [
+    [0.695_452_2, 0.140_678_7, 0.163_869_07],
+    [0.044_794_563, 0.859_671_1, 0.095_534_32],
+    [-0.005_525_883, 0.004_025_21, 1.001_500_7],
+];
+
+const SRGB_TO_XYZ: Matrix = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175_0],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+
+const XYZ_TO_SRGB: Matrix = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266, 1.876_010_8, 0.041_556_0],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+
+const XYZ_TO_BRADFORD: Matrix = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const BRADFORD_TO_XYZ: Matrix = [
+    [0.986_992_9, -0.147_054_3, 0.159_962_7],
+    [0.432_305_3, 0.518_360_3, 0.049_291_2],
+    [-0.008_528_7, 0.040_042_8, 0.968_486_7],
+];
+
+/**
+ * The color `rgb` transformed by `matrix`.
+ */
+pub fn multiply(matrix: &Matrix, rgb: [Float; 3]) -> [Float; 3] {
+    matrix.map(|row| row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2])
+}
+
+fn product(a: &Matrix, b: &Matrix) -> Matrix {
+    [0, 1, 2]
+        .map(|i| [0, 1, 2].map(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+/**
+ * Linear value of an sRGB encoded channel.
+ */
+pub fn srgb_to_linear(value: Float) -> Float {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/**
+ * sRGB encoding of a linear channel.
+ */
+pub fn linear_to_srgb(value: Float) -> Float {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+impl ColorSpace {
+    /**
+     * The values of `rgb` in ACEScg.
+     */
+    fn to_acescg(self, rgb: [Float; 3]) -> [Float; 3] {
+        match self {
+            ColorSpace::Srgb => {
+                multiply(&SRGB_TO_ACESCG, rgb.map(srgb_to_linear))
+            }
+            ColorSpace::LinearSrgb => multiply(&SRGB_TO_ACESCG, rgb),
+            ColorSpace::AcesCg => rgb,
+            ColorSpace::Aces2065 => multiply(&ACES2065_TO_ACESCG, rgb),
+        }
+    }
+
+    /**
+     * The values in this space of the ACEScg `rgb`.
+     */
+    fn out_of_acescg(self, rgb: [Float; 3]) -> [Float; 3] {
+        match self {
+            ColorSpace::Srgb => {
+                multiply(&ACESCG_TO_SRGB, rgb).map(linear_to_srgb)
+            }
+            ColorSpace::LinearSrgb => multiply(&ACESCG_TO_SRGB, rgb),
+            ColorSpace::AcesCg => rgb,
+            ColorSpace::Aces2065 => multiply(&ACESCG_TO_ACES2065, rgb),
+        }
+    }
+
+    /**
+     * The values in the space `to` of the values `rgb` in this space.
+     */
+    pub fn convert(self, rgb: [Float; 3], to: ColorSpace) -> [Float; 3] {
+        if self == to {
+            rgb
+        } else if self == ColorSpace::Srgb && to == ColorSpace::LinearSrgb {
+            rgb.map(srgb_to_linear)
+        } else if self == ColorSpace::LinearSrgb && to == ColorSpace::Srgb {
+            rgb.map(linear_to_srgb)
+        } else {
+            to.out_of_acescg(self.to_acescg(rgb))
+        }
+    }
+}
+
+/**
+ * Chromatic adaptation (Bradford) of the linear sRGB colors seen under
+ * the white `source` to those seen under the white `target` (in XYZ).
+ */
+pub fn adaptation(source: [Float; 3], target: [Float; 3]) -> Matrix {
+    let (source, target) = (
+        multiply(&XYZ_TO_BRADFORD, source),
+        multiply(&XYZ_TO_BRADFORD, target),
+    );
+    let mut scale = [[0.0; 3]; 3];
+    for i in 0..3 {
+        scale[i][i] = target[i] / source[i];
+    }
+    let xyz = product(&BRADFORD_TO_XYZ, &product(&scale, &XYZ_TO_BRADFORD));
+    product(&XYZ_TO_SRGB, &product(&xyz, &SRGB_TO_XYZ))
+}
+
+/**
+ * White balance of linear sRGB colors for a light of `temperature`
+ * kelvins, `tint` green (or magenta, if negative) as a fraction of its
+ * green: the light turns white, and `WHITE_TEMPERATURE` with no tint
+ * changes nothing.
+ */
+pub fn white_balance(temperature: Float, tint: Float) -> Matrix {
+    let [r, g, b] = blackbody_color(temperature);
+    let light = srgb_to_xyz([r, g * (1.0 + tint), b]);
+    adaptation(light, srgb_to_xyz([1.0; 3]))
+}
@@ -0,0 +1,1816 @@
+use crate::actor::RayTraceable;
+use crate::actor::Sphere;
+use crate::actor::Visibility;
+use crate::atmosphere::Atmosphere;
+use crate::black_hole::AccretionDisk;
+use crate::black_hole::BlackHole;
+use crate::camera::Camera;
+use crate::clip::Cap;
+use crate::clip::ClipPlane;
+use crate::clip::Clipped;
+#[cfg(feature = "serde")]
+use crate::color::blackbody_color;
+#[cfg(feature = "serde")]
+use crate::color::parse_temperature;
+use crate::fog::Fog;
+use crate::fractal::Fractal;
+use crate::fractal::FractalKind;
+use crate::fractal::Palette;
+use crate::instance::Instance;
+use crate::instance::Instances;
+use crate::instance::Prototype;
+use crate::instance::Switch;
+use crate::instance::Transform;
+use crate::library::MaterialLibrary;
+use crate::light::Light;
+use crate::light::Omni;
+use crate::light::Spot;
+use crate::light::Sun;
+#[cfg(feature = "serde")]
+use crate::light::MAX_EFFICACY;
+use crate::material::AccretionGlow;
+use crate::material::AnisotropicMetal;
+use crate::material::Clearcoat;
+use crate::material::Cutout;
+use crate::material::Dielectric;
+use crate::material::DiffuseLight;
+use crate::material::Dispersion;
+use crate::material::Lambertian;
+use crate::material::Mask;
+use crate::material::MaskTexture;
+use crate::material::Metal;
+use crate::material::Mix;
+use crate::material::Photosphere;
+use crate::material::RingParticles;
+use crate::material::Scattering;
+use crate::material::Shading;
+use crate::material::ShadowCatcher;
+use crate::material::Subsurface;
+use crate::material::ThinFilm;
+use crate::mesh::Mesh;
+use crate::metaballs::Charge;
+use crate::metaballs::Metaballs;
+use crate::motion::MotionKey;
+use crate::motion::Moving;
+use crate::ring::Ring;
+use crate::scatter::ScatterRegion;
+use crate::scatter::Scatterer;
+use crate::sky::SunAndSky;
+use crate::stars::StarField;
+use crate::texture::TextureSampler;
+use crate::units::Units;
+use crate::volume::EmissiveVolume;
+use ndarray::{arr1, Array1};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Vec4;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::instrument;
+
+// Plain data descriptions of scenes, to build them from outside Rust (e.g.
+// JSON). Colors are RGB in [0, 1], points and directions are XYZ. The
+// colors of the lights may be color temperatures instead ("6500K").
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum MaterialDescription {
+    Lambertian {
+        albedo: [Float; 3],
+    },
+    Metal {
+        color: [Float; 3],
+        #[cfg_attr(feature = "serde", serde(default))]
+        fuzz: Float,
+    },
+    /**
+     * GGX roughness `alpha_x` along the surface tangent and `alpha_y`
+     * across it, the tangent turned by `rotation` half turns: a number or
+     * a mask.
+     */
+    AnisotropicMetal {
+        color: [Float; 3],
+        alpha_x: Float,
+        alpha_y: Float,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default = "no_rotation", deserialize_with = "value_or_mask")
+        )]
+        rotation: Mask,
+    },
+    Dielectric {
+        refraction_idx: Float,
+        /**
+         * Wavelength dependent index, used instead of `refraction_idx`
+         * in spectral mode.
+         */
+        #[cfg_attr(feature = "serde", serde(default))]
+        dispersion: Option<Dispersion>,
+    },
+    DiffuseLight {
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "color"))]
+        color: [Float; 3],
+        #[cfg_attr(feature = "serde", serde(default = "one"))]
+        intensity: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        light_group: Option<String>,
+    },
+    ShadowCatcher {
+        #[cfg_attr(feature = "serde", serde(default = "one"))]
+        strength: Float,
+    },
+    Subsurface {
+        albedo: [Float; 3],
+        mean_free_path: Float,
+        #[cfg_attr(feature = "serde", serde(default = "glass"))]
+        refraction_idx: Float,
+    },
+    /**
+     * A clear coat over `base`, see `Clearcoat`.
+     */
+    Clearcoat {
+        base: Box<MaterialDescription>,
+        #[cfg_attr(feature = "serde", serde(default = "glass"))]
+        refraction_idx: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        roughness: Float,
+        #[cfg_attr(feature = "serde", serde(default = "white"))]
+        tint: [Float; 3],
+    },
+    /**
+     * A thin film coating `base`, `thickness` in nanometers.
+     */
+    ThinFilm {
+        base: Box<MaterialDescription>,
+        thickness: Float,
+        refraction_idx: Float,
+        #[cfg_attr(feature = "serde", serde(default = "glass"))]
+        substrate_idx: Float,
+    },
+    /**
+     * `base` with holes where `mask` is transparent, see `Cutout`.
+     */
+    Cutout {
+        base: Box<MaterialDescription>,
+        mask: Mask,
+    },
+    /**
+     * `first` and `second` blended by `factor` (0 is all `first`), see
+     * `Mix`.
+     */
+    Mix {
+        first: Box<MaterialDescription>,
+        second: Box<MaterialDescription>,
+        factor: Mask,
+    },
+    /**
+     * Particles of planetary rings, see `RingParticles`.
+     */
+    RingParticles {
+        albedo: [Float; 3],
+        #[cfg_attr(feature = "serde", serde(default = "ring_forward"))]
+        forward: Float,
+        #[cfg_attr(feature = "serde", serde(default = "ring_backward"))]
+        backward: Float,
+        #[cfg_attr(feature = "serde", serde(default = "ring_forward_weight"))]
+        forward_weight: Float,
+    },
+    /**
+     * Glowing gas of an accretion disk, see `AccretionGlow`.
+     */
+    AccretionGlow {
+        #[cfg_attr(feature = "serde", serde(deserialize_with = "color"))]
+        color: [Float; 3],
+        luminance: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        light_group: Option<String>,
+    },
+    /**
+     * Glowing surface of a star of surface `temperature` (kelvin) and mean
+     * `luminance` (nits), see `Photosphere`. The Sun's limb darkening by
+     * default. A `sampled` star is also lit by a light of the scene (an
+     * `omni` of pi radius^2 luminance candela at its center).
+     */
+    Photosphere {
+        #[cfg_attr(feature = "serde", serde(default = "solar_temperature"))]
+        temperature: Float,
+        luminance: Float,
+        #[cfg_attr(feature = "serde", serde(default = "limb_darkening"))]
+        limb_darkening: [Float; 2],
+        #[cfg_attr(feature = "serde", serde(default))]
+        granulation: Float,
+        #[cfg_attr(feature = "serde", serde(default = "one"))]
+        granule_size: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        sampled: bool,
+        #[cfg_attr(feature = "serde", serde(default))]
+        light_group: Option<String>,
+    },
+    /**
+     * The material of the scene called `name` (see
+     * `SceneDescription::materials`), shared by every actor naming it.
+     */
+    Named {
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SphereDescription {
+    pub center: [Float; 3],
+    pub radius: Float,
+    pub material: MaterialDescription,
+    /**
+     * The rays that see the sphere, all by default.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub visibility: Visibility,
+}
+
+/**
+ * A mesh read from an OBJ or STL file, its vertices in `units` (those
+ * usual for the format if not given: millimeters for STL, meters for OBJ)
+ * converted to the units of the scene. Building the scene leaves the
+ * meshes to the readers of the files (`saturno_io::mesh`).
+ *
+ * A deforming mesh lists the files of the same mesh (its triangles in the
+ * same order) at later times of the shutter interval in `deformation`,
+ * the last when it closes: see `Mesh::with_deformation`. Large meshes
+ * may be `compressed`: see `Mesh::compressed`. A `displacement` moves the
+ * vertices by a height map once the mesh is read.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MeshDescription {
+    pub path: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub units: Option<Units>,
+    pub material: MaterialDescription,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub visibility: Visibility,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deformation: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub compressed: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub displacement: Option<DisplacementDescription>,
+}
+
+/**
+ * The height map at `path` displacing a mesh along its normals by `scale`
+ * (in the units of the scene) times its gray, the mesh subdivided
+ * `subdivisions` times first: see `Mesh::displace`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DisplacementDescription {
+    pub path: String,
+    pub scale: Float,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub subdivisions: u32,
+}
+
+/**
+ * The polygon meshes (and subdivision surfaces, as their polygons) of an
+ * Alembic cache, placed by its transforms at the time of the `frame` of
+ * the scene, their positions in `units` (meters if not given). The caches
+ * are read with the meshes (`saturno_io::alembic`), sampled `samples`
+ * times over the shutter of the scene for their motion blur.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AlembicDescription {
+    pub path: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub units: Option<Units>,
+    pub material: MaterialDescription,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub visibility: Visibility,
+    #[cfg_attr(feature = "serde", serde(default = "motion_samples"))]
+    pub samples: usize,
+}
+
+/**
+ * The parts shared by the instances of a prototype, see `Instances`: its
+ * meshes are read as those of the scene. The parts of a named material
+ * (see `SceneDescription::materials`) may take another in each instance.
+ * Coarser `lods` (levels of detail) may replace the parts for the
+ * instances far from the camera or small on the screen.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PrototypeDescription {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spheres: Vec<SphereDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub meshes: Vec<MeshDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lods: Vec<LodDescription>,
+}
+
+/**
+ * A coarser level of detail of a prototype, from the finest, taking over
+ * from the previous one beyond a `distance` from the camera, or for the
+ * instances fewer `pixels` across on the screen (one of them).
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LodDescription {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub distance: Option<Float>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub pixels: Option<Float>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spheres: Vec<SphereDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub meshes: Vec<MeshDescription>,
+}
+
+fn build_parts(
+    spheres: &[SphereDescription],
+    meshes: &[MeshDescription],
+    library: &MaterialLibrary,
+    files: &mut dyn SceneFiles,
+) -> Result<Prototype, String> {
+    let mut parts = vec![];
+    for sphere in spheres {
+        parts.push(Box::new(Sphere {
+            center: point(&sphere.center),
+            radius: sphere.radius,
+            material: sphere.material.build(library)?,
+        }) as Box<dyn RayTraceable>);
+    }
+    for mesh in meshes {
+        let material = mesh.material.build(library)?;
+        parts.push(Box::new(files.mesh(mesh, material)?));
+    }
+    if parts.is_empty() {
+        return Err("prototype without parts".to_string());
+    }
+    Ok(Prototype::new(parts))
+}
+
+impl PrototypeDescription {
+    /**
+     * Build the parts, the meshes read by `files`.
+     */
+    pub fn build(
+        &self,
+        library: &MaterialLibrary,
+        files: &mut dyn SceneFiles,
+    ) -> Result<Prototype, String> {
+        build_parts(&self.spheres, &self.meshes, library, files)
+    }
+
+    /**
+     * Build the coarser levels of detail, see `Instances::with_levels`.
+     */
+    pub fn build_levels(
+        &self,
+        library: &MaterialLibrary,
+        files: &mut dyn SceneFiles,
+    ) -> Result<Vec<(Switch, Arc<Prototype>)>, String> {
+        let mut levels = vec![];
+        for lod in &self.lods {
+            let switch = match (lod.distance, lod.pixels) {
+                (Some(distance), None) => Switch::Distance(distance),
+                (None, Some(pixels)) => Switch::Pixels(pixels),
+                _ => {
+                    return Err("level of detail needs a distance or pixels"
+                        .to_string())
+                }
+            };
+            let parts = build_parts(&lod.spheres, &lod.meshes, library, files)?;
+            levels.push((switch, Arc::new(parts)));
+        }
+        Ok(levels)
+    }
+}
+
+/**
+ * A copy of the prototype called `prototype`, scaled, rotated (degrees
+ * about x, then y, then z) and moved by `translation`. The named
+ * `materials` of the prototype are replaced by those given for them.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InstanceDescription {
+    pub prototype: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub translation: [Float; 3],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rotation: [Float; 3],
+    #[cfg_attr(feature = "serde", serde(default = "unit_scale"))]
+    pub scale: [Float; 3],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub visibility: Visibility,
+    /**
+     * Placements at evenly spaced times of the shutter interval, for the
+     * motion blur of an instance placed by them after the placement above
+     * (see `Moving`).
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub motion: Vec<MotionKey>,
+}
+
+/**
+ * Instances of the `prototypes` (one at random for each) scattered over
+ * `region`, see `Scatterer`. The `density` is the path of an image.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScatterDescription {
+    pub prototypes: Vec<String>,
+    pub region: ScatterRegion,
+    pub count: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub density: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default = "turn_about_y"))]
+    pub rotation: [[Float; 2]; 3],
+    #[cfg_attr(feature = "serde", serde(default = "unit_range"))]
+    pub scale: [Float; 2],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub align: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub seed: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub visibility: Visibility,
+}
+
+/**
+ * Reads the files a scene refers to, for the parts of it built from them
+ * (see `SceneDescription::instances`).
+ */
+pub trait SceneFiles {
+    fn mesh(
+        &mut self,
+        mesh: &MeshDescription,
+        material: Arc<dyn Scattering>,
+    ) -> Result<Mesh, String>;
+
+    /**
+     * The image at `path`.
+     */
+    fn texture(
+        &mut self,
+        path: &str,
+    ) -> Result<Arc<dyn TextureSampler>, String>;
+}
+
+/**
+ * The files of a scene that has none (as one sent over the network):
+ * reading any of them fails.
+ */
+pub struct NoFiles;
+
+impl SceneFiles for NoFiles {
+    fn mesh(
+        &mut self,
+        mesh: &MeshDescription,
+        _material: Arc<dyn Scattering>,
+    ) -> Result<Mesh, String> {
+        Err(format!("cannot read the mesh {}", mesh.path))
+    }
+
+    fn texture(
+        &mut self,
+        path: &str,
+    ) -> Result<Arc<dyn TextureSampler>, String> {
+        Err(format!("cannot read the image {}", path))
+    }
+}
+
+/**
+ * A ring around `center` perpendicular to `normal`, see `Ring`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RingDescription {
+    pub center: [Float; 3],
+    pub normal: [Float; 3],
+    pub inner_radius: Float,
+    pub outer_radius: Float,
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub optical_depth: Float,
+    pub material: MaterialDescription,
+}
+
+/**
+ * The disk of gas around a black hole, see `AccretionDisk`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AccretionDiskDescription {
+    pub center: [Float; 3],
+    pub normal: [Float; 3],
+    pub inner_radius: Float,
+    pub outer_radius: Float,
+    pub material: MaterialDescription,
+}
+
+/**
+ * A fractal of `size` around `center`, see `Fractal`. A `palette` colors
+ * a lambertian material by the orbit traps, from the first color (the
+ * orbits closest to the origin) to the last.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FractalDescription {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub fractal: FractalKind,
+    pub center: [Float; 3],
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub size: Float,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub palette: Vec<[Float; 3]>,
+    pub material: MaterialDescription,
+}
+
+impl FractalDescription {
+    pub fn build(&self, library: &MaterialLibrary) -> Result<Fractal, String> {
+        let material = match (&self.material, self.palette.is_empty()) {
+            (_, true) => self.material.build(library)?,
+            (MaterialDescription::Lambertian { albedo }, false) => {
+                Arc::new(Lambertian::textured(
+                    point(albedo),
+                    Arc::new(Palette::new(self.palette.clone())?),
+                ))
+            }
+            _ => return Err("a palette colors lambertian fractals only".into()),
+        };
+        Ok(Fractal::new(self.fractal, self.center, self.size, material))
+    }
+}
+
+/**
+ * Blobs of the fields of `charges`, see `Metaballs`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MetaballsDescription {
+    pub charges: Vec<Charge>,
+    #[cfg_attr(feature = "serde", serde(default = "half"))]
+    pub threshold: Float,
+    pub material: MaterialDescription,
+}
+
+/**
+ * Planes cutting every actor of the scene, see `Clipped`, the cuts filled
+ * with the `cap` material, or with the material of each actor if
+ * `cap_actors`.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClippingDescription {
+    pub planes: Vec<ClipPlane>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cap: Option<MaterialDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cap_actors: bool,
+}
+
+/**
+ * Lights sampled directly, besides the emissive spheres.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum LightDescription {
+    /**
+     * Direction towards the sun, illuminance in lux and angular diameter
+     * in degrees.
+     */
+    Sun {
+        direction: [Float; 3],
+        illuminance: Float,
+        #[cfg_attr(feature = "serde", serde(default = "sun_diameter"))]
+        angular_diameter: Float,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default = "white", deserialize_with = "color")
+        )]
+        color: [Float; 3],
+        #[cfg_attr(feature = "serde", serde(default))]
+        light_group: Option<String>,
+    },
+    /**
+     * Spot at a position pointing in a direction, intensity in candela (or
+     * `unit`) and cone apertures in degrees. Gobos are only set through
+     * the API.
+     */
+    Spot {
+        position: [Float; 3],
+        direction: [Float; 3],
+        intensity: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        unit: IntensityUnit,
+        #[cfg_attr(feature = "serde", serde(default = "max_efficacy"))]
+        efficacy: Float,
+        #[cfg_attr(feature = "serde", serde(default = "spot_inner_angle"))]
+        inner_angle: Float,
+        #[cfg_attr(feature = "serde", serde(default = "spot_outer_angle"))]
+        outer_angle: Float,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default = "white", deserialize_with = "color")
+        )]
+        color: [Float; 3],
+        #[cfg_attr(feature = "serde", serde(default))]
+        light_group: Option<String>,
+    },
+    /**
+     * Light shining in every direction from a position, intensity in
+     * candela (or `unit`), see `Omni`.
+     */
+    Omni {
+        position: [Float; 3],
+        intensity: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        unit: IntensityUnit,
+        #[cfg_attr(feature = "serde", serde(default = "max_efficacy"))]
+        efficacy: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        radius: Float,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default = "white", deserialize_with = "color")
+        )]
+        color: [Float; 3],
+        #[cfg_attr(feature = "serde", serde(default))]
+        light_group: Option<String>,
+    },
+    /**
+     * The sun and the clear sky at a place (degrees) and local time, see
+     * `SunAndSky`. The sky is baked into a map `resolution` texels around.
+     */
+    SunAndSky {
+        latitude: Float,
+        longitude: Float,
+        year: i32,
+        month: u32,
+        day: u32,
+        hour: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        utc_offset: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        north: Float,
+        #[cfg_attr(feature = "serde", serde(default = "turbidity"))]
+        turbidity: Float,
+        #[cfg_attr(feature = "serde", serde(default = "sky_resolution"))]
+        resolution: usize,
+    },
+    /**
+     * A night sky of `count` made up stars down to `limiting_magnitude`
+     * over a Milky Way of `milky_way` nits, see `StarField`. The sky is
+     * baked into a map `resolution` texels around.
+     */
+    Stars {
+        count: usize,
+        #[cfg_attr(feature = "serde", serde(default = "limiting_magnitude"))]
+        limiting_magnitude: Float,
+        #[cfg_attr(feature = "serde", serde(default))]
+        seed: u64,
+        #[cfg_attr(feature = "serde", serde(default))]
+        milky_way: Float,
+        #[cfg_attr(feature = "serde", serde(default = "stars_resolution"))]
+        resolution: usize,
+    },
+}
+
+/**
+ * An Earth-like atmosphere around a planet of `planet_radius` (the planet
+ * itself is one of the spheres). `density` scales the air and `haze` the
+ * aerosols in it.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AtmosphereDescription {
+    pub center: [Float; 3],
+    pub planet_radius: Float,
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub density: Float,
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub haze: Float,
+}
+
+/**
+ * Camera placement, the resolution comes from the render settings.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CameraDescription {
+    pub look_from: [Float; 3],
+    pub look_at: [Float; 3],
+    #[cfg_attr(feature = "serde", serde(default = "up"))]
+    pub up: [Float; 3],
+    #[cfg_attr(feature = "serde", serde(default = "vertical_fov"))]
+    pub vertical_fov: Float,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub aperture: Float,
+    /**
+     * Span of the shutter interval the rays are cast at, for the motion
+     * blur of the moving actors: [0, 1] for all of it, [0.25, 0.75] for a
+     * shutter half as long. Closed by default, all at its opening.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shutter: [Float; 2],
+}
+
+/**
+ * Unit of the intensity of a spot or an omni light: candela, the flux of
+ * the light in lumens, or the power it draws in watts, each giving
+ * `efficacy` lumens (683 by default, the most there is).
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IntensityUnit {
+    #[default]
+    Candela,
+    Lumens,
+    Watts,
+}
+
+impl IntensityUnit {
+    /**
+     * The luminous flux of `value` in this unit, None for candela.
+     */
+    fn lumens(&self, value: Float, efficacy: Float) -> Option<Float> {
+        match self {
+            IntensityUnit::Candela => None,
+            IntensityUnit::Lumens => Some(value),
+            IntensityUnit::Watts => Some(value * efficacy),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SceneDescription {
+    /**
+     * Units of the positions and sizes of the scene, meters by default.
+     * The meshes are converted to them, and the lights of an intensity
+     * in candela fall off with the distance in meters.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub units: Units,
+    pub camera: CameraDescription,
+    /**
+     * More cameras by name, for renders of every view of the scene in a
+     * batch (see `views`).
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cameras: BTreeMap<String, CameraDescription>,
+    /**
+     * Materials the spheres (and other materials) can refer to by name.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spheres: Vec<SphereDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub meshes: Vec<MeshDescription>,
+    /**
+     * Animated caches, read with the meshes.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub alembic: Vec<AlembicDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rings: Vec<RingDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub lights: Vec<LightDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fog: Option<Fog>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub atmosphere: Option<AtmosphereDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub black_hole: Option<BlackHole>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub accretion_disks: Vec<AccretionDiskDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fractals: Vec<FractalDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metaballs: Vec<MetaballsDescription>,
+    /**
+     * Prototypes the instances copy, by name.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub prototypes: BTreeMap<String, PrototypeDescription>,
+    /**
+     * Built by `instances`, as the meshes (their prototypes may have
+     * some).
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub instances: Vec<InstanceDescription>,
+    /**
+     * Built by `instances` too.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scatters: Vec<ScatterDescription>,
+    /**
+     * Cutaway of the whole scene.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub clipping: Option<ClippingDescription>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub volumes: Vec<EmissiveVolume>,
+    /**
+     * Frame of the animation shown, at `fps` frames per second: the time
+     * the animated caches are read at.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub frame: Float,
+    #[cfg_attr(feature = "serde", serde(default = "fps"))]
+    pub fps: Float,
+    /**
+     * Frames the shutter stays open for from the `frame` (0.5 for a 180
+     * degree shutter): the animated caches move over them for the motion
+     * blur, from the opening of the shutter interval of the cameras to
+     * its closing.
+     */
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shutter: Float,
+}
+
+#[cfg(feature = "serde")]
+fn one() -> Float {
+    1.0
+}
+
+#[cfg(feature = "serde")]
+fn half() -> Float {
+    0.5
+}
+
+#[cfg(feature = "serde")]
+fn glass() -> Float {
+    1.5
+}
+
+#[cfg(feature = "serde")]
+fn white() -> [Float; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[cfg(feature = "serde")]
+fn unit_scale() -> [Float; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+#[cfg(feature = "serde")]
+fn unit_range() -> [Float; 2] {
+    [1.0, 1.0]
+}
+
+#[cfg(feature = "serde")]
+fn turn_about_y() -> [[Float; 2]; 3] {
+    [[0.0, 0.0], [0.0, 360.0], [0.0, 0.0]]
+}
+
+/**
+ * A color given as RGB, or as a color temperature ("1800K"), the color
+ * of a blackbody of luminance 1.
+ */
+#[cfg(feature = "serde")]
+fn color<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<[Float; 3], D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Color {
+        Rgb([Float; 3]),
+        Temperature(String),
+    }
+    match Color::deserialize(deserializer)? {
+        Color::Rgb(rgb) => Ok(rgb),
+        Color::Temperature(text) => parse_temperature(&text)
+            .map(blackbody_color)
+            .ok_or_else(|| {
+                serde::de::Error::custom(format!("invalid color {:?}", text))
+            }),
+    }
+}
+
+/**
+ * A mask, or a number for a uniform one.
+ */
+#[cfg(feature = "serde")]
+fn value_or_mask<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Mask, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ValueOrMask {
+        Value(Float),
+        Mask(Mask),
+    }
+    Ok(match ValueOrMask::deserialize(deserializer)? {
+        ValueOrMask::Value(value) => Mask::Uniform { value },
+        ValueOrMask::Mask(mask) => mask,
+    })
+}
+
+#[cfg(feature = "serde")]
+fn no_rotation() -> Mask {
+    Mask::Uniform { value: 0.0 }
+}
+
+#[cfg(feature = "serde")]
+fn ring_forward() -> Float {
+    0.7
+}
+
+#[cfg(feature = "serde")]
+fn ring_backward() -> Float {
+    -0.4
+}
+
+#[cfg(feature = "serde")]
+fn ring_forward_weight() -> Float {
+    0.3
+}
+
+#[cfg(feature = "serde")]
+fn max_efficacy() -> Float {
+    MAX_EFFICACY
+}
+
+#[cfg(feature = "serde")]
+fn sun_diameter() -> Float {
+    0.53
+}
+
+#[cfg(feature = "serde")]
+fn spot_inner_angle() -> Float {
+    30.0
+}
+
+#[cfg(feature = "serde")]
+fn spot_outer_angle() -> Float {
+    45.0
+}
+
+#[cfg(feature = "serde")]
+fn turbidity() -> Float {
+    3.0
+}
+
+#[cfg(feature = "serde")]
+fn sky_resolution() -> usize {
+    256
+}
+
+#[cfg(feature = "serde")]
+fn solar_temperature() -> Float {
+    5772.0
+}
+
+#[cfg(feature = "serde")]
+fn limb_darkening() -> [Float; 2] {
+    [0.47, 0.23]
+}
+
+#[cfg(feature = "serde")]
+fn limiting_magnitude() -> Float {
+    6.5
+}
+
+#[cfg(feature = "serde")]
+fn stars_resolution() -> usize {
+    4096
+}
+
+#[cfg(feature = "serde")]
+fn motion_samples() -> usize {
+    2
+}
+
+#[cfg(feature = "serde")]
+fn fps() -> Float {
+    24.0
+}
+
+#[cfg(feature = "serde")]
+fn up() -> [Float; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+#[cfg(feature = "serde")]
+fn vertical_fov() -> Float {
+    90.0
+}
+
+fn point(p: &[Float; 3]) -> Array1<Float> {
+    arr1(&[p[0], p[1], p[2], 1.0])
+}
+
+impl MaterialDescription {
+    /**
+     * Build the material, taking the materials it names from `library`.
+     */
+    pub fn build(
+        &self,
+        library: &MaterialLibrary,
+    ) -> Result<Arc<dyn Scattering>, String> {
+        let material: Arc<dyn Scattering> = match self {
+            MaterialDescription::Lambertian { albedo } => {
+                Arc::new(Lambertian::new(point(albedo), Shading::COLOR))
+            }
+            MaterialDescription::Metal { color, fuzz } => {
+                Arc::new(Metal::new(point(color), Shading::COLOR, *fuzz))
+            }
+            MaterialDescription::AnisotropicMetal {
+                color,
+                alpha_x,
+                alpha_y,
+                rotation,
+            } => Arc::new(AnisotropicMetal::new(
+                point(color),
+                *alpha_x,
+                *alpha_y,
+                read_mask(rotation)?,
+            )),
+            MaterialDescription::Dielectric {
+                refraction_idx,
+                dispersion,
+            } => {
+                let mut dielectric = Dielectric::new(
+                    point(&[1.0, 1.0, 1.0]),
+                    Shading::COLOR,
+                    *refraction_idx,
+                );
+                dielectric.dispersion = dispersion.clone();
+                Arc::new(dielectric)
+            }
+            MaterialDescription::DiffuseLight {
+                color,
+                intensity,
+                light_group,
+            } => {
+                let mut light = DiffuseLight::new(point(color), *intensity);
+                light.light_group = light_group.clone();
+                Arc::new(light)
+            }
+            MaterialDescription::ShadowCatcher { strength } => {
+                Arc::new(ShadowCatcher::new(*strength))
+            }
+            MaterialDescription::Subsurface {
+                albedo,
+                mean_free_path,
+                refraction_idx,
+            } => Arc::new(Subsurface::new(
+                point(albedo),
+                *mean_free_path,
+                *refraction_idx,
+            )),
+            MaterialDescription::Clearcoat {
+                base,
+                refraction_idx,
+                roughness,
+                tint,
+            } => {
+                let mut coat = Clearcoat::new(
+                    base.build(library)?,
+                    *refraction_idx,
+                    *roughness,
+                );
+                coat.tint = point(tint);
+                Arc::new(coat)
+            }
+            MaterialDescription::ThinFilm {
+                base,
+                thickness,
+                refraction_idx,
+                substrate_idx,
+            } => Arc::new(ThinFilm::new(
+                base.build(library)?,
+                *thickness,
+                *refraction_idx,
+                *substrate_idx,
+            )),
+            MaterialDescription::Cutout { base, mask } => {
+                Arc::new(Cutout::new(base.build(library)?, read_mask(mask)?))
+            }
+            MaterialDescription::Mix {
+                first,
+                second,
+                factor,
+            } => Arc::new(Mix::new(
+                first.build(library)?,
+                second.build(library)?,
+                read_mask(factor)?,
+            )),
+            MaterialDescription::RingParticles {
+                albedo,
+                forward,
+                backward,
+                forward_weight,
+            } => Arc::new(RingParticles {
+                albedo: point(albedo),
+                forward: *forward,
+                backward: *backward,
+                forward_weight: *forward_weight,
+            }),
+            MaterialDescription::AccretionGlow {
+                color,
+                luminance,
+                light_group,
+            } => {
+                let mut glow = AccretionGlow::new(point(color), *luminance);
+                glow.light_group = light_group.clone();
+                Arc::new(glow)
+            }
+            MaterialDescription::Photosphere {
+                temperature,
+                luminance,
+                limb_darkening,
+                granulation,
+                granule_size,
+                sampled,
+                light_group,
+            } => {
+                let mut star = Photosphere::blackbody(*temperature, *luminance);
+                star.limb_darkening = *limb_darkening;
+                star.granulation = *granulation;
+                star.granule_size = *granule_size;
+                star.sampled = *sampled;
+                star.light_group = light_group.clone();
+                Arc::new(star)
+            }
+            MaterialDescription::Named { name } => {
+                return library
+                    .get(name)
+                    .ok_or_else(|| format!("unknown material {}", name));
+            }
+        };
+        Ok(material)
+    }
+
+    /**
+     * Names of the materials it refers to.
+     */
+    pub fn references(&self) -> Vec<&str> {
+        match self {
+            MaterialDescription::Clearcoat { base, .. }
+            | MaterialDescription::ThinFilm { base, .. }
+            | MaterialDescription::Cutout { base, .. } => base.references(),
+            MaterialDescription::Mix { first, second, .. } => {
+                let mut references = first.references();
+                references.extend(second.references());
+                references
+            }
+            MaterialDescription::Named { name } => vec![name.as_str()],
+            _ => vec![],
+        }
+    }
+
+    /**
+     * The masks of the material and of the materials it is made of.
+     */
+    fn masks_mut(&mut self) -> Vec<&mut Mask> {
+        match self {
+            MaterialDescription::AnisotropicMetal { rotation, .. } => {
+                vec![rotation]
+            }
+            MaterialDescription::Clearcoat { base, .. }
+            | MaterialDescription::ThinFilm { base, .. } => base.masks_mut(),
+            MaterialDescription::Cutout { base, mask } => {
+                let mut masks = base.masks_mut();
+                masks.push(mask);
+                masks
+            }
+            MaterialDescription::Mix {
+                first,
+                second,
+                factor,
+            } => {
+                let mut masks = first.masks_mut();
+                masks.extend(second.masks_mut());
+                masks.push(factor);
+                masks
+            }
+            _ => vec![],
+        }
+    }
+}
+
+/**
+ * `mask`, unless it is a texture whose image was not read.
+ */
+fn read_mask(mask: &Mask) -> Result<Mask, String> {
+    match mask {
+        Mask::Texture {
+            path,
+            texture: None,
+            ..
+        } => Err(format!("the image {} of a mask is not read", path)),
+        mask => Ok(mask.clone()),
+    }
+}
+
+impl CameraDescription {
+    pub fn build(&self, width: u32, height: u32) -> Camera {
+        let up = self.up;
+        let mut camera = Camera::new(
+            self.vertical_fov,
+            width,
+            height,
+            point(&self.look_from),
+            point(&self.look_at),
+            arr1(&[up[0], up[1], up[2], 0.0]),
+            self.aperture,
+        );
+        camera.shutter = self.shutter;
+        camera
+    }
+
+    /**
+     * Distance from the camera to the point it looks at.
+     */
+    pub fn distance(&self) -> Float {
+        Vec4::l2_norm(self.offset().view())
+    }
+
+    /**
+     * Rotate the camera around the point it looks at: `yaw` around the
+     * vertical (Y) axis and `pitch` up or down, in radians. The pitch
+     * stops short of the poles, where the view would flip.
+     */
+    pub fn orbit(&mut self, yaw: Float, pitch: Float) {
+        let offset = self.offset();
+        let distance = self.distance();
+        if distance == 0.0 {
+            return;
+        }
+
+        let limit = 89.0 as Float * consts::PI / 180.0;
+        let theta = offset[0].atan2(offset[2]) + yaw;
+        let phi = ((offset[1] / distance).clamp(-1.0, 1.0).asin() + pitch)
+            .clamp(-limit, limit);
+        self.look_from = [
+            self.look_at[0] + distance * phi.cos() * theta.sin(),
+            self.look_at[1] + distance * phi.sin(),
+            self.look_at[2] + distance * phi.cos() * theta.cos(),
+        ];
+    }
+
+    /**
+     * Move the camera and the point it looks at together, `right` and `up`
+     * in the image plane, in world units.
+     */
+    pub fn pan(&mut self, right: Float, up: Float) {
+        let forward = Vec4::normalize(-self.offset());
+        let up_vector = arr1(&[self.up[0], self.up[1], self.up[2], 0.0]);
+        let right_axis =
+            Vec4::normalize(Vec4::cross(forward.clone(), up_vector));
+        let up_axis = Vec4::cross(right_axis.clone(), forward);
+
+        let delta = right_axis * right + up_axis * up;
+        for c in 0..3 {
+            self.look_from[c] += delta[c];
+            self.look_at[c] += delta[c];
+        }
+    }
+
+    /**
+     * Scale the distance to the point the camera looks at, below one
+     * moves closer.
+     */
+    pub fn dolly(&mut self, factor: Float) {
+        let offset = self.offset() * factor.max(1e-3);
+        for c in 0..3 {
+            self.look_from[c] = self.look_at[c] + offset[c];
+        }
+    }
+
+    fn offset(&self) -> Array1<Float> {
+        let (from, at) = (self.look_from, self.look_at);
+        arr1(&[from[0] - at[0], from[1] - at[1], from[2] - at[2], 0.0])
+    }
+}
+
+impl LightDescription {
+    /**
+     * Build the light, or the lights of a rig (the sun and the sky).
+     */
+    pub fn build(&self) -> Result<Vec<Arc<dyn Light>>, String> {
+        self.build_in(Units::Meters)
+    }
+
+    /**
+     * Build the light for a scene in `units`: the intensity of the spots
+     * and the omni lights lights a point 1 meter away, however many units
+     * that is.
+     */
+    pub fn build_in(
+        &self,
+        units: Units,
+    ) -> Result<Vec<Arc<dyn Light>>, String> {
+        let falloff = 1.0 / (units.meters() * units.meters());
+        let light: Arc<dyn Light> = match self {
+            LightDescription::Sun {
+                direction,
+                illuminance,
+                angular_diameter,
+                color,
+                light_group,
+            } => Arc::new(Sun {
+                direction: *direction,
+                angular_diameter: *angular_diameter,
+                illuminance: *illuminance,
+                color: *color,
+                light_group: light_group.clone(),
+            }),
+            LightDescription::Spot {
+                position,
+                direction,
+                intensity,
+                unit,
+                efficacy,
+                inner_angle,
+                outer_angle,
+                color,
+                light_group,
+            } => {
+                let mut spot = Spot {
+                    position: *position,
+                    direction: *direction,
+                    inner_angle: *inner_angle,
+                    outer_angle: *outer_angle,
+                    intensity: *intensity,
+                    color: *color,
+                    gobo: None,
+                    light_group: light_group.clone(),
+                };
+                if let Some(lumens) = unit.lumens(*intensity, *efficacy) {
+                    spot.set_lumens(lumens);
+                }
+                spot.intensity *= falloff;
+                Arc::new(spot)
+            }
+            LightDescription::Omni {
+                position,
+                intensity,
+                unit,
+                efficacy,
+                radius,
+                color,
+                light_group,
+            } => {
+                let mut omni = Omni {
+                    position: *position,
+                    radius: *radius,
+                    intensity: *intensity,
+                    color: *color,
+                    light_group: light_group.clone(),
+                };
+                if let Some(lumens) = unit.lumens(*intensity, *efficacy) {
+                    omni.set_lumens(lumens);
+                }
+                omni.intensity *= falloff;
+                Arc::new(omni)
+            }
+            LightDescription::SunAndSky {
+                latitude,
+                longitude,
+                year,
+                month,
+                day,
+                hour,
+                utc_offset,
+                north,
+                turbidity,
+                resolution,
+            } => {
+                let rig = SunAndSky {
+                    latitude: *latitude,
+                    longitude: *longitude,
+                    year: *year,
+                    month: *month,
+                    day: *day,
+                    hour: *hour,
+                    utc_offset: *utc_offset,
+                    north: *north,
+                    turbidity: *turbidity,
+                };
+                rig.validate()?;
+                return Ok(vec![
+                    Arc::new(rig.sun()),
+                    Arc::new(rig.sky(*resolution)),
+                ]);
+            }
+            LightDescription::Stars {
+                count,
+                limiting_magnitude,
+                seed,
+                milky_way,
+                resolution,
+            } => {
+                let mut stars =
+                    StarField::procedural(*count, *limiting_magnitude, *seed);
+                stars.milky_way = *milky_way;
+                stars.validate()?;
+                Arc::new(stars.environment(*resolution))
+            }
+        };
+        Ok(vec![light])
+    }
+}
+
+impl AtmosphereDescription {
+    pub fn build(&self) -> Atmosphere {
+        let mut atmosphere = Atmosphere::earth(self.center, self.planet_radius);
+        atmosphere.rayleigh =
+            atmosphere.rayleigh.map(|beta| beta * self.density);
+        atmosphere.mie *= self.density * self.haze;
+        atmosphere
+    }
+}
+
+impl SceneDescription {
+    /**
+     * Time (seconds) of the animation at `fraction` of the shutter
+     * interval, from 0 when it opens to 1 when it closes.
+     */
+    pub fn time(&self, fraction: Float) -> Float {
+        (self.frame + fraction * self.shutter) / self.fps
+    }
+
+    /**
+     * Every camera of the scene with its name: the main one first, as
+     * "camera", then the named ones by name.
+     */
+    pub fn views(&self) -> Vec<(&str, &CameraDescription)> {
+        let mut views = vec![("camera", &self.camera)];
+        views.extend(self.cameras.iter().map(|(n, c)| (n.as_str(), c)));
+        views
+    }
+
+    pub fn lights(&self) -> Result<Vec<Arc<dyn Light>>, String> {
+        let mut lights = vec![];
+        for light in &self.lights {
+            lights.extend(light.build_in(self.units)?);
+        }
+        Ok(lights)
+    }
+
+    /**
+     * Every material of the scene, named or of an actor, an instance or
+     * the caps of the clipping planes.
+     */
+    fn materials_mut(&mut self) -> Vec<&mut MaterialDescription> {
+        let mut materials: Vec<_> = self.materials.values_mut().collect();
+        materials.extend(self.spheres.iter_mut().map(|s| &mut s.material));
+        materials.extend(self.meshes.iter_mut().map(|m| &mut m.material));
+        materials.extend(self.alembic.iter_mut().map(|a| &mut a.material));
+        materials.extend(self.rings.iter_mut().map(|r| &mut r.material));
+        let disks = self.accretion_disks.iter_mut();
+        materials.extend(disks.map(|d| &mut d.material));
+        materials.extend(self.fractals.iter_mut().map(|f| &mut f.material));
+        materials.extend(self.metaballs.iter_mut().map(|m| &mut m.material));
+        for prototype in self.prototypes.values_mut() {
+            let lods = prototype.lods.iter_mut();
+            let parts = lods
+                .map(|lod| (&mut lod.spheres, &mut lod.meshes))
+                .chain([(&mut prototype.spheres, &mut prototype.meshes)]);
+            for (spheres, meshes) in parts {
+                materials.extend(spheres.iter_mut().map(|s| &mut s.material));
+                materials.extend(meshes.iter_mut().map(|m| &mut m.material));
+            }
+        }
+        for instance in &mut self.instances {
+            materials.extend(instance.materials.values_mut());
+        }
+        for scatter in &mut self.scatters {
+            materials.extend(scatter.materials.values_mut());
+        }
+        if let Some(clipping) = &mut self.clipping {
+            materials.extend(clipping.cap.as_mut());
+        }
+        materials
+    }
+
+    /**
+     * Read the images of the texture masks of the materials with `files`,
+     * each image once, for the materials to be built.
+     */
+    pub fn read_textures(
+        &mut self,
+        files: &mut dyn SceneFiles,
+    ) -> Result<(), String> {
+        let mut images: BTreeMap<String, Arc<dyn TextureSampler>> =
+            BTreeMap::new();
+        for material in self.materials_mut() {
+            for mask in material.masks_mut() {
+                if let Mask::Texture {
+                    path,
+                    texture: texture @ None,
+                    ..
+                } = mask
+                {
+                    let image = match images.get(path.as_str()) {
+                        Some(image) => image.clone(),
+                        None => {
+                            let image = files.texture(path)?;
+                            images.insert(path.clone(), image.clone());
+                            image
+                        }
+                    };
+                    *texture = Some(MaskTexture(image));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Build the named materials of the scene.
+     */
+    pub fn library(&self) -> Result<MaterialLibrary, String> {
+        let mut library = MaterialLibrary::new();
+        for name in self.materials.keys() {
+            self.resolve(name, &mut library, &mut vec![])?;
+        }
+        Ok(library)
+    }
+
+    /**
+     * Build the named material `name` into `library`, after the ones it
+     * refers to. `pending` holds the materials waiting for it, to catch
+     * circular references.
+     */
+    fn resolve(
+        &self,
+        name: &str,
+        library: &mut MaterialLibrary,
+        pending: &mut Vec<String>,
+    ) -> Result<(), String> {
+        if library.contains(name) {
+            return Ok(());
+        }
+        if pending.iter().any(|waiting| waiting == name) {
+            return Err(format!("material {} refers to itself", name));
+        }
+        let description = self
+            .materials
+            .get(name)
+            .ok_or_else(|| format!("unknown material {}", name))?;
+
+        pending.push(name.to_string());
+        for reference in description.references() {
+            self.resolve(reference, library, pending)?;
+        }
+        pending.pop();
+
+        let material = description.build(library)?;
+        library.insert(name, material);
+        Ok(())
+    }
+
+    #[instrument(
+        level = "debug",
+        skip_all,
+        fields(spheres = self.spheres.len(), rings = self.rings.len())
+    )]
+    pub fn actors(&self) -> Result<Vec<Box<dyn RayTraceable>>, String> {
+        let library = self.library()?;
+        let mut actors = self
+            .spheres
+            .iter()
+            .map(|sphere| {
+                Ok(sphere.visibility.apply(Box::new(Sphere {
+                    center: point(&sphere.center),
+                    radius: sphere.radius,
+                    material: sphere.material.build(&library)?,
+                })))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        for ring in &self.rings {
+            let mut actor = Ring::new(
+                ring.center,
+                ring.normal,
+                ring.inner_radius,
+                ring.outer_radius,
+                ring.material.build(&library)?,
+            );
+            actor.optical_depth = ring.optical_depth;
+            actors.push(Box::new(actor));
+        }
+        for disk in &self.accretion_disks {
+            actors.push(Box::new(AccretionDisk::new(
+                disk.center,
+                disk.normal,
+                disk.inner_radius,
+                disk.outer_radius,
+                disk.material.build(&library)?,
+            )));
+        }
+        for fractal in &self.fractals {
+            actors.push(Box::new(fractal.build(&library)?));
+        }
+        for blobs in &self.metaballs {
+            actors.push(Box::new(Metaballs::new(
+                blobs.charges.clone(),
+                blobs.threshold,
+                blobs.material.build(&library)?,
+            )));
+        }
+        self.clip(actors)
+    }
+
+    /**
+     * The instances of the scene, given and scattered, one actor for those
+     * of each prototype (and visibility), each prototype built once. The
+     * meshes and images are read by `files`. Instances replacing a
+     * material by the same description share the replacement. Moving
+     * instances are actors of their own.
+     */
+    pub fn instances(
+        &self,
+        files: &mut dyn SceneFiles,
+    ) -> Result<Vec<Box<dyn RayTraceable>>, String> {
+        if self.instances.is_empty() && self.scatters.is_empty() {
+            return Ok(vec![]);
+        }
+        let library = self.library()?;
+        let mut replacements: Vec<(MaterialDescription, Arc<dyn Scattering>)> =
+            vec![];
+        let mut groups: Vec<(&str, Visibility, Vec<Instance>)> = vec![];
+        let mut moving: Vec<(&str, Visibility, Instance, Vec<MotionKey>)> =
+            vec![];
+        let mut add = |name: &'_ str,
+                       transform: Transform,
+                       materials: &'_ BTreeMap<String, MaterialDescription>,
+                       visibility: Visibility,
+                       motion: &'_ [MotionKey]|
+         -> Result<(), String> {
+            let name = match self.prototypes.get_key_value(name) {
+                Some((name, _)) => name.as_str(),
+                None => return Err(format!("unknown prototype {}", name)),
+            };
+            let mut instance = Instance::new(transform);
+            for (replaced, material) in materials {
+                let original = library
+                    .get(replaced)
+                    .ok_or_else(|| format!("unknown material {}", replaced))?;
+                let replacement = match replacements
+                    .iter()
+                    .find(|(other, _)| other == material)
+                {
+                    Some((_, built)) => built.clone(),
+                    None => {
+                        let built = material.build(&library)?;
+                        replacements.push((material.clone(), built.clone()));
+                        built
+                    }
+                };
+                instance.materials.push((original, replacement));
+            }
+            if !motion.is_empty() {
+                moving.push((name, visibility, instance, motion.to_vec()));
+                return Ok(());
+            }
+            match groups
+                .iter_mut()
+                .find(|(other, seen, _)| *other == name && *seen == visibility)
+            {
+                Some((_, _, instances)) => instances.push(instance),
+                None => groups.push((name, visibility, vec![instance])),
+            }
+            Ok(())
+        };
+
+        for description in &self.instances {
+            let transform = Transform::new(
+                description.translation,
+                description.rotation,
+                description.scale,
+            )?;
+            add(
+                &description.prototype,
+                transform,
+                &description.materials,
+                description.visibility,
+                &description.motion,
+            )?;
+        }
+        for scatter in &self.scatters {
+            if scatter.prototypes.is_empty() {
+                return Err("scatter without prototypes".to_string());
+            }
+            let mut scatterer = Scatterer::new(
+                scatter.region.clone(),
+                scatter.count,
+                scatter.seed,
+            );
+            scatterer.rotation = scatter.rotation;
+            scatterer.scale = scatter.scale;
+            scatterer.align = scatter.align;
+            if let Some(path) = &scatter.density {
+                scatterer.density = Some(files.texture(path)?);
+            }
+            let mesh = match &scatter.region {
+                ScatterRegion::Mesh { path, units } => {
+                    let surface = MeshDescription {
+                        path: path.clone(),
+                        units: *units,
+                        material: MaterialDescription::Lambertian {
+                            albedo: [0.5, 0.5, 0.5],
+                        },
+                        visibility: Visibility::default(),
+                        deformation: vec![],
+                        compressed: false,
+                        displacement: None,
+                    };
+                    let material = surface.material.build(&library)?;
+                    Some(files.mesh(&surface, material)?)
+                }
+                _ => None,
+            };
+            let last = scatter.prototypes.len() - 1;
+            for placed in scatterer.scatter(mesh.as_ref())? {
+                let pick = (placed.pick * (last + 1) as Float) as usize;
+                add(
+                    &scatter.prototypes[pick.min(last)],
+                    placed.transform,
+                    &scatter.materials,
+                    scatter.visibility,
+                    &[],
+                )?;
+            }
+        }
+
+        type Levels = Vec<(Switch, Arc<Prototype>)>;
+        let mut prototypes: BTreeMap<&str, (Arc<Prototype>, Levels)> =
+            BTreeMap::new();
+        let mut build = |name| -> Result<(Arc<Prototype>, Levels), String> {
+            if let Some(built) = prototypes.get(name) {
+                return Ok(built.clone());
+            }
+            let description = &self.prototypes[name];
+            let built = (
+                Arc::new(description.build(&library, files)?),
+                description.build_levels(&library, files)?,
+            );
+            prototypes.insert(name, built.clone());
+            Ok(built)
+        };
+        let mut actors = vec![];
+        for (name, visibility, instances) in groups {
+            let (prototype, levels) = build(name)?;
+            actors.push(visibility.apply(Box::new(Instances::with_levels(
+                prototype, levels, instances,
+            )?)));
+        }
+        for (name, visibility, instance, motion) in moving {
+            let (prototype, levels) = build(name)?;
+            let placed =
+                Instances::with_levels(prototype, levels, vec![instance])?;
+            actors.push(
+                visibility
+                    .apply(Box::new(Moving::new(Box::new(placed), &motion)?)),
+            );
+        }
+        Ok(actors)
+    }
+
+    /**
+     * The `actors` cut by the clipping planes of the scene, if any (those
+     * built elsewhere, as the meshes, too).
+     */
+    pub fn clip(
+        &self,
+        actors: Vec<Box<dyn RayTraceable>>,
+    ) -> Result<Vec<Box<dyn RayTraceable>>, String> {
+        let clipping = match &self.clipping {
+            Some(clipping) => clipping,
+            None => return Ok(actors),
+        };
+        let cap = match (&clipping.cap, clipping.cap_actors) {
+            (Some(_), true) => {
+                return Err("cap with a material or the actors".to_string())
+            }
+            (Some(cap), false) => {
+                Some(Cap::Material(cap.build(&self.library()?)?))
+            }
+            (None, true) => Some(Cap::Actor),
+            (None, false) => None,
+        };
+        Ok(actors
+            .into_iter()
+            .map(|actor| {
+                let mut clipped = Clipped::new(actor, clipping.planes.clone());
+                clipped.cap = cap.clone();
+                Box::new(clipped) as Box<dyn RayTraceable>
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,434 @@
+use crate::light::Light;
+use crate::light::LightSample;
+use crate::texture::Texture;
+use crate::texture::TextureSampler;
+use ndarray::{arr1, Array1};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::random;
+use std::sync::Arc;
+
+// Light from the surroundings of the scene, at an infinite distance: a
+// latitude-longitude radiance map (e.g. an HDR sky) that replaces the
+// background gradient, lighting the scene and seen by escaping rays.
+
+/**
+ * A rectangle, `corner` + s `edge_u` + t `edge_v` for s and t in [0, 1],
+ * marking an opening (a window, a door) through which the environment
+ * lights an interior. The edges must be at right angles.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Portal {
+    pub corner: [Float; 3],
+    pub edge_u: [Float; 3],
+    pub edge_v: [Float; 3],
+}
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[Float; 3], b: &[Float; 3]) -> [Float; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+impl Portal {
+    pub fn area(&self) -> Float {
+        let n = cross(&self.edge_u, &self.edge_v);
+        dot(&n, &n).sqrt()
+    }
+
+    fn unit_normal(&self) -> [Float; 3] {
+        let n = cross(&self.edge_u, &self.edge_v);
+        let length = dot(&n, &n).sqrt();
+        n.map(|c| c / length)
+    }
+
+    /**
+     * Distance from `origin` to the portal along the unit `direction`,
+     * None if the ray misses it.
+     */
+    fn distance(
+        &self,
+        origin: &[Float; 3],
+        direction: &[Float; 3],
+    ) -> Option<Float> {
+        let n = self.unit_normal();
+        let facing = dot(direction, &n);
+        if facing == 0.0 {
+            return None;
+        }
+        let to_corner = [0, 1, 2].map(|i| self.corner[i] - origin[i]);
+        let t = dot(&to_corner, &n) / facing;
+        if t.is_nan() || t <= 0.0 {
+            return None;
+        }
+        let offset =
+            [0, 1, 2].map(|i| origin[i] + t * direction[i] - self.corner[i]);
+        let s = dot(&offset, &self.edge_u) / dot(&self.edge_u, &self.edge_u);
+        let r = dot(&offset, &self.edge_v) / dot(&self.edge_v, &self.edge_v);
+        if (0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&r) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let (u, v) = (self.edge_u, self.edge_v);
+        let finite = |p: &[Float; 3]| p.iter().all(|c| c.is_finite());
+        let (length_u, length_v) = (dot(&u, &u).sqrt(), dot(&v, &v).sqrt());
+        let valid = finite(&self.corner)
+            && finite(&u)
+            && finite(&v)
+            && length_u > 0.0
+            && length_v > 0.0
+            && dot(&u, &v).abs() <= 1e-6 * length_u * length_v;
+        if !valid {
+            return Err(format!("invalid portal {:?}", self));
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Where the light of a map comes from, to sample its bright texels (the
+ * sun) more often than the dark ones: a row is picked by its share of the
+ * whole map, then a texel by its share of the row.
+ */
+#[derive(Debug)]
+struct MapDistribution {
+    width: usize,
+    height: usize,
+    /**
+     * Texel weights, summed along every row.
+     */
+    columns: Vec<Float>,
+    /**
+     * Row weights, summed down the map.
+     */
+    rows: Vec<Float>,
+}
+
+fn luminance(texel: &[Float; 4]) -> Float {
+    0.2126 * texel[0] + 0.7152 * texel[1] + 0.0722 * texel[2]
+}
+
+/**
+ * Index of the first entry of the running sum `cdf` above `x`, skipping
+ * the entries of weight 0.
+ */
+fn pick(cdf: &[Float], x: Float) -> usize {
+    cdf.partition_point(|c| *c <= x).min(cdf.len() - 1)
+}
+
+impl MapDistribution {
+    /**
+     * None if the map is black. A texel weighs the brightest of itself
+     * and its neighbours, which the filtering of the map may blend into
+     * it, times the solid angle of its row.
+     */
+    fn new(map: &Texture) -> Option<MapDistribution> {
+        let (width, height) = (map.width(), map.height());
+        let texel =
+            |x: usize, y: usize| luminance(&map.texel(0, x as i64, y as i64));
+        let brightness: Vec<Float> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| texel(x, y).max(0.0))
+            .collect();
+
+        let mut columns = Vec::with_capacity(width * height);
+        let mut rows = Vec::with_capacity(height);
+        let mut total = 0.0;
+        for y in 0..height {
+            let latitude =
+                (0.5 - (y as Float + 0.5) / height as Float) * consts::PI;
+            let mut sum = 0.0;
+            for x in 0..width {
+                let mut brightest: Float = 0.0;
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for dx in [width - 1, 0, 1] {
+                        let nx = (x + dx) % width;
+                        brightest = brightest.max(brightness[ny * width + nx]);
+                    }
+                }
+                sum += brightest * latitude.cos();
+                columns.push(sum);
+            }
+            total += sum;
+            rows.push(total);
+        }
+
+        if total > 0.0 && total.is_finite() {
+            Some(MapDistribution {
+                width,
+                height,
+                columns,
+                rows,
+            })
+        } else {
+            None
+        }
+    }
+
+    /**
+     * A texture coordinate picked by the weights of the texels, and its
+     * probability density over the unit square.
+     */
+    fn sample(&self) -> ([Float; 2], Float) {
+        let total = self.rows[self.height - 1];
+        let y = pick(&self.rows, random::gen_range(0.0, total));
+        let row = &self.columns[y * self.width..(y + 1) * self.width];
+        let x = pick(row, random::gen_range(0.0, row[self.width - 1]));
+
+        let before = if x > 0 { row[x - 1] } else { 0.0 };
+        let probability = (row[x] - before) / total;
+        let uv = [
+            (x as Float + random::gen_range(0.0, 1.0)) / self.width as Float,
+            1.0 - (y as Float + random::gen_range(0.0, 1.0))
+                / self.height as Float,
+        ];
+        (uv, probability * (self.width * self.height) as Float)
+    }
+}
+
+/**
+ * Radiance coming from every direction, from a map in latitude and
+ * longitude (mapped as the textures of spheres: v up, u around the
+ * vertical axis from -z) times `color`, or `color` alone. `rotation`
+ * turns the map around the vertical axis, in degrees.
+ *
+ * Without `portals` the directions towards the environment are sampled
+ * over the whole sphere, by the brightness of the map if there is one, so
+ * that small and bright light sources in it (the sun) are found by the
+ * lit points instead of by chance. In interiors lit through small openings, the
+ * `portals` covering those openings make the lit points sample only the
+ * directions through them. Openings must then be left empty (no glass),
+ * and points outside still gather the rest of the sky through the rays
+ * escaping the scene.
+ */
+#[derive(Clone)]
+pub struct Environment {
+    map: Option<Texture>,
+    distribution: Option<Arc<MapDistribution>>,
+    pub color: [Float; 3],
+    pub rotation: Float,
+    pub portals: Vec<Portal>,
+    pub light_group: Option<String>,
+}
+
+impl Environment {
+    /**
+     * The same radiance `color` from every direction.
+     */
+    pub fn uniform(color: [Float; 3]) -> Environment {
+        Environment {
+            map: None,
+            distribution: None,
+            color,
+            rotation: 0.0,
+            portals: vec![],
+            light_group: None,
+        }
+    }
+
+    pub fn new(map: Texture) -> Environment {
+        Environment {
+            distribution: MapDistribution::new(&map).map(Arc::new),
+            map: Some(map),
+            ..Environment::uniform([1.0, 1.0, 1.0])
+        }
+    }
+
+    pub fn map(&self) -> Option<&Texture> {
+        self.map.as_ref()
+    }
+
+    /**
+     * Radiance along the unit `direction`.
+     */
+    pub fn radiance(&self, direction: &[Float; 3]) -> Array1<Float> {
+        let c = self.color;
+        let mut radiance = arr1(&[c[0], c[1], c[2], 0.0]);
+        if let Some(map) = &self.map {
+            let longitude = direction[0].atan2(direction[2])
+                - self.rotation * consts::PI / 180.0;
+            let latitude = direction[1].clamp(-1.0, 1.0).asin();
+            let u = 0.5 + longitude / (2.0 * consts::PI);
+            let texel =
+                map.sample([u - u.floor(), 0.5 + latitude / consts::PI], 0.0);
+            for i in 0..3 {
+                radiance[i] *= texel[i];
+            }
+        }
+        radiance
+    }
+
+    /**
+     * Probability density (per solid angle) of sampling the unit
+     * `direction` from `origin` through the portals.
+     */
+    fn portal_pdf(&self, origin: &[Float; 3], direction: &[Float; 3]) -> Float {
+        let total: Float = self.portals.iter().map(|p| p.area()).sum();
+        self.portals
+            .iter()
+            .filter_map(|portal| {
+                let distance = portal.distance(origin, direction)?;
+                let cosine = dot(direction, &portal.unit_normal()).abs();
+                Some(distance * distance / (total * cosine))
+            })
+            .sum()
+    }
+
+    /**
+     * A point picked uniformly over the area of the portals.
+     */
+    fn portal_point(&self) -> [Float; 3] {
+        let total: Float = self.portals.iter().map(|p| p.area()).sum();
+        let mut pick = random::gen_range(0.0, total);
+        let last = self.portals.len() - 1;
+        for (i, portal) in self.portals.iter().enumerate() {
+            if pick < portal.area() || i == last {
+                let s = random::gen_range(0.0, 1.0);
+                let t = random::gen_range(0.0, 1.0);
+                return [0, 1, 2].map(|c| {
+                    portal.corner[c]
+                        + s * portal.edge_u[c]
+                        + t * portal.edge_v[c]
+                });
+            }
+            pick -= portal.area();
+        }
+        unreachable!()
+    }
+}
+
+impl Light for Environment {
+    fn sample(&self, point: &Array1<Float>) -> Option<LightSample> {
+        let origin = [point[0], point[1], point[2]];
+        let (direction, pdf) = if !self.portals.is_empty() {
+            let target = self.portal_point();
+            let offset = [0, 1, 2].map(|i| target[i] - origin[i]);
+            let length = dot(&offset, &offset).sqrt();
+            if length.is_nan() || length <= 0.0 {
+                return None;
+            }
+            let direction = offset.map(|c| c / length);
+            // Portals in line add up their densities.
+            (direction, self.portal_pdf(&origin, &direction))
+        } else if let Some(distribution) = &self.distribution {
+            let ([u, v], density) = distribution.sample();
+            let longitude = (u - 0.5) * 2.0 * consts::PI
+                + self.rotation * consts::PI / 180.0;
+            let latitude = (v - 0.5) * consts::PI;
+            let (sin_latitude, cos_latitude) = latitude.sin_cos();
+            let direction = [
+                cos_latitude * longitude.sin(),
+                sin_latitude,
+                cos_latitude * longitude.cos(),
+            ];
+            // The unit square spans 2 pi^2 cos(latitude) of solid angle
+            // per unit of area.
+            let pdf = density / (2.0 * consts::PI * consts::PI * cos_latitude);
+            (direction, pdf)
+        } else {
+            let z = random::gen_range(-1.0, 1.0);
+            let phi = random::gen_range(0.0, 2.0 * consts::PI);
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            ([r * phi.cos(), r * phi.sin(), z], 1.0 / (4.0 * consts::PI))
+        };
+        if !(pdf > 0.0 && pdf.is_finite()) {
+            return None;
+        }
+
+        Some(LightSample {
+            direction: arr1(&[direction[0], direction[1], direction[2], 0.0]),
+            distance: Float::INFINITY,
+            radiance: self.radiance(&direction) / pdf,
+        })
+    }
+
+    fn emitted(
+        &self,
+        _origin: &Array1<Float>,
+        direction: &Array1<Float>,
+    ) -> Option<Array1<Float>> {
+        let d = [direction[0], direction[1], direction[2]];
+        let length = dot(&d, &d).sqrt();
+        Some(self.radiance(&d.map(|c| c / length)))
+    }
+
+    fn can_sample(
+        &self,
+        origin: &Array1<Float>,
+        direction: &Array1<Float>,
+    ) -> bool {
+        if self.portals.is_empty() {
+            return true;
+        }
+        let o = [origin[0], origin[1], origin[2]];
+        let d = [direction[0], direction[1], direction[2]];
+        let length = dot(&d, &d).sqrt();
+        let d = d.map(|c| c / length);
+        self.portals
+            .iter()
+            .any(|portal| portal.distance(&o, &d).is_some())
+    }
+
+    fn environment(&self) -> bool {
+        true
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.light_group.clone()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.color.iter().all(|c| *c >= 0.0 && c.is_finite()) {
+            return Err(format!("invalid environment color {:?}", self.color));
+        }
+        if !self.rotation.is_finite() {
+            return Err(format!(
+                "invalid environment rotation {}",
+                self.rotation
+            ));
+        }
+        self.portals.iter().try_for_each(|portal| portal.validate())
+    }
+
+    fn name(&self) -> String {
+        let map = match &self.map {
+            Some(map) => map.name(),
+            None => "uniform".to_string(),
+        };
+        if self.portals.is_empty() {
+            format!("environment({})", map)
+        } else {
+            format!("environment({};{} portals)", map, self.portals.len())
+        }
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        if let Some(map) = &self.map {
+            textures.push(map);
+        }
+    }
+
+    /**
+     * Only the portals move, the environment stays infinitely far.
+     */
+    fn translated(&self, offset: &[Float; 3]) -> Option<Arc<dyn Light>> {
+        if self.portals.is_empty() {
+            return None;
+        }
+        let mut environment = self.clone();
+        for portal in &mut environment.portals {
+            portal.corner = [0, 1, 2].map(|i| portal.corner[i] + offset[i]);
+        }
+        Some(Arc::new(environment))
+    }
+}
@@ -0,0 +1,448 @@
+use crate::light::orthonormal_basis;
+use saturno_math::common::Float;
+
+// Where the planets and their major moons are at a date. The planets
+// follow their mean orbital elements (E. M. Standish, "Keplerian Elements
+// for Approximate Positions of the Major Planets", JPL, fitted from 1800 to
+// 2050, about an arcminute off for the inner planets), the Moon a low
+// precision series (about a degree off), the other moons circular orbits in
+// the equatorial plane of their planet (their phases are only indicative).
+//
+// Positions are in astronomical units, in the ecliptic frame of J2000 laid
+// out as the scenes are: y towards the north pole of the ecliptic, x towards
+// the March equinox and z opposite to the ecliptic y axis.
+
+/**
+ * Kilometers per astronomical unit.
+ */
+pub const AU: Float = 149_597_870.7;
+
+/**
+ * Obliquity of the ecliptic at J2000, in degrees.
+ */
+const OBLIQUITY: Float = 23.43928;
+
+/**
+ * Mass of the Earth over the mass of the Moon.
+ */
+const EARTH_MOON_RATIO: Float = 81.30056;
+
+/**
+ * Julian date of a date and time (hours, UTC) of the Gregorian calendar.
+ */
+pub fn julian_date(year: i32, month: u32, day: u32, hour: Float) -> Float {
+    let (year, month) = if month <= 2 {
+        (year as Float - 1.0, month as Float + 12.0)
+    } else {
+        (year as Float, month as Float)
+    };
+    let century = (year / 100.0).floor();
+    let gregorian = 2.0 - century + (century / 4.0).floor();
+    (365.25 * (year + 4716.0)).floor()
+        + (30.6001 * (month + 1.0)).floor()
+        + day as Float
+        + gregorian
+        - 1524.5
+        + hour / 24.0
+}
+
+fn ecliptic_to_scene(p: [Float; 3]) -> [Float; 3] {
+    [p[0], p[2], -p[1]]
+}
+
+/**
+ * Unit direction at right ascension `ra` and declination `dec` (degrees,
+ * equatorial frame of J2000), in the scene frame.
+ */
+pub fn equatorial_direction(ra: Float, dec: Float) -> [Float; 3] {
+    let (ra, dec) = (ra.to_radians(), dec.to_radians());
+    let equatorial = [dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin()];
+    let (sin, cos) = OBLIQUITY.to_radians().sin_cos();
+    ecliptic_to_scene([
+        equatorial[0],
+        cos * equatorial[1] + sin * equatorial[2],
+        -sin * equatorial[1] + cos * equatorial[2],
+    ])
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Planet {
+    Mercury,
+    Venus,
+    Earth,
+    Mars,
+    Jupiter,
+    Saturn,
+    Uranus,
+    Neptune,
+}
+
+impl Planet {
+    pub const ALL: [Planet; 8] = [
+        Planet::Mercury,
+        Planet::Venus,
+        Planet::Earth,
+        Planet::Mars,
+        Planet::Jupiter,
+        Planet::Saturn,
+        Planet::Uranus,
+        Planet::Neptune,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Planet::Mercury => "mercury",
+            Planet::Venus => "venus",
+            Planet::Earth => "earth",
+            Planet::Mars => "mars",
+            Planet::Jupiter => "jupiter",
+            Planet::Saturn => "saturn",
+            Planet::Uranus => "uranus",
+            Planet::Neptune => "neptune",
+        }
+    }
+
+    /**
+     * Mean radius in kilometers.
+     */
+    pub fn radius(&self) -> Float {
+        match self {
+            Planet::Mercury => 2439.7,
+            Planet::Venus => 6051.8,
+            Planet::Earth => 6371.0,
+            Planet::Mars => 3389.5,
+            Planet::Jupiter => 69911.0,
+            Planet::Saturn => 58232.0,
+            Planet::Uranus => 25362.0,
+            Planet::Neptune => 24622.0,
+        }
+    }
+
+    /**
+     * Unit direction of the north pole (IAU).
+     */
+    pub fn pole(&self) -> [Float; 3] {
+        match self {
+            Planet::Mercury => equatorial_direction(281.0097, 61.4143),
+            Planet::Venus => equatorial_direction(272.76, 67.16),
+            Planet::Earth => equatorial_direction(0.0, 90.0),
+            Planet::Mars => equatorial_direction(317.68143, 52.8865),
+            Planet::Jupiter => equatorial_direction(268.0566, 64.4953),
+            Planet::Saturn => equatorial_direction(40.589, 83.537),
+            Planet::Uranus => equatorial_direction(257.311, -15.175),
+            Planet::Neptune => equatorial_direction(299.36, 43.46),
+        }
+    }
+
+    /**
+     * Semi-major axis (AU), eccentricity, inclination, mean longitude,
+     * longitude of the perihelion and longitude of the ascending node
+     * (degrees) at J2000, and their rates per century. The Earth's are
+     * those of the Earth-Moon barycenter. In f64 whatever the Float, the
+     * mean longitudes growing by thousands of degrees a century.
+     */
+    fn elements(&self) -> ([f64; 6], [f64; 6]) {
+        match self {
+            Planet::Mercury => (
+                [
+                    0.38709927,
+                    0.20563593,
+                    7.00497902,
+                    252.2503235,
+                    77.45779628,
+                    48.33076593,
+                ],
+                [
+                    0.00000037,
+                    0.00001906,
+                    -0.00594749,
+                    149472.67411175,
+                    0.16047689,
+                    -0.12534081,
+                ],
+            ),
+            Planet::Venus => (
+                [
+                    0.72333566,
+                    0.00677672,
+                    3.39467605,
+                    181.9790995,
+                    131.60246718,
+                    76.67984255,
+                ],
+                [
+                    0.0000039,
+                    -0.00004107,
+                    -0.0007889,
+                    58517.81538729,
+                    0.00268329,
+                    -0.27769418,
+                ],
+            ),
+            Planet::Earth => (
+                [
+                    1.00000261,
+                    0.01671123,
+                    -0.00001531,
+                    100.46457166,
+                    102.93768193,
+                    0.0,
+                ],
+                [
+                    0.00000562,
+                    -0.00004392,
+                    -0.01294668,
+                    35999.37244981,
+                    0.32327364,
+                    0.0,
+                ],
+            ),
+            Planet::Mars => (
+                [
+                    1.52371034,
+                    0.0933941,
+                    1.84969142,
+                    -4.55343205,
+                    -23.94362959,
+                    49.55953891,
+                ],
+                [
+                    0.00001847,
+                    0.00007882,
+                    -0.00813131,
+                    19140.30268499,
+                    0.44441088,
+                    -0.29257343,
+                ],
+            ),
+            Planet::Jupiter => (
+                [
+                    5.202887,
+                    0.04838624,
+                    1.30439695,
+                    34.39644051,
+                    14.72847983,
+                    100.47390909,
+                ],
+                [
+                    -0.00011607,
+                    -0.00013253,
+                    -0.00183714,
+                    3034.74612775,
+                    0.21252668,
+                    0.20469106,
+                ],
+            ),
+            Planet::Saturn => (
+                [
+                    9.53667594,
+                    0.05386179,
+                    2.48599187,
+                    49.95424423,
+                    92.59887831,
+                    113.66242448,
+                ],
+                [
+                    -0.0012506,
+                    -0.00050991,
+                    0.00193609,
+                    1222.49362201,
+                    -0.41897216,
+                    -0.28867794,
+                ],
+            ),
+            Planet::Uranus => (
+                [
+                    19.18916464,
+                    0.04725744,
+                    0.77263783,
+                    313.23810451,
+                    170.9542763,
+                    74.01692503,
+                ],
+                [
+                    -0.00196176,
+                    -0.00004397,
+                    -0.00242939,
+                    428.48202785,
+                    0.40805281,
+                    0.04240589,
+                ],
+            ),
+            Planet::Neptune => (
+                [
+                    30.06992276,
+                    0.00859048,
+                    1.77004347,
+                    -55.12002969,
+                    44.96476227,
+                    131.78422574,
+                ],
+                [
+                    0.00026291,
+                    0.00005105,
+                    0.00035372,
+                    218.45945325,
+                    -0.32241464,
+                    -0.00508664,
+                ],
+            ),
+        }
+    }
+
+    /**
+     * Position around the Sun at `julian_date`, in AU.
+     */
+    pub fn position(&self, julian_date: Float) -> [Float; 3] {
+        // Float is f32 with the `f32` feature.
+        #[allow(clippy::unnecessary_cast)]
+        let centuries = (julian_date - 2451545.0) as f64 / 36525.0;
+        let (at_epoch, rates) = self.elements();
+        let [a, e, inclination, longitude, perihelion, node] =
+            [0, 1, 2, 3, 4, 5]
+                .map(|i| (at_epoch[i] + rates[i] * centuries) as Float);
+
+        // Mean anomaly, and the eccentric one (Kepler's equation).
+        let mean = ((longitude - perihelion + 180.0).rem_euclid(360.0) - 180.0)
+            .to_radians();
+        let mut eccentric = mean + e * mean.sin();
+        for _ in 0..10 {
+            eccentric -= (eccentric - e * eccentric.sin() - mean)
+                / (1.0 - e * eccentric.cos());
+        }
+        let x = a * (eccentric.cos() - e);
+        let y = a * (1.0 - e * e).sqrt() * eccentric.sin();
+
+        let (sin_w, cos_w) = (perihelion - node).to_radians().sin_cos();
+        let (sin_o, cos_o) = node.to_radians().sin_cos();
+        let (sin_i, cos_i) = inclination.to_radians().sin_cos();
+        let ecliptic = [
+            (cos_w * cos_o - sin_w * sin_o * cos_i) * x
+                + (-sin_w * cos_o - cos_w * sin_o * cos_i) * y,
+            (cos_w * sin_o + sin_w * cos_o * cos_i) * x
+                + (-sin_w * sin_o + cos_w * cos_o * cos_i) * y,
+            sin_w * sin_i * x + cos_w * sin_i * y,
+        ];
+        let position = ecliptic_to_scene(ecliptic);
+
+        if *self == Planet::Earth {
+            // Off the barycenter, away from the Moon.
+            let moon = Satellite::Moon.position(julian_date);
+            let share = 1.0 / (1.0 + EARTH_MOON_RATIO);
+            return [0, 1, 2].map(|i| position[i] - moon[i] * share);
+        }
+        position
+    }
+}
+
+/**
+ * The major moons of the planets.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Satellite {
+    Moon,
+    Io,
+    Europa,
+    Ganymede,
+    Callisto,
+    Titan,
+}
+
+impl Satellite {
+    pub const ALL: [Satellite; 6] = [
+        Satellite::Moon,
+        Satellite::Io,
+        Satellite::Europa,
+        Satellite::Ganymede,
+        Satellite::Callisto,
+        Satellite::Titan,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Satellite::Moon => "moon",
+            Satellite::Io => "io",
+            Satellite::Europa => "europa",
+            Satellite::Ganymede => "ganymede",
+            Satellite::Callisto => "callisto",
+            Satellite::Titan => "titan",
+        }
+    }
+
+    pub fn planet(&self) -> Planet {
+        match self {
+            Satellite::Moon => Planet::Earth,
+            Satellite::Titan => Planet::Saturn,
+            _ => Planet::Jupiter,
+        }
+    }
+
+    /**
+     * Mean radius in kilometers.
+     */
+    pub fn radius(&self) -> Float {
+        match self {
+            Satellite::Moon => 1737.4,
+            Satellite::Io => 1821.6,
+            Satellite::Europa => 1560.8,
+            Satellite::Ganymede => 2634.1,
+            Satellite::Callisto => 2410.3,
+            Satellite::Titan => 2574.7,
+        }
+    }
+
+    /**
+     * Radius of the orbit (km), period (days) and angle at J2000 (degrees)
+     * of the circular orbits.
+     */
+    fn orbit(&self) -> (Float, Float, Float) {
+        match self {
+            Satellite::Moon => (384400.0, 27.321662, 0.0),
+            Satellite::Io => (421700.0, 1.769138, 163.8069),
+            Satellite::Europa => (671034.0, 3.551181, 358.414),
+            Satellite::Ganymede => (1070412.0, 7.154553, 5.7176),
+            Satellite::Callisto => (1882709.0, 16.689018, 224.8092),
+            Satellite::Titan => (1221870.0, 15.945421, 0.0),
+        }
+    }
+
+    /**
+     * Position around its planet at `julian_date`, in AU.
+     */
+    pub fn position(&self, julian_date: Float) -> [Float; 3] {
+        let days = julian_date - 2451545.0;
+        if *self == Satellite::Moon {
+            let longitude = 218.316 + 13.176396 * days;
+            let anomaly = (134.963 + 13.064993 * days).to_radians();
+            let argument = (93.272 + 13.22935 * days).to_radians();
+            let longitude = (longitude + 6.289 * anomaly.sin()).to_radians();
+            let latitude = (5.128 * argument.sin()).to_radians();
+            let distance = (385001.0 - 20905.0 * anomaly.cos()) / AU;
+            return ecliptic_to_scene([
+                distance * latitude.cos() * longitude.cos(),
+                distance * latitude.cos() * longitude.sin(),
+                distance * latitude.sin(),
+            ]);
+        }
+
+        // From the ascending node of the equator on the ecliptic.
+        let (radius, period, angle) = self.orbit();
+        let angle = (angle + 360.0 * days / period).to_radians();
+        let pole = self.planet().pole();
+        let node = [pole[2], 0.0, -pole[0]];
+        let length = (node[0] * node[0] + node[2] * node[2]).sqrt();
+        let (u, v) = if length > 1e-9 {
+            let u = node.map(|c| c / length);
+            let v = [
+                pole[1] * u[2] - pole[2] * u[1],
+                pole[2] * u[0] - pole[0] * u[2],
+                pole[0] * u[1] - pole[1] * u[0],
+            ];
+            (u, v)
+        } else {
+            orthonormal_basis(&pole)
+        };
+        let (sin, cos) = angle.sin_cos();
+        [0, 1, 2].map(|i| radius / AU * (cos * u[i] + sin * v[i]))
+    }
+}
@@ -0,0 +1,179 @@
+use crate::light::orthonormal_basis;
+use ndarray::{arr1, Array1};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Participating medium filling the space between the actors: haze that
+// fades the distance (aerial perspective) and scatters the light of the
+// lights into visible shafts where the actors shadow it.
+
+/**
+ * Homogeneous fog: `density` is the chance of an interaction per unit of
+ * distance (the inverse of the mean free path), `albedo` the fraction of
+ * the light scattered rather than absorbed at an interaction, per channel.
+ * `anisotropy` is the Henyey-Greenstein g, from -1 (scatters back) through
+ * 0 (evenly) to 1 (forward, as haze around the sun).
+ *
+ * The fog fills the space below the height `top`, or all of it. A fog with
+ * no top hides what lies beyond a few mean free paths, the sun and the
+ * environment included.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Fog {
+    pub density: Float,
+    #[cfg_attr(feature = "serde", serde(default = "white"))]
+    pub albedo: [Float; 3],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub anisotropy: Float,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub top: Option<Float>,
+}
+
+#[cfg(feature = "serde")]
+fn white() -> [Float; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+/**
+ * Henyey-Greenstein phase function of anisotropy `g`: the density of the
+ * light going on at `cosine` from its direction, per solid angle.
+ */
+pub(crate) fn henyey_greenstein(g: Float, cosine: Float) -> Float {
+    let denominator = 1.0 + g * g - 2.0 * g * cosine;
+    (1.0 - g * g) / (4.0 * consts::PI * denominator * denominator.sqrt())
+}
+
+/**
+ * Direction distributed by the Henyey-Greenstein phase function of
+ * anisotropy `g` around the unit `incoming` direction.
+ */
+pub(crate) fn sample_henyey_greenstein(
+    g: Float,
+    incoming: &Array1<Float>,
+) -> Array1<Float> {
+    let xi = random::gen_range(0.0, 1.0);
+    let cos_theta = if g.abs() < 1e-3 {
+        1.0 - 2.0 * xi
+    } else {
+        let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+        (1.0 + g * g - s * s) / (2.0 * g)
+    };
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = random::gen_range(0.0, 2.0 * consts::PI);
+
+    let w = [incoming[0], incoming[1], incoming[2]];
+    let (u, v) = orthonormal_basis(&w);
+    let (x, y) = (sin_theta * phi.cos(), sin_theta * phi.sin());
+    let d = [0, 1, 2].map(|i| x * u[i] + y * v[i] + cos_theta * w[i]);
+    arr1(&[d[0], d[1], d[2], 0.0])
+}
+
+impl Fog {
+    pub fn new(density: Float) -> Fog {
+        Fog {
+            density,
+            albedo: [1.0, 1.0, 1.0],
+            anisotropy: 0.0,
+            top: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.density >= 0.0 && self.density.is_finite()) {
+            return Err(format!("invalid fog density {}", self.density));
+        }
+        if !self.albedo.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return Err(format!("invalid fog albedo {:?}", self.albedo));
+        }
+        if !(self.anisotropy > -1.0 && self.anisotropy < 1.0) {
+            return Err(format!("invalid fog anisotropy {}", self.anisotropy));
+        }
+        if self.top.is_some_and(|top| !top.is_finite()) {
+            return Err(format!("invalid fog top {:?}", self.top));
+        }
+        Ok(())
+    }
+
+    /**
+     * The range of the parameters of `ray` within the fog, clipped to
+     * [0, `t_max`]. None if the ray does not go through it.
+     */
+    fn span(&self, ray: &Ray, t_max: Float) -> Option<(Float, Float)> {
+        let (t_min, t_max) = match self.top {
+            None => (0.0, t_max),
+            Some(top) => {
+                let (height, rise) = (ray.origin[1], ray.direction[1]);
+                let t_top = (top - height) / rise;
+                if height < top {
+                    if rise > 0.0 {
+                        (0.0, t_max.min(t_top))
+                    } else {
+                        (0.0, t_max)
+                    }
+                } else if rise < 0.0 {
+                    (t_top, t_max)
+                } else {
+                    return None;
+                }
+            }
+        };
+        if self.density > 0.0 && t_min < t_max {
+            Some((t_min, t_max))
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Fraction of the light going through the fog along `ray`, up to the
+     * parameter `t_max`.
+     */
+    pub fn transmittance(&self, ray: &Ray, t_max: Float) -> Float {
+        match self.span(ray, t_max) {
+            Some((t_min, t_max)) => {
+                let length = Vec4::l2_norm(ray.direction.view());
+                (-self.density * (t_max - t_min) * length).exp()
+            }
+            None => 1.0,
+        }
+    }
+
+    /**
+     * Parameter of `ray` where it interacts with the fog before `t_max`
+     * (exponential free flight), None if it goes through. Rays go through
+     * with the probability of the transmittance.
+     */
+    pub fn sample_interaction(&self, ray: &Ray, t_max: Float) -> Option<Float> {
+        let (t_min, t_max) = self.span(ray, t_max)?;
+        let length = Vec4::l2_norm(ray.direction.view());
+        let distance = -(1.0 - random::gen_range(0.0, 1.0)).ln() / self.density;
+        let t = t_min + distance / length;
+        if t < t_max {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Henyey-Greenstein phase function: the density of the light going on
+     * at `cosine` from its direction, per solid angle.
+     */
+    pub fn phase(&self, cosine: Float) -> Float {
+        henyey_greenstein(self.anisotropy, cosine)
+    }
+
+    /**
+     * Direction after an interaction, distributed by the phase function
+     * around the unit `incoming` direction.
+     */
+    pub fn sample_direction(&self, incoming: &Array1<Float>) -> Array1<Float> {
+        sample_henyey_greenstein(self.anisotropy, incoming)
+    }
+}
@@ -0,0 +1,351 @@
+use crate::actor::Aabb;
+use crate::actor::Hit;
+use crate::actor::Hittable;
+use crate::actor::RayTraceable;
+use crate::light::orthonormal_basis;
+use crate::material::Scattering;
+use crate::texture::TextureSampler;
+use ndarray::arr1;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Fractals given by a distance estimator: a lower bound of the distance
+// from a point to the surface, with which a ray is marched (sphere
+// tracing), stepping by the estimated distance until it comes closer than
+// the precision, or leaves the sphere around the fractal. The normal is
+// the gradient of the estimator, by finite differences.
+//
+// The estimators are those of the Mandelbulb (the 3D Mandelbrot set of
+// White and Nylander, in spherical coordinates) and of the Menger sponge
+// (a cube with crosses carved out at every level, as Quilez folds it). They
+// also give an orbit trap: how close the orbit of the point came to the
+// origin (the least squared radius of the bulb, the least radius in the
+// cubes of the sponge), in [0, 1], which the fractal puts in the u texture coordinate of
+// the hits for a `Palette` to color.
+
+/**
+ * The fractal and the iterations of its estimator (more iterations, more
+ * details).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum FractalKind {
+    /**
+     * Of `power` 8 for the classic one, within the sphere of radius 1.2
+     * (larger for lower powers).
+     */
+    Mandelbulb { power: Float, iterations: u32 },
+    /**
+     * In the cube from -1 to 1.
+     */
+    MengerSponge { iterations: u32 },
+}
+
+impl FractalKind {
+    /**
+     * Radius of the sphere around the fractal (of size 1).
+     */
+    fn radius(&self) -> Float {
+        match *self {
+            FractalKind::Mandelbulb { power, .. } if power >= 8.0 => 1.2,
+            FractalKind::Mandelbulb { .. } => 2.0,
+            FractalKind::MengerSponge { .. } => Float::sqrt(3.0),
+        }
+    }
+
+    /**
+     * Estimated distance from `p` to the fractal (of size 1), and the
+     * orbit trap of `p`.
+     */
+    pub fn distance(&self, p: [Float; 3]) -> (Float, Float) {
+        match *self {
+            FractalKind::Mandelbulb { power, iterations } => {
+                mandelbulb(p, power, iterations)
+            }
+            FractalKind::MengerSponge { iterations } => {
+                menger_sponge(p, iterations)
+            }
+        }
+    }
+}
+
+fn length(v: [Float; 3]) -> Float {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+fn mandelbulb(p: [Float; 3], power: Float, iterations: u32) -> (Float, Float) {
+    let mut z = p;
+    let mut dr = 1.0;
+    let mut r = length(z);
+    let mut trap = r * r;
+    for _ in 0..iterations {
+        if r > 2.0 {
+            break;
+        }
+        // z -> z^power + p, raising the radius to the power and
+        // multiplying the angles by it.
+        let theta = if r > 0.0 {
+            (z[2] / r).clamp(-1.0, 1.0).acos() * power
+        } else {
+            0.0
+        };
+        let phi = z[1].atan2(z[0]) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        let zr = r.powf(power);
+        z = [
+            zr * theta.sin() * phi.cos() + p[0],
+            zr * theta.sin() * phi.sin() + p[1],
+            zr * theta.cos() + p[2],
+        ];
+        r = length(z);
+        trap = trap.min(r * r);
+    }
+    if r <= Float::MIN_POSITIVE {
+        return (0.0, 0.0);
+    }
+    (0.5 * r.ln() * r / dr, trap.min(1.0))
+}
+
+fn menger_sponge(p: [Float; 3], iterations: u32) -> (Float, Float) {
+    // The cube, then a cross carved out of every cube of each level.
+    let q = p.map(|c| c.abs() - 1.0);
+    let outside = length(q.map(|c| c.max(0.0)));
+    let mut distance = outside + q[0].max(q[1]).max(q[2]).min(0.0);
+    let mut trap: Float = 1.0;
+    let mut scale = 1.0;
+    for _ in 0..iterations {
+        // Position in the cube of the level, from -1 to 1.
+        let a = p.map(|c| (c * scale).rem_euclid(2.0) - 1.0);
+        scale *= 3.0;
+        let r = a.map(|c| (1.0 - 3.0 * c.abs()).abs());
+        let da = r[0].max(r[1]);
+        let db = r[1].max(r[2]);
+        let dc = r[2].max(r[0]);
+        let cross = (da.min(db).min(dc) - 1.0) / scale;
+        distance = distance.max(cross);
+        trap = trap.min(length(a) / Float::sqrt(3.0));
+    }
+    (distance, trap)
+}
+
+/**
+ * A fractal of `kind` around `center`, `size` times its own (see
+ * `FractalKind`). Rays march until they come within `precision` (relative
+ * to the size) of the surface, in at most `max_steps` steps.
+ */
+pub struct Fractal {
+    pub kind: FractalKind,
+    pub center: [Float; 3],
+    pub size: Float,
+    pub precision: Float,
+    pub max_steps: u32,
+    pub material: Arc<dyn Scattering>,
+}
+
+impl Fractal {
+    pub fn new(
+        kind: FractalKind,
+        center: [Float; 3],
+        size: Float,
+        material: Arc<dyn Scattering>,
+    ) -> Fractal {
+        Fractal {
+            kind,
+            center,
+            size,
+            precision: 1e-4,
+            max_steps: 512,
+            material,
+        }
+    }
+
+    /**
+     * Estimated distance from `point` to the surface, and its orbit trap.
+     */
+    pub fn distance(&self, point: [Float; 3]) -> (Float, Float) {
+        let local = [0, 1, 2].map(|i| (point[i] - self.center[i]) / self.size);
+        let (distance, trap) = self.kind.distance(local);
+        (distance * self.size, trap)
+    }
+
+    /**
+     * Unit normal at `point`, the gradient of the estimator by central
+     * differences (on the 4 corners of a tetrahedron).
+     */
+    fn normal(&self, point: [Float; 3], step: Float) -> [Float; 3] {
+        let corners = [
+            [1.0, -1.0, -1.0],
+            [-1.0, -1.0, 1.0],
+            [-1.0, 1.0, -1.0],
+            [1.0, 1.0, 1.0],
+        ];
+        let mut gradient = [0.0; 3];
+        for corner in corners {
+            let at = [0, 1, 2].map(|i| point[i] + corner[i] * step);
+            let distance = self.distance(at).0;
+            for i in 0..3 {
+                gradient[i] += corner[i] * distance;
+            }
+        }
+        let length = length(gradient);
+        if length <= 0.0 || length.is_nan() {
+            return [0.0, 0.0, 1.0];
+        }
+        gradient.map(|c| c / length)
+    }
+}
+
+impl Hittable for Fractal {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let direction = [0, 1, 2].map(|i| ray.direction[i]);
+        let speed = length(direction);
+        if speed <= 0.0 || !speed.is_finite() {
+            return false;
+        }
+
+        // March inside the sphere around the fractal only.
+        let radius = self.kind.radius() * self.size;
+        let oc = [0, 1, 2].map(|i| ray.origin[i] - self.center[i]);
+        let a = speed * speed;
+        let b = (0..3).map(|i| oc[i] * direction[i]).sum::<Float>();
+        let c = (0..3).map(|i| oc[i] * oc[i]).sum::<Float>() - radius * radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return false;
+        }
+        let root = discriminant.sqrt();
+        let mut t = ((-b - root) / a).max(t_min);
+        let end = ((-b + root) / a).min(t_max);
+
+        let precision = self.precision * self.size;
+        let mut trap = 0.0;
+        let mut found = false;
+        for _ in 0..self.max_steps {
+            if t >= end {
+                break;
+            }
+            let point = [0, 1, 2].map(|i| ray.origin[i] + t * direction[i]);
+            let (distance, orbit) = self.distance(point);
+            if distance < precision {
+                trap = orbit;
+                found = true;
+                break;
+            }
+            t += distance / speed;
+        }
+        if !found {
+            return false;
+        }
+
+        let point = ray.point_at_parameter(t);
+        let mut n =
+            self.normal([point[0], point[1], point[2]], 0.5 * precision);
+        // Facing the ray: inside the precision the sign may flip.
+        if (0..3).map(|i| n[i] * direction[i]).sum::<Float>() > 0.0 {
+            n = n.map(|c| -c);
+        }
+        let (tangent, _) = orthonormal_basis(&n);
+
+        record.t = t;
+        record.point = point;
+        record.normal = arr1(&[n[0], n[1], n[2], 0.0]);
+        record.tangent = arr1(&[tangent[0], tangent[1], tangent[2], 0.0]);
+        record.uv = [trap, 0.5];
+        record.footprint = 0.0;
+        record.differentials = None;
+        record.material = self.material.clone();
+        record.alpha_test()
+    }
+}
+
+impl RayTraceable for Fractal {
+    fn validate(&self) -> Result<(), String> {
+        if !self.center.iter().all(|c| c.is_finite()) {
+            return Err("center is not finite".to_string());
+        }
+        if !(self.size > 0.0 && self.size.is_finite()) {
+            return Err(format!("invalid fractal size {}", self.size));
+        }
+        if !(self.precision > 0.0 && self.precision < 1.0) {
+            return Err(format!(
+                "invalid fractal precision {}",
+                self.precision
+            ));
+        }
+        if let FractalKind::Mandelbulb { power, .. } = self.kind {
+            if !(power > 1.0 && power.is_finite()) {
+                return Err(format!("invalid mandelbulb power {}", power));
+            }
+        }
+        Ok(())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.kind.radius() * self.size;
+        let c = &self.center;
+        Some(Aabb::new(
+            [c[0] - r, c[1] - r, c[2] - r],
+            [c[0] + r, c[1] + r, c[2] + r],
+        ))
+    }
+
+    fn kind(&self) -> &'static str {
+        "fractal"
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.material.textures(textures);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        self.center = [0, 1, 2].map(|i| self.center[i] + offset[i]);
+    }
+}
+
+/**
+ * Colors evenly spaced along u, from the first at u = 0 to the last at
+ * u = 1, blended in between: textures lambertian fractals by their orbit
+ * traps.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<[Float; 3]>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<[Float; 3]>) -> Result<Palette, String> {
+        if colors.is_empty() {
+            return Err("empty palette".to_string());
+        }
+        Ok(Palette { colors })
+    }
+}
+
+impl TextureSampler for Palette {
+    fn sample(&self, uv: [Float; 2], _footprint: Float) -> [Float; 4] {
+        let last = self.colors.len() - 1;
+        let x = uv[0].clamp(0.0, 1.0) * last as Float;
+        let i = (x.floor() as usize).min(last);
+        let j = (i + 1).min(last);
+        let f = x - i as Float;
+        let (a, b) = (self.colors[i], self.colors[j]);
+        [0, 1, 2, 3].map(|c| match c {
+            3 => 1.0,
+            _ => a[c] + (b[c] - a[c]) * f,
+        })
+    }
+
+    fn name(&self) -> String {
+        format!("palette({})", self.colors.len())
+    }
+}
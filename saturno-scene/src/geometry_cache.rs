@@ -0,0 +1,452 @@
+use crate::actor::count_work;
+use crate::actor::{Aabb, Hit, Hittable, RayTraceable};
+use crate::instance::Transform;
+use crate::material::Scattering;
+use crate::mesh::Mesh;
+use crate::texture::TextureSampler;
+use crate::texture_cache::CacheStats;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+// Meshes too large for memory (photogrammetry, city models), split into
+// chunks of nearby triangles loaded on demand and kept under a memory
+// budget, as the tiles of textures are (see `texture_cache`). Only the
+// bounds of the chunks stay resident: a ray loads the chunks whose bounds
+// it crosses, and the least recently used are evicted once they take more
+// than the budget, to be read again when a ray comes back to them.
+//
+// The chunks are reference counted: a chunk evicted while a ray is
+// crossing it stays valid until the ray is done with it. The vertex
+// normals of a chunk are those of the whole mesh, so the shading is
+// smooth across the seams between the chunks.
+
+/**
+ * The vertices and triangles of a chunk of a mesh, indexing its own
+ * vertices.
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChunkData {
+    pub positions: Vec<[Float; 3]>,
+    pub normals: Vec<[Float; 3]>,
+    pub uvs: Vec<[Float; 2]>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+/**
+ * Where the chunks of a mesh come from, e.g. a chunked mesh file.
+ */
+pub trait ChunkSource: Send + Sync {
+    /**
+     * Bounds of every chunk, around its triangles, known without reading
+     * them.
+     */
+    fn chunk_bounds(&self) -> Vec<Aabb>;
+
+    /**
+     * Number of triangles of every chunk.
+     */
+    fn chunk_triangles(&self) -> Vec<usize>;
+
+    fn read_chunk(&self, chunk: usize) -> Result<ChunkData, String>;
+}
+
+/**
+ * Identifies a mesh registered in a cache.
+ */
+pub type MeshId = usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct ChunkKey {
+    mesh: MeshId,
+    chunk: usize,
+}
+
+/**
+ * A loaded chunk, None if it could not be read (and is looked up as
+ * empty).
+ */
+type Chunk = Option<Arc<Mesh>>;
+
+fn chunk_bytes(chunk: &Chunk) -> usize {
+    chunk.as_ref().map_or(0, |mesh| mesh.bytes())
+}
+
+#[derive(Default)]
+struct CacheState {
+    sources: Vec<(Arc<dyn ChunkSource>, Arc<dyn Scattering>)>,
+    chunks: HashMap<ChunkKey, (Chunk, u64)>,
+    /**
+     * The resident chunks by time of last use, least recent first.
+     */
+    recency: BTreeMap<u64, ChunkKey>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+/**
+ * Chunks shared by all the meshes registered in it, the least recently
+ * used evicted once they take more than `capacity` bytes.
+ */
+pub struct GeometryCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+impl GeometryCache {
+    pub fn new(capacity: usize) -> GeometryCache {
+        GeometryCache {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /**
+     * Add a mesh of `material` whose chunks are read from `source` when
+     * first crossed by a ray. Nothing is loaded yet.
+     */
+    pub fn register(
+        &self,
+        source: Arc<dyn ChunkSource>,
+        material: Arc<dyn Scattering>,
+    ) -> MeshId {
+        let mut state = self.state.lock().unwrap();
+        state.sources.push((source, material));
+        state.sources.len() - 1
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /**
+     * A chunk of a registered mesh, read from its source if it is not
+     * resident. The chunk stays valid after it is evicted.
+     */
+    pub fn chunk(&self, mesh: MeshId, chunk: usize) -> Chunk {
+        let key = ChunkKey { mesh, chunk };
+
+        let (source, material) = {
+            let mut state = self.state.lock().unwrap();
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some((chunk, last_use)) = state.chunks.get_mut(&key) {
+                let chunk = chunk.clone();
+                let previous = std::mem::replace(last_use, clock);
+                state.recency.remove(&previous);
+                state.recency.insert(clock, key);
+                state.stats.hits += 1;
+                return chunk;
+            }
+            state.stats.misses += 1;
+            state.sources[mesh].clone()
+        };
+
+        // Read and build without holding the lock, other threads keep
+        // tracing (two threads may read the same chunk, the last one is
+        // kept).
+        let loaded = self.read(source.as_ref(), material, key);
+
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        if let Some((previous, last_use)) =
+            state.chunks.insert(key, (loaded.clone(), clock))
+        {
+            state.recency.remove(&last_use);
+            state.stats.resident_bytes -= chunk_bytes(&previous);
+        }
+        state.recency.insert(clock, key);
+        state.stats.resident_bytes += chunk_bytes(&loaded);
+
+        // Always keep the chunk just read, however small the capacity.
+        while state.stats.resident_bytes > self.capacity
+            && state.recency.len() > 1
+        {
+            let (&oldest, &evicted) = state.recency.iter().next().unwrap();
+            state.recency.remove(&oldest);
+            if let Some((evicted, _)) = state.chunks.remove(&evicted) {
+                state.stats.resident_bytes -= chunk_bytes(&evicted);
+                state.stats.evictions += 1;
+            }
+        }
+        loaded
+    }
+
+    fn read(
+        &self,
+        source: &dyn ChunkSource,
+        material: Arc<dyn Scattering>,
+        key: ChunkKey,
+    ) -> Chunk {
+        let mesh = source.read_chunk(key.chunk).and_then(|data| {
+            Mesh::new(data.positions, data.uvs, data.triangles, material)?
+                .with_normals(data.normals)
+        });
+        match mesh {
+            Ok(mesh) => Some(Arc::new(mesh)),
+            Err(e) => {
+                warn!(
+                    mesh = key.mesh,
+                    chunk = key.chunk,
+                    "failed to read mesh chunk: {}",
+                    e
+                );
+                self.state.lock().unwrap().stats.failures += 1;
+                None
+            }
+        }
+    }
+}
+
+/**
+ * Chunks per leaf of the hierarchy, at most.
+ */
+const LEAF_SIZE: usize = 2;
+
+enum Node {
+    /**
+     * The chunks `order[first..first + count]`.
+     */
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+/**
+ * A mesh whose chunks are loaded in a `GeometryCache` when rays cross
+ * them, traversed through a hierarchy of the bounds of its chunks.
+ */
+pub struct StreamedMesh {
+    cache: Arc<GeometryCache>,
+    id: MeshId,
+    material: Arc<dyn Scattering>,
+    triangles: usize,
+    /**
+     * The chunks in the order of the leaves of the hierarchy.
+     */
+    order: Vec<usize>,
+    nodes: Vec<Node>,
+    /**
+     * Where the mesh was moved to (see `translate`), the chunks staying
+     * where their source has them.
+     */
+    offset: [Float; 3],
+    transform: Transform,
+}
+
+impl StreamedMesh {
+    /**
+     * A mesh of `material` whose chunks are read from `source` when first
+     * crossed by a ray, into `cache`.
+     */
+    pub fn new(
+        cache: Arc<GeometryCache>,
+        source: Arc<dyn ChunkSource>,
+        material: Arc<dyn Scattering>,
+    ) -> Result<StreamedMesh, String> {
+        let bounds = source.chunk_bounds();
+        let triangles = source.chunk_triangles();
+        if bounds.is_empty() {
+            return Err("mesh without chunks".to_string());
+        }
+        if triangles.len() != bounds.len() {
+            return Err(format!(
+                "{} triangle counts for {} chunks",
+                triangles.len(),
+                bounds.len()
+            ));
+        }
+        let corners = bounds.iter().flat_map(|b| b.min.iter().chain(&b.max));
+        if !corners.clone().all(|c| c.is_finite()) {
+            return Err("chunk bounds are not finite".to_string());
+        }
+
+        let mut order: Vec<usize> = (0..bounds.len()).collect();
+        let mut nodes = vec![];
+        StreamedMesh::build_node(&bounds, &mut order, 0, &mut nodes);
+        let id = cache.register(source, material.clone());
+        Ok(StreamedMesh {
+            cache,
+            id,
+            material,
+            triangles: triangles.iter().sum(),
+            order,
+            nodes,
+            offset: [0.0; 3],
+            transform: Transform::identity(),
+        })
+    }
+
+    /**
+     * Number of chunks of the mesh.
+     */
+    pub fn chunks(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn cache(&self) -> &Arc<GeometryCache> {
+        &self.cache
+    }
+
+    /**
+     * Build the hierarchy, splitting the chunks at the median of their
+     * centers along the longest axis of their bounds, as for the
+     * triangles of meshes.
+     */
+    fn build_node(
+        bounds: &[Aabb],
+        order: &mut [usize],
+        first: usize,
+        nodes: &mut Vec<Node>,
+    ) -> usize {
+        let node_bounds = order
+            .iter()
+            .map(|&i| bounds[i])
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+        let index = nodes.len();
+        if order.len() <= LEAF_SIZE {
+            nodes.push(Node::Leaf {
+                bounds: node_bounds,
+                first,
+                count: order.len(),
+            });
+            return index;
+        }
+
+        let center = |i: usize, axis: usize| {
+            (bounds[i].min[axis] + bounds[i].max[axis]) / 2.0
+        };
+        let extent =
+            [0, 1, 2].map(|axis| node_bounds.max[axis] - node_bounds.min[axis]);
+        let axis = if extent[0] >= extent[1] && extent[0] >= extent[2] {
+            0
+        } else if extent[1] >= extent[2] {
+            1
+        } else {
+            2
+        };
+        let middle = order.len() / 2;
+        order.select_nth_unstable_by(middle, |&a, &b| {
+            center(a, axis).total_cmp(&center(b, axis))
+        });
+
+        // Placeholder, replaced once the children are built.
+        nodes.push(Node::Leaf {
+            bounds: node_bounds,
+            first,
+            count: 0,
+        });
+        let (left_order, right_order) = order.split_at_mut(middle);
+        let left = StreamedMesh::build_node(bounds, left_order, first, nodes);
+        let right = StreamedMesh::build_node(
+            bounds,
+            right_order,
+            first + middle,
+            nodes,
+        );
+        nodes[index] = Node::Inner {
+            bounds: node_bounds,
+            left,
+            right,
+        };
+        index
+    }
+}
+
+impl Hittable for StreamedMesh {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let local = self.transform.local_ray(ray);
+        let origin = [local.origin[0], local.origin[1], local.origin[2]];
+        let inverse_direction = [0, 1, 2].map(|i| 1.0 / local.direction[i]);
+
+        let mut closest = t_max;
+        let mut visited = 0;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            visited += 1;
+            let node = &self.nodes[node];
+            if !node.bounds().is_hit(
+                &origin,
+                &inverse_direction,
+                t_min,
+                closest,
+            ) {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for &chunk in &self.order[first..first + count] {
+                        let Some(mesh) = self.cache.chunk(self.id, chunk)
+                        else {
+                            continue;
+                        };
+                        if mesh.is_hit(&local, t_min, closest, record) {
+                            closest = record.t;
+                            self.transform.place(ray, record);
+                        }
+                    }
+                }
+                Node::Inner { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        // The triangles of the chunks count themselves.
+        count_work(visited, 0);
+        closest < t_max
+    }
+}
+
+impl RayTraceable for StreamedMesh {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.nodes[0].bounds().translated(&self.offset))
+    }
+
+    fn kind(&self) -> &'static str {
+        "streamed mesh"
+    }
+
+    fn triangles(&self) -> usize {
+        self.triangles
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.material.textures(textures);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        self.offset = [0, 1, 2].map(|i| self.offset[i] + offset[i]);
+        self.transform = Transform::new(self.offset, [0.0; 3], [1.0; 3])
+            .unwrap_or_else(|_| Transform::identity());
+    }
+}
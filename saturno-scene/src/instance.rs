@@ -0,0 +1,705 @@
+use crate::actor::count_work;
+use crate::actor::Aabb;
+use crate::actor::Hit;
+use crate::actor::HitDifferentials;
+use crate::actor::Hittable;
+use crate::actor::RayTraceable;
+use crate::camera::Camera;
+use crate::material::Scattering;
+use crate::texture::TextureSampler;
+use ndarray::{arr1, Array1};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::RayDifferentials;
+use std::sync::Arc;
+
+// Instancing: many copies of a prototype (a car, a tree) placed by their
+// own transforms, sharing its geometry. The prototype is built once, with
+// the hierarchies of its meshes (the bottom level); the instances of it are
+// traversed through a hierarchy of their bounds in the scene (the top
+// level), each ray hitting one brought into the space of the prototype by
+// the inverse of its transform, and the hit brought back.
+//
+// An instance may override some materials of the prototype (the paint of
+// a car): a hit on a part of one of those materials takes its replacement
+// instead. The instances only hold the materials they replace, and the
+// same replacement may be shared by many of them (see `MaterialLibrary`).
+//
+// A prototype may come in coarser levels of detail, which take over for
+// the instances far from the camera, or small on the screen (the diameter
+// of their bounding sphere in pixels). The level of each instance is
+// chosen whenever the camera is set, and kept for every ray: shadows and
+// reflections see the same level as the camera.
+
+type Vector = [Float; 3];
+type Matrix = [[Float; 3]; 3];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 3]; 3];
+    for (i, row) in product.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    product
+}
+
+fn apply(m: &Matrix, v: &Vector) -> Vector {
+    [0, 1, 2].map(|i| m[i][0] * v[0] + m[i][1] * v[1] + m[i][2] * v[2])
+}
+
+fn transpose(m: &Matrix) -> Matrix {
+    [0, 1, 2].map(|i| [0, 1, 2].map(|j| m[j][i]))
+}
+
+fn determinant(m: &Matrix) -> Float {
+    m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+}
+
+/**
+ * Inverse by the cofactors, None if singular.
+ */
+fn inverse(m: &Matrix) -> Option<Matrix> {
+    let det = determinant(m);
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    // The adjugate is the transpose of the cofactors.
+    Some([0, 1, 2].map(|i| [0, 1, 2].map(|j| cofactor(j, i) / det)))
+}
+
+fn vector(a: &Array1<Float>) -> Vector {
+    [a[0], a[1], a[2]]
+}
+
+fn direction(v: Vector) -> Array1<Float> {
+    arr1(&[v[0], v[1], v[2], 0.0])
+}
+
+fn normalize(v: Vector) -> Vector {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if length > 0.0 {
+        v.map(|c| c / length)
+    } else {
+        v
+    }
+}
+
+/**
+ * Placement of an instance: scaled by `scale` along the axes, rotated
+ * about x, then y, then z by `rotation` (degrees), then moved by
+ * `translation`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    linear: Matrix,
+    inverse: Matrix,
+    translation: Vector,
+}
+
+impl Transform {
+    pub fn new(
+        translation: Vector,
+        rotation: Vector,
+        scale: Vector,
+    ) -> Result<Transform, String> {
+        if !translation.iter().chain(&rotation).all(|c| c.is_finite()) {
+            return Err("instance placement is not finite".to_string());
+        }
+        let [x, y, z] = rotation.map(|degrees| degrees * consts::PI / 180.0);
+        let rx = [
+            [1.0, 0.0, 0.0],
+            [0.0, x.cos(), -x.sin()],
+            [0.0, x.sin(), x.cos()],
+        ];
+        let ry = [
+            [y.cos(), 0.0, y.sin()],
+            [0.0, 1.0, 0.0],
+            [-y.sin(), 0.0, y.cos()],
+        ];
+        let rz = [
+            [z.cos(), -z.sin(), 0.0],
+            [z.sin(), z.cos(), 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let s = [
+            [scale[0], 0.0, 0.0],
+            [0.0, scale[1], 0.0],
+            [0.0, 0.0, scale[2]],
+        ];
+        let linear = multiply(&rz, &multiply(&ry, &multiply(&rx, &s)));
+        let inverse = inverse(&linear)
+            .ok_or_else(|| format!("invalid instance scale {:?}", scale))?;
+        Ok(Transform {
+            linear,
+            inverse,
+            translation,
+        })
+    }
+
+    /**
+     * The transform by `linear` (by rows), then moved by `translation`.
+     */
+    pub fn from_matrix(
+        linear: [[Float; 3]; 3],
+        translation: Vector,
+    ) -> Result<Transform, String> {
+        let inverse = inverse(&linear)
+            .ok_or_else(|| format!("singular instance matrix {:?}", linear))?;
+        Ok(Transform {
+            linear,
+            inverse,
+            translation,
+        })
+    }
+
+    /**
+     * This transform, then `outer`.
+     */
+    pub fn then(&self, outer: &Transform) -> Transform {
+        Transform {
+            linear: multiply(&outer.linear, &self.linear),
+            inverse: multiply(&self.inverse, &outer.inverse),
+            translation: outer.point(&self.translation),
+        }
+    }
+
+    pub fn identity() -> Transform {
+        let linear = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        Transform {
+            linear,
+            inverse: linear,
+            translation: [0.0; 3],
+        }
+    }
+
+    pub fn point(&self, p: &Vector) -> Vector {
+        let moved = apply(&self.linear, p);
+        [0, 1, 2].map(|i| moved[i] + self.translation[i])
+    }
+
+    pub fn vector(&self, v: &Vector) -> Vector {
+        apply(&self.linear, v)
+    }
+
+    /**
+     * A normal of the prototype in the scene: by the inverse transpose,
+     * so it stays perpendicular to the surface (not of unit length).
+     */
+    pub fn normal(&self, n: &Vector) -> Vector {
+        apply(&transpose(&self.inverse), n)
+    }
+
+    pub fn local_point(&self, p: &Vector) -> Vector {
+        let offset = [0, 1, 2].map(|i| p[i] - self.translation[i]);
+        apply(&self.inverse, &offset)
+    }
+
+    pub fn local_vector(&self, v: &Vector) -> Vector {
+        apply(&self.inverse, v)
+    }
+
+    /**
+     * The box enclosing `bounds` placed by the transform.
+     */
+    pub fn bounds(&self, bounds: &Aabb) -> Aabb {
+        let corners = bounds.corners().map(|corner| self.point(&corner));
+        corners[1..]
+            .iter()
+            .fold(Aabb::new(corners[0], corners[0]), |bounds, corner| {
+                bounds.union(&Aabb::new(*corner, *corner))
+            })
+    }
+
+    /**
+     * `ray` in the space of the prototype. Its direction keeps the scale,
+     * so the distances along it are the same in both spaces.
+     */
+    pub(crate) fn local_ray(&self, ray: &Ray) -> Ray {
+        let point = |p: &Array1<Float>| {
+            let local = self.local_point(&vector(p));
+            arr1(&[local[0], local[1], local[2], 1.0])
+        };
+        let along =
+            |v: &Array1<Float>| direction(self.local_vector(&vector(v)));
+        Ray {
+            origin: point(&ray.origin),
+            direction: along(&ray.direction),
+            differentials: ray.differentials.as_ref().map(|differentials| {
+                RayDifferentials {
+                    x_origin: point(&differentials.x_origin),
+                    x_direction: along(&differentials.x_direction),
+                    y_origin: point(&differentials.y_origin),
+                    y_direction: along(&differentials.y_direction),
+                }
+            }),
+            time: ray.time,
+        }
+    }
+
+    /**
+     * Bring back the hit of a ray in the local space (see `local_ray`),
+     * `ray` being the one in the scene.
+     */
+    pub(crate) fn place(&self, ray: &Ray, record: &mut Hit) {
+        record.point = ray.point_at_parameter(record.t);
+        let normal = normalize(self.normal(&vector(&record.normal)));
+        let tangent = self.vector(&vector(&record.tangent));
+        // Perpendicular to the normal again, if the scale sheared them.
+        let along = (0..3).map(|i| tangent[i] * normal[i]).sum::<Float>();
+        record.normal = direction(normal);
+        record.tangent = direction(normalize(
+            [0, 1, 2].map(|i| tangent[i] - along * normal[i]),
+        ));
+        if let Some(differentials) = &mut record.differentials {
+            let by = |v: &Array1<Float>, f: &dyn Fn(&Vector) -> Vector| {
+                direction(f(&vector(v)))
+            };
+            *differentials = HitDifferentials {
+                dpdx: by(&differentials.dpdx, &|v| self.vector(v)),
+                dpdy: by(&differentials.dpdy, &|v| self.vector(v)),
+                dndx: by(&differentials.dndx, &|v| self.normal(v)),
+                dndy: by(&differentials.dndy, &|v| self.normal(v)),
+            };
+        }
+    }
+}
+
+/**
+ * The parts of a prototype, hit as one actor whatever their visibility
+ * (that of the instances counts).
+ */
+pub struct Prototype {
+    pub parts: Vec<Box<dyn RayTraceable>>,
+}
+
+impl Prototype {
+    pub fn new(parts: Vec<Box<dyn RayTraceable>>) -> Prototype {
+        Prototype { parts }
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.parts
+            .iter()
+            .map(|part| part.bounding_box())
+            .reduce(|a, b| Some(a?.union(&b?)))
+            .flatten()
+    }
+
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let mut closest = t_max;
+        let mut part_hit = Hit::new();
+        for part in &self.parts {
+            if part.is_hit(ray, t_min, closest, &mut part_hit) {
+                closest = part_hit.t;
+                *record = Hit::copy(&part_hit);
+            }
+        }
+        closest < t_max
+    }
+}
+
+/**
+ * A copy of a prototype placed by `transform`, each of its materials
+ * that is the first of a pair of `materials` replaced by the second.
+ */
+#[derive(Clone)]
+pub struct Instance {
+    pub transform: Transform,
+    pub materials: Vec<(Arc<dyn Scattering>, Arc<dyn Scattering>)>,
+    /**
+     * The level of detail rendered, 0 for the prototype itself (see
+     * `Instances::levels`).
+     */
+    pub level: usize,
+}
+
+impl Instance {
+    pub fn new(transform: Transform) -> Instance {
+        Instance {
+            transform,
+            materials: vec![],
+            level: 0,
+        }
+    }
+
+    /**
+     * The material of the instance for `material` of the prototype.
+     */
+    fn material(&self, material: &Arc<dyn Scattering>) -> Arc<dyn Scattering> {
+        self.materials
+            .iter()
+            .find(|(from, _)| Arc::ptr_eq(from, material))
+            .map_or_else(|| material.clone(), |(_, to)| to.clone())
+    }
+
+    /**
+     * The hit of the prototype, in its space, brought back to the scene
+     * along `ray`, of which it is the hit.
+     */
+    fn place(&self, ray: &Ray, record: &mut Hit) {
+        self.transform.place(ray, record);
+        record.material = self.material(&record.material);
+    }
+}
+
+/**
+ * When a coarser level of detail takes over: beyond a distance from the
+ * camera, or for instances smaller on the screen than a number of pixels.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Switch {
+    Distance(Float),
+    Pixels(Float),
+}
+
+/**
+ * Instances per leaf of the hierarchy, at most.
+ */
+const LEAF_SIZE: usize = 2;
+
+enum Node {
+    /**
+     * The instances `first..first + count` (in the order of the build).
+     */
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+/**
+ * The `instances` of a shared `prototype`, traversed through a hierarchy
+ * of their bounds.
+ */
+pub struct Instances {
+    prototype: Arc<Prototype>,
+    levels: Vec<(Switch, Arc<Prototype>)>,
+    instances: Vec<Instance>,
+    /**
+     * Enclosing the prototype at every level, in its space.
+     */
+    bounds: Aabb,
+    nodes: Vec<Node>,
+}
+
+impl Instances {
+    pub fn new(
+        prototype: Arc<Prototype>,
+        instances: Vec<Instance>,
+    ) -> Result<Instances, String> {
+        Instances::with_levels(prototype, vec![], instances)
+    }
+
+    /**
+     * Instances of a prototype with coarser `levels` of detail, from the
+     * finest, each taking over from the previous one at its switch.
+     */
+    pub fn with_levels(
+        prototype: Arc<Prototype>,
+        levels: Vec<(Switch, Arc<Prototype>)>,
+        instances: Vec<Instance>,
+    ) -> Result<Instances, String> {
+        if instances.is_empty() {
+            return Err("prototype without instances".to_string());
+        }
+        for (switch, _) in &levels {
+            let (Switch::Distance(value) | Switch::Pixels(value)) = *switch;
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!("invalid level of detail {:?}", switch));
+            }
+        }
+        let mut bounds = None;
+        for level in std::iter::once(&prototype)
+            .chain(levels.iter().map(|(_, level)| level))
+        {
+            let level = level
+                .bounding_box()
+                .ok_or_else(|| "unbounded prototype".to_string())?;
+            bounds = Some(match bounds {
+                Some(bounds) => level.union(&bounds),
+                None => level,
+            });
+        }
+        let mut set = Instances {
+            prototype,
+            levels,
+            instances,
+            bounds: bounds.unwrap(),
+            nodes: vec![],
+        };
+        set.build();
+        Ok(set)
+    }
+
+    pub fn prototype(&self) -> &Arc<Prototype> {
+        &self.prototype
+    }
+
+    /**
+     * The coarser levels of detail of the prototype: level i + 1 of the
+     * instances is the i-th.
+     */
+    pub fn levels(&self) -> &[(Switch, Arc<Prototype>)] {
+        &self.levels
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.instances
+    }
+
+    fn level(&self, level: usize) -> &Prototype {
+        match level {
+            0 => &self.prototype,
+            _ => &self.levels[level - 1].1,
+        }
+    }
+
+    fn prototypes(&self) -> impl Iterator<Item = &Arc<Prototype>> {
+        std::iter::once(&self.prototype)
+            .chain(self.levels.iter().map(|(_, level)| level))
+    }
+
+    /**
+     * Build the hierarchy, splitting the instances at the median of their
+     * centers along the longest axis of their bounds, as for the
+     * triangles of meshes. Reorders the instances so the leaves are
+     * ranges of them.
+     */
+    fn build(&mut self) {
+        let bounds: Vec<Aabb> = self
+            .instances
+            .iter()
+            .map(|instance| instance.transform.bounds(&self.bounds))
+            .collect();
+        let mut order: Vec<usize> = (0..self.instances.len()).collect();
+        let mut nodes = vec![];
+        Instances::build_node(&bounds, &mut order, 0, &mut nodes);
+        self.instances =
+            order.iter().map(|&i| self.instances[i].clone()).collect();
+        self.nodes = nodes;
+    }
+
+    fn build_node(
+        bounds: &[Aabb],
+        order: &mut [usize],
+        first: usize,
+        nodes: &mut Vec<Node>,
+    ) -> usize {
+        let node_bounds = order
+            .iter()
+            .map(|&i| bounds[i])
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+        let index = nodes.len();
+        if order.len() <= LEAF_SIZE {
+            nodes.push(Node::Leaf {
+                bounds: node_bounds,
+                first,
+                count: order.len(),
+            });
+            return index;
+        }
+
+        let center = |i: usize, axis: usize| {
+            (bounds[i].min[axis] + bounds[i].max[axis]) / 2.0
+        };
+        let extent =
+            [0, 1, 2].map(|axis| node_bounds.max[axis] - node_bounds.min[axis]);
+        let axis = if extent[0] >= extent[1] && extent[0] >= extent[2] {
+            0
+        } else if extent[1] >= extent[2] {
+            1
+        } else {
+            2
+        };
+        let middle = order.len() / 2;
+        order.select_nth_unstable_by(middle, |&a, &b| {
+            center(a, axis).total_cmp(&center(b, axis))
+        });
+
+        // Placeholder, replaced once the children are built.
+        nodes.push(Node::Leaf {
+            bounds: node_bounds,
+            first,
+            count: 0,
+        });
+        let (left_order, right_order) = order.split_at_mut(middle);
+        let left = Instances::build_node(bounds, left_order, first, nodes);
+        let right =
+            Instances::build_node(bounds, right_order, first + middle, nodes);
+        nodes[index] = Node::Inner {
+            bounds: node_bounds,
+            left,
+            right,
+        };
+        index
+    }
+}
+
+impl Hittable for Instances {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let origin = vector(&ray.origin);
+        let inverse_direction = vector(&ray.direction).map(|d| 1.0 / d);
+
+        let mut closest = t_max;
+        let (mut visited, mut tests) = (0, 0);
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            visited += 1;
+            let node = &self.nodes[node];
+            if !node.bounds().is_hit(
+                &origin,
+                &inverse_direction,
+                t_min,
+                closest,
+            ) {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    tests += count;
+                    for instance in &self.instances[first..first + count] {
+                        let local = instance.transform.local_ray(ray);
+                        let prototype = self.level(instance.level);
+                        if prototype.is_hit(&local, t_min, closest, record) {
+                            closest = record.t;
+                            instance.place(ray, record);
+                        }
+                    }
+                }
+                Node::Inner { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        count_work(visited, tests);
+        closest < t_max
+    }
+}
+
+impl RayTraceable for Instances {
+    fn validate(&self) -> Result<(), String> {
+        for prototype in self.prototypes() {
+            for part in &prototype.parts {
+                part.validate()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(*self.nodes[0].bounds())
+    }
+
+    fn kind(&self) -> &'static str {
+        "instances"
+    }
+
+    /**
+     * Those of the prototype at every level, stored once.
+     */
+    fn triangles(&self) -> usize {
+        self.prototypes()
+            .flat_map(|prototype| &prototype.parts)
+            .map(|part| part.triangles())
+            .sum()
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        for part in self.prototypes().flat_map(|prototype| &prototype.parts) {
+            part.textures(textures);
+        }
+        // Once per material, however many instances share it.
+        let mut seen: Vec<&Arc<dyn Scattering>> = vec![];
+        for instance in &self.instances {
+            for (_, material) in &instance.materials {
+                if !seen.iter().any(|other| Arc::ptr_eq(other, material)) {
+                    seen.push(material);
+                    material.textures(textures);
+                }
+            }
+        }
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        for instance in &mut self.instances {
+            let translation = &mut instance.transform.translation;
+            *translation = [0, 1, 2].map(|i| translation[i] + offset[i]);
+        }
+        for node in &mut self.nodes {
+            match node {
+                Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => {
+                    *bounds = bounds.translated(offset);
+                }
+            }
+        }
+    }
+
+    /**
+     * Each instance at the coarsest level whose switch it passes, and
+     * those of the finer levels, by the bounding sphere of its box.
+     */
+    fn select_detail(&mut self, camera: &Camera) {
+        if self.levels.is_empty() {
+            return;
+        }
+        for instance in &mut self.instances {
+            let bounds = instance.transform.bounds(&self.bounds);
+            let center =
+                [0, 1, 2].map(|i| 0.5 * (bounds.min[i] + bounds.max[i]));
+            let diameter = (0..3)
+                .map(|i| (bounds.max[i] - bounds.min[i]).powi(2))
+                .sum::<Float>()
+                .sqrt();
+            let distance = (0..3)
+                .map(|i| (center[i] - camera.origin[i]).powi(2))
+                .sum::<Float>()
+                .sqrt();
+            let pixels = diameter / camera.pixel_size(distance);
+            instance.level = self
+                .levels
+                .iter()
+                .take_while(|(switch, _)| match *switch {
+                    Switch::Distance(beyond) => distance > beyond,
+                    Switch::Pixels(below) => pixels < below,
+                })
+                .count();
+        }
+    }
+}
@@ -0,0 +1,34 @@
+// Description of the scenes: the actors (geometry), their materials and the
+// camera, plus a few ready made scenes. The wide BVH the meshes are
+// traced through is private.
+
+pub mod actor;
+pub mod atmosphere;
+pub mod black_hole;
+pub mod bvh;
+pub mod camera;
+pub mod clip;
+pub mod color;
+pub mod description;
+pub mod environment;
+pub mod ephemeris;
+pub mod fog;
+pub mod fractal;
+pub mod geometry_cache;
+pub mod instance;
+pub mod library;
+pub mod light;
+pub mod material;
+pub mod mesh;
+pub mod metaballs;
+pub mod motion;
+pub mod ring;
+pub mod scatter;
+pub mod scenes;
+pub mod sky;
+pub mod stars;
+pub mod texture;
+pub mod texture_cache;
+pub mod units;
+pub mod volume;
+pub(crate) mod wide_bvh;
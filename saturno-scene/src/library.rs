@@ -0,0 +1,54 @@
+use crate::material::Scattering;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// Named materials shared by the actors of a scene: every actor using a
+// material of the library points to the same instance.
+
+#[derive(Clone, Default)]
+pub struct MaterialLibrary {
+    materials: BTreeMap<String, Arc<dyn Scattering>>,
+}
+
+impl MaterialLibrary {
+    pub fn new() -> MaterialLibrary {
+        MaterialLibrary::default()
+    }
+
+    /**
+     * Add a material under `name`, replacing the one of the same name
+     * (actors built with the previous one keep it). Returns the shared
+     * material, to hand to the actors.
+     */
+    pub fn insert(
+        &mut self,
+        name: &str,
+        material: Arc<dyn Scattering>,
+    ) -> Arc<dyn Scattering> {
+        self.materials.insert(name.to_string(), material.clone());
+        material
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Scattering>> {
+        self.materials.get(name).cloned()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.materials.contains_key(name)
+    }
+
+    /**
+     * Names of the materials, in alphabetical order.
+     */
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.materials.keys().map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+}
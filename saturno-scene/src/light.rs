@@ -0,0 +1,786 @@
+use crate::texture::TextureSampler;
+use ndarray::{arr1, Array1};
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+use std::sync::Arc;
+
+// Lights that are not actors: the renderer samples them directly at every
+// diffuse bounce (next event estimation) and casts a shadow ray towards
+// them, instead of waiting for paths to hit them by chance.
+
+/**
+ * A direction towards a light from a lit point.
+ */
+pub struct LightSample {
+    /**
+     * Unit direction from the point towards the light.
+     */
+    pub direction: Array1<Float>,
+    /**
+     * Distance to the light along the direction, infinite for distant
+     * lights. Actors closer than this cast a shadow.
+     */
+    pub distance: Float,
+    /**
+     * Radiance arriving along the direction, divided by the probability
+     * of choosing it.
+     */
+    pub radiance: Array1<Float>,
+}
+
+/**
+ * A photon leaving a light (see `Light::emit`).
+ */
+pub struct Emission {
+    pub origin: [Float; 3],
+    /**
+     * Unit direction of the photon.
+     */
+    pub direction: [Float; 3],
+    /**
+     * Power of the light carried along the direction (in lumens, for
+     * the photometric units of the lights), divided by the probability
+     * of choosing it.
+     */
+    pub power: Array1<Float>,
+}
+
+/**
+ * Where a light shines from, and how much, for a hierarchy of the lights
+ * to pick them by (see `Light::bounds`).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LightBounds {
+    pub position: [Float; 3],
+    /**
+     * Radius of the sphere around the position it shines from.
+     */
+    pub radius: Float,
+    /**
+     * Intensity (in candela) times the luminance of its color, in the
+     * brightest direction.
+     */
+    pub intensity: Float,
+    /**
+     * Unit axis of the cone the light shines in, and the cosine of its
+     * half angle (-1 for every direction).
+     */
+    pub axis: [Float; 3],
+    pub cos_cone: Float,
+}
+
+/**
+ * Luminance of an RGB color.
+ */
+fn luminance(c: &[Float; 3]) -> Float {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+/**
+ * Luminous efficacy of light at 555 nm, in lumens per watt: the most light
+ * a watt can give. Lamps give far less for each watt they draw (about 15
+ * lm/W for incandescent bulbs, 100 for LEDs).
+ */
+pub const MAX_EFFICACY: Float = 683.0;
+
+pub trait Light: Send + Sync {
+    /**
+     * Pick a direction towards the light from `point`, None if the light
+     * does not reach it.
+     */
+    fn sample(&self, point: &Array1<Float>) -> Option<LightSample>;
+
+    /**
+     * Radiance of the light seen from `origin` along `direction` by a ray
+     * escaping the scene, for lights of some size (e.g. the sun disk).
+     * Paths only gather it where they did not sample the lights.
+     */
+    fn emitted(
+        &self,
+        _origin: &Array1<Float>,
+        _direction: &Array1<Float>,
+    ) -> Option<Array1<Float>> {
+        None
+    }
+
+    /**
+     * Whether `sample` may pick `direction` from `origin`. Rays escaping
+     * along directions it never picks gather `emitted` even where the
+     * lights were sampled.
+     */
+    fn can_sample(
+        &self,
+        _origin: &Array1<Float>,
+        _direction: &Array1<Float>,
+    ) -> bool {
+        true
+    }
+
+    /**
+     * Whether the light surrounds the whole scene, in place of the
+     * background gradient.
+     */
+    fn environment(&self) -> bool {
+        false
+    }
+
+    /**
+     * Light group the light is accounted to.
+     */
+    fn light_group(&self) -> Option<String> {
+        None
+    }
+
+    /**
+     * Pick a photon leaving the light, for photon mapping. Distant lights
+     * shine on the sphere `bounds` (center and radius) around the scene.
+     * None for lights which do not emit photons (the environments).
+     */
+    fn emit(&self, _bounds: &([Float; 3], Float)) -> Option<Emission> {
+        None
+    }
+
+    /**
+     * Where the light shines from, for the hierarchy of the lights. None
+     * for the distant lights and the environments, which light the whole
+     * scene.
+     */
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    /**
+     * Check the light is well formed, otherwise return the reason why it
+     * can not be rendered.
+     */
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /**
+     * The light moved by `offset` (see `Canvas::recenter`), None for the
+     * lights which are not anywhere (distant lights and environments).
+     */
+    fn translated(&self, _offset: &[Float; 3]) -> Option<Arc<dyn Light>> {
+        None
+    }
+
+    /**
+     * Add the textures of the light (a gobo, a map) to `textures`.
+     */
+    fn textures<'a>(&'a self, _textures: &mut Vec<&'a dyn TextureSampler>) {}
+
+    fn name(&self) -> String;
+}
+
+/**
+ * Two unit vectors completing `w` into an orthonormal basis.
+ */
+pub fn orthonormal_basis(w: &[Float; 3]) -> ([Float; 3], [Float; 3]) {
+    let a = if w[0].abs() > 0.9 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    // u = normalize(a x w), v = w x u
+    let u = [
+        a[1] * w[2] - a[2] * w[1],
+        a[2] * w[0] - a[0] * w[2],
+        a[0] * w[1] - a[1] * w[0],
+    ];
+    let length = (u[0] * u[0] + u[1] * u[1] + u[2] * u[2]).sqrt();
+    let u = [u[0] / length, u[1] / length, u[2] / length];
+    let v = [
+        w[1] * u[2] - w[2] * u[1],
+        w[2] * u[0] - w[0] * u[2],
+        w[0] * u[1] - w[1] * u[0],
+    ];
+    (u, v)
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Distant light over a disk of the sky, the standard outdoor key light.
+ * Its `illuminance` is in lux on a surface facing it (about 100000 for the
+ * noon sun, radiances being in nits), spread uniformly over a disk
+ * `angular_diameter` degrees wide (0.53 for the sun) whose size softens
+ * the shadows. A diameter of 0 gives a point-like sun with hard shadows.
+ */
+#[derive(Clone, Debug)]
+pub struct Sun {
+    /**
+     * Direction towards the sun.
+     */
+    pub direction: [Float; 3],
+    pub angular_diameter: Float,
+    pub illuminance: Float,
+    pub color: [Float; 3],
+    pub light_group: Option<String>,
+}
+
+impl Sun {
+    pub fn new(direction: [Float; 3], illuminance: Float) -> Sun {
+        Sun {
+            direction,
+            angular_diameter: 0.53,
+            illuminance,
+            color: [1.0, 1.0, 1.0],
+            light_group: None,
+        }
+    }
+
+    fn unit_direction(&self) -> [Float; 3] {
+        let d = self.direction;
+        let length = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        [d[0] / length, d[1] / length, d[2] / length]
+    }
+
+    /**
+     * Cosine of the angular radius of the disk.
+     */
+    fn cos_radius(&self) -> Float {
+        (self.angular_diameter / 2.0 * consts::PI / 180.0).cos()
+    }
+
+    /**
+     * Solid angle of the disk.
+     */
+    pub fn solid_angle(&self) -> Float {
+        2.0 * consts::PI * (1.0 - self.cos_radius())
+    }
+
+    fn illuminance_rgb(&self) -> Array1<Float> {
+        let c = self.color;
+        arr1(&[c[0], c[1], c[2], 0.0]) * self.illuminance
+    }
+}
+
+impl Light for Sun {
+    /**
+     * A direction uniformly distributed over the disk, every one carrying
+     * the whole illuminance.
+     */
+    fn sample(&self, _point: &Array1<Float>) -> Option<LightSample> {
+        let w = self.unit_direction();
+        let cos_radius = self.cos_radius();
+        let direction = if cos_radius < 1.0 {
+            let cos_theta = random::gen_range(cos_radius, 1.0);
+            let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+            let phi = random::gen_range(0.0, 2.0 * consts::PI);
+            let (u, v) = orthonormal_basis(&w);
+            let (x, y) = (sin_theta * phi.cos(), sin_theta * phi.sin());
+            [0, 1, 2].map(|i| x * u[i] + y * v[i] + cos_theta * w[i])
+        } else {
+            w
+        };
+
+        Some(LightSample {
+            direction: Vec4::normalize(arr1(&[
+                direction[0],
+                direction[1],
+                direction[2],
+                0.0,
+            ])),
+            distance: Float::INFINITY,
+            radiance: self.illuminance_rgb(),
+        })
+    }
+
+    /**
+     * A photon from the disk, through the cross section of the bounds.
+     */
+    fn emit(&self, bounds: &([Float; 3], Float)) -> Option<Emission> {
+        let sample = self.sample(&arr1(&[0.0, 0.0, 0.0, 1.0]))?;
+        let w = [0, 1, 2].map(|i| sample.direction[i]);
+        let (center, radius) = *bounds;
+        let (u, v) = orthonormal_basis(&w);
+        let r = radius * random::gen_range(0.0, 1.0).sqrt();
+        let phi = random::gen_range(0.0, 2.0 * consts::PI);
+        let (x, y) = (r * phi.cos(), r * phi.sin());
+        Some(Emission {
+            origin: [0, 1, 2]
+                .map(|i| center[i] + radius * w[i] + x * u[i] + y * v[i]),
+            direction: w.map(|c| -c),
+            power: sample.radiance * (consts::PI * radius * radius),
+        })
+    }
+
+    fn emitted(
+        &self,
+        _origin: &Array1<Float>,
+        direction: &Array1<Float>,
+    ) -> Option<Array1<Float>> {
+        let solid_angle = self.solid_angle();
+        if solid_angle <= 0.0 {
+            return None;
+        }
+        let w = self.unit_direction();
+        let length = Vec4::l2_norm(direction.view());
+        let cosine =
+            (0..3).map(|i| direction[i] * w[i]).sum::<Float>() / length;
+        if cosine >= self.cos_radius() {
+            Some(self.illuminance_rgb() / solid_angle)
+        } else {
+            None
+        }
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.light_group.clone()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let d = self.direction;
+        let length = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        if !(length > 0.0 && length.is_finite()) {
+            return Err(format!("invalid sun direction {:?}", d));
+        }
+        if !(0.0..180.0).contains(&self.angular_diameter) {
+            return Err(format!(
+                "invalid sun angular diameter {}",
+                self.angular_diameter
+            ));
+        }
+        if !(self.illuminance >= 0.0 && self.illuminance.is_finite()) {
+            return Err(format!(
+                "invalid sun illuminance {}",
+                self.illuminance
+            ));
+        }
+        if !self.color.iter().all(|c| *c >= 0.0 && c.is_finite()) {
+            return Err(format!("invalid sun color {:?}", self.color));
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "sun({:.0}lx;{:.2}deg)",
+            self.illuminance, self.angular_diameter
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Point light shining within a cone, for stage lights and flashlights. Its
+ * `intensity` is in candela along the axis (an illuminance of intensity /
+ * distance^2 lux), fading out smoothly between the `inner_angle` and
+ * `outer_angle` cones (full apertures, in degrees). A `gobo` texture is
+ * projected over the outer cone, the square of the texture framing its
+ * circle, and filters the light like a slide in a projector.
+ */
+#[derive(Clone)]
+pub struct Spot {
+    pub position: [Float; 3],
+    /**
+     * Direction the spot points at.
+     */
+    pub direction: [Float; 3],
+    pub inner_angle: Float,
+    pub outer_angle: Float,
+    pub intensity: Float,
+    pub color: [Float; 3],
+    /**
+     * Projected texture, upright when the spot points horizontally (up
+     * is towards -z when it points straight up or down).
+     */
+    pub gobo: Option<Arc<dyn TextureSampler>>,
+    pub light_group: Option<String>,
+}
+
+impl Spot {
+    pub fn new(
+        position: [Float; 3],
+        direction: [Float; 3],
+        intensity: Float,
+    ) -> Spot {
+        Spot {
+            position,
+            direction,
+            inner_angle: 30.0,
+            outer_angle: 45.0,
+            intensity,
+            color: [1.0, 1.0, 1.0],
+            gobo: None,
+            light_group: None,
+        }
+    }
+
+    /**
+     * Solid angle of the cone, weighted by the falloff: the luminous flux
+     * over the intensity.
+     */
+    fn solid_angle(&self) -> Float {
+        let half_cos = |angle: Float| (angle / 2.0 * consts::PI / 180.0).cos();
+        let (cos_inner, cos_outer) =
+            (half_cos(self.inner_angle), half_cos(self.outer_angle));
+        // The smoothstep averages 1/2 between the cones.
+        2.0 * consts::PI * (1.0 - cos_inner + (cos_inner - cos_outer) / 2.0)
+    }
+
+    /**
+     * Luminous flux of the spot, in lumens (for a white light).
+     */
+    pub fn lumens(&self) -> Float {
+        self.intensity * self.solid_angle()
+    }
+
+    /**
+     * Set the intensity for a luminous flux of `lumens` through the cone,
+     * as lamps are rated.
+     */
+    pub fn set_lumens(&mut self, lumens: Float) {
+        let solid_angle = self.solid_angle();
+        self.intensity = if solid_angle > 0.0 {
+            lumens / solid_angle
+        } else {
+            0.0
+        };
+    }
+
+    /**
+     * The axis of the spot, and the right and up directions of the gobo.
+     */
+    fn frame(&self) -> ([Float; 3], [Float; 3], [Float; 3]) {
+        let d = self.direction;
+        let length = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        let w = [d[0] / length, d[1] / length, d[2] / length];
+        let up = if w[1].abs() > 0.999 {
+            [0.0, 0.0, -1.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        // right = normalize(w x up), up = right x w
+        let u = [
+            w[1] * up[2] - w[2] * up[1],
+            w[2] * up[0] - w[0] * up[2],
+            w[0] * up[1] - w[1] * up[0],
+        ];
+        let length = (u[0] * u[0] + u[1] * u[1] + u[2] * u[2]).sqrt();
+        let u = [u[0] / length, u[1] / length, u[2] / length];
+        let v = [
+            u[1] * w[2] - u[2] * w[1],
+            u[2] * w[0] - u[0] * w[2],
+            u[0] * w[1] - u[1] * w[0],
+        ];
+        (w, u, v)
+    }
+
+    /**
+     * Fraction of the intensity leaving along a direction at `cosine` from
+     * the axis, a smoothstep between the cones.
+     */
+    fn falloff(&self, cosine: Float) -> Float {
+        let half_cos = |angle: Float| (angle / 2.0 * consts::PI / 180.0).cos();
+        let (cos_inner, cos_outer) =
+            (half_cos(self.inner_angle), half_cos(self.outer_angle));
+        if cosine >= cos_inner {
+            1.0
+        } else if cosine <= cos_outer {
+            0.0
+        } else {
+            let t = (cosine - cos_outer) / (cos_inner - cos_outer);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+
+    /**
+     * Intensity (and color) of the light leaving along the unit direction
+     * `out`, None out of the cone.
+     */
+    fn shine(&self, out: &[Float; 3]) -> Option<Array1<Float>> {
+        let (w, u, v) = self.frame();
+        let dot = |a: [Float; 3]| (0..3).map(|i| a[i] * out[i]).sum::<Float>();
+        let cosine = dot(w);
+        let falloff = self.falloff(cosine);
+        if falloff <= 0.0 {
+            return None;
+        }
+
+        let c = self.color;
+        let mut intensity =
+            arr1(&[c[0], c[1], c[2], 0.0]) * (self.intensity * falloff);
+        if let Some(gobo) = &self.gobo {
+            let extent = (self.outer_angle / 2.0 * consts::PI / 180.0).tan();
+            let uv = [
+                0.5 + 0.5 * dot(u) / cosine / extent,
+                0.5 + 0.5 * dot(v) / cosine / extent,
+            ];
+            let filter = gobo.sample(uv, 0.0);
+            for i in 0..3 {
+                intensity[i] *= filter[i];
+            }
+        }
+        Some(intensity)
+    }
+}
+
+impl Light for Spot {
+    fn sample(&self, point: &Array1<Float>) -> Option<LightSample> {
+        let p = self.position;
+        let to_light = [p[0] - point[0], p[1] - point[1], p[2] - point[2]];
+        let distance_squared = to_light.iter().map(|c| c * c).sum::<Float>();
+        if distance_squared <= 0.0 {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        // Direction leaving the spot towards the point.
+        let out = to_light.map(|c| -c / distance);
+
+        let radiance = self.shine(&out)? / distance_squared;
+
+        Some(LightSample {
+            direction: arr1(&[
+                to_light[0] / distance,
+                to_light[1] / distance,
+                to_light[2] / distance,
+                0.0,
+            ]),
+            distance,
+            radiance,
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let (axis, _, _) = self.frame();
+        Some(LightBounds {
+            position: self.position,
+            radius: 0.0,
+            intensity: self.intensity * luminance(&self.color),
+            axis,
+            cos_cone: (self.outer_angle / 2.0 * consts::PI / 180.0).cos(),
+        })
+    }
+
+    /**
+     * A photon uniformly distributed over the outer cone.
+     */
+    fn emit(&self, _bounds: &([Float; 3], Float)) -> Option<Emission> {
+        let cos_outer = (self.outer_angle / 2.0 * consts::PI / 180.0).cos();
+        let solid_angle = 2.0 * consts::PI * (1.0 - cos_outer);
+        let cos_theta = random::gen_range(cos_outer, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = random::gen_range(0.0, 2.0 * consts::PI);
+        let (w, u, v) = self.frame();
+        let (x, y) = (sin_theta * phi.cos(), sin_theta * phi.sin());
+        let direction =
+            [0, 1, 2].map(|i| x * u[i] + y * v[i] + cos_theta * w[i]);
+        Some(Emission {
+            origin: self.position,
+            direction,
+            power: self.shine(&direction)? * solid_angle,
+        })
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.light_group.clone()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.position.iter().all(|c| c.is_finite()) {
+            return Err(format!("invalid spot position {:?}", self.position));
+        }
+        let d = self.direction;
+        let length = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+        if !(length > 0.0 && length.is_finite()) {
+            return Err(format!("invalid spot direction {:?}", d));
+        }
+        if !(0.0..180.0).contains(&self.outer_angle)
+            || !(0.0..=self.outer_angle).contains(&self.inner_angle)
+        {
+            return Err(format!(
+                "invalid spot cone angles {} and {}",
+                self.inner_angle, self.outer_angle
+            ));
+        }
+        if !(self.intensity >= 0.0 && self.intensity.is_finite()) {
+            return Err(format!("invalid spot intensity {}", self.intensity));
+        }
+        if !self.color.iter().all(|c| *c >= 0.0 && c.is_finite()) {
+            return Err(format!("invalid spot color {:?}", self.color));
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        match &self.gobo {
+            Some(gobo) => format!(
+                "spot({:.0}cd;{:.0}deg;{})",
+                self.intensity,
+                self.outer_angle,
+                gobo.name()
+            ),
+            None => {
+                format!(
+                    "spot({:.0}cd;{:.0}deg)",
+                    self.intensity, self.outer_angle
+                )
+            }
+        }
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        if let Some(gobo) = &self.gobo {
+            textures.push(gobo.as_ref());
+        }
+    }
+
+    fn translated(&self, offset: &[Float; 3]) -> Option<Arc<dyn Light>> {
+        let mut light = self.clone();
+        light.position = [0, 1, 2].map(|i| self.position[i] + offset[i]);
+        Some(Arc::new(light))
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Light shining evenly in every direction from `position`, its
+ * `intensity` in candela. With a `radius` it is a glowing sphere (a star):
+ * actors closer to the center than its surface do not shadow it, and rays
+ * escaping through it see a disk of intensity / (pi radius^2) nits. The
+ * sphere is not an actor, the other actors show through it.
+ */
+#[derive(Clone, Debug)]
+pub struct Omni {
+    pub position: [Float; 3],
+    pub radius: Float,
+    pub intensity: Float,
+    pub color: [Float; 3],
+    pub light_group: Option<String>,
+}
+
+impl Omni {
+    pub fn new(position: [Float; 3], intensity: Float) -> Omni {
+        Omni {
+            position,
+            radius: 0.0,
+            intensity,
+            color: [1.0, 1.0, 1.0],
+            light_group: None,
+        }
+    }
+
+    /**
+     * Luminous flux of the light, in lumens (for a white light).
+     */
+    pub fn lumens(&self) -> Float {
+        4.0 * consts::PI * self.intensity
+    }
+
+    /**
+     * Set the intensity for a luminous flux of `lumens`, as lamps are
+     * rated.
+     */
+    pub fn set_lumens(&mut self, lumens: Float) {
+        self.intensity = lumens / (4.0 * consts::PI);
+    }
+}
+
+impl Light for Omni {
+    fn sample(&self, point: &Array1<Float>) -> Option<LightSample> {
+        let p = self.position;
+        let to_light = [p[0] - point[0], p[1] - point[1], p[2] - point[2]];
+        let distance_squared = to_light.iter().map(|c| c * c).sum::<Float>();
+        let distance = distance_squared.sqrt();
+        if distance <= self.radius || distance_squared <= 0.0 {
+            return None;
+        }
+
+        // A sphere lights as a point at its center.
+        let c = self.color;
+        Some(LightSample {
+            direction: arr1(&[
+                to_light[0] / distance,
+                to_light[1] / distance,
+                to_light[2] / distance,
+                0.0,
+            ]),
+            distance: distance - self.radius,
+            radiance: arr1(&[c[0], c[1], c[2], 0.0])
+                * (self.intensity / distance_squared),
+        })
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        Some(LightBounds {
+            position: self.position,
+            radius: self.radius,
+            intensity: self.intensity * luminance(&self.color),
+            axis: [0.0, 0.0, 1.0],
+            cos_cone: -1.0,
+        })
+    }
+
+    /**
+     * A photon leaving the center in a uniformly distributed direction.
+     */
+    fn emit(&self, _bounds: &([Float; 3], Float)) -> Option<Emission> {
+        let z = random::gen_range(-1.0, 1.0);
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = random::gen_range(0.0, 2.0 * consts::PI);
+        let c = self.color;
+        Some(Emission {
+            origin: self.position,
+            direction: [r * phi.cos(), r * phi.sin(), z],
+            power: arr1(&[c[0], c[1], c[2], 0.0])
+                * (4.0 * consts::PI * self.intensity),
+        })
+    }
+
+    fn emitted(
+        &self,
+        origin: &Array1<Float>,
+        direction: &Array1<Float>,
+    ) -> Option<Array1<Float>> {
+        if self.radius <= 0.0 {
+            return None;
+        }
+        let p = self.position;
+        let offset = [origin[0] - p[0], origin[1] - p[1], origin[2] - p[2]];
+        let a = (0..3).map(|i| direction[i] * direction[i]).sum::<Float>();
+        let b = (0..3).map(|i| offset[i] * direction[i]).sum::<Float>();
+        let c = offset.iter().map(|o| o * o).sum::<Float>()
+            - self.radius * self.radius;
+        // In front of the ray, or around its origin.
+        if b * b - a * c <= 0.0 || (b > 0.0 && c > 0.0) {
+            return None;
+        }
+        let color = self.color;
+        let radiance =
+            self.intensity / (consts::PI * self.radius * self.radius);
+        Some(arr1(&[color[0], color[1], color[2], 0.0]) * radiance)
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.light_group.clone()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if !self.position.iter().all(|c| c.is_finite()) {
+            return Err(format!("invalid omni position {:?}", self.position));
+        }
+        if !(self.radius >= 0.0 && self.radius.is_finite()) {
+            return Err(format!("invalid omni radius {}", self.radius));
+        }
+        if !(self.intensity >= 0.0 && self.intensity.is_finite()) {
+            return Err(format!("invalid omni intensity {}", self.intensity));
+        }
+        if !self.color.iter().all(|c| *c >= 0.0 && c.is_finite()) {
+            return Err(format!("invalid omni color {:?}", self.color));
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> String {
+        format!("omni({:.0}cd;{})", self.intensity, self.radius)
+    }
+
+    fn translated(&self, offset: &[Float; 3]) -> Option<Arc<dyn Light>> {
+        let mut light = self.clone();
+        light.position = [0, 1, 2].map(|i| self.position[i] + offset[i]);
+        Some(Arc::new(light))
+    }
+}
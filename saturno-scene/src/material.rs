@@ -0,0 +1,2144 @@
+use crate::actor::Hit;
+use crate::fog::henyey_greenstein;
+use crate::fog::sample_henyey_greenstein;
+use crate::light::Omni;
+use crate::texture::Channel;
+use crate::texture::TextureSampler;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::RayDifferentials;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+
+use ndarray::{arr1, Array1};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+fn random_dir_unit_sphere() -> Array1<Float> {
+    let mut dir = arr1(&[Float::MAX, 0.0, 0.0]);
+    let min = -1.0;
+    let max = 1.0;
+
+    while Vec4::squared_length(dir.view()) >= 1.0 {
+        dir = arr1(&[
+            random::gen_range(min, max),
+            random::gen_range(min, max),
+            random::gen_range(min, max),
+            0.0,
+        ]);
+    }
+
+    dir
+}
+
+/**
+ *  Specular reflection.
+ *
+ *  Re = In + 2 |In . N| N
+ */
+pub(crate) fn reflect(fuzz: Float, incident: &Ray, hit: &Hit) -> Ray {
+    let dir = mirror(&incident.direction, &hit.normal);
+
+    let mut reflected =
+        Ray::new(hit.point.clone(), dir + fuzz * random_dir_unit_sphere());
+    reflected.differentials =
+        bend_differentials(incident, hit, &hit.normal, &reflected, |d, n| {
+            Some(mirror(d, n))
+        });
+    reflected
+}
+
+fn mirror(direction: &Array1<Float>, normal: &Array1<Float>) -> Array1<Float> {
+    direction.clone() - 2.0 * direction.dot(normal) * normal.clone()
+}
+
+/**
+ * Differentials of the ray `scattered` from a specular bounce at `hit`.
+ * The differential rays leave from their own hit points, and `bend`
+ * turns them as it turned the incident ray, given their direction and
+ * the (offset) `normal` of the bounce. Any randomness of the bounce
+ * (e.g. fuzz) is carried over from the scattered ray.
+ */
+pub(crate) fn bend_differentials(
+    incident: &Ray,
+    hit: &Hit,
+    normal: &Array1<Float>,
+    scattered: &Ray,
+    bend: impl Fn(&Array1<Float>, &Array1<Float>) -> Option<Array1<Float>>,
+) -> Option<RayDifferentials> {
+    let differentials = incident.differentials.as_ref()?;
+    let offsets = hit.differentials.as_ref()?;
+    // The normal of the bounce may be the flipped surface normal.
+    let sign = normal.dot(&hit.normal).signum();
+
+    let center = Vec4::normalize(bend(&incident.direction, normal)?);
+    let direction = |offset_direction: &Array1<Float>,
+                     dn: &Array1<Float>|
+     -> Option<Array1<Float>> {
+        let offset_normal = Vec4::normalize(normal.clone() + sign * dn);
+        let bent = Vec4::normalize(bend(offset_direction, &offset_normal)?);
+        Some(scattered.direction.clone() + bent - center.clone())
+    };
+
+    Some(RayDifferentials {
+        x_origin: hit.point.clone() + offsets.dpdx.clone(),
+        x_direction: direction(&differentials.x_direction, &offsets.dndx)?,
+        y_origin: hit.point.clone() + offsets.dpdy.clone(),
+        y_direction: direction(&differentials.y_direction, &offsets.dndy)?,
+    })
+}
+
+/**
+ * Now real glass has reflectivity that varies with angle-- look at a window
+ * at a steep angle and itbecomes a mirror. There is a big ugly equation
+ * (Fresnel ?) for that, but almost everybody uses a simple and surprisingly
+ * simple polynomial approximation by Christophe Schlick.
+ */
+pub(crate) fn schlick(cosine: Float, ref_idx: Float) -> Float {
+    let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+    r0 = r0 * r0;
+
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
+
+fn color_name(color: &Array1<Float>) -> String {
+    format!("{:.3},{:.3},{:.3}", color[0], color[1], color[2])
+}
+
+#[derive(Clone)]
+pub enum Shading {
+    COLOR,
+    NORMALS,
+}
+
+pub trait Scattering: Send + Sync {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        depth: u32,
+    ) -> bool;
+
+    fn color(&self, hit: &Hit) -> Array1<Float>;
+
+    fn clone_box(&self) -> Box<dyn Scattering>;
+
+    /**
+     * Identifies the material (e.g. in ID passes). Materials of the same
+     * kind and parameters share the name.
+     */
+    fn name(&self) -> String;
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float>;
+
+    /**
+     * Shadow catchers are not shaded like regular surfaces, the canvas
+     * composites the occlusion they receive over whatever lies behind.
+     * Returns the shadow strength for shadow catchers.
+     */
+    fn shadow_catcher(&self) -> Option<Float> {
+        None
+    }
+
+    /**
+     * Light group the emission of the material is accounted to.
+     */
+    fn light_group(&self) -> Option<String> {
+        None
+    }
+
+    /**
+     * Whether the emission of the material is the surface of a light also
+     * sampled directly (see `Photosphere`): rays which sampled the lights
+     * at their previous bounce then do not count it twice.
+     */
+    fn sampled_light(&self) -> bool {
+        false
+    }
+
+    /**
+     * Add the textures the material looks its colors up in to `textures`.
+     */
+    fn textures<'a>(&'a self, _textures: &mut Vec<&'a dyn TextureSampler>) {}
+
+    /**
+     * Whether the scattering depends on the wavelength of the path (see
+     * `Hit::wavelength`). In spectral mode, the secondary wavelengths of
+     * a path end at such a bounce and only the hero goes on.
+     */
+    fn dispersive(&self) -> bool {
+        false
+    }
+
+    /**
+     * For smooth dielectrics, which refract the light without spreading
+     * it, the index of refraction of the inside over the outside at the
+     * hit. Caustics are connected to the lights through them (manifold
+     * next event estimation). None for other materials.
+     */
+    fn refraction(&self, _hit: &Hit) -> Option<Float> {
+        None
+    }
+
+    /**
+     * For Lambertian surfaces, whose reflected light depends only on the
+     * irradiance, their albedo at the hit. The irradiance cache then
+     * interpolates their indirect light. None for other materials.
+     */
+    fn diffuse(&self, _hit: &Hit) -> Option<Array1<Float>> {
+        None
+    }
+
+    /**
+     * The participating medium filling the (closed) actor, if any. Rays
+     * travelling inside it scatter in the volume before reaching the
+     * surface again.
+     */
+    fn medium(&self) -> Option<&Medium> {
+        None
+    }
+
+    /**
+     * Opacity of the surface at the hit point. Rays go through partly
+     * transparent surfaces with the probability of their transparency
+     * (stochastic alpha testing, see `Hit::alpha_test`).
+     */
+    fn opacity(&self, _hit: &Hit) -> Float {
+        1.0
+    }
+
+    /**
+     * Fraction of the light arriving at the hit from `direction` (unit,
+     * away from the surface) that the surface sends back along the
+     * incident ray, per unit solid angle and including the cosine with
+     * the normal. Lights are sampled directly through it (see `Light`).
+     * None for materials that do not sample lights whatever the direction
+     * (e.g. specular ones, which see lights by reflecting them).
+     */
+    fn evaluate(
+        &self,
+        _hit: &Hit,
+        _direction: &Array1<Float>,
+    ) -> Option<Array1<Float>> {
+        None
+    }
+}
+
+//https://users.rust-lang.org/t/solved-is-it-possible-to-clone-a-boxed-trait-object/1714/5
+impl Clone for Box<dyn Scattering> {
+    fn clone(&self) -> Box<dyn Scattering> {
+        self.clone_box()
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * 0 Scattering material (only primary rays).
+ *
+ */
+
+// Derives self.clone(), which is then used in the clone_box implementation.
+#[derive(Clone)]
+pub struct Primary {
+    pub color: Array1<Float>,
+    pub shading: Shading,
+}
+
+impl Primary {
+    pub fn new(color: Array1<Float>, shading: Shading) -> Primary {
+        Primary { color, shading }
+    }
+}
+
+impl Scattering for Primary {
+    fn scatter(
+        &self,
+        _incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        _scattered: &mut Ray,
+        depth: u32,
+    ) -> bool {
+        *attenuation = self.color(hit_record);
+        depth < 1
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        match self.shading {
+            Shading::COLOR => self.color.clone(),
+            Shading::NORMALS => {
+                let normal = &hit.normal;
+
+                // In order to use the normal vectors (i,j,k) as (r,g,b)
+                // they need to be mapped from [-1.0, 1.0] to the
+                // [0.0, 1.0] range.
+                (normal + 1.0) * 0.5
+            }
+        }
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.color(hit)
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!("primary({})", color_name(&self.color))
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Ideal diffusive material (Lambertian). Albedo is a measure of the
+ * diffuse reflection ("whiteness")[0- black body, absorbs 100%, 1- ideal
+ * reflector].
+ */
+
+// Derives self.clone(), which is then used in the clone_box implementation.
+#[derive(Clone)]
+pub struct Lambertian {
+    pub albedo: Array1<Float>,
+    pub shading: Shading,
+    /**
+     * Multiplies the albedo, looked up at the texture coordinates of the
+     * hit (an image or a UDIM set).
+     */
+    pub texture: Option<Arc<dyn TextureSampler>>,
+}
+
+impl Lambertian {
+    pub fn new(albedo: Array1<Float>, shading: Shading) -> Lambertian {
+        Lambertian {
+            albedo,
+            shading,
+            texture: None,
+        }
+    }
+
+    pub fn textured(
+        albedo: Array1<Float>,
+        texture: Arc<dyn TextureSampler>,
+    ) -> Lambertian {
+        Lambertian {
+            texture: Some(texture),
+            ..Lambertian::new(albedo, Shading::COLOR)
+        }
+    }
+}
+
+impl Scattering for Lambertian {
+    fn scatter(
+        &self,
+        _incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        // A point on the unit sphere tangent at the hit: the directions
+        // are distributed by the cosine, as `evaluate` expects.
+        let target = hit_record.point.clone()
+            + hit_record.normal.clone()
+            + Vec4::normalize(random_dir_unit_sphere());
+
+        *scattered = Ray::new(
+            hit_record.point.clone(),
+            target - hit_record.point.clone(),
+        );
+
+        *attenuation = self.color(hit_record);
+
+        true
+    }
+
+    fn color_noscatter(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn diffuse(&self, hit: &Hit) -> Option<Array1<Float>> {
+        match self.shading {
+            Shading::COLOR => Some(self.color(hit)),
+            Shading::NORMALS => None,
+        }
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        match self.shading {
+            Shading::COLOR => match &self.texture {
+                Some(texture) => {
+                    let texel = texture.sample(hit.uv, hit.footprint);
+                    let mut color = self.albedo.clone();
+                    for c in 0..3 {
+                        color[c] *= texel[c];
+                    }
+                    color
+                }
+                None => self.albedo.clone(),
+            },
+            Shading::NORMALS => {
+                let normal = &hit.normal;
+
+                // In order to use the normal vectors (i,j,k) as (r,g,b)
+                // they need to be mapped from [-1.0, 1.0] to the
+                // [0.0, 1.0] range.
+                (normal + 1.0) * 0.5
+            }
+        }
+    }
+
+    fn evaluate(
+        &self,
+        hit: &Hit,
+        direction: &Array1<Float>,
+    ) -> Option<Array1<Float>> {
+        match self.shading {
+            Shading::COLOR => {
+                let cosine = hit.normal.dot(direction).max(0.0);
+                Some(self.color(hit) * (cosine / consts::PI))
+            }
+            Shading::NORMALS => None,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        match &self.texture {
+            Some(texture) => format!(
+                "lambertian({};{})",
+                color_name(&self.albedo),
+                texture.name()
+            ),
+            None => format!("lambertian({})", color_name(&self.albedo)),
+        }
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        if let Some(texture) = &self.texture {
+            textures.push(texture.as_ref());
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Metal (perfect reflector).
+ *
+ */
+
+// Derives self.clone(), which is then used in the clone_box implementation.
+#[derive(Clone)]
+pub struct Metal {
+    pub color: Array1<Float>,
+    pub shading: Shading,
+    pub fuzz: Float,
+}
+
+impl Metal {
+    pub fn new(color: Array1<Float>, shading: Shading, fuzz: Float) -> Metal {
+        Metal {
+            color,
+            shading,
+            fuzz,
+        }
+    }
+}
+
+impl Scattering for Metal {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        *scattered = reflect(self.fuzz, incident, hit_record);
+        *attenuation = self.color(hit_record);
+
+        scattered.direction.dot(&hit_record.normal) > 0.0
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        match self.shading {
+            Shading::COLOR => self.color.clone(),
+            Shading::NORMALS => {
+                let normal = &hit.normal;
+
+                // In order to use the normal vectors (i,j,k) as (r,g,b)
+                // they need to be mapped from [-1.0, 1.0] to the
+                // [0.0, 1.0] range.
+                (normal + 1.0) * 0.5
+            }
+        }
+    }
+
+    fn color_noscatter(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!("metal({};{:.3})", color_name(&self.color), self.fuzz)
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Anisotropic metal (brushed metal, vinyl records, hair sheen).
+ *
+ * GGX microfacets with roughness `alpha_x` along the surface tangent and
+ * `alpha_y` across it, the tangent turned around the normal by the
+ * `rotation` at the hit, in half turns: from 0 to 1 it takes every
+ * direction, the reflections being the same half a turn apart.
+ * Reflections are sampled from the visible normals (Heitz 2018) and
+ * weighted by a Schlick Fresnel tinted by `color`.
+ */
+#[derive(Clone)]
+pub struct AnisotropicMetal {
+    pub color: Array1<Float>,
+    pub alpha_x: Float,
+    pub alpha_y: Float,
+    pub rotation: Mask,
+}
+
+impl AnisotropicMetal {
+    pub fn new(
+        color: Array1<Float>,
+        alpha_x: Float,
+        alpha_y: Float,
+        rotation: Mask,
+    ) -> AnisotropicMetal {
+        AnisotropicMetal {
+            color,
+            alpha_x,
+            alpha_y,
+            rotation,
+        }
+    }
+
+    /**
+     * Smith masking term of the GGX distribution, for a direction in the
+     * shading frame.
+     */
+    fn lambda(&self, w: [Float; 3]) -> Float {
+        let ax = self.alpha_x * w[0];
+        let ay = self.alpha_y * w[1];
+        (-1.0 + (1.0 + (ax * ax + ay * ay) / (w[2] * w[2])).sqrt()) / 2.0
+    }
+
+    /**
+     * Microfacet normal seen from `wo` (shading frame), for two uniform
+     * random numbers.
+     */
+    pub fn sample_normal(
+        &self,
+        wo: [Float; 3],
+        u1: Float,
+        u2: Float,
+    ) -> [Float; 3] {
+        let vh =
+            normalize3([self.alpha_x * wo[0], self.alpha_y * wo[1], wo[2]]);
+        let lensq = vh[0] * vh[0] + vh[1] * vh[1];
+        let t1 = if lensq > 0.0 {
+            [-vh[1] / lensq.sqrt(), vh[0] / lensq.sqrt(), 0.0]
+        } else {
+            [1.0, 0.0, 0.0]
+        };
+        let t2 = cross3(vh, t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * consts::PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh[2]);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let pz = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        let nh = [
+            p1 * t1[0] + p2 * t2[0] + pz * vh[0],
+            p1 * t1[1] + p2 * t2[1] + pz * vh[1],
+            p1 * t1[2] + p2 * t2[2] + pz * vh[2],
+        ];
+        normalize3([self.alpha_x * nh[0], self.alpha_y * nh[1], nh[2].max(0.0)])
+    }
+
+    /**
+     * Shading frame at a hit: the (rotated) tangent, the bitangent and the
+     * normal.
+     */
+    fn frame(&self, hit: &Hit) -> [[Float; 3]; 3] {
+        let n = normalize3([hit.normal[0], hit.normal[1], hit.normal[2]]);
+        let t = [hit.tangent[0], hit.tangent[1], hit.tangent[2]];
+        let t = normalize3(sub3(t, scale3(n, dot3(n, t))));
+        let b = cross3(n, t);
+        let (sin, cos) = (self.rotation.value(hit) * consts::PI).sin_cos();
+        let t = [
+            cos * t[0] + sin * b[0],
+            cos * t[1] + sin * b[1],
+            cos * t[2] + sin * b[2],
+        ];
+        [t, cross3(n, t), n]
+    }
+}
+
+impl Scattering for AnisotropicMetal {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        let frame = self.frame(hit_record);
+        let d = &incident.direction;
+        let wo_world = normalize3([-d[0], -d[1], -d[2]]);
+        let wo = frame.map(|axis| dot3(axis, wo_world));
+        if wo[2] <= 0.0 {
+            return false;
+        }
+
+        let m = self.sample_normal(
+            wo,
+            random::gen_range(0.0, 1.0),
+            random::gen_range(0.0, 1.0),
+        );
+        let cos = dot3(wo, m);
+        let wi = sub3(scale3(m, 2.0 * cos), wo);
+        if wi[2] <= 0.0 {
+            return false;
+        }
+
+        // With visible normal sampling the weight is F G2 / G1.
+        let masking =
+            (1.0 + self.lambda(wo)) / (1.0 + self.lambda(wo) + self.lambda(wi));
+        let schlick = (1.0 - cos.clamp(0.0, 1.0)).powi(5);
+        let mut weight = self.color.clone();
+        for c in 0..3 {
+            weight[c] =
+                (self.color[c] + (1.0 - self.color[c]) * schlick) * masking;
+        }
+        *attenuation = weight;
+
+        let direction = [0, 1, 2].map(|i| {
+            wi[0] * frame[0][i] + wi[1] * frame[1][i] + wi[2] * frame[2][i]
+        });
+        *scattered = Ray::new(
+            hit_record.point.clone(),
+            arr1(&[direction[0], direction[1], direction[2], 0.0]),
+        );
+        true
+    }
+
+    fn color(&self, _hit: &Hit) -> Array1<Float> {
+        self.color.clone()
+    }
+
+    fn color_noscatter(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "anisotropic_metal({};{:.3};{:.3};{:?})",
+            color_name(&self.color),
+            self.alpha_x,
+            self.alpha_y,
+            self.rotation
+        )
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.rotation.textures(textures);
+    }
+}
+
+fn dot3(a: [Float; 3], b: [Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross3(a: [Float; 3], b: [Float; 3]) -> [Float; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn sub3(a: [Float; 3], b: [Float; 3]) -> [Float; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn scale3(a: [Float; 3], s: Float) -> [Float; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+fn normalize3(a: [Float; 3]) -> [Float; 3] {
+    scale3(a, 1.0 / dot3(a, a).sqrt())
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Dielectric material.
+ *
+ * Refracts light following Snell's law and angle-varying reflectivity using
+ * a polynomial approximation (Christophe Schlick).
+ *
+ */
+
+#[derive(Clone)]
+pub struct Dielectric {
+    pub color: Array1<Float>,
+    pub shading: Shading,
+    /**
+     * Refraction index used without a wavelength (RGB rendering). For
+     * dispersive dielectrics, the index at the sodium d-line.
+     */
+    pub refraction_idx: Float,
+    pub refraction_idx_ext: Float,
+    pub dispersion: Option<Dispersion>,
+}
+
+/**
+ * Wavelength of the sodium d-line in nanometers, where the refraction
+ * index of optical glasses is usually given.
+ */
+pub const D_LINE: Float = 587.6;
+
+/**
+ * Refraction index as a function of the wavelength. Coefficients use
+ * wavelengths in micrometers, as in glass catalogs.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "model", rename_all = "snake_case"))]
+pub enum Dispersion {
+    /**
+     * n = a + b / λ²
+     */
+    Cauchy { a: Float, b: Float },
+    /**
+     * n² = 1 + Σ b_i λ² / (λ² - c_i)
+     */
+    Sellmeier { b: [Float; 3], c: [Float; 3] },
+}
+
+impl Dispersion {
+    /**
+     * Schott N-BK7, the common crown glass.
+     */
+    pub fn bk7() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.039_612_2, 0.231_792_35, 1.010_469_4],
+            c: [0.006_000_698_5, 0.020_017_914, 103.560_65],
+        }
+    }
+
+    /**
+     * Diamond, strongly dispersive ("fire").
+     */
+    pub fn diamond() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [4.3356, 0.3306, 0.0],
+            c: [0.1060 * 0.1060, 0.1750 * 0.1750, 0.0],
+        }
+    }
+
+    /**
+     * Refraction index at a wavelength in nanometers.
+     */
+    pub fn refraction_idx(&self, wavelength: Float) -> Float {
+        let l2 = (wavelength / 1000.0) * (wavelength / 1000.0);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let sum: Float = (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
+}
+
+impl Dielectric {
+    pub fn new(
+        color: Array1<Float>,
+        shading: Shading,
+        refraction_idx: Float,
+    ) -> Dielectric {
+        // Air
+        let refraction_idx_ext = 1.0;
+        Dielectric {
+            color,
+            shading,
+            refraction_idx,
+            refraction_idx_ext,
+            dispersion: None,
+        }
+    }
+
+    /**
+     * A dielectric whose refraction index depends on the wavelength,
+     * splitting light into its colors in spectral mode.
+     */
+    pub fn dispersive(
+        color: Array1<Float>,
+        shading: Shading,
+        dispersion: Dispersion,
+    ) -> Dielectric {
+        Dielectric {
+            refraction_idx: dispersion.refraction_idx(D_LINE),
+            dispersion: Some(dispersion),
+            ..Dielectric::new(color, shading, 1.0)
+        }
+    }
+
+    /**
+     * Refraction index for the wavelength of a hit, if any.
+     */
+    pub fn refraction_idx_at(&self, hit: &Hit) -> Float {
+        match (&self.dispersion, hit.wavelength) {
+            (Some(dispersion), Some(wavelength)) => {
+                dispersion.refraction_idx(wavelength)
+            }
+            _ => self.refraction_idx,
+        }
+    }
+
+    /**
+     * Snell's Law:
+     * n_inci * sin(Theta_inci) = n_trans * sin(Theta_trans)
+     *
+     * Handles total internal reflection by checking the discriminant first.
+     *
+     * n_i ( Ray_i - Cos(Theta_i) Norm ) = n_t ( Ray_t + Cos(Theta_t) Norm )
+     */
+    pub(crate) fn refract(
+        &self,
+        incident: &Ray,
+        normal: Array1<Float>,
+        hit: &Hit,
+        ni_over_nt: Float,
+        refracted: &mut Ray,
+    ) -> bool {
+        match refraction(&incident.direction, &normal, ni_over_nt) {
+            Some(dir) => {
+                *refracted = Ray::new(hit.point.clone(), dir);
+                refracted.differentials = bend_differentials(
+                    incident,
+                    hit,
+                    &normal,
+                    refracted,
+                    |d, n| refraction(d, n, ni_over_nt),
+                );
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/**
+ * Direction of the refracted ray, None on total internal reflection.
+ */
+fn refraction(
+    direction: &Array1<Float>,
+    normal: &Array1<Float>,
+    ni_over_nt: Float,
+) -> Option<Array1<Float>> {
+    let ri_dot_normal = direction.dot(normal);
+
+    // Discriminant
+    let sq_cos_theta_t =
+        1.0 - ni_over_nt * ni_over_nt * (1.0 - ri_dot_normal * ri_dot_normal);
+
+    if sq_cos_theta_t > 0.0 {
+        Some(
+            ni_over_nt * (direction.clone() - normal.clone() * ri_dot_normal)
+                - normal.clone() * sq_cos_theta_t.sqrt(),
+        )
+    } else {
+        None
+    }
+}
+
+impl Scattering for Dielectric {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        let refraction_idx = self.refraction_idx_at(hit_record);
+        let mut outward_normal = hit_record.normal.clone();
+        let mut ni_over_nt = self.refraction_idx_ext / refraction_idx;
+        let mut cosine = -hit_record.normal.dot(&incident.direction)
+            / Vec4::l2_norm(incident.direction.view());
+
+        // Change signs and invert refraction ratio if the normal points
+        // inwards (default outwards; but when the ray exits then it needs
+        // to be inverted).
+        if hit_record.normal.dot(&incident.direction) > 0.0 {
+            outward_normal = -hit_record.normal.clone();
+            ni_over_nt = refraction_idx / self.refraction_idx_ext;
+            cosine = refraction_idx
+                * hit_record.normal.dot(&incident.direction)
+                / Vec4::l2_norm(incident.direction.view());
+        }
+        *attenuation = self.color(hit_record);
+
+        let reflected = reflect(0.0, incident, hit_record);
+        let reflect_prob = if self.refract(
+            incident,
+            outward_normal,
+            hit_record,
+            ni_over_nt,
+            scattered,
+        ) {
+            schlick(cosine, refraction_idx)
+        } else {
+            1.0
+        };
+
+        if random::gen_range(0.0, 1.0) < reflect_prob {
+            *scattered = reflected;
+        }
+
+        true
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        match self.shading {
+            Shading::COLOR => self.color.clone(),
+            Shading::NORMALS => {
+                let normal = &hit.normal;
+
+                // In order to use the normal vectors (i,j,k) as (r,g,b)
+                // they need to be mapped from [-1.0, 1.0] to the
+                // [0.0, 1.0] range.
+                (normal + 1.0) * 0.5
+            }
+        }
+    }
+
+    fn color_noscatter(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        match &self.dispersion {
+            None => format!(
+                "dielectric({};{:.3})",
+                color_name(&self.color),
+                self.refraction_idx
+            ),
+            Some(dispersion) => format!(
+                "dielectric({};{:?})",
+                color_name(&self.color),
+                dispersion
+            ),
+        }
+    }
+
+    fn refraction(&self, hit: &Hit) -> Option<Float> {
+        Some(self.refraction_idx_at(hit) / self.refraction_idx_ext)
+    }
+
+    fn dispersive(&self) -> bool {
+        self.dispersion.is_some()
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Shadow catcher.
+ *
+ * Invisible to the camera, except where other actors occlude it. Used as a
+ * stand-in for the ground of a photographic backplate, so rendered objects
+ * can be composited over it with their shadows and contact darkening.
+ * Strength scales the darkening [0- no shadow, 1- fully occluded is black].
+ */
+#[derive(Clone)]
+pub struct ShadowCatcher {
+    pub strength: Float,
+}
+
+impl ShadowCatcher {
+    pub fn new(strength: Float) -> ShadowCatcher {
+        ShadowCatcher { strength }
+    }
+}
+
+impl Scattering for ShadowCatcher {
+    /**
+     * Scatters a diffuse (shadow) ray, used to probe the occlusion of the
+     * hemisphere around the hit point.
+     */
+    fn scatter(
+        &self,
+        _incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        let target = hit_record.point.clone()
+            + hit_record.normal.clone()
+            + random_dir_unit_sphere();
+
+        *scattered = Ray::new(
+            hit_record.point.clone(),
+            target - hit_record.point.clone(),
+        );
+        *attenuation = self.color(hit_record);
+
+        true
+    }
+
+    fn color(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn color_noscatter(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!("shadow_catcher({:.3})", self.strength)
+    }
+
+    fn shadow_catcher(&self) -> Option<Float> {
+        Some(self.strength)
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Diffuse light (emissive material).
+ *
+ * Does not scatter, it emits its color scaled by the intensity. Lights can
+ * be tagged into a light group so their contribution is also rendered in a
+ * separate AOV.
+ */
+#[derive(Clone)]
+pub struct DiffuseLight {
+    pub color: Array1<Float>,
+    pub intensity: Float,
+    pub light_group: Option<String>,
+}
+
+impl DiffuseLight {
+    pub fn new(color: Array1<Float>, intensity: Float) -> DiffuseLight {
+        DiffuseLight {
+            color,
+            intensity,
+            light_group: None,
+        }
+    }
+}
+
+impl Scattering for DiffuseLight {
+    fn scatter(
+        &self,
+        _incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        _scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        *attenuation = self.color(hit_record);
+        false
+    }
+
+    fn color(&self, _hit: &Hit) -> Array1<Float> {
+        self.color.clone() * self.intensity
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.color(hit)
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "diffuse_light({};{:.3})",
+            color_name(&self.color),
+            self.intensity
+        )
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.light_group.clone()
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Clear coat layered over a base material (car paint, lacquered wood,
+ * plastics).
+ *
+ * A smooth dielectric coat of index `refraction_idx` (`roughness` fuzzes
+ * its reflection like a metal's). Light reflects off the coat with the
+ * Fresnel reflectance of the angle and reaches the base otherwise, so no
+ * energy is created: the coat takes what it reflects from the base. Light
+ * through the coat is filtered by `tint`, on its way in and out.
+ */
+#[derive(Clone)]
+pub struct Clearcoat {
+    pub base: Arc<dyn Scattering>,
+    pub refraction_idx: Float,
+    pub roughness: Float,
+    pub tint: Array1<Float>,
+}
+
+impl Clearcoat {
+    pub fn new(
+        base: Arc<dyn Scattering>,
+        refraction_idx: Float,
+        roughness: Float,
+    ) -> Clearcoat {
+        Clearcoat {
+            base,
+            refraction_idx,
+            roughness,
+            tint: arr1(&[1.0, 1.0, 1.0, 1.0]),
+        }
+    }
+
+    /**
+     * Fraction of the light reflected by the coat, for the cosine of the
+     * incidence angle.
+     */
+    pub fn reflectance(&self, cos_i: Float) -> Float {
+        schlick(cos_i.clamp(0.0, 1.0), self.refraction_idx)
+    }
+}
+
+impl Scattering for Clearcoat {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        depth: u32,
+    ) -> bool {
+        let cos_i = -hit_record.normal.dot(&incident.direction)
+            / Vec4::l2_norm(incident.direction.view());
+        if cos_i <= 0.0 {
+            return self.base.scatter(
+                incident,
+                hit_record,
+                attenuation,
+                scattered,
+                depth,
+            );
+        }
+
+        if random::gen_range(0.0, 1.0) < self.reflectance(cos_i) {
+            *scattered = reflect(self.roughness, incident, hit_record);
+            *attenuation = arr1(&[1.0, 1.0, 1.0, 1.0]);
+            return scattered.direction.dot(&hit_record.normal) > 0.0;
+        }
+
+        let scatters = self.base.scatter(
+            incident,
+            hit_record,
+            attenuation,
+            scattered,
+            depth,
+        );
+        for c in 0..3 {
+            attenuation[c] *= self.tint[c] * self.tint[c];
+        }
+        scatters
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        self.base.color(hit)
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.base.color_noscatter(hit)
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "clearcoat({};{:.3};{:.3};{})",
+            self.base.name(),
+            self.refraction_idx,
+            self.roughness,
+            color_name(&self.tint)
+        )
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.base.textures(textures);
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.base.light_group()
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        self.base.medium()
+    }
+
+    fn opacity(&self, hit: &Hit) -> Float {
+        self.base.opacity(hit)
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Thin film coating (soap bubbles, oil slicks, coated optics).
+ *
+ * A film of `thickness` nanometers and index `refraction_idx` on top of a
+ * base material whose (real) index is `substrate_idx`. Light reflected by
+ * the two faces of the film interferes, so the film reflects some
+ * wavelengths more than others depending on the viewing angle. Paths
+ * reflect specularly off the film with its reflectance and reach the base
+ * material otherwise.
+ */
+#[derive(Clone)]
+pub struct ThinFilm {
+    pub base: Arc<dyn Scattering>,
+    pub thickness: Float,
+    pub refraction_idx: Float,
+    pub substrate_idx: Float,
+}
+
+/**
+ * Wavelengths (nm) the film is evaluated at without a path wavelength,
+ * for the red, green and blue channels.
+ */
+const RGB_WAVELENGTHS: [Float; 3] = [650.0, 532.0, 465.0];
+
+impl ThinFilm {
+    pub fn new(
+        base: Arc<dyn Scattering>,
+        thickness: Float,
+        refraction_idx: Float,
+        substrate_idx: Float,
+    ) -> ThinFilm {
+        ThinFilm {
+            base,
+            thickness,
+            refraction_idx,
+            substrate_idx,
+        }
+    }
+
+    /**
+     * Reflectance of the film for unpolarized light of a wavelength (nm)
+     * arriving from air at an angle whose cosine is `cos_i` (Airy
+     * summation of the reflections inside the film).
+     */
+    pub fn reflectance(&self, wavelength: Float, cos_i: Float) -> Float {
+        let (n1, n2, n3) = (1.0, self.refraction_idx, self.substrate_idx);
+        let sin2_i = (1.0 - cos_i * cos_i).max(0.0);
+        let cos_t = |n: Float| -> Option<Float> {
+            let sin2 = sin2_i * (n1 / n) * (n1 / n);
+            if sin2 < 1.0 {
+                Some((1.0 - sin2).sqrt())
+            } else {
+                None
+            }
+        };
+        let cos_2 = match cos_t(n2) {
+            Some(cos) => cos,
+            None => return 1.0,
+        };
+
+        // Total internal reflection at the substrate is approximated
+        // without its phase shift.
+        let (r_s23, r_p23) = match cos_t(n3) {
+            Some(cos_3) => (
+                (n2 * cos_2 - n3 * cos_3) / (n2 * cos_2 + n3 * cos_3),
+                (n3 * cos_2 - n2 * cos_3) / (n3 * cos_2 + n2 * cos_3),
+            ),
+            None => (1.0, 1.0),
+        };
+        let r_s12 = (n1 * cos_i - n2 * cos_2) / (n1 * cos_i + n2 * cos_2);
+        let r_p12 = (n2 * cos_i - n1 * cos_2) / (n2 * cos_i + n1 * cos_2);
+
+        let phase = 4.0 * consts::PI * n2 * self.thickness * cos_2 / wavelength;
+        let airy = |r12: Float, r23: Float| {
+            let cross = 2.0 * r12 * r23 * phase.cos();
+            (r12 * r12 + r23 * r23 + cross)
+                / (1.0 + r12 * r12 * r23 * r23 + cross)
+        };
+        0.5 * (airy(r_s12, r_s23) + airy(r_p12, r_p23))
+    }
+}
+
+impl Scattering for ThinFilm {
+    /**
+     * Reflect off the film with probability its mean reflectance, and
+     * weight each channel by its own reflectance over that probability
+     * (or transmittance, when the base scatters). Rays leaving the base
+     * from inside do not see the film.
+     */
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        depth: u32,
+    ) -> bool {
+        let cos_i = -hit_record.normal.dot(&incident.direction)
+            / Vec4::l2_norm(incident.direction.view());
+        if cos_i <= 0.0 {
+            return self.base.scatter(
+                incident,
+                hit_record,
+                attenuation,
+                scattered,
+                depth,
+            );
+        }
+
+        let reflectance = match hit_record.wavelength {
+            Some(wavelength) => {
+                let r = self.reflectance(wavelength, cos_i);
+                [r, r, r]
+            }
+            None => RGB_WAVELENGTHS.map(|w| self.reflectance(w, cos_i)),
+        };
+        let probability =
+            (reflectance.iter().sum::<Float>() / 3.0).clamp(0.0, 1.0);
+
+        if random::gen_range(0.0, 1.0) < probability {
+            *scattered = reflect(0.0, incident, hit_record);
+            *attenuation = arr1(&[
+                reflectance[0] / probability,
+                reflectance[1] / probability,
+                reflectance[2] / probability,
+                1.0,
+            ]);
+            return true;
+        }
+
+        let scatters = self.base.scatter(
+            incident,
+            hit_record,
+            attenuation,
+            scattered,
+            depth,
+        );
+        for (c, r) in reflectance.iter().enumerate() {
+            attenuation[c] *= (1.0 - r) / (1.0 - probability);
+        }
+        scatters
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        self.base.color(hit)
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.base.color_noscatter(hit)
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "thin_film({};{:.1};{:.3};{:.3})",
+            self.base.name(),
+            self.thickness,
+            self.refraction_idx,
+            self.substrate_idx
+        )
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.base.textures(textures);
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.base.light_group()
+    }
+
+    /**
+     * The reflectance of the film depends on the wavelength.
+     */
+    fn dispersive(&self) -> bool {
+        true
+    }
+
+    fn opacity(&self, hit: &Hit) -> Float {
+        self.base.opacity(hit)
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Homogeneous medium scattering light isotropically. `albedo` is the
+ * fraction of light surviving each scattering event (per channel), and
+ * `mean_free_path` the mean distance between events, in scene units.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct Medium {
+    pub albedo: Array1<Float>,
+    pub mean_free_path: Float,
+}
+
+impl Medium {
+    /**
+     * Distance to the next scattering event (exponential free flight).
+     */
+    pub fn sample_distance(&self) -> Float {
+        -(1.0 - random::gen_range(0.0, 1.0)).ln() * self.mean_free_path
+    }
+
+    /**
+     * Direction after a scattering event, uniform over the sphere.
+     */
+    pub fn sample_direction(&self) -> Array1<Float> {
+        Vec4::normalize(random_dir_unit_sphere())
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Subsurface scattering (skin, wax, marble, milk).
+ *
+ * A dielectric boundary of index `refraction_idx` enclosing a scattering
+ * medium: light refracts in, random walks through the volume and leaves
+ * somewhere else on the surface. Long walks (small mean free paths) need
+ * a `max_depth` large enough for their scattering events.
+ */
+#[derive(Clone)]
+pub struct Subsurface {
+    pub medium: Medium,
+    pub surface: Dielectric,
+}
+
+impl Subsurface {
+    pub fn new(
+        albedo: Array1<Float>,
+        mean_free_path: Float,
+        refraction_idx: Float,
+    ) -> Subsurface {
+        Subsurface {
+            medium: Medium {
+                albedo,
+                mean_free_path,
+            },
+            surface: Dielectric::new(
+                arr1(&[1.0, 1.0, 1.0, 1.0]),
+                Shading::COLOR,
+                refraction_idx,
+            ),
+        }
+    }
+}
+
+impl Scattering for Subsurface {
+    /**
+     * The boundary reflects or refracts like a clear dielectric, the
+     * color comes from the walk in the medium.
+     */
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        depth: u32,
+    ) -> bool {
+        self.surface.scatter(
+            incident,
+            hit_record,
+            attenuation,
+            scattered,
+            depth,
+        )
+    }
+
+    fn color(&self, _hit: &Hit) -> Array1<Float> {
+        self.medium.albedo.clone()
+    }
+
+    fn color_noscatter(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "subsurface({};{:.3};{:.3})",
+            color_name(&self.medium.albedo),
+            self.medium.mean_free_path,
+            self.surface.refraction_idx
+        )
+    }
+
+    fn medium(&self) -> Option<&Medium> {
+        Some(&self.medium)
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Scalar pattern over surfaces, from 0 to 1: the opacity of cutouts or the
+ * blend factor of mixes.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "pattern", rename_all = "snake_case")
+)]
+pub enum Mask {
+    Uniform {
+        value: Float,
+    },
+    /**
+     * Solid (3D) checkerboard of cubes `size` wide in world space,
+     * alternating between the two values.
+     */
+    Checker {
+        size: Float,
+        values: [Float; 2],
+    },
+    /**
+     * A `channel` of an image texture at the texture coordinates of the
+     * hit. Scene files name the image by its `path`, the `texture` is read
+     * with the files of the scene (see `SceneDescription::read_textures`).
+     */
+    Texture {
+        path: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        channel: Channel,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        texture: Option<MaskTexture>,
+    },
+}
+
+/**
+ * The image of a texture mask, the same as another only if it is the same
+ * texture.
+ */
+#[derive(Clone)]
+pub struct MaskTexture(pub Arc<dyn TextureSampler>);
+
+impl fmt::Debug for MaskTexture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.name())
+    }
+}
+
+impl PartialEq for MaskTexture {
+    fn eq(&self, other: &MaskTexture) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Mask {
+    /**
+     * The mask of a `channel` of `texture`, named after it.
+     */
+    pub fn texture(texture: Arc<dyn TextureSampler>, channel: Channel) -> Mask {
+        Mask::Texture {
+            path: texture.name(),
+            channel,
+            texture: Some(MaskTexture(texture)),
+        }
+    }
+
+    pub fn value(&self, hit: &Hit) -> Float {
+        match self {
+            Mask::Uniform { value } => *value,
+            Mask::Checker { size, values } => {
+                let cell: i64 =
+                    (0..3).map(|i| (hit.point[i] / size).floor() as i64).sum();
+                values[cell.rem_euclid(2) as usize]
+            }
+            Mask::Texture {
+                channel, texture, ..
+            } => match texture {
+                Some(texture) => {
+                    channel.of(texture.0.sample(hit.uv, hit.footprint))
+                }
+                // Not read, building the material refuses it.
+                None => 1.0,
+            },
+        }
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        if let Mask::Texture {
+            texture: Some(texture),
+            ..
+        } = self
+        {
+            textures.push(texture.0.as_ref());
+        }
+    }
+}
+
+/**
+ * Cutout: the `base` material with holes where `mask` is transparent, for
+ * leaves, fences or decals. Camera, scattered and shadow rays all go
+ * through the holes.
+ */
+#[derive(Clone)]
+pub struct Cutout {
+    pub base: Arc<dyn Scattering>,
+    pub mask: Mask,
+}
+
+impl Cutout {
+    pub fn new(base: Arc<dyn Scattering>, mask: Mask) -> Cutout {
+        Cutout { base, mask }
+    }
+}
+
+impl Scattering for Cutout {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        depth: u32,
+    ) -> bool {
+        self.base
+            .scatter(incident, hit_record, attenuation, scattered, depth)
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        self.base.color(hit)
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.base.color_noscatter(hit)
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!("cutout({};{:?})", self.base.name(), self.mask)
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.base.textures(textures);
+        self.mask.textures(textures);
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.base.light_group()
+    }
+
+    fn dispersive(&self) -> bool {
+        self.base.dispersive()
+    }
+
+    fn opacity(&self, hit: &Hit) -> Float {
+        self.mask.value(hit) * self.base.opacity(hit)
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Blend of two materials (worn edges, rust over paint, decals): `factor`
+ * is the weight of `second` at the hit point, 0 being all `first`. Every
+ * bounce picks one of them with its weight, so the blend costs no more
+ * than either. Emission and color are blended as is, mixes of an emitter
+ * with a scattering material are not supported.
+ */
+#[derive(Clone)]
+pub struct Mix {
+    pub first: Arc<dyn Scattering>,
+    pub second: Arc<dyn Scattering>,
+    pub factor: Mask,
+}
+
+impl Mix {
+    pub fn new(
+        first: Arc<dyn Scattering>,
+        second: Arc<dyn Scattering>,
+        factor: Mask,
+    ) -> Mix {
+        Mix {
+            first,
+            second,
+            factor,
+        }
+    }
+
+    /**
+     * Weight of `second` at the hit point.
+     */
+    pub fn weight(&self, hit: &Hit) -> Float {
+        self.factor.value(hit).clamp(0.0, 1.0)
+    }
+
+    fn blend(
+        &self,
+        hit: &Hit,
+        first: Array1<Float>,
+        second: Array1<Float>,
+    ) -> Array1<Float> {
+        let weight = self.weight(hit);
+        first * (1.0 - weight) + second * weight
+    }
+}
+
+impl Scattering for Mix {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        depth: u32,
+    ) -> bool {
+        // Pure parts of the blend draw no random numbers.
+        let weight = self.weight(hit_record);
+        let material = if weight >= 1.0
+            || (weight > 0.0 && random::gen_range(0.0, 1.0) < weight)
+        {
+            &self.second
+        } else {
+            &self.first
+        };
+        material.scatter(incident, hit_record, attenuation, scattered, depth)
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        self.blend(hit, self.first.color(hit), self.second.color(hit))
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.blend(
+            hit,
+            self.first.color_noscatter(hit),
+            self.second.color_noscatter(hit),
+        )
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "mix({};{};{:?})",
+            self.first.name(),
+            self.second.name(),
+            self.factor
+        )
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.first.textures(textures);
+        self.second.textures(textures);
+        self.factor.textures(textures);
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.first
+            .light_group()
+            .or_else(|| self.second.light_group())
+    }
+
+    fn dispersive(&self) -> bool {
+        self.first.dispersive() || self.second.dispersive()
+    }
+
+    fn opacity(&self, hit: &Hit) -> Float {
+        let weight = self.weight(hit);
+        self.first.opacity(hit) * (1.0 - weight)
+            + self.second.opacity(hit) * weight
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Particles of planetary rings (ice and dust, see `Ring`): each bounce
+ * scatters the light by a double Henyey-Greenstein phase function, a
+ * `forward_weight` share of it in a lobe of anisotropy `forward` and the
+ * rest in a lobe of anisotropy `backward` (negative, towards the light).
+ * The albedo is the fraction of the light the particles scatter.
+ */
+#[derive(Clone)]
+pub struct RingParticles {
+    pub albedo: Array1<Float>,
+    pub forward: Float,
+    pub backward: Float,
+    pub forward_weight: Float,
+}
+
+impl RingParticles {
+    /**
+     * Mostly back scattering, as the icy rings of Saturn.
+     */
+    pub fn new(albedo: Array1<Float>) -> RingParticles {
+        RingParticles {
+            albedo,
+            forward: 0.7,
+            backward: -0.4,
+            forward_weight: 0.3,
+        }
+    }
+
+    /**
+     * Density of the light going on at `cosine` from its direction, per
+     * solid angle.
+     */
+    pub fn phase(&self, cosine: Float) -> Float {
+        self.forward_weight * henyey_greenstein(self.forward, cosine)
+            + (1.0 - self.forward_weight)
+                * henyey_greenstein(self.backward, cosine)
+    }
+}
+
+impl Scattering for RingParticles {
+    fn scatter(
+        &self,
+        incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        let g = if random::gen_range(0.0, 1.0) < self.forward_weight {
+            self.forward
+        } else {
+            self.backward
+        };
+        let incoming = Vec4::normalize(incident.direction.clone());
+        *scattered = Ray::new(
+            hit_record.point.clone(),
+            sample_henyey_greenstein(g, &incoming),
+        );
+        *attenuation = self.albedo.clone();
+        true
+    }
+
+    fn color(&self, _hit: &Hit) -> Array1<Float> {
+        self.albedo.clone()
+    }
+
+    fn color_noscatter(&self, _hit: &Hit) -> Array1<Float> {
+        arr1(&[0.0, 0.0, 0.0, 0.0])
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "ring_particles({};{:.2},{:.2},{:.2})",
+            color_name(&self.albedo),
+            self.forward,
+            self.backward,
+            self.forward_weight
+        )
+    }
+
+    fn evaluate(
+        &self,
+        hit: &Hit,
+        direction: &Array1<Float>,
+    ) -> Option<Array1<Float>> {
+        Some(&self.albedo * self.phase(hit.incident.dot(direction)))
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Glowing gas of an accretion disk (see `AccretionDisk`). Its radiance
+ * falls off from the inner edge as the flux of a thin disk, r^-3 (1 -
+ * sqrt(r_in / r)), peaking at `luminance` times `color` a little way out
+ * of the inner edge. Like `DiffuseLight`, it does not scatter.
+ */
+#[derive(Clone)]
+pub struct AccretionGlow {
+    pub color: Array1<Float>,
+    pub luminance: Float,
+    pub light_group: Option<String>,
+}
+
+impl AccretionGlow {
+    pub fn new(color: Array1<Float>, luminance: Float) -> AccretionGlow {
+        AccretionGlow {
+            color,
+            luminance,
+            light_group: None,
+        }
+    }
+
+    /**
+     * Radiance relative to the peak, where the inner radius over the
+     * radius is `u` (1 on the inner edge). The flux peaks at u = 36/49.
+     */
+    pub fn profile(u: Float) -> Float {
+        let u = u.clamp(0.0, 1.0);
+        let peak = (36.0 as Float / 49.0).powi(3) / 7.0;
+        u.powi(3) * (1.0 - u.sqrt()) / peak
+    }
+}
+
+impl Scattering for AccretionGlow {
+    fn scatter(
+        &self,
+        _incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        _scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        *attenuation = self.color(hit_record);
+        false
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        self.color.clone() * (self.luminance * Self::profile(hit.uv[0]))
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.color(hit)
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "accretion_glow({};{:.3})",
+            color_name(&self.color),
+            self.luminance
+        )
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.light_group.clone()
+    }
+}
+
+// ----------------------------------------------------------------------------
+/**
+ * Distance from `p` to the closest of the points scattered one per unit
+ * cell of space (cellular noise), about 0.5 on average.
+ */
+fn cellular(p: [Float; 3]) -> Float {
+    let cell = p.map(|c| c.floor());
+    let mut closest = Float::MAX;
+    for offset in 0..27 {
+        let corner =
+            [offset % 3, offset / 3 % 3, offset / 9].map(|o| o as Float - 1.0);
+        let c = [0, 1, 2].map(|i| cell[i] + corner[i]);
+        let mut hash = (c[0] as i64 as u64).wrapping_mul(0x9e3779b97f4a7c15)
+            ^ (c[1] as i64 as u64).wrapping_mul(0xc2b2ae3d27d4eb4f)
+            ^ (c[2] as i64 as u64).wrapping_mul(0x165667b19e3779f9);
+        let feature = [0, 1, 2].map(|i| {
+            hash ^= hash >> 33;
+            hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+            hash ^= hash >> 33;
+            c[i] + (hash >> 11) as Float / (1u64 << 53) as Float
+        });
+        let d = (0..3).map(|i| (feature[i] - p[i]).powi(2)).sum::<Float>();
+        closest = closest.min(d);
+    }
+    closest.sqrt()
+}
+
+/**
+ * Mean luminance (nits) of the solar disk: the solar illuminance, 128000
+ * lux, over the solid angle of the Sun seen from 1 AU.
+ */
+pub const SOLAR_LUMINANCE: Float =
+    128000.0 / (consts::PI * SOLAR_DISK * SOLAR_DISK);
+
+/**
+ * Radius of the Sun over the astronomical unit.
+ */
+const SOLAR_DISK: Float = 695700.0 / 149_597_870.7;
+
+/**
+ * Glowing surface of a star (the Sun). Its radiance is `luminance` nits
+ * times `color` on average over the disk, brighter at the center and
+ * darker towards the limb, where the line of sight grazes the surface
+ * (mu, its cosine with the normal): 1 - u1 (1 - mu) - u2 (1 - mu)^2 for
+ * the `limb_darkening` coefficients [u1, u2]. Convection cells
+ * (`granule_size` across, in scene units) brighten their centers and
+ * darken the lanes between them by `granulation`.
+ *
+ * The star may also light the scene as a light sampled directly (see
+ * `omni`), the material being its visible surface: it is then `sampled`,
+ * and its emission not counted again by the rays that sampled the lights.
+ * Like `DiffuseLight`, it does not scatter.
+ */
+#[derive(Clone)]
+pub struct Photosphere {
+    pub color: Array1<Float>,
+    pub luminance: Float,
+    pub limb_darkening: [Float; 2],
+    pub granulation: Float,
+    pub granule_size: Float,
+    pub sampled: bool,
+    pub light_group: Option<String>,
+}
+
+impl Photosphere {
+    pub fn new(color: Array1<Float>, luminance: Float) -> Photosphere {
+        Photosphere {
+            color,
+            luminance,
+            limb_darkening: [0.0, 0.0],
+            granulation: 0.0,
+            granule_size: 1.0,
+            sampled: false,
+            light_group: None,
+        }
+    }
+
+    /**
+     * A star of surface `temperature` (kelvin), colored as a blackbody.
+     */
+    pub fn blackbody(temperature: Float, luminance: Float) -> Photosphere {
+        let [r, g, b] = crate::color::blackbody_color(temperature);
+        Photosphere::new(arr1(&[r, g, b, 1.0]), luminance)
+    }
+
+    /**
+     * The Sun: 5772 K, `SOLAR_LUMINANCE`, and its limb darkening in the
+     * visible (the limb at 30% of the center).
+     */
+    pub fn sun() -> Photosphere {
+        let mut sun = Photosphere::blackbody(5772.0, SOLAR_LUMINANCE);
+        sun.limb_darkening = [0.47, 0.23];
+        sun
+    }
+
+    /**
+     * Radiance relative to the mean over the disk, where the line of
+     * sight makes a cosine `mu` with the normal.
+     */
+    pub fn limb(&self, mu: Float) -> Float {
+        let [u1, u2] = self.limb_darkening;
+        let x = 1.0 - mu.clamp(0.0, 1.0);
+        (1.0 - u1 * x - u2 * x * x) / (1.0 - u1 / 3.0 - u2 / 6.0)
+    }
+
+    /**
+     * The light of a star of this surface at `center`, of `radius`: a
+     * sphere of `luminance` nits shines pi radius^2 luminance candela.
+     * The material is marked as `sampled`.
+     */
+    pub fn omni(&mut self, center: [Float; 3], radius: Float) -> Omni {
+        self.sampled = true;
+        let mut omni =
+            Omni::new(center, self.luminance * consts::PI * radius * radius);
+        omni.radius = radius;
+        omni.color = [self.color[0], self.color[1], self.color[2]];
+        omni.light_group = self.light_group.clone();
+        omni
+    }
+}
+
+impl Scattering for Photosphere {
+    fn scatter(
+        &self,
+        _incident: &Ray,
+        hit_record: &Hit,
+        attenuation: &mut Array1<Float>,
+        _scattered: &mut Ray,
+        _depth: u32,
+    ) -> bool {
+        *attenuation = self.color(hit_record);
+        false
+    }
+
+    fn color(&self, hit: &Hit) -> Array1<Float> {
+        let normal = Vec4::normalize(hit.normal.clone());
+        let mu = (0..3)
+            .map(|i| normal[i] * hit.incident[i])
+            .sum::<Float>()
+            .abs();
+        let mut radiance = self.luminance * self.limb(mu);
+        if self.granulation > 0.0 {
+            let p = [0, 1, 2].map(|i| hit.point[i] / self.granule_size);
+            let cell = 1.0 + self.granulation * (1.0 - 2.0 * cellular(p));
+            radiance *= cell.max(0.0);
+        }
+        self.color.clone() * radiance
+    }
+
+    fn color_noscatter(&self, hit: &Hit) -> Array1<Float> {
+        self.color(hit)
+    }
+
+    fn clone_box(&self) -> Box<dyn Scattering> {
+        Box::new((*self).clone())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "photosphere({};{:.3e};{:.2},{:.2})",
+            color_name(&self.color),
+            self.luminance,
+            self.limb_darkening[0],
+            self.limb_darkening[1]
+        )
+    }
+
+    fn light_group(&self) -> Option<String> {
+        self.light_group.clone()
+    }
+
+    fn sampled_light(&self) -> bool {
+        self.sampled
+    }
+}
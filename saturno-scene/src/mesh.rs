@@ -0,0 +1,1208 @@
+use crate::actor::count_work;
+use crate::actor::{Aabb, Hit, HitDifferentials, Hittable, RayTraceable};
+use crate::bvh;
+use crate::material::Scattering;
+use crate::texture::TextureSampler;
+use crate::wide_bvh::WideBvh;
+use ndarray::{arr1, Array1};
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+// Triangle meshes: shared vertices with smooth normals and texture
+// coordinates, traversed through a bounding volume hierarchy of their
+// triangles. Meshes can be subdivided and displaced by a height texture,
+// so detailed surfaces (e.g. terrain) come from height maps rather than
+// from huge source meshes. Huge meshes may still compress their hierarchy
+// (see `wide_bvh`).
+//
+// An animated mesh (a character, cloth) may be deformed over the shutter
+// interval, for motion blur: its vertices take positions sampled at evenly
+// spaced times of it, between which a ray finds them moved linearly at its
+// time (see `Ray::time`), their normals too. The bounds of the triangles in
+// the hierarchy enclose them at every sample, so in between.
+
+type Vector = [Float; 3];
+
+fn sub(a: &Vector, b: &Vector) -> Vector {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: &Vector, b: &Vector) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &Vector, b: &Vector) -> Vector {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: &Vector) -> Vector {
+    let length = dot(a, a).sqrt();
+    if length > 0.0 {
+        [a[0] / length, a[1] / length, a[2] / length]
+    } else {
+        *a
+    }
+}
+
+/**
+ * Sum of three values weighted by barycentric coordinates.
+ */
+fn interpolate<const N: usize>(
+    values: [&[Float; N]; 3],
+    weights: [Float; 3],
+) -> [Float; N] {
+    let mut result = [0.0; N];
+    for (value, weight) in values.iter().zip(weights) {
+        for c in 0..N {
+            result[c] += value[c] * weight;
+        }
+    }
+    result
+}
+
+fn vector(a: &Array1<Float>) -> Vector {
+    [a[0], a[1], a[2]]
+}
+
+/**
+ * Little endian values read in turn from an encoded mesh, see
+ * `Mesh::encode`.
+ */
+pub(crate) struct Bytes<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Bytes<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        if self.bytes.len() < N {
+            return Err("truncated mesh".to_string());
+        }
+        let (value, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+        Ok(value.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, String> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, String> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<usize, String> {
+        self.take().map(|bytes| u64::from_le_bytes(bytes) as usize)
+    }
+
+    fn float(&mut self) -> Result<Float, String> {
+        #[allow(clippy::unnecessary_cast)]
+        let value = f64::from_le_bytes(self.take()?) as Float;
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err("values of the mesh are not finite".to_string())
+        }
+    }
+
+    fn floats<const N: usize>(&mut self) -> Result<[Float; N], String> {
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = self.float()?;
+        }
+        Ok(values)
+    }
+
+    /**
+     * A number of items of `size` bytes, no more than the bytes left
+     * hold.
+     */
+    pub(crate) fn count(&mut self, size: usize) -> Result<usize, String> {
+        let count = self.u64()?;
+        if count.saturating_mul(size) > self.bytes.len() {
+            return Err("truncated mesh".to_string());
+        }
+        Ok(count)
+    }
+
+    fn list<T>(
+        &mut self,
+        count: usize,
+        mut item: impl FnMut(&mut Bytes<'a>) -> Result<T, String>,
+    ) -> Result<Vec<T>, String> {
+        (0..count).map(|_| item(self)).collect()
+    }
+}
+
+fn put_count(out: &mut Vec<u8>, count: usize) {
+    out.extend_from_slice(&(count as u64).to_le_bytes());
+}
+
+/**
+ * As f64 whatever the precision of `Float`, so a mesh decodes in both.
+ */
+fn put_floats(out: &mut Vec<u8>, values: &[Float]) {
+    // Float is f32 with the `f32` feature.
+    #[allow(clippy::unnecessary_cast)]
+    for &value in values {
+        out.extend_from_slice(&(value as f64).to_le_bytes());
+    }
+}
+
+/**
+ * Triangles per leaf of the hierarchy, at most.
+ */
+pub(crate) const LEAF_SIZE: usize = 4;
+
+#[derive(Clone)]
+pub(crate) enum Node {
+    /**
+     * The triangles `first..first + count` (in the order of the build).
+     */
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Inner {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl Node {
+    pub(crate) fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => bounds,
+        }
+    }
+}
+
+/**
+ * The hierarchy of the triangles of a mesh.
+ */
+#[derive(Clone)]
+enum Hierarchy {
+    Binary(Vec<Node>),
+    /**
+     * Quantized nodes of up to 8 children, see `Mesh::compressed`.
+     */
+    Wide(WideBvh),
+}
+
+impl Hierarchy {
+    /**
+     * An empty hierarchy of the same layout, to be built.
+     */
+    fn cleared(&self) -> Hierarchy {
+        match self {
+            Hierarchy::Binary(_) => Hierarchy::Binary(vec![]),
+            Hierarchy::Wide(_) => Hierarchy::Wide(WideBvh::default()),
+        }
+    }
+}
+
+/**
+ * Height map displacing the vertices of a mesh along their normals, by
+ * `scale` times the average of the red, green and blue of the texture at
+ * their texture coordinates. The mesh is subdivided `subdivisions` times
+ * first (every triangle split in 4), to carry the detail of the map.
+ */
+#[derive(Clone)]
+pub struct Displacement {
+    pub texture: Arc<dyn TextureSampler>,
+    pub scale: Float,
+    pub subdivisions: u32,
+}
+
+/**
+ * A ray in the space of the watertight triangle test: the axes permuted so
+ * the ray goes along the last one (its largest component, positive), then
+ * sheared so it goes along (0, 0, 1) with a unit step.
+ */
+struct ShearedRay {
+    origin: Vector,
+    axes: [usize; 3],
+    shear: Vector,
+}
+
+impl ShearedRay {
+    fn new(origin: Vector, direction: &Vector) -> ShearedRay {
+        let z = (0..3)
+            .max_by(|&i, &j| direction[i].abs().total_cmp(&direction[j].abs()))
+            .unwrap_or(2);
+        let (mut x, mut y) = ((z + 1) % 3, (z + 2) % 3);
+        // Keep the winding of the triangles.
+        if direction[z] < 0.0 {
+            std::mem::swap(&mut x, &mut y);
+        }
+        ShearedRay {
+            origin,
+            axes: [x, y, z],
+            shear: [
+                direction[x] / direction[z],
+                direction[y] / direction[z],
+                1.0 / direction[z],
+            ],
+        }
+    }
+
+    /**
+     * The point `p`, relative to the origin, in the space of the ray.
+     */
+    fn shear(&self, p: &Vector) -> Vector {
+        let [x, y, z] = self.axes;
+        [
+            p[x] - self.shear[0] * p[z],
+            p[y] - self.shear[1] * p[z],
+            self.shear[2] * p[z],
+        ]
+    }
+}
+
+#[derive(Clone)]
+pub struct Mesh {
+    positions: Vec<Vector>,
+    normals: Vec<Vector>,
+    uvs: Vec<[Float; 2]>,
+    triangles: Vec<[usize; 3]>,
+    /**
+     * Positions and normals of the vertices at the later samples of the
+     * shutter interval, `positions` and `normals` being those when it
+     * opens. Empty if the mesh does not deform.
+     */
+    deformation: Vec<(Vec<Vector>, Vec<Vector>)>,
+    /**
+     * Shared with the other actors of the same material (see
+     * `MaterialLibrary`).
+     */
+    pub material: Arc<dyn Scattering>,
+    nodes: Hierarchy,
+}
+
+impl Mesh {
+    /**
+     * A mesh of triangles indexing the vertices `positions`, with their
+     * texture coordinates `uvs`. The vertex normals are the area weighted
+     * average of the normals of the triangles around them, which face
+     * where the triangles go counterclockwise.
+     */
+    pub fn new(
+        positions: Vec<Vector>,
+        uvs: Vec<[Float; 2]>,
+        triangles: Vec<[usize; 3]>,
+        material: Arc<dyn Scattering>,
+    ) -> Result<Mesh, String> {
+        if triangles.is_empty() {
+            return Err("mesh without triangles".to_string());
+        }
+        if uvs.len() != positions.len() {
+            return Err(format!(
+                "{} texture coordinates for {} vertices",
+                uvs.len(),
+                positions.len()
+            ));
+        }
+        if let Some(triangle) = triangles
+            .iter()
+            .find(|triangle| triangle.iter().any(|&i| i >= positions.len()))
+        {
+            return Err(format!(
+                "triangle {:?} out of the {} vertices",
+                triangle,
+                positions.len()
+            ));
+        }
+        if !positions.iter().flatten().all(|c| c.is_finite()) {
+            return Err("vertex positions are not finite".to_string());
+        }
+
+        let mut mesh = Mesh {
+            normals: vec![],
+            positions,
+            uvs,
+            triangles,
+            deformation: vec![],
+            material,
+            nodes: Hierarchy::Binary(vec![]),
+        };
+        mesh.compute_normals();
+        mesh.build();
+        Ok(mesh)
+    }
+
+    /**
+     * The mesh deformed over the shutter interval: its vertices at
+     * `positions` when it opens, then at each of `samples` in turn, evenly
+     * spaced until it closes.
+     */
+    pub fn with_deformation(
+        mut self,
+        samples: Vec<Vec<Vector>>,
+    ) -> Result<Mesh, String> {
+        for sample in &samples {
+            if sample.len() != self.positions.len() {
+                return Err(format!(
+                    "{} deformed vertices for {} vertices",
+                    sample.len(),
+                    self.positions.len()
+                ));
+            }
+            if !sample.iter().flatten().all(|c| c.is_finite()) {
+                return Err("deformed positions are not finite".to_string());
+            }
+        }
+        self.deformation =
+            samples.into_iter().map(|sample| (sample, vec![])).collect();
+        self.compute_normals();
+        self.build();
+        Ok(self)
+    }
+
+    /**
+     * The mesh with the vertex normals `normals` instead of those of its
+     * triangles, e.g. those of the whole mesh for a part of it.
+     */
+    pub fn with_normals(
+        mut self,
+        normals: Vec<Vector>,
+    ) -> Result<Mesh, String> {
+        if normals.len() != self.positions.len() {
+            return Err(format!(
+                "{} normals for {} vertices",
+                normals.len(),
+                self.positions.len()
+            ));
+        }
+        if !normals.iter().flatten().all(|c| c.is_finite()) {
+            return Err("vertex normals are not finite".to_string());
+        }
+        self.normals = normals.iter().map(normalize).collect();
+        Ok(self)
+    }
+
+    /**
+     * The mesh with a compressed hierarchy (see `wide_bvh`), taking
+     * several times less memory, for large meshes. Kept when the mesh is
+     * subdivided, displaced or moved.
+     */
+    pub fn compressed(mut self) -> Result<Mesh, String> {
+        self.nodes = Hierarchy::Wide(WideBvh::default());
+        self.build();
+        match self.nodes {
+            Hierarchy::Wide(_) => Ok(self),
+            Hierarchy::Binary(_) => {
+                Err("the mesh is too large to compress its hierarchy"
+                    .to_string())
+            }
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(self.nodes, Hierarchy::Wide(_))
+    }
+
+    /**
+     * The mesh as built, its hierarchy included, to be decoded (see
+     * `decode`) instead of built again. The material is not part of it.
+     */
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![];
+        put_count(&mut out, self.positions.len());
+        put_count(&mut out, self.triangles.len());
+        put_count(&mut out, self.deformation.len());
+        let samples = std::iter::once((&self.positions, &self.normals))
+            .chain(self.deformation.iter().map(|(p, n)| (p, n)));
+        for (positions, normals) in samples {
+            for vector in positions.iter().chain(normals) {
+                put_floats(&mut out, vector);
+            }
+        }
+        for uv in &self.uvs {
+            put_floats(&mut out, uv);
+        }
+        for triangle in &self.triangles {
+            for &vertex in triangle {
+                put_count(&mut out, vertex);
+            }
+        }
+
+        match &self.nodes {
+            Hierarchy::Binary(nodes) => {
+                out.push(0);
+                put_count(&mut out, nodes.len());
+                for node in nodes {
+                    let (tag, a, b) = match *node {
+                        Node::Leaf { first, count, .. } => (0, first, count),
+                        Node::Inner { left, right, .. } => (1, left, right),
+                    };
+                    out.push(tag);
+                    put_floats(&mut out, &node.bounds().min);
+                    put_floats(&mut out, &node.bounds().max);
+                    put_count(&mut out, a);
+                    put_count(&mut out, b);
+                }
+            }
+            Hierarchy::Wide(wide) => {
+                out.push(1);
+                wide.encode(&mut out);
+            }
+        }
+        out
+    }
+
+    /**
+     * A mesh of `material` encoded by `encode`, without building its
+     * hierarchy. Checks the indices, so that a damaged mesh is refused
+     * rather than traversed.
+     */
+    pub fn decode(
+        bytes: &[u8],
+        material: Arc<dyn Scattering>,
+    ) -> Result<Mesh, String> {
+        let mut bytes = Bytes { bytes };
+        let vertices = bytes.count(1)?;
+        let triangles = bytes.count(1)?;
+        let samples = bytes.count(1)?;
+        let vectors =
+            |bytes: &mut Bytes| bytes.list(vertices, Bytes::floats::<3>);
+        let positions = vectors(&mut bytes)?;
+        let normals = vectors(&mut bytes)?;
+        let mut deformation = vec![];
+        for _ in 0..samples {
+            deformation.push((vectors(&mut bytes)?, vectors(&mut bytes)?));
+        }
+        let uvs = bytes.list(vertices, Bytes::floats::<2>)?;
+        let triangles = bytes.list(triangles, |bytes| {
+            let triangle = [bytes.u64()?, bytes.u64()?, bytes.u64()?];
+            if triangle.iter().any(|&vertex| vertex >= vertices) {
+                return Err(format!(
+                    "triangle {:?} out of the {} vertices",
+                    triangle, vertices
+                ));
+            }
+            Ok(triangle)
+        })?;
+
+        let nodes = match bytes.u8()? {
+            0 => {
+                let count = bytes.count(57)?;
+                let nodes = bytes.list(count, |bytes| {
+                    let tag = bytes.u8()?;
+                    let bounds = Aabb::new(bytes.floats()?, bytes.floats()?);
+                    let (a, b) = (bytes.u64()?, bytes.u64()?);
+                    Ok(match tag {
+                        0 => Node::Leaf {
+                            bounds,
+                            first: a,
+                            count: b,
+                        },
+                        _ => Node::Inner {
+                            bounds,
+                            left: a,
+                            right: b,
+                        },
+                    })
+                })?;
+                // Children after their parent, so there are no cycles.
+                let valid = |(index, node): (usize, &Node)| match *node {
+                    Node::Leaf { first, count, .. } => first
+                        .checked_add(count)
+                        .is_some_and(|end| end <= triangles.len()),
+                    Node::Inner { left, right, .. } => {
+                        index < left.min(right) && left.max(right) < count
+                    }
+                };
+                if nodes.is_empty() || !nodes.iter().enumerate().all(valid) {
+                    return Err("invalid hierarchy".to_string());
+                }
+                Hierarchy::Binary(nodes)
+            }
+            _ => Hierarchy::Wide(WideBvh::decode(&mut bytes, triangles.len())?),
+        };
+        if !bytes.bytes.is_empty() {
+            return Err("trailing bytes after the mesh".to_string());
+        }
+
+        Ok(Mesh {
+            positions,
+            normals,
+            uvs,
+            triangles,
+            deformation,
+            material,
+            nodes,
+        })
+    }
+
+    /**
+     * A flat square grid of `resolution` x `resolution` cells facing up
+     * (+y), `size` wide and centered at `center`; u runs along x and v
+     * along -z. The base of terrains.
+     */
+    pub fn grid(
+        center: [Float; 3],
+        size: Float,
+        resolution: usize,
+        material: Arc<dyn Scattering>,
+    ) -> Result<Mesh, String> {
+        if resolution == 0 {
+            return Err("grid without cells".to_string());
+        }
+
+        let n = resolution + 1;
+        let mut positions = Vec::with_capacity(n * n);
+        let mut uvs = Vec::with_capacity(n * n);
+        for row in 0..n {
+            for column in 0..n {
+                let u = column as Float / resolution as Float;
+                let v = row as Float / resolution as Float;
+                positions.push([
+                    center[0] + (u - 0.5) * size,
+                    center[1],
+                    center[2] - (v - 0.5) * size,
+                ]);
+                uvs.push([u, v]);
+            }
+        }
+
+        let mut triangles = Vec::with_capacity(2 * resolution * resolution);
+        for row in 0..resolution {
+            for column in 0..resolution {
+                let corner = row * n + column;
+                triangles.push([corner, corner + 1, corner + n + 1]);
+                triangles.push([corner, corner + n + 1, corner + n]);
+            }
+        }
+
+        Mesh::new(positions, uvs, triangles, material)
+    }
+
+    pub fn vertices(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangles(&self) -> usize {
+        self.triangles.len()
+    }
+
+    /**
+     * The vertices of a triangle, counterclockwise.
+     */
+    pub fn triangle(&self, triangle: usize) -> [usize; 3] {
+        self.triangles[triangle]
+    }
+
+    pub fn position(&self, vertex: usize) -> [Float; 3] {
+        self.positions[vertex]
+    }
+
+    pub fn normal(&self, vertex: usize) -> [Float; 3] {
+        self.normals[vertex]
+    }
+
+    pub fn uv(&self, vertex: usize) -> [Float; 2] {
+        self.uvs[vertex]
+    }
+
+    /**
+     * Bytes the hierarchy of the triangles takes.
+     */
+    pub fn hierarchy_bytes(&self) -> usize {
+        match &self.nodes {
+            Hierarchy::Binary(nodes) => {
+                nodes.len() * std::mem::size_of::<Node>()
+            }
+            Hierarchy::Wide(wide) => wide.bytes(),
+        }
+    }
+
+    /**
+     * Bytes the vertices, triangles and hierarchy of the mesh take.
+     */
+    pub fn bytes(&self) -> usize {
+        let vertex = std::mem::size_of::<Vector>();
+        let deformed = self
+            .deformation
+            .iter()
+            .map(|(positions, normals)| {
+                (positions.len() + normals.len()) * vertex
+            })
+            .sum::<usize>();
+        (self.positions.len() + self.normals.len()) * vertex
+            + self.uvs.len() * std::mem::size_of::<[Float; 2]>()
+            + self.triangles.len() * std::mem::size_of::<[usize; 3]>()
+            + self.hierarchy_bytes()
+            + deformed
+    }
+
+    /**
+     * The samples of the deformation, the positions when the shutter
+     * opens included (1 if the mesh does not deform).
+     */
+    pub fn deformation_samples(&self) -> usize {
+        1 + self.deformation.len()
+    }
+
+    /**
+     * The vertices of `triangle`, their positions then their normals (not
+     * of unit length between samples), at `time` of the shutter interval.
+     */
+    fn corners(&self, triangle: usize, time: Float) -> [[Vector; 3]; 2] {
+        let vertices = self.triangles[triangle];
+        if self.deformation.is_empty() {
+            return [
+                vertices.map(|i| self.positions[i]),
+                vertices.map(|i| self.normals[i]),
+            ];
+        }
+        let last = self.deformation.len();
+        let at = time.clamp(0.0, 1.0) * last as Float;
+        let sample = (at.floor() as usize).min(last - 1);
+        let f = at - sample as Float;
+        let (from, to) = match sample {
+            0 => ((&self.positions, &self.normals), &self.deformation[0]),
+            _ => {
+                let (p, n) = &self.deformation[sample - 1];
+                ((p, n), &self.deformation[sample])
+            }
+        };
+        let lerp = |a: &Vector, b: &Vector| {
+            [0, 1, 2].map(|c| a[c] + f * (b[c] - a[c]))
+        };
+        [
+            vertices.map(|i| lerp(&from.0[i], &to.0[i])),
+            vertices.map(|i| lerp(&from.1[i], &to.1[i])),
+        ]
+    }
+
+    /**
+     * Every triangle split in 4 by the midpoints of its edges, which are
+     * shared by the triangles on both sides (the mesh stays closed). The
+     * shape does not change, the new vertices lie on the triangles.
+     */
+    pub fn subdivide(&self) -> Mesh {
+        let mut positions = self.positions.clone();
+        let mut uvs = self.uvs.clone();
+        let mut deformation: Vec<Vec<Vector>> = self
+            .deformation
+            .iter()
+            .map(|(sample, _)| sample.clone())
+            .collect();
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(interpolate(
+                    [&self.positions[a], &self.positions[b], &[0.0; 3]],
+                    [0.5, 0.5, 0.0],
+                ));
+                for (sample, (original, _)) in
+                    deformation.iter_mut().zip(&self.deformation)
+                {
+                    sample.push(interpolate(
+                        [&original[a], &original[b], &[0.0; 3]],
+                        [0.5, 0.5, 0.0],
+                    ));
+                }
+                uvs.push(interpolate(
+                    [&self.uvs[a], &self.uvs[b], &[0.0; 2]],
+                    [0.5, 0.5, 0.0],
+                ));
+                positions.len() - 1
+            })
+        };
+
+        let mut triangles = Vec::with_capacity(4 * self.triangles.len());
+        for &[a, b, c] in &self.triangles {
+            let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+            triangles.push([a, ab, ca]);
+            triangles.push([ab, b, bc]);
+            triangles.push([ca, bc, c]);
+            triangles.push([ab, bc, ca]);
+        }
+
+        let mut mesh = Mesh {
+            positions,
+            normals: vec![],
+            uvs,
+            triangles,
+            deformation: deformation
+                .into_iter()
+                .map(|sample| (sample, vec![]))
+                .collect(),
+            material: self.material.clone(),
+            nodes: self.nodes.cleared(),
+        };
+        mesh.compute_normals();
+        mesh.build();
+        mesh
+    }
+
+    /**
+     * The mesh subdivided and displaced by a height map. The texture is
+     * filtered to the size of the subdivided triangles. Vertices split
+     * along texture seams may be displaced apart.
+     */
+    pub fn displace(&self, displacement: &Displacement) -> Mesh {
+        let mut mesh = self.clone();
+        for _ in 0..displacement.subdivisions {
+            mesh = mesh.subdivide();
+        }
+
+        // Average length of the edges in texture coordinates.
+        let mut footprint = 0.0;
+        for triangle in &mesh.triangles {
+            for i in 0..3 {
+                let a = mesh.uvs[triangle[i]];
+                let b = mesh.uvs[triangle[(i + 1) % 3]];
+                footprint +=
+                    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+            }
+        }
+        footprint /= (3 * mesh.triangles.len()) as Float;
+
+        let heights: Vec<Float> = mesh
+            .uvs
+            .iter()
+            .map(|uv| {
+                let texel = displacement.texture.sample(*uv, footprint);
+                displacement.scale * (texel[0] + texel[1] + texel[2]) / 3.0
+            })
+            .collect();
+        // Along the normals of every sample of the deformation.
+        let displace = |positions: &mut Vec<Vector>, normals: &[Vector]| {
+            for ((position, normal), height) in
+                positions.iter_mut().zip(normals).zip(&heights)
+            {
+                for (coordinate, n) in position.iter_mut().zip(normal) {
+                    *coordinate += height * n;
+                }
+            }
+        };
+        displace(&mut mesh.positions, &mesh.normals);
+        for (positions, normals) in &mut mesh.deformation {
+            displace(positions, normals);
+        }
+
+        mesh.compute_normals();
+        mesh.build();
+        mesh
+    }
+
+    fn compute_normals(&mut self) {
+        self.normals = self.normals_of(&self.positions);
+        let deformed: Vec<Vec<Vector>> = self
+            .deformation
+            .iter()
+            .map(|(positions, _)| self.normals_of(positions))
+            .collect();
+        for ((_, normals), computed) in
+            self.deformation.iter_mut().zip(deformed)
+        {
+            *normals = computed;
+        }
+    }
+
+    fn normals_of(&self, positions: &[Vector]) -> Vec<Vector> {
+        let mut normals = vec![[0.0; 3]; positions.len()];
+        for triangle in &self.triangles {
+            let [a, b, c] = triangle.map(|i| positions[i]);
+            // Twice the area, facing out.
+            let normal = cross(&sub(&b, &a), &sub(&c, &a));
+            for &i in triangle {
+                for axis in 0..3 {
+                    normals[i][axis] += normal[axis];
+                }
+            }
+        }
+        normals.iter().map(normalize).collect()
+    }
+
+    /**
+     * Around the triangle at every sample of the deformation.
+     */
+    fn triangle_bounds(&self, triangle: usize) -> Aabb {
+        std::iter::once(&self.positions)
+            .chain(self.deformation.iter().map(|(positions, _)| positions))
+            .map(|positions| {
+                let [a, b, c] = self.triangles[triangle].map(|i| positions[i]);
+                Aabb::new(
+                    [0, 1, 2].map(|axis| a[axis].min(b[axis]).min(c[axis])),
+                    [0, 1, 2].map(|axis| a[axis].max(b[axis]).max(c[axis])),
+                )
+            })
+            .reduce(|a, b| a.union(&b))
+            .unwrap()
+    }
+
+    /**
+     * Build the hierarchy with the surface area heuristic (see `bvh`).
+     * Reorders the triangles so the leaves are ranges of them. A
+     * compressed hierarchy is collapsed from the binary one, which it
+     * stays if the mesh is beyond the range of f32.
+     */
+    fn build(&mut self) {
+        let bounds: Vec<Aabb> = (0..self.triangles.len())
+            .map(|triangle| self.triangle_bounds(triangle))
+            .collect();
+        let (nodes, order) = bvh::build(&bounds);
+        self.triangles = order.iter().map(|&i| self.triangles[i]).collect();
+        self.nodes = match self.nodes {
+            Hierarchy::Wide(_) => match WideBvh::new(&nodes) {
+                Ok((wide, order)) => {
+                    self.triangles =
+                        order.iter().map(|&i| self.triangles[i]).collect();
+                    Hierarchy::Wide(wide)
+                }
+                Err(_) => Hierarchy::Binary(nodes),
+            },
+            Hierarchy::Binary(_) => Hierarchy::Binary(nodes),
+        };
+    }
+
+    /**
+     * Watertight ray-triangle test (Woop, Benthin and Wald): distance and
+     * barycentric coordinates of the second and third vertices where the
+     * ray crosses the triangle. The vertices, relative to the origin, are
+     * sheared into the space of the ray (see `ShearedRay`), where the test
+     * is 2D: the signs of the three edge functions at the origin. An edge
+     * shared by two triangles gives both the same value with opposite
+     * signs, so a ray through it hits one of them, never none.
+     */
+    fn intersect(
+        corners: &[Vector; 3],
+        ray: &ShearedRay,
+    ) -> Option<(Float, Float, Float)> {
+        let [a, b, c] =
+            corners.map(|corner| ray.shear(&sub(&corner, &ray.origin)));
+        let u = c[0] * b[1] - c[1] * b[0];
+        let v = a[0] * c[1] - a[1] * c[0];
+        let w = b[0] * a[1] - b[1] * a[0];
+        // The origin is outside unless the edge functions agree (zero on
+        // an edge).
+        if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+            return None;
+        }
+        // Seen edge-on.
+        let determinant = u + v + w;
+        if determinant == 0.0 {
+            return None;
+        }
+        let t = (u * a[2] + v * b[2] + w * c[2]) / determinant;
+        Some((t, v / determinant, w / determinant))
+    }
+
+    /**
+     * The closest triangle the ray crosses between `t_min` and `t_max`,
+     * with its distance and barycentric coordinates.
+     */
+    fn closest(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<(usize, Float, Float, Float)> {
+        let origin = vector(&ray.origin);
+        let direction = vector(&ray.direction);
+        let inverse_direction = direction.map(|d| 1.0 / d);
+        let sheared = ShearedRay::new(origin, &direction);
+
+        let mut closest = None;
+        let mut t_closest = t_max;
+        let mut tests = 0;
+        let mut test = |triangle: usize, t_closest: &mut Float| {
+            tests += 1;
+            let [corners, _] = self.corners(triangle, ray.time);
+            if let Some((t, u, v)) = Mesh::intersect(&corners, &sheared) {
+                if t_min < t && t < *t_closest {
+                    *t_closest = t;
+                    closest = Some((triangle, t, u, v));
+                }
+            }
+        };
+        let nodes = match &self.nodes {
+            Hierarchy::Binary(nodes) => nodes,
+            Hierarchy::Wide(wide) => {
+                wide.traverse(
+                    &origin,
+                    &inverse_direction,
+                    t_min,
+                    t_max,
+                    |triangles, mut t_closest| {
+                        for triangle in triangles {
+                            test(triangle, &mut t_closest);
+                        }
+                        t_closest
+                    },
+                );
+                count_work(0, tests);
+                return closest;
+            }
+        };
+        let mut visited = 0;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            visited += 1;
+            let node = &nodes[node];
+            if !node.bounds().is_hit(
+                &origin,
+                &inverse_direction,
+                t_min,
+                t_closest,
+            ) {
+                continue;
+            }
+            match *node {
+                Node::Leaf { first, count, .. } => {
+                    for triangle in first..first + count {
+                        test(triangle, &mut t_closest);
+                    }
+                }
+                Node::Inner { left, right, .. } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+        count_work(visited, tests);
+        closest
+    }
+
+    /**
+     * Barycentric coordinates of a point in the plane of a triangle.
+     */
+    fn barycentric(
+        &self,
+        triangle: usize,
+        point: &Vector,
+        time: Float,
+    ) -> [Float; 3] {
+        let [[a, b, c], _] = self.corners(triangle, time);
+        let normal = cross(&sub(&b, &a), &sub(&c, &a));
+        let area = dot(&normal, &normal);
+        let wb = dot(&cross(&sub(point, &a), &sub(&c, &a)), &normal) / area;
+        let wc = dot(&cross(&sub(&b, &a), &sub(point, &a)), &normal) / area;
+        [1.0 - wb - wc, wb, wc]
+    }
+
+    fn shading_normal(
+        &self,
+        triangle: usize,
+        weights: [Float; 3],
+        time: Float,
+    ) -> Vector {
+        let [_, [a, b, c]] = self.corners(triangle, time);
+        normalize(&interpolate([&a, &b, &c], weights))
+    }
+
+    fn texture_coordinates(
+        &self,
+        triangle: usize,
+        weights: [Float; 3],
+    ) -> [Float; 2] {
+        let [a, b, c] = self.triangles[triangle].map(|i| &self.uvs[i]);
+        interpolate([a, b, c], weights)
+    }
+
+    /**
+     * Direction of increasing u on the triangle, perpendicular to the
+     * normal, or any perpendicular where u does not vary.
+     */
+    fn tangent(&self, triangle: usize, normal: &Vector, time: Float) -> Vector {
+        let [a, b, c] = self.triangles[triangle];
+        let [[pa, pb, pc], _] = self.corners(triangle, time);
+        let (ua, ub, uc) = (self.uvs[a], self.uvs[b], self.uvs[c]);
+        let (e1, e2) = (sub(&pb, &pa), sub(&pc, &pa));
+        let (du1, dv1) = (ub[0] - ua[0], ub[1] - ua[1]);
+        let (du2, dv2) = (uc[0] - ua[0], uc[1] - ua[1]);
+        let determinant = du1 * dv2 - du2 * dv1;
+
+        let dpdu = if determinant.abs() > 1e-12 {
+            [0, 1, 2].map(|i| (dv2 * e1[i] - dv1 * e2[i]) / determinant)
+        } else {
+            e1
+        };
+        let along = dot(&dpdu, normal);
+        let tangent =
+            normalize(&[0, 1, 2].map(|i| dpdu[i] - along * normal[i]));
+        if dot(&tangent, &tangent) > 0.5 {
+            tangent
+        } else if normal[0].abs() < 0.9 {
+            normalize(&cross(normal, &[1.0, 0.0, 0.0]))
+        } else {
+            normalize(&cross(normal, &[0.0, 1.0, 0.0]))
+        }
+    }
+
+    fn fill(
+        &self,
+        ray: &Ray,
+        triangle: usize,
+        t: Float,
+        u: Float,
+        v: Float,
+        record: &mut Hit,
+    ) {
+        let weights = [1.0 - u - v, u, v];
+        let normal = self.shading_normal(triangle, weights, ray.time);
+        let uv = self.texture_coordinates(triangle, weights);
+        record.t = t;
+        record.point = ray.point_at_parameter(t);
+        record.normal = arr1(&[normal[0], normal[1], normal[2], 0.0]);
+        let tangent = self.tangent(triangle, &normal, ray.time);
+        record.tangent = arr1(&[tangent[0], tangent[1], tangent[2], 0.0]);
+        record.uv = uv;
+        record.material = self.material.clone();
+        self.compute_differentials(ray, triangle, weights, record);
+    }
+
+    /**
+     * Where the differential rays of `ray` cross the plane of the
+     * triangle, with the normals and texture coordinates interpolated
+     * there, as for spheres.
+     */
+    fn compute_differentials(
+        &self,
+        ray: &Ray,
+        triangle: usize,
+        weights: [Float; 3],
+        record: &mut Hit,
+    ) {
+        record.footprint = 0.0;
+        record.differentials = None;
+        let differentials = match &ray.differentials {
+            Some(differentials) => differentials,
+            None => return,
+        };
+
+        let [[a, b, c], _] = self.corners(triangle, ray.time);
+        let plane = normalize(&cross(&sub(&b, &a), &sub(&c, &a)));
+        let point = vector(&record.point);
+        let offset = |origin: &Array1<Float>, direction: &Array1<Float>| {
+            let (origin, direction) = (vector(origin), vector(direction));
+            let cosine = dot(&direction, &plane);
+            if cosine.abs() < 1e-9 {
+                return None;
+            }
+            let t = dot(&sub(&point, &origin), &plane) / cosine;
+            Some([0, 1, 2].map(|i| origin[i] + t * direction[i] - point[i]))
+        };
+        let (dpdx, dpdy) = match (
+            offset(&differentials.x_origin, &differentials.x_direction),
+            offset(&differentials.y_origin, &differentials.y_direction),
+        ) {
+            (Some(dpdx), Some(dpdy)) => (dpdx, dpdy),
+            _ => return,
+        };
+
+        let normal = self.shading_normal(triangle, weights, ray.time);
+        let uv = record.uv;
+        let derivatives = |dp: &Vector| {
+            let moved = [0, 1, 2].map(|i| point[i] + dp[i]);
+            let weights = self.barycentric(triangle, &moved, ray.time);
+            let moved_normal = self.shading_normal(triangle, weights, ray.time);
+            let moved_uv = self.texture_coordinates(triangle, weights);
+            let (du, dv) = (moved_uv[0] - uv[0], moved_uv[1] - uv[1]);
+            (sub(&moved_normal, &normal), (du * du + dv * dv).sqrt())
+        };
+        let (dndx, width_x) = derivatives(&dpdx);
+        let (dndy, width_y) = derivatives(&dpdy);
+        let direction = |d: Vector| arr1(&[d[0], d[1], d[2], 0.0]);
+
+        record.footprint = width_x.max(width_y);
+        record.differentials = Some(HitDifferentials {
+            dpdx: direction(dpdx),
+            dpdy: direction(dpdy),
+            dndx: direction(dndx),
+            dndy: direction(dndy),
+        });
+    }
+}
+
+impl Hittable for Mesh {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        // The closest triangle, then the next ones behind it through the
+        // holes of cutout materials.
+        let mut t_min = t_min;
+        while let Some((triangle, t, u, v)) = self.closest(ray, t_min, t_max) {
+            self.fill(ray, triangle, t, u, v, record);
+            if record.alpha_test() {
+                return true;
+            }
+            t_min = t;
+        }
+        false
+    }
+}
+
+impl RayTraceable for Mesh {
+    fn validate(&self) -> Result<(), String> {
+        let deformed = self.deformation.iter().flat_map(|(sample, _)| sample);
+        if !self
+            .positions
+            .iter()
+            .chain(deformed)
+            .flatten()
+            .all(|c| c.is_finite())
+        {
+            return Err("vertex positions are not finite".to_string());
+        }
+        Ok(())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match &self.nodes {
+            Hierarchy::Binary(nodes) => Some(*nodes[0].bounds()),
+            Hierarchy::Wide(wide) => Some(wide.bounds()),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        "mesh"
+    }
+
+    fn triangles(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.material.textures(textures);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        let deformed =
+            self.deformation.iter_mut().flat_map(|(sample, _)| sample);
+        for position in self.positions.iter_mut().chain(deformed) {
+            *position = [0, 1, 2].map(|i| position[i] + offset[i]);
+        }
+        let nodes = match &mut self.nodes {
+            Hierarchy::Binary(nodes) => nodes,
+            // Quantized on a grid that does not move with them.
+            Hierarchy::Wide(_) => return self.build(),
+        };
+        for node in nodes {
+            match node {
+                Node::Leaf { bounds, .. } | Node::Inner { bounds, .. } => {
+                    *bounds = bounds.translated(offset);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::actor::Aabb;
+use crate::actor::Hit;
+use crate::actor::Hittable;
+use crate::actor::RayTraceable;
+use crate::light::orthonormal_basis;
+use crate::material::Scattering;
+use crate::texture::TextureSampler;
+use ndarray::arr1;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Metaballs (blobby objects): point charges whose fields add up, the
+// surface where the sum reaches a threshold. Close charges blend into one
+// smooth blob, negative ones dig into the others.
+//
+// The field of a charge falls off with the squared distance d² as
+// (1 - d² / r²)³ (Wyvill's polynomial), reaching 0 at its radius r with a
+// null slope, so that only the charges whose spheres a ray crosses count
+// there. A ray is sampled along those spheres in steps of a fraction of
+// the smallest radius until the field crosses the threshold, and the
+// crossing is then refined by bisection. The normal is the gradient of the
+// field, computed exactly, smooth across the blends.
+
+/**
+ * A charge of `strength` (negative to subtract) at `center`, of influence
+ * within `radius`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Charge {
+    pub center: [Float; 3],
+    pub radius: Float,
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub strength: Float,
+}
+
+#[cfg(feature = "serde")]
+fn one() -> Float {
+    1.0
+}
+
+impl Charge {
+    /**
+     * Field of the charge at `point`, and its gradient.
+     */
+    fn field(&self, point: &[Float; 3]) -> (Float, [Float; 3]) {
+        let offset = [0, 1, 2].map(|i| point[i] - self.center[i]);
+        let r2 = self.radius * self.radius;
+        let x = (offset[0] * offset[0]
+            + offset[1] * offset[1]
+            + offset[2] * offset[2])
+            / r2;
+        if x >= 1.0 {
+            return (0.0, [0.0; 3]);
+        }
+        let falloff = 1.0 - x;
+        let slope = -6.0 * self.strength * falloff * falloff / r2;
+        (
+            self.strength * falloff * falloff * falloff,
+            offset.map(|c| slope * c),
+        )
+    }
+}
+
+/**
+ * The surface where the field of the `charges` reaches `threshold` (from
+ * 0 to the strength of a single charge: the lower, the larger and more
+ * blended the blobs). Rays sample the field `steps` times across the
+ * radius of the smallest charge.
+ */
+pub struct Metaballs {
+    pub charges: Vec<Charge>,
+    pub threshold: Float,
+    pub steps: u32,
+    pub material: Arc<dyn Scattering>,
+}
+
+impl Metaballs {
+    pub fn new(
+        charges: Vec<Charge>,
+        threshold: Float,
+        material: Arc<dyn Scattering>,
+    ) -> Metaballs {
+        Metaballs {
+            charges,
+            threshold,
+            steps: 16,
+            material,
+        }
+    }
+
+    /**
+     * Sum of the fields of the charges at `point` minus the threshold
+     * (positive inside), and its gradient.
+     */
+    pub fn field(&self, point: &[Float; 3]) -> (Float, [Float; 3]) {
+        let mut value = -self.threshold;
+        let mut gradient = [0.0; 3];
+        for charge in &self.charges {
+            let (field, slope) = charge.field(point);
+            value += field;
+            for i in 0..3 {
+                gradient[i] += slope[i];
+            }
+        }
+        (value, gradient)
+    }
+
+    /**
+     * Spans of the ray parameter within the spheres of the positive
+     * charges, the only places the field can reach the threshold, merged
+     * and in order.
+     */
+    fn spans(&self, ray: &Ray, t_min: Float, t_max: Float) -> Vec<[Float; 2]> {
+        let direction = [0, 1, 2].map(|i| ray.direction[i]);
+        let a = (0..3).map(|i| direction[i] * direction[i]).sum::<Float>();
+        let mut spans: Vec<[Float; 2]> = self
+            .charges
+            .iter()
+            .filter(|charge| charge.strength > 0.0)
+            .filter_map(|charge| {
+                let oc = [0, 1, 2].map(|i| ray.origin[i] - charge.center[i]);
+                let b = (0..3).map(|i| oc[i] * direction[i]).sum::<Float>();
+                let c = (0..3).map(|i| oc[i] * oc[i]).sum::<Float>()
+                    - charge.radius * charge.radius;
+                let discriminant = b * b - a * c;
+                if discriminant <= 0.0 {
+                    return None;
+                }
+                let root = discriminant.sqrt();
+                let span = [
+                    ((-b - root) / a).max(t_min),
+                    ((-b + root) / a).min(t_max),
+                ];
+                (span[0] < span[1]).then_some(span)
+            })
+            .collect();
+        spans.sort_by(|s, o| s[0].total_cmp(&o[0]));
+
+        let mut merged: Vec<[Float; 2]> = Vec::with_capacity(spans.len());
+        for span in spans {
+            match merged.last_mut() {
+                Some(last) if span[0] <= last[1] => {
+                    last[1] = last[1].max(span[1])
+                }
+                _ => merged.push(span),
+            }
+        }
+        merged
+    }
+}
+
+impl Hittable for Metaballs {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let speed = (0..3)
+            .map(|i| ray.direction[i] * ray.direction[i])
+            .sum::<Float>()
+            .sqrt();
+        if speed <= 0.0 || !speed.is_finite() {
+            return false;
+        }
+        let smallest = self
+            .charges
+            .iter()
+            .map(|charge| charge.radius)
+            .fold(Float::INFINITY, Float::min);
+        let step = smallest / self.steps.max(1) as Float / speed;
+        let at =
+            |t: Float| [0, 1, 2].map(|i| ray.origin[i] + t * ray.direction[i]);
+
+        for [start, end] in self.spans(ray, t_min, t_max) {
+            let mut t0 = start;
+            let mut f0 = self.field(&at(t0)).0;
+            while t0 < end {
+                let t1 = (t0 + step).min(end);
+                let f1 = self.field(&at(t1)).0;
+                if (f0 >= 0.0) != (f1 >= 0.0) {
+                    // Bisect down to the crossing.
+                    let (mut low, mut high) = (t0, t1);
+                    for _ in 0..48 {
+                        let middle = 0.5 * (low + high);
+                        if (self.field(&at(middle)).0 >= 0.0) == (f0 >= 0.0) {
+                            low = middle;
+                        } else {
+                            high = middle;
+                        }
+                    }
+                    let t = 0.5 * (low + high);
+                    let gradient = self.field(&at(t)).1;
+                    let length = (0..3)
+                        .map(|i| gradient[i] * gradient[i])
+                        .sum::<Float>()
+                        .sqrt();
+                    // Outwards, where the field decreases.
+                    let n = if length > 0.0 {
+                        gradient.map(|c| -c / length)
+                    } else {
+                        [0, 1, 2].map(|i| -ray.direction[i] / speed)
+                    };
+                    let (tangent, _) = orthonormal_basis(&n);
+
+                    record.t = t;
+                    record.point = ray.point_at_parameter(t);
+                    record.normal = arr1(&[n[0], n[1], n[2], 0.0]);
+                    record.tangent =
+                        arr1(&[tangent[0], tangent[1], tangent[2], 0.0]);
+                    record.uv = [0.0, 0.0];
+                    record.footprint = 0.0;
+                    record.differentials = None;
+                    record.material = self.material.clone();
+                    if record.alpha_test() {
+                        return true;
+                    }
+                }
+                t0 = t1;
+                f0 = f1;
+            }
+        }
+        false
+    }
+}
+
+impl RayTraceable for Metaballs {
+    fn validate(&self) -> Result<(), String> {
+        if self.charges.is_empty() {
+            return Err("metaballs without charges".to_string());
+        }
+        for charge in &self.charges {
+            if !charge.center.iter().all(|c| c.is_finite()) {
+                return Err("charge center is not finite".to_string());
+            }
+            if !(charge.radius > 0.0 && charge.radius.is_finite()) {
+                return Err(format!("invalid charge radius {}", charge.radius));
+            }
+            if !charge.strength.is_finite() {
+                return Err(format!(
+                    "invalid charge strength {}",
+                    charge.strength
+                ));
+            }
+        }
+        if !(self.threshold > 0.0 && self.threshold.is_finite()) {
+            return Err(format!("invalid threshold {}", self.threshold));
+        }
+        Ok(())
+    }
+
+    /**
+     * Around the spheres of the positive charges.
+     */
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.charges
+            .iter()
+            .filter(|charge| charge.strength > 0.0)
+            .map(|charge| {
+                let (c, r) = (charge.center, charge.radius);
+                Aabb::new(
+                    [c[0] - r, c[1] - r, c[2] - r],
+                    [c[0] + r, c[1] + r, c[2] + r],
+                )
+            })
+            .reduce(|a, b| a.union(&b))
+    }
+
+    fn kind(&self) -> &'static str {
+        "metaballs"
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.material.textures(textures);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        for charge in &mut self.charges {
+            charge.center = [0, 1, 2].map(|i| charge.center[i] + offset[i]);
+        }
+    }
+}
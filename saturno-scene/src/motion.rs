@@ -0,0 +1,265 @@
+use crate::actor::Aabb;
+use crate::actor::Hit;
+use crate::actor::Hittable;
+use crate::actor::RayTraceable;
+use crate::actor::Visibility;
+use crate::instance::Transform;
+use crate::texture::TextureSampler;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Motion blur of transformed actors: an actor placed by transforms sampled
+// at evenly spaced times of the shutter interval (the subframes a DCC
+// exports), the first when it opens, the last when it closes. A ray cast
+// at a time (see `Ray::time`) sees the actor placed between the two
+// samples around it: moved and scaled linearly, and turned along the
+// shortest arc between their orientations (the spherical interpolation of
+// their quaternions). Two samples move the actor along a straight line;
+// more bend its path through them, and a spinning wheel blurs along arcs
+// rather than chords.
+//
+// The actor is hit in its own space, through the inverse of the transform
+// at the time of the ray, as instances are (see `Instances`).
+
+type Vector = [Float; 3];
+type Quaternion = [Float; 4];
+
+/**
+ * The placement of a moving actor at one time: scaled by `scale` along
+ * the axes, rotated about x, then y, then z by `rotation` (degrees), then
+ * moved by `translation` (as `Transform::new`).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MotionKey {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub translation: Vector,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rotation: Vector,
+    #[cfg_attr(feature = "serde", serde(default = "unit_scale"))]
+    pub scale: Vector,
+}
+
+#[cfg(feature = "serde")]
+fn unit_scale() -> Vector {
+    [1.0, 1.0, 1.0]
+}
+
+/**
+ * Product of quaternions (w, x, y, z): the rotation `b`, then `a`.
+ */
+fn multiply(a: &Quaternion, b: &Quaternion) -> Quaternion {
+    [
+        a[0] * b[0] - a[1] * b[1] - a[2] * b[2] - a[3] * b[3],
+        a[0] * b[1] + a[1] * b[0] + a[2] * b[3] - a[3] * b[2],
+        a[0] * b[2] - a[1] * b[3] + a[2] * b[0] + a[3] * b[1],
+        a[0] * b[3] + a[1] * b[2] - a[2] * b[1] + a[3] * b[0],
+    ]
+}
+
+/**
+ * The rotation about x, then y, then z by `degrees`.
+ */
+fn quaternion(degrees: &Vector) -> Quaternion {
+    let half = degrees.map(|angle| angle * consts::PI / 360.0);
+    let qx = [half[0].cos(), half[0].sin(), 0.0, 0.0];
+    let qy = [half[1].cos(), 0.0, half[1].sin(), 0.0];
+    let qz = [half[2].cos(), 0.0, 0.0, half[2].sin()];
+    multiply(&qz, &multiply(&qy, &qx))
+}
+
+/**
+ * Spherical interpolation from `a` (at 0) to `b` (at 1), along the
+ * shortest arc.
+ */
+fn slerp(a: &Quaternion, b: &Quaternion, f: Float) -> Quaternion {
+    let mut dot = (0..4).map(|i| a[i] * b[i]).sum::<Float>();
+    let mut b = *b;
+    if dot < 0.0 {
+        b = b.map(|c| -c);
+        dot = -dot;
+    }
+    let (wa, wb) = if dot > 0.9995 {
+        // Nearly the same: linearly, normalized below.
+        (1.0 - f, f)
+    } else {
+        let theta = dot.acos();
+        let sin = theta.sin();
+        (((1.0 - f) * theta).sin() / sin, (f * theta).sin() / sin)
+    };
+    let q: Quaternion = [0, 1, 2, 3].map(|i| wa * a[i] + wb * b[i]);
+    let length = (0..4).map(|i| q[i] * q[i]).sum::<Float>().sqrt();
+    q.map(|c| c / length)
+}
+
+fn rotation_matrix(q: &Quaternion) -> [[Float; 3]; 3] {
+    let [w, x, y, z] = *q;
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+        ],
+        [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+        ],
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ]
+}
+
+/**
+ * A key of the motion, ready to interpolate.
+ */
+#[derive(Clone, Copy, Debug)]
+struct Key {
+    translation: Vector,
+    rotation: Quaternion,
+    scale: Vector,
+}
+
+/**
+ * `actor` moving over the shutter interval, placed by `keys` evenly
+ * spaced over it (one keeps it still).
+ */
+pub struct Moving {
+    pub actor: Box<dyn RayTraceable>,
+    keys: Vec<Key>,
+}
+
+impl Moving {
+    pub fn new(
+        actor: Box<dyn RayTraceable>,
+        keys: &[MotionKey],
+    ) -> Result<Moving, String> {
+        if keys.is_empty() {
+            return Err("motion without keys".to_string());
+        }
+        let mut converted = vec![];
+        for key in keys {
+            // Checks the key as a placement.
+            Transform::new(key.translation, key.rotation, key.scale)?;
+            converted.push(Key {
+                translation: key.translation,
+                rotation: quaternion(&key.rotation),
+                scale: key.scale,
+            });
+        }
+        let first = keys[0].scale;
+        let flips = keys.iter().any(|key| {
+            (0..3).any(|i| (key.scale[i] > 0.0) != (first[i] > 0.0))
+        });
+        if flips {
+            return Err("motion scale changes sign".to_string());
+        }
+        Ok(Moving {
+            actor,
+            keys: converted,
+        })
+    }
+
+    /**
+     * The placement at `time` of the shutter interval (clamped to it).
+     */
+    pub fn transform(&self, time: Float) -> Transform {
+        let last = self.keys.len() - 1;
+        let at = time.clamp(0.0, 1.0) * last as Float;
+        let i = (at.floor() as usize).min(last.saturating_sub(1));
+        let (a, b) = (&self.keys[i], &self.keys[(i + 1).min(last)]);
+        let f = at - i as Float;
+        let lerp = |a: &Vector, b: &Vector| {
+            [0, 1, 2].map(|c| a[c] + f * (b[c] - a[c]))
+        };
+        let scale = lerp(&a.scale, &b.scale);
+        let rotation = rotation_matrix(&slerp(&a.rotation, &b.rotation, f));
+        let linear =
+            [0, 1, 2].map(|r| [0, 1, 2].map(|c| rotation[r][c] * scale[c]));
+        // The scales keep their signs between the keys: never singular.
+        Transform::from_matrix(linear, lerp(&a.translation, &b.translation))
+            .unwrap_or_else(|_| Transform::identity())
+    }
+}
+
+impl Hittable for Moving {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let transform = self.transform(ray.time);
+        let local = transform.local_ray(ray);
+        if !self.actor.is_hit(&local, t_min, t_max, record) {
+            return false;
+        }
+        transform.place(ray, record);
+        true
+    }
+}
+
+impl RayTraceable for Moving {
+    fn validate(&self) -> Result<(), String> {
+        self.actor.validate()
+    }
+
+    /**
+     * Around the spheres the actor turns in (about its origin, at the
+     * largest scale) at every key: the translations between two keys lie
+     * between theirs.
+     */
+    fn bounding_box(&self) -> Option<Aabb> {
+        let local = self.actor.bounding_box()?;
+        let reach = local
+            .corners()
+            .iter()
+            .map(|c| (c[0] * c[0] + c[1] * c[1] + c[2] * c[2]).sqrt())
+            .fold(0.0, Float::max);
+        let scale = self
+            .keys
+            .iter()
+            .flat_map(|key| key.scale)
+            .fold(0.0, |a: Float, b| a.max(b.abs()));
+        let r = reach * scale;
+        self.keys
+            .iter()
+            .map(|key| {
+                let t = key.translation;
+                Aabb::new(
+                    [t[0] - r, t[1] - r, t[2] - r],
+                    [t[0] + r, t[1] + r, t[2] + r],
+                )
+            })
+            .reduce(|a, b| a.union(&b))
+    }
+
+    fn kind(&self) -> &'static str {
+        self.actor.kind()
+    }
+
+    fn triangles(&self) -> usize {
+        self.actor.triangles()
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        self.actor.textures(textures);
+    }
+
+    fn visibility(&self) -> Visibility {
+        self.actor.visibility()
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        for key in &mut self.keys {
+            key.translation = [0, 1, 2].map(|i| key.translation[i] + offset[i]);
+        }
+    }
+}
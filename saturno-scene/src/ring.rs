@@ -0,0 +1,204 @@
+use crate::actor::Aabb;
+use crate::actor::Hit;
+use crate::actor::Hittable;
+use crate::actor::RayTraceable;
+use crate::light::orthonormal_basis;
+use crate::material::Scattering;
+use crate::texture::TextureSampler;
+use ndarray::arr1;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+use std::sync::Arc;
+
+// Rings of particles around a planet, as Saturn's: a flat annulus with no
+// thickness, which rays cross or bounce off by its optical depth. As the
+// shadow rays do too, the planet shadows the ring and the ring casts a
+// partial shadow on the planet.
+
+/**
+ * A ring around `center`, perpendicular to `normal`, from `inner_radius`
+ * to `outer_radius`. `optical_depth` is the depth of particles across the
+ * ring: a ray crossing it at an angle whose cosine with the normal is mu
+ * goes through with the probability exp(-depth / mu), otherwise it hits
+ * a particle of `material` (typically `RingParticles`).
+ */
+pub struct Ring {
+    pub center: [Float; 3],
+    pub normal: [Float; 3],
+    pub inner_radius: Float,
+    pub outer_radius: Float,
+    pub optical_depth: Float,
+    /**
+     * Multiplies the optical depth by the mean of its channels, looked up
+     * from the inner edge (u = 0) to the outer one (u = 1), for the gaps
+     * and ringlets.
+     */
+    pub profile: Option<Arc<dyn TextureSampler>>,
+    pub material: Arc<dyn Scattering>,
+}
+
+impl Ring {
+    pub fn new(
+        center: [Float; 3],
+        normal: [Float; 3],
+        inner_radius: Float,
+        outer_radius: Float,
+        material: Arc<dyn Scattering>,
+    ) -> Ring {
+        Ring {
+            center,
+            normal,
+            inner_radius,
+            outer_radius,
+            optical_depth: 1.0,
+            profile: None,
+            material,
+        }
+    }
+
+    fn unit_normal(&self) -> [Float; 3] {
+        let n = self.normal;
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        n.map(|c| c / length)
+    }
+
+    /**
+     * Optical depth across the ring at `radius` from its center, 0 out of
+     * the ring.
+     */
+    pub fn optical_depth_at(&self, radius: Float) -> Float {
+        if radius < self.inner_radius || radius > self.outer_radius {
+            return 0.0;
+        }
+        match &self.profile {
+            Some(profile) => {
+                let u = (radius - self.inner_radius)
+                    / (self.outer_radius - self.inner_radius);
+                let texel = profile.sample([u, 0.5], 0.0);
+                self.optical_depth * (texel[0] + texel[1] + texel[2]) / 3.0
+            }
+            None => self.optical_depth,
+        }
+    }
+}
+
+impl Hittable for Ring {
+    fn is_hit(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        record: &mut Hit,
+    ) -> bool {
+        let n = self.unit_normal();
+        let rise = (0..3).map(|i| ray.direction[i] * n[i]).sum::<Float>();
+        if rise.abs() < 1e-12 {
+            return false;
+        }
+        let t = (0..3)
+            .map(|i| (self.center[i] - ray.origin[i]) * n[i])
+            .sum::<Float>()
+            / rise;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+
+        let point = ray.point_at_parameter(t);
+        let offset = [0, 1, 2].map(|i| point[i] - self.center[i]);
+        let radius = Vec4::l2_norm(arr1(&offset).view());
+        let depth = self.optical_depth_at(radius);
+        if depth <= 0.0 {
+            return false;
+        }
+        // The slant crosses more particles.
+        let mu = rise.abs() / Vec4::l2_norm(ray.direction.view());
+        let opacity = 1.0 - (-depth / mu).exp();
+        if random::gen_range(0.0, 1.0) >= opacity {
+            return false;
+        }
+
+        // Facing the ray, the tangent along the orbit.
+        let side = if rise > 0.0 { -1.0 } else { 1.0 };
+        let (u, v) = orthonormal_basis(&n);
+        let azimuth = (0..3)
+            .map(|i| offset[i] * v[i])
+            .sum::<Float>()
+            .atan2((0..3).map(|i| offset[i] * u[i]).sum::<Float>());
+        let tangent = [0, 1, 2]
+            .map(|i| {
+                n[(i + 1) % 3] * offset[(i + 2) % 3]
+                    - n[(i + 2) % 3] * offset[(i + 1) % 3]
+            })
+            .map(|c| c / radius.max(Float::MIN_POSITIVE));
+
+        record.t = t;
+        record.point = point;
+        record.normal = arr1(&[n[0] * side, n[1] * side, n[2] * side, 0.0]);
+        record.tangent = arr1(&[tangent[0], tangent[1], tangent[2], 0.0]);
+        record.uv = [
+            (radius - self.inner_radius)
+                / (self.outer_radius - self.inner_radius),
+            0.5 + azimuth / (2.0 * consts::PI),
+        ];
+        record.footprint = 0.0;
+        record.differentials = None;
+        record.material = self.material.clone();
+        record.alpha_test()
+    }
+}
+
+impl RayTraceable for Ring {
+    fn validate(&self) -> Result<(), String> {
+        if !self.center.iter().all(|c| c.is_finite()) {
+            return Err("center is not finite".to_string());
+        }
+        let n = self.normal;
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if !(length > 0.0 && length.is_finite()) {
+            return Err(format!("invalid ring normal {:?}", self.normal));
+        }
+        let radii = self.inner_radius >= 0.0
+            && self.inner_radius < self.outer_radius
+            && self.outer_radius.is_finite();
+        if !radii {
+            return Err(format!(
+                "invalid ring radii {} to {}",
+                self.inner_radius, self.outer_radius
+            ));
+        }
+        if !(self.optical_depth >= 0.0 && self.optical_depth.is_finite()) {
+            return Err(format!(
+                "invalid ring optical depth {}",
+                self.optical_depth
+            ));
+        }
+        Ok(())
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = self.outer_radius;
+        let c = &self.center;
+        Some(Aabb::new(
+            [c[0] - r, c[1] - r, c[2] - r],
+            [c[0] + r, c[1] + r, c[2] + r],
+        ))
+    }
+
+    fn kind(&self) -> &'static str {
+        "ring"
+    }
+
+    fn textures<'a>(&'a self, textures: &mut Vec<&'a dyn TextureSampler>) {
+        if let Some(profile) = &self.profile {
+            textures.push(profile.as_ref());
+        }
+        self.material.textures(textures);
+    }
+
+    fn translate(&mut self, offset: &[Float; 3]) {
+        self.center = [0, 1, 2].map(|i| self.center[i] + offset[i]);
+    }
+}
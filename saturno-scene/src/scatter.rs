@@ -0,0 +1,327 @@
+use crate::instance::Transform;
+use crate::light::orthonormal_basis;
+use crate::mesh::Mesh;
+use crate::texture::TextureSampler;
+use crate::units::Units;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// Procedural scattering of instances (forests, rock fields, crowds): a
+// number of candidate places drawn uniformly over a region (by area on
+// surfaces, by volume in boxes), each kept with the probability of the
+// density there, the mean of the red, green and blue of a texture at its
+// texture coordinates (a painted map clears paths through a forest). The
+// ones kept are scaled and turned at random within ranges, upright (about
+// y) or along the normal of the surface.
+//
+// Every candidate draws the same random numbers whether it is kept or not,
+// all from the seed: the same scatter places the same instances, and
+// painting the density only removes or adds some of them.
+
+type Vector = [Float; 3];
+
+/**
+ * Where the instances are scattered.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum ScatterRegion {
+    /**
+     * On the plane y = `center[1]`, `size` wide along x and z. Its texture
+     * coordinates are those of `Mesh::grid`: u along x, v along -z.
+     */
+    Rectangle { center: Vector, size: [Float; 2] },
+    /**
+     * On the surface of a sphere, with its texture coordinates (see
+     * `Sphere`).
+     */
+    Sphere { center: Vector, radius: Float },
+    /**
+     * Within a box, with the texture coordinates of its top, as a
+     * rectangle's, and pointing up.
+     */
+    Box { min: Vector, max: Vector },
+    /**
+     * On the surface of the mesh of an OBJ or STL file (see
+     * `MeshDescription`).
+     */
+    Mesh {
+        path: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        units: Option<Units>,
+    },
+}
+
+/**
+ * A place drawn in a region: its `point`, the unit `normal` of the
+ * surface there, and its texture coordinates.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub point: Vector,
+    pub normal: Vector,
+    pub uv: [Float; 2],
+}
+
+/**
+ * The triangles of a mesh, drawn by their areas.
+ */
+struct MeshSurface<'a> {
+    mesh: &'a Mesh,
+    /**
+     * Sum of the areas up to each triangle, included.
+     */
+    areas: Vec<Float>,
+}
+
+impl<'a> MeshSurface<'a> {
+    fn new(mesh: &'a Mesh) -> MeshSurface<'a> {
+        let mut total = 0.0;
+        let areas = (0..mesh.triangles())
+            .map(|triangle| {
+                let [a, b, c] =
+                    mesh.triangle(triangle).map(|v| mesh.position(v));
+                let (e1, e2) = (sub(&b, &a), sub(&c, &a));
+                total += 0.5 * length(&cross(&e1, &e2));
+                total
+            })
+            .collect();
+        MeshSurface { mesh, areas }
+    }
+
+    fn sample(&self, u: Vector) -> Placement {
+        let total = self.areas[self.areas.len() - 1];
+        let triangle = self
+            .areas
+            .partition_point(|&area| area <= u[0] * total)
+            .min(self.areas.len() - 1);
+        // Uniform on the triangle.
+        let root = u[1].sqrt();
+        let weights = [1.0 - root, root * (1.0 - u[2]), root * u[2]];
+        let vertices = self.mesh.triangle(triangle);
+        let mut placement = Placement {
+            point: [0.0; 3],
+            normal: [0.0; 3],
+            uv: [0.0; 2],
+        };
+        for (&vertex, weight) in vertices.iter().zip(weights) {
+            let (p, n, uv) = (
+                self.mesh.position(vertex),
+                self.mesh.normal(vertex),
+                self.mesh.uv(vertex),
+            );
+            for i in 0..3 {
+                placement.point[i] += weight * p[i];
+                placement.normal[i] += weight * n[i];
+            }
+            for (coordinate, c) in placement.uv.iter_mut().zip(uv) {
+                *coordinate += weight * c;
+            }
+        }
+        placement.normal = normalize(&placement.normal);
+        placement
+    }
+}
+
+fn sub(a: &Vector, b: &Vector) -> Vector {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: &Vector, b: &Vector) -> Vector {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: &Vector) -> Float {
+    (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt()
+}
+
+fn normalize(a: &Vector) -> Vector {
+    let length = length(a);
+    if length > 0.0 {
+        a.map(|c| c / length)
+    } else {
+        [0.0, 1.0, 0.0]
+    }
+}
+
+/**
+ * An instance placed by a `Scatterer`, and a random number in [0, 1) to
+ * pick its prototype among several.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scattered {
+    pub transform: Transform,
+    pub pick: Float,
+}
+
+/**
+ * Scatters `count` candidates over `region`, kept by the `density` if
+ * any, each scaled uniformly within the `scale` range and turned about x,
+ * then y, then z by angles (degrees) within the `rotation` ranges, its y
+ * axis along the normal of the region if `align`d (up otherwise). All
+ * from the `seed`.
+ */
+#[derive(Clone)]
+pub struct Scatterer {
+    pub region: ScatterRegion,
+    pub count: usize,
+    pub density: Option<Arc<dyn TextureSampler>>,
+    pub rotation: [[Float; 2]; 3],
+    pub scale: [Float; 2],
+    pub align: bool,
+    pub seed: u64,
+}
+
+impl Scatterer {
+    /**
+     * Upright, turned at random about y, of the scale of the prototype.
+     */
+    pub fn new(region: ScatterRegion, count: usize, seed: u64) -> Scatterer {
+        Scatterer {
+            region,
+            count,
+            density: None,
+            rotation: [[0.0, 0.0], [0.0, 360.0], [0.0, 0.0]],
+            scale: [1.0, 1.0],
+            align: false,
+            seed,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let ranges = self.rotation.iter().chain([&self.scale]);
+        if !ranges.flatten().all(|c| c.is_finite()) {
+            return Err("scatter ranges are not finite".to_string());
+        }
+        if !(self.scale[0] > 0.0 && self.scale[1] >= self.scale[0]) {
+            return Err(format!("invalid scatter scale {:?}", self.scale));
+        }
+        match &self.region {
+            ScatterRegion::Rectangle { size, .. }
+                if size[0] <= 0.0 || size[1] <= 0.0 =>
+            {
+                Err(format!("invalid scatter rectangle {:?}", size))
+            }
+            ScatterRegion::Sphere { radius, .. }
+                if !(*radius > 0.0 && radius.is_finite()) =>
+            {
+                Err(format!("invalid scatter radius {}", radius))
+            }
+            ScatterRegion::Box { min, max }
+                if (0..3).any(|i| min[i] > max[i]) =>
+            {
+                Err(format!("invalid scatter box {:?} {:?}", min, max))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /**
+     * A place of the region, for the uniform numbers `u`.
+     */
+    fn place(&self, u: Vector, mesh: Option<&MeshSurface>) -> Placement {
+        let up = [0.0, 1.0, 0.0];
+        match &self.region {
+            ScatterRegion::Rectangle { center, size } => Placement {
+                point: [
+                    center[0] + (u[0] - 0.5) * size[0],
+                    center[1],
+                    center[2] - (u[1] - 0.5) * size[1],
+                ],
+                normal: up,
+                uv: [u[0], u[1]],
+            },
+            ScatterRegion::Sphere { center, radius } => {
+                let y = 1.0 - 2.0 * u[0];
+                let ring = (1.0 - y * y).max(0.0).sqrt();
+                let longitude = 2.0 * consts::PI * (u[1] - 0.5);
+                let normal =
+                    [ring * longitude.sin(), y, ring * longitude.cos()];
+                Placement {
+                    point: [0, 1, 2].map(|i| center[i] + radius * normal[i]),
+                    normal,
+                    uv: [u[1], 0.5 + y.clamp(-1.0, 1.0).asin() / consts::PI],
+                }
+            }
+            ScatterRegion::Box { min, max } => Placement {
+                point: [0, 1, 2].map(|i| min[i] + u[i] * (max[i] - min[i])),
+                normal: up,
+                uv: [u[0], 1.0 - u[2]],
+            },
+            ScatterRegion::Mesh { .. } => match mesh {
+                Some(surface) => surface.sample(u),
+                None => Placement {
+                    point: [0.0; 3],
+                    normal: up,
+                    uv: [0.0; 2],
+                },
+            },
+        }
+    }
+
+    /**
+     * The instances, the surface of a `Mesh` region given by `mesh`.
+     */
+    pub fn scatter(
+        &self,
+        mesh: Option<&Mesh>,
+    ) -> Result<Vec<Scattered>, String> {
+        self.validate()?;
+        let surface = match (&self.region, mesh) {
+            (ScatterRegion::Mesh { .. }, Some(mesh)) => {
+                Some(MeshSurface::new(mesh))
+            }
+            (ScatterRegion::Mesh { path, .. }, None) => {
+                return Err(format!("scatter over {} without its mesh", path))
+            }
+            _ => None,
+        };
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut uniform = |range: [Float; 2]| -> Float {
+            range[0] + rng.gen::<Float>() * (range[1] - range[0])
+        };
+        let mut scattered = Vec::with_capacity(self.count);
+        for _ in 0..self.count {
+            let u = [0, 1, 2].map(|_| uniform([0.0, 1.0]));
+            let keep = uniform([0.0, 1.0]);
+            let rotation = self.rotation.map(&mut uniform);
+            let scale = uniform(self.scale);
+            let pick = uniform([0.0, 1.0]);
+
+            let placement = self.place(u, surface.as_ref());
+            if let Some(density) = &self.density {
+                let texel = density.sample(placement.uv, 0.0);
+                if keep >= (texel[0] + texel[1] + texel[2]) / 3.0 {
+                    continue;
+                }
+            }
+            let turned = Transform::new([0.0; 3], rotation, [scale; 3])?;
+            let frame = if self.align {
+                // Columns along the normal's basis, y along the normal.
+                let n = placement.normal;
+                let (u, v) = orthonormal_basis(&n);
+                [0, 1, 2].map(|i| [v[i], n[i], u[i]])
+            } else {
+                [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+            };
+            let placed = Transform::from_matrix(frame, placement.point)?;
+            scattered.push(Scattered {
+                transform: turned.then(&placed),
+                pick,
+            });
+        }
+        Ok(scattered)
+    }
+}
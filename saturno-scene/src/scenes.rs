@@ -0,0 +1,469 @@
+use crate::actor::RayTraceable;
+use crate::actor::Sphere;
+use crate::camera::Camera;
+use crate::ephemeris;
+use crate::ephemeris::Planet;
+use crate::ephemeris::Satellite;
+use crate::fractal::Fractal;
+use crate::fractal::FractalKind;
+use crate::fractal::Palette;
+use crate::light::Light;
+use crate::material::Dielectric;
+use crate::material::Lambertian;
+use crate::material::Metal;
+use crate::material::Photosphere;
+use crate::material::RingParticles;
+use crate::material::Shading;
+use crate::ring::Ring;
+use crate::texture::Texture;
+use crate::texture::TextureSampler;
+use ndarray::arr1;
+use rand::Rng;
+use saturno_math::common::Float;
+use saturno_math::common::Vec4;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::instrument;
+
+#[instrument(level = "debug")]
+pub fn random_book_cover() -> Vec<Box<dyn RayTraceable>> {
+    let mut actors: Vec<Box<dyn RayTraceable>> = Vec::with_capacity(1000);
+
+    // World orbe
+    actors.push(Box::new(Sphere {
+        center: arr1(&[0.0, -1000.0, 0.0, 1.0]),
+        radius: 1000.0,
+        material: Arc::new(Lambertian::new(
+            arr1(&[0.5, 0.5, 0.5, 1.0]),
+            Shading::COLOR,
+        )),
+    }) as Box<dyn RayTraceable>);
+
+    let mut rng = rand::thread_rng();
+    let max = 10;
+    for a in -max..max {
+        for b in -max..max {
+            let choose_mat: Float = rng.gen();
+            let center = arr1(&[
+                a as Float + 0.9 * rng.gen::<Float>(),
+                0.2,
+                b as Float + 0.9 * rng.gen::<Float>() as Float,
+                1.0,
+            ]);
+            let radius = 0.2;
+
+            // Filter colliding spheres
+            let max_pos = arr1(&[4.0, 0.2, 0.0, 1.0]);
+            if Vec4::l2_norm((center.clone() - max_pos.clone()).view()) > 2.0 {
+                if choose_mat < 0.8 {
+                    actors.push(Box::new(Sphere {
+                        center,
+                        radius,
+                        material: Arc::new(Lambertian::new(
+                            arr1(&[
+                                rng.gen::<Float>() * rng.gen::<Float>(),
+                                rng.gen::<Float>() * rng.gen::<Float>(),
+                                rng.gen::<Float>() * rng.gen::<Float>(),
+                                1.0,
+                            ]),
+                            Shading::COLOR,
+                        )),
+                    })
+                        as Box<dyn RayTraceable>);
+                } else if choose_mat < 0.95 {
+                    actors.push(Box::new(Sphere {
+                        center,
+                        radius,
+                        material: Arc::new(Metal::new(
+                            arr1(&[
+                                0.5 * (1.0 + rng.gen::<Float>()),
+                                0.5 * (1.0 + rng.gen::<Float>()),
+                                0.5 * (1.0 + rng.gen::<Float>()),
+                                1.0,
+                            ]),
+                            Shading::COLOR,
+                            0.5 * rng.gen::<Float>(),
+                        )),
+                    })
+                        as Box<dyn RayTraceable>);
+                } else {
+                    actors.push(Box::new(Sphere {
+                        center,
+                        radius,
+                        material: Arc::new(Dielectric::new(
+                            arr1(&[1.0, 1.0, 1.0, 0.0]),
+                            Shading::COLOR,
+                            1.5,
+                        )),
+                    })
+                        as Box<dyn RayTraceable>);
+                }
+            }
+        }
+    }
+
+    // Center spheres
+    actors.push(Box::new(Sphere {
+        center: arr1(&[0.0, 1.0, 0.0, 1.0]),
+        radius: 1.0,
+        material: Arc::new(Dielectric::new(
+            arr1(&[1.0, 1.0, 1.0, 0.0]),
+            Shading::COLOR,
+            1.5,
+        )),
+    }) as Box<dyn RayTraceable>);
+
+    actors.push(Box::new(Sphere {
+        center: arr1(&[-4.0, 1.0, 0.0, 1.0]),
+        radius: 1.0,
+        material: Arc::new(Lambertian::new(
+            arr1(&[0.4, 0.2, 0.1, 1.0]),
+            Shading::COLOR,
+        )),
+    }) as Box<dyn RayTraceable>);
+
+    actors.push(Box::new(Sphere {
+        center: arr1(&[4.0, 1.0, 0.0, 1.0]),
+        radius: 1.0,
+        material: Arc::new(Metal::new(
+            arr1(&[0.7, 0.6, 0.5, 1.0]),
+            Shading::COLOR,
+            0.0,
+        )),
+    }) as Box<dyn RayTraceable>);
+
+    actors
+}
+
+#[instrument(level = "debug")]
+pub fn two_spheres_normals() -> Vec<Box<dyn RayTraceable>> {
+    let mut actors: Vec<Box<dyn RayTraceable>> = Vec::with_capacity(1000);
+    actors.push(Box::new(Sphere {
+        center: arr1(&[0.0, 0.0, -1.0, 1.0]),
+        radius: 0.5,
+        material: Arc::new(Lambertian::new(
+            arr1(&[1.0, 0.0, 0.0, 1.0]),
+            Shading::NORMALS,
+        )),
+    }) as Box<dyn RayTraceable>);
+
+    actors.push(Box::new(Sphere {
+        center: arr1(&[0.0, -100.5, -1.0, 1.0]),
+        radius: 100.0,
+        material: Arc::new(Lambertian::new(
+            arr1(&[0.0, 1.0, 0.0, 1.0]),
+            Shading::NORMALS,
+        )),
+    }) as Box<dyn RayTraceable>);
+
+    actors
+}
+
+/**
+ * Camera looking down at the book cover spheres from a distance, with a
+ * slight defocus blur.
+ */
+pub fn book_cover_camera(width: u32, height: u32) -> Camera {
+    Camera::new(
+        20.0,
+        width,
+        height,
+        arr1(&[13.0, 2.0, 3.0, 1.0]),
+        arr1(&[0.0, 0.0, 0.0, 1.0]),
+        arr1(&[0.0, 1.0, 0.0, 0.0]),
+        0.2,
+    )
+}
+
+pub fn two_spheres_camera(width: u32, height: u32) -> Camera {
+    Camera::new(
+        90.0,
+        width,
+        height,
+        arr1(&[0.0, 0.0, 0.0, 1.0]),
+        arr1(&[0.0, 0.0, -1.0, 1.0]),
+        arr1(&[0.0, 1.0, 0.0, 0.0]),
+        0.0,
+    )
+}
+
+/**
+ * A fractal on a gray floor, colored by its orbit traps from orange (the
+ * orbits closest to the origin) to blue.
+ */
+#[instrument(level = "debug")]
+pub fn fractal(kind: FractalKind) -> Vec<Box<dyn RayTraceable>> {
+    let palette =
+        Palette::new(vec![[0.9, 0.3, 0.1], [0.95, 0.8, 0.4], [0.2, 0.4, 0.8]])
+            .unwrap();
+    let material = Arc::new(Lambertian::textured(
+        arr1(&[1.0, 1.0, 1.0, 1.0]),
+        Arc::new(palette),
+    ));
+    vec![
+        Box::new(Fractal::new(kind, [0.0, 0.0, 0.0], 1.0, material)),
+        Box::new(Sphere {
+            center: arr1(&[0.0, -1001.2, 0.0, 1.0]),
+            radius: 1000.0,
+            material: Arc::new(Lambertian::new(
+                arr1(&[0.5, 0.5, 0.5, 1.0]),
+                Shading::COLOR,
+            )),
+        }),
+    ]
+}
+
+pub fn fractal_camera(width: u32, height: u32) -> Camera {
+    Camera::new(
+        40.0,
+        width,
+        height,
+        arr1(&[2.5, 1.5, 3.0, 1.0]),
+        arr1(&[0.0, 0.0, 0.0, 1.0]),
+        arr1(&[0.0, 1.0, 0.0, 0.0]),
+        0.0,
+    )
+}
+
+/**
+ * Options of the `solar_system` scene: the date (UTC) and the scales. One
+ * astronomical unit is `scale` scene units, and the radii of the planets
+ * and moons are multiplied by `radius_scale` and the Sun's by
+ * `sun_radius_scale`: at 1 they are true to the distances, and the planets
+ * are specks. The distances of the moons from their planets are multiplied
+ * by `moon_scale`, to clear planets grown larger.
+ */
+#[derive(Clone)]
+pub struct SolarSystem {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: Float,
+    pub scale: Float,
+    pub radius_scale: Float,
+    pub sun_radius_scale: Float,
+    pub moon_scale: Float,
+    pub moons: bool,
+    pub rings: bool,
+    /**
+     * Whether the Sun is also an actor, its limb darkened surface (see
+     * `Photosphere`) in front of `solar_system_sun`.
+     */
+    pub sun: bool,
+    /**
+     * Textures wrapped around the bodies, by name ("earth", "moon", ...),
+     * their poles up along y. The others have a flat color.
+     */
+    pub textures: BTreeMap<String, Arc<dyn TextureSampler>>,
+}
+
+impl SolarSystem {
+    /**
+     * True to scale, with the moons and the rings of Saturn.
+     */
+    pub fn new(year: i32, month: u32, day: u32, hour: Float) -> SolarSystem {
+        SolarSystem {
+            year,
+            month,
+            day,
+            hour,
+            scale: 1.0,
+            radius_scale: 1.0,
+            sun_radius_scale: 1.0,
+            moon_scale: 1.0,
+            moons: true,
+            rings: true,
+            sun: false,
+            textures: BTreeMap::new(),
+        }
+    }
+
+    pub fn julian_date(&self) -> Float {
+        ephemeris::julian_date(self.year, self.month, self.day, self.hour)
+    }
+
+    /**
+     * Center of `planet` in the scene.
+     */
+    pub fn position(&self, planet: Planet) -> [Float; 3] {
+        planet.position(self.julian_date()).map(|c| c * self.scale)
+    }
+
+    /**
+     * Center of `satellite` in the scene.
+     */
+    pub fn satellite_position(&self, satellite: Satellite) -> [Float; 3] {
+        let planet = self.position(satellite.planet());
+        let offset = satellite.position(self.julian_date());
+        [0, 1, 2].map(|i| planet[i] + offset[i] * self.scale * self.moon_scale)
+    }
+
+    /**
+     * Scene radius of a body of `radius` kilometers.
+     */
+    fn size(&self, radius: Float) -> Float {
+        radius / ephemeris::AU * self.scale * self.radius_scale
+    }
+
+    fn body(
+        &self,
+        name: &str,
+        center: [Float; 3],
+        radius: Float,
+        color: [Float; 3],
+    ) -> Box<dyn RayTraceable> {
+        let material = match self.textures.get(name) {
+            Some(texture) => Lambertian::textured(
+                arr1(&[1.0, 1.0, 1.0, 1.0]),
+                texture.clone(),
+            ),
+            None => Lambertian::new(
+                arr1(&[color[0], color[1], color[2], 1.0]),
+                Shading::COLOR,
+            ),
+        };
+        Box::new(Sphere {
+            center: arr1(&[center[0], center[1], center[2], 1.0]),
+            radius,
+            material: Arc::new(material),
+        })
+    }
+}
+
+fn planet_color(planet: Planet) -> [Float; 3] {
+    match planet {
+        Planet::Mercury => [0.55, 0.53, 0.5],
+        Planet::Venus => [0.9, 0.85, 0.7],
+        Planet::Earth => [0.3, 0.4, 0.6],
+        Planet::Mars => [0.75, 0.45, 0.3],
+        Planet::Jupiter => [0.8, 0.7, 0.6],
+        Planet::Saturn => [0.85, 0.8, 0.6],
+        Planet::Uranus => [0.6, 0.8, 0.85],
+        Planet::Neptune => [0.35, 0.5, 0.85],
+    }
+}
+
+fn satellite_color(satellite: Satellite) -> [Float; 3] {
+    match satellite {
+        Satellite::Moon | Satellite::Callisto => [0.5, 0.5, 0.5],
+        Satellite::Io => [0.8, 0.75, 0.45],
+        Satellite::Europa => [0.8, 0.8, 0.75],
+        Satellite::Ganymede => [0.6, 0.58, 0.55],
+        Satellite::Titan => [0.8, 0.6, 0.3],
+    }
+}
+
+/**
+ * Optical depth across the main rings of Saturn, from 74500 km (the inner
+ * edge of the C ring) to 137000 km from its center (the outer edge of the
+ * A ring), with the Cassini division between the B and A rings.
+ */
+fn saturn_ring_profile() -> Texture {
+    let (inner, outer) = (74500.0, 137000.0);
+    let texels = (0..256)
+        .map(|i| {
+            let radius = inner + (i as Float + 0.5) / 256.0 * (outer - inner);
+            let depth = match radius {
+                r if r < 92000.0 => 0.1,
+                r if r < 117580.0 => 1.8,
+                r if r < 122170.0 => 0.1,
+                r if r < 133400.0 => 0.5,
+                r if r < 133700.0 => 0.0,
+                _ => 0.4,
+            };
+            [depth, depth, depth, 1.0]
+        })
+        .collect();
+    Texture::new(256, 1, texels).unwrap()
+}
+
+/**
+ * The planets at the date of `options`, with the major moons and the rings
+ * of Saturn if asked, lit by `solar_system_sun`.
+ */
+#[instrument(level = "debug", skip_all)]
+pub fn solar_system(options: &SolarSystem) -> Vec<Box<dyn RayTraceable>> {
+    let mut actors = Vec::new();
+    for planet in Planet::ALL {
+        actors.push(options.body(
+            planet.name(),
+            options.position(planet),
+            options.size(planet.radius()),
+            planet_color(planet),
+        ));
+    }
+    if options.moons {
+        for satellite in Satellite::ALL {
+            actors.push(options.body(
+                satellite.name(),
+                options.satellite_position(satellite),
+                options.size(satellite.radius()),
+                satellite_color(satellite),
+            ));
+        }
+    }
+    if options.rings {
+        let mut ring = Ring::new(
+            options.position(Planet::Saturn),
+            Planet::Saturn.pole(),
+            options.size(74500.0),
+            options.size(137000.0),
+            Arc::new(RingParticles::new(arr1(&[0.85, 0.8, 0.7, 1.0]))),
+        );
+        ring.profile = Some(Arc::new(saturn_ring_profile()));
+        actors.push(Box::new(ring));
+    }
+    if options.sun {
+        let (photosphere, radius) = solar_system_photosphere(options);
+        actors.push(Box::new(Sphere {
+            center: arr1(&[0.0, 0.0, 0.0, 1.0]),
+            radius,
+            material: Arc::new(photosphere),
+        }));
+    }
+    actors
+}
+
+/**
+ * Surface and radius of the Sun, as large as asked and as bright as the
+ * light: larger, it is dimmer.
+ */
+fn solar_system_photosphere(options: &SolarSystem) -> (Photosphere, Float) {
+    let mut photosphere = Photosphere::sun();
+    // White: sunlight is the white of the renders.
+    photosphere.color = arr1(&[1.0, 1.0, 1.0, 1.0]);
+    photosphere.luminance /= options.sun_radius_scale.powi(2);
+    photosphere.light_group = Some("sun".to_string());
+    let radius =
+        695700.0 / ephemeris::AU * options.scale * options.sun_radius_scale;
+    (photosphere, radius)
+}
+
+/**
+ * The Sun of the `solar_system` scene: an `Omni` light at the origin, of
+ * the solar illuminance (128000 lux) at 1 AU, in the "sun" light group.
+ */
+pub fn solar_system_sun(options: &SolarSystem) -> Arc<dyn Light> {
+    let (mut photosphere, radius) = solar_system_photosphere(options);
+    Arc::new(photosphere.omni([0.0, 0.0, 0.0], radius))
+}
+
+/**
+ * Camera above the ecliptic looking at the Sun, framing the orbit of Mars.
+ */
+pub fn solar_system_camera(
+    options: &SolarSystem,
+    width: u32,
+    height: u32,
+) -> Camera {
+    let scale = options.scale;
+    Camera::new(
+        30.0,
+        width,
+        height,
+        arr1(&[0.0, 3.0 * scale, 5.2 * scale, 1.0]),
+        arr1(&[0.0, 0.0, 0.0, 1.0]),
+        arr1(&[0.0, 1.0, 0.0, 0.0]),
+        0.0,
+    )
+}
@@ -0,0 +1,286 @@
+use crate::environment::Environment;
+use crate::light::Sun;
+use crate::texture::Texture;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+
+// Daylight at a place and time: the position of the sun (NOAA solar
+// calculator equations), and the clear sky around it (Preetham, Shirley
+// and Smits, "A Practical Analytic Model for Daylight", 1999).
+//
+// The scene is laid out with y up, north towards -z and east towards +x,
+// unless `north` turns the compass.
+
+/**
+ * The sun and the sky seen from `latitude` (degrees, north positive) and
+ * `longitude` (degrees, east positive) at a local clock `hour` of a day,
+ * for shadow studies. `utc_offset` is the time zone of the clock in hours
+ * (2 for central European summer time). `turbidity` is the haze of the
+ * air, from 2 (very clear) to 10 (hazy).
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SunAndSky {
+    pub latitude: Float,
+    pub longitude: Float,
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub hour: Float,
+    pub utc_offset: Float,
+    /**
+     * Angle from -z to the north of the scene, turning towards +x, in
+     * degrees.
+     */
+    pub north: Float,
+    pub turbidity: Float,
+}
+
+fn leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/**
+ * The Perez distribution of the sky luminance (or chromaticity) at the
+ * zenith angle `theta` of a direction, `gamma` away from the sun.
+ */
+fn perez(coefficients: &[Float; 5], theta: Float, gamma: Float) -> Float {
+    let [a, b, c, d, e] = *coefficients;
+    (1.0 + a * (b / theta.cos()).exp())
+        * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+}
+
+impl SunAndSky {
+    /**
+     * At noon of the June solstice of 2024, in UTC.
+     */
+    pub fn new(latitude: Float, longitude: Float) -> SunAndSky {
+        SunAndSky {
+            latitude,
+            longitude,
+            year: 2024,
+            month: 6,
+            day: 21,
+            hour: 12.0,
+            utc_offset: 0.0,
+            north: 0.0,
+            turbidity: 3.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(format!("invalid latitude {}", self.latitude));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(format!("invalid longitude {}", self.longitude));
+        }
+        if !(1..=12).contains(&self.month)
+            || !(1..=days_in_month(self.year, self.month)).contains(&self.day)
+        {
+            return Err(format!(
+                "invalid date {}-{:02}-{:02}",
+                self.year, self.month, self.day
+            ));
+        }
+        if !(0.0..=24.0).contains(&self.hour) {
+            return Err(format!("invalid hour {}", self.hour));
+        }
+        if !(-12.0..=14.0).contains(&self.utc_offset) {
+            return Err(format!("invalid UTC offset {}", self.utc_offset));
+        }
+        if !self.north.is_finite() {
+            return Err(format!("invalid north {}", self.north));
+        }
+        if !(1.7..=10.0).contains(&self.turbidity) {
+            return Err(format!("invalid turbidity {}", self.turbidity));
+        }
+        Ok(())
+    }
+
+    /**
+     * Elevation of the sun above the horizon and its azimuth from the
+     * north towards the east, in degrees.
+     */
+    pub fn sun_position(&self) -> (Float, Float) {
+        let day_of_year = (1..self.month)
+            .map(|month| days_in_month(self.year, month))
+            .sum::<u32>()
+            + self.day;
+        let days = if leap_year(self.year) { 366.0 } else { 365.0 };
+        let utc_hour = self.hour - self.utc_offset;
+
+        // Fractional year, equation of time (minutes) and declination.
+        let g = 2.0 * consts::PI / days
+            * (day_of_year as Float - 1.0 + (utc_hour - 12.0) / 24.0);
+        let equation_of_time = 229.18
+            * (0.000075 + 0.001868 * g.cos()
+                - 0.032077 * g.sin()
+                - 0.014615 * (2.0 * g).cos()
+                - 0.040849 * (2.0 * g).sin());
+        let declination = 0.006918 - 0.399912 * g.cos() + 0.070257 * g.sin()
+            - 0.006758 * (2.0 * g).cos()
+            + 0.000907 * (2.0 * g).sin()
+            - 0.002697 * (3.0 * g).cos()
+            + 0.00148 * (3.0 * g).sin();
+
+        let solar_minutes =
+            utc_hour * 60.0 + equation_of_time + 4.0 * self.longitude;
+        let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+        let latitude = self.latitude.to_radians();
+
+        let sin_elevation = latitude.sin() * declination.sin()
+            + latitude.cos() * declination.cos() * hour_angle.cos();
+        let elevation = sin_elevation.clamp(-1.0, 1.0).asin();
+        // From the south, towards the west.
+        let azimuth = hour_angle.sin().atan2(
+            hour_angle.cos() * latitude.sin()
+                - declination.tan() * latitude.cos(),
+        );
+
+        let azimuth = (azimuth.to_degrees() + 180.0).rem_euclid(360.0);
+        (elevation.to_degrees(), azimuth)
+    }
+
+    /**
+     * Unit direction towards the sun.
+     */
+    pub fn sun_direction(&self) -> [Float; 3] {
+        let (elevation, azimuth) = self.sun_position();
+        let (elevation, azimuth) =
+            (elevation.to_radians(), (azimuth + self.north).to_radians());
+        [
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            -elevation.cos() * azimuth.cos(),
+        ]
+    }
+
+    /**
+     * The sun, its illuminance dimmed by the air it crosses (an airmass
+     * and turbidity fit, about 100000 lux at the zenith). It does not
+     * shine below the horizon.
+     */
+    pub fn sun(&self) -> Sun {
+        let (elevation, _) = self.sun_position();
+        let zenith = 90.0 - elevation;
+        let illuminance = if elevation > 0.0 {
+            // Kasten and Young.
+            let airmass = 1.0
+                / (zenith.to_radians().cos()
+                    + 0.50572 * (96.07995 - zenith).powf(-1.6364));
+            128000.0 * (-0.06 * self.turbidity * airmass).exp()
+        } else {
+            0.0
+        };
+        Sun::new(self.sun_direction(), illuminance)
+    }
+
+    /**
+     * Radiance of the clear sky (in nits) along a unit direction, black at
+     * night. Below the horizon it repeats the horizon.
+     */
+    pub fn sky_radiance(&self, direction: &[Float; 3]) -> [Float; 3] {
+        let sun = self.sun_direction();
+        if sun[1] <= 0.0 {
+            return [0.0, 0.0, 0.0];
+        }
+        let t = self.turbidity;
+        let theta_sun = sun[1].clamp(-1.0, 1.0).acos();
+        let theta = direction[1].clamp(0.001, 1.0).acos();
+        let cos_gamma = direction[0] * sun[0]
+            + direction[1].max(0.001) * sun[1]
+            + direction[2] * sun[2];
+        let gamma = cos_gamma.clamp(-1.0, 1.0).acos();
+
+        let luminance = [
+            0.1787 * t - 1.4630,
+            -0.3554 * t + 0.4275,
+            -0.0227 * t + 5.3251,
+            0.1206 * t - 2.5771,
+            -0.0670 * t + 0.3703,
+        ];
+        let x = [
+            -0.0193 * t - 0.2592,
+            -0.0665 * t + 0.0008,
+            -0.0004 * t + 0.2125,
+            -0.0641 * t - 0.8989,
+            -0.0033 * t + 0.0452,
+        ];
+        let y = [
+            -0.0167 * t - 0.2608,
+            -0.0950 * t + 0.0092,
+            -0.0079 * t + 0.2102,
+            -0.0441 * t - 1.6537,
+            -0.0109 * t + 0.0529,
+        ];
+
+        // At the zenith, the luminance in kcd/m2 and the chromaticity.
+        let chi = (4.0 / 9.0 - t / 120.0) * (consts::PI - 2.0 * theta_sun);
+        let zenith_luminance =
+            (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let s = theta_sun;
+        let (s2, s3) = (s * s, s * s * s);
+        let zenith_x = t * t * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+        let zenith_y = t * t * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+
+        let relative = |coefficients: &[Float; 5]| {
+            perez(coefficients, theta, gamma)
+                / perez(coefficients, 0.0, theta_sun)
+        };
+        let luminance =
+            (1000.0 * zenith_luminance * relative(&luminance)).max(0.0);
+        let x = zenith_x * relative(&x);
+        let y = zenith_y * relative(&y);
+
+        // xyY to linear sRGB.
+        let (big_x, big_z) = (x / y * luminance, (1.0 - x - y) / y * luminance);
+        [
+            3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+            -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+            0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+        ]
+        .map(|c| c.max(0.0))
+    }
+
+    /**
+     * The sky as an environment, baked into a map `width` texels around
+     * (and half as high).
+     */
+    pub fn sky(&self, width: usize) -> Environment {
+        let width = width.max(2);
+        let height = width / 2;
+        let mut texels = Vec::with_capacity(width * height);
+        for row in 0..height {
+            // Rows from the top, as environment maps are looked up.
+            let latitude =
+                (0.5 - (row as Float + 0.5) / height as Float) * consts::PI;
+            for column in 0..width {
+                let longitude = ((column as Float + 0.5) / width as Float
+                    - 0.5)
+                    * 2.0
+                    * consts::PI;
+                let direction = [
+                    latitude.cos() * longitude.sin(),
+                    latitude.sin(),
+                    latitude.cos() * longitude.cos(),
+                ];
+                let [r, g, b] = self.sky_radiance(&direction);
+                texels.push([r, g, b, 1.0]);
+            }
+        }
+        Environment::new(Texture::new(width, height, texels).unwrap())
+    }
+}
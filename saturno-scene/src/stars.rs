@@ -0,0 +1,284 @@
+use crate::color::blackbody_color;
+use crate::environment::Environment;
+use crate::ephemeris::equatorial_direction;
+use crate::texture::Texture;
+use crate::texture::TextureFilter;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use saturno_math::common::consts;
+use saturno_math::common::Float;
+
+// The night sky behind astronomy renders: stars of a catalog (or made up)
+// at their positions, magnitudes and colors, over the faint band of the
+// Milky Way. The sky is laid out in the frame of the `ephemeris` (y
+// towards the north pole of the ecliptic), so it sits behind the
+// `solar_system` scene.
+
+/**
+ * Illuminance of a star of magnitude 0 (Vega), in lux.
+ */
+const ZERO_MAGNITUDE: Float = 2.54e-6;
+
+/**
+ * A star at right ascension and declination (degrees, J2000), of apparent
+ * visual magnitude `magnitude` and B-V `color_index`.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Star {
+    pub right_ascension: Float,
+    pub declination: Float,
+    pub magnitude: Float,
+    pub color_index: Float,
+}
+
+impl Star {
+    /**
+     * Unit direction towards the star, in the scene frame.
+     */
+    pub fn direction(&self) -> [Float; 3] {
+        equatorial_direction(self.right_ascension, self.declination)
+    }
+
+    /**
+     * Illuminance (lux) of the star on a surface facing it.
+     */
+    pub fn illuminance(&self) -> Float {
+        ZERO_MAGNITUDE * (10.0 as Float).powf(-0.4 * self.magnitude)
+    }
+
+    /**
+     * Surface temperature in kelvin (Ballesteros 2012).
+     */
+    pub fn temperature(&self) -> Float {
+        let bv = self.color_index;
+        4600.0 * (1.0 / (0.92 * bv + 1.7) + 1.0 / (0.92 * bv + 0.62))
+    }
+
+    /**
+     * Linear RGB of the star, of luminance 1 (see `blackbody_color`).
+     */
+    pub fn color(&self) -> [Float; 3] {
+        blackbody_color(self.temperature())
+    }
+}
+
+/**
+ * Stars, and the Milky Way behind them: a glow of `milky_way` nits along
+ * the galactic plane, brighter towards the galactic center.
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct StarField {
+    pub stars: Vec<Star>,
+    pub milky_way: Float,
+}
+
+fn dot(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/**
+ * Galactic latitude and longitude (radians) of a unit direction.
+ */
+fn galactic(direction: &[Float; 3]) -> (Float, Float) {
+    let pole = equatorial_direction(192.85948, 27.12825);
+    let center = equatorial_direction(266.405, -28.93617);
+    // The center, exactly in the plane.
+    let along = dot(&center, &pole);
+    let center = [0, 1, 2].map(|i| center[i] - along * pole[i]);
+    let length = dot(&center, &center).sqrt();
+    let center = center.map(|c| c / length);
+    let side = [
+        pole[1] * center[2] - pole[2] * center[1],
+        pole[2] * center[0] - pole[0] * center[2],
+        pole[0] * center[1] - pole[1] * center[0],
+    ];
+    let latitude = dot(direction, &pole).clamp(-1.0, 1.0).asin();
+    let longitude = dot(direction, &side).atan2(dot(direction, &center));
+    (latitude, longitude)
+}
+
+impl StarField {
+    pub fn new(stars: Vec<Star>) -> StarField {
+        StarField {
+            stars,
+            milky_way: 0.0,
+        }
+    }
+
+    /**
+     * `count` made up stars down to `limiting_magnitude`, drawn from
+     * `seed`. As in the sky, every magnitude fainter holds about three
+     * times more stars, and they crowd towards the galactic plane.
+     */
+    pub fn procedural(
+        count: usize,
+        limiting_magnitude: Float,
+        seed: u64,
+    ) -> StarField {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let brightest: Float = -1.5;
+        let limiting_magnitude = limiting_magnitude.max(brightest);
+        let (low, high) = (
+            (10.0 as Float).powf(0.5 * brightest),
+            (10.0 as Float).powf(0.5 * limiting_magnitude),
+        );
+
+        let mut stars = Vec::with_capacity(count);
+        while stars.len() < count {
+            let z: Float = rng.gen_range(-1.0, 1.0);
+            let phi: Float = rng.gen_range(0.0, 2.0 * consts::PI);
+            let star = Star {
+                right_ascension: phi.to_degrees(),
+                declination: z.asin().to_degrees(),
+                magnitude: 2.0
+                    * (low + rng.gen::<Float>() * (high - low)).log10(),
+                color_index: rng.gen_range(-0.3, 1.7) / 2.0
+                    + rng.gen_range(-0.3, 1.7) / 2.0,
+            };
+            let (latitude, _) = galactic(&star.direction());
+            let crowding = 0.25 + 0.75 * (-(latitude / 0.25).powi(2)).exp();
+            if rng.gen::<Float>() < crowding {
+                stars.push(star);
+            }
+        }
+        StarField {
+            stars,
+            milky_way: 0.0,
+        }
+    }
+
+    /**
+     * Stars of a catalog in the CSV layout of the HYG database: a header
+     * naming the columns, among them `ra` (in hours), `dec` (degrees),
+     * `mag` and `ci` (B-V, white when blank). The Sun, if listed, is left
+     * out.
+     */
+    pub fn parse_catalog(text: &str) -> Result<StarField, String> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or_else(|| "empty star catalog".to_string())?
+            .split(',')
+            .map(|name| name.trim().trim_matches('"'))
+            .collect();
+        let column = |name: &str| {
+            header.iter().position(|c| *c == name).ok_or_else(|| {
+                format!("star catalog without a {} column", name)
+            })
+        };
+        let (ra, dec, mag) = (column("ra")?, column("dec")?, column("mag")?);
+        let ci = column("ci").ok();
+
+        let mut stars = vec![];
+        for (number, line) in lines.enumerate() {
+            let fields: Vec<&str> = line
+                .split(',')
+                .map(|f| f.trim().trim_matches('"'))
+                .collect();
+            let value = |index: usize| -> Result<Float, String> {
+                fields
+                    .get(index)
+                    .and_then(|field| field.parse::<Float>().ok())
+                    .ok_or_else(|| {
+                        format!("invalid star on line {}: {}", number + 2, line)
+                    })
+            };
+            let star = Star {
+                right_ascension: value(ra)? * 15.0,
+                declination: value(dec)?,
+                magnitude: value(mag)?,
+                color_index: ci.and_then(|ci| value(ci).ok()).unwrap_or(0.65),
+            };
+            if star.magnitude > -20.0 {
+                stars.push(star);
+            }
+        }
+        Ok(StarField::new(stars))
+    }
+
+    /**
+     * Radiance of the Milky Way along a unit direction.
+     */
+    pub fn milky_way_radiance(&self, direction: &[Float; 3]) -> [Float; 3] {
+        if self.milky_way <= 0.0 {
+            return [0.0, 0.0, 0.0];
+        }
+        let (latitude, longitude) = galactic(direction);
+        let band = (-(latitude / 0.15).powi(2)).exp()
+            * (0.4 + 0.6 * (-(longitude / 0.8).powi(2)).exp());
+        [1.0, 0.95, 0.85].map(|c| c * self.milky_way * band)
+    }
+
+    /**
+     * The sky as an environment, baked into a map `width` texels around
+     * (and half as high). Each star lights the texel it falls in, keeping
+     * its illuminance, and the map is looked up without filtering so the
+     * stars stay sharp points.
+     */
+    pub fn environment(&self, width: usize) -> Environment {
+        let width = width.max(2);
+        let height = width / 2;
+        let latitude = |row: usize| {
+            (0.5 - (row as Float + 0.5) / height as Float) * consts::PI
+        };
+
+        let mut texels = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for column in 0..width {
+                let longitude = ((column as Float + 0.5) / width as Float
+                    - 0.5)
+                    * 2.0
+                    * consts::PI;
+                let direction = [
+                    latitude(row).cos() * longitude.sin(),
+                    latitude(row).sin(),
+                    latitude(row).cos() * longitude.cos(),
+                ];
+                let [r, g, b] = self.milky_way_radiance(&direction);
+                texels.push([r, g, b, 1.0]);
+            }
+        }
+
+        for star in &self.stars {
+            let d = star.direction();
+            let u = 0.5 + d[0].atan2(d[2]) / (2.0 * consts::PI);
+            let v = 0.5 + d[1].clamp(-1.0, 1.0).asin() / consts::PI;
+            let column = ((u * width as Float) as usize).min(width - 1);
+            let row = (((1.0 - v) * height as Float) as usize).min(height - 1);
+            let solid_angle = 2.0 * consts::PI * consts::PI
+                / (width * height) as Float
+                * latitude(row).cos();
+            let radiance = star.illuminance() / solid_angle;
+            let color = star.color();
+            let texel = &mut texels[row * width + column];
+            for c in 0..3 {
+                texel[c] += color[c] * radiance;
+            }
+        }
+
+        let mut map = Texture::new(width, height, texels).unwrap();
+        map.filter = TextureFilter::Nearest;
+        Environment::new(map)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.milky_way >= 0.0 && self.milky_way.is_finite()) {
+            return Err(format!(
+                "invalid milky way radiance {}",
+                self.milky_way
+            ));
+        }
+        for star in &self.stars {
+            let valid = star.right_ascension.is_finite()
+                && (-90.0..=90.0).contains(&star.declination)
+                && star.magnitude.is_finite()
+                && star.color_index > -0.5
+                && star.color_index.is_finite();
+            if !valid {
+                return Err(format!("invalid star {:?}", star));
+            }
+        }
+        Ok(())
+    }
+}
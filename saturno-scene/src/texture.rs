@@ -0,0 +1,542 @@
+use crate::texture_cache::{TextureCache, TextureId, TileSource, TILE_SIZE};
+use saturno_math::common::Float;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// Image textures: linear RGBA texels looked up by (u, v) coordinates, with
+// a chain of prefiltered (mipmap) levels to sample detailed textures
+// without shimmering or moiré. The levels are either in memory or tiles
+// of a texture cache. UDIM sets spread the texture coordinates over
+// several images.
+
+/**
+ * What lies outside the [0, 1] range of the texture coordinates.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Wrap {
+    /**
+     * The texture tiles.
+     */
+    Repeat,
+    /**
+     * The texture tiles, every other tile flipped so the edges match.
+     */
+    Mirror,
+    /**
+     * The edge texels extend forever.
+     */
+    Clamp,
+}
+
+/**
+ * How a lookup combines the texels around the texture coordinates.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TextureFilter {
+    /**
+     * The closest texel of the closest level.
+     */
+    Nearest,
+    /**
+     * Interpolation of the 4 closest texels of the closest level.
+     */
+    Bilinear,
+    /**
+     * Interpolation of the bilinear lookups in the two closest levels.
+     */
+    Trilinear,
+}
+
+/**
+ * Which level of the mipmap chain a lookup reads.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "mode", rename_all = "snake_case"))]
+pub enum LevelSelection {
+    /**
+     * The level whose texels are as wide as the footprint of the lookup,
+     * so each texel covers about one pixel.
+     */
+    Footprint,
+    /**
+     * Always the same level, 0 being the full resolution (fractional
+     * levels blend two levels with trilinear filtering).
+     */
+    Fixed { level: Float },
+}
+
+/**
+ * A channel of the texels of a texture, for textures read as scalars.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    #[default]
+    Alpha,
+    /**
+     * The average of red, green and blue.
+     */
+    Average,
+}
+
+impl Channel {
+    pub fn of(&self, texel: [Float; 4]) -> Float {
+        match self {
+            Channel::Red => texel[0],
+            Channel::Green => texel[1],
+            Channel::Blue => texel[2],
+            Channel::Alpha => texel[3],
+            Channel::Average => (texel[0] + texel[1] + texel[2]) / 3.0,
+        }
+    }
+}
+
+/**
+ * Anything materials look colors up in, by texture coordinates.
+ */
+pub trait TextureSampler: Send + Sync {
+    /**
+     * Filtered color at the texture coordinates `uv` (v pointing up), for
+     * a lookup covering `footprint` (a width in texture coordinates).
+     */
+    fn sample(&self, uv: [Float; 2], footprint: Float) -> [Float; 4];
+
+    /**
+     * Identifies the texture in the name of the materials using it.
+     */
+    fn name(&self) -> String;
+
+    /**
+     * Bytes of the texels held in memory, those of a texture cache only
+     * counting in the cache.
+     */
+    fn memory(&self) -> usize {
+        0
+    }
+}
+
+/**
+ * One level of the mipmap chain, texels in rows from the top.
+ */
+#[derive(Clone, Debug, PartialEq)]
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<[Float; 4]>,
+}
+
+impl Level {
+    /**
+     * Half the size (rounded up), each texel the average of the (up to)
+     * 4 texels it covers.
+     */
+    fn downsample(&self) -> Level {
+        let width = self.width.div_ceil(2);
+        let height = self.height.div_ceil(2);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 4];
+                let mut count = 0.0;
+                for sy in 2 * y..(2 * y + 2).min(self.height) {
+                    for sx in 2 * x..(2 * x + 2).min(self.width) {
+                        let texel = self.texels[sy * self.width + sx];
+                        for c in 0..4 {
+                            sum[c] += texel[c];
+                        }
+                        count += 1.0;
+                    }
+                }
+                texels.push(sum.map(|value| value / count));
+            }
+        }
+        Level {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+/**
+ * Where the texels of the levels are.
+ */
+#[derive(Clone)]
+enum Texels {
+    Resident(Vec<Level>),
+    Cached {
+        cache: Arc<TextureCache>,
+        id: TextureId,
+        sizes: Vec<(usize, usize)>,
+    },
+}
+
+#[derive(Clone)]
+pub struct Texture {
+    texels: Texels,
+    pub wrap: Wrap,
+    pub filter: TextureFilter,
+    pub lod: LevelSelection,
+}
+
+impl Texture {
+    /**
+     * A texture of `width` x `height` linear RGBA texels, in rows from
+     * the top, and its mipmap chain down to a single texel. Repeats, with
+     * trilinear filtering of the level matching the footprint.
+     */
+    pub fn new(
+        width: usize,
+        height: usize,
+        texels: Vec<[Float; 4]>,
+    ) -> Result<Texture, String> {
+        if width == 0 || height == 0 {
+            return Err(format!("empty texture ({}x{})", width, height));
+        }
+        if texels.len() != width * height {
+            return Err(format!(
+                "{} texels for a {}x{} texture",
+                texels.len(),
+                width,
+                height
+            ));
+        }
+
+        let mut levels = vec![Level {
+            width,
+            height,
+            texels,
+        }];
+        while let Some(last) = levels.last() {
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            levels.push(last.downsample());
+        }
+
+        Ok(Texture::with_texels(Texels::Resident(levels)))
+    }
+
+    /**
+     * A texture whose tiles are loaded in `cache` when first looked up,
+     * from `source`.
+     */
+    pub fn cached(
+        cache: Arc<TextureCache>,
+        source: Arc<dyn TileSource>,
+    ) -> Result<Texture, String> {
+        let sizes = source.level_sizes();
+        match sizes.first() {
+            Some(&(width, height)) if width > 0 && height > 0 => {}
+            _ => return Err("texture without levels".to_string()),
+        }
+        let id = cache.register(source);
+        Ok(Texture::with_texels(Texels::Cached { cache, id, sizes }))
+    }
+
+    fn with_texels(texels: Texels) -> Texture {
+        Texture {
+            texels,
+            wrap: Wrap::Repeat,
+            filter: TextureFilter::Trilinear,
+            lod: LevelSelection::Footprint,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.level_size(0).0
+    }
+
+    pub fn height(&self) -> usize {
+        self.level_size(0).1
+    }
+
+    /**
+     * Number of levels of the mipmap chain.
+     */
+    pub fn levels(&self) -> usize {
+        match &self.texels {
+            Texels::Resident(levels) => levels.len(),
+            Texels::Cached { sizes, .. } => sizes.len(),
+        }
+    }
+
+    /**
+     * Width and height of a level.
+     */
+    pub fn level_size(&self, level: usize) -> (usize, usize) {
+        let level = level.min(self.levels() - 1);
+        match &self.texels {
+            Texels::Resident(levels) => {
+                (levels[level].width, levels[level].height)
+            }
+            Texels::Cached { sizes, .. } => sizes[level],
+        }
+    }
+
+    /**
+     * Texel (x, y) of a level, wrapped into it.
+     */
+    pub fn texel(&self, level: usize, x: i64, y: i64) -> [Float; 4] {
+        let level = level.min(self.levels() - 1);
+        let (width, height) = self.level_size(level);
+        let x = self.wrap_index(x, width);
+        let y = self.wrap_index(y, height);
+        match &self.texels {
+            Texels::Resident(levels) => levels[level].texels[y * width + x],
+            Texels::Cached { cache, id, .. } => {
+                let tile = cache.tile(*id, level, x / TILE_SIZE, y / TILE_SIZE);
+                tile.texels[(y % TILE_SIZE) * tile.width + x % TILE_SIZE]
+            }
+        }
+    }
+
+    /**
+     * The texels of a tile of a level (see `TILE_SIZE`), in rows from the
+     * top, e.g. to write the texture tiled.
+     */
+    pub fn tile(
+        &self,
+        level: usize,
+        tile_x: usize,
+        tile_y: usize,
+    ) -> Vec<[Float; 4]> {
+        let (width, height) = self.level_size(level);
+        let (x0, y0) = (tile_x * TILE_SIZE, tile_y * TILE_SIZE);
+        let mut texels = vec![];
+        for y in y0..(y0 + TILE_SIZE).min(height) {
+            for x in x0..(x0 + TILE_SIZE).min(width) {
+                texels.push(self.texel(level, x as i64, y as i64));
+            }
+        }
+        texels
+    }
+
+    fn wrap_index(&self, i: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self.wrap {
+            Wrap::Repeat => i.rem_euclid(size),
+            Wrap::Mirror => {
+                let i = i.rem_euclid(2 * size);
+                if i < size {
+                    i
+                } else {
+                    2 * size - 1 - i
+                }
+            }
+            Wrap::Clamp => i.clamp(0, size - 1),
+        };
+        wrapped as usize
+    }
+
+    /**
+     * Level of detail of a lookup covering `footprint` (a width in
+     * texture coordinates), 0 being the full resolution.
+     */
+    pub fn level_of_detail(&self, footprint: Float) -> Float {
+        let max_level = (self.levels() - 1) as Float;
+        let level = match self.lod {
+            LevelSelection::Footprint => {
+                let size = self.width().max(self.height()) as Float;
+                (footprint * size).log2()
+            }
+            LevelSelection::Fixed { level } => level,
+        };
+        if level.is_nan() {
+            0.0
+        } else {
+            level.clamp(0.0, max_level)
+        }
+    }
+
+    /**
+     * Filtered color at the texture coordinates `uv` (v pointing up, from
+     * the bottom row), for a lookup covering `footprint`.
+     */
+    pub fn sample(&self, uv: [Float; 2], footprint: Float) -> [Float; 4] {
+        let lod = self.level_of_detail(footprint);
+        match self.filter {
+            TextureFilter::Nearest => self.nearest(lod.round() as usize, uv),
+            TextureFilter::Bilinear => self.bilinear(lod.round() as usize, uv),
+            TextureFilter::Trilinear => {
+                let (low, t) = (lod.floor(), lod.fract());
+                let fine = self.bilinear(low as usize, uv);
+                if t == 0.0 {
+                    return fine;
+                }
+                let coarse = self.bilinear(low as usize + 1, uv);
+                let mut color = [0.0; 4];
+                for c in 0..4 {
+                    color[c] = fine[c] * (1.0 - t) + coarse[c] * t;
+                }
+                color
+            }
+        }
+    }
+
+    fn nearest(&self, level: usize, uv: [Float; 2]) -> [Float; 4] {
+        let (x, y) = self.texel_coordinates(level, uv);
+        self.texel(level, x.floor() as i64, y.floor() as i64)
+    }
+
+    fn bilinear(&self, level: usize, uv: [Float; 2]) -> [Float; 4] {
+        // Texel centers are at half integers.
+        let (x, y) = self.texel_coordinates(level, uv);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let mut color = [0.0; 4];
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - tx) * (1.0 - ty)),
+            (1, 0, tx * (1.0 - ty)),
+            (0, 1, (1.0 - tx) * ty),
+            (1, 1, tx * ty),
+        ] {
+            let texel = self.texel(level, x0 + dx, y0 + dy);
+            for c in 0..4 {
+                color[c] += texel[c] * weight;
+            }
+        }
+        color
+    }
+
+    fn texel_coordinates(
+        &self,
+        level: usize,
+        uv: [Float; 2],
+    ) -> (Float, Float) {
+        let (width, height) = self.level_size(level);
+        (uv[0] * width as Float, (1.0 - uv[1]) * height as Float)
+    }
+}
+
+impl TextureSampler for Texture {
+    fn sample(&self, uv: [Float; 2], footprint: Float) -> [Float; 4] {
+        Texture::sample(self, uv, footprint)
+    }
+
+    fn name(&self) -> String {
+        format!("texture{}x{}", self.width(), self.height())
+    }
+
+    fn memory(&self) -> usize {
+        match &self.texels {
+            Texels::Resident(levels) => levels
+                .iter()
+                .map(|level| level.texels.len() * size_of::<[Float; 4]>())
+                .sum(),
+            Texels::Cached { .. } => 0,
+        }
+    }
+}
+
+/**
+ * First UDIM tile, covering the texture coordinates [0, 1] x [0, 1].
+ */
+pub const UDIM_FIRST: u32 = 1001;
+
+/**
+ * A UDIM texture set: one texture (tile) per unit square of the texture
+ * coordinates, numbered 1001 + u + 10 v from the square (u, v), u in [0,
+ * 10), as Mari, Substance and most texturing tools author multi-tile
+ * assets.
+ */
+#[derive(Clone, Default)]
+pub struct Udim {
+    tiles: BTreeMap<u32, Texture>,
+}
+
+impl Udim {
+    pub fn new() -> Udim {
+        Udim::default()
+    }
+
+    /**
+     * UDIM number of the unit square containing the texture coordinates,
+     * None outside the 10 columns of tiles.
+     */
+    pub fn number(uv: [Float; 2]) -> Option<u32> {
+        let (u, v) = (uv[0].floor(), uv[1].floor());
+        if !(0.0..10.0).contains(&u) || v < 0.0 || !v.is_finite() {
+            return None;
+        }
+        Some(UDIM_FIRST + u as u32 + 10 * v as u32)
+    }
+
+    /**
+     * Add (or replace) the tile `number`. Tiles clamp their texture
+     * coordinates, so lookups near their edges do not wrap around to the
+     * opposite edge.
+     */
+    pub fn insert(
+        &mut self,
+        number: u32,
+        mut texture: Texture,
+    ) -> Result<(), String> {
+        if number < UDIM_FIRST {
+            return Err(format!("invalid UDIM tile {}", number));
+        }
+        texture.wrap = Wrap::Clamp;
+        self.tiles.insert(number, texture);
+        Ok(())
+    }
+
+    pub fn get(&self, number: u32) -> Option<&Texture> {
+        self.tiles.get(&number)
+    }
+
+    /**
+     * Numbers of the tiles, in increasing order.
+     */
+    pub fn numbers(&self) -> impl Iterator<Item = u32> + '_ {
+        self.tiles.keys().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+}
+
+impl TextureSampler for Udim {
+    /**
+     * Lookup in the tile under `uv`, transparent black where there is no
+     * tile.
+     */
+    fn sample(&self, uv: [Float; 2], footprint: Float) -> [Float; 4] {
+        let tile = Udim::number(uv).and_then(|number| self.tiles.get(&number));
+        match tile {
+            Some(texture) => {
+                let local = [uv[0] - uv[0].floor(), uv[1] - uv[1].floor()];
+                texture.sample(local, footprint)
+            }
+            None => [0.0; 4],
+        }
+    }
+
+    fn name(&self) -> String {
+        let numbers: Vec<String> =
+            self.numbers().map(|number| number.to_string()).collect();
+        format!("udim({})", numbers.join(","))
+    }
+
+    fn memory(&self) -> usize {
+        self.tiles.values().map(TextureSampler::memory).sum()
+    }
+}
@@ -0,0 +1,229 @@
+use saturno_math::common::Float;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+// Tiles of the mipmap levels of textures, loaded on demand and kept under
+// a memory budget: scenes with many huge textures only hold the tiles the
+// rays actually look at, at the level of detail they look at.
+
+/**
+ * Width and height of the tiles, in texels (tiles on the right and bottom
+ * edges of a level may be smaller).
+ */
+pub const TILE_SIZE: usize = 64;
+
+/**
+ * Where the tiles of a texture come from, e.g. a tiled texture file.
+ */
+pub trait TileSource: Send + Sync {
+    /**
+     * Width and height of every level of the mipmap chain, from the full
+     * resolution down to 1x1.
+     */
+    fn level_sizes(&self) -> Vec<(usize, usize)>;
+
+    /**
+     * The texels of a tile, in rows from the top.
+     */
+    fn read_tile(
+        &self,
+        level: usize,
+        tile_x: usize,
+        tile_y: usize,
+    ) -> Result<Vec<[Float; 4]>, String>;
+}
+
+/**
+ * Identifies a texture registered in a cache.
+ */
+pub type TextureId = usize;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /**
+     * Tiles that could not be read, looked up as transparent black.
+     */
+    pub failures: u64,
+    pub resident_bytes: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct TileKey {
+    texture: TextureId,
+    level: usize,
+    tile_x: usize,
+    tile_y: usize,
+}
+
+/**
+ * A loaded tile, `width` texels wide.
+ */
+pub struct Tile {
+    pub width: usize,
+    pub texels: Vec<[Float; 4]>,
+}
+
+impl Tile {
+    fn bytes(&self) -> usize {
+        self.texels.len() * std::mem::size_of::<[Float; 4]>()
+    }
+}
+
+#[derive(Default)]
+struct CacheState {
+    sources: Vec<Arc<dyn TileSource>>,
+    tiles: HashMap<TileKey, (Arc<Tile>, u64)>,
+    /**
+     * The resident tiles by time of last use, least recent first.
+     */
+    recency: BTreeMap<u64, TileKey>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+/**
+ * Tiles shared by all the textures registered in it, the least recently
+ * used evicted once they take more than `capacity` bytes.
+ */
+pub struct TextureCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+impl TextureCache {
+    pub fn new(capacity: usize) -> TextureCache {
+        TextureCache {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /**
+     * Add a texture whose tiles are read from `source` when first looked
+     * up. Nothing is loaded yet.
+     */
+    pub fn register(&self, source: Arc<dyn TileSource>) -> TextureId {
+        let mut state = self.state.lock().unwrap();
+        state.sources.push(source);
+        state.sources.len() - 1
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.state.lock().unwrap().stats
+    }
+
+    /**
+     * A tile of a registered texture, read from its source if it is not
+     * resident. The tile stays valid after it is evicted.
+     */
+    pub fn tile(
+        &self,
+        texture: TextureId,
+        level: usize,
+        tile_x: usize,
+        tile_y: usize,
+    ) -> Arc<Tile> {
+        let key = TileKey {
+            texture,
+            level,
+            tile_x,
+            tile_y,
+        };
+
+        let source = {
+            let mut state = self.state.lock().unwrap();
+            state.clock += 1;
+            let clock = state.clock;
+            if let Some((tile, last_use)) = state.tiles.get_mut(&key) {
+                let tile = tile.clone();
+                let previous = std::mem::replace(last_use, clock);
+                state.recency.remove(&previous);
+                state.recency.insert(clock, key);
+                state.stats.hits += 1;
+                return tile;
+            }
+            state.stats.misses += 1;
+            state.sources[texture].clone()
+        };
+
+        // Read without holding the lock, other threads keep sampling
+        // (two threads may read the same tile, the last one is kept).
+        let tile = Arc::new(self.read(source.as_ref(), key));
+
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        if let Some((previous, last_use)) =
+            state.tiles.insert(key, (tile.clone(), clock))
+        {
+            state.recency.remove(&last_use);
+            state.stats.resident_bytes -= previous.bytes();
+        }
+        state.recency.insert(clock, key);
+        state.stats.resident_bytes += tile.bytes();
+
+        // Always keep the tile just read, however small the capacity.
+        while state.stats.resident_bytes > self.capacity
+            && state.recency.len() > 1
+        {
+            let (&oldest, &evicted) = state.recency.iter().next().unwrap();
+            state.recency.remove(&oldest);
+            if let Some((evicted, _)) = state.tiles.remove(&evicted) {
+                state.stats.resident_bytes -= evicted.bytes();
+                state.stats.evictions += 1;
+            }
+        }
+        tile
+    }
+
+    fn read(&self, source: &dyn TileSource, key: TileKey) -> Tile {
+        let sizes = source.level_sizes();
+        let (width, height) = sizes[key.level];
+        let tile_width = TILE_SIZE.min(width - key.tile_x * TILE_SIZE);
+        let tile_height = TILE_SIZE.min(height - key.tile_y * TILE_SIZE);
+
+        let texels = source
+            .read_tile(key.level, key.tile_x, key.tile_y)
+            .and_then(|texels| {
+                if texels.len() == tile_width * tile_height {
+                    Ok(texels)
+                } else {
+                    Err(format!(
+                        "{} texels for a {}x{} tile",
+                        texels.len(),
+                        tile_width,
+                        tile_height
+                    ))
+                }
+            });
+        match texels {
+            Ok(texels) => Tile {
+                width: tile_width,
+                texels,
+            },
+            Err(e) => {
+                warn!(
+                    texture = key.texture,
+                    level = key.level,
+                    tile_x = key.tile_x,
+                    tile_y = key.tile_y,
+                    "failed to read texture tile: {}",
+                    e
+                );
+                self.state.lock().unwrap().stats.failures += 1;
+                Tile {
+                    width: tile_width,
+                    texels: vec![[0.0; 4]; tile_width * tile_height],
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use saturno_math::common::Float;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Units of length. The renderer works in the units of the scene, whatever
+// they are, but the physically based quantities are not free of them: the
+// intensity of a light in candela lights a point 1 meter away with 1 lux,
+// and a lens focuses at a distance in meters. A scene tells its units (see
+// `SceneDescription::units`), meters by default, and what comes from
+// elsewhere is converted to them: the meshes modelled in millimeters or
+// centimeters (as CAD tools export OBJ and STL files), and the intensity
+// of the lights, to fall off with the square of the distance in meters.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Units {
+    Millimeters,
+    Centimeters,
+    #[default]
+    Meters,
+    Kilometers,
+    Inches,
+    Feet,
+}
+
+impl Units {
+    /**
+     * Length of one unit in meters.
+     */
+    pub fn meters(self) -> Float {
+        match self {
+            Units::Millimeters => 0.001,
+            Units::Centimeters => 0.01,
+            Units::Meters => 1.0,
+            Units::Kilometers => 1000.0,
+            Units::Inches => 0.0254,
+            Units::Feet => 0.3048,
+        }
+    }
+
+    /**
+     * Factor converting lengths in these units into lengths in `to`.
+     */
+    pub fn factor(self, to: Units) -> Float {
+        self.meters() / to.meters()
+    }
+
+    /**
+     * Parse the units from their name or symbol (mm, cm, m, km, in, ft).
+     */
+    pub fn parse(name: &str) -> Result<Units, String> {
+        match name {
+            "mm" | "millimeters" => Ok(Units::Millimeters),
+            "cm" | "centimeters" => Ok(Units::Centimeters),
+            "m" | "meters" => Ok(Units::Meters),
+            "km" | "kilometers" => Ok(Units::Kilometers),
+            "in" | "inches" => Ok(Units::Inches),
+            "ft" | "feet" => Ok(Units::Feet),
+            _ => Err(format!("unknown units {}", name)),
+        }
+    }
+}
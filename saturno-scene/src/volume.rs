@@ -0,0 +1,236 @@
+use crate::color::blackbody_color;
+use saturno_math::common::Float;
+use saturno_math::common::Ray;
+use saturno_math::common::Vec4;
+use saturno_math::random;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Glowing volumes: fire, explosions and nebulae, from the dense grids of
+// the simulation caches (a value per voxel, x varying fastest, then y,
+// then z). The voxels hold either a temperature, glowing as a blackbody
+// (its color, and brighter as the fourth power of the temperature, after
+// Stefan-Boltzmann), or the color of the light directly.
+//
+// The light of a volume is the integral of its emission along the rays
+// through its box, marched in steps of half a voxel from a random offset,
+// the grid interpolated trilinearly between the voxels. The volumes only
+// add light: they neither absorb nor scatter it (fill them with fog for
+// smoke).
+
+/**
+ * What the voxels of an `EmissiveVolume` hold.
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Emission {
+    /**
+     * Temperatures in kelvins, glowing as blackbodies.
+     */
+    Temperature(Vec<Float>),
+    /**
+     * Light emitted per unit of length, per channel.
+     */
+    Rgb(Vec<[Float; 3]>),
+}
+
+/**
+ * A grid of `resolution` voxels filling the box from `min` to `max`,
+ * glowing. The light per unit of length is `intensity` times the color
+ * of the voxels, or for temperatures `intensity` at 1000 K (in the color
+ * of the blackbody, and growing as the fourth power of the temperature).
+ */
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EmissiveVolume {
+    pub min: [Float; 3],
+    pub max: [Float; 3],
+    pub resolution: [usize; 3],
+    pub emission: Emission,
+    #[cfg_attr(feature = "serde", serde(default = "one"))]
+    pub intensity: Float,
+}
+
+#[cfg(feature = "serde")]
+fn one() -> Float {
+    1.0
+}
+
+/**
+ * Temperature of the blackbodies glowing `intensity` times over.
+ */
+const REFERENCE_TEMPERATURE: Float = 1000.0;
+
+impl EmissiveVolume {
+    pub fn new(
+        min: [Float; 3],
+        max: [Float; 3],
+        resolution: [usize; 3],
+        emission: Emission,
+    ) -> EmissiveVolume {
+        EmissiveVolume {
+            min,
+            max,
+            resolution,
+            emission,
+            intensity: 1.0,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let bounds = (0..3).all(|i| {
+            self.min[i].is_finite()
+                && self.max[i].is_finite()
+                && self.min[i] < self.max[i]
+        });
+        if !bounds {
+            return Err(format!(
+                "invalid volume box {:?} to {:?}",
+                self.min, self.max
+            ));
+        }
+        if self.resolution.contains(&0) {
+            return Err(format!(
+                "invalid volume resolution {:?}",
+                self.resolution
+            ));
+        }
+        let voxels = self.resolution.iter().product::<usize>();
+        let (count, valid) = match &self.emission {
+            Emission::Temperature(values) => (
+                values.len(),
+                values.iter().all(|t| *t >= 0.0 && t.is_finite()),
+            ),
+            Emission::Rgb(values) => (
+                values.len(),
+                values.iter().flatten().all(|c| *c >= 0.0 && c.is_finite()),
+            ),
+        };
+        if count != voxels {
+            return Err(format!(
+                "volume of {} voxels holds {} values",
+                voxels, count
+            ));
+        }
+        if !valid {
+            return Err("invalid volume emission".to_string());
+        }
+        if !(self.intensity >= 0.0 && self.intensity.is_finite()) {
+            return Err(format!("invalid volume intensity {}", self.intensity));
+        }
+        Ok(())
+    }
+
+    /**
+     * Light emitted by the voxel `index`, per unit of length.
+     */
+    fn voxel(&self, index: usize) -> [Float; 3] {
+        match &self.emission {
+            Emission::Temperature(values) => {
+                let temperature = values[index];
+                if temperature <= 0.0 {
+                    return [0.0; 3];
+                }
+                let brightness = (temperature / REFERENCE_TEMPERATURE).powi(4);
+                blackbody_color(temperature).map(|c| c * brightness)
+            }
+            Emission::Rgb(values) => values[index],
+        }
+    }
+
+    /**
+     * Light emitted at `point` (inside the box) per unit of length, the
+     * voxels interpolated trilinearly.
+     */
+    pub fn emission(&self, point: &[Float; 3]) -> [Float; 3] {
+        let [nx, ny, _] = self.resolution;
+        let mut lower = [0; 3];
+        let mut fraction = [0.0; 3];
+        for i in 0..3 {
+            let n = self.resolution[i];
+            let relative =
+                (point[i] - self.min[i]) / (self.max[i] - self.min[i]);
+            // The values are at the centers of the voxels.
+            let position =
+                (relative * n as Float - 0.5).clamp(0.0, (n - 1) as Float);
+            lower[i] = (position.floor() as usize).min(n.saturating_sub(2));
+            fraction[i] = position - lower[i] as Float;
+        }
+
+        let mut light = [0.0; 3];
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let mut weight = 1.0;
+            let mut voxel = [0; 3];
+            for i in 0..3 {
+                voxel[i] = (lower[i] + offset[i]).min(self.resolution[i] - 1);
+                weight *= if offset[i] == 1 {
+                    fraction[i]
+                } else {
+                    1.0 - fraction[i]
+                };
+            }
+            if weight > 0.0 {
+                let emitted =
+                    self.voxel(voxel[0] + nx * (voxel[1] + ny * voxel[2]));
+                for c in 0..3 {
+                    light[c] += weight * emitted[c];
+                }
+            }
+        }
+        light.map(|c| c * self.intensity)
+    }
+
+    /**
+     * The range of the parameters of `ray` within the box, clipped to
+     * [0, `t_max`]. None if the ray misses it.
+     */
+    fn span(&self, ray: &Ray, t_max: Float) -> Option<(Float, Float)> {
+        let (mut t_min, mut t_max) = (0.0 as Float, t_max);
+        for i in 0..3 {
+            let inverse = 1.0 / ray.direction[i];
+            let mut t0 = (self.min[i] - ray.origin[i]) * inverse;
+            let mut t1 = (self.max[i] - ray.origin[i]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN (a ray along a face) keeps the bounds.
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+
+    /**
+     * Light emitted towards the origin of `ray` along it, up to the
+     * parameter `t_max`.
+     */
+    pub fn radiance(&self, ray: &Ray, t_max: Float) -> [Float; 3] {
+        let Some((t_min, t_max)) = self.span(ray, t_max) else {
+            return [0.0; 3];
+        };
+        let length = Vec4::l2_norm(ray.direction.view());
+        let voxel = (0..3)
+            .map(|i| (self.max[i] - self.min[i]) / self.resolution[i] as Float)
+            .fold(Float::INFINITY, Float::min);
+        let distance = (t_max - t_min) * length;
+        let steps = (2.0 * distance / voxel).ceil().max(1.0);
+        let step = (t_max - t_min) / steps;
+
+        let mut light = [0.0; 3];
+        let mut t = t_min + step * random::gen_range(0.0, 1.0);
+        while t < t_max {
+            let point = ray.point_at_parameter(t);
+            let emitted = self.emission(&[point[0], point[1], point[2]]);
+            for c in 0..3 {
+                light[c] += emitted[c];
+            }
+            t += step;
+        }
+        light.map(|c| c * step * length)
+    }
+}
@@ -0,0 +1,382 @@
+use crate::actor::count_work;
+use crate::actor::Aabb;
+use crate::mesh::{Bytes, Node};
+use saturno_math::common::Float;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+// Compressed bounding volume hierarchies for large meshes: the binary
+// hierarchy collapsed into nodes of up to 8 children whose bounds are
+// quantized to 8 bits per coordinate on the grid of their parent (Ylitie,
+// Karras and Laine, Efficient Incoherent Ray Traversal on GPUs Through
+// Compressed Wide BVHs). A node takes 80 bytes for its 8 children where
+// the binary hierarchy takes 72 per child, several times less memory, and
+// a ray tests the children of a node together, from one cache line or two.
+//
+// The grid of a node starts at its min corner rounded down to f32, its
+// cells a power of two wide along each axis, so decoding is exact; the
+// bounds of the children are rounded outwards onto it, so they still
+// enclose their triangles (at worst they are a 255th of their parent
+// wider). The inner children of a node follow each other, as do the
+// triangles of its leaves, so a node only keeps where they start.
+
+/**
+ * Children per node, at most.
+ */
+pub const WIDTH: usize = 8;
+
+#[derive(Clone, Debug, Default)]
+struct WideNode {
+    /**
+     * Min corner of the grid of the children bounds.
+     */
+    origin: [f32; 3],
+    /**
+     * The cells of the grid are 2^exponent wide along each axis.
+     */
+    exponents: [i8; 3],
+    /**
+     * Bit i set if child i is an inner node.
+     */
+    inner: u8,
+    /**
+     * Triangles of the leaf children, 0 for the inner and unused ones.
+     */
+    counts: [u8; WIDTH],
+    /**
+     * The first inner child, the others after it in the order of the
+     * children.
+     */
+    first_child: u32,
+    /**
+     * The first triangle of the first leaf child, those of the others
+     * after it.
+     */
+    first_triangle: u32,
+    /**
+     * Bounds of the children on the grid, by axis.
+     */
+    min: [[u8; WIDTH]; 3],
+    max: [[u8; WIDTH]; 3],
+}
+
+impl WideNode {
+    fn scale(&self, axis: usize) -> Float {
+        (2.0 as Float).powi(self.exponents[axis] as i32)
+    }
+
+    fn bounds(&self, child: usize) -> Aabb {
+        let corner = |cells: &[[u8; WIDTH]; 3]| {
+            [0, 1, 2].map(|axis| {
+                self.origin[axis] as Float
+                    + cells[axis][child] as Float * self.scale(axis)
+            })
+        };
+        Aabb::new(corner(&self.min), corner(&self.max))
+    }
+}
+
+/**
+ * What a ray visits next: a node, or the triangles of a leaf.
+ */
+#[derive(Clone, Copy)]
+enum Entry {
+    Node(usize),
+    Triangles(usize, usize),
+}
+
+/**
+ * A hierarchy of quantized nodes of up to `WIDTH` children.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct WideBvh {
+    nodes: Vec<WideNode>,
+}
+
+impl WideBvh {
+    /**
+     * Collapse a binary hierarchy, whose root is `binary[0]`. Also gives
+     * the order of the triangles the leaves refer to: the i-th is
+     * `order[i]` of the binary hierarchy.
+     */
+    pub(crate) fn new(
+        binary: &[Node],
+    ) -> Result<(WideBvh, Vec<usize>), String> {
+        let mut nodes = vec![WideNode::default()];
+        let mut order = vec![];
+        let mut queue = VecDeque::from([(0, 0)]);
+        while let Some((index, source)) = queue.pop_front() {
+            let children = WideBvh::collapse(binary, source);
+            let mut node = WideNode {
+                first_child: nodes.len() as u32,
+                first_triangle: order.len() as u32,
+                ..WideNode::default()
+            };
+            for (i, &child) in children.iter().enumerate() {
+                match binary[child] {
+                    Node::Leaf { first, count, .. } => {
+                        node.counts[i] = count as u8;
+                        order.extend(first..first + count);
+                    }
+                    Node::Inner { .. } => {
+                        node.inner |= 1 << i;
+                        queue.push_back((nodes.len(), child));
+                        nodes.push(WideNode::default());
+                    }
+                }
+            }
+            let bounds: Vec<Aabb> = children
+                .iter()
+                .map(|&child| *binary[child].bounds())
+                .collect();
+            WideBvh::quantize(&mut node, binary[source].bounds(), &bounds)?;
+            nodes[index] = node;
+        }
+        Ok((WideBvh { nodes }, order))
+    }
+
+    /**
+     * The binary nodes below `source` that become the children of its
+     * wide node: its children, the largest inner one replaced by its own
+     * children until there are `WIDTH`. A leaf is its own child.
+     */
+    fn collapse(binary: &[Node], source: usize) -> Vec<usize> {
+        let mut children = match binary[source] {
+            Node::Leaf { .. } => return vec![source],
+            Node::Inner { left, right, .. } => vec![left, right],
+        };
+        while children.len() < WIDTH {
+            let area = |node: usize| binary[node].bounds().area();
+            let largest = (0..children.len())
+                .filter(|&i| matches!(binary[children[i]], Node::Inner { .. }))
+                .max_by(|&a, &b| {
+                    area(children[a]).total_cmp(&area(children[b]))
+                });
+            let Some(largest) = largest else {
+                break;
+            };
+            if let Node::Inner { left, right, .. } = binary[children[largest]] {
+                children[largest] = left;
+                children.push(right);
+            }
+        }
+        children
+    }
+
+    /**
+     * Fit the grid of `node` to `bounds`, and the bounds of its children
+     * onto it.
+     */
+    fn quantize(
+        node: &mut WideNode,
+        bounds: &Aabb,
+        children: &[Aabb],
+    ) -> Result<(), String> {
+        for axis in 0..3 {
+            // Float is f32 with the `f32` feature.
+            #[allow(clippy::unnecessary_cast)]
+            let mut origin = bounds.min[axis] as f32;
+            if origin as Float > bounds.min[axis] {
+                origin = origin.next_down();
+            }
+            if !origin.is_finite() {
+                return Err(format!(
+                    "bounds {:?} beyond the range of the compressed hierarchy",
+                    bounds
+                ));
+            }
+            let extent = bounds.max[axis] - origin as Float;
+            let mut exponent = if extent > 0.0 {
+                (extent / 255.0).log2().ceil().clamp(-128.0, 127.0) as i8
+            } else {
+                i8::MIN
+            };
+            let cells = |exponent: i8| (2.0 as Float).powi(exponent as i32);
+            while 255.0 * cells(exponent) < extent && exponent < i8::MAX {
+                exponent += 1;
+            }
+            node.origin[axis] = origin;
+            node.exponents[axis] = exponent;
+
+            // Outwards: the decoded bounds enclose the exact ones.
+            let scale = cells(exponent);
+            let decode = |cell: u8| origin as Float + cell as Float * scale;
+            for (i, child) in children.iter().enumerate() {
+                let cell = |value: Float, round: fn(Float) -> Float| {
+                    round((value - origin as Float) / scale).clamp(0.0, 255.0)
+                        as u8
+                };
+                let mut min = cell(child.min[axis], Float::floor);
+                while min > 0 && decode(min) > child.min[axis] {
+                    min -= 1;
+                }
+                let mut max = cell(child.max[axis], Float::ceil);
+                while max < 255 && decode(max) < child.max[axis] {
+                    max += 1;
+                }
+                if decode(max) < child.max[axis] {
+                    return Err(format!(
+                        "bounds {:?} beyond the range of the compressed hierarchy",
+                        bounds
+                    ));
+                }
+                node.min[axis][i] = min;
+                node.max[axis][i] = max;
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Bounds of the root.
+     */
+    pub fn bounds(&self) -> Aabb {
+        let root = &self.nodes[0];
+        let used = (0..WIDTH)
+            .filter(|&i| root.inner & (1 << i) != 0 || root.counts[i] > 0);
+        used.map(|i| root.bounds(i))
+            .reduce(|a, b| a.union(&b))
+            .unwrap()
+    }
+
+    /**
+     * Append the nodes to an encoded mesh, see `Mesh::encode`.
+     */
+    pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.nodes.len() as u64).to_le_bytes());
+        for node in &self.nodes {
+            for origin in node.origin {
+                out.extend_from_slice(&origin.to_le_bytes());
+            }
+            out.extend(node.exponents.map(|exponent| exponent as u8));
+            out.push(node.inner);
+            out.extend(node.counts);
+            out.extend_from_slice(&node.first_child.to_le_bytes());
+            out.extend_from_slice(&node.first_triangle.to_le_bytes());
+            for cells in node.min.iter().chain(&node.max) {
+                out.extend(cells);
+            }
+        }
+    }
+
+    /**
+     * The nodes of an encoded mesh of `triangles` triangles.
+     */
+    pub(crate) fn decode(
+        bytes: &mut Bytes,
+        triangles: usize,
+    ) -> Result<WideBvh, String> {
+        const NODE_BYTES: usize = 12 + 3 + 1 + WIDTH + 8 + 6 * WIDTH;
+        let count = bytes.count(NODE_BYTES)?;
+        let mut nodes = vec![];
+        for index in 0..count {
+            let mut node = WideNode::default();
+            for origin in &mut node.origin {
+                *origin = bytes.f32()?;
+            }
+            for exponent in &mut node.exponents {
+                *exponent = bytes.u8()? as i8;
+            }
+            node.inner = bytes.u8()?;
+            for count in &mut node.counts {
+                *count = bytes.u8()?;
+            }
+            node.first_child = bytes.u32()?;
+            node.first_triangle = bytes.u32()?;
+            for cells in node.min.iter_mut().chain(&mut node.max) {
+                for cell in cells {
+                    *cell = bytes.u8()?;
+                }
+            }
+
+            // Children after their parent, so there are no cycles.
+            let children = node.inner.count_ones() as usize;
+            let leaves: usize = node.counts.iter().map(|&c| c as usize).sum();
+            let first_child = node.first_child as usize;
+            let valid = node.origin.iter().all(|o| o.is_finite())
+                && (children == 0
+                    || (index < first_child
+                        && first_child + children <= count))
+                && node.first_triangle as usize + leaves <= triangles;
+            if !valid {
+                return Err("invalid compressed hierarchy".to_string());
+            }
+            nodes.push(node);
+        }
+        if nodes.is_empty() {
+            return Err("invalid compressed hierarchy".to_string());
+        }
+        Ok(WideBvh { nodes })
+    }
+
+    /**
+     * Bytes the nodes take.
+     */
+    pub fn bytes(&self) -> usize {
+        self.nodes.len() * std::mem::size_of::<WideNode>()
+    }
+
+    /**
+     * Visit the leaves a ray crosses between `t_min` and `t_max`, the
+     * nearest first: `leaf` tests the triangles of a leaf and gives the
+     * distance of the closest hit so far, beyond which the rest of the
+     * hierarchy is skipped.
+     */
+    pub(crate) fn traverse(
+        &self,
+        origin: &[Float; 3],
+        inverse_direction: &[Float; 3],
+        t_min: Float,
+        t_max: Float,
+        mut leaf: impl FnMut(Range<usize>, Float) -> Float,
+    ) {
+        let mut closest = t_max;
+        let mut visited = 0;
+        let mut stack = vec![(t_min, Entry::Node(0))];
+        while let Some((near, entry)) = stack.pop() {
+            if near > closest {
+                continue;
+            }
+            let node = match entry {
+                Entry::Triangles(first, count) => {
+                    closest = leaf(first..first + count, closest);
+                    continue;
+                }
+                Entry::Node(node) => &self.nodes[node],
+            };
+            visited += 1;
+
+            let mut hits = [(0.0, Entry::Node(0)); WIDTH];
+            let mut count = 0;
+            let mut child_node = node.first_child as usize;
+            let mut triangle = node.first_triangle as usize;
+            for i in 0..WIDTH {
+                let entry = if node.inner & (1 << i) != 0 {
+                    child_node += 1;
+                    Entry::Node(child_node - 1)
+                } else if node.counts[i] > 0 {
+                    let triangles = node.counts[i] as usize;
+                    triangle += triangles;
+                    Entry::Triangles(triangle - triangles, triangles)
+                } else {
+                    continue;
+                };
+                if let Some(near) = node.bounds(i).entry(
+                    origin,
+                    inverse_direction,
+                    t_min,
+                    closest,
+                ) {
+                    hits[count] = (near, entry);
+                    count += 1;
+                }
+            }
+
+            // The nearest child on top of the stack.
+            let hits = &mut hits[..count];
+            hits.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+            stack.extend_from_slice(hits);
+        }
+        count_work(visited, 0);
+    }
+}
@@ -0,0 +1,17 @@
+// The Saturno ray tracer. Brings the workspace crates together under one
+// name; most programs only need `saturno::prelude::*`:
+//
+//     use saturno::prelude::*;
+//
+//     let camera = scenes::two_spheres_camera(200, 100);
+//     let canvas = Canvas::new(scenes::two_spheres_normals(), camera);
+//     let settings = RenderSettings::builder().size(200, 100).build()?;
+//     let image = Renderer::new(canvas, settings).render()?;
+
+#[cfg(feature = "io")]
+pub use saturno_io as io;
+pub use saturno_math as math;
+pub use saturno_render as render;
+pub use saturno_scene as scene;
+
+pub mod prelude;
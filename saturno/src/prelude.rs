@@ -0,0 +1,104 @@
+// The types needed to build and render a scene.
+
+pub use saturno_math::common::Ray;
+pub use saturno_math::common::Vec4;
+pub use saturno_render::canvas::Canvas;
+pub use saturno_render::canvas::Renderer;
+pub use saturno_render::display::Lut3d;
+pub use saturno_render::error::SaturnoError;
+pub use saturno_render::settings::Bloom;
+pub use saturno_render::settings::CameraExposure;
+pub use saturno_render::settings::Caustics;
+pub use saturno_render::settings::Crop;
+pub use saturno_render::settings::Filter;
+pub use saturno_render::settings::GradientDomain;
+pub use saturno_render::settings::Imperfections;
+pub use saturno_render::settings::IrradianceCaching;
+pub use saturno_render::settings::LensFlare;
+pub use saturno_render::settings::LightHierarchy;
+pub use saturno_render::settings::Metropolis;
+pub use saturno_render::settings::OutputTransform;
+pub use saturno_render::settings::PathGuiding;
+pub use saturno_render::settings::PhotonMapping;
+pub use saturno_render::settings::RenderSettings;
+pub use saturno_render::settings::ReservoirSampling;
+pub use saturno_render::settings::ThreadPriority;
+pub use saturno_render::settings::TileOrder;
+pub use saturno_render::settings::Tonemapper;
+pub use saturno_render::settings::Toon;
+pub use saturno_render::settings::WhiteBalance;
+pub use saturno_render::stats::SceneStats;
+pub use saturno_render::HdrImage;
+pub use saturno_render::Image;
+pub use saturno_scene::actor::Hittable;
+pub use saturno_scene::actor::RayKind;
+pub use saturno_scene::actor::RayTraceable;
+pub use saturno_scene::actor::Sphere;
+pub use saturno_scene::actor::Visibility;
+pub use saturno_scene::actor::WithVisibility;
+pub use saturno_scene::atmosphere::Atmosphere;
+pub use saturno_scene::black_hole::AccretionDisk;
+pub use saturno_scene::black_hole::BlackHole;
+pub use saturno_scene::camera::Camera;
+pub use saturno_scene::clip::Cap;
+pub use saturno_scene::clip::ClipPlane;
+pub use saturno_scene::clip::Clipped;
+pub use saturno_scene::color::ColorSpace;
+pub use saturno_scene::environment::Environment;
+pub use saturno_scene::environment::Portal;
+pub use saturno_scene::fog::Fog;
+pub use saturno_scene::fractal::Fractal;
+pub use saturno_scene::fractal::FractalKind;
+pub use saturno_scene::fractal::Palette;
+pub use saturno_scene::instance::Instance;
+pub use saturno_scene::instance::Instances;
+pub use saturno_scene::instance::Prototype;
+pub use saturno_scene::instance::Switch;
+pub use saturno_scene::instance::Transform;
+pub use saturno_scene::library::MaterialLibrary;
+pub use saturno_scene::light::Light;
+pub use saturno_scene::light::Spot;
+pub use saturno_scene::light::Sun;
+pub use saturno_scene::material::AccretionGlow;
+pub use saturno_scene::material::AnisotropicMetal;
+pub use saturno_scene::material::Clearcoat;
+pub use saturno_scene::material::Cutout;
+pub use saturno_scene::material::Dielectric;
+pub use saturno_scene::material::DiffuseLight;
+pub use saturno_scene::material::Lambertian;
+pub use saturno_scene::material::Mask;
+pub use saturno_scene::material::Metal;
+pub use saturno_scene::material::Mix;
+pub use saturno_scene::material::Photosphere;
+pub use saturno_scene::material::Primary;
+pub use saturno_scene::material::RingParticles;
+pub use saturno_scene::material::Scattering;
+pub use saturno_scene::material::Shading;
+pub use saturno_scene::material::ShadowCatcher;
+pub use saturno_scene::material::Subsurface;
+pub use saturno_scene::material::ThinFilm;
+pub use saturno_scene::mesh::Displacement;
+pub use saturno_scene::mesh::Mesh;
+pub use saturno_scene::metaballs::Charge;
+pub use saturno_scene::metaballs::Metaballs;
+pub use saturno_scene::motion::MotionKey;
+pub use saturno_scene::motion::Moving;
+pub use saturno_scene::ring::Ring;
+pub use saturno_scene::scatter::ScatterRegion;
+pub use saturno_scene::scatter::Scattered;
+pub use saturno_scene::scatter::Scatterer;
+pub use saturno_scene::scenes;
+pub use saturno_scene::sky::SunAndSky;
+pub use saturno_scene::stars::Star;
+pub use saturno_scene::stars::StarField;
+pub use saturno_scene::texture::Channel;
+pub use saturno_scene::texture::LevelSelection;
+pub use saturno_scene::texture::Texture;
+pub use saturno_scene::texture::TextureFilter;
+pub use saturno_scene::texture::TextureSampler;
+pub use saturno_scene::texture::Udim;
+pub use saturno_scene::texture::Wrap;
+pub use saturno_scene::texture_cache::TextureCache;
+pub use saturno_scene::units::Units;
+pub use saturno_scene::volume::Emission;
+pub use saturno_scene::volume::EmissiveVolume;